@@ -0,0 +1,112 @@
+/*
+ * C API of jdwp-client, see src/ffi.rs
+ *
+ * Build the library with
+ *     cargo rustc --release --features ffi --crate-type cdylib
+ * or --crate-type staticlib. Functions returning a status return 0 on success and -1 on
+ * failure, with the message of the failure available from jdwp_last_error().
+ */
+
+#ifndef JDWP_CLIENT_H
+#define JDWP_CLIENT_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A connection to a VM, created by jdwp_connect() and destroyed by jdwp_free() */
+typedef struct JdwpSession JdwpSession;
+
+/* An event received by jdwp_poll_event(). Ids the event doesn't have are 0. */
+typedef struct JdwpEventData {
+    /* The EventKind constant of the JDWP specification */
+    uint8_t kind;
+    int32_t request_id;
+    /* Breakpoint returned by jdwp_set_breakpoint() which was hit */
+    int32_t breakpoint;
+    uint64_t thread_id;
+    uint64_t class_id;
+    uint64_t method_id;
+    uint64_t index;
+} JdwpEventData;
+
+/*
+ * A value read by jdwp_read_value(). Integral values and characters are in int_value,
+ * floating point values in float_value and objects in object_id.
+ */
+typedef struct JdwpValueData {
+    /* The JDWP tag of the value, e.g. 'I' */
+    uint8_t tag;
+    int64_t int_value;
+    double float_value;
+    uint64_t object_id;
+} JdwpValueData;
+
+/* Connects to a VM listening on address, e.g. "localhost:5005". Returns NULL on failure. */
+JdwpSession *jdwp_connect(const char *address);
+
+/*
+ * Sets a breakpoint on a line of a class given by its name, e.g. "com.example.Main", which
+ * suspends the thread hitting it. Its number is written to breakpoint.
+ */
+int32_t jdwp_set_breakpoint(JdwpSession *session, const char *class_name, int32_t line,
+                            int32_t *breakpoint);
+
+/*
+ * Waits up to timeout_ms milliseconds for an event, forever if negative. Returns 1 and writes
+ * the event to event if one was received, 0 on timeout and -1 on failure, including a closed
+ * connection.
+ */
+int32_t jdwp_poll_event(JdwpSession *session, int32_t timeout_ms, JdwpEventData *event);
+
+/*
+ * Reads the local variable in slot of the frame at frame_index of a suspended thread, the
+ * current frame being 0. tag is the JDWP tag of the variable's type, e.g. 'I'.
+ */
+int32_t jdwp_read_value(JdwpSession *session, uint64_t thread_id, int32_t frame_index,
+                        int32_t slot, uint8_t tag, JdwpValueData *value);
+
+/*
+ * Writes the ids of up to capacity threads of the VM to threads and their total number to
+ * count, so a capacity of 0 asks for the number only.
+ */
+int32_t jdwp_all_threads(JdwpSession *session, uint64_t *threads, size_t capacity,
+                         size_t *count);
+
+/*
+ * Writes the name of a thread to name as a NUL-terminated string, truncated to fit into
+ * capacity bytes, and its length without truncation to length.
+ */
+int32_t jdwp_thread_name(JdwpSession *session, uint64_t thread_id, char *name, size_t capacity,
+                         size_t *length);
+
+/* Writes the id of a loaded class given by its name, e.g. "com.example.Main", to class_id. */
+int32_t jdwp_find_class(JdwpSession *session, const char *class_name, uint64_t *class_id);
+
+/*
+ * Calls a static method without arguments in a suspended thread, e.g. one stopped at a
+ * breakpoint, and writes its return value to value.
+ */
+int32_t jdwp_invoke_static(JdwpSession *session, uint64_t thread_id, const char *class_name,
+                           const char *method_name, JdwpValueData *value);
+
+/* Resumes a thread, e.g. one suspended by a breakpoint. */
+int32_t jdwp_resume_thread(JdwpSession *session, uint64_t thread_id);
+
+/* Closes the connection and frees the session. NULL is ignored. */
+void jdwp_free(JdwpSession *session);
+
+/*
+ * Message of the last failure on the calling thread, NULL if nothing failed yet. The string
+ * stays valid until the next failing call on the thread.
+ */
+const char *jdwp_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* JDWP_CLIENT_H */
@@ -0,0 +1,232 @@
+//! The JDWP wire format at the packet level, with nothing but `core` and `alloc`
+//!
+//! Packets are split and framed here; what's inside them is left to the caller. `jdwp-client`
+//! builds its commands, replies and events on top of this crate.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Length of command and reply headers, which only differ after the flags byte
+pub const HEADER_LENGTH: usize = 11;
+
+/// Set in the flags of reply packets
+pub const FLAG_REPLY: u8 = 0x80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The length in a header is shorter than the header itself
+    InvalidLength(u32),
+    /// The data doesn't fit into a packet
+    TooLong(usize),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidLength(length) => write!(f, "Invalid packet length: {}", length),
+            Error::TooLong(length) => write!(f, "Packet data too long: {} bytes", length),
+        }
+    }
+}
+impl core::error::Error for Error {}
+
+/// What follows the flags byte of a header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderKind {
+    Command { command_set: u8, command: u8 },
+    Reply { error_code: u16 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// Length of the whole packet, including the header
+    pub length: u32,
+    pub id: u32,
+    pub flags: u8,
+    pub kind: HeaderKind,
+}
+impl Header {
+    pub fn decode(bytes: &[u8; HEADER_LENGTH]) -> Result<Header, Error> {
+        let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        if (length as usize) < HEADER_LENGTH {
+            return Err(Error::InvalidLength(length));
+        }
+        let flags = bytes[8];
+        let kind = if flags & FLAG_REPLY != 0 {
+            HeaderKind::Reply {
+                error_code: u16::from_be_bytes([bytes[9], bytes[10]]),
+            }
+        } else {
+            HeaderKind::Command {
+                command_set: bytes[9],
+                command: bytes[10],
+            }
+        };
+        Ok(Header {
+            length,
+            id: u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            flags,
+            kind,
+        })
+    }
+
+    pub fn encode(&self) -> [u8; HEADER_LENGTH] {
+        let mut bytes = [0u8; HEADER_LENGTH];
+        bytes[0..4].copy_from_slice(&self.length.to_be_bytes());
+        bytes[4..8].copy_from_slice(&self.id.to_be_bytes());
+        bytes[8] = self.flags;
+        match self.kind {
+            HeaderKind::Command {
+                command_set,
+                command,
+            } => {
+                bytes[9] = command_set;
+                bytes[10] = command;
+            }
+            HeaderKind::Reply { error_code } => {
+                bytes[9..11].copy_from_slice(&error_code.to_be_bytes())
+            }
+        }
+        bytes
+    }
+
+    /// Length of the data following the header
+    pub fn data_length(&self) -> usize {
+        self.length as usize - HEADER_LENGTH
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    pub header: Header,
+    pub data: Vec<u8>,
+}
+
+pub fn encode_command(
+    id: u32,
+    command_set: u8,
+    command: u8,
+    data: &[u8],
+) -> Result<Vec<u8>, Error> {
+    encode(
+        id,
+        0,
+        HeaderKind::Command {
+            command_set,
+            command,
+        },
+        data,
+    )
+}
+
+pub fn encode_reply(id: u32, error_code: u16, data: &[u8]) -> Result<Vec<u8>, Error> {
+    encode(id, FLAG_REPLY, HeaderKind::Reply { error_code }, data)
+}
+
+fn encode(id: u32, flags: u8, kind: HeaderKind, data: &[u8]) -> Result<Vec<u8>, Error> {
+    let length =
+        u32::try_from(HEADER_LENGTH + data.len()).map_err(|_| Error::TooLong(data.len()))?;
+    let header = Header {
+        length,
+        id,
+        flags,
+        kind,
+    };
+    let mut bytes = Vec::with_capacity(length as usize);
+    bytes.extend_from_slice(&header.encode());
+    bytes.extend_from_slice(data);
+    Ok(bytes)
+}
+
+/// Splits a byte stream into packets, whatever chunks it arrives in
+#[derive(Debug, Default)]
+pub struct PacketDecoder {
+    buffer: Vec<u8>,
+}
+impl PacketDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends received bytes
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Takes the next complete packet, None if more bytes are needed. An invalid length
+    /// leaves the stream out of sync, so the decoder shouldn't be used after an error.
+    pub fn next_packet(&mut self) -> Result<Option<Packet>, Error> {
+        let Some(header) = self.buffer.first_chunk::<HEADER_LENGTH>() else {
+            return Ok(None);
+        };
+        let header = Header::decode(header)?;
+        let length = header.length as usize;
+        if self.buffer.len() < length {
+            return Ok(None);
+        }
+        let data = self.buffer[HEADER_LENGTH..length].to_vec();
+        self.buffer.drain(..length);
+        Ok(Some(Packet { header, data }))
+    }
+
+    /// Number of received bytes not part of a complete packet yet
+    pub fn buffered_len(&self) -> usize {
+        self.buffer.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn test_header_round_trip() {
+        let bytes = encode_command(3, 1, 7, &[0xA, 0xB]).unwrap();
+        let header = Header::decode(bytes.first_chunk().unwrap()).unwrap();
+        assert_eq!(
+            header,
+            Header {
+                length: 13,
+                id: 3,
+                flags: 0,
+                kind: HeaderKind::Command {
+                    command_set: 1,
+                    command: 7
+                },
+            }
+        );
+        assert_eq!(header.data_length(), 2);
+        assert_eq!(&header.encode(), bytes.first_chunk().unwrap());
+    }
+
+    #[test]
+    fn test_decode_split_packets() {
+        let mut bytes = encode_reply(7, 0, &[0x1, 0x2]).unwrap();
+        bytes.extend(encode_command(8, 64, 100, &[0x3]).unwrap());
+
+        let mut decoder = PacketDecoder::new();
+        decoder.push(&bytes[..5]);
+        assert_eq!(decoder.next_packet(), Ok(None));
+        decoder.push(&bytes[5..20]);
+        let reply = decoder.next_packet().unwrap().unwrap();
+        assert_eq!(reply.header.kind, HeaderKind::Reply { error_code: 0 });
+        assert_eq!(reply.data, vec![0x1, 0x2]);
+        assert_eq!(decoder.next_packet(), Ok(None));
+        decoder.push(&bytes[20..]);
+        let command = decoder.next_packet().unwrap().unwrap();
+        assert_eq!(command.header.id, 8);
+        assert_eq!(command.data, vec![0x3]);
+        assert_eq!(decoder.buffered_len(), 0);
+    }
+
+    #[test]
+    fn test_invalid_length() {
+        let mut decoder = PacketDecoder::new();
+        decoder.push(&[0x0, 0x0, 0x0, 0x2, 0x0, 0x0, 0x0, 0x1, 0x80, 0x0, 0x0]);
+        assert_eq!(decoder.next_packet(), Err(Error::InvalidLength(2)));
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::event_channel::{EventReceiver, EventSender};
+use crate::{
+    Event, EventKind, EventModifier, JdwpClient, JdwpErrorCode, Location, SuspendPolicy, TypeTag,
+    VariableLengthId, result, type_name_to_signature,
+};
+
+/// Where instances of a class were allocated, see [AllocationTracker]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllocationSite {
+    /// Class of the allocated instances, e.g. `com.example.Order`
+    pub class_name: String,
+    /// Location calling the constructor, None if it couldn't be read, e.g. when called from
+    /// native code
+    pub caller: Option<Location>,
+    pub count: u64,
+}
+
+/// Counts allocations of selected classes by call site, for VMs without JFR access
+///
+/// Every constructor of the classes gets a breakpoint that suspends the allocating thread just
+/// long enough to read the calling frame. Constructors called by subclass constructors count
+/// with the subclass constructor as caller. Only classes loaded when the tracker starts are
+/// tracked, and hits also show up in the client's event queue.
+pub struct AllocationTracker {
+    client: JdwpClient,
+    request_ids: Vec<i32>,
+    hits: EventReceiver,
+    class_names: HashMap<VariableLengthId, String>,
+    counts: HashMap<(VariableLengthId, Option<Location>), u64>,
+}
+
+impl AllocationTracker {
+    /// Sets breakpoints on the constructors of the loaded classes named `class_names`, e.g.
+    /// `com.example.Order`
+    pub async fn start(client: JdwpClient, class_names: &[&str]) -> result::Result<Self> {
+        let (route, hits) = client.event_channel();
+        let mut tracker = AllocationTracker {
+            client,
+            request_ids: Vec::new(),
+            hits,
+            class_names: HashMap::new(),
+            counts: HashMap::new(),
+        };
+        if let Err(e) = tracker.set_requests(class_names, route).await {
+            tracker.clear().await;
+            return Err(e);
+        }
+        Ok(tracker)
+    }
+
+    async fn set_requests(
+        &mut self,
+        class_names: &[&str],
+        route: EventSender,
+    ) -> result::Result<()> {
+        for class_name in class_names {
+            let signature = type_name_to_signature(class_name);
+            let classes = self.client.vm_get_classes_by_signature(&signature).await?;
+            for class in classes.classes {
+                self.class_names
+                    .insert(class.type_id, String::from(*class_name));
+                let methods = self.client.ref_type_get_methods(class.type_id).await?;
+                for method in methods.methods {
+                    if method.name.string != "<init>" {
+                        continue;
+                    }
+                    let location = Location {
+                        type_tag: TypeTag::Class,
+                        class_id: class.type_id,
+                        method_id: method.method_id,
+                        index: 0,
+                    };
+                    let request_id = self
+                        .client
+                        .event_request_set_routed(
+                            EventKind::Breakpoint,
+                            SuspendPolicy::EventThread,
+                            vec![EventModifier::LocationOnly(location)],
+                            route.clone(),
+                        )
+                        .await?;
+                    self.request_ids.push(request_id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Ids of the breakpoint requests, one per constructor
+    pub fn request_ids(&self) -> &[i32] {
+        &self.request_ids
+    }
+
+    /// Counts allocations for `duration`, or until the connection is closed
+    pub async fn record_for(&mut self, duration: Duration) -> result::Result<()> {
+        let deadline = tokio::time::Instant::now() + duration;
+        while let Ok(Some(event)) = tokio::time::timeout_at(deadline, self.hits.recv()).await {
+            self.record(event).await?;
+        }
+        Ok(())
+    }
+
+    /// Allocation sites with the most allocations first, at most `limit` of them
+    pub fn top_allocators(&self, limit: usize) -> Vec<AllocationSite> {
+        let mut sites: Vec<AllocationSite> = self
+            .counts
+            .iter()
+            .map(|((class_id, caller), count)| AllocationSite {
+                class_name: self.class_names[class_id].clone(),
+                caller: *caller,
+                count: *count,
+            })
+            .collect();
+        sites.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.class_name.cmp(&b.class_name))
+        });
+        sites.truncate(limit);
+        sites
+    }
+
+    /// Clears the breakpoints. Counts stay available.
+    pub async fn stop(&mut self) {
+        self.clear().await;
+    }
+
+    async fn record(&mut self, event: Event) -> result::Result<()> {
+        let Event::Breakpoint {
+            thread, location, ..
+        } = event
+        else {
+            return Ok(());
+        };
+        let caller = match self.client.thread_get_frames(thread, 1, 1).await {
+            Ok(reply) => reply.frames.first().map(|frame| frame.location),
+            Err(result::Error::JdwpError(
+                JdwpErrorCode::InvalidThread | JdwpErrorCode::InvalidIndex,
+            )) => None,
+            Err(e) => return Err(e),
+        };
+        *self.counts.entry((location.class_id, caller)).or_insert(0) += 1;
+
+        match self.client.thread_resume(thread).await {
+            Err(result::Error::JdwpError(JdwpErrorCode::InvalidThread)) => Ok(()),
+            other => other,
+        }
+    }
+
+    async fn clear(&mut self) {
+        for request_id in self.request_ids.drain(..) {
+            // The connection may already be closed
+            let _ = self
+                .client
+                .event_request_clear(EventKind::Breakpoint, request_id)
+                .await;
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use binrw::{BinRead, BinWrite, binread, binrw};
+use std::io::Cursor;
+
+use crate::{Command, JdwpClient, QuirkDatabase, Quirks, VmFamily, result};
+
+/// Version of the DDM protocol sent in HELO chunks, the one DDMS and Android Studio speak
+pub const DDM_PROTOCOL_VERSION: u32 = 1;
+
+/// Type of the chunk a debugger introduces itself with
+pub const DDM_CHUNK_HELO: [u8; 4] = *b"HELO";
+
+/// Kind of VM a client connects to, which decides how the client adapts to it, see
+/// [JdwpClient::adapt_to_target]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum TargetKind {
+    /// A VM behaving like the reference implementation. Nothing is sent on connecting.
+    #[default]
+    Jvm,
+    /// The Android runtime, whose JDWP implementation lacks some commands, expects a DDM
+    /// handshake and renames threads while they run
+    Art,
+    /// Fingerprints the VM with VirtualMachine.Version and adapts to the quirks the default
+    /// [QuirkDatabase] lists for it
+    Detect,
+}
+
+/// Chunk of Android's Dalvik Debug Monitor protocol, which is tunnelled through JDWP
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DdmChunk {
+    pub chunk_type: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+/// The VM's reply to a HELO chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DdmHello {
+    pub protocol_version: u32,
+    pub pid: u32,
+    /// Description of the VM, e.g. `Android Runtime 2.1.0`
+    pub vm_identifier: String,
+    /// Process name of the app, `<pre-initialized>` until the app is bound
+    pub app_name: String,
+}
+
+#[binrw]
+#[brw(big)]
+struct DdmChunkWire {
+    chunk_type: [u8; 4],
+    #[bw(calc = data.len() as u32)]
+    length: u32,
+    #[br(count = length)]
+    data: Vec<u8>,
+}
+
+#[binread]
+#[br(big)]
+struct DdmHelloReply {
+    protocol_version: u32,
+    pid: u32,
+    #[br(temp)]
+    vm_identifier_length: u32,
+    #[br(temp)]
+    app_name_length: u32,
+    #[br(count = vm_identifier_length)]
+    vm_identifier: Vec<u16>,
+    #[br(count = app_name_length)]
+    app_name: Vec<u16>,
+}
+
+fn parsing_error(e: binrw::Error) -> result::Error {
+    result::Error::ParsingError {
+        message: format!("Binary parsing error: {:?}", e),
+    }
+}
+
+impl JdwpClient {
+    /// Adapts the client to the kind of VM it is connected to. For ART, or a VM detected to need
+    /// it, this sets the [Quirks] of the VM and sends the DDM handshake, whose reply is
+    /// returned. Applies to the whole connection.
+    pub async fn adapt_to_target(&self, target: TargetKind) -> result::Result<Option<DdmHello>> {
+        let quirks = match target {
+            TargetKind::Jvm => return Ok(None),
+            TargetKind::Art => {
+                let quirks = VmFamily::Art.known_quirks();
+                self.set_quirks(quirks);
+                quirks
+            }
+            TargetKind::Detect => self.detect_quirks(&QuirkDatabase::default()).await?,
+        };
+
+        if quirks.contains(Quirks::DDM_HELLO) {
+            return Ok(Some(self.ddm_hello().await?));
+        }
+        Ok(None)
+    }
+
+    /// Sends a DDM chunk and returns the chunk replied with, None if the VM has no handler for
+    /// the type of the chunk
+    pub async fn ddm_send_chunk(&self, chunk: &DdmChunk) -> result::Result<Option<DdmChunk>> {
+        let mut data = Cursor::new(Vec::new());
+        DdmChunkWire {
+            chunk_type: chunk.chunk_type,
+            data: chunk.data.clone(),
+        }
+        .write_be(&mut data)
+        .map_err(parsing_error)?;
+
+        let reply = self
+            .send_raw(Command::DdmChunk, data.get_ref(), None)
+            .await?;
+        if !reply.header.is_success() {
+            return Err(result::Error::from_error_code(reply.header.error_code));
+        }
+        if reply.data.is_empty() {
+            return Ok(None);
+        }
+        let wire = DdmChunkWire::read_be(&mut Cursor::new(&reply.data)).map_err(parsing_error)?;
+        Ok(Some(DdmChunk {
+            chunk_type: wire.chunk_type,
+            data: wire.data,
+        }))
+    }
+
+    /// Introduces the client to the VM as a DDM-aware debugger, as DDMS and Android Studio do.
+    /// ART starts reporting DDM chunks, e.g. of thread creation, only afterwards.
+    pub async fn ddm_hello(&self) -> result::Result<DdmHello> {
+        let chunk = DdmChunk {
+            chunk_type: DDM_CHUNK_HELO,
+            data: DDM_PROTOCOL_VERSION.to_be_bytes().to_vec(),
+        };
+        let reply = self
+            .ddm_send_chunk(&chunk)
+            .await?
+            .filter(|reply| reply.chunk_type == DDM_CHUNK_HELO)
+            .ok_or_else(|| result::Error::ParsingError {
+                message: String::from("The VM didn't reply to HELO with a HELO chunk"),
+            })?;
+
+        // Newer versions of ART append fields, e.g. the ABI, which are ignored
+        let hello = DdmHelloReply::read_be(&mut Cursor::new(&reply.data)).map_err(parsing_error)?;
+        Ok(DdmHello {
+            protocol_version: hello.protocol_version,
+            pid: hello.pid,
+            vm_identifier: String::from_utf16_lossy(&hello.vm_identifier),
+            app_name: String::from_utf16_lossy(&hello.app_name),
+        })
+    }
+}
@@ -0,0 +1,78 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::Command;
+
+/// A mutating command sent by a client, as handed to an [AuditSink]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// When the command was sent
+    pub timestamp: SystemTime,
+    pub command: Command,
+    /// Id of the command packet
+    pub packet_id: u32,
+    /// Encoded arguments of the command, as sent to the VM unless redacted
+    pub arguments: Vec<u8>,
+}
+
+/// Destination of audit records, e.g. a file or a remote log
+pub trait AuditSink: Send + Sync {
+    fn record(&self, record: AuditRecord);
+}
+impl<F: Fn(AuditRecord) + Send + Sync> AuditSink for F {
+    fn record(&self, record: AuditRecord) {
+        self(record)
+    }
+}
+
+/// Changes a record before it reaches the sink, e.g. to remove values which must not be logged
+pub type AuditRedactor = Arc<dyn Fn(&mut AuditRecord) + Send + Sync>;
+
+/// Records every command for which [Command::is_mutating] holds to a sink, after passing it
+/// through the redactors in the order they were added
+#[derive(Clone)]
+pub struct AuditLog {
+    sink: Arc<dyn AuditSink>,
+    redactors: Vec<AuditRedactor>,
+}
+impl AuditLog {
+    pub fn new(sink: impl AuditSink + 'static) -> Self {
+        AuditLog {
+            sink: Arc::new(sink),
+            redactors: Vec::new(),
+        }
+    }
+
+    pub fn with_redactor(
+        mut self,
+        redactor: impl Fn(&mut AuditRecord) + Send + Sync + 'static,
+    ) -> Self {
+        self.redactors.push(Arc::new(redactor));
+        self
+    }
+
+    pub(crate) fn record(&self, command: Command, packet_id: u32, arguments: &[u8]) {
+        if !command.is_mutating() {
+            return;
+        }
+
+        let mut record = AuditRecord {
+            timestamp: SystemTime::now(),
+            command,
+            packet_id,
+            arguments: arguments.to_vec(),
+        };
+        for redactor in &self.redactors {
+            redactor(&mut record);
+        }
+        self.sink.record(record);
+    }
+}
+impl fmt::Debug for AuditLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AuditLog")
+            .field("redactors", &self.redactors.len())
+            .finish_non_exhaustive()
+    }
+}
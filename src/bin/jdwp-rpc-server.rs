@@ -0,0 +1,41 @@
+//! Serves a debugging session to JSON-RPC clients, see [jdwp_client::RpcServer]
+//!
+//! Usage: `jdwp-rpc-server <listen address> <VM address>`, e.g.
+//! `jdwp-rpc-server 0.0.0.0:9000 localhost:5005`
+
+use std::process::ExitCode;
+
+use jdwp_client::{Debugger, RpcServer};
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    let [_, listen, target] = &args[..] else {
+        eprintln!("Usage: jdwp-rpc-server <listen address> <VM address>");
+        return ExitCode::FAILURE;
+    };
+
+    let debugger = match Debugger::attach(target).await {
+        Ok(debugger) => debugger,
+        Err(e) => {
+            eprintln!("Couldn't attach to {}: {:?}", target, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let server = match RpcServer::bind(listen.as_str(), debugger).await {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("Couldn't listen on {}: {}", listen, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match server.local_addr() {
+        Ok(address) => eprintln!("Serving {} on {}", target, address),
+        Err(_) => eprintln!("Serving {} on {}", target, listen),
+    }
+    if let Err(e) = server.run().await {
+        eprintln!("Server stopped: {}", e);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
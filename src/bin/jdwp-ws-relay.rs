@@ -0,0 +1,34 @@
+//! Forwards WebSocket connections to the JDWP agent of a VM
+//!
+//! Usage: `jdwp-ws-relay <listen address> <VM address>`, e.g.
+//! `jdwp-ws-relay 127.0.0.1:8080 localhost:5005`
+
+use std::process::ExitCode;
+
+use jdwp_client::WebSocketRelay;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    let [_, listen, target] = &args[..] else {
+        eprintln!("Usage: jdwp-ws-relay <listen address> <VM address>");
+        return ExitCode::FAILURE;
+    };
+
+    let relay = match WebSocketRelay::bind(listen.as_str(), target.as_str()).await {
+        Ok(relay) => relay,
+        Err(e) => {
+            eprintln!("Couldn't listen on {}: {}", listen, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match relay.local_addr() {
+        Ok(address) => eprintln!("Relaying ws://{} to {}", address, target),
+        Err(_) => eprintln!("Relaying {} to {}", listen, target),
+    }
+    if let Err(e) = relay.run().await {
+        eprintln!("Relay stopped: {}", e);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
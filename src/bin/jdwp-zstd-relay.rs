@@ -0,0 +1,35 @@
+//! Forwards zstd compressed connections to the JDWP agent of a VM, for debuggers connecting
+//! with `ClientConfig::compression` over a slow link
+//!
+//! Usage: `jdwp-zstd-relay <listen address> <VM address>`, e.g.
+//! `jdwp-zstd-relay 0.0.0.0:5006 localhost:5005`
+
+use std::process::ExitCode;
+
+use jdwp_client::CompressionRelay;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    let [_, listen, target] = &args[..] else {
+        eprintln!("Usage: jdwp-zstd-relay <listen address> <VM address>");
+        return ExitCode::FAILURE;
+    };
+
+    let relay = match CompressionRelay::bind(listen.as_str(), target.as_str()).await {
+        Ok(relay) => relay,
+        Err(e) => {
+            eprintln!("Couldn't listen on {}: {}", listen, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match relay.local_addr() {
+        Ok(address) => eprintln!("Relaying {} to {}", address, target),
+        Err(_) => eprintln!("Relaying {} to {}", listen, target),
+    }
+    if let Err(e) = relay.run().await {
+        eprintln!("Relay stopped: {}", e);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,488 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+
+use crate::event_channel::{EventReceiver, EventSender};
+use crate::{
+    Event, EventKind, EventModifier, EventRequestHandle, JdwpClient, JdwpErrorCode, Location,
+    SuspendPolicy, TypeTag, VariableLengthId, jvm_lines, result, type_name_to_signature,
+};
+
+/// A breakpoint on a source line, created with [JdwpClient::set_line_breakpoint]
+///
+/// The class may be loaded by several class loaders, so the breakpoint is made of one
+/// breakpoint request per loaded class with code at the line. Classes loaded later are
+/// resolved in the background as their CLASS_PREPARE events arrive. Hits of all requests are
+/// delivered to the handle in arrival order, buffered like the hits of an
+/// [crate::EventRequestHandle].
+///
+/// When a class is unloaded, the requests set in it are cleared. A breakpoint left without
+/// requests is pending reload and is set again once the class is prepared anew.
+///
+/// A breakpoint dropped without [LineBreakpoint::clear] clears its requests from a background
+/// task, as the class prepare request would otherwise keep suspending the loading threads.
+pub struct LineBreakpoint {
+    client: JdwpClient,
+    class_name: String,
+    line: i32,
+    class_prepare_request_id: i32,
+    class_unload_request_id: i32,
+    requests: Arc<Mutex<Requests>>,
+    hits: EventReceiver,
+    resolver: JoinHandle<()>,
+}
+impl LineBreakpoint {
+    pub fn class_name(&self) -> &str {
+        &self.class_name
+    }
+
+    pub fn line(&self) -> i32 {
+        self.line
+    }
+
+    /// Ids of the breakpoint requests set in the currently loaded classes
+    pub fn request_ids(&self) -> Vec<i32> {
+        lock(&self.requests)
+            .by_class
+            .iter()
+            .map(|(_, request_id)| *request_id)
+            .collect()
+    }
+
+    /// Whether every class the breakpoint was set in has been unloaded, so it waits for the
+    /// class to be loaded again
+    pub fn is_pending_reload(&self) -> bool {
+        lock(&self.requests).pending_reload
+    }
+
+    /// Tells whether an event request belongs to the breakpoint, checked against the requests
+    /// it has when called
+    pub(crate) fn request_matcher(&self) -> impl Fn(i32) -> bool + Send + Sync + 'static {
+        let requests = self.requests.clone();
+        move |request_id| {
+            lock(&requests)
+                .by_class
+                .iter()
+                .any(|(_, id)| *id == request_id)
+        }
+    }
+
+    /// Waits for the next hit in any of the matching classes
+    pub async fn next_hit(&mut self) -> Option<Event> {
+        self.hits.recv().await
+    }
+
+    /// Returns the next received hit without waiting
+    pub fn try_next_hit(&mut self) -> Option<Event> {
+        self.hits.try_recv()
+    }
+
+    /// Number of hits discarded because the handle's buffer was full
+    pub fn dropped_hit_count(&self) -> u64 {
+        self.hits.dropped()
+    }
+
+    /// Stops resolving new classes and clears every request of the breakpoint
+    pub async fn clear(&self, client: &JdwpClient) -> result::Result<()> {
+        self.resolver.abort();
+        lock(&self.requests).cleared = true;
+        client
+            .event_request_clear(EventKind::ClassPrepare, self.class_prepare_request_id)
+            .await?;
+        client
+            .event_request_clear(EventKind::ClassUnload, self.class_unload_request_id)
+            .await?;
+        for request_id in self.request_ids() {
+            client
+                .event_request_clear(EventKind::Breakpoint, request_id)
+                .await?;
+        }
+        Ok(())
+    }
+}
+impl fmt::Debug for LineBreakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LineBreakpoint")
+            .field("class_name", &self.class_name)
+            .field("line", &self.line)
+            .field("class_prepare_request_id", &self.class_prepare_request_id)
+            .field("class_unload_request_id", &self.class_unload_request_id)
+            .field("requests", &self.requests)
+            .finish_non_exhaustive()
+    }
+}
+impl Drop for LineBreakpoint {
+    fn drop(&mut self) {
+        self.resolver.abort();
+        if lock(&self.requests).cleared {
+            return;
+        }
+        // Without a runtime there is no reader left to route the replies either
+        if let Ok(runtime) = Handle::try_current() {
+            let client = self.client.clone();
+            let class_prepare_request_id = self.class_prepare_request_id;
+            let class_unload_request_id = self.class_unload_request_id;
+            let requests = self.requests.clone();
+            runtime.spawn(async move {
+                clear_all(
+                    &client,
+                    class_prepare_request_id,
+                    Some(class_unload_request_id),
+                    &requests,
+                )
+                .await;
+            });
+        }
+    }
+}
+
+/// Clears every request of a line breakpoint which is dropped or failed to be set, going on
+/// past the requests that fail to clear
+async fn clear_all(
+    client: &JdwpClient,
+    class_prepare_request_id: i32,
+    class_unload_request_id: Option<i32>,
+    requests: &Mutex<Requests>,
+) {
+    let mut cleared = vec![(EventKind::ClassPrepare, class_prepare_request_id)];
+    cleared.extend(class_unload_request_id.map(|id| (EventKind::ClassUnload, id)));
+    cleared.extend(
+        std::mem::take(&mut lock(requests).by_class)
+            .into_iter()
+            .map(|(_, id)| (EventKind::Breakpoint, id)),
+    );
+    for (event_kind, request_id) in cleared {
+        if let Err(e) = client.event_request_clear(event_kind, request_id).await {
+            eprintln!("Failed to clear line breakpoint request: {:?}", e);
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Breakpoint requests of a [LineBreakpoint], shared with the task resolving it in new classes
+#[derive(Debug, Default)]
+struct Requests {
+    /// Class each request was set in, in the order they were set
+    by_class: Vec<(VariableLengthId, i32)>,
+    pending_reload: bool,
+    /// Set once [LineBreakpoint::clear] was called, so dropping the breakpoint clears nothing
+    cleared: bool,
+}
+
+/// How the requests of a line breakpoint are set
+#[derive(Debug, Clone)]
+pub(crate) struct LineBreakpointOptions {
+    pub suspend_policy: SuspendPolicy,
+    /// Stratum the line is given in, None for lines of the class file
+    pub stratum: Option<String>,
+    /// Applied after the location of each breakpoint request
+    pub modifiers: Vec<EventModifier>,
+}
+impl LineBreakpointOptions {
+    pub fn new(suspend_policy: SuspendPolicy) -> Self {
+        LineBreakpointOptions {
+            suspend_policy,
+            stratum: None,
+            modifiers: Vec::new(),
+        }
+    }
+}
+
+struct Resolver {
+    client: JdwpClient,
+    line: i32,
+    options: LineBreakpointOptions,
+    route: EventSender,
+    requests: Arc<Mutex<Requests>>,
+    resolved_classes: HashSet<VariableLengthId>,
+}
+impl Resolver {
+    /// Sets a breakpoint at the first code index of the line in every method of the class.
+    /// Returns whether the class has code at the line.
+    async fn resolve(
+        &mut self,
+        type_tag: TypeTag,
+        class_id: VariableLengthId,
+    ) -> result::Result<bool> {
+        if !self.resolved_classes.insert(class_id) {
+            return Ok(true);
+        }
+
+        let lines = match &self.options.stratum {
+            Some(stratum) => {
+                let smap = self.client.class_smap(class_id).await?;
+                jvm_lines(smap.as_ref(), stratum, self.line)
+            }
+            None => vec![self.line],
+        };
+        let locations = line_locations(&self.client, type_tag, class_id, &lines).await?;
+        for location in &locations {
+            let mut modifiers = vec![EventModifier::LocationOnly(*location)];
+            modifiers.extend(self.options.modifiers.iter().cloned());
+            let request_id = self
+                .client
+                .event_request_set_routed(
+                    EventKind::Breakpoint,
+                    self.options.suspend_policy,
+                    modifiers,
+                    self.route.clone(),
+                )
+                .await?;
+            let mut requests = lock(&self.requests);
+            requests.by_class.push((class_id, request_id));
+            requests.pending_reload = false;
+        }
+        Ok(!locations.is_empty())
+    }
+
+    /// Clears the requests set in classes of the given signature which are no longer loaded.
+    /// The event doesn't say which class loader's class was unloaded, so the loaded ones are
+    /// looked up.
+    async fn unload(&mut self, signature: &str) -> result::Result<()> {
+        let loaded: HashSet<_> = self
+            .client
+            .vm_get_classes_by_signature(signature)
+            .await?
+            .classes
+            .iter()
+            .map(|class| class.type_id)
+            .collect();
+        self.resolved_classes
+            .retain(|class_id| loaded.contains(class_id));
+
+        let unloaded = {
+            let mut requests = lock(&self.requests);
+            let (kept, unloaded) = std::mem::take(&mut requests.by_class)
+                .into_iter()
+                .partition(|(class_id, _)| loaded.contains(class_id));
+            requests.by_class = kept;
+            if !unloaded.is_empty() && requests.by_class.is_empty() {
+                requests.pending_reload = true;
+            }
+            unloaded
+        };
+        for (_, request_id) in unloaded {
+            // The VM may already have dropped requests located in the unloaded class
+            match self
+                .client
+                .event_request_clear(EventKind::Breakpoint, request_id)
+                .await
+            {
+                Ok(()) | Err(result::Error::JdwpError(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    async fn run(
+        mut self,
+        mut class_prepare: EventRequestHandle,
+        mut class_unload: EventRequestHandle,
+    ) {
+        loop {
+            let event = tokio::select! {
+                event = class_prepare.next_hit() => event,
+                Some(event) = class_unload.next_hit() => Some(event),
+            };
+            let Some(event) = event else {
+                break;
+            };
+            if let Event::ClassUnload { signature, .. } = event {
+                if let Err(e) = self.unload(&signature.string).await {
+                    eprintln!("Failed to clear breakpoint in unloaded class: {:?}", e);
+                }
+                continue;
+            }
+            let Event::ClassPrepare {
+                thread,
+                ref_type_tag,
+                type_id,
+                ..
+            } = event
+            else {
+                continue;
+            };
+            if let Err(e) = self.resolve(ref_type_tag, type_id).await {
+                eprintln!("Failed to resolve breakpoint in new class: {:?}", e);
+            }
+            // The class prepare request suspends the loading thread so the breakpoint is set
+            // before any code of the class runs
+            if let Err(e) = self.client.thread_resume(thread).await {
+                eprintln!("Failed to resume thread after class prepare: {:?}", e);
+            }
+        }
+    }
+}
+
+/// Locations of the first code index of any of `lines` in each method of the class. Methods
+/// without line information are skipped.
+async fn line_locations(
+    client: &JdwpClient,
+    type_tag: TypeTag,
+    class_id: VariableLengthId,
+    lines: &[i32],
+) -> result::Result<Vec<Location>> {
+    let mut locations = Vec::new();
+    for method in client.ref_type_get_methods(class_id).await?.methods {
+        let table = match client
+            .method_get_line_table(class_id, method.method_id)
+            .await
+        {
+            Ok(table) => table,
+            Err(result::Error::JdwpError(
+                JdwpErrorCode::AbsentInformation | JdwpErrorCode::NativeMethod,
+            )) => continue,
+            Err(e) => return Err(e),
+        };
+
+        let index = table
+            .lines
+            .iter()
+            .filter(|entry| lines.contains(&entry.line_number))
+            .map(|entry| entry.line_code_index)
+            .min();
+        if let Some(index) = index {
+            locations.push(Location {
+                type_tag,
+                class_id,
+                method_id: method.method_id,
+                index,
+            });
+        }
+    }
+    Ok(locations)
+}
+
+impl JdwpClient {
+    /// Sets a breakpoint on a source line of a class given by its name, e.g. `com.example.Main`,
+    /// in every loaded class of that name and in those loaded later.
+    ///
+    /// Fails with [result::Error::InvalidArgument] if the class is loaded but has no code at the
+    /// line.
+    pub async fn set_line_breakpoint(
+        &self,
+        class_name: &str,
+        line: i32,
+        suspend_policy: SuspendPolicy,
+    ) -> result::Result<LineBreakpoint> {
+        let (route, hits) = self.event_channel();
+        let options = LineBreakpointOptions::new(suspend_policy);
+        self.set_line_breakpoint_routed(class_name, line, options, route, hits)
+            .await
+    }
+
+    /// Sets a breakpoint on a line given in the lines of `stratum`, e.g. `Kotlin`, which the SMAP
+    /// of each matching class translates to the lines of the class file. The line is looked up in
+    /// the first source file of the stratum, the class's own. Classes without an SMAP or without
+    /// the stratum use the line as is.
+    pub async fn set_stratum_line_breakpoint(
+        &self,
+        class_name: &str,
+        stratum: &str,
+        line: i32,
+        suspend_policy: SuspendPolicy,
+    ) -> result::Result<LineBreakpoint> {
+        let (route, hits) = self.event_channel();
+        let mut options = LineBreakpointOptions::new(suspend_policy);
+        options.stratum = Some(String::from(stratum));
+        self.set_line_breakpoint_routed(class_name, line, options, route, hits)
+            .await
+    }
+
+    /// Sets a line breakpoint whose hits are sent to `route`. The returned handle reads its hits
+    /// from `hits`.
+    pub(crate) async fn set_line_breakpoint_routed(
+        &self,
+        class_name: &str,
+        line: i32,
+        options: LineBreakpointOptions,
+        route: EventSender,
+        hits: EventReceiver,
+    ) -> result::Result<LineBreakpoint> {
+        // Listen for new classes first, so a class loaded while the loaded ones are resolved
+        // isn't missed
+        let class_prepare = self
+            .event_request_set(
+                EventKind::ClassPrepare,
+                SuspendPolicy::EventThread,
+                vec![EventModifier::ClassMatch(String::from(class_name))],
+            )
+            .await?;
+        let class_prepare_request_id = class_prepare.request_id();
+        let requests = Arc::new(Mutex::new(Requests::default()));
+        let class_unload = match self
+            .event_request_set(
+                EventKind::ClassUnload,
+                SuspendPolicy::None,
+                vec![EventModifier::ClassMatch(String::from(class_name))],
+            )
+            .await
+        {
+            Ok(class_unload) => class_unload,
+            Err(e) => {
+                clear_all(self, class_prepare_request_id, None, &requests).await;
+                return Err(e);
+            }
+        };
+        let class_unload_request_id = class_unload.request_id();
+
+        let mut resolver = Resolver {
+            client: self.clone(),
+            line,
+            options,
+            route,
+            requests: requests.clone(),
+            resolved_classes: HashSet::new(),
+        };
+
+        let signature = type_name_to_signature(class_name);
+        let has_line = async {
+            let classes = self.vm_get_classes_by_signature(&signature).await?.classes;
+            let mut has_line = classes.is_empty();
+            for class in &classes {
+                has_line |= resolver.resolve(class.ref_type_tag, class.type_id).await?;
+            }
+            Ok(has_line)
+        }
+        .await;
+        // Clear whatever was set, so a failed breakpoint doesn't keep suspending the threads
+        // loading the class
+        let error = match has_line {
+            Ok(true) => None,
+            Ok(false) => Some(result::Error::InvalidArgument {
+                message: format!("{} has no code at line {}", class_name, line),
+            }),
+            Err(e) => Some(e),
+        };
+        if let Some(error) = error {
+            clear_all(
+                self,
+                class_prepare_request_id,
+                Some(class_unload_request_id),
+                &requests,
+            )
+            .await;
+            return Err(error);
+        }
+
+        Ok(LineBreakpoint {
+            client: self.clone(),
+            class_name: String::from(class_name),
+            line,
+            class_prepare_request_id,
+            class_unload_request_id,
+            requests,
+            hits,
+            resolver: tokio::spawn(resolver.run(class_prepare, class_unload)),
+        })
+    }
+}
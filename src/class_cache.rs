@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+
+use crate::conformance;
+use crate::connection::ReplyHook;
+use crate::event_channel::event_channel;
+use crate::{
+    AllClassesReply, ClassStatus, Command, Event, EventComposite, EventKind, JdwpClient,
+    SuspendPolicy, TypeTag, VariableLengthId, result, signature_to_type_name,
+};
+
+/// A class loaded in the debuggee, see [JdwpClient::find_classes]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadedClass {
+    pub ref_type_tag: TypeTag,
+    pub type_id: VariableLengthId,
+    /// JNI signature, e.g. `Lcom/example/Main;`
+    pub signature: String,
+    pub status: ClassStatus,
+}
+impl LoadedClass {
+    /// Source-level name, e.g. `com.example.Main$Inner`
+    pub fn type_name(&self) -> String {
+        signature_to_type_name(&self.signature)
+    }
+
+    /// Name without package and enclosing classes, e.g. `Inner` for `com.example.Main$Inner`
+    pub fn simple_name(&self) -> String {
+        let name = self.type_name();
+        let name = name.rsplit('.').next().unwrap_or(&name);
+        String::from(name.rsplit('$').next().unwrap_or(name))
+    }
+}
+
+/// Classes loaded in the debuggee, filled once from AllClasses and kept current by CLASS_PREPARE
+/// and CLASS_UNLOAD events. Events are only recorded after tracking started.
+#[derive(Debug, Default)]
+pub(crate) struct ClassCache {
+    classes: Option<HashMap<VariableLengthId, LoadedClass>>,
+}
+impl ClassCache {
+    /// Starts recording prepared classes. Called before the AllClasses request is sent so no
+    /// class prepared in between is missed.
+    pub(crate) fn start(&mut self) {
+        self.classes.get_or_insert_with(HashMap::new);
+    }
+
+    pub(crate) fn fill(&mut self, reply: AllClassesReply) {
+        let classes = self.classes.get_or_insert_with(HashMap::new);
+        for class in reply.classes {
+            classes.insert(
+                class.type_id,
+                LoadedClass {
+                    ref_type_tag: class.ref_type_tag,
+                    type_id: class.type_id,
+                    signature: class.signature.string,
+                    status: class.status,
+                },
+            );
+        }
+    }
+
+    pub(crate) fn event_received(&mut self, composite: &EventComposite) {
+        let Some(classes) = &mut self.classes else {
+            return;
+        };
+        for event in &composite.events {
+            match event {
+                Event::ClassPrepare {
+                    ref_type_tag,
+                    type_id,
+                    signature,
+                    status,
+                    ..
+                } => {
+                    classes.insert(
+                        *type_id,
+                        LoadedClass {
+                            ref_type_tag: *ref_type_tag,
+                            type_id: *type_id,
+                            signature: signature.string.clone(),
+                            status: *status,
+                        },
+                    );
+                }
+                Event::ClassUnload { signature, .. } => {
+                    classes.retain(|_, class| class.signature != signature.string);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Classes matching `predicate`, sorted by signature
+    pub(crate) fn matching(&self, predicate: impl Fn(&LoadedClass) -> bool) -> Vec<LoadedClass> {
+        let mut found: Vec<LoadedClass> = self
+            .classes
+            .iter()
+            .flat_map(|classes| classes.values())
+            .filter(|class| predicate(class))
+            .cloned()
+            .collect();
+        found.sort_by(|a, b| a.signature.cmp(&b.signature));
+        found
+    }
+}
+
+impl JdwpClient {
+    /// Starts keeping a registry of loaded classes, read by [Self::classes]
+    ///
+    /// All classes are fetched once, after which CLASS_PREPARE and CLASS_UNLOAD requests keep
+    /// the registry current. Their events also show up in the event queue. Tracking is enabled
+    /// at most once per connection; [Self::find_classes] enables it as well.
+    pub async fn enable_class_tracking(&self) -> result::Result<()> {
+        self.shared
+            .class_tracking
+            .get_or_try_init(|| async {
+                self.shared.classes().start();
+                for event_kind in [EventKind::ClassPrepare, EventKind::ClassUnload] {
+                    self.event_request_set_routed(
+                        event_kind,
+                        SuspendPolicy::None,
+                        vec![],
+                        event_channel(1).0,
+                    )
+                    .await?;
+                }
+                // The list is stored by the reader task as soon as the reply arrives, so events
+                // read right after it are applied on top of it
+                let sizes = self.id_sizes()?;
+                let fill: ReplyHook = Arc::new(move |shared, data| {
+                    if let Ok(reply) = conformance::decode::<AllClassesReply>(
+                        data,
+                        sizes,
+                        shared.conformance(),
+                        shared.max_string_length.load(Ordering::Relaxed),
+                        "the reply to AllClasses",
+                    ) {
+                        shared.classes().fill(reply);
+                    }
+                });
+                let reply = self
+                    .send_raw(Command::VirtualMachineAllClasses, &[], Some(fill))
+                    .await?;
+                match reply.header.is_success() {
+                    true => Ok(()),
+                    false => Err(result::Error::from_error_code(reply.header.error_code)),
+                }
+            })
+            .await
+            .map(|_| ())
+    }
+
+    /// Snapshot of the loaded classes, sorted by signature, without a round trip. Empty until
+    /// [Self::enable_class_tracking] completed.
+    ///
+    /// CLASS_UNLOAD only carries the signature, so when one of several classes with the same
+    /// signature (from different class loaders) is unloaded, all of them are dropped.
+    pub fn classes(&self) -> Vec<LoadedClass> {
+        self.shared.classes().matching(|_| true)
+    }
+
+    /// Classes whose simple name is `simple_name`, e.g. `MyService` finds
+    /// `com.acme.MyService` and `com.acme.Registry$MyService`
+    ///
+    /// The first search enables [Self::enable_class_tracking], so later searches need no round
+    /// trip.
+    pub async fn find_classes(&self, simple_name: &str) -> result::Result<Vec<LoadedClass>> {
+        self.enable_class_tracking().await?;
+        Ok(self
+            .shared
+            .classes()
+            .matching(|class| class.simple_name() == simple_name))
+    }
+
+    /// Classes in packages starting with `prefix`, e.g. `com.acme.`. See [Self::find_classes].
+    pub async fn find_classes_in_package(&self, prefix: &str) -> result::Result<Vec<LoadedClass>> {
+        self.enable_class_tracking().await?;
+        Ok(self
+            .shared
+            .classes()
+            .matching(|class| class.type_name().starts_with(prefix)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class(signature: &str) -> LoadedClass {
+        LoadedClass {
+            ref_type_tag: TypeTag::Class,
+            type_id: VariableLengthId { value: 1 },
+            signature: String::from(signature),
+            status: ClassStatus::all(),
+        }
+    }
+
+    #[test]
+    fn test_simple_name() {
+        assert_eq!(class("Lcom/acme/MyService;").simple_name(), "MyService");
+        assert_eq!(class("Lcom/acme/Registry$Entry;").simple_name(), "Entry");
+        assert_eq!(class("LMain;").simple_name(), "Main");
+    }
+}
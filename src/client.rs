@@ -1,137 +1,352 @@
 use binrw::{BinRead, BinWrite};
-use std::collections::HashMap;
+use bytes::{Bytes, BytesMut};
 use std::io;
 use std::io::Cursor;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
-use tokio::sync::{Mutex, oneshot};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{mpsc, oneshot};
 use tokio::time::timeout;
 
+use crate::conformance;
+use crate::connection::{
+    OutgoingPacket, PendingRequest, PendingRequestGuard, ReplyHook, Shared, do_handshake,
+    handle_command, negotiate_id_sizes, reader_loop, send_detached, writer_loop,
+};
+use crate::event_channel::EventSender;
+use crate::suspension::SuspensionChange;
 use crate::{
-    AllClassesReply, AllThreadsReply, ClassesBySignatureOut, ClassesBySignatureReply, Command,
-    CommandPacketHeader, IdSizesReply, JdwpIdSizes, JdwpStringSlice, ReplyPacketHeader,
-    TopLevelThreadGroupsReply, VersionReply, result,
+    AllClassesReply, AllThreadsReply, ArrayGetValuesOut, ArrayGetValuesReply, ArrayLengthOut,
+    ArrayLengthReply, ArrayNewInstanceOut, ArrayNewInstanceReply, ArrayValues, AuditLog,
+    CapabilitiesNewReply, ClassInvokeMethodOut, ClassNewInstanceOut, ClassNewInstanceReply,
+    ClassObjectReply, ClassPathsReply, ClassesBySignatureOut, ClassesBySignatureReply,
+    ClientDriver, Command, CommandPriority, Conformance, CreateStringOut, CreateStringReply,
+    EventBufferConfig, EventComposite, EventKind, EventModifier, EventQueue, EventRequestClearOut,
+    EventRequestHandle, EventRequestSetOut, EventRequestSetReply, EventSubscriptionBuilder,
+    ExceptionHandle, ExitOut, FieldsReply, FlowControl, FrameCountReply, FrameSlot, FramesOut,
+    FramesReply, GetValuesReply, IdSizesReply, InstanceCountsOut, InstanceCountsReply,
+    InstancesOut, InstancesReply, InterfacesReply, IntoJdwpArguments, InvokeMethodReply,
+    InvokeOptions, JdwpErrorCode, JdwpIdSizes, JdwpStringSlice, JdwpValue, LineTableReply,
+    MethodOut, MethodsReply, MethodsReplyMethod, ObjectGetValuesOut, ObjectInvokeMethodOut,
+    ObjectOut, ObjectReferenceTypeReply, PACKET_HEADER_LENGTH, PrimitiveArray, RateLimiter,
+    RefTypeGetValuesOut, RefTypeOut, ReferringObjectsOut, ReferringObjectsReply,
+    ReflectedTypeReply, ReplyPacket, RetryPolicy, SignatureReply, SourceDebugExtensionReply,
+    SourceFileReply, StackFrameGetValuesOut, StackFrameOut, StringValueReply, SuperclassReply,
+    SuspendCountReply, SuspendPolicy, Tag, TaggedObjectId, ThisObjectReply,
+    ThreadGroupChildrenReply, ThreadGroupNameReply, ThreadGroupOut, ThreadNameReply, ThreadOut,
+    ThreadPicker, ThreadStatusReply, TopLevelThreadGroupsReply, VariableLengthId,
+    VariableTableReply, VersionReply, VmInfo, encode_command, parse_method_descriptor, result,
+    signature_tag, type_name_to_signature,
 };
 
-pub struct JdwpClient<T> {
-    writer: Arc<Mutex<WriteHalf<T>>>,
-    pending_requests: Arc<Mutex<HashMap<u32, oneshot::Sender<ReplyPacket>>>>,
-    packet_id: Arc<Mutex<u32>>,
-    _reader_handle: tokio::task::JoinHandle<()>,
+/// Default number of array elements requested per ArrayReference.GetValues command
+pub const DEFAULT_ARRAY_CHUNK_SIZE: i32 = 64 * 1024;
+
+/// Replies of at least this many bytes, e.g. AllClasses of a huge application or an array
+/// dump, are decoded on the blocking thread pool, so decoding them doesn't hold up the reader
+/// task and event handlers sharing a runtime thread with the caller. Clients created with
+/// [JdwpClient::new_with_driver] or [JdwpClient::new_inline] spawn nothing and decode every
+/// reply on the task waiting for it.
+pub const BLOCKING_DECODE_THRESHOLD: usize = 256 * 1024;
+
+/// A handle to a JDWP connection.
+///
+/// The connection itself is driven by background reader and writer tasks, or by a
+/// [crate::ClientDriver] run by the caller, so handles are cheap to clone and can be used
+/// concurrently from different tasks. The timeout, retry policy, suspension checks and thread
+/// picker belong to the handle they are set on. The connection is closed once every handle is
+/// dropped.
+#[derive(Clone)]
+pub struct JdwpClient {
+    outgoing: mpsc::UnboundedSender<OutgoingPacket>,
+    pub(crate) shared: Arc<Shared>,
     sizes: Option<JdwpIdSizes>,
-    timeout_duration: Duration,
+    pub(crate) timeout_duration: Duration,
+    retry_policy: RetryPolicy,
+    suspension_checks: bool,
+    pub(crate) thread_picker: ThreadPicker,
+    audit_log: Option<AuditLog>,
+    rate_limiter: Option<RateLimiter>,
+    command_priority: CommandPriority,
+    /// Driver polled by the handle itself while waiting, see [JdwpClient::new_inline]
+    pub(crate) inline_driver: Option<Arc<tokio::sync::Mutex<ClientDriver>>>,
+    /// Whether large replies are decoded on the blocking thread pool, only for clients whose
+    /// connection runs on spawned tasks
+    blocking_decode: bool,
 }
 
-struct ReplyPacket {
-    header: ReplyPacketHeader,
-    data: Vec<u8>,
-}
+impl JdwpClient {
+    pub async fn new<T>(mut stream: T) -> result::Result<Self>
+    where
+        T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        do_handshake(&mut stream).await?;
+        Self::after_handshake(stream).await
+    }
+
+    /// Sets up the connection on a stream whose handshake is already done
+    pub(crate) async fn after_handshake<T>(stream: T) -> result::Result<Self>
+    where
+        T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let (mut client, driver) = Self::after_handshake_with_driver(stream).await?;
+        tokio::spawn(driver);
+        client.blocking_decode = true;
+        Ok(client)
+    }
 
-impl<T> JdwpClient<T>
-where
-    T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
-{
-    pub async fn new(mut stream: T) -> result::Result<Self> {
-        Self::do_handshake(&mut stream).await?;
+    pub(crate) async fn after_handshake_with_driver<T>(
+        mut stream: T,
+    ) -> result::Result<(Self, ClientDriver)>
+    where
+        T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        const ID_SIZES_PACKET_ID: u32 = 1;
+
+        // The VM may send events (e.g. VM_START) right after the handshake, and decoding them
+        // requires the id sizes, so they are negotiated before the reader task starts
+        let timeout_duration = Duration::from_secs(5);
+        let mut early_commands = Vec::new();
+        let sizes = timeout(
+            timeout_duration,
+            negotiate_id_sizes(&mut stream, ID_SIZES_PACKET_ID, &mut early_commands),
+        )
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Request timed out"))??;
+        let sizes = Self::id_sizes_from_reply(sizes)?;
 
         let (reader, writer) = tokio::io::split(stream);
+        let (outgoing, outgoing_rx) = mpsc::unbounded_channel();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
 
-        let pending_requests = Arc::new(Mutex::new(HashMap::new()));
-        let writer_arc = Arc::new(Mutex::new(writer));
-        let packet_id = Arc::new(Mutex::new(0));
+        let shared = Arc::new(Shared::new(
+            EventQueue::new(EventBufferConfig::default()),
+            ID_SIZES_PACKET_ID,
+        ));
+        for command in early_commands {
+            handle_command(command, sizes, &shared, Some(&outgoing)).await;
+        }
 
-        // Spawn reader task
-        let pending_clone = pending_requests.clone();
-        let reader_handle = tokio::spawn(async move {
-            Self::reader_loop(reader, pending_clone).await;
+        let writer = writer_loop(writer, outgoing_rx, shared.clone(), sizes, shutdown_tx);
+        let reader = reader_loop(
+            reader,
+            shared.clone(),
+            outgoing.downgrade(),
+            sizes,
+            shutdown_rx,
+        );
+        let driver = ClientDriver::new(async move {
+            tokio::join!(writer, reader);
         });
 
-        let mut client = JdwpClient {
-            writer: writer_arc,
-            pending_requests,
-            packet_id,
-            _reader_handle: reader_handle,
-            sizes: None,
-            timeout_duration: Duration::from_secs(5),
+        let client = JdwpClient {
+            outgoing,
+            shared,
+            sizes: Some(sizes),
+            timeout_duration,
+            retry_policy: RetryPolicy::default(),
+            suspension_checks: true,
+            thread_picker: ThreadPicker::default(),
+            audit_log: None,
+            rate_limiter: None,
+            command_priority: CommandPriority::default(),
+            inline_driver: None,
+            blocking_decode: false,
         };
-        client.get_id_sizes().await?;
-        Ok(client)
+        Ok((client, driver))
     }
 
-    async fn reader_loop(
-        mut reader: ReadHalf<T>,
-        pending_requests: Arc<Mutex<HashMap<u32, oneshot::Sender<ReplyPacket>>>>,
-    ) {
-        loop {
-            // TODO: Handle command packets coming from the VM
-            match Self::read_reply_packet(&mut reader).await {
-                Ok(reply_packet) => {
-                    let mut pending = pending_requests.lock().await;
-                    if let Some(sender) = pending.remove(&reply_packet.header.id) {
-                        let _ = sender.send(reply_packet);
-                    }
-                }
-                Err(e) => {
-                    eprintln!("Reader task error: {:?}", e);
-                    // Notify all pending requests about the error
-                    let mut pending = pending_requests.lock().await;
-                    for (_, sender) in pending.drain() {
-                        let _ = sender.send(ReplyPacket {
-                            header: ReplyPacketHeader::default(),
-                            data: Vec::new(),
-                        });
-                    }
-                    break;
-                }
-            }
+    /// Waits for the next set of events sent by the VM. Returns None once the connection is
+    /// closed and all buffered events have been consumed.
+    pub async fn next_event(&self) -> Option<EventComposite> {
+        let (composite, flow) = self.drive_until(self.shared.events.pop()).await;
+        self.apply_event_flow_control(flow);
+        composite
+    }
+
+    /// Returns the next buffered set of events without waiting
+    pub fn try_next_event(&self) -> Option<EventComposite> {
+        let (composite, flow) = self.shared.events.try_pop();
+        self.apply_event_flow_control(flow);
+        composite
+    }
+
+    fn apply_event_flow_control(&self, flow: FlowControl) {
+        if flow == FlowControl::ReleaseEvents {
+            send_detached(
+                &self.outgoing,
+                &self.shared,
+                Command::VirtualMachineReleaseEvents,
+            );
         }
     }
 
-    async fn read_reply_packet(reader: &mut ReadHalf<T>) -> result::Result<ReplyPacket> {
-        // Read header
-        let mut header_buffer = vec![0u8; ReplyPacketHeader::get_length()];
-        reader.read_exact(&mut header_buffer).await?;
+    /// Starts building a subscription to events matching client-side filters
+    pub fn events(&self) -> EventSubscriptionBuilder {
+        EventSubscriptionBuilder::new(self.shared.clone())
+    }
 
-        let mut cursor = Cursor::new(&header_buffer);
-        let header =
-            ReplyPacketHeader::read_be(&mut cursor).map_err(|e| result::Error::ParsingError {
-                message: format!("Parsing error: {:?}", e),
-            })?;
+    /// Changes the capacity and overflow policy of the event buffer
+    pub fn set_event_buffer_config(&self, config: EventBufferConfig) {
+        self.shared.events.set_config(config);
+    }
 
-        // Read data
-        let data_length = header.length as usize - ReplyPacketHeader::get_length();
-        let mut data = vec![0u8; data_length];
-        reader.read_exact(&mut data).await?;
+    pub fn event_buffer_config(&self) -> EventBufferConfig {
+        self.shared.events.config()
+    }
 
-        Ok(ReplyPacket { header, data })
+    /// Number of event sets waiting to be consumed
+    pub fn buffered_event_count(&self) -> usize {
+        self.shared.events.len()
     }
 
-    async fn write_request(
-        writer: &mut WriteHalf<T>,
-        header: &CommandPacketHeader,
-        data: &[u8],
-    ) -> result::Result<()> {
-        // Write header
-        let mut header_buffer = Vec::with_capacity(CommandPacketHeader::get_length());
-        let mut cursor = Cursor::new(&mut header_buffer);
-        header
-            .write_be(&mut cursor)
-            .map_err(|e| result::Error::ParsingError {
-                message: format!("Serialization error: {:?}", e),
-            })?;
+    /// Number of buffered event sets discarded by [OverflowPolicy::DropOldest]
+    pub fn dropped_event_count(&self) -> u64 {
+        self.shared.events.dropped()
+    }
+
+    /// Sets the policy used to retry commands which fail with transient JDWP errors
+    pub fn set_retry_policy(&mut self, retry_policy: RetryPolicy) {
+        self.retry_policy = retry_policy;
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry_policy
+    }
+
+    /// Records the mutating commands sent through this handle, and handles cloned from it
+    /// afterwards, to an audit log. None stops recording.
+    pub fn set_audit_log(&mut self, audit_log: Option<AuditLog>) {
+        self.audit_log = audit_log;
+    }
+
+    pub fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit_log.as_ref()
+    }
+
+    /// Limits the rate of commands sent through this handle. Handles cloned from it afterwards
+    /// share the limit. None removes the limit.
+    pub fn set_rate_limiter(&mut self, rate_limiter: Option<RateLimiter>) {
+        self.rate_limiter = rate_limiter;
+    }
+
+    pub fn rate_limiter(&self) -> Option<&RateLimiter> {
+        self.rate_limiter.as_ref()
+    }
+
+    /// Sets the priority of commands sent through this handle, and handles cloned from it
+    /// afterwards. Commands queued while the connection is busy writing are sent highest
+    /// priority first, so e.g. a UI's resume doesn't wait behind a sampler's fetches.
+    pub fn set_command_priority(&mut self, priority: CommandPriority) {
+        self.command_priority = priority;
+    }
+
+    pub fn command_priority(&self) -> CommandPriority {
+        self.command_priority
+    }
+
+    /// Enables or disables checking that a thread is suspended before sending commands which
+    /// require it. With the checks disabled such commands are sent as they are and the VM reports
+    /// THREAD_NOT_SUSPENDED itself.
+    pub fn set_suspension_checks(&mut self, enabled: bool) {
+        self.suspension_checks = enabled;
+    }
+
+    pub fn suspension_checks(&self) -> bool {
+        self.suspension_checks
+    }
+
+    /// Whether the whole VM is suspended by VirtualMachine.Suspend or an event set with
+    /// [SuspendPolicy::All], as seen by this client
+    pub fn is_vm_suspended(&self) -> bool {
+        self.shared.suspension().is_vm_suspended()
+    }
+
+    /// Whether the thread is suspended, as seen by this client
+    pub fn is_thread_suspended(&self, thread_id: VariableLengthId) -> bool {
+        self.shared.suspension().is_thread_suspended(thread_id)
+    }
+
+    /// Suspend count of the thread, as seen by this client
+    pub fn thread_suspend_count(&self, thread_id: VariableLengthId) -> u32 {
+        self.shared.suspension().count(thread_id).max(0) as u32
+    }
 
-        writer.write_all(&header_buffer).await?;
-        writer.write_all(data).await?;
-        writer.flush().await?;
+    /// Resumes the suspensions this client has seen but not undone once every handle is
+    /// dropped, and before [JdwpClient::vm_dispose], so a tool which crashes or forgets to
+    /// resume doesn't leave the target stopped. Off by default. Unlike the timeout, this
+    /// applies to the whole connection rather than to this handle.
+    pub fn set_resume_on_shutdown(&self, enabled: bool) {
+        self.shared
+            .resume_on_shutdown
+            .store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn resume_on_shutdown(&self) -> bool {
+        self.shared.resume_on_shutdown.load(Ordering::Relaxed)
+    }
+
+    /// Makes replies and events which deviate from the specification fail to decode, or be
+    /// tolerated and logged, see [Conformance]. Lenient by default. Like
+    /// [JdwpClient::set_resume_on_shutdown], this applies to the whole connection.
+    pub fn set_conformance(&self, conformance: Conformance) {
+        self.shared
+            .strict_conformance
+            .store(conformance == Conformance::Strict, Ordering::Relaxed);
+    }
+
+    pub fn conformance(&self) -> Conformance {
+        self.shared.conformance()
+    }
+
+    /// Sets the longest string, in bytes, decoded from the replies and events of the VM.
+    /// Longer strings fail to decode instead of making the client allocate whatever length a
+    /// hostile or buggy VM sends. [crate::DEFAULT_MAX_STRING_LENGTH] by default. This applies to the
+    /// whole connection.
+    pub fn set_max_string_length(&self, max_length: usize) {
+        self.shared
+            .max_string_length
+            .store(max_length, Ordering::Relaxed);
+    }
+
+    pub fn max_string_length(&self) -> usize {
+        self.shared.max_string_length.load(Ordering::Relaxed)
+    }
 
+    /// Sends VirtualMachine.Resume and ThreadReference.Resume as many times as needed to undo
+    /// every suspension seen by this client, including those by events
+    pub async fn resume_leaked_suspensions(&self) -> result::Result<()> {
+        let (vm, threads) = self.shared.suspension().leaked();
+        for _ in 0..vm {
+            self.vm_resume().await?;
+        }
+        for (thread_id, count) in threads {
+            for _ in 0..count {
+                self.thread_resume(thread_id).await?;
+            }
+        }
         Ok(())
     }
 
-    async fn next_packet_id(&self) -> u32 {
-        let mut id = self.packet_id.lock().await;
-        *id = id.wrapping_add(1);
-        *id
+    /// Changes whenever the thread may have run since, as seen by this client. Frames read
+    /// while the thread was suspended are only valid for the generation they were read in.
+    pub fn thread_generation(&self, thread_id: VariableLengthId) -> u64 {
+        self.shared.suspension().generation(thread_id)
+    }
+
+    pub(crate) fn ensure_thread_suspended(
+        &self,
+        thread_id: VariableLengthId,
+        required_by: Command,
+    ) -> result::Result<()> {
+        if self.suspension_checks && !self.is_thread_suspended(thread_id) {
+            return Err(result::Error::ThreadNotSuspended {
+                thread: thread_id,
+                required_by,
+            });
+        }
+        Ok(())
     }
 
     async fn send_request_with_timeout(
@@ -140,69 +355,183 @@ where
         data: Vec<u8>,
         timeout_duration: Duration,
     ) -> result::Result<ReplyPacket> {
-        let id = self.next_packet_id().await;
-        let (tx, rx) = oneshot::channel();
+        self.send_request_with_hook(command, data, timeout_duration, None)
+            .await
+    }
 
-        // Register pending request
-        {
-            let mut pending = self.pending_requests.lock().await;
-            pending.insert(id, tx);
+    async fn send_request_with_hook(
+        &self,
+        command: Command,
+        data: Vec<u8>,
+        timeout_duration: Duration,
+        on_success: Option<ReplyHook>,
+    ) -> result::Result<ReplyPacket> {
+        let mut attempt = 0;
+        loop {
+            let reply = self
+                .send_request_once(
+                    command,
+                    &data,
+                    timeout_duration,
+                    on_success.clone(),
+                    None,
+                    None,
+                )
+                .await?;
+            if reply.header.is_success() {
+                return Ok(reply);
+            }
+
+            let code = self
+                .quirks()
+                .normalize_error_code(command, reply.header.error_code);
+            let error = result::Error::from_error_code(code);
+            let rule = match &error {
+                result::Error::JdwpError(code) => self.retry_policy.rule(*code),
+                _ => None,
+            };
+            match rule {
+                Some(rule) if attempt < rule.max_retries => {
+                    tokio::time::sleep(rule.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                _ => return Err(error),
+            }
+        }
+    }
+
+    /// Sends a command once and waits for its reply. With `body`, the data of a large reply is
+    /// sent there by the reader task instead of being part of the returned packet.
+    pub(crate) async fn send_request_once(
+        &self,
+        command: Command,
+        data: &[u8],
+        timeout_duration: Duration,
+        on_success: Option<ReplyHook>,
+        body: Option<mpsc::Sender<io::Result<Bytes>>>,
+        written: Option<oneshot::Sender<()>>,
+    ) -> result::Result<ReplyPacket> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
         }
+        let (tx, rx) = oneshot::channel();
 
-        // Create header
-        let header = CommandPacketHeader {
-            length: CommandPacketHeader::get_length() as u32 + data.len() as u32,
+        // Register pending request
+        let suspension_change = self
+            .sizes
+            .and_then(|sizes| SuspensionChange::for_request(command, data, sizes));
+        let id = self.shared.register_request(PendingRequest {
+            reply: tx,
+            suspension_change,
+            on_success,
+            body,
+        });
+        let _guard = PendingRequestGuard {
+            shared: &self.shared,
             id,
-            flags: 0,
-            command,
         };
 
-        // Send request
-        {
-            let mut writer = self.writer.lock().await;
-            Self::write_request(&mut *writer, &header, &data).await?;
+        let bytes = encode_command(id, command, data)?;
+        let sent = bytes.len();
+        if let Some(audit_log) = &self.audit_log {
+            audit_log.record(command, id, data);
+        }
+
+        // Send request. The writer task writes whole packets, so dropping this future can't
+        // leave a partially written packet on the stream.
+        let queued = Instant::now();
+        let (written_tx, written_rx) = oneshot::channel();
+        self.outgoing
+            .send(OutgoingPacket {
+                bytes,
+                written: Some(written_tx),
+                priority: self.command_priority,
+            })
+            .map_err(|_| io::Error::new(io::ErrorKind::NotConnected, "Connection closed"))?;
+        self.drive_until(written_rx)
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::NotConnected, "Connection closed"))??;
+        if let Some(written) = written {
+            let _ = written.send(());
         }
 
         // Wait for reply with timeout
-        match timeout(timeout_duration, rx).await {
-            Ok(Ok(reply)) => Ok(reply),
-            Ok(Err(_)) => Err(result::Error::IoError(io::Error::new(
-                io::ErrorKind::Other,
+        match self.drive_until(timeout(timeout_duration, rx)).await {
+            Ok(Ok(reply)) => {
+                self.shared.command_stats().record(
+                    command,
+                    sent,
+                    PACKET_HEADER_LENGTH + reply.data.len(),
+                    queued.elapsed(),
+                    !reply.header.is_success(),
+                );
+                Ok(reply)
+            }
+            Ok(Err(_)) => Err(result::Error::IoError(io::Error::other(
                 "Reply channel closed",
             ))),
-            Err(_) => {
-                // Timeout - clean up pending request
-                let mut pending = self.pending_requests.lock().await;
-                pending.remove(&id);
-                Err(result::Error::IoError(io::Error::new(
-                    io::ErrorKind::TimedOut,
-                    "Request timed out",
-                )))
-            }
+            Err(_) => Err(result::Error::IoError(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Request timed out",
+            ))),
         }
     }
 
-    async fn send_bodyless<TReply: for<'a> BinRead<Args<'a> = ()>>(
+    /// Sends an encoded command once and returns the reply as it is, error code included
+    pub(crate) async fn send_raw(
+        &self,
+        command: Command,
+        data: &[u8],
+        on_success: Option<ReplyHook>,
+    ) -> result::Result<ReplyPacket> {
+        self.send_request_once(command, data, self.timeout_duration, on_success, None, None)
+            .await
+    }
+
+    /// Like [JdwpClient::send_raw], but signals `written` once the command was written to the
+    /// stream, so callers can send commands in order without waiting for their replies
+    pub(crate) async fn send_raw_ordered(
+        &self,
+        command: Command,
+        data: &[u8],
+        on_success: Option<ReplyHook>,
+        written: oneshot::Sender<()>,
+    ) -> result::Result<ReplyPacket> {
+        self.send_request_once(
+            command,
+            data,
+            self.timeout_duration,
+            on_success,
+            None,
+            Some(written),
+        )
+        .await
+    }
+
+    pub(crate) fn id_sizes(&self) -> result::Result<JdwpIdSizes> {
+        self.sizes.ok_or(result::Error::IdSizesUnknown)
+    }
+
+    /// Number of requests still waiting for a reply
+    pub fn pending_request_count(&self) -> usize {
+        self.shared.pending().len()
+    }
+
+    async fn send_bodyless<TReply: for<'a> BinRead<Args<'a> = ()> + Send + 'static>(
         &self,
         cmd: Command,
         timeout_duration: Duration,
-    ) -> result::Result<TReply>
-    where
-        for<'a> <TReply as BinRead>::Args<'a>: Default,
-    {
+    ) -> result::Result<TReply> {
         let reply_packet = self
             .send_request_with_timeout(cmd, Vec::new(), timeout_duration)
             .await?;
 
-        let mut cursor = Cursor::new(&reply_packet.data);
-        let reply = TReply::read_be(&mut cursor).map_err(|e| result::Error::ParsingError {
-            message: format!("Binary parsing error: {:?}", e),
-        })?;
-
-        Ok(reply)
+        self.decode_reply(cmd, reply_packet, ()).await
     }
 
-    async fn send_bodyless_variable<TReply: for<'a> BinRead<Args<'a> = JdwpIdSizes>>(
+    async fn send_bodyless_variable<
+        TReply: for<'a> BinRead<Args<'a> = JdwpIdSizes> + Send + 'static,
+    >(
         &self,
         cmd: Command,
         timeout_duration: Duration,
@@ -211,21 +540,12 @@ where
             .send_request_with_timeout(cmd, Vec::new(), timeout_duration)
             .await?;
 
-        let mut cursor = Cursor::new(&reply_packet.data);
-        let reply = TReply::read_be_args(
-            &mut cursor,
-            self.sizes.ok_or(result::Error::IdSizesUnknown)?,
-        )
-        .map_err(|e| result::Error::ParsingError {
-            message: format!("Binary parsing error: {:?}", e),
-        })?;
-
-        Ok(reply)
+        self.decode_reply(cmd, reply_packet, self.id_sizes()?).await
     }
 
     async fn send_out_data_variable_reply<
         TOut: for<'a> BinWrite<Args<'a> = ()>,
-        TReply: for<'a> BinRead<Args<'a> = JdwpIdSizes>,
+        TReply: for<'a> BinRead<Args<'a> = JdwpIdSizes> + Send + 'static,
     >(
         &self,
         cmd: Command,
@@ -245,72 +565,142 @@ where
             .send_request_with_timeout(cmd, out_buffer, timeout_duration)
             .await?;
 
-        let mut cursor = Cursor::new(&reply_packet.data);
-        let reply = TReply::read_be_args(
-            &mut cursor,
-            self.sizes.ok_or(result::Error::IdSizesUnknown)?,
-        )
-        .map_err(|e| result::Error::ParsingError {
-            message: format!("Binary parsing error: {:?}", e),
-        })?;
-
-        Ok(reply)
+        self.decode_reply(cmd, reply_packet, self.id_sizes()?).await
     }
 
-    async fn do_handshake(stream: &mut T) -> result::Result<()> {
-        const HANDSHAKE_STR: &str = "JDWP-Handshake";
+    async fn send_out_data_reply<
+        TOut: for<'a> BinWrite<Args<'a> = ()>,
+        TReply: for<'a> BinRead<Args<'a> = ()> + Send + 'static,
+    >(
+        &self,
+        cmd: Command,
+        out: TOut,
+        timeout_duration: Duration,
+    ) -> result::Result<TReply> {
+        let mut out_buffer: Vec<u8> = Vec::new();
+
+        {
+            let mut out_cursor = Cursor::new(&mut out_buffer);
+            TOut::write_be(&out, &mut out_cursor).map_err(|e| result::Error::ParsingError {
+                message: format!("Binary parsing error: {:?}", e),
+            })?;
+        }
+
+        let reply_packet = self
+            .send_request_with_timeout(cmd, out_buffer, timeout_duration)
+            .await?;
 
-        let handshake_bytes = HANDSHAKE_STR.as_bytes();
-        stream.write_all(handshake_bytes).await?;
-        stream.flush().await?;
+        self.decode_reply(cmd, reply_packet, ()).await
+    }
 
-        let mut buffer = [0u8; 14];
-        stream.read_exact(&mut buffer).await?;
+    async fn send_variable_out_data_raw_reply<TOut: for<'a> BinWrite<Args<'a> = JdwpIdSizes>>(
+        &self,
+        cmd: Command,
+        out: TOut,
+        timeout_duration: Duration,
+    ) -> result::Result<ReplyPacket> {
+        let out_buffer = self.encode_variable_out_data(&out)?;
+        self.send_request_with_timeout(cmd, out_buffer, timeout_duration)
+            .await
+    }
 
-        let received = std::str::from_utf8(&buffer)
-            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid UTF-8"))?;
+    pub(crate) fn encode_variable_out_data<TOut: for<'a> BinWrite<Args<'a> = JdwpIdSizes>>(
+        &self,
+        out: &TOut,
+    ) -> result::Result<Vec<u8>> {
+        let sizes = self.sizes.ok_or(result::Error::IdSizesUnknown)?;
+        let mut out_buffer: Vec<u8> = Vec::new();
 
-        if received != HANDSHAKE_STR {
-            return Err(result::Error::ParsingError {
-                message: format!(
-                    "Invalid handshake: expected '{}', got '{}'",
-                    HANDSHAKE_STR, received
-                ),
-            });
+        {
+            let mut out_cursor = Cursor::new(&mut out_buffer);
+            TOut::write_be_args(out, &mut out_cursor, sizes).map_err(|e| {
+                result::Error::ParsingError {
+                    message: format!("Binary parsing error: {:?}", e),
+                }
+            })?;
         }
 
-        Ok(())
+        Ok(out_buffer)
     }
-    async fn get_id_sizes(&mut self) -> result::Result<()> {
-        let sizes = self.vm_get_id_sizes().await?;
-        let field_id: u8 = sizes
-            .field_id_size
-            .try_into()
-            .map_err(|_| result::Error::IdSizesTruncated)?;
-        let method_id: u8 = sizes
-            .method_id_size
-            .try_into()
-            .map_err(|_| result::Error::IdSizesTruncated)?;
-        let object_id: u8 = sizes
-            .object_id_size
-            .try_into()
-            .map_err(|_| result::Error::IdSizesTruncated)?;
-        let ref_id: u8 = sizes
-            .reference_type_id_size
-            .try_into()
-            .map_err(|_| result::Error::IdSizesTruncated)?;
-        let frame_id: u8 = sizes
-            .frame_id_size
-            .try_into()
-            .map_err(|_| result::Error::IdSizesTruncated)?;
-        self.sizes = Some(JdwpIdSizes {
-            field_id_size: field_id,
-            method_id_size: method_id,
-            object_id_size: object_id,
-            reference_type_id_size: ref_id,
-            frame_id_size: frame_id,
-        });
-        Ok(())
+
+    async fn send_variable_out_data_reply<
+        TOut: for<'a> BinWrite<Args<'a> = JdwpIdSizes>,
+        TReply: for<'a> BinRead<Args<'a> = ()> + Send + 'static,
+    >(
+        &self,
+        cmd: Command,
+        out: TOut,
+        timeout_duration: Duration,
+    ) -> result::Result<TReply> {
+        let reply_packet = self
+            .send_variable_out_data_raw_reply(cmd, out, timeout_duration)
+            .await?;
+
+        self.decode_reply(cmd, reply_packet, ()).await
+    }
+
+    async fn send_variable_out_data_variable_reply<
+        TOut: for<'a> BinWrite<Args<'a> = JdwpIdSizes>,
+        TReply: for<'a> BinRead<Args<'a> = JdwpIdSizes> + Send + 'static,
+    >(
+        &self,
+        cmd: Command,
+        out: TOut,
+        timeout_duration: Duration,
+    ) -> result::Result<TReply> {
+        let sizes = self.sizes.ok_or(result::Error::IdSizesUnknown)?;
+        let reply_packet = self
+            .send_variable_out_data_raw_reply(cmd, out, timeout_duration)
+            .await?;
+
+        self.decode_reply(cmd, reply_packet, sizes).await
+    }
+
+    /// Decodes the data of a successful reply, off the runtime for large replies unless the
+    /// client spawns nothing
+    async fn decode_reply<TReply, TArgs>(
+        &self,
+        cmd: Command,
+        reply: ReplyPacket,
+        args: TArgs,
+    ) -> result::Result<TReply>
+    where
+        TReply: for<'a> BinRead<Args<'a> = TArgs> + Send + 'static,
+        TArgs: Send + 'static,
+    {
+        let conformance = self.conformance();
+        let max_string_length = self.max_string_length();
+        let large = self.blocking_decode && reply.data.len() >= BLOCKING_DECODE_THRESHOLD;
+        let decode = move || {
+            conformance::decode(
+                &reply.data,
+                args,
+                conformance,
+                max_string_length,
+                format_args!("the reply to {:?}", cmd),
+            )
+        };
+        if !large {
+            return decode();
+        }
+        tokio::task::spawn_blocking(decode)
+            .await
+            .map_err(|e| result::Error::IoError(io::Error::other(e)))?
+    }
+
+    pub(crate) fn id_sizes_from_reply(sizes: IdSizesReply) -> result::Result<JdwpIdSizes> {
+        let size = |name, size: i32| {
+            u8::try_from(size).map_err(|_| result::Error::InvalidIdSize { name, size })
+        };
+        let sizes = JdwpIdSizes {
+            field_id_size: size("fieldID", sizes.field_id_size)?,
+            method_id_size: size("methodID", sizes.method_id_size)?,
+            object_id_size: size("objectID", sizes.object_id_size)?,
+            reference_type_id_size: size("referenceTypeID", sizes.reference_type_id_size)?,
+            frame_id_size: size("frameID", sizes.frame_id_size)?,
+        };
+        sizes.validate()?;
+        Ok(sizes)
     }
 
     pub async fn vm_get_version(&self) -> result::Result<VersionReply> {
@@ -352,11 +742,27 @@ where
         .await
     }
 
+    /// Closes the connection on the VM side. The VM resumes the threads suspended by this
+    /// debugger itself; with [JdwpClient::set_resume_on_shutdown] they're also resumed
+    /// explicitly beforehand.
     pub async fn vm_dispose(&self) -> result::Result<()> {
+        if self.resume_on_shutdown() {
+            self.resume_leaked_suspensions().await?;
+        }
         self.send_bodyless(Command::VirtualMachineDispose, self.timeout_duration)
             .await
     }
 
+    /// Terminates the VM with the exit code, as `System.exit` would
+    pub async fn vm_exit(&self, exit_code: i32) -> result::Result<()> {
+        self.send_out_data_reply::<_, ()>(
+            Command::VirtualMachineExit,
+            ExitOut { exit_code },
+            self.timeout_duration,
+        )
+        .await
+    }
+
     pub async fn vm_get_id_sizes(&self) -> result::Result<IdSizesReply> {
         self.send_bodyless(Command::VirtualMachineIDSizes, self.timeout_duration)
             .await
@@ -371,4 +777,1034 @@ where
         self.send_bodyless(Command::VirtualMachineResume, self.timeout_duration)
             .await
     }
+
+    pub async fn vm_hold_events(&self) -> result::Result<()> {
+        self.send_bodyless(Command::VirtualMachineHoldEvents, self.timeout_duration)
+            .await
+    }
+
+    pub async fn vm_release_events(&self) -> result::Result<()> {
+        self.send_bodyless(Command::VirtualMachineReleaseEvents, self.timeout_duration)
+            .await
+    }
+
+    /// Creates a string in the VM. The string may be garbage collected unless it's referenced or
+    /// collection is disabled for it.
+    pub async fn vm_create_string(&self, value: &str) -> result::Result<CreateStringReply> {
+        self.send_out_data_variable_reply(
+            Command::VirtualMachineCreateString,
+            CreateStringOut {
+                utf: JdwpStringSlice { value },
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    pub async fn vm_get_class_paths(&self) -> result::Result<ClassPathsReply> {
+        self.send_bodyless(Command::VirtualMachineClassPaths, self.timeout_duration)
+            .await
+    }
+
+    pub async fn vm_get_capabilities_new(&self) -> result::Result<CapabilitiesNewReply> {
+        self.send_bodyless(
+            Command::VirtualMachineCapabilitiesNew,
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// Counts the reachable instances of each type, in the order of `ref_types`. Requires the
+    /// canGetInstanceInfo capability.
+    pub async fn vm_get_instance_counts(
+        &self,
+        ref_types: Vec<VariableLengthId>,
+    ) -> result::Result<InstanceCountsReply> {
+        self.send_variable_out_data_reply(
+            Command::VirtualMachineInstanceCounts,
+            InstanceCountsOut { ref_types },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// Fetches the version, capabilities and class paths of the VM. The three commands are
+    /// sent concurrently, so this takes about as long as the slowest of them.
+    pub async fn vm_info(&self) -> result::Result<VmInfo> {
+        let (version, capabilities, class_paths) = tokio::try_join!(
+            self.vm_get_version(),
+            self.vm_get_capabilities_new(),
+            self.vm_get_class_paths()
+        )?;
+        Ok(VmInfo::new(version, capabilities, class_paths))
+    }
+
+    pub async fn method_get_line_table(
+        &self,
+        ref_type_id: VariableLengthId,
+        method_id: VariableLengthId,
+    ) -> result::Result<LineTableReply> {
+        self.send_variable_out_data_reply(
+            Command::MethodLineTable,
+            MethodOut {
+                ref_type_id,
+                method_id,
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// Local variables and arguments of a method. Fails with
+    /// [JdwpErrorCode::AbsentInformation] for classes compiled without debug information.
+    pub async fn method_get_variable_table(
+        &self,
+        ref_type_id: VariableLengthId,
+        method_id: VariableLengthId,
+    ) -> result::Result<VariableTableReply> {
+        self.send_variable_out_data_reply(
+            Command::MethodVariableTable,
+            MethodOut {
+                ref_type_id,
+                method_id,
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// Reads instance fields of an object in a single command
+    pub async fn object_get_values(
+        &self,
+        object_id: VariableLengthId,
+        fields: Vec<VariableLengthId>,
+    ) -> result::Result<GetValuesReply> {
+        self.send_variable_out_data_variable_reply(
+            Command::ObjectReferenceGetValues,
+            ObjectGetValuesOut { object_id, fields },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    pub async fn object_get_reference_type(
+        &self,
+        object_id: VariableLengthId,
+    ) -> result::Result<ObjectReferenceTypeReply> {
+        self.send_variable_out_data_variable_reply(
+            Command::ObjectReferenceReferenceType,
+            ObjectOut { object_id },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// Objects directly referencing the object, at most `max_referrers` of them or all for 0.
+    /// Requires the canGetInstanceInfo capability.
+    pub async fn object_get_referring_objects(
+        &self,
+        object_id: VariableLengthId,
+        max_referrers: i32,
+    ) -> result::Result<ReferringObjectsReply> {
+        self.send_variable_out_data_variable_reply(
+            Command::ObjectReferenceReferringObjects,
+            ReferringObjectsOut {
+                object_id,
+                max_referrers,
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    pub async fn object_invoke_method(
+        &self,
+        object_id: VariableLengthId,
+        thread_id: VariableLengthId,
+        class_id: VariableLengthId,
+        method_id: VariableLengthId,
+        arguments: Vec<JdwpValue>,
+        options: InvokeOptions,
+    ) -> result::Result<InvokeMethodReply> {
+        self.ensure_thread_suspended(thread_id, Command::ObjectReferenceInvokeMethod)?;
+        self.send_variable_out_data_variable_reply(
+            Command::ObjectReferenceInvokeMethod,
+            ObjectInvokeMethodOut {
+                object_id,
+                thread_id,
+                class_id,
+                method_id,
+                arguments,
+                options,
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    pub async fn string_get_value(
+        &self,
+        string_id: VariableLengthId,
+    ) -> result::Result<StringValueReply> {
+        self.send_variable_out_data_reply(
+            Command::StringReferenceValue,
+            ObjectOut {
+                object_id: string_id,
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    pub async fn thread_get_name(
+        &self,
+        thread_id: VariableLengthId,
+    ) -> result::Result<ThreadNameReply> {
+        self.send_variable_out_data_reply(
+            Command::ThreadReferenceName,
+            ThreadOut { thread_id },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// Name of the thread. Names are cached after the first lookup and forgotten when a
+    /// THREAD_DEATH event for the thread arrives, which requires an event request for it.
+    /// Names aren't cached for VMs with [crate::Quirks::MUTABLE_THREAD_NAMES].
+    pub async fn thread_name(&self, thread_id: VariableLengthId) -> result::Result<String> {
+        if self.quirks().contains(crate::Quirks::MUTABLE_THREAD_NAMES) {
+            return Ok(self.thread_get_name(thread_id).await?.thread_name.string);
+        }
+        let generation = {
+            let names = self.shared.thread_names();
+            if let Some(name) = names.get(thread_id) {
+                return Ok(name.clone());
+            }
+            names.generation()
+        };
+
+        let name = self.thread_get_name(thread_id).await?.thread_name.string;
+        self.shared
+            .thread_names()
+            .insert(thread_id, name.clone(), generation);
+        Ok(name)
+    }
+
+    /// Forgets all cached thread names, e.g. after threads were renamed
+    pub fn clear_thread_name_cache(&self) {
+        self.shared.thread_names().clear();
+    }
+
+    pub async fn thread_suspend(&self, thread_id: VariableLengthId) -> result::Result<()> {
+        self.send_variable_out_data_reply(
+            Command::ThreadReferenceSuspend,
+            ThreadOut { thread_id },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    pub async fn thread_resume(&self, thread_id: VariableLengthId) -> result::Result<()> {
+        self.send_variable_out_data_reply(
+            Command::ThreadReferenceResume,
+            ThreadOut { thread_id },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    pub async fn thread_get_status(
+        &self,
+        thread_id: VariableLengthId,
+    ) -> result::Result<ThreadStatusReply> {
+        self.send_variable_out_data_reply(
+            Command::ThreadReferenceStatus,
+            ThreadOut { thread_id },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// Frames of the thread's stack, the current one first. The thread must be suspended.
+    pub async fn thread_get_frames(
+        &self,
+        thread_id: VariableLengthId,
+        start_frame: i32,
+        length: i32,
+    ) -> result::Result<FramesReply> {
+        self.ensure_thread_suspended(thread_id, Command::ThreadReferenceFrames)?;
+        self.send_variable_out_data_variable_reply(
+            Command::ThreadReferenceFrames,
+            FramesOut {
+                thread_id,
+                start_frame,
+                length,
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// Number of frames on the thread's stack. The thread must be suspended.
+    pub async fn thread_frame_count(&self, thread_id: VariableLengthId) -> result::Result<i32> {
+        self.ensure_thread_suspended(thread_id, Command::ThreadReferenceFrameCount)?;
+        let reply: FrameCountReply = self
+            .send_variable_out_data_reply(
+                Command::ThreadReferenceFrameCount,
+                ThreadOut { thread_id },
+                self.timeout_duration,
+            )
+            .await?;
+        Ok(reply.frame_count)
+    }
+
+    /// Suspend count of the thread as kept by the VM, which includes suspensions by other
+    /// debuggers and agents, unlike [JdwpClient::thread_suspend_count]
+    pub async fn thread_get_suspend_count(
+        &self,
+        thread_id: VariableLengthId,
+    ) -> result::Result<i32> {
+        let reply: SuspendCountReply = self
+            .send_variable_out_data_reply(
+                Command::ThreadReferenceSuspendCount,
+                ThreadOut { thread_id },
+                self.timeout_duration,
+            )
+            .await?;
+        Ok(reply.suspend_count)
+    }
+
+    pub async fn thread_group_get_name(
+        &self,
+        group_id: VariableLengthId,
+    ) -> result::Result<ThreadGroupNameReply> {
+        self.send_variable_out_data_reply(
+            Command::ThreadGroupReferenceName,
+            ThreadGroupOut { group_id },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// Live threads and active thread groups directly in the group
+    pub async fn thread_group_get_children(
+        &self,
+        group_id: VariableLengthId,
+    ) -> result::Result<ThreadGroupChildrenReply> {
+        self.send_variable_out_data_variable_reply(
+            Command::ThreadGroupReferenceChildren,
+            ThreadGroupOut { group_id },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// Values of local variables in a frame. The thread must be suspended, and the frame id is
+    /// only valid until it's resumed; [JdwpClient::frame_get_values] checks that.
+    pub async fn stack_frame_get_values(
+        &self,
+        thread_id: VariableLengthId,
+        frame_id: VariableLengthId,
+        slots: Vec<FrameSlot>,
+    ) -> result::Result<GetValuesReply> {
+        self.ensure_thread_suspended(thread_id, Command::StackFrameGetValues)?;
+        self.send_variable_out_data_variable_reply(
+            Command::StackFrameGetValues,
+            StackFrameGetValuesOut {
+                thread_id,
+                frame_id,
+                slots,
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// The object `this` refers to in a frame, null (0) in static and native methods. The
+    /// thread must be suspended.
+    pub async fn stack_frame_this_object(
+        &self,
+        thread_id: VariableLengthId,
+        frame_id: VariableLengthId,
+    ) -> result::Result<ThisObjectReply> {
+        self.ensure_thread_suspended(thread_id, Command::StackFrameThisObject)?;
+        self.send_variable_out_data_variable_reply(
+            Command::StackFrameThisObject,
+            StackFrameOut {
+                thread_id,
+                frame_id,
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// The type a class object (`java.lang.Class` instance) represents
+    pub async fn class_object_get_reflected_type(
+        &self,
+        class_object_id: VariableLengthId,
+    ) -> result::Result<ReflectedTypeReply> {
+        self.send_variable_out_data_variable_reply(
+            Command::ClassObjectReferenceReflectedType,
+            ObjectOut {
+                object_id: class_object_id,
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    pub async fn array_get_length(
+        &self,
+        array_id: VariableLengthId,
+    ) -> result::Result<ArrayLengthReply> {
+        self.send_variable_out_data_reply(
+            Command::ArrayReferenceLength,
+            ArrayLengthOut { array_id },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    pub async fn array_get_values(
+        &self,
+        array_id: VariableLengthId,
+        first_index: i32,
+        length: i32,
+    ) -> result::Result<ArrayGetValuesReply> {
+        self.send_variable_out_data_variable_reply(
+            Command::ArrayReferenceGetValues,
+            ArrayGetValuesOut {
+                array_id,
+                first_index,
+                length,
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// Creates an event request. Events it generates can be awaited on the returned handle, or
+    /// read from the global event stream.
+    pub async fn event_request_set(
+        &self,
+        event_kind: EventKind,
+        suspend_policy: SuspendPolicy,
+        modifiers: Vec<EventModifier>,
+    ) -> result::Result<EventRequestHandle> {
+        let (route, hits) = self.event_channel();
+        let request_id = self
+            .event_request_set_routed(event_kind, suspend_policy, modifiers, route)
+            .await?;
+        Ok(EventRequestHandle::new(
+            request_id,
+            event_kind,
+            suspend_policy,
+            hits,
+        ))
+    }
+
+    /// Creates an event request whose events are delivered to `route`. Several requests may
+    /// share a route.
+    pub(crate) async fn event_request_set_routed(
+        &self,
+        event_kind: EventKind,
+        suspend_policy: SuspendPolicy,
+        modifiers: Vec<EventModifier>,
+        route: EventSender,
+    ) -> result::Result<i32> {
+        let out_buffer = self.encode_variable_out_data(&EventRequestSetOut {
+            event_kind,
+            suspend_policy,
+            modifiers,
+        })?;
+
+        // The route is added by the reader task as soon as the reply arrives, so events sent
+        // right after it can't miss the handle
+        let add_route: ReplyHook = Arc::new(move |shared, data| {
+            if let Ok(reply) = EventRequestSetReply::read_be(&mut Cursor::new(data)) {
+                shared.bus().add_route(reply.request_id, route.clone());
+                shared.event_requests().insert(reply.request_id, event_kind);
+            }
+        });
+        let reply_packet = self
+            .send_request_with_hook(
+                Command::EventRequestSet,
+                out_buffer,
+                self.timeout_duration,
+                Some(add_route),
+            )
+            .await?;
+
+        let reply: EventRequestSetReply = self
+            .decode_reply(Command::EventRequestSet, reply_packet, ())
+            .await?;
+        Ok(reply.request_id)
+    }
+
+    /// Clears an event request. A handle for it stops receiving events.
+    pub async fn event_request_clear(
+        &self,
+        event_kind: EventKind,
+        request_id: i32,
+    ) -> result::Result<()> {
+        self.send_out_data_reply::<_, ()>(
+            Command::EventRequestClear,
+            EventRequestClearOut {
+                event_kind,
+                request_id,
+            },
+            self.timeout_duration,
+        )
+        .await?;
+        self.shared.bus().remove_route(request_id);
+        self.shared.event_requests().remove(&request_id);
+        Ok(())
+    }
+
+    /// Clears every breakpoint request of this debugger, including those created by other
+    /// means than this client
+    pub async fn event_request_clear_all_breakpoints(&self) -> result::Result<()> {
+        self.send_bodyless::<()>(
+            Command::EventRequestClearAllBreakpoints,
+            self.timeout_duration,
+        )
+        .await?;
+        self.shared
+            .event_requests()
+            .retain(|_, kind| *kind != EventKind::Breakpoint);
+        Ok(())
+    }
+
+    /// Clears every event request created through this connection and not cleared yet, then
+    /// all breakpoints, so a tool can detach without leaving requests behind. Handles of the
+    /// requests stop receiving events, and helpers relying on their own requests, such as the
+    /// class cache, stop being updated.
+    ///
+    /// Clearing goes on after a failure; the first error is returned once everything was
+    /// tried.
+    pub async fn clear_all_event_requests(&self) -> result::Result<()> {
+        let requests: Vec<_> = self
+            .shared
+            .event_requests()
+            .iter()
+            .map(|(request_id, kind)| (*request_id, *kind))
+            .collect();
+        let mut first_error = None;
+        for (request_id, kind) in requests {
+            if let Err(e) = self.event_request_clear(kind, request_id).await {
+                first_error.get_or_insert(e);
+            }
+        }
+        if let Err(e) = self.event_request_clear_all_breakpoints().await {
+            first_error.get_or_insert(e);
+        }
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads a whole array, fetching at most [DEFAULT_ARRAY_CHUNK_SIZE] elements per request
+    pub async fn read_array(&self, array_id: VariableLengthId) -> result::Result<ArrayValues> {
+        self.read_array_chunked(array_id, DEFAULT_ARRAY_CHUNK_SIZE)
+            .await
+    }
+
+    /// Reads a whole array, fetching at most `chunk_size` elements per request
+    pub async fn read_array_chunked(
+        &self,
+        array_id: VariableLengthId,
+        chunk_size: i32,
+    ) -> result::Result<ArrayValues> {
+        if chunk_size <= 0 {
+            return Err(result::Error::InvalidArgument {
+                message: format!("Invalid array chunk size: {}", chunk_size),
+            });
+        }
+
+        let length = self.array_get_length(array_id).await?.array_length;
+        let mut first_index = 0;
+        let mut values: Option<ArrayValues> = None;
+        while first_index < length {
+            let count = chunk_size.min(length - first_index);
+            let chunk = self
+                .array_get_values(array_id, first_index, count)
+                .await?
+                .values;
+            match values.as_mut() {
+                Some(values) => {
+                    if !values.append(chunk) {
+                        return Err(result::Error::ParsingError {
+                            message: String::from("Array element type changed between chunks"),
+                        });
+                    }
+                }
+                None => values = Some(chunk),
+            }
+            first_index += count;
+        }
+
+        // An empty array has no region to take the element type from, so fetch an empty one
+        match values {
+            Some(values) => Ok(values),
+            None => Ok(self.array_get_values(array_id, 0, 0).await?.values),
+        }
+    }
+
+    /// Reads a whole `byte[]` array without parsing it element by element.
+    ///
+    /// Arrays that fit in a single chunk are returned as a slice of the reply buffer.
+    pub async fn read_byte_array(&self, array_id: VariableLengthId) -> result::Result<Bytes> {
+        self.read_byte_array_chunked(array_id, DEFAULT_ARRAY_CHUNK_SIZE)
+            .await
+    }
+
+    /// Reads a whole `byte[]` array, fetching at most `chunk_size` elements per request
+    pub async fn read_byte_array_chunked(
+        &self,
+        array_id: VariableLengthId,
+        chunk_size: i32,
+    ) -> result::Result<Bytes> {
+        if chunk_size <= 0 {
+            return Err(result::Error::InvalidArgument {
+                message: format!("Invalid array chunk size: {}", chunk_size),
+            });
+        }
+
+        let length = self.array_get_length(array_id).await?.array_length;
+        if length <= chunk_size {
+            return self.get_byte_array_region(array_id, 0, length).await;
+        }
+
+        let mut bytes = BytesMut::with_capacity(length as usize);
+        let mut first_index = 0;
+        while first_index < length {
+            let count = chunk_size.min(length - first_index);
+            bytes.extend_from_slice(
+                &self
+                    .get_byte_array_region(array_id, first_index, count)
+                    .await?,
+            );
+            first_index += count;
+        }
+        Ok(bytes.freeze())
+    }
+
+    /// Reads a whole `char[]` array as a string, combining UTF-16 surrogate pairs. Unpaired
+    /// surrogates are an error; use [ArrayValues::chars_to_string_lossy] to replace them.
+    pub async fn read_char_array(&self, array_id: VariableLengthId) -> result::Result<String> {
+        let units = match self.read_array(array_id).await? {
+            ArrayValues::Primitive(PrimitiveArray::Char(units)) => units,
+            values => {
+                return Err(result::Error::TypeMismatch {
+                    expected: "char[]",
+                    found: values.element_tag(),
+                });
+            }
+        };
+        String::from_utf16(&units).map_err(|_| result::Error::InvalidArgument {
+            message: String::from("Unpaired surrogate in char array"),
+        })
+    }
+
+    async fn get_byte_array_region(
+        &self,
+        array_id: VariableLengthId,
+        first_index: i32,
+        length: i32,
+    ) -> result::Result<Bytes> {
+        // arrayregion: byte tag, int count, then the untagged values
+        const REGION_HEADER_LENGTH: usize = 1 + 4;
+
+        let reply_packet = self
+            .send_variable_out_data_raw_reply(
+                Command::ArrayReferenceGetValues,
+                ArrayGetValuesOut {
+                    array_id,
+                    first_index,
+                    length,
+                },
+                self.timeout_duration,
+            )
+            .await?;
+
+        let data = Bytes::from(reply_packet.data);
+        if data.len() < REGION_HEADER_LENGTH {
+            return Err(result::Error::ParsingError {
+                message: String::from("Array region is truncated"),
+            });
+        }
+        if data[0] != Tag::Byte as u8 {
+            return Err(result::Error::ParsingError {
+                message: format!("Expected a byte array region, got tag {}", data[0]),
+            });
+        }
+
+        let count = u32::from_be_bytes([data[1], data[2], data[3], data[4]]) as usize;
+        if data.len() - REGION_HEADER_LENGTH < count {
+            return Err(result::Error::ParsingError {
+                message: String::from("Array region is truncated"),
+            });
+        }
+        Ok(data.slice(REGION_HEADER_LENGTH..REGION_HEADER_LENGTH + count))
+    }
+
+    pub async fn ref_type_get_signature(
+        &self,
+        ref_type_id: VariableLengthId,
+    ) -> result::Result<SignatureReply> {
+        self.send_variable_out_data_reply(
+            Command::ReferenceTypeSignature,
+            RefTypeOut { ref_type_id },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    pub async fn ref_type_get_fields(
+        &self,
+        ref_type_id: VariableLengthId,
+    ) -> result::Result<FieldsReply> {
+        self.send_variable_out_data_variable_reply(
+            Command::ReferenceTypeFields,
+            RefTypeOut { ref_type_id },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    pub async fn ref_type_get_methods(
+        &self,
+        ref_type_id: VariableLengthId,
+    ) -> result::Result<MethodsReply> {
+        self.send_variable_out_data_variable_reply(
+            Command::ReferenceTypeMethods,
+            RefTypeOut { ref_type_id },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// Reads static fields of a type in a single command
+    pub async fn ref_type_get_values(
+        &self,
+        ref_type_id: VariableLengthId,
+        fields: Vec<VariableLengthId>,
+    ) -> result::Result<GetValuesReply> {
+        self.send_variable_out_data_variable_reply(
+            Command::ReferenceTypeGetValues,
+            RefTypeGetValuesOut {
+                ref_type_id,
+                fields,
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// Fails with ABSENT_INFORMATION if the class wasn't compiled with a source file attribute
+    pub async fn ref_type_get_source_file(
+        &self,
+        ref_type_id: VariableLengthId,
+    ) -> result::Result<SourceFileReply> {
+        self.send_variable_out_data_reply(
+            Command::ReferenceTypeSourceFile,
+            RefTypeOut { ref_type_id },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    pub async fn ref_type_get_interfaces(
+        &self,
+        ref_type_id: VariableLengthId,
+    ) -> result::Result<InterfacesReply> {
+        self.send_variable_out_data_variable_reply(
+            Command::ReferenceTypeInterfaces,
+            RefTypeOut { ref_type_id },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// The class object (`java.lang.Class` instance) of a type, the inverse of
+    /// [JdwpClient::class_object_get_reflected_type]
+    pub async fn ref_type_get_class_object(
+        &self,
+        ref_type_id: VariableLengthId,
+    ) -> result::Result<ClassObjectReply> {
+        self.send_variable_out_data_variable_reply(
+            Command::ReferenceTypeClassObject,
+            RefTypeOut { ref_type_id },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// Requires the can_get_source_debug_extension capability. Fails with ABSENT_INFORMATION if
+    /// the class has no SourceDebugExtension attribute.
+    pub async fn ref_type_get_source_debug_extension(
+        &self,
+        ref_type_id: VariableLengthId,
+    ) -> result::Result<SourceDebugExtensionReply> {
+        self.send_variable_out_data_reply(
+            Command::ReferenceTypeSourceDebugExtension,
+            RefTypeOut { ref_type_id },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// Reachable instances of the type, at most `max_instances` of them or all for 0. Requires
+    /// the canGetInstanceInfo capability.
+    pub async fn ref_type_get_instances(
+        &self,
+        ref_type_id: VariableLengthId,
+        max_instances: i32,
+    ) -> result::Result<InstancesReply> {
+        self.send_variable_out_data_variable_reply(
+            Command::ReferenceTypeInstances,
+            InstancesOut {
+                ref_type_id,
+                max_instances,
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    pub async fn class_type_get_superclass(
+        &self,
+        class_id: VariableLengthId,
+    ) -> result::Result<SuperclassReply> {
+        self.send_variable_out_data_variable_reply(
+            Command::ClassTypeSuperclass,
+            RefTypeOut {
+                ref_type_id: class_id,
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    pub async fn class_type_invoke_method(
+        &self,
+        class_id: VariableLengthId,
+        thread_id: VariableLengthId,
+        method_id: VariableLengthId,
+        arguments: Vec<JdwpValue>,
+        options: InvokeOptions,
+    ) -> result::Result<InvokeMethodReply> {
+        self.ensure_thread_suspended(thread_id, Command::ClassTypeInvokeMethod)?;
+        self.send_variable_out_data_variable_reply(
+            Command::ClassTypeInvokeMethod,
+            ClassInvokeMethodOut {
+                class_id,
+                thread_id,
+                method_id,
+                arguments,
+                options,
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    pub async fn class_type_new_instance(
+        &self,
+        class_id: VariableLengthId,
+        thread_id: VariableLengthId,
+        method_id: VariableLengthId,
+        arguments: Vec<JdwpValue>,
+        options: InvokeOptions,
+    ) -> result::Result<ClassNewInstanceReply> {
+        self.ensure_thread_suspended(thread_id, Command::ClassTypeNewInstance)?;
+        self.send_variable_out_data_variable_reply(
+            Command::ClassTypeNewInstance,
+            ClassNewInstanceOut {
+                class_id,
+                thread_id,
+                method_id,
+                arguments,
+                options,
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    pub async fn array_type_new_instance(
+        &self,
+        array_type_id: VariableLengthId,
+        length: i32,
+    ) -> result::Result<ArrayNewInstanceReply> {
+        self.send_variable_out_data_variable_reply(
+            Command::ArrayTypeNewInstance,
+            ArrayNewInstanceOut {
+                array_type_id,
+                length,
+            },
+            self.timeout_duration,
+        )
+        .await
+    }
+
+    /// Allocates a new array in the debuggee, e.g. `new_array("int", 16)` for `new int[16]`.
+    ///
+    /// The array type must already be loaded by the VM.
+    pub async fn new_array(
+        &self,
+        element_type_name: &str,
+        length: i32,
+    ) -> result::Result<TaggedObjectId> {
+        let signature = format!("[{}", type_name_to_signature(element_type_name));
+        let array_type_id = self.find_loaded_class(&signature).await?;
+        Ok(self
+            .array_type_new_instance(array_type_id, length)
+            .await?
+            .new_array)
+    }
+
+    /// Creates a new object in the debuggee by invoking the constructor of `class_name` whose
+    /// parameters match `arguments`, e.g. `(16,)` or `&[JdwpValue::Int(16)]`. `thread_id` must be
+    /// suspended by an event.
+    pub async fn new_object(
+        &self,
+        thread_id: VariableLengthId,
+        class_name: &str,
+        arguments: impl IntoJdwpArguments,
+    ) -> result::Result<TaggedObjectId> {
+        self.ensure_thread_suspended(thread_id, Command::ClassTypeNewInstance)?;
+        let arguments = arguments.into_jdwp_arguments(self).await?;
+        let signature = type_name_to_signature(class_name);
+        let class_id = self.find_loaded_class(&signature).await?;
+
+        let constructor = self.resolve_method(class_id, "<init>", &arguments).await?;
+
+        let reply = self
+            .class_type_new_instance(
+                class_id,
+                thread_id,
+                constructor.method_id,
+                arguments,
+                InvokeOptions::empty(),
+            )
+            .await?;
+        if reply.exception.object_id.value != 0 {
+            return Err(result::Error::InvocationException {
+                exception: ExceptionHandle::new(reply.exception, thread_id),
+            });
+        }
+        Ok(reply.new_object)
+    }
+
+    /// Finds a method by name and descriptor, e.g. `("toString", "()Ljava/lang/String;")`,
+    /// declared by the type or inherited from its superclasses
+    pub async fn find_method(
+        &self,
+        type_id: VariableLengthId,
+        name: &str,
+        signature: &str,
+    ) -> result::Result<MethodsReplyMethod> {
+        let mut matches = self
+            .methods_in_hierarchy(type_id, name, |method| method.signature == signature)
+            .await?;
+        match matches.pop() {
+            Some(method) => Ok(method),
+            None => Err(result::Error::MethodNotFound {
+                name: String::from(name),
+                signature: String::from(signature),
+            }),
+        }
+    }
+
+    /// Picks the overload of a method which accepts the argument values, searching the type and
+    /// then its superclasses. Object arguments match any reference parameter, so overloads which
+    /// only differ in reference parameter types are reported as ambiguous.
+    pub async fn resolve_method(
+        &self,
+        type_id: VariableLengthId,
+        name: &str,
+        arguments: &[JdwpValue],
+    ) -> result::Result<MethodsReplyMethod> {
+        let mut matches = self
+            .methods_in_hierarchy(type_id, name, |method| {
+                arguments_match(&method.signature.string, arguments)
+            })
+            .await?;
+        match (matches.pop(), matches.is_empty()) {
+            (Some(method), true) => Ok(method),
+            (None, _) => Err(result::Error::MethodNotFound {
+                name: String::from(name),
+                signature: format!("with {} argument(s)", arguments.len()),
+            }),
+            (Some(_), false) => Err(result::Error::InvalidArgument {
+                message: format!("Ambiguous method {} for the given arguments", name),
+            }),
+        }
+    }
+
+    /// Methods named `name` accepted by `filter` in the first type of the hierarchy which has
+    /// any. Constructors are not inherited, so they are only looked up in the type itself.
+    async fn methods_in_hierarchy(
+        &self,
+        type_id: VariableLengthId,
+        name: &str,
+        filter: impl Fn(&MethodsReplyMethod) -> bool,
+    ) -> result::Result<Vec<MethodsReplyMethod>> {
+        let mut current = type_id;
+        loop {
+            let matches: Vec<_> = self
+                .ref_type_get_methods(current)
+                .await?
+                .methods
+                .into_iter()
+                .filter(|method| method.name == name && filter(method))
+                .collect();
+            if !matches.is_empty() || name == "<init>" {
+                return Ok(matches);
+            }
+
+            // Interfaces and arrays have no superclass to continue with
+            current = match self.class_type_get_superclass(current).await {
+                Ok(reply) if reply.superclass.value != 0 => reply.superclass,
+                Ok(_) | Err(result::Error::JdwpError(JdwpErrorCode::InvalidClass)) => {
+                    return Ok(matches);
+                }
+                Err(e) => return Err(e),
+            };
+        }
+    }
+
+    pub(crate) async fn find_loaded_class(
+        &self,
+        signature: &str,
+    ) -> result::Result<VariableLengthId> {
+        self.vm_get_classes_by_signature(signature)
+            .await?
+            .classes
+            .first()
+            .map(|class| class.type_id)
+            .ok_or_else(|| result::Error::ClassNotFound {
+                signature: String::from(signature),
+            })
+    }
+}
+
+/// Checks whether `arguments` can be passed to a method with the given descriptor. Object
+/// arguments are accepted for any reference parameter, as sub-typing is checked by the VM.
+fn arguments_match(descriptor: &str, arguments: &[JdwpValue]) -> bool {
+    let Ok((params, _)) = parse_method_descriptor(descriptor) else {
+        return false;
+    };
+    params.len() == arguments.len()
+        && params
+            .iter()
+            .zip(arguments)
+            .all(|(param, argument)| match signature_tag(param) {
+                Some(tag) if tag.is_object() => argument.tag().is_object(),
+                Some(tag) => tag == argument.tag(),
+                None => false,
+            })
 }
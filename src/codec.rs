@@ -0,0 +1,175 @@
+//! Packet framing independent of any async runtime
+//!
+//! The client drives these functions from tokio tasks. Other runtimes, or transports such as
+//! WebSockets which deliver bytes in chunks of their own, can push whatever they receive into a
+//! [PacketDecoder] and write the bytes of [encode_command] and [encode_reply] themselves. The
+//! framing itself lives in the `jdwp-codec` crate, which only needs `alloc`; this module adds
+//! the typed headers of this crate on top.
+
+use binrw::{BinRead, BinWrite};
+use std::io::Cursor;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{Command, CommandPacketHeader, ReplyPacketHeader, result};
+
+/// Length of command and reply headers, which only differ after the flags byte
+pub const PACKET_HEADER_LENGTH: usize = jdwp_codec::HEADER_LENGTH;
+
+#[derive(Debug, Clone)]
+pub struct ReplyPacket {
+    pub header: ReplyPacketHeader,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandPacket {
+    pub header: CommandPacketHeader,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub enum IncomingPacket {
+    Reply(ReplyPacket),
+    Command(CommandPacket),
+    /// A command packet with a command this client does not know
+    Unknown,
+}
+
+/// Hands out the ids of outgoing command packets. Ids wrap around after `u32::MAX`.
+#[derive(Debug)]
+pub struct PacketIdAllocator {
+    last: AtomicU32,
+}
+impl PacketIdAllocator {
+    /// Starts after `last`, e.g. the id of a packet sent before the allocator existed
+    pub fn new(last: u32) -> Self {
+        PacketIdAllocator {
+            last: AtomicU32::new(last),
+        }
+    }
+
+    pub fn next_id(&self) -> u32 {
+        self.last.fetch_add(1, Ordering::Relaxed).wrapping_add(1)
+    }
+}
+
+pub fn encode_command(id: u32, command: Command, data: &[u8]) -> result::Result<Vec<u8>> {
+    let mut command_bytes = [0u8; 2];
+    command
+        .write_be(&mut Cursor::new(&mut command_bytes[..]))
+        .map_err(|e| result::Error::ParsingError {
+            message: format!("Serialization error: {:?}", e),
+        })?;
+    Ok(jdwp_codec::encode_command(
+        id,
+        command_bytes[0],
+        command_bytes[1],
+        data,
+    )?)
+}
+
+pub fn encode_reply(id: u32, error_code: u16, data: &[u8]) -> result::Result<Vec<u8>> {
+    Ok(jdwp_codec::encode_reply(id, error_code, data)?)
+}
+
+/// Length of the whole packet a header starts, including the header
+pub fn packet_length(header: &[u8; PACKET_HEADER_LENGTH]) -> result::Result<usize> {
+    Ok(jdwp_codec::Header::decode(header)?.length as usize)
+}
+
+/// Decodes a packet from its header and the data following it
+pub fn decode_packet(
+    header: &[u8; PACKET_HEADER_LENGTH],
+    data: Vec<u8>,
+) -> result::Result<IncomingPacket> {
+    let mut cursor = Cursor::new(header);
+    if header[8] & jdwp_codec::FLAG_REPLY != 0 {
+        let header =
+            ReplyPacketHeader::read_be(&mut cursor).map_err(|e| result::Error::ParsingError {
+                message: format!("Parsing error: {:?}", e),
+            })?;
+        Ok(IncomingPacket::Reply(ReplyPacket { header, data }))
+    } else {
+        match CommandPacketHeader::read_be(&mut cursor) {
+            Ok(header) => Ok(IncomingPacket::Command(CommandPacket { header, data })),
+            Err(_) => Ok(IncomingPacket::Unknown),
+        }
+    }
+}
+
+/// Splits a byte stream into packets, whatever chunks it arrives in
+#[derive(Debug, Default)]
+pub struct PacketDecoder {
+    inner: jdwp_codec::PacketDecoder,
+}
+impl PacketDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends received bytes
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.inner.push(bytes);
+    }
+
+    /// Takes the next complete packet, None if more bytes are needed. An invalid length
+    /// leaves the stream out of sync, so the decoder shouldn't be used after an error.
+    pub fn next_packet(&mut self) -> result::Result<Option<IncomingPacket>> {
+        match self.inner.next_packet()? {
+            Some(packet) => decode_packet(&packet.header.encode(), packet.data).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Number of received bytes not part of a complete packet yet
+    pub fn buffered_len(&self) -> usize {
+        self.inner.buffered_len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_split_packets() {
+        let mut bytes = encode_reply(7, 0, &[0x1, 0x2]).unwrap();
+        bytes.extend(encode_command(8, Command::EventComposite, &[0x3]).unwrap());
+
+        let mut decoder = PacketDecoder::new();
+        decoder.push(&bytes[..5]);
+        assert!(decoder.next_packet().unwrap().is_none());
+        decoder.push(&bytes[5..20]);
+        match decoder.next_packet().unwrap() {
+            Some(IncomingPacket::Reply(reply)) => {
+                assert_eq!(reply.header.id, 7);
+                assert_eq!(reply.data, vec![0x1, 0x2]);
+            }
+            _ => panic!("expected a reply"),
+        }
+        assert!(decoder.next_packet().unwrap().is_none());
+        decoder.push(&bytes[20..]);
+        match decoder.next_packet().unwrap() {
+            Some(IncomingPacket::Command(command)) => {
+                assert_eq!(command.header.command, Command::EventComposite);
+                assert_eq!(command.data, vec![0x3]);
+            }
+            _ => panic!("expected a command"),
+        }
+        assert_eq!(decoder.buffered_len(), 0);
+    }
+
+    #[test]
+    fn test_invalid_length() {
+        let mut decoder = PacketDecoder::new();
+        decoder.push(&[0x0, 0x0, 0x0, 0x2, 0x0, 0x0, 0x0, 0x1, 0x80, 0x0, 0x0]);
+        assert!(decoder.next_packet().is_err());
+    }
+
+    #[test]
+    fn test_packet_ids_wrap() {
+        let ids = PacketIdAllocator::new(u32::MAX - 1);
+        assert_eq!(ids.next_id(), u32::MAX);
+        assert_eq!(ids.next_id(), 0);
+    }
+}
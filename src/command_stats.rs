@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use crate::{Command, JdwpClient};
+
+/// Upper bounds of the round trip time buckets of [CommandStats]. Longer round trips fall into
+/// one more bucket.
+pub const RTT_BUCKET_BOUNDS: [Duration; 12] = [
+    Duration::from_micros(100),
+    Duration::from_micros(250),
+    Duration::from_micros(500),
+    Duration::from_millis(1),
+    Duration::from_micros(2500),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_secs(1),
+];
+
+/// Traffic and round trip times of one kind of command, see [JdwpClient::command_stats]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandStats {
+    pub command: Command,
+    /// Replies received, error replies included
+    pub count: u64,
+    /// Replies with an error code
+    pub errors: u64,
+    /// Bytes of the command packets, headers included
+    pub bytes_sent: u64,
+    /// Bytes of the reply packets, headers included
+    pub bytes_received: u64,
+    /// Time from queuing the commands to receiving their replies, summed
+    pub total_rtt: Duration,
+    pub max_rtt: Duration,
+    /// Number of round trips per bucket of [RTT_BUCKET_BOUNDS], plus one for longer ones
+    pub rtt_buckets: [u64; RTT_BUCKET_BOUNDS.len() + 1],
+}
+impl CommandStats {
+    fn new(command: Command) -> Self {
+        CommandStats {
+            command,
+            count: 0,
+            errors: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            total_rtt: Duration::ZERO,
+            max_rtt: Duration::ZERO,
+            rtt_buckets: [0; RTT_BUCKET_BOUNDS.len() + 1],
+        }
+    }
+
+    pub fn mean_rtt(&self) -> Duration {
+        match u32::try_from(self.count) {
+            Ok(0) => Duration::ZERO,
+            Ok(count) => self.total_rtt / count,
+            Err(_) => Duration::from_secs_f64(self.total_rtt.as_secs_f64() / self.count as f64),
+        }
+    }
+
+    /// Round trip time `quantile` (0.0 to 1.0) of the round trips fall under, rounded up to a
+    /// bucket bound and capped at [CommandStats::max_rtt]
+    pub fn rtt_quantile(&self, quantile: f64) -> Duration {
+        let rank = (quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64;
+        let mut seen = 0;
+        for (bucket, count) in self.rtt_buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank.max(1) {
+                return RTT_BUCKET_BOUNDS
+                    .get(bucket)
+                    .map_or(self.max_rtt, |bound| (*bound).min(self.max_rtt));
+            }
+        }
+        self.max_rtt
+    }
+
+    fn record(&mut self, sent: usize, received: usize, rtt: Duration, error: bool) {
+        self.count += 1;
+        self.errors += u64::from(error);
+        self.bytes_sent += sent as u64;
+        self.bytes_received += received as u64;
+        self.total_rtt += rtt;
+        self.max_rtt = self.max_rtt.max(rtt);
+        let bucket = RTT_BUCKET_BOUNDS.partition_point(|bound| *bound < rtt);
+        self.rtt_buckets[bucket] += 1;
+    }
+}
+
+/// Statistics of all commands sent on a connection
+#[derive(Debug, Default)]
+pub(crate) struct CommandStatsRecorder {
+    commands: HashMap<Command, CommandStats>,
+}
+impl CommandStatsRecorder {
+    pub(crate) fn record(
+        &mut self,
+        command: Command,
+        sent: usize,
+        received: usize,
+        rtt: Duration,
+        error: bool,
+    ) {
+        self.commands
+            .entry(command)
+            .or_insert_with(|| CommandStats::new(command))
+            .record(sent, received, rtt, error);
+    }
+}
+
+/// The commands which kept callers waiting the longest, see [JdwpClient::slow_command_report]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowCommandReport {
+    /// Most total round trip time first
+    pub commands: Vec<CommandStats>,
+    /// Round trip time of all commands, including those left out of the report
+    pub total_rtt: Duration,
+}
+impl fmt::Display for SlowCommandReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<48} {:>8} {:>10} {:>6} {:>10} {:>10} {:>10} {:>12}",
+            "command", "count", "total ms", "share", "mean ms", "p95 ms", "max ms", "bytes in"
+        )?;
+        let total = self.total_rtt.as_secs_f64();
+        for stats in &self.commands {
+            let share = if total > 0.0 {
+                stats.total_rtt.as_secs_f64() / total * 100.0
+            } else {
+                0.0
+            };
+            writeln!(
+                f,
+                "{:<48} {:>8} {:>10.1} {:>5.1}% {:>10.2} {:>10.2} {:>10.2} {:>12}",
+                format!("{:?}", stats.command),
+                stats.count,
+                millis(stats.total_rtt),
+                share,
+                millis(stats.mean_rtt()),
+                millis(stats.rtt_quantile(0.95)),
+                millis(stats.max_rtt),
+                stats.bytes_received,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+impl JdwpClient {
+    /// Traffic and round trip times of every kind of command sent on the connection, by all
+    /// handles, since it was opened or [JdwpClient::reset_command_stats], most frequent first.
+    /// Commands without a reply, e.g. timed out ones, aren't counted.
+    pub fn command_stats(&self) -> Vec<CommandStats> {
+        let mut stats: Vec<_> = self
+            .shared
+            .command_stats()
+            .commands
+            .values()
+            .cloned()
+            .collect();
+        stats.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| b.total_rtt.cmp(&a.total_rtt))
+        });
+        stats
+    }
+
+    pub fn reset_command_stats(&self) {
+        self.shared.command_stats().commands.clear();
+    }
+
+    /// The `limit` kinds of commands with the most total round trip time, the best candidates
+    /// for caching or batching. Prints as a table.
+    pub fn slow_command_report(&self, limit: usize) -> SlowCommandReport {
+        let mut commands = self.command_stats();
+        let total_rtt = commands.iter().map(|stats| stats.total_rtt).sum();
+        commands.sort_by(|a, b| {
+            b.total_rtt
+                .cmp(&a.total_rtt)
+                .then_with(|| b.count.cmp(&a.count))
+        });
+        commands.truncate(limit);
+        SlowCommandReport {
+            commands,
+            total_rtt,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rtt_quantile() {
+        let mut stats = CommandStats::new(Command::VirtualMachineAllClasses);
+        for millis in [1, 1, 2, 3, 4, 6, 8, 9, 20, 700] {
+            stats.record(11, 15, Duration::from_millis(millis), false);
+        }
+        assert_eq!(stats.count, 10);
+        assert_eq!(stats.mean_rtt(), Duration::from_micros(75400));
+        assert_eq!(stats.rtt_quantile(0.5), Duration::from_millis(5));
+        assert_eq!(stats.rtt_quantile(0.9), Duration::from_millis(25));
+        assert_eq!(stats.rtt_quantile(1.0), Duration::from_millis(700));
+        assert_eq!(stats.rtt_quantile(0.0), Duration::from_millis(1));
+    }
+}
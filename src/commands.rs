@@ -1,12 +1,14 @@
-use binrw::{BinRead, binrw, binwrite};
+use binrw::{BinRead, BinWrite, binread, binrw, binwrite};
 
 use crate::{
-    ClassStatus, JdwpIdSize, JdwpIdSizes, JdwpString, JdwpStringSlice, TypeTag, binrw_enum,
+    ArrayValues, ClassStatus, CommandSet, EventKind, EventModifier, InvokeOptions, JdwpIdSize,
+    JdwpIdSizes, JdwpString, JdwpStringSlice, JdwpValue, Location, PacketFlags, SuspendPolicy,
+    SuspendStatus, Tag, TaggedObjectId, ThreadStatus, TypeTag, binrw_enum,
 };
 
 binrw_enum! {
     #[repr(u16)]
-    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub enum Command {
         VirtualMachineVersion =                 (1 << 8) | 1,
         VirtualMachineClassesBySignature =      (1 << 8) | 2,
@@ -17,49 +19,157 @@ binrw_enum! {
         VirtualMachineIDSizes =                 (1 << 8) | 7,
         VirtualMachineSuspend =                 (1 << 8) | 8,
         VirtualMachineResume =                  (1 << 8) | 9,
+        VirtualMachineExit =                    (1 << 8) | 10,
+        VirtualMachineCreateString =            (1 << 8) | 11,
+        VirtualMachineClassPaths =              (1 << 8) | 13,
+        VirtualMachineHoldEvents =              (1 << 8) | 15,
+        VirtualMachineReleaseEvents =           (1 << 8) | 16,
+        VirtualMachineCapabilitiesNew =         (1 << 8) | 17,
+        VirtualMachineRedefineClasses =         (1 << 8) | 18,
+        VirtualMachineInstanceCounts =          (1 << 8) | 21,
+        ReferenceTypeSignature =                (2 << 8) | 1,
+        ReferenceTypeFields =                   (2 << 8) | 4,
+        ReferenceTypeMethods =                  (2 << 8) | 5,
+        ReferenceTypeGetValues =                (2 << 8) | 6,
+        ReferenceTypeSourceFile =               (2 << 8) | 7,
+        ReferenceTypeInterfaces =               (2 << 8) | 10,
+        ReferenceTypeClassObject =              (2 << 8) | 11,
+        ReferenceTypeSourceDebugExtension =     (2 << 8) | 12,
+        ReferenceTypeInstances =                (2 << 8) | 16,
+        ClassTypeSuperclass =                   (3 << 8) | 1,
+        ClassTypeSetValues =                    (3 << 8) | 2,
+        ClassTypeInvokeMethod =                 (3 << 8) | 3,
+        ClassTypeNewInstance =                  (3 << 8) | 4,
+        ArrayTypeNewInstance =                  (4 << 8) | 1,
+        MethodLineTable =                       (6 << 8) | 1,
+        MethodVariableTable =                   (6 << 8) | 2,
+        ObjectReferenceReferenceType =          (9 << 8) | 1,
+        ObjectReferenceGetValues =              (9 << 8) | 2,
+        ObjectReferenceSetValues =              (9 << 8) | 3,
+        ObjectReferenceInvokeMethod =           (9 << 8) | 6,
+        ObjectReferenceReferringObjects =       (9 << 8) | 10,
+        StringReferenceValue =                  (10 << 8) | 1,
+        ThreadReferenceName =                   (11 << 8) | 1,
+        ThreadReferenceSuspend =                (11 << 8) | 2,
+        ThreadReferenceResume =                 (11 << 8) | 3,
+        ThreadReferenceStatus =                 (11 << 8) | 4,
+        ThreadReferenceFrames =                 (11 << 8) | 6,
+        ThreadReferenceFrameCount =             (11 << 8) | 7,
+        ThreadReferenceSuspendCount =           (11 << 8) | 12,
+        ThreadGroupReferenceName =              (12 << 8) | 1,
+        ThreadGroupReferenceChildren =          (12 << 8) | 3,
+        ArrayReferenceLength =                  (13 << 8) | 1,
+        ArrayReferenceGetValues =               (13 << 8) | 2,
+        ArrayReferenceSetValues =               (13 << 8) | 3,
+        EventRequestSet =                       (15 << 8) | 1,
+        EventRequestClear =                     (15 << 8) | 2,
+        EventRequestClearAllBreakpoints =       (15 << 8) | 3,
+        StackFrameGetValues =                   (16 << 8) | 1,
+        StackFrameSetValues =                   (16 << 8) | 2,
+        StackFrameThisObject =                  (16 << 8) | 3,
+        ClassObjectReferenceReflectedType =     (17 << 8) | 1,
+        EventComposite =                        (64 << 8) | 100,
+        DdmChunk =                              (199 << 8) | 1,
+        #[unknown]
+        Unknown,
+    }
+}
+impl Command {
+    /// The command `command` of a command set, e.g. `(CommandSet::VirtualMachine, 1)` for
+    /// [Command::VirtualMachineVersion]. Commands not known to this crate are
+    /// [Command::Unknown].
+    pub fn from_parts(command_set: CommandSet, command: u8) -> Self {
+        let value = (u16::from(u8::from(command_set)) << 8) | u16::from(command);
+        Command::try_from(value).unwrap_or(Command::Unknown(value))
+    }
+
+    pub fn command_set(&self) -> CommandSet {
+        let command_set = (u16::from(*self) >> 8) as u8;
+        CommandSet::try_from(command_set).unwrap_or(CommandSet::Unknown(command_set))
+    }
+
+    /// Number of the command within its command set
+    pub fn command(&self) -> u8 {
+        u16::from(*self) as u8
+    }
+
+    /// Whether the command changes the state of the VM or of the debugging session, such as
+    /// setting values, invoking methods, suspending threads or creating event requests. Commands
+    /// not known to this crate count as mutating, as they might be.
+    pub fn is_mutating(&self) -> bool {
+        matches!(
+            self,
+            Command::VirtualMachineDispose
+                | Command::VirtualMachineSuspend
+                | Command::VirtualMachineResume
+                | Command::VirtualMachineExit
+                | Command::VirtualMachineCreateString
+                | Command::VirtualMachineHoldEvents
+                | Command::VirtualMachineReleaseEvents
+                | Command::VirtualMachineRedefineClasses
+                | Command::ClassTypeSetValues
+                | Command::ClassTypeInvokeMethod
+                | Command::ClassTypeNewInstance
+                | Command::ArrayTypeNewInstance
+                | Command::ObjectReferenceSetValues
+                | Command::ObjectReferenceInvokeMethod
+                | Command::ThreadReferenceSuspend
+                | Command::ThreadReferenceResume
+                | Command::ArrayReferenceSetValues
+                | Command::EventRequestSet
+                | Command::EventRequestClear
+                | Command::EventRequestClearAllBreakpoints
+                | Command::StackFrameSetValues
+                | Command::DdmChunk
+                | Command::Unknown(_)
+        )
     }
 }
 
 #[binrw]
 #[brw(big)]
+#[derive(Debug, Clone, Copy)]
 pub struct CommandPacketHeader {
     pub length: u32,
     pub id: u32,
-    pub flags: u8,
+    pub flags: PacketFlags,
     pub command: Command,
 }
 impl CommandPacketHeader {
     pub fn get_length() -> usize {
-        return 4 + 4 + 1 + 2;
+        4 + 4 + 1 + 2
     }
 }
 
 #[binrw]
 #[brw(big)]
+#[derive(Debug, Clone, Copy)]
 pub struct ReplyPacketHeader {
     pub length: u32,
     pub id: u32,
-    pub flags: u8,
+    pub flags: PacketFlags,
     pub error_code: u16,
 }
-impl ReplyPacketHeader {
-    pub fn default() -> Self {
+impl Default for ReplyPacketHeader {
+    fn default() -> Self {
         ReplyPacketHeader {
             length: 0,
             id: 0xFFFFFFFF,
-            flags: 0,
+            flags: PacketFlags::REPLY,
             error_code: 0,
         }
     }
+}
+impl ReplyPacketHeader {
     pub fn get_length() -> usize {
-        return 4 + 4 + 1 + 2;
+        4 + 4 + 1 + 2
     }
     pub fn is_success(&self) -> bool {
-        return self.error_code == 0;
+        self.error_code == 0
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct VariableLengthId {
     pub value: u64,
 }
@@ -88,6 +198,27 @@ impl BinRead for VariableLengthId {
         Ok(VariableLengthId { value: val })
     }
 }
+impl BinWrite for VariableLengthId {
+    type Args<'a> = JdwpIdSize;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        match args {
+            1 => (self.value as u8).write_options(writer, endian, ()),
+            2 => (self.value as u16).write_options(writer, endian, ()),
+            4 => (self.value as u32).write_options(writer, endian, ()),
+            8 => self.value.write_options(writer, endian, ()),
+            _ => binrw::BinResult::Err(binrw::Error::Custom {
+                pos: writer.stream_position().unwrap_or(0),
+                err: Box::new("Unsupported variable size ID"),
+            }),
+        }
+    }
+}
 
 #[binrw]
 #[brw(big)]
@@ -309,17 +440,1309 @@ pub struct IdSizesReply {
 }
 // ====== END VirtualMachine_IDSizes ======
 
-#[cfg(test)]
-mod tests {
-    use crate::Command;
-    use binrw::BinRead;
-    use std::io::Cursor;
+// ====== BEGIN VirtualMachine_Exit ======
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Copy, Debug)]
+pub struct ExitOut {
+    pub exit_code: i32,
+}
+// ====== END VirtualMachine_Exit ======
 
-    #[test]
-    fn test_deserialize_vm_version_command() {
-        let data = [1u8, 1u8];
-        let mut cursor = Cursor::new(&data);
-        let value = Command::read_be(&mut cursor).unwrap();
-        assert_eq!(value, Command::VirtualMachineVersion);
+// ====== BEGIN VirtualMachine_CreateString ======
+#[binwrite]
+#[bw(big)]
+#[derive(Debug)]
+pub struct CreateStringOut<'a> {
+    pub utf: JdwpStringSlice<'a>,
+}
+
+#[derive(Debug)]
+pub struct CreateStringReply {
+    pub string_object: VariableLengthId,
+}
+impl BinRead for CreateStringReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        Ok(CreateStringReply {
+            string_object: VariableLengthId::read_options(reader, endian, args.object_id_size)?,
+        })
+    }
+}
+// ====== END VirtualMachine_CreateString ======
+
+// ====== BEGIN VirtualMachine_ClassPaths ======
+#[derive(Debug)]
+pub struct ClassPathsReply {
+    pub base_dir: JdwpString,
+    pub classpaths: Vec<JdwpString>,
+    pub bootclasspaths: Vec<JdwpString>,
+}
+impl BinRead for ClassPathsReply {
+    type Args<'a> = ();
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        _: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        fn read_paths<R: std::io::Read + std::io::Seek>(
+            reader: &mut R,
+            endian: binrw::Endian,
+        ) -> binrw::BinResult<Vec<JdwpString>> {
+            let length = i32::read_options(reader, endian, ())?;
+            let mut paths = Vec::with_capacity(length.max(0) as usize);
+            for _ in 0..length {
+                paths.push(JdwpString::read_options(reader, endian, ())?);
+            }
+            Ok(paths)
+        }
+
+        Ok(ClassPathsReply {
+            base_dir: JdwpString::read_options(reader, endian, ())?,
+            classpaths: read_paths(reader, endian)?,
+            bootclasspaths: read_paths(reader, endian)?,
+        })
+    }
+}
+// ====== END VirtualMachine_ClassPaths ======
+
+// ====== BEGIN VirtualMachine_CapabilitiesNew ======
+#[binread]
+#[br(big)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CapabilitiesNewReply {
+    #[br(map = |x: u8| x != 0)]
+    pub can_watch_field_modification: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_watch_field_access: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_get_bytecodes: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_get_synthetic_attribute: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_get_owned_monitor_info: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_get_current_contended_monitor: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_get_monitor_info: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_redefine_classes: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_add_method: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_unrestrictedly_redefine_classes: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_pop_frames: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_use_instance_filters: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_get_source_debug_extension: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_request_vm_death_event: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_set_default_stratum: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_get_instance_info: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_request_monitor_events: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_get_monitor_frame_info: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_use_source_name_filters: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_get_constant_pool: bool,
+    #[br(map = |x: u8| x != 0)]
+    pub can_force_early_return: bool,
+    /// reserved22 to reserved32
+    #[br(temp)]
+    _reserved: [u8; 11],
+}
+// ====== END VirtualMachine_CapabilitiesNew ======
+
+// ====== BEGIN VirtualMachine_InstanceCounts ======
+#[derive(Clone, Debug)]
+pub struct InstanceCountsOut {
+    pub ref_types: Vec<VariableLengthId>,
+}
+impl BinWrite for InstanceCountsOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        (self.ref_types.len() as i32).write_options(writer, endian, ())?;
+        for ref_type in &self.ref_types {
+            ref_type.write_options(writer, endian, args.reference_type_id_size)?;
+        }
+        Ok(())
+    }
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct InstanceCountsReply {
+    #[bw(calc = counts.len() as i32)]
+    counts_count: i32,
+    /// Number of reachable instances of each requested type, in request order
+    #[br(count = counts_count)]
+    pub counts: Vec<i64>,
+}
+// ====== END VirtualMachine_InstanceCounts ======
+
+// ====== BEGIN ReferenceType_Signature ======
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct SignatureReply {
+    pub signature: JdwpString,
+}
+// ====== END ReferenceType_Signature ======
+
+// ====== BEGIN ReferenceType_Fields ======
+#[derive(Clone, Debug)]
+pub struct FieldsReplyField {
+    pub field_id: VariableLengthId,
+    pub name: JdwpString,
+    /// JNI signature of the field type, e.g. `I` or `Ljava/lang/String;`
+    pub signature: JdwpString,
+    pub mod_bits: i32,
+}
+impl BinRead for FieldsReplyField {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        Ok(FieldsReplyField {
+            field_id: VariableLengthId::read_options(reader, endian, args.field_id_size)?,
+            name: JdwpString::read_options(reader, endian, ())?,
+            signature: JdwpString::read_options(reader, endian, ())?,
+            mod_bits: i32::read_options(reader, endian, ())?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct FieldsReply {
+    /// Fields declared by the type itself, without inherited ones
+    pub fields: Vec<FieldsReplyField>,
+}
+impl BinRead for FieldsReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        let length = i32::read_options(reader, endian, ())?;
+        let mut fields = Vec::with_capacity(length.max(0) as usize);
+        for _ in 0..length {
+            fields.push(FieldsReplyField::read_options(reader, endian, args)?);
+        }
+        Ok(FieldsReply { fields })
+    }
+}
+// ====== END ReferenceType_Fields ======
+
+// ====== BEGIN ReferenceType_Methods ======
+#[derive(Clone, Copy, Debug)]
+pub struct RefTypeOut {
+    pub ref_type_id: VariableLengthId,
+}
+impl BinWrite for RefTypeOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.ref_type_id
+            .write_options(writer, endian, args.reference_type_id_size)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MethodsReplyMethod {
+    pub method_id: VariableLengthId,
+    pub name: JdwpString,
+    pub signature: JdwpString,
+    pub mod_bits: i32,
+}
+impl BinRead for MethodsReplyMethod {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        Ok(MethodsReplyMethod {
+            method_id: VariableLengthId::read_options(reader, endian, args.method_id_size)?,
+            name: JdwpString::read_options(reader, endian, ())?,
+            signature: JdwpString::read_options(reader, endian, ())?,
+            mod_bits: i32::read_options(reader, endian, ())?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct MethodsReply {
+    pub methods: Vec<MethodsReplyMethod>,
+}
+impl BinRead for MethodsReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        let length = i32::read_options(reader, endian, ())?;
+        let mut methods = Vec::with_capacity(length.max(0) as usize);
+        for _ in 0..length {
+            methods.push(MethodsReplyMethod::read_options(reader, endian, args)?);
+        }
+        Ok(MethodsReply { methods })
+    }
+}
+// ====== END ReferenceType_Methods ======
+
+// ====== BEGIN ReferenceType_GetValues ======
+#[derive(Clone, Debug)]
+pub struct RefTypeGetValuesOut {
+    pub ref_type_id: VariableLengthId,
+    /// Static fields of the type or of its superclasses and superinterfaces
+    pub fields: Vec<VariableLengthId>,
+}
+impl BinWrite for RefTypeGetValuesOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.ref_type_id
+            .write_options(writer, endian, args.reference_type_id_size)?;
+        write_field_ids(&self.fields, writer, endian, args)
+    }
+}
+
+fn write_field_ids<W: std::io::Write + std::io::Seek>(
+    fields: &[VariableLengthId],
+    writer: &mut W,
+    endian: binrw::Endian,
+    args: JdwpIdSizes,
+) -> binrw::BinResult<()> {
+    (fields.len() as i32).write_options(writer, endian, ())?;
+    for field in fields {
+        field.write_options(writer, endian, args.field_id_size)?;
+    }
+    Ok(())
+}
+
+/// Values of the requested fields, in the order they were requested
+#[derive(Debug)]
+pub struct GetValuesReply {
+    pub values: Vec<JdwpValue>,
+}
+impl BinRead for GetValuesReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        let length = i32::read_options(reader, endian, ())?;
+        let mut values = Vec::with_capacity(length.max(0) as usize);
+        for _ in 0..length {
+            values.push(JdwpValue::read_options(reader, endian, args)?);
+        }
+        Ok(GetValuesReply { values })
+    }
+}
+// ====== END ReferenceType_GetValues ======
+
+// ====== BEGIN ClassType_Superclass ======
+#[derive(Debug)]
+pub struct SuperclassReply {
+    /// Null (0) for java.lang.Object
+    pub superclass: VariableLengthId,
+}
+impl BinRead for SuperclassReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        Ok(SuperclassReply {
+            superclass: VariableLengthId::read_options(
+                reader,
+                endian,
+                args.reference_type_id_size,
+            )?,
+        })
+    }
+}
+// ====== END ClassType_Superclass ======
+
+// ====== BEGIN ClassType_InvokeMethod ======
+#[derive(Clone, Debug)]
+pub struct ClassInvokeMethodOut {
+    pub class_id: VariableLengthId,
+    pub thread_id: VariableLengthId,
+    pub method_id: VariableLengthId,
+    pub arguments: Vec<JdwpValue>,
+    pub options: InvokeOptions,
+}
+impl BinWrite for ClassInvokeMethodOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.class_id
+            .write_options(writer, endian, args.reference_type_id_size)?;
+        self.thread_id
+            .write_options(writer, endian, args.object_id_size)?;
+        self.method_id
+            .write_options(writer, endian, args.method_id_size)?;
+        (self.arguments.len() as i32).write_options(writer, endian, ())?;
+        for argument in &self.arguments {
+            argument.write_options(writer, endian, args)?;
+        }
+        self.options.write_options(writer, endian, ())
+    }
+}
+
+/// Reply of the InvokeMethod commands of ClassType, InterfaceType and ObjectReference
+#[derive(Debug)]
+pub struct InvokeMethodReply {
+    pub return_value: JdwpValue,
+    /// Null (0) if the method returned normally
+    pub exception: TaggedObjectId,
+}
+impl BinRead for InvokeMethodReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        Ok(InvokeMethodReply {
+            return_value: JdwpValue::read_options(reader, endian, args)?,
+            exception: TaggedObjectId::read_options(reader, endian, args)?,
+        })
+    }
+}
+// ====== END ClassType_InvokeMethod ======
+
+// ====== BEGIN ClassType_NewInstance ======
+#[derive(Clone, Debug)]
+pub struct ClassNewInstanceOut {
+    pub class_id: VariableLengthId,
+    pub thread_id: VariableLengthId,
+    pub method_id: VariableLengthId,
+    pub arguments: Vec<JdwpValue>,
+    pub options: InvokeOptions,
+}
+impl BinWrite for ClassNewInstanceOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.class_id
+            .write_options(writer, endian, args.reference_type_id_size)?;
+        self.thread_id
+            .write_options(writer, endian, args.object_id_size)?;
+        self.method_id
+            .write_options(writer, endian, args.method_id_size)?;
+        (self.arguments.len() as i32).write_options(writer, endian, ())?;
+        for argument in &self.arguments {
+            argument.write_options(writer, endian, args)?;
+        }
+        self.options.write_options(writer, endian, ())
+    }
+}
+
+#[derive(Debug)]
+pub struct ClassNewInstanceReply {
+    pub new_object: TaggedObjectId,
+    pub exception: TaggedObjectId,
+}
+impl BinRead for ClassNewInstanceReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        Ok(ClassNewInstanceReply {
+            new_object: TaggedObjectId::read_options(reader, endian, args)?,
+            exception: TaggedObjectId::read_options(reader, endian, args)?,
+        })
+    }
+}
+// ====== END ClassType_NewInstance ======
+
+// ====== BEGIN ArrayType_NewInstance ======
+#[derive(Clone, Copy, Debug)]
+pub struct ArrayNewInstanceOut {
+    pub array_type_id: VariableLengthId,
+    pub length: i32,
+}
+impl BinWrite for ArrayNewInstanceOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.array_type_id
+            .write_options(writer, endian, args.reference_type_id_size)?;
+        self.length.write_options(writer, endian, ())
+    }
+}
+
+#[derive(Debug)]
+pub struct ArrayNewInstanceReply {
+    pub new_array: TaggedObjectId,
+}
+impl BinRead for ArrayNewInstanceReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        Ok(ArrayNewInstanceReply {
+            new_array: TaggedObjectId::read_options(reader, endian, args)?,
+        })
+    }
+}
+// ====== END ArrayType_NewInstance ======
+
+// ====== BEGIN Method_LineTable ======
+#[derive(Clone, Copy, Debug)]
+pub struct MethodOut {
+    pub ref_type_id: VariableLengthId,
+    pub method_id: VariableLengthId,
+}
+impl BinWrite for MethodOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.ref_type_id
+            .write_options(writer, endian, args.reference_type_id_size)?;
+        self.method_id
+            .write_options(writer, endian, args.method_id_size)
+    }
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineTableEntry {
+    pub line_code_index: u64,
+    pub line_number: i32,
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct LineTableReply {
+    /// Lowest valid code index, or -1 for native methods
+    pub start: i64,
+    pub end: i64,
+    #[bw(calc = lines.len() as i32)]
+    lines_count: i32,
+    #[br(count = lines_count)]
+    pub lines: Vec<LineTableEntry>,
+}
+// ====== END Method_LineTable ======
+
+// ====== BEGIN Method_VariableTable ======
+/// A local variable or argument of a method. Its slot may be reused by other variables outside
+/// the code it's visible in.
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VariableTableEntry {
+    /// First code index at which the variable is visible
+    pub code_index: u64,
+    pub name: JdwpString,
+    pub signature: JdwpString,
+    /// Number of code indices the variable is visible for
+    pub length: u32,
+    pub slot: i32,
+}
+impl VariableTableEntry {
+    pub fn is_visible_at(&self, index: u64) -> bool {
+        index >= self.code_index && index - self.code_index < self.length as u64
+    }
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct VariableTableReply {
+    /// Number of words in the frame used by arguments, `this` included
+    pub arg_count: i32,
+    #[bw(calc = slots.len() as i32)]
+    slots_count: i32,
+    #[br(count = slots_count)]
+    pub slots: Vec<VariableTableEntry>,
+}
+// ====== END Method_VariableTable ======
+
+// ====== BEGIN ReferenceType_SourceFile ======
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct SourceFileReply {
+    /// Name of the source file without its path, e.g. `Main.java`
+    pub source_file: JdwpString,
+}
+// ====== END ReferenceType_SourceFile ======
+
+// ====== BEGIN ReferenceType_Interfaces ======
+#[derive(Debug)]
+pub struct InterfacesReply {
+    /// Interfaces directly implemented by a class or extended by an interface
+    pub interfaces: Vec<VariableLengthId>,
+}
+impl BinRead for InterfacesReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        let length = i32::read_options(reader, endian, ())?;
+        let mut interfaces = Vec::with_capacity(length.max(0) as usize);
+        for _ in 0..length {
+            interfaces.push(VariableLengthId::read_options(
+                reader,
+                endian,
+                args.reference_type_id_size,
+            )?);
+        }
+        Ok(InterfacesReply { interfaces })
+    }
+}
+// ====== END ReferenceType_Interfaces ======
+
+// ====== BEGIN ReferenceType_ClassObject ======
+#[derive(Debug)]
+pub struct ClassObjectReply {
+    /// The `java.lang.Class` instance of the type
+    pub class_object: VariableLengthId,
+}
+impl BinRead for ClassObjectReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        Ok(ClassObjectReply {
+            class_object: VariableLengthId::read_options(reader, endian, args.object_id_size)?,
+        })
+    }
+}
+// ====== END ReferenceType_ClassObject ======
+
+// ====== BEGIN ReferenceType_SourceDebugExtension ======
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct SourceDebugExtensionReply {
+    pub extension: JdwpString,
+}
+// ====== END ReferenceType_SourceDebugExtension ======
+
+// ====== BEGIN ReferenceType_Instances ======
+#[derive(Clone, Copy, Debug)]
+pub struct InstancesOut {
+    pub ref_type_id: VariableLengthId,
+    /// 0 for all instances
+    pub max_instances: i32,
+}
+impl BinWrite for InstancesOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.ref_type_id
+            .write_options(writer, endian, args.reference_type_id_size)?;
+        self.max_instances.write_options(writer, endian, ())
+    }
+}
+
+#[derive(Debug)]
+pub struct InstancesReply {
+    /// Reachable instances of the type itself, not of its subtypes
+    pub instances: Vec<TaggedObjectId>,
+}
+impl BinRead for InstancesReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        let length = i32::read_options(reader, endian, ())?;
+        let mut instances = Vec::with_capacity(length.max(0) as usize);
+        for _ in 0..length {
+            instances.push(TaggedObjectId::read_options(reader, endian, args)?);
+        }
+        Ok(InstancesReply { instances })
+    }
+}
+// ====== END ReferenceType_Instances ======
+
+// ====== BEGIN ObjectReference_ReferenceType ======
+#[derive(Clone, Copy, Debug)]
+pub struct ObjectOut {
+    pub object_id: VariableLengthId,
+}
+impl BinWrite for ObjectOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.object_id
+            .write_options(writer, endian, args.object_id_size)
+    }
+}
+
+#[derive(Debug)]
+pub struct ObjectReferenceTypeReply {
+    pub ref_type_tag: TypeTag,
+    pub type_id: VariableLengthId,
+}
+impl BinRead for ObjectReferenceTypeReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        Ok(ObjectReferenceTypeReply {
+            ref_type_tag: TypeTag::read_options(reader, endian, ())?,
+            type_id: VariableLengthId::read_options(reader, endian, args.reference_type_id_size)?,
+        })
+    }
+}
+// ====== END ObjectReference_ReferenceType ======
+
+// ====== BEGIN ObjectReference_GetValues ======
+#[derive(Clone, Debug)]
+pub struct ObjectGetValuesOut {
+    pub object_id: VariableLengthId,
+    /// Instance fields of the object's type or of its superclasses
+    pub fields: Vec<VariableLengthId>,
+}
+impl BinWrite for ObjectGetValuesOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.object_id
+            .write_options(writer, endian, args.object_id_size)?;
+        write_field_ids(&self.fields, writer, endian, args)
+    }
+}
+// ====== END ObjectReference_GetValues ======
+
+// ====== BEGIN ObjectReference_InvokeMethod ======
+#[derive(Clone, Debug)]
+pub struct ObjectInvokeMethodOut {
+    pub object_id: VariableLengthId,
+    pub thread_id: VariableLengthId,
+    pub class_id: VariableLengthId,
+    pub method_id: VariableLengthId,
+    pub arguments: Vec<JdwpValue>,
+    pub options: InvokeOptions,
+}
+impl BinWrite for ObjectInvokeMethodOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.object_id
+            .write_options(writer, endian, args.object_id_size)?;
+        self.thread_id
+            .write_options(writer, endian, args.object_id_size)?;
+        self.class_id
+            .write_options(writer, endian, args.reference_type_id_size)?;
+        self.method_id
+            .write_options(writer, endian, args.method_id_size)?;
+        (self.arguments.len() as i32).write_options(writer, endian, ())?;
+        for argument in &self.arguments {
+            argument.write_options(writer, endian, args)?;
+        }
+        self.options.write_options(writer, endian, ())
+    }
+}
+// ====== END ObjectReference_InvokeMethod ======
+
+// ====== BEGIN ObjectReference_ReferringObjects ======
+#[derive(Clone, Copy, Debug)]
+pub struct ReferringObjectsOut {
+    pub object_id: VariableLengthId,
+    /// 0 for all referrers
+    pub max_referrers: i32,
+}
+impl BinWrite for ReferringObjectsOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.object_id
+            .write_options(writer, endian, args.object_id_size)?;
+        self.max_referrers.write_options(writer, endian, ())
+    }
+}
+
+#[derive(Debug)]
+pub struct ReferringObjectsReply {
+    /// Reachable objects directly referencing the object
+    pub referrers: Vec<TaggedObjectId>,
+}
+impl BinRead for ReferringObjectsReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        let length = i32::read_options(reader, endian, ())?;
+        let mut referrers = Vec::with_capacity(length.max(0) as usize);
+        for _ in 0..length {
+            referrers.push(TaggedObjectId::read_options(reader, endian, args)?);
+        }
+        Ok(ReferringObjectsReply { referrers })
+    }
+}
+// ====== END ObjectReference_ReferringObjects ======
+
+// ====== BEGIN StringReference_Value ======
+
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct StringValueReply {
+    pub string_value: JdwpString,
+}
+// ====== END StringReference_Value ======
+
+// ====== BEGIN ThreadReference_Name ======
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct ThreadNameReply {
+    pub thread_name: JdwpString,
+}
+// ====== END ThreadReference_Name ======
+
+// ====== BEGIN ThreadReference_Suspend ======
+#[derive(Clone, Copy, Debug)]
+pub struct ThreadOut {
+    pub thread_id: VariableLengthId,
+}
+impl BinWrite for ThreadOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.thread_id
+            .write_options(writer, endian, args.object_id_size)
+    }
+}
+// ====== END ThreadReference_Suspend ======
+
+// ====== BEGIN ThreadReference_Status ======
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct ThreadStatusReply {
+    pub thread_status: ThreadStatus,
+    pub suspend_status: SuspendStatus,
+}
+// ====== END ThreadReference_Status ======
+
+// ====== BEGIN ThreadReference_Frames ======
+#[derive(Clone, Copy, Debug)]
+pub struct FramesOut {
+    pub thread_id: VariableLengthId,
+    pub start_frame: i32,
+    /// Number of frames to retrieve, or -1 for all remaining ones
+    pub length: i32,
+}
+impl BinWrite for FramesOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.thread_id
+            .write_options(writer, endian, args.object_id_size)?;
+        self.start_frame.write_options(writer, endian, ())?;
+        self.length.write_options(writer, endian, ())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FramesReplyFrame {
+    pub frame_id: VariableLengthId,
+    pub location: Location,
+}
+impl BinRead for FramesReplyFrame {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        Ok(FramesReplyFrame {
+            frame_id: VariableLengthId::read_options(reader, endian, args.frame_id_size)?,
+            location: Location::read_options(reader, endian, args)?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct FramesReply {
+    pub frames: Vec<FramesReplyFrame>,
+}
+impl BinRead for FramesReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        let length = i32::read_options(reader, endian, ())?;
+        let mut frames = Vec::with_capacity(length.max(0) as usize);
+        for _ in 0..length {
+            frames.push(FramesReplyFrame::read_options(reader, endian, args)?);
+        }
+        Ok(FramesReply { frames })
+    }
+}
+// ====== END ThreadReference_Frames ======
+
+// ====== BEGIN ThreadReference_FrameCount ======
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct FrameCountReply {
+    pub frame_count: i32,
+}
+// ====== END ThreadReference_FrameCount ======
+
+// ====== BEGIN ThreadReference_SuspendCount ======
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct SuspendCountReply {
+    pub suspend_count: i32,
+}
+// ====== END ThreadReference_SuspendCount ======
+
+// ====== BEGIN ThreadGroupReference_Name ======
+#[derive(Clone, Copy, Debug)]
+pub struct ThreadGroupOut {
+    pub group_id: VariableLengthId,
+}
+impl BinWrite for ThreadGroupOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.group_id
+            .write_options(writer, endian, args.object_id_size)
+    }
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct ThreadGroupNameReply {
+    pub group_name: JdwpString,
+}
+// ====== END ThreadGroupReference_Name ======
+
+// ====== BEGIN ThreadGroupReference_Children ======
+#[derive(Debug)]
+pub struct ThreadGroupChildrenReply {
+    pub child_threads: Vec<VariableLengthId>,
+    pub child_groups: Vec<VariableLengthId>,
+}
+impl BinRead for ThreadGroupChildrenReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        let read_ids = |reader: &mut R| -> binrw::BinResult<Vec<VariableLengthId>> {
+            let length = i32::read_options(reader, endian, ())?;
+            let mut ids = Vec::with_capacity(length.max(0) as usize);
+            for _ in 0..length {
+                ids.push(VariableLengthId::read_options(
+                    reader,
+                    endian,
+                    args.object_id_size,
+                )?);
+            }
+            Ok(ids)
+        };
+        Ok(ThreadGroupChildrenReply {
+            child_threads: read_ids(reader)?,
+            child_groups: read_ids(reader)?,
+        })
+    }
+}
+// ====== END ThreadGroupReference_Children ======
+
+// ====== BEGIN ArrayReference_Length ======
+#[derive(Clone, Copy, Debug)]
+pub struct ArrayLengthOut {
+    pub array_id: VariableLengthId,
+}
+impl BinWrite for ArrayLengthOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.array_id
+            .write_options(writer, endian, args.object_id_size)
+    }
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct ArrayLengthReply {
+    pub array_length: i32,
+}
+// ====== END ArrayReference_Length ======
+
+// ====== BEGIN ArrayReference_GetValues ======
+#[derive(Clone, Copy, Debug)]
+pub struct ArrayGetValuesOut {
+    pub array_id: VariableLengthId,
+    pub first_index: i32,
+    pub length: i32,
+}
+impl BinWrite for ArrayGetValuesOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.array_id
+            .write_options(writer, endian, args.object_id_size)?;
+        self.first_index.write_options(writer, endian, ())?;
+        self.length.write_options(writer, endian, ())
+    }
+}
+
+#[derive(Debug)]
+pub struct ArrayGetValuesReply {
+    pub values: ArrayValues,
+}
+impl BinRead for ArrayGetValuesReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        Ok(ArrayGetValuesReply {
+            values: ArrayValues::read_options(reader, endian, args)?,
+        })
+    }
+}
+// ====== END ArrayReference_GetValues ======
+
+// ====== BEGIN EventRequest_Set ======
+#[derive(Clone, Debug)]
+pub struct EventRequestSetOut {
+    pub event_kind: EventKind,
+    pub suspend_policy: SuspendPolicy,
+    pub modifiers: Vec<EventModifier>,
+}
+impl BinWrite for EventRequestSetOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.event_kind.write_options(writer, endian, ())?;
+        self.suspend_policy.write_options(writer, endian, ())?;
+        (self.modifiers.len() as i32).write_options(writer, endian, ())?;
+        for modifier in &self.modifiers {
+            modifier.write_options(writer, endian, args)?;
+        }
+        Ok(())
+    }
+}
+
+impl BinRead for EventRequestSetOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        let event_kind = EventKind::read_options(reader, endian, ())?;
+        let suspend_policy = SuspendPolicy::read_options(reader, endian, ())?;
+        let length = i32::read_options(reader, endian, ())?;
+        let mut modifiers = Vec::with_capacity(length.max(0) as usize);
+        for _ in 0..length {
+            modifiers.push(EventModifier::read_options(reader, endian, args)?);
+        }
+        Ok(EventRequestSetOut {
+            event_kind,
+            suspend_policy,
+            modifiers,
+        })
+    }
+}
+
+#[binrw]
+#[brw(big)]
+#[derive(Debug)]
+pub struct EventRequestSetReply {
+    pub request_id: i32,
+}
+// ====== END EventRequest_Set ======
+
+// ====== BEGIN EventRequest_Clear ======
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Copy, Debug)]
+pub struct EventRequestClearOut {
+    pub event_kind: EventKind,
+    pub request_id: i32,
+}
+// ====== END EventRequest_Clear ======
+
+// ====== BEGIN StackFrame_GetValues ======
+/// A local variable of a frame, by its slot and the tag of its declared type
+#[binrw]
+#[brw(big)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameSlot {
+    pub slot: i32,
+    pub tag: Tag,
+}
+
+#[derive(Clone, Debug)]
+pub struct StackFrameGetValuesOut {
+    pub thread_id: VariableLengthId,
+    pub frame_id: VariableLengthId,
+    pub slots: Vec<FrameSlot>,
+}
+impl BinWrite for StackFrameGetValuesOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.thread_id
+            .write_options(writer, endian, args.object_id_size)?;
+        self.frame_id
+            .write_options(writer, endian, args.frame_id_size)?;
+        (self.slots.len() as i32).write_options(writer, endian, ())?;
+        for slot in &self.slots {
+            slot.write_options(writer, endian, ())?;
+        }
+        Ok(())
+    }
+}
+// ====== END StackFrame_GetValues ======
+
+// ====== BEGIN StackFrame_ThisObject ======
+#[derive(Clone, Copy, Debug)]
+pub struct StackFrameOut {
+    pub thread_id: VariableLengthId,
+    pub frame_id: VariableLengthId,
+}
+impl BinWrite for StackFrameOut {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.thread_id
+            .write_options(writer, endian, args.object_id_size)?;
+        self.frame_id
+            .write_options(writer, endian, args.frame_id_size)
+    }
+}
+
+#[derive(Debug)]
+pub struct ThisObjectReply {
+    /// Null (0) in static and native methods
+    pub object: TaggedObjectId,
+}
+impl BinRead for ThisObjectReply {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        Ok(ThisObjectReply {
+            object: TaggedObjectId::read_options(reader, endian, args)?,
+        })
+    }
+}
+// ====== END StackFrame_ThisObject ======
+
+// ====== BEGIN ClassObjectReference_ReflectedType ======
+/// The type a class object represents, laid out like the reply of ObjectReference.ReferenceType
+pub type ReflectedTypeReply = ObjectReferenceTypeReply;
+// ====== END ClassObjectReference_ReflectedType ======
+
+#[cfg(test)]
+mod tests {
+    use crate::{Command, CommandPacketHeader, CommandSet, PacketFlags, ReplyPacketHeader};
+    use binrw::BinRead;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_deserialize_vm_version_command() {
+        let data = [1u8, 1u8];
+        let mut cursor = Cursor::new(&data);
+        let value = Command::read_be(&mut cursor).unwrap();
+        assert_eq!(value, Command::VirtualMachineVersion);
+    }
+
+    #[test]
+    fn test_command_parts() {
+        let command = Command::from_parts(CommandSet::ThreadReference, 12);
+        assert_eq!(command, Command::ThreadReferenceSuspendCount);
+        assert_eq!(command.command_set(), CommandSet::ThreadReference);
+        assert_eq!(command.command(), 12);
+        assert_eq!(u16::from(command), (11 << 8) | 12);
+
+        // ThreadReference.Interrupt isn't known to the crate
+        let command = Command::from_parts(CommandSet::ThreadReference, 11);
+        assert_eq!(command, Command::Unknown((11 << 8) | 11));
+        assert_eq!(command.command_set(), CommandSet::ThreadReference);
+        assert_eq!(Command::EventComposite.command_set(), CommandSet::Event);
+    }
+
+    #[test]
+    fn test_deserialize_packet_flags() {
+        let data = [0, 0, 0, 11, 0, 0, 0, 1, 0x80, 0, 0];
+        let header = ReplyPacketHeader::read_be(&mut Cursor::new(&data)).unwrap();
+        assert!(header.flags.is_reply());
+
+        // Unknown bits are kept
+        let data = [0, 0, 0, 11, 0, 0, 0, 1, 0x01, 1, 1];
+        let header = CommandPacketHeader::read_be(&mut Cursor::new(&data)).unwrap();
+        assert!(!header.flags.is_reply());
+        assert_eq!(header.flags.bits(), 0x01);
+        assert_eq!(header.flags, PacketFlags::from_bits_retain(0x01));
     }
 }
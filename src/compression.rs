@@ -0,0 +1,196 @@
+//! JDWP compressed with zstd, for links with a high latency or little bandwidth such as VPNs
+//!
+//! [CompressionRelay] runs next to the VM, accepts compressed connections and forwards them
+//! uncompressed to its JDWP agent. [ClientConfig::compression](crate::ClientConfig::compression)
+//! makes TCP connections compress towards such a relay. Each direction is a single zstd stream,
+//! flushed whenever data is written, so replies are compressed against everything sent before
+//! them, which pays off most for repetitive data such as AllClasses or array dumps.
+
+use std::io::{self, Write};
+use std::net::SocketAddr;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use zstd::stream::write::{Decoder, Encoder};
+
+const BUFFER_SIZE: usize = 64 * 1024;
+/// zstd's default, fast enough not to add noticeable latency
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Forwards compressed connections to the JDWP agent of a VM, one TCP connection per
+/// compressed connection
+///
+/// Like [crate::WebSocketRelay] the relay doesn't look at the packets. Clients must connect
+/// with [ClientConfig::compression](crate::ClientConfig::compression) enabled.
+pub struct CompressionRelay {
+    listener: TcpListener,
+    target: String,
+}
+
+impl CompressionRelay {
+    /// Listens for compressed connections on `address`, forwarding them to `target`, e.g.
+    /// `localhost:5005`
+    pub async fn bind(address: impl ToSocketAddrs, target: impl Into<String>) -> io::Result<Self> {
+        Ok(CompressionRelay {
+            listener: TcpListener::bind(address).await?,
+            target: target.into(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts connections until accepting fails. A connection whose target fails is closed
+    /// without affecting the others.
+    pub async fn run(self) -> io::Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            let target = self.target.clone();
+            tokio::spawn(async move {
+                if let Err(e) = relay(stream, &target).await {
+                    eprintln!("Compression relay error: {:?}", e);
+                }
+            });
+        }
+    }
+}
+
+async fn relay(stream: TcpStream, target: &str) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut compressed = compress(stream)?;
+    let mut vm = TcpStream::connect(target).await?;
+    vm.set_nodelay(true)?;
+    tokio::io::copy_bidirectional(&mut compressed, &mut vm).await?;
+    Ok(())
+}
+
+/// Compresses what is written to the returned stream onto `stream` and decompresses what is
+/// read from it, in two pump tasks
+pub(crate) fn compress<S>(stream: S) -> io::Result<DuplexStream>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let mut encoder = Encoder::new(Vec::new(), COMPRESSION_LEVEL)?;
+    let mut decoder = Decoder::new(Vec::new())?;
+    let (app_end, pipe_end) = tokio::io::duplex(BUFFER_SIZE);
+    let (mut from_app, mut to_app) = tokio::io::split(pipe_end);
+    let (mut socket_reader, mut socket_writer) = tokio::io::split(stream);
+
+    tokio::spawn(async move {
+        let mut buffer = vec![0u8; BUFFER_SIZE];
+        while let Ok(read @ 1..) = from_app.read(&mut buffer).await {
+            let Ok(compressed) = transcode(&mut encoder, &buffer[..read]) else {
+                break;
+            };
+            if socket_writer.write_all(&compressed).await.is_err()
+                || socket_writer.flush().await.is_err()
+            {
+                break;
+            }
+        }
+        let _ = socket_writer.shutdown().await;
+    });
+
+    tokio::spawn(async move {
+        let mut buffer = vec![0u8; BUFFER_SIZE];
+        while let Ok(read @ 1..) = socket_reader.read(&mut buffer).await {
+            let decompressed = match transcode(&mut decoder, &buffer[..read]) {
+                Ok(decompressed) => decompressed,
+                Err(e) => {
+                    eprintln!("Invalid compressed data: {:?}", e);
+                    break;
+                }
+            };
+            if to_app.write_all(&decompressed).await.is_err() {
+                break;
+            }
+        }
+        let _ = to_app.shutdown().await;
+    });
+
+    Ok(app_end)
+}
+
+/// Output buffer of a zstd encoder or decoder
+trait Transcoder: Write {
+    fn output(&mut self) -> &mut Vec<u8>;
+}
+impl Transcoder for Encoder<'static, Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+}
+impl Transcoder for Decoder<'static, Vec<u8>> {
+    fn output(&mut self) -> &mut Vec<u8> {
+        self.get_mut()
+    }
+}
+
+/// Runs a chunk through the encoder or decoder, flushing it so the peer can process
+/// everything written so far
+fn transcode(transcoder: &mut impl Transcoder, chunk: &[u8]) -> io::Result<Vec<u8>> {
+    transcoder.write_all(chunk)?;
+    transcoder.flush()?;
+    Ok(std::mem::take(transcoder.output()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let (client_end, relay_end) = tokio::io::duplex(BUFFER_SIZE);
+        let mut client = compress(client_end).unwrap();
+        let mut relay = compress(relay_end).unwrap();
+
+        // Sent in parts, each of which must arrive without waiting for the next
+        let data = b"Ljava/lang/String;".repeat(1000);
+        for part in data.chunks(7000) {
+            relay.write_all(part).await.unwrap();
+            let mut received = vec![0u8; part.len()];
+            client.read_exact(&mut received).await.unwrap();
+            assert_eq!(received, part);
+        }
+
+        client.write_all(b"JDWP-Handshake").await.unwrap();
+        let mut received = [0u8; 14];
+        relay.read_exact(&mut received).await.unwrap();
+        assert_eq!(&received, b"JDWP-Handshake");
+    }
+
+    #[test]
+    fn test_repetitive_data_shrinks() {
+        let mut encoder = Encoder::new(Vec::new(), COMPRESSION_LEVEL).unwrap();
+        let data = b"Ljava/lang/String;".repeat(1000);
+        let compressed = transcode(&mut encoder, &data).unwrap();
+        assert!(compressed.len() < data.len() / 20);
+
+        let mut decoder = Decoder::new(Vec::new()).unwrap();
+        assert_eq!(transcode(&mut decoder, &compressed).unwrap(), data);
+    }
+
+    #[tokio::test]
+    async fn test_relay() {
+        let vm = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let vm_address = vm.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = vm.accept().await.unwrap();
+            let mut handshake = [0u8; 14];
+            stream.read_exact(&mut handshake).await.unwrap();
+            stream.write_all(&handshake).await.unwrap();
+        });
+        let relay = CompressionRelay::bind("127.0.0.1:0", vm_address.to_string())
+            .await
+            .unwrap();
+        let relay_address = relay.local_addr().unwrap();
+        tokio::spawn(relay.run());
+
+        let mut stream = compress(TcpStream::connect(relay_address).await.unwrap()).unwrap();
+        stream.write_all(b"JDWP-Handshake").await.unwrap();
+        let mut reply = [0u8; 14];
+        stream.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"JDWP-Handshake");
+    }
+}
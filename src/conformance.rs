@@ -0,0 +1,178 @@
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::io::Cursor;
+
+use binrw::BinRead;
+
+use crate::{DEFAULT_MAX_STRING_LENGTH, result};
+
+/// How strictly packets received from the VM are checked against the JDWP specification
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Conformance {
+    /// Fails to decode packets with bytes left over after their contents or with enum values
+    /// the specification doesn't define, e.g. to check an agent under development
+    Strict,
+    /// Ignores left over bytes and decodes unknown enum values as `Unknown`, logging both.
+    /// Some agents of embedded or obfuscated VMs need this.
+    #[default]
+    Lenient,
+}
+
+/// Conformance and limits of the packet being decoded on this thread and the deviations
+/// tolerated so far
+struct Decoding {
+    conformance: Conformance,
+    max_string_length: usize,
+    tolerated: Vec<String>,
+}
+
+thread_local! {
+    static DECODING: RefCell<Option<Decoding>> = const { RefCell::new(None) };
+}
+
+/// Whether the packet being decoded on this thread must conform strictly
+pub(crate) fn decoding_strictly() -> bool {
+    DECODING.with_borrow(|decoding| {
+        decoding
+            .as_ref()
+            .is_some_and(|decoding| decoding.conformance == Conformance::Strict)
+    })
+}
+
+/// Longest string, in bytes, the packet being decoded on this thread may hold. Strings decoded
+/// outside of [decode] are held to [DEFAULT_MAX_STRING_LENGTH].
+pub(crate) fn max_string_length() -> usize {
+    DECODING.with_borrow(|decoding| {
+        decoding
+            .as_ref()
+            .map_or(DEFAULT_MAX_STRING_LENGTH, |decoding| {
+                decoding.max_string_length
+            })
+    })
+}
+
+/// Records a deviation tolerated while decoding, logged once the packet is decoded
+pub(crate) fn tolerate(deviation: String) {
+    DECODING.with_borrow_mut(|decoding| {
+        if let Some(decoding) = decoding {
+            decoding.tolerated.push(deviation);
+        }
+    });
+}
+
+/// Decodes the data of a packet received from the VM, which must be decoded entirely unless
+/// `conformance` is lenient, and whose strings may be at most `max_string_length` bytes long.
+/// `packet` describes the packet in errors and logs.
+#[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+pub(crate) fn decode<'a, T: BinRead>(
+    data: &[u8],
+    args: T::Args<'a>,
+    conformance: Conformance,
+    max_string_length: usize,
+    packet: impl Display,
+) -> result::Result<T> {
+    let previous = DECODING.replace(Some(Decoding {
+        conformance,
+        max_string_length,
+        tolerated: Vec::new(),
+    }));
+    let mut cursor = Cursor::new(data);
+    let decoded = T::read_be_args(&mut cursor, args);
+    let mut tolerated = DECODING
+        .replace(previous)
+        .map(|decoding| decoding.tolerated)
+        .unwrap_or_default();
+
+    let value = decoded.map_err(|e| result::Error::ParsingError {
+        message: format!("Binary parsing error: {:?}", e),
+    })?;
+    let decoded = cursor.position() as usize;
+    if decoded < data.len() {
+        if conformance == Conformance::Strict {
+            return Err(result::Error::ReplyParseError(
+                result::ReplyParseError::TrailingBytes {
+                    expected: data.len(),
+                    actual: decoded,
+                },
+            ));
+        }
+        tolerated.push(format!("{} bytes left over", data.len() - decoded));
+    }
+    if !tolerated.is_empty() {
+        eprintln!(
+            "Tolerated deviations from the specification in {}: {}",
+            packet,
+            tolerated.join(", ")
+        );
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EventKind, JdwpString, SuspendPolicy};
+
+    #[test]
+    fn test_left_over_bytes() {
+        let data = [0x2, 0x0];
+        let lenient: SuspendPolicy = decode(
+            &data,
+            (),
+            Conformance::Lenient,
+            DEFAULT_MAX_STRING_LENGTH,
+            "test",
+        )
+        .unwrap();
+        assert_eq!(lenient, SuspendPolicy::All);
+        assert!(matches!(
+            decode::<SuspendPolicy>(
+                &data,
+                (),
+                Conformance::Strict,
+                DEFAULT_MAX_STRING_LENGTH,
+                "test"
+            ),
+            Err(result::Error::ReplyParseError(
+                result::ReplyParseError::TrailingBytes {
+                    expected: 2,
+                    actual: 1
+                }
+            ))
+        ));
+        assert!(!decoding_strictly());
+    }
+
+    #[test]
+    fn test_unknown_enum_values() {
+        let data = [0x7f];
+        let lenient: EventKind = decode(
+            &data,
+            (),
+            Conformance::Lenient,
+            DEFAULT_MAX_STRING_LENGTH,
+            "test",
+        )
+        .unwrap();
+        assert_eq!(lenient, EventKind::Unknown(0x7f));
+        assert!(
+            decode::<EventKind>(
+                &data,
+                (),
+                Conformance::Strict,
+                DEFAULT_MAX_STRING_LENGTH,
+                "test"
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_max_string_length() {
+        let data = [0x0, 0x0, 0x0, 0x3, b'a', b'b', b'c'];
+        let string: JdwpString = decode(&data, (), Conformance::Lenient, 3, "test").unwrap();
+        assert_eq!(string, "abc");
+        assert!(decode::<JdwpString>(&data, (), Conformance::Lenient, 2, "test").is_err());
+        assert_eq!(max_string_length(), DEFAULT_MAX_STRING_LENGTH);
+    }
+}
@@ -0,0 +1,763 @@
+use binrw::{BinRead, BinWrite};
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::io;
+use std::io::{Cursor, IoSlice};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{OnceCell, mpsc, oneshot};
+
+use crate::class_cache::ClassCache;
+use crate::command_stats::CommandStatsRecorder;
+use crate::conformance;
+use crate::event_bus::EventBus;
+use crate::priority::OutgoingQueue;
+use crate::reply_body::{STREAMING_THRESHOLD, pump_body};
+use crate::suspension::{SuspensionChange, SuspensionState};
+use crate::{
+    Command, CommandPacket, CommandPriority, Conformance, DEFAULT_MAX_STRING_LENGTH, Event,
+    EventComposite, EventKind, EventQueue, FieldsReplyField, FlowControl, IdSizesReply,
+    IncomingPacket, JdwpIdSizes, PACKET_HEADER_LENGTH, PacketIdAllocator, Quirks, ReplyPacket,
+    UnsolicitedReplyPolicy, VariableLengthId, decode_packet, encode_command, packet_length, result,
+};
+
+/// Most packets the writer task writes with one flush
+const MAX_COALESCED_PACKETS: usize = 64;
+
+/// A fully encoded packet queued for the writer task
+pub(crate) struct OutgoingPacket {
+    pub(crate) bytes: Vec<u8>,
+    /// Notified once the packet has been written, if anyone cares
+    pub(crate) written: Option<oneshot::Sender<io::Result<()>>>,
+    pub(crate) priority: CommandPriority,
+}
+
+/// Runs in the reader task with the reply data of a successful request, before the reply is
+/// handed to the caller
+pub(crate) type ReplyHook = Arc<dyn Fn(&Shared, &[u8]) + Send + Sync>;
+
+pub(crate) struct PendingRequest {
+    pub(crate) reply: oneshot::Sender<ReplyPacket>,
+    pub(crate) suspension_change: Option<SuspensionChange>,
+    pub(crate) on_success: Option<ReplyHook>,
+    /// Where the data of a large reply is streamed to, see [crate::ReplyBody]
+    pub(crate) body: Option<mpsc::Sender<io::Result<Bytes>>>,
+}
+
+/// Who a reply received by the reader task is for
+pub(crate) enum ReplyRoute {
+    Pending(PendingRequest),
+    /// A cancelled request or a command sent detached, with the change to the suspension state
+    /// its successful reply still makes
+    Discarded(Option<SuspensionChange>),
+    Unsolicited,
+}
+
+/// Cached names of threads, see [crate::JdwpClient::thread_name]
+#[derive(Default)]
+pub(crate) struct ThreadNames {
+    names: HashMap<VariableLengthId, String>,
+    /// Changes whenever names are forgotten, so a lookup which was in flight meanwhile doesn't
+    /// cache the name of a thread which died
+    generation: u64,
+}
+impl ThreadNames {
+    pub(crate) fn get(&self, thread_id: VariableLengthId) -> Option<&String> {
+        self.names.get(&thread_id)
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Caches a name looked up when the cache was at `generation`, unless names were
+    /// forgotten since
+    pub(crate) fn insert(&mut self, thread_id: VariableLengthId, name: String, generation: u64) {
+        if generation == self.generation {
+            self.names.insert(thread_id, name);
+        }
+    }
+
+    pub(crate) fn forget(&mut self, thread_id: VariableLengthId) {
+        self.names.remove(&thread_id);
+        self.generation += 1;
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.names.clear();
+        self.generation += 1;
+    }
+}
+
+/// State shared by all client handles and the background tasks of one connection
+pub(crate) struct Shared {
+    pending_requests: Mutex<HashMap<u32, PendingRequest>>,
+    /// Ids of commands whose replies nobody waits for, because the request was cancelled or
+    /// the command was sent detached, with the suspension change of the command. Locked after
+    /// `pending_requests`.
+    discarded_replies: Mutex<HashMap<u32, Option<SuspensionChange>>>,
+    unsolicited_reply_policy: Mutex<UnsolicitedReplyPolicy>,
+    quirks: Mutex<Quirks>,
+    pub(crate) events: EventQueue,
+    suspension: Mutex<SuspensionState>,
+    bus: Mutex<EventBus>,
+    /// Kinds of the event requests created by this connection and not cleared since
+    event_requests: Mutex<HashMap<i32, EventKind>>,
+    thread_names: Mutex<ThreadNames>,
+    /// Fields of types and their superclasses, see [crate::JdwpClient::get_fields]
+    fields: Mutex<HashMap<VariableLengthId, Vec<FieldsReplyField>>>,
+    classes: Mutex<ClassCache>,
+    command_stats: Mutex<CommandStatsRecorder>,
+    pub(crate) class_tracking: OnceCell<()>,
+    /// Whether the writer task resumes leaked suspensions once every handle is dropped
+    pub(crate) resume_on_shutdown: AtomicBool,
+    /// Whether packets from the VM are decoded in [Conformance::Strict] mode
+    pub(crate) strict_conformance: AtomicBool,
+    /// Longest string packets from the VM may hold, see [crate::JdwpClient::set_max_string_length]
+    pub(crate) max_string_length: AtomicUsize,
+    packet_ids: PacketIdAllocator,
+}
+impl Shared {
+    pub(crate) fn new(events: EventQueue, last_packet_id: u32) -> Self {
+        Shared {
+            pending_requests: Mutex::new(HashMap::new()),
+            discarded_replies: Mutex::new(HashMap::new()),
+            unsolicited_reply_policy: Mutex::new(UnsolicitedReplyPolicy::default()),
+            quirks: Mutex::new(Quirks::empty()),
+            events,
+            suspension: Mutex::new(SuspensionState::default()),
+            bus: Mutex::new(EventBus::default()),
+            event_requests: Mutex::new(HashMap::new()),
+            thread_names: Mutex::new(ThreadNames::default()),
+            fields: Mutex::new(HashMap::new()),
+            classes: Mutex::new(ClassCache::default()),
+            command_stats: Mutex::new(CommandStatsRecorder::default()),
+            class_tracking: OnceCell::new(),
+            resume_on_shutdown: AtomicBool::new(false),
+            strict_conformance: AtomicBool::new(false),
+            max_string_length: AtomicUsize::new(DEFAULT_MAX_STRING_LENGTH),
+            packet_ids: PacketIdAllocator::new(last_packet_id),
+        }
+    }
+
+    pub(crate) fn conformance(&self) -> Conformance {
+        if self.strict_conformance.load(Ordering::Relaxed) {
+            Conformance::Strict
+        } else {
+            Conformance::Lenient
+        }
+    }
+
+    /// Next packet id not used by a command waiting for its reply, for a command whose reply
+    /// is discarded. Ids wrap around after `u32::MAX`, so in a long session an id can come up
+    /// again while an old command with that id is still waiting.
+    pub(crate) fn next_packet_id(&self) -> u32 {
+        let pending = self.pending();
+        let id = self.free_packet_id(&pending);
+        self.discarded_replies().insert(id, None);
+        id
+    }
+
+    /// Registers a request waiting for its reply under a free packet id, returning the id
+    pub(crate) fn register_request(&self, request: PendingRequest) -> u32 {
+        let mut pending = self.pending();
+        let id = self.free_packet_id(&pending);
+        pending.insert(id, request);
+        id
+    }
+
+    fn free_packet_id(&self, pending: &HashMap<u32, PendingRequest>) -> u32 {
+        let discarded = self.discarded_replies();
+        loop {
+            let id = self.packet_ids.next_id();
+            if !pending.contains_key(&id) && !discarded.contains_key(&id) {
+                return id;
+            }
+        }
+    }
+
+    /// Removes a request before its reply arrived, so the reply is discarded as expected. A
+    /// suspend or resume still changes the suspension state once its reply arrives, as the VM
+    /// carries it out whether or not anyone waits for the reply.
+    pub(crate) fn abandon_request(&self, id: u32) {
+        let mut pending = self.pending();
+        if let Some(request) = pending.remove(&id) {
+            self.discarded_replies()
+                .insert(id, request.suspension_change);
+        }
+    }
+
+    /// Takes the request a reply belongs to
+    pub(crate) fn route_reply(&self, id: u32) -> ReplyRoute {
+        let mut pending = self.pending();
+        match pending.remove(&id) {
+            Some(request) => ReplyRoute::Pending(request),
+            None => match self.discarded_replies().remove(&id) {
+                Some(suspension_change) => ReplyRoute::Discarded(suspension_change),
+                None => ReplyRoute::Unsolicited,
+            },
+        }
+    }
+
+    fn discarded_replies(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<u32, Option<SuspensionChange>>> {
+        match self.discarded_replies.lock() {
+            Ok(discarded) => discarded,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    pub(crate) fn quirks(&self) -> std::sync::MutexGuard<'_, Quirks> {
+        match self.quirks.lock() {
+            Ok(quirks) => quirks,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    pub(crate) fn unsolicited_reply_policy(
+        &self,
+    ) -> std::sync::MutexGuard<'_, UnsolicitedReplyPolicy> {
+        match self.unsolicited_reply_policy.lock() {
+            Ok(policy) => policy,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    pub(crate) fn pending(&self) -> std::sync::MutexGuard<'_, HashMap<u32, PendingRequest>> {
+        match self.pending_requests.lock() {
+            Ok(pending) => pending,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    pub(crate) fn bus(&self) -> std::sync::MutexGuard<'_, EventBus> {
+        match self.bus.lock() {
+            Ok(bus) => bus,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    pub(crate) fn event_requests(&self) -> std::sync::MutexGuard<'_, HashMap<i32, EventKind>> {
+        match self.event_requests.lock() {
+            Ok(requests) => requests,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    pub(crate) fn thread_names(&self) -> std::sync::MutexGuard<'_, ThreadNames> {
+        match self.thread_names.lock() {
+            Ok(names) => names,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    pub(crate) fn fields(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<VariableLengthId, Vec<FieldsReplyField>>> {
+        match self.fields.lock() {
+            Ok(fields) => fields,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    pub(crate) fn classes(&self) -> std::sync::MutexGuard<'_, ClassCache> {
+        match self.classes.lock() {
+            Ok(classes) => classes,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Forgets the names of threads which died, since their ids may be reused
+    fn forget_dead_threads(&self, composite: &EventComposite) {
+        let mut names = self.thread_names();
+        for event in &composite.events {
+            if let Event::ThreadDeath { thread, .. } = event {
+                names.forget(*thread);
+            }
+        }
+    }
+
+    pub(crate) fn command_stats(&self) -> std::sync::MutexGuard<'_, CommandStatsRecorder> {
+        match self.command_stats.lock() {
+            Ok(stats) => stats,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    pub(crate) fn suspension(&self) -> std::sync::MutexGuard<'_, SuspensionState> {
+        match self.suspension.lock() {
+            Ok(suspension) => suspension,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+/// Removes a pending request when the future waiting for its reply completes or is dropped, so
+/// a cancelled request doesn't leak its entry and its late reply is discarded by the reader
+pub(crate) struct PendingRequestGuard<'a> {
+    pub(crate) shared: &'a Shared,
+    pub(crate) id: u32,
+}
+impl Drop for PendingRequestGuard<'_> {
+    fn drop(&mut self) {
+        self.shared.abandon_request(self.id);
+    }
+}
+
+pub(crate) async fn read_packet<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> result::Result<IncomingPacket> {
+    let header = read_header(reader).await?;
+    read_data(reader, &header).await
+}
+
+async fn read_header<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> result::Result<[u8; PACKET_HEADER_LENGTH]> {
+    let mut header = [0u8; PACKET_HEADER_LENGTH];
+    reader.read_exact(&mut header).await?;
+    Ok(header)
+}
+
+/// Reads the data following a header and decodes the whole packet
+async fn read_data<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    header: &[u8; PACKET_HEADER_LENGTH],
+) -> result::Result<IncomingPacket> {
+    let length = packet_length(header)?;
+    let mut data = vec![0u8; length - PACKET_HEADER_LENGTH];
+    reader.read_exact(&mut data).await?;
+    decode_packet(header, data)
+}
+
+pub(crate) async fn write_packet<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    bytes: &[u8],
+) -> io::Result<()> {
+    writer.write_all(bytes).await?;
+    writer.flush().await
+}
+
+/// Writes the packets and flushes once: in vectored writes if the stream supports them,
+/// otherwise from a single buffer
+pub(crate) async fn write_packets<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    packets: &[&[u8]],
+) -> io::Result<()> {
+    if writer.is_write_vectored() {
+        let mut slices: Vec<_> = packets.iter().map(|bytes| IoSlice::new(bytes)).collect();
+        let mut remaining = &mut slices[..];
+        while !remaining.is_empty() {
+            let written = writer.write_vectored(remaining).await?;
+            if written == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            IoSlice::advance_slices(&mut remaining, written);
+        }
+    } else {
+        writer.write_all(&packets.concat()).await?;
+    }
+    writer.flush().await
+}
+
+pub(crate) async fn do_handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+) -> result::Result<()> {
+    const HANDSHAKE_STR: &str = "JDWP-Handshake";
+
+    let handshake_bytes = HANDSHAKE_STR.as_bytes();
+    stream.write_all(handshake_bytes).await?;
+    stream.flush().await?;
+
+    let mut buffer = [0u8; 14];
+    stream.read_exact(&mut buffer).await?;
+
+    let received = std::str::from_utf8(&buffer)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Invalid UTF-8"))?;
+
+    if received != HANDSHAKE_STR {
+        return Err(result::Error::ParsingError {
+            message: format!(
+                "Invalid handshake: expected '{}', got '{}'",
+                HANDSHAKE_STR, received
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+/// Sends VirtualMachine.IDSizes directly on the stream and waits for its reply, keeping any
+/// commands the VM sends in the meantime
+pub(crate) async fn negotiate_id_sizes<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    id: u32,
+    early_commands: &mut Vec<CommandPacket>,
+) -> result::Result<IdSizesReply> {
+    send_directly(stream, id, Command::VirtualMachineIDSizes, early_commands).await
+}
+
+/// Sends a bodyless command directly on the stream, before the reader and writer tasks own it,
+/// and waits for its reply, keeping any commands the VM sends in the meantime
+pub(crate) async fn send_directly<S, T>(
+    stream: &mut S,
+    id: u32,
+    command: Command,
+    early_commands: &mut Vec<CommandPacket>,
+) -> result::Result<T>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    T: for<'a> BinRead<Args<'a> = ()>,
+{
+    write_packet(stream, &encode_command(id, command, &[])?).await?;
+
+    loop {
+        match read_packet(stream).await? {
+            IncomingPacket::Reply(reply) if reply.header.id == id => {
+                if !reply.header.is_success() {
+                    return Err(result::Error::from_error_code(reply.header.error_code));
+                }
+
+                let mut cursor = Cursor::new(&reply.data);
+                return T::read_be(&mut cursor).map_err(|e| result::Error::ParsingError {
+                    message: format!("Binary parsing error: {:?}", e),
+                });
+            }
+            IncomingPacket::Command(command) => early_commands.push(command),
+            _ => {}
+        }
+    }
+}
+
+/// Hands the header of a large successful reply to a request waiting for its data as a
+/// [crate::ReplyBody], returning the length of the data and where to stream it
+fn start_streamed_reply(
+    shared: &Shared,
+    header: &[u8; PACKET_HEADER_LENGTH],
+) -> Option<(usize, mpsc::Sender<io::Result<Bytes>>)> {
+    let Ok(IncomingPacket::Reply(reply)) = decode_packet(header, Vec::new()) else {
+        return None;
+    };
+    let length = (reply.header.length as usize).saturating_sub(PACKET_HEADER_LENGTH);
+    if !reply.header.is_success() || length < STREAMING_THRESHOLD {
+        return None;
+    }
+    let mut pending = shared.pending();
+    if pending
+        .get(&reply.header.id)
+        .is_none_or(|request| request.body.is_none())
+    {
+        return None;
+    }
+    let request = pending.remove(&reply.header.id)?;
+    drop(pending);
+
+    if let Some(change) = request.suspension_change {
+        shared.suspension().apply(change);
+    }
+    let _ = request.reply.send(reply);
+    Some((length, request.body?))
+}
+
+/// Encodes a bodyless command and queues it ahead of other commands without waiting for the
+/// reply, which the reader then discards
+pub(crate) fn send_detached(
+    outgoing: &mpsc::UnboundedSender<OutgoingPacket>,
+    shared: &Shared,
+    command: Command,
+) {
+    match encode_command(shared.next_packet_id(), command, &[]) {
+        Ok(bytes) => {
+            let _ = outgoing.send(OutgoingPacket {
+                bytes,
+                written: None,
+                priority: CommandPriority::Interactive,
+            });
+        }
+        Err(e) => eprintln!("Failed to encode {:?}: {:?}", command, e),
+    }
+}
+
+/// Handles a command sent by the VM. The only command the VM sends is Event.Composite.
+pub(crate) async fn handle_command(
+    command: CommandPacket,
+    sizes: JdwpIdSizes,
+    shared: &Shared,
+    outgoing: Option<&mpsc::UnboundedSender<OutgoingPacket>>,
+) {
+    if command.header.command != Command::EventComposite {
+        return;
+    }
+
+    match conformance::decode::<EventComposite>(
+        &command.data,
+        sizes,
+        shared.conformance(),
+        shared.max_string_length.load(Ordering::Relaxed),
+        "an Event.Composite packet",
+    ) {
+        Ok(composite) => {
+            shared.suspension().event_received(&composite);
+            shared.bus().deliver(&composite);
+            shared.forget_dead_threads(&composite);
+            shared.classes().event_received(&composite);
+            if shared.events.push(composite).await == FlowControl::HoldEvents
+                && let Some(outgoing) = outgoing
+            {
+                send_detached(outgoing, shared, Command::VirtualMachineHoldEvents);
+            }
+        }
+        Err(e) => eprintln!("Event parsing error: {:?}", e),
+    }
+}
+
+/// Owns the write half of the connection. Exits once every client handle is dropped, which
+/// also tells the reader task to stop.
+pub(crate) async fn writer_loop<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    mut outgoing: mpsc::UnboundedReceiver<OutgoingPacket>,
+    shared: Arc<Shared>,
+    sizes: JdwpIdSizes,
+    _shutdown: oneshot::Sender<()>,
+) {
+    let mut queue = OutgoingQueue::default();
+    loop {
+        if queue.is_empty() {
+            match outgoing.recv().await {
+                Some(packet) => queue.push(packet),
+                None => break,
+            }
+        }
+        // Everything queued in the meantime competes by priority
+        while let Ok(packet) = outgoing.try_recv() {
+            queue.push(packet);
+        }
+        // Packets queued together are written together, saving a syscall and a flush each
+        let mut batch = Vec::new();
+        while batch.len() < MAX_COALESCED_PACKETS
+            && let Some(packet) = queue.pop()
+        {
+            batch.push(packet);
+        }
+        let bytes: Vec<_> = batch.iter().map(|packet| packet.bytes.as_slice()).collect();
+        let result = write_packets(&mut writer, &bytes).await;
+        if let Err(e) = &result {
+            eprintln!("Writer task error: {:?}", e);
+        }
+        for packet in batch {
+            if let Some(written) = packet.written {
+                let result = match &result {
+                    Ok(()) => Ok(()),
+                    Err(e) => Err(io::Error::new(e.kind(), e.to_string())),
+                };
+                let _ = written.send(result);
+            }
+        }
+    }
+
+    if shared.resume_on_shutdown.load(Ordering::Relaxed) {
+        // Nobody is left to wait for the replies, so the resumes are only written
+        let packets = leaked_resume_packets(&shared, sizes);
+        let bytes: Vec<_> = packets.iter().map(Vec::as_slice).collect();
+        if let Err(e) = write_packets(&mut writer, &bytes).await {
+            eprintln!("Failed to resume the VM on shutdown: {:?}", e);
+        }
+    }
+}
+
+/// Encoded resumes undoing the suspensions the client has seen
+fn leaked_resume_packets(shared: &Shared, sizes: JdwpIdSizes) -> Vec<Vec<u8>> {
+    let (vm, threads) = shared.suspension().leaked();
+    let mut packets = Vec::new();
+    for _ in 0..vm {
+        match encode_command(shared.next_packet_id(), Command::VirtualMachineResume, &[]) {
+            Ok(bytes) => packets.push(bytes),
+            Err(e) => eprintln!("Failed to encode VirtualMachine.Resume: {:?}", e),
+        }
+    }
+    for (thread, count) in threads {
+        let mut data = Vec::new();
+        if let Err(e) = thread.write_be_args(&mut Cursor::new(&mut data), sizes.object_id_size) {
+            eprintln!("Failed to encode ThreadReference.Resume: {:?}", e);
+            continue;
+        }
+        for _ in 0..count {
+            match encode_command(
+                shared.next_packet_id(),
+                Command::ThreadReferenceResume,
+                &data,
+            ) {
+                Ok(bytes) => packets.push(bytes),
+                Err(e) => eprintln!("Failed to encode ThreadReference.Resume: {:?}", e),
+            }
+        }
+    }
+    packets
+}
+
+/// Owns the read half of the connection, routing replies to pending requests and events to
+/// the event queue
+pub(crate) async fn reader_loop<R: AsyncRead + Unpin>(
+    mut reader: R,
+    shared: Arc<Shared>,
+    outgoing: mpsc::WeakUnboundedSender<OutgoingPacket>,
+    sizes: JdwpIdSizes,
+    mut shutdown: oneshot::Receiver<()>,
+) {
+    loop {
+        let header = tokio::select! {
+            header = read_header(&mut reader) => header,
+            _ = &mut shutdown => break,
+        };
+        let header = match header {
+            Ok(header) => header,
+            Err(e) => {
+                eprintln!("Reader task error: {:?}", e);
+                break;
+            }
+        };
+        if let Some((length, body)) = start_streamed_reply(&shared, &header) {
+            if let Err(e) = pump_body(&mut reader, length, body).await {
+                eprintln!("Reader task error: {:?}", e);
+                break;
+            }
+            continue;
+        }
+
+        match read_data(&mut reader, &header).await {
+            Ok(IncomingPacket::Reply(reply_packet)) => {
+                match shared.route_reply(reply_packet.header.id) {
+                    ReplyRoute::Pending(pending) => {
+                        if let Some(change) = pending.suspension_change
+                            && reply_packet.header.is_success()
+                        {
+                            shared.suspension().apply(change);
+                        }
+                        if let Some(on_success) = pending.on_success
+                            && reply_packet.header.is_success()
+                        {
+                            on_success(&shared, &reply_packet.data);
+                        }
+                        let _ = pending.reply.send(reply_packet);
+                    }
+                    ReplyRoute::Discarded(suspension_change) => {
+                        if let Some(change) = suspension_change
+                            && reply_packet.header.is_success()
+                        {
+                            shared.suspension().apply(change);
+                        }
+                    }
+                    ReplyRoute::Unsolicited => {
+                        shared.unsolicited_reply_policy().handle(reply_packet)
+                    }
+                }
+            }
+            Ok(IncomingPacket::Command(command)) => {
+                let outgoing = outgoing.upgrade();
+                handle_command(command, sizes, &shared, outgoing.as_ref()).await;
+            }
+            Ok(IncomingPacket::Unknown) => {}
+            Err(e) => {
+                eprintln!("Reader task error: {:?}", e);
+                break;
+            }
+        }
+    }
+
+    // Dropping the senders fails all pending requests
+    shared.pending().clear();
+    shared.bus().close();
+    shared.events.close();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EventBufferConfig, encode_reply};
+
+    const SIZES: JdwpIdSizes = JdwpIdSizes {
+        field_id_size: 8,
+        method_id_size: 8,
+        object_id_size: 8,
+        reference_type_id_size: 8,
+        frame_id_size: 8,
+    };
+
+    fn pending_request() -> (PendingRequest, oneshot::Receiver<ReplyPacket>) {
+        let (reply, receiver) = oneshot::channel();
+        let request = PendingRequest {
+            reply,
+            suspension_change: None,
+            on_success: None,
+            body: None,
+        };
+        (request, receiver)
+    }
+
+    #[test]
+    fn test_packet_ids_skip_pending() {
+        let shared = Shared::new(EventQueue::new(EventBufferConfig::default()), u32::MAX - 1);
+        // Requests from before the ids wrapped around, still waiting for their replies
+        shared.pending().insert(0, pending_request().0);
+        shared.pending().insert(1, pending_request().0);
+
+        assert_eq!(shared.register_request(pending_request().0), u32::MAX);
+        assert_eq!(shared.register_request(pending_request().0), 2);
+        assert_eq!(shared.next_packet_id(), 3);
+        assert_eq!(shared.pending().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_replies_after_wraparound() {
+        let shared = Arc::new(Shared::new(
+            EventQueue::new(EventBufferConfig::default()),
+            u32::MAX - 1,
+        ));
+        let (old_request, old_reply) = pending_request();
+        shared.pending().insert(0, old_request);
+        let (request, last_reply) = pending_request();
+        let last_id = shared.register_request(request);
+        let (request, wrapped_reply) = pending_request();
+        let wrapped_id = shared.register_request(request);
+        assert_eq!((last_id, wrapped_id), (u32::MAX, 1));
+
+        let (mut vm, reader) = tokio::io::duplex(1024);
+        let (outgoing, _outgoing_rx) = mpsc::unbounded_channel();
+        let (_shutdown_tx, shutdown) = oneshot::channel();
+        tokio::spawn(reader_loop(
+            reader,
+            shared.clone(),
+            outgoing.downgrade(),
+            SIZES,
+            shutdown,
+        ));
+        for (id, data) in [(wrapped_id, [0x1]), (0, [0x2]), (last_id, [0x3])] {
+            vm.write_all(&encode_reply(id, 0, &data).unwrap())
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(wrapped_reply.await.unwrap().data, vec![0x1]);
+        assert_eq!(old_reply.await.unwrap().data, vec![0x2]);
+        assert_eq!(last_reply.await.unwrap().data, vec![0x3]);
+        assert!(shared.pending().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_write_packets() {
+        let packets: [&[u8]; 3] = [&[0x1, 0x2], &[], &[0x3, 0x4, 0x5]];
+
+        // Vec supports vectored writes
+        let mut vectored = Vec::new();
+        write_packets(&mut vectored, &packets).await.unwrap();
+        assert_eq!(vectored, vec![0x1, 0x2, 0x3, 0x4, 0x5]);
+
+        let (mut writer, mut reader) = tokio::io::duplex(64);
+        write_packets(&mut writer, &packets).await.unwrap();
+        let mut buffered = [0; 5];
+        reader.read_exact(&mut buffered).await.unwrap();
+        assert_eq!(buffered, [0x1, 0x2, 0x3, 0x4, 0x5]);
+    }
+}
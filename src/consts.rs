@@ -1,8 +1,13 @@
+//! Numeric constants of the JDWP specification as typed enums and flags, for building and
+//! inspecting raw packets without copying magic numbers from the spec. Each enum converts to its
+//! wire value with `From` and back with `TryFrom`; the open ones keep values the specification
+//! doesn't define as `Unknown`.
+
 use crate::binrw_enum;
 use binrw::binrw;
 use bitflags::bitflags;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[binrw]
 pub struct ClassStatus(i32);
 bitflags! {
@@ -14,12 +19,193 @@ bitflags! {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[binrw]
+pub struct InvokeOptions(i32);
+bitflags! {
+    impl InvokeOptions : i32 {
+        const INVOKE_SINGLE_THREADED = 1;
+        const INVOKE_NONVIRTUAL = 1 << 1;
+    }
+}
+
+/// Suspend status of a thread, see [ThreadStatus]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[binrw]
+pub struct SuspendStatus(i32);
+bitflags! {
+    impl SuspendStatus : i32 {
+        const SUSPENDED = 1;
+    }
+}
+
+/// Flags byte of a packet header. Unknown bits are kept as they were received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[binrw]
+pub struct PacketFlags(u8);
+bitflags! {
+    impl PacketFlags : u8 {
+        const REPLY = 0x80;
+    }
+}
+impl PacketFlags {
+    /// Whether the packet is a reply rather than a command
+    pub fn is_reply(&self) -> bool {
+        self.contains(PacketFlags::REPLY)
+    }
+}
+
 binrw_enum! {
     #[repr(u8)]
-    #[derive(Debug, Clone, Copy, PartialEq)]
+    /// First byte of a command, see [crate::Command::command_set]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum CommandSet {
+        VirtualMachine = 1,
+        ReferenceType = 2,
+        ClassType = 3,
+        ArrayType = 4,
+        InterfaceType = 5,
+        Method = 6,
+        Field = 8,
+        ObjectReference = 9,
+        StringReference = 10,
+        ThreadReference = 11,
+        ThreadGroupReference = 12,
+        ArrayReference = 13,
+        ClassLoaderReference = 14,
+        EventRequest = 15,
+        StackFrame = 16,
+        ClassObjectReference = 17,
+        ModuleReference = 18,
+        Event = 64,
+        #[unknown]
+        Unknown,
+    }
+}
+
+binrw_enum! {
+    #[repr(u8)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub enum TypeTag {
         Class = 1,
         Interface = 2,
-        Array = 3
+        Array = 3,
+        #[unknown]
+        Unknown,
+    }
+}
+
+binrw_enum! {
+    #[repr(u8)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Tag {
+        Array = b'[',
+        Byte = b'B',
+        Char = b'C',
+        Object = b'L',
+        Float = b'F',
+        Double = b'D',
+        Int = b'I',
+        Long = b'J',
+        Short = b'S',
+        Void = b'V',
+        Boolean = b'Z',
+        String = b's',
+        Thread = b't',
+        ThreadGroup = b'g',
+        ClassLoader = b'l',
+        ClassObject = b'c',
+    }
+}
+impl Tag {
+    /// Returns true for tags whose values are object ids
+    pub fn is_object(&self) -> bool {
+        matches!(
+            self,
+            Tag::Array
+                | Tag::Object
+                | Tag::String
+                | Tag::Thread
+                | Tag::ThreadGroup
+                | Tag::ClassLoader
+                | Tag::ClassObject
+        )
+    }
+}
+
+binrw_enum! {
+    #[repr(i32)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum ThreadStatus {
+        Zombie = 0,
+        Running = 1,
+        Sleeping = 2,
+        Monitor = 3,
+        Wait = 4,
+        #[unknown]
+        Unknown,
+    }
+}
+
+binrw_enum! {
+    #[repr(u8)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum EventKind {
+        SingleStep = 1,
+        Breakpoint = 2,
+        FramePop = 3,
+        Exception = 4,
+        UserDefined = 5,
+        ThreadStart = 6,
+        ThreadDeath = 7,
+        ClassPrepare = 8,
+        ClassUnload = 9,
+        ClassLoad = 10,
+        FieldAccess = 20,
+        FieldModification = 21,
+        ExceptionCatch = 30,
+        MethodEntry = 40,
+        MethodExit = 41,
+        MethodExitWithReturnValue = 42,
+        MonitorContendedEnter = 43,
+        MonitorContendedEntered = 44,
+        MonitorWait = 45,
+        MonitorWaited = 46,
+        VmStart = 90,
+        VmDeath = 99,
+        VmDisconnected = 100,
+        #[unknown]
+        Unknown,
+    }
+}
+
+binrw_enum! {
+    #[repr(u8)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum SuspendPolicy {
+        None = 0,
+        EventThread = 1,
+        All = 2,
+        #[unknown]
+        Unknown,
+    }
+}
+
+binrw_enum! {
+    #[repr(i32)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum StepSize {
+        Min = 0,
+        Line = 1,
+    }
+}
+
+binrw_enum! {
+    #[repr(i32)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum StepDepth {
+        Into = 0,
+        Over = 1,
+        Out = 2,
     }
 }
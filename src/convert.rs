@@ -0,0 +1,236 @@
+use std::future::Future;
+
+use crate::{JdwpClient, JdwpValue, Tag, TaggedObjectId, VariableLengthId, result};
+
+/// Converts a Rust value to a value which can be sent to the VM. Strings are created in the VM,
+/// which is why the conversion needs a client.
+pub trait IntoJdwpValue {
+    fn into_jdwp_value(
+        self,
+        client: &JdwpClient,
+    ) -> impl Future<Output = result::Result<JdwpValue>> + Send;
+}
+
+/// Converts a value sent by the VM to a Rust value. Strings are read from the VM, which is why
+/// the conversion needs a client.
+pub trait TryFromJdwpValue: Sized {
+    fn try_from_jdwp_value(
+        value: JdwpValue,
+        client: &JdwpClient,
+    ) -> impl Future<Output = result::Result<Self>> + Send;
+}
+
+/// Argument lists for invocations, e.g. `(42, "hello", true)` or `&[JdwpValue]`
+pub trait IntoJdwpArguments {
+    fn into_jdwp_arguments(
+        self,
+        client: &JdwpClient,
+    ) -> impl Future<Output = result::Result<Vec<JdwpValue>>> + Send;
+}
+
+fn mismatch<T>(expected: &'static str, value: &JdwpValue) -> result::Result<T> {
+    Err(result::Error::TypeMismatch {
+        expected,
+        found: value.tag(),
+    })
+}
+
+macro_rules! primitive_conversions {
+    ($($ty:ty => $variant:ident),* $(,)?) => {
+        $(
+            impl IntoJdwpValue for $ty {
+                async fn into_jdwp_value(self, _: &JdwpClient) -> result::Result<JdwpValue> {
+                    Ok(JdwpValue::$variant(self))
+                }
+            }
+
+            impl TryFromJdwpValue for $ty {
+                async fn try_from_jdwp_value(
+                    value: JdwpValue,
+                    _: &JdwpClient,
+                ) -> result::Result<Self> {
+                    match value {
+                        JdwpValue::$variant(value) => Ok(value),
+                        other => mismatch(stringify!($ty), &other),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+primitive_conversions! {
+    bool => Boolean,
+    i8 => Byte,
+    u16 => Char,
+    i16 => Short,
+    i32 => Int,
+    i64 => Long,
+    f32 => Float,
+    f64 => Double,
+}
+
+/// Java chars are UTF-16 code units, so only chars of the Basic Multilingual Plane convert
+impl IntoJdwpValue for char {
+    async fn into_jdwp_value(self, _: &JdwpClient) -> result::Result<JdwpValue> {
+        let mut units = [0u16; 2];
+        match self.encode_utf16(&mut units) {
+            [unit] => Ok(JdwpValue::Char(*unit)),
+            _ => Err(result::Error::InvalidArgument {
+                message: format!("{:?} doesn't fit in a Java char", self),
+            }),
+        }
+    }
+}
+impl TryFromJdwpValue for char {
+    async fn try_from_jdwp_value(value: JdwpValue, _: &JdwpClient) -> result::Result<Self> {
+        match value {
+            JdwpValue::Char(unit) => {
+                char::from_u32(unit as u32).ok_or_else(|| result::Error::InvalidArgument {
+                    message: format!("Unpaired surrogate {:#x}", unit),
+                })
+            }
+            other => mismatch("char", &other),
+        }
+    }
+}
+
+impl IntoJdwpValue for () {
+    async fn into_jdwp_value(self, _: &JdwpClient) -> result::Result<JdwpValue> {
+        Ok(JdwpValue::Void)
+    }
+}
+impl TryFromJdwpValue for () {
+    async fn try_from_jdwp_value(value: JdwpValue, _: &JdwpClient) -> result::Result<Self> {
+        match value {
+            JdwpValue::Void => Ok(()),
+            other => mismatch("void", &other),
+        }
+    }
+}
+
+impl IntoJdwpValue for JdwpValue {
+    async fn into_jdwp_value(self, _: &JdwpClient) -> result::Result<JdwpValue> {
+        Ok(self)
+    }
+}
+impl TryFromJdwpValue for JdwpValue {
+    async fn try_from_jdwp_value(value: JdwpValue, _: &JdwpClient) -> result::Result<Self> {
+        Ok(value)
+    }
+}
+
+impl IntoJdwpValue for TaggedObjectId {
+    async fn into_jdwp_value(self, _: &JdwpClient) -> result::Result<JdwpValue> {
+        Ok(JdwpValue::Object(self))
+    }
+}
+/// Any object, including null
+impl TryFromJdwpValue for TaggedObjectId {
+    async fn try_from_jdwp_value(value: JdwpValue, _: &JdwpClient) -> result::Result<Self> {
+        match value {
+            JdwpValue::Object(object) => Ok(object),
+            other => mismatch("object", &other),
+        }
+    }
+}
+
+impl IntoJdwpValue for &str {
+    async fn into_jdwp_value(self, client: &JdwpClient) -> result::Result<JdwpValue> {
+        let reply = client.vm_create_string(self).await?;
+        Ok(JdwpValue::Object(TaggedObjectId {
+            tag: Tag::String,
+            object_id: reply.string_object,
+        }))
+    }
+}
+impl IntoJdwpValue for String {
+    async fn into_jdwp_value(self, client: &JdwpClient) -> result::Result<JdwpValue> {
+        self.as_str().into_jdwp_value(client).await
+    }
+}
+impl IntoJdwpValue for &String {
+    async fn into_jdwp_value(self, client: &JdwpClient) -> result::Result<JdwpValue> {
+        self.as_str().into_jdwp_value(client).await
+    }
+}
+/// A non-null java.lang.String
+impl TryFromJdwpValue for String {
+    async fn try_from_jdwp_value(value: JdwpValue, client: &JdwpClient) -> result::Result<Self> {
+        match value {
+            JdwpValue::Object(object)
+                if object.tag == Tag::String && object.object_id.value != 0 =>
+            {
+                Ok(client
+                    .string_get_value(object.object_id)
+                    .await?
+                    .string_value
+                    .string)
+            }
+            other => mismatch("java.lang.String", &other),
+        }
+    }
+}
+
+/// None is sent as null
+impl<T: IntoJdwpValue + Send> IntoJdwpValue for Option<T> {
+    async fn into_jdwp_value(self, client: &JdwpClient) -> result::Result<JdwpValue> {
+        match self {
+            Some(value) => value.into_jdwp_value(client).await,
+            None => Ok(JdwpValue::Object(TaggedObjectId {
+                tag: Tag::Object,
+                object_id: VariableLengthId { value: 0 },
+            })),
+        }
+    }
+}
+/// Null converts to None
+impl<T: TryFromJdwpValue + Send> TryFromJdwpValue for Option<T> {
+    async fn try_from_jdwp_value(value: JdwpValue, client: &JdwpClient) -> result::Result<Self> {
+        match value {
+            JdwpValue::Object(object) if object.object_id.value == 0 => Ok(None),
+            value => Ok(Some(T::try_from_jdwp_value(value, client).await?)),
+        }
+    }
+}
+
+impl IntoJdwpArguments for &[JdwpValue] {
+    async fn into_jdwp_arguments(self, _: &JdwpClient) -> result::Result<Vec<JdwpValue>> {
+        Ok(self.to_vec())
+    }
+}
+impl<const N: usize> IntoJdwpArguments for &[JdwpValue; N] {
+    async fn into_jdwp_arguments(self, _: &JdwpClient) -> result::Result<Vec<JdwpValue>> {
+        Ok(self.to_vec())
+    }
+}
+impl IntoJdwpArguments for Vec<JdwpValue> {
+    async fn into_jdwp_arguments(self, _: &JdwpClient) -> result::Result<Vec<JdwpValue>> {
+        Ok(self)
+    }
+}
+
+macro_rules! tuple_arguments {
+    ($($name:ident),*) => {
+        impl<$($name: IntoJdwpValue + Send),*> IntoJdwpArguments for ($($name,)*) {
+            #[allow(non_snake_case, unused_variables)]
+            async fn into_jdwp_arguments(
+                self,
+                client: &JdwpClient,
+            ) -> result::Result<Vec<JdwpValue>> {
+                let ($($name,)*) = self;
+                Ok(vec![$($name.into_jdwp_value(client).await?),*])
+            }
+        }
+    };
+}
+
+tuple_arguments!();
+tuple_arguments!(A);
+tuple_arguments!(A, B);
+tuple_arguments!(A, B, C);
+tuple_arguments!(A, B, C, D);
+tuple_arguments!(A, B, C, D, E);
+tuple_arguments!(A, B, C, D, E, F);
+tuple_arguments!(A, B, C, D, E, F, G);
+tuple_arguments!(A, B, C, D, E, F, G, H);
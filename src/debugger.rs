@@ -0,0 +1,327 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::time::Instant;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::breakpoint::LineBreakpointOptions;
+use crate::event_channel::{EventReceiver, EventSender, event_channel};
+use crate::{
+    BreakpointSpec, Event, EventKind, EventModifier, EventRequestHandle, HitPolicy,
+    IntoJdwpArguments, JdwpClient, LineBreakpoint, Location, StepDepth, StepSize, SuspendPolicy,
+    Transport, TryFromJdwpValue, VariableLengthId, format_breakpoints, parse_breakpoints, result,
+    type_name_to_signature,
+};
+
+/// Identifies a breakpoint set with [Debugger::set_breakpoint]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BreakpointId(pub(crate) u32);
+
+/// Where and why the debuggee stopped. The thread stays suspended until the debugger continues.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stop {
+    pub thread: VariableLengthId,
+    pub location: Location,
+    /// The breakpoint which was hit, None at the end of a step
+    pub breakpoint: Option<BreakpointId>,
+    pub event: Event,
+    /// When the debugger received the event, comparable with [crate::OutputLine::timestamp]
+    pub timestamp: Instant,
+}
+
+/// A debugging session with a single current stop, for tools which drive the debuggee like a
+/// user in an IDE would
+///
+/// The debugger consumes the events of its client: breakpoint hits and steps are delivered to
+/// its methods and everything else is discarded. Breakpoints and steps only suspend the thread
+/// which hit them.
+pub struct Debugger {
+    client: JdwpClient,
+    breakpoints: BTreeMap<BreakpointId, LineBreakpoint>,
+    specs: BTreeMap<BreakpointId, BreakpointSpec>,
+    next_breakpoint_id: u32,
+    route: EventSender,
+    hits: EventReceiver,
+    stop: Option<Stop>,
+    closed: watch::Receiver<bool>,
+    events: JoinHandle<()>,
+}
+
+impl Debugger {
+    pub fn new(client: JdwpClient) -> Self {
+        let (route, hits) = client.event_channel();
+        let (closed_tx, closed) = watch::channel(false);
+        let events_client = client.clone();
+        let events = tokio::spawn(async move {
+            // Events are only read so the buffer doesn't fill up and hold back the VM
+            while events_client.next_event().await.is_some() {}
+            let _ = closed_tx.send(true);
+        });
+        Debugger {
+            client,
+            breakpoints: BTreeMap::new(),
+            specs: BTreeMap::new(),
+            next_breakpoint_id: 0,
+            route,
+            hits,
+            stop: None,
+            closed,
+            events,
+        }
+    }
+
+    /// Connects to a VM listening on `address`, e.g. `localhost:5005`
+    pub async fn attach(address: &str) -> result::Result<Self> {
+        Ok(Self::new(Transport::tcp(address).connect().await?))
+    }
+
+    pub fn client(&self) -> &JdwpClient {
+        &self.client
+    }
+
+    /// Where the debuggee is stopped, if it is
+    pub fn stop(&self) -> Option<&Stop> {
+        self.stop.as_ref()
+    }
+
+    /// Sets a breakpoint on a source line, see [JdwpClient::set_line_breakpoint]
+    pub async fn set_breakpoint(
+        &mut self,
+        class_name: &str,
+        line: i32,
+    ) -> result::Result<BreakpointId> {
+        self.set_breakpoint_with(BreakpointSpec::new(class_name, line))
+            .await
+    }
+
+    /// Sets a breakpoint with a condition, hit policy or stratum
+    pub async fn set_breakpoint_with(
+        &mut self,
+        spec: BreakpointSpec,
+    ) -> result::Result<BreakpointId> {
+        let mut options = LineBreakpointOptions::new(SuspendPolicy::EventThread);
+        options.stratum = spec.stratum.clone();
+        if let HitPolicy::Nth(count) = spec.hit_policy {
+            options.modifiers.push(EventModifier::Count(count));
+        }
+        // Hits are read from the debugger's channel, not from the breakpoint
+        let (_, unused_hits) = event_channel(1);
+        let breakpoint = self
+            .client
+            .set_line_breakpoint_routed(
+                &spec.class_name,
+                spec.line,
+                options,
+                self.route.clone(),
+                unused_hits,
+            )
+            .await?;
+        self.next_breakpoint_id += 1;
+        let id = BreakpointId(self.next_breakpoint_id);
+        self.breakpoints.insert(id, breakpoint);
+        self.specs.insert(id, spec);
+        Ok(id)
+    }
+
+    pub async fn clear_breakpoint(&mut self, id: BreakpointId) -> result::Result<()> {
+        self.specs.remove(&id);
+        match self.breakpoints.remove(&id) {
+            Some(breakpoint) => breakpoint.clear(&self.client).await,
+            None => Err(result::Error::InvalidArgument {
+                message: format!("Unknown breakpoint {:?}", id),
+            }),
+        }
+    }
+
+    pub fn breakpoint(&self, id: BreakpointId) -> Option<&LineBreakpoint> {
+        self.breakpoints.get(&id)
+    }
+
+    pub fn breakpoint_spec(&self, id: BreakpointId) -> Option<&BreakpointSpec> {
+        self.specs.get(&id)
+    }
+
+    pub fn breakpoint_ids(&self) -> Vec<BreakpointId> {
+        self.breakpoints.keys().copied().collect()
+    }
+
+    /// Writes the breakpoints of the session in the format of [format_breakpoints], so they can
+    /// be saved to a file and set again with [Debugger::import_breakpoints]
+    pub fn export_breakpoints(&self) -> String {
+        // Ordered by id, so breakpoints are exported in the order they were set
+        format_breakpoints(self.specs.values())
+    }
+
+    /// Sets the breakpoints exported by [Debugger::export_breakpoints]. Nothing is set if the
+    /// data can't be parsed, and if setting one of them fails, those set before it are cleared
+    /// again before the error is returned.
+    pub async fn import_breakpoints(&mut self, data: &str) -> result::Result<Vec<BreakpointId>> {
+        let mut ids = Vec::new();
+        for spec in parse_breakpoints(data)? {
+            match self.set_breakpoint_with(spec).await {
+                Ok(id) => ids.push(id),
+                Err(e) => {
+                    for id in ids {
+                        let _ = self.clear_breakpoint(id).await;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Ends the session and leaves the debuggee running: every event request of the client is
+    /// cleared, whatever it suspended is resumed and the connection is disposed. All steps are
+    /// tried; the first error is returned.
+    pub async fn detach(self) -> result::Result<()> {
+        let cleared = self.client.clear_all_event_requests().await;
+        let resumed = self.client.resume_leaked_suspensions().await;
+        let disposed = self.client.vm_dispose().await;
+        cleared.and(resumed).and(disposed)
+    }
+
+    /// Ends the session by terminating the debuggee with the exit code
+    pub async fn kill(self, exit_code: i32) -> result::Result<()> {
+        self.client.vm_exit(exit_code).await
+    }
+
+    /// Resumes the thread of the current stop. Without a stop, the VM is resumed if it is
+    /// suspended, e.g. by a VM started with `suspend=y`.
+    pub async fn continue_(&mut self) -> result::Result<()> {
+        match self.stop.take() {
+            Some(stop) => self.client.thread_resume(stop.thread).await,
+            None if self.client.is_vm_suspended() => self.client.vm_resume().await,
+            None => Ok(()),
+        }
+    }
+
+    /// Continues and waits until a breakpoint is hit. Hits of breakpoints whose condition is
+    /// false are continued from.
+    pub async fn run_to_breakpoint(&mut self) -> result::Result<Stop> {
+        loop {
+            self.continue_().await?;
+            let mut closed = self.closed.clone();
+            let event = tokio::select! {
+                event = self.hits.recv() => event,
+                _ = closed.wait_for(|closed| *closed) => None,
+            };
+            let stop = self.stopped_at(event)?;
+            if self.condition_holds(&stop).await? {
+                return Ok(stop);
+            }
+        }
+    }
+
+    async fn condition_holds(&self, stop: &Stop) -> result::Result<bool> {
+        let Some(condition) = stop
+            .breakpoint
+            .and_then(|id| self.specs.get(&id))
+            .and_then(|spec| spec.condition.as_deref())
+        else {
+            return Ok(true);
+        };
+        let value = self
+            .client
+            .invoke_static_method(stop.thread, stop.location.class_id, condition, ())
+            .await?;
+        bool::try_from_jdwp_value(value, &self.client).await
+    }
+
+    /// Runs the thread of the current stop to the next line, stepping over calls. A breakpoint
+    /// hit before the step completes becomes the new stop instead.
+    pub async fn step_over(&mut self) -> result::Result<Stop> {
+        let thread = self
+            .stop
+            .as_ref()
+            .map(|stop| stop.thread)
+            .ok_or(result::Error::NoSuspendedThread)?;
+        let mut step = self
+            .client
+            .event_request_set(
+                EventKind::SingleStep,
+                SuspendPolicy::EventThread,
+                vec![EventModifier::Step {
+                    thread,
+                    size: StepSize::Line,
+                    depth: StepDepth::Over,
+                }],
+            )
+            .await?;
+
+        let event = self.wait_for_step(&mut step).await;
+        let cleared = self
+            .client
+            .event_request_clear(EventKind::SingleStep, step.request_id())
+            .await;
+        let stop = self.stopped_at(event?)?;
+        cleared?;
+        Ok(stop)
+    }
+
+    async fn wait_for_step(
+        &mut self,
+        step: &mut EventRequestHandle,
+    ) -> result::Result<Option<Event>> {
+        self.continue_().await?;
+        let mut closed = self.closed.clone();
+        Ok(tokio::select! {
+            event = step.next_hit() => event,
+            event = self.hits.recv() => event,
+            _ = closed.wait_for(|closed| *closed) => None,
+        })
+    }
+
+    /// Calls a static method in the thread of the current stop, e.g.
+    /// `evaluate::<i32>("com.example.Main", "count", ())`, and converts its return value
+    pub async fn evaluate<R: TryFromJdwpValue>(
+        &self,
+        class_name: &str,
+        name: &str,
+        arguments: impl IntoJdwpArguments,
+    ) -> result::Result<R> {
+        let stop = self.stop.as_ref().ok_or(result::Error::NoSuspendedThread)?;
+        let class_id = self
+            .client
+            .find_loaded_class(&type_name_to_signature(class_name))
+            .await?;
+        let value = self
+            .client
+            .invoke_static_method(stop.thread, class_id, name, arguments)
+            .await?;
+        R::try_from_jdwp_value(value, &self.client).await
+    }
+
+    fn stopped_at(&mut self, event: Option<Event>) -> result::Result<Stop> {
+        let event = event.ok_or_else(|| {
+            result::Error::IoError(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "Connection closed",
+            ))
+        })?;
+        let (Some(thread), Some(location)) = (event.thread(), event.location()) else {
+            return Err(result::Error::ParsingError {
+                message: format!("Stop event without a location: {:?}", event),
+            });
+        };
+        let breakpoint = self
+            .breakpoints
+            .iter()
+            .find(|(_, breakpoint)| breakpoint.request_ids().contains(&event.request_id()))
+            .map(|(id, _)| *id);
+        let stop = Stop {
+            thread,
+            location,
+            breakpoint,
+            event,
+            timestamp: Instant::now(),
+        };
+        self.stop = Some(stop.clone());
+        Ok(stop)
+    }
+}
+impl Drop for Debugger {
+    fn drop(&mut self) {
+        self.events.abort();
+    }
+}
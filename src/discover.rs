@@ -0,0 +1,240 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use crate::connection::{do_handshake, send_directly};
+use crate::{CapabilitiesNewReply, Command, JdwpClient, JdwpIdSizes, VersionReply, result};
+
+/// Ports JDWP agents are commonly configured to listen on
+pub const DEFAULT_DISCOVERY_PORTS: [u16; 4] = [5005, 8000, 8787, 9009];
+
+/// Largest number of addresses a CIDR range passed to [discover] may contain
+pub const MAX_DISCOVERY_HOSTS: usize = 1 << 16;
+
+/// Ports, parallelism and timeouts of a [discover] scan
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveryOptions {
+    pub ports: Vec<u16>,
+    /// Number of endpoints probed at the same time
+    pub concurrency: usize,
+    /// Limit for connecting, for the handshake and for the Version command, each
+    pub timeout: Duration,
+}
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        DiscoveryOptions {
+            ports: DEFAULT_DISCOVERY_PORTS.to_vec(),
+            concurrency: 64,
+            timeout: Duration::from_secs(2),
+        }
+    }
+}
+
+/// Version of a discovered VM, as replied to VirtualMachine.Version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredVersion {
+    pub description: String,
+    pub jdwp_major: i32,
+    pub jdwp_minor: i32,
+    pub vm_version: String,
+    pub vm_name: String,
+}
+impl From<VersionReply> for DiscoveredVersion {
+    fn from(version: VersionReply) -> Self {
+        DiscoveredVersion {
+            description: version.description.string,
+            jdwp_major: version.jdwp_major,
+            jdwp_minor: version.jdwp_minor,
+            vm_version: version.vm_version.string,
+            vm_name: version.vm_name.string,
+        }
+    }
+}
+
+/// An endpoint which answered the JDWP handshake
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredEndpoint {
+    pub address: SocketAddr,
+    /// None if the agent completed the handshake but didn't reply to VirtualMachine.Version
+    pub version: Option<DiscoveredVersion>,
+}
+
+/// What [JdwpClient::probe] found out about a VM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmFingerprint {
+    pub version: DiscoveredVersion,
+    pub id_sizes: JdwpIdSizes,
+    pub capabilities: CapabilitiesNewReply,
+}
+
+impl JdwpClient {
+    /// Connects to `address`, fingerprints the VM like [JdwpClient::probe_stream] and
+    /// disconnects. `probe_timeout` limits the whole probe.
+    pub async fn probe(address: &str, probe_timeout: Duration) -> result::Result<VmFingerprint> {
+        timeout(probe_timeout, async {
+            let stream = TcpStream::connect(address).await?;
+            stream.set_nodelay(true)?;
+            Self::probe_stream(stream).await
+        })
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Probe timed out"))?
+    }
+
+    /// Handshakes, asks the VM for its version, id sizes and capabilities and disposes the
+    /// connection again, for inventory tools. Unlike [JdwpClient::new] this doesn't start the
+    /// reader and writer tasks; events the VM sends in the meantime are ignored.
+    pub async fn probe_stream<T>(mut stream: T) -> result::Result<VmFingerprint>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        do_handshake(&mut stream).await?;
+        let mut events = Vec::new();
+        let id_sizes = send_directly(&mut stream, 1, Command::VirtualMachineIDSizes, &mut events)
+            .await
+            .and_then(JdwpClient::id_sizes_from_reply)?;
+        let version: VersionReply =
+            send_directly(&mut stream, 2, Command::VirtualMachineVersion, &mut events).await?;
+        let capabilities = send_directly(
+            &mut stream,
+            3,
+            Command::VirtualMachineCapabilitiesNew,
+            &mut events,
+        )
+        .await?;
+        send_directly::<_, ()>(&mut stream, 4, Command::VirtualMachineDispose, &mut events).await?;
+        Ok(VmFingerprint {
+            version: version.into(),
+            id_sizes,
+            capabilities,
+        })
+    }
+}
+
+/// Scans `target`, a host name, an IP address or a CIDR range such as `10.0.0.0/24`, for JDWP
+/// agents on the ports of `options`. Endpoints are returned sorted by address.
+///
+/// Each endpoint which accepts a connection is sent the handshake; those echoing it are asked
+/// for their version and disconnected again. An agent accepts a single debugger, so one which
+/// is already being debugged doesn't answer and isn't found.
+pub async fn discover(
+    target: &str,
+    options: &DiscoveryOptions,
+) -> result::Result<Vec<DiscoveredEndpoint>> {
+    let hosts = discovery_hosts(target)?;
+    let semaphore = Arc::new(Semaphore::new(options.concurrency.max(1)));
+    let mut probes = JoinSet::new();
+    for host in hosts {
+        for port in &options.ports {
+            let permit = semaphore
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("semaphore is never closed");
+            let host = host.clone();
+            let port = *port;
+            let probe_timeout = options.timeout;
+            probes.spawn(async move {
+                let _permit = permit;
+                probe(&host, port, probe_timeout).await
+            });
+        }
+    }
+
+    let mut endpoints = Vec::new();
+    while let Some(probe) = probes.join_next().await {
+        if let Ok(Some(endpoint)) = probe {
+            endpoints.push(endpoint);
+        }
+    }
+    endpoints.sort_by_key(|endpoint| endpoint.address);
+    Ok(endpoints)
+}
+
+async fn probe(host: &str, port: u16, probe_timeout: Duration) -> Option<DiscoveredEndpoint> {
+    let mut stream = timeout(probe_timeout, TcpStream::connect((host, port)))
+        .await
+        .ok()?
+        .ok()?;
+    let address = stream.peer_addr().ok()?;
+    timeout(probe_timeout, do_handshake(&mut stream))
+        .await
+        .ok()?
+        .ok()?;
+
+    let version = timeout(probe_timeout, async {
+        let client = JdwpClient::after_handshake(stream).await?;
+        client.vm_get_version().await
+    })
+    .await
+    .ok()
+    .and_then(Result::ok)
+    .map(DiscoveredVersion::from);
+    Some(DiscoveredEndpoint { address, version })
+}
+
+/// Expands a host, an address or a CIDR range into the hosts to connect to
+fn discovery_hosts(target: &str) -> result::Result<Vec<String>> {
+    let Some((network, prefix)) = target.split_once('/') else {
+        return Ok(vec![String::from(target)]);
+    };
+    let invalid = |message: String| result::Error::InvalidArgument { message };
+    let network: IpAddr = network
+        .parse()
+        .map_err(|_| invalid(format!("Invalid network address in {}", target)))?;
+    let bits = match network {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+    let prefix: u32 = prefix
+        .parse()
+        .ok()
+        .filter(|prefix| *prefix <= bits)
+        .ok_or_else(|| invalid(format!("Invalid prefix length in {}", target)))?;
+    let host_bits = bits - prefix;
+    if host_bits > MAX_DISCOVERY_HOSTS.trailing_zeros() {
+        return Err(invalid(format!(
+            "{} contains more than {} addresses",
+            target, MAX_DISCOVERY_HOSTS
+        )));
+    }
+
+    let first = match network {
+        IpAddr::V4(address) => u32::from(address) as u128,
+        IpAddr::V6(address) => u128::from(address),
+    } & !((1u128 << host_bits) - 1);
+    Ok((0..1u128 << host_bits)
+        .map(|offset| match network {
+            IpAddr::V4(_) => Ipv4Addr::from((first + offset) as u32).to_string(),
+            IpAddr::V6(_) => Ipv6Addr::from(first + offset).to_string(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_discovery_hosts() {
+        assert_eq!(discovery_hosts("localhost").unwrap(), ["localhost"]);
+        assert_eq!(
+            discovery_hosts("10.0.0.5/30").unwrap(),
+            ["10.0.0.4", "10.0.0.5", "10.0.0.6", "10.0.0.7"]
+        );
+        assert_eq!(
+            discovery_hosts("fd00::1/127").unwrap(),
+            ["fd00::", "fd00::1"]
+        );
+        assert_eq!(discovery_hosts("10.0.0.0/16").unwrap().len(), 1 << 16);
+        assert!(discovery_hosts("10.0.0.0/8").is_err());
+        assert!(discovery_hosts("10.0.0.0/33").is_err());
+        assert!(discovery_hosts("example.com/24").is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::Mutex;
+
+use crate::connection::do_handshake;
+use crate::{JdwpClient, result};
+
+/// Reads and writes the packets of a connection, the work [JdwpClient::new] spawns background
+/// tasks for. Returned by [JdwpClient::new_with_driver] for callers which run it themselves,
+/// e.g. in a `JoinSet` of their own or on a runtime where libraries mustn't spawn.
+///
+/// The client sends and receives nothing unless the driver is polled. The driver completes
+/// once every handle to the client is dropped and the VM's replies have been read, or once the
+/// VM closes the connection and every handle is dropped. Dropping the driver instead aborts the
+/// connection; requests still waiting for replies then time out. A panic while handling a
+/// packet surfaces where the driver is polled rather than in a detached task.
+pub struct ClientDriver {
+    future: Pin<Box<dyn Future<Output = ()> + Send>>,
+    done: bool,
+}
+impl ClientDriver {
+    pub(crate) fn new(future: impl Future<Output = ()> + Send + 'static) -> Self {
+        ClientDriver {
+            future: Box::pin(future),
+            done: false,
+        }
+    }
+}
+impl Future for ClientDriver {
+    type Output = ();
+
+    /// Stays ready once complete, as inline clients poll it again after the connection closed
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.done {
+            return Poll::Ready(());
+        }
+        let poll = self.future.as_mut().poll(cx);
+        self.done = poll.is_ready();
+        poll
+    }
+}
+impl fmt::Debug for ClientDriver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClientDriver").finish_non_exhaustive()
+    }
+}
+
+impl JdwpClient {
+    /// Like [JdwpClient::new], but returns the [ClientDriver] of the connection instead of
+    /// spawning tasks for it. The handshake and the negotiation of id sizes are done before
+    /// returning, without the driver.
+    pub async fn new_with_driver<T>(mut stream: T) -> result::Result<(Self, ClientDriver)>
+    where
+        T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        do_handshake(&mut stream).await?;
+        Self::after_handshake_with_driver(stream).await
+    }
+
+    /// Sets up a client which runs entirely on the tasks using it: commands and
+    /// [JdwpClient::next_event] read and write the stream while they wait, and nothing is
+    /// spawned. Combined with a mock stream and `#[tokio::test(start_paused = true)]`, every
+    /// packet and timeout happens in a deterministic order.
+    ///
+    /// Only waiting handles drive the connection: events reach subscriptions and the event bus
+    /// only while a command or [JdwpClient::next_event] is waiting, and a streamed
+    /// [crate::ReplyBody] stalls, as nothing reads the rest of it.
+    pub async fn new_inline<T>(stream: T) -> result::Result<Self>
+    where
+        T: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    {
+        let (mut client, driver) = Self::new_with_driver(stream).await?;
+        client.inline_driver = Some(Arc::new(Mutex::new(driver)));
+        Ok(client)
+    }
+
+    /// Waits for `future`, polling the driver of an inline client meanwhile. Concurrent waiters
+    /// take turns: the driver is polled by whichever holds its lock.
+    pub(crate) async fn drive_until<F: Future>(&self, future: F) -> F::Output {
+        let Some(driver) = &self.inline_driver else {
+            return future.await;
+        };
+        tokio::pin!(future);
+        tokio::select! {
+            biased;
+            output = &mut future => return output,
+            _ = async { (&mut *driver.lock().await).await } => {}
+        }
+        // The connection is closed, which fails or times out whatever is awaited
+        future.await
+    }
+}
@@ -0,0 +1,31 @@
+use crate::{JdwpClient, result};
+
+/// A stretch of time in which no thread has run, as seen by the client
+///
+/// Frame ids, local values and stack snapshots read in one epoch can't be trusted in the next,
+/// so caching layers tag what they keep with the epoch it was read in and drop it once
+/// [SuspendEpoch::is_current] turns false. The epoch changes whenever a thread is resumed by
+/// this client and runs, and on every [JdwpClient::resume_barrier].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SuspendEpoch(u64);
+impl SuspendEpoch {
+    pub fn is_current(&self, client: &JdwpClient) -> bool {
+        client.suspend_epoch() == *self
+    }
+}
+
+impl JdwpClient {
+    pub fn suspend_epoch(&self) -> SuspendEpoch {
+        SuspendEpoch(self.shared.suspension().epoch())
+    }
+
+    /// Resumes the VM once and starts a new epoch, even if the VM stays suspended by another
+    /// VirtualMachine.Suspend. Frames read before are stale afterwards, see
+    /// [JdwpClient::is_frame_stale].
+    pub async fn resume_barrier(&self) -> result::Result<SuspendEpoch> {
+        self.vm_resume().await?;
+        let mut suspension = self.shared.suspension();
+        suspension.barrier();
+        Ok(SuspendEpoch(suspension.epoch()))
+    }
+}
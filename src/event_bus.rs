@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use crate::connection::Shared;
+use crate::event_channel::{EventReceiver, EventSender, event_channel};
+use crate::{Event, EventComposite, EventKind, VariableLengthId};
+
+/// Client-side filter applied to single events. Each criterion matches if it's empty or if the
+/// event matches any of its values, and an event has to match all criteria.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EventFilter {
+    pub kinds: Vec<EventKind>,
+    pub threads: Vec<VariableLengthId>,
+    pub classes: Vec<VariableLengthId>,
+}
+impl EventFilter {
+    pub fn matches(&self, event: &Event) -> bool {
+        (self.kinds.is_empty() || self.kinds.contains(&event.kind()))
+            && (self.threads.is_empty()
+                || event
+                    .thread()
+                    .is_some_and(|thread| self.threads.contains(&thread)))
+            && (self.classes.is_empty()
+                || event
+                    .class()
+                    .is_some_and(|class| self.classes.contains(&class)))
+    }
+}
+
+/// Builds a subscription to events sent by the VM, created with [crate::JdwpClient::events]
+///
+/// The filters only apply on the client, the VM still sends every event its event requests
+/// generate.
+pub struct EventSubscriptionBuilder {
+    shared: Arc<Shared>,
+    filter: EventFilter,
+}
+impl EventSubscriptionBuilder {
+    pub(crate) fn new(shared: Arc<Shared>) -> Self {
+        EventSubscriptionBuilder {
+            shared,
+            filter: EventFilter::default(),
+        }
+    }
+
+    /// Accepts events of the kind. Can be called multiple times to accept several kinds.
+    pub fn filter(mut self, kind: EventKind) -> Self {
+        self.filter.kinds.push(kind);
+        self
+    }
+
+    /// Accepts events which occurred in the thread
+    pub fn in_thread(mut self, thread: VariableLengthId) -> Self {
+        self.filter.threads.push(thread);
+        self
+    }
+
+    /// Accepts events which occurred in, or are about, the class
+    pub fn in_class(mut self, class: VariableLengthId) -> Self {
+        self.filter.classes.push(class);
+        self
+    }
+
+    /// Starts receiving matching events. Events received before this call are not delivered.
+    pub fn stream(self) -> EventSubscription {
+        let (sender, events) = event_channel(self.shared.events.config().capacity);
+        self.shared.bus().subscribe(self.filter.clone(), sender);
+        EventSubscription {
+            filter: self.filter,
+            events,
+        }
+    }
+}
+
+/// Events matching a filter, in the order they arrived. The events are also delivered to the
+/// client's global event stream.
+///
+/// Like an [crate::EventRequestHandle], the subscription buffers as many events as the
+/// client's event buffer holds event sets and discards its oldest event once full.
+#[derive(Debug)]
+pub struct EventSubscription {
+    filter: EventFilter,
+    events: EventReceiver,
+}
+impl EventSubscription {
+    pub fn event_filter(&self) -> &EventFilter {
+        &self.filter
+    }
+
+    /// Waits for the next matching event. Returns None once the connection is closed and all
+    /// received events have been consumed.
+    pub async fn next(&mut self) -> Option<Event> {
+        self.events.recv().await
+    }
+
+    /// Returns the next received event without waiting
+    pub fn try_next(&mut self) -> Option<Event> {
+        self.events.try_recv()
+    }
+
+    /// Number of events discarded because the subscription's buffer was full
+    pub fn dropped_event_count(&self) -> u64 {
+        self.events.dropped()
+    }
+}
+
+/// Delivers single events to event request handles and subscriptions
+#[derive(Default)]
+pub(crate) struct EventBus {
+    /// Event requests whose events are delivered to an [crate::EventRequestHandle]
+    routes: HashMap<i32, EventSender>,
+    subscriptions: Vec<(EventFilter, EventSender)>,
+    /// Receivers of whole event sets, such as [crate::Hooks], which need the suspend policy
+    composite_subscriptions: Vec<mpsc::UnboundedSender<EventComposite>>,
+    closed: bool,
+}
+impl EventBus {
+    pub(crate) fn add_route(&mut self, request_id: i32, route: EventSender) {
+        if !self.closed {
+            self.routes.insert(request_id, route);
+        }
+    }
+
+    pub(crate) fn remove_route(&mut self, request_id: i32) {
+        self.routes.remove(&request_id);
+    }
+
+    fn subscribe(&mut self, filter: EventFilter, sender: EventSender) {
+        if !self.closed {
+            self.subscriptions.push((filter, sender));
+        }
+    }
+
+    pub(crate) fn subscribe_composites(&mut self, sender: mpsc::UnboundedSender<EventComposite>) {
+        if !self.closed {
+            self.composite_subscriptions.push(sender);
+        }
+    }
+
+    /// Delivers each event of the set. Routes and subscriptions whose receiver was dropped are
+    /// removed.
+    pub(crate) fn deliver(&mut self, composite: &EventComposite) {
+        self.composite_subscriptions
+            .retain(|sender| sender.send(composite.clone()).is_ok());
+        for event in &composite.events {
+            let request_id = event.request_id();
+            if let Some(route) = self.routes.get(&request_id)
+                && route.send(event.clone()).is_err()
+            {
+                self.routes.remove(&request_id);
+            }
+
+            self.subscriptions.retain(|(filter, sender)| {
+                !filter.matches(event) || sender.send(event.clone()).is_ok()
+            });
+        }
+    }
+
+    /// Closes every route and subscription once the connection is gone
+    pub(crate) fn close(&mut self) {
+        self.closed = true;
+        self.routes.clear();
+        self.subscriptions.clear();
+        self.composite_subscriptions.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Location, TypeTag};
+
+    fn breakpoint(thread: u64, class: u64) -> Event {
+        Event::Breakpoint {
+            request_id: 1,
+            thread: VariableLengthId { value: thread },
+            location: Location {
+                type_tag: TypeTag::Class,
+                class_id: VariableLengthId { value: class },
+                method_id: VariableLengthId { value: 1 },
+                index: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_empty_filter_matches_everything() {
+        let filter = EventFilter::default();
+        assert!(filter.matches(&breakpoint(1, 2)));
+        assert!(filter.matches(&Event::VmDeath { request_id: 0 }));
+    }
+
+    #[test]
+    fn test_filter_criteria_combine() {
+        let filter = EventFilter {
+            kinds: vec![EventKind::Breakpoint, EventKind::SingleStep],
+            threads: vec![VariableLengthId { value: 1 }],
+            classes: vec![],
+        };
+        assert!(filter.matches(&breakpoint(1, 2)));
+        assert!(!filter.matches(&breakpoint(3, 2)));
+        assert!(!filter.matches(&Event::ThreadStart {
+            request_id: 1,
+            thread: VariableLengthId { value: 1 },
+        }));
+
+        // Events without a thread don't match a thread filter
+        assert!(!filter.matches(&Event::VmDeath { request_id: 0 }));
+    }
+}
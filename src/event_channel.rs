@@ -0,0 +1,188 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+use crate::{Event, JdwpClient};
+
+/// Creates a channel buffering up to `capacity` events for one receiver. Once the buffer is
+/// full, each new event discards the oldest one, so a receiver nobody reads doesn't grow
+/// without limit and never holds back the reader task delivering to it.
+pub(crate) fn event_channel(capacity: usize) -> (EventSender, EventReceiver) {
+    let channel = Arc::new(Channel {
+        state: Mutex::new(ChannelState {
+            events: VecDeque::new(),
+            capacity: capacity.max(1),
+            senders: 1,
+            receiver_alive: true,
+            dropped: 0,
+        }),
+        available: Notify::new(),
+    });
+    (
+        EventSender {
+            channel: channel.clone(),
+        },
+        EventReceiver { channel },
+    )
+}
+
+struct Channel {
+    state: Mutex<ChannelState>,
+    /// Notified when an event is sent or the last sender is dropped
+    available: Notify,
+}
+impl Channel {
+    fn lock(&self) -> std::sync::MutexGuard<'_, ChannelState> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+struct ChannelState {
+    events: VecDeque<Event>,
+    capacity: usize,
+    senders: usize,
+    receiver_alive: bool,
+    /// Events discarded to make room for newer ones
+    dropped: u64,
+}
+
+/// Sending half of an [event_channel], cloned to route several event requests to one receiver
+pub(crate) struct EventSender {
+    channel: Arc<Channel>,
+}
+impl EventSender {
+    /// Buffers the event, discarding the oldest one if the buffer is full. Fails with the event
+    /// once the receiver was dropped.
+    pub(crate) fn send(&self, event: Event) -> Result<(), Event> {
+        let mut state = self.channel.lock();
+        if !state.receiver_alive {
+            return Err(event);
+        }
+        if state.events.len() >= state.capacity {
+            state.events.pop_front();
+            state.dropped += 1;
+        }
+        state.events.push_back(event);
+        drop(state);
+        self.channel.available.notify_one();
+        Ok(())
+    }
+}
+impl Clone for EventSender {
+    fn clone(&self) -> Self {
+        self.channel.lock().senders += 1;
+        EventSender {
+            channel: self.channel.clone(),
+        }
+    }
+}
+impl Drop for EventSender {
+    fn drop(&mut self) {
+        let mut state = self.channel.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            drop(state);
+            self.channel.available.notify_one();
+        }
+    }
+}
+
+/// Receiving half of an [event_channel]
+pub(crate) struct EventReceiver {
+    channel: Arc<Channel>,
+}
+impl EventReceiver {
+    /// Waits for the next event. Returns None once every sender is dropped and the buffered
+    /// events have been received.
+    pub(crate) async fn recv(&mut self) -> Option<Event> {
+        loop {
+            let notified = self.channel.available.notified();
+            {
+                let mut state = self.channel.lock();
+                if let Some(event) = state.events.pop_front() {
+                    return Some(event);
+                }
+                if state.senders == 0 {
+                    return None;
+                }
+            }
+            notified.await;
+        }
+    }
+
+    /// Returns the next buffered event without waiting
+    pub(crate) fn try_recv(&mut self) -> Option<Event> {
+        self.channel.lock().events.pop_front()
+    }
+
+    /// Number of events discarded because the buffer was full
+    pub(crate) fn dropped(&self) -> u64 {
+        self.channel.lock().dropped
+    }
+}
+impl Drop for EventReceiver {
+    fn drop(&mut self) {
+        let mut state = self.channel.lock();
+        state.receiver_alive = false;
+        state.events.clear();
+    }
+}
+impl std::fmt::Debug for EventReceiver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = self.channel.lock();
+        f.debug_struct("EventReceiver")
+            .field("buffered", &state.events.len())
+            .field("dropped", &state.dropped)
+            .finish()
+    }
+}
+
+impl JdwpClient {
+    /// Creates a channel for the events of event requests, holding as many events as the
+    /// client's event buffer holds event sets
+    pub(crate) fn event_channel(&self) -> (EventSender, EventReceiver) {
+        event_channel(self.shared.events.config().capacity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vm_death(request_id: i32) -> Event {
+        Event::VmDeath { request_id }
+    }
+
+    #[tokio::test]
+    async fn test_full_channel_drops_oldest() {
+        let (sender, mut receiver) = event_channel(2);
+        for request_id in 1..=3 {
+            sender.send(vm_death(request_id)).unwrap();
+        }
+        assert_eq!(receiver.dropped(), 1);
+        assert_eq!(receiver.recv().await, Some(vm_death(2)));
+        assert_eq!(receiver.try_recv(), Some(vm_death(3)));
+        assert_eq!(receiver.try_recv(), None);
+    }
+
+    #[tokio::test]
+    async fn test_closed_once_every_sender_is_dropped() {
+        let (sender, mut receiver) = event_channel(2);
+        let other = sender.clone();
+        drop(sender);
+        other.send(vm_death(1)).unwrap();
+        drop(other);
+        assert_eq!(receiver.recv().await, Some(vm_death(1)));
+        assert_eq!(receiver.recv().await, None);
+    }
+
+    #[test]
+    fn test_send_fails_once_receiver_is_dropped() {
+        let (sender, receiver) = event_channel(2);
+        drop(receiver);
+        assert!(sender.send(vm_death(1)).is_err());
+    }
+}
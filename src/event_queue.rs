@@ -0,0 +1,284 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+use crate::EventComposite;
+
+/// What the client does when the event buffer is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Discard the oldest buffered event set to make room for the new one. The default, as it
+    /// never holds back the VM or the replies of commands.
+    DropOldest,
+    /// Stop reading from the connection until the consumer catches up. Replies to commands are
+    /// delayed as well, so the consumer must not wait on commands while the buffer is full.
+    BlockReader,
+    /// Send VirtualMachine.HoldEvents to make the VM hold back further events, and
+    /// VirtualMachine.ReleaseEvents once the buffer has drained to half its capacity. Blocks
+    /// the reader like [OverflowPolicy::BlockReader] on VMs with [crate::Quirks::NO_HOLD_EVENTS].
+    ///
+    /// The VM blocks the threads which generate events while they are held, so a slow or
+    /// absent consumer stalls the debuggee. Events already in flight when HoldEvents is sent
+    /// are still buffered, so the buffer can grow past its capacity while events are held.
+    HoldEvents,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventBufferConfig {
+    /// Maximum number of buffered event sets, which [OverflowPolicy::HoldEvents] may exceed
+    pub capacity: usize,
+    pub overflow_policy: OverflowPolicy,
+}
+impl Default for EventBufferConfig {
+    fn default() -> Self {
+        EventBufferConfig {
+            capacity: 1024,
+            overflow_policy: OverflowPolicy::DropOldest,
+        }
+    }
+}
+
+/// Action the caller has to take on the VM after touching the queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FlowControl {
+    None,
+    HoldEvents,
+    ReleaseEvents,
+}
+
+struct EventQueueState {
+    events: VecDeque<EventComposite>,
+    config: EventBufferConfig,
+    dropped: u64,
+    held: bool,
+    hold_events_supported: bool,
+    closed: bool,
+}
+
+/// Bounded buffer between the reader task and the consumer of events
+pub(crate) struct EventQueue {
+    state: Mutex<EventQueueState>,
+    events_available: Notify,
+    space_available: Notify,
+}
+impl EventQueue {
+    pub(crate) fn new(config: EventBufferConfig) -> Self {
+        EventQueue {
+            state: Mutex::new(EventQueueState {
+                events: VecDeque::new(),
+                config,
+                dropped: 0,
+                held: false,
+                hold_events_supported: true,
+                closed: false,
+            }),
+            events_available: Notify::new(),
+            space_available: Notify::new(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, EventQueueState> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    pub(crate) fn set_config(&self, config: EventBufferConfig) {
+        self.lock().config = config;
+        // A larger capacity may unblock the reader
+        self.space_available.notify_waiters();
+    }
+
+    /// Whether the VM implements VirtualMachine.HoldEvents, without which
+    /// [OverflowPolicy::HoldEvents] has to block the reader
+    pub(crate) fn set_hold_events_supported(&self, supported: bool) {
+        self.lock().hold_events_supported = supported;
+        self.space_available.notify_waiters();
+    }
+
+    pub(crate) fn config(&self) -> EventBufferConfig {
+        self.lock().config
+    }
+
+    pub(crate) fn dropped(&self) -> u64 {
+        self.lock().dropped
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.lock().events.len()
+    }
+
+    pub(crate) async fn push(&self, composite: EventComposite) -> FlowControl {
+        loop {
+            let space_available = self.space_available.notified();
+            tokio::pin!(space_available);
+            space_available.as_mut().enable();
+
+            {
+                let mut state = self.lock();
+                let capacity = state.config.capacity.max(1);
+                let full = state.events.len() >= capacity;
+                let flow = match state.config.overflow_policy {
+                    OverflowPolicy::BlockReader if full && !state.closed => None,
+                    OverflowPolicy::DropOldest if full => {
+                        state.events.pop_front();
+                        state.dropped += 1;
+                        Some(FlowControl::None)
+                    }
+                    OverflowPolicy::HoldEvents
+                        if full && !state.hold_events_supported && !state.closed =>
+                    {
+                        None
+                    }
+                    // Events which were already in flight still have to be buffered
+                    OverflowPolicy::HoldEvents if full && !state.held => {
+                        state.held = true;
+                        Some(FlowControl::HoldEvents)
+                    }
+                    _ => Some(FlowControl::None),
+                };
+
+                if let Some(flow) = flow {
+                    state.events.push_back(composite);
+                    drop(state);
+                    self.events_available.notify_one();
+                    return flow;
+                }
+            }
+
+            space_available.await;
+        }
+    }
+
+    /// Returns the next event set, or None once the queue is closed and drained
+    pub(crate) async fn pop(&self) -> (Option<EventComposite>, FlowControl) {
+        loop {
+            let events_available = self.events_available.notified();
+            tokio::pin!(events_available);
+            events_available.as_mut().enable();
+
+            let (composite, flow) = self.try_pop();
+            if composite.is_some() || self.lock().closed {
+                return (composite, flow);
+            }
+
+            events_available.await;
+        }
+    }
+
+    pub(crate) fn try_pop(&self) -> (Option<EventComposite>, FlowControl) {
+        let mut state = self.lock();
+        let composite = state.events.pop_front();
+        if composite.is_none() {
+            return (None, FlowControl::None);
+        }
+
+        let mut flow = FlowControl::None;
+        if state.held && state.events.len() <= state.config.capacity / 2 {
+            state.held = false;
+            flow = FlowControl::ReleaseEvents;
+        }
+        drop(state);
+
+        self.space_available.notify_one();
+        (composite, flow)
+    }
+
+    pub(crate) fn close(&self) {
+        self.lock().closed = true;
+        self.events_available.notify_waiters();
+        self.space_available.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SuspendPolicy;
+
+    fn composite() -> EventComposite {
+        EventComposite {
+            suspend_policy: SuspendPolicy::None,
+            events: Vec::new(),
+        }
+    }
+
+    fn config(capacity: usize, overflow_policy: OverflowPolicy) -> EventBufferConfig {
+        EventBufferConfig {
+            capacity,
+            overflow_policy,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest() {
+        let queue = EventQueue::new(config(2, OverflowPolicy::DropOldest));
+        for _ in 0..5 {
+            assert_eq!(queue.push(composite()).await, FlowControl::None);
+        }
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.dropped(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_hold_and_release() {
+        let queue = EventQueue::new(config(2, OverflowPolicy::HoldEvents));
+        assert_eq!(queue.push(composite()).await, FlowControl::None);
+        assert_eq!(queue.push(composite()).await, FlowControl::None);
+        assert_eq!(queue.push(composite()).await, FlowControl::HoldEvents);
+        assert_eq!(queue.push(composite()).await, FlowControl::None);
+        assert_eq!(queue.len(), 4);
+
+        assert_eq!(queue.try_pop().1, FlowControl::None);
+        assert_eq!(queue.try_pop().1, FlowControl::None);
+        assert_eq!(queue.try_pop().1, FlowControl::ReleaseEvents);
+        assert_eq!(queue.try_pop().1, FlowControl::None);
+        assert_eq!(queue.dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_block_reader() {
+        let queue = std::sync::Arc::new(EventQueue::new(config(1, OverflowPolicy::BlockReader)));
+        queue.push(composite()).await;
+
+        let producer = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.push(composite()).await })
+        };
+        tokio::task::yield_now().await;
+        assert!(!producer.is_finished());
+        assert_eq!(queue.len(), 1);
+
+        assert!(queue.pop().await.0.is_some());
+        producer.await.unwrap();
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_hold_events_unsupported() {
+        let queue = std::sync::Arc::new(EventQueue::new(config(1, OverflowPolicy::HoldEvents)));
+        queue.set_hold_events_supported(false);
+        assert_eq!(queue.push(composite()).await, FlowControl::None);
+
+        let producer = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.push(composite()).await })
+        };
+        tokio::task::yield_now().await;
+        assert!(!producer.is_finished());
+
+        assert_eq!(queue.pop().await.1, FlowControl::None);
+        assert_eq!(producer.await.unwrap(), FlowControl::None);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pop_after_close() {
+        let queue = EventQueue::new(EventBufferConfig::default());
+        queue.push(composite()).await;
+        queue.close();
+        assert!(queue.pop().await.0.is_some());
+        assert!(queue.pop().await.0.is_none());
+    }
+}
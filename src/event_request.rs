@@ -0,0 +1,250 @@
+#[cfg(feature = "tokio")]
+use crate::event_channel::EventReceiver;
+#[cfg(feature = "tokio")]
+use crate::{Event, EventKind, SuspendPolicy};
+use crate::{JdwpIdSizes, JdwpString, Location, StepDepth, StepSize, VariableLengthId};
+use binrw::{BinRead, BinWrite};
+
+/// Restricts which events an event request reports. Modifiers are applied in order, so e.g. a
+/// count placed after a class filter only counts events which passed the filter.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EventModifier {
+    /// Report only the n-th occurrence, after which the request is cancelled
+    Count(i32),
+    Conditional {
+        expr_id: i32,
+    },
+    ThreadOnly(VariableLengthId),
+    ClassOnly(VariableLengthId),
+    /// Class name pattern, which may begin or end with '*'
+    ClassMatch(String),
+    ClassExclude(String),
+    LocationOnly(Location),
+    ExceptionOnly {
+        /// Exception class, or null (0) for any exception
+        exception_or_null: VariableLengthId,
+        caught: bool,
+        uncaught: bool,
+    },
+    FieldOnly {
+        declaring: VariableLengthId,
+        field_id: VariableLengthId,
+    },
+    Step {
+        thread: VariableLengthId,
+        size: StepSize,
+        depth: StepDepth,
+    },
+    InstanceOnly(VariableLengthId),
+    SourceNameMatch(String),
+    PlatformThreadsOnly,
+}
+impl EventModifier {
+    pub fn mod_kind(&self) -> u8 {
+        match self {
+            EventModifier::Count(_) => 1,
+            EventModifier::Conditional { .. } => 2,
+            EventModifier::ThreadOnly(_) => 3,
+            EventModifier::ClassOnly(_) => 4,
+            EventModifier::ClassMatch(_) => 5,
+            EventModifier::ClassExclude(_) => 6,
+            EventModifier::LocationOnly(_) => 7,
+            EventModifier::ExceptionOnly { .. } => 8,
+            EventModifier::FieldOnly { .. } => 9,
+            EventModifier::Step { .. } => 10,
+            EventModifier::InstanceOnly(_) => 11,
+            EventModifier::SourceNameMatch(_) => 12,
+            EventModifier::PlatformThreadsOnly => 13,
+        }
+    }
+}
+impl BinWrite for EventModifier {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.mod_kind().write_options(writer, endian, ())?;
+        match self {
+            EventModifier::Count(count) => count.write_options(writer, endian, ()),
+            EventModifier::Conditional { expr_id } => expr_id.write_options(writer, endian, ()),
+            EventModifier::ThreadOnly(id) | EventModifier::InstanceOnly(id) => {
+                id.write_options(writer, endian, args.object_id_size)
+            }
+            EventModifier::ClassOnly(id) => {
+                id.write_options(writer, endian, args.reference_type_id_size)
+            }
+            EventModifier::ClassMatch(pattern)
+            | EventModifier::ClassExclude(pattern)
+            | EventModifier::SourceNameMatch(pattern) => JdwpString {
+                string: pattern.clone(),
+            }
+            .write_options(writer, endian, ()),
+            EventModifier::LocationOnly(location) => location.write_options(writer, endian, args),
+            EventModifier::ExceptionOnly {
+                exception_or_null,
+                caught,
+                uncaught,
+            } => {
+                exception_or_null.write_options(writer, endian, args.reference_type_id_size)?;
+                (*caught as u8).write_options(writer, endian, ())?;
+                (*uncaught as u8).write_options(writer, endian, ())
+            }
+            EventModifier::FieldOnly {
+                declaring,
+                field_id,
+            } => {
+                declaring.write_options(writer, endian, args.reference_type_id_size)?;
+                field_id.write_options(writer, endian, args.field_id_size)
+            }
+            EventModifier::Step {
+                thread,
+                size,
+                depth,
+            } => {
+                thread.write_options(writer, endian, args.object_id_size)?;
+                size.write_options(writer, endian, ())?;
+                depth.write_options(writer, endian, ())
+            }
+            EventModifier::PlatformThreadsOnly => Ok(()),
+        }
+    }
+}
+
+/// Decodes modifiers as a VM does, e.g. for a mock VM or a proxy inspecting event requests
+impl BinRead for EventModifier {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        let position = reader.stream_position()?;
+        let mod_kind = u8::read_options(reader, endian, ())?;
+        let string = |reader: &mut R| -> binrw::BinResult<String> {
+            Ok(JdwpString::read_options(reader, endian, ())?.string)
+        };
+        Ok(match mod_kind {
+            1 => EventModifier::Count(i32::read_options(reader, endian, ())?),
+            2 => EventModifier::Conditional {
+                expr_id: i32::read_options(reader, endian, ())?,
+            },
+            3 => EventModifier::ThreadOnly(VariableLengthId::read_options(
+                reader,
+                endian,
+                args.object_id_size,
+            )?),
+            4 => EventModifier::ClassOnly(VariableLengthId::read_options(
+                reader,
+                endian,
+                args.reference_type_id_size,
+            )?),
+            5 => EventModifier::ClassMatch(string(reader)?),
+            6 => EventModifier::ClassExclude(string(reader)?),
+            7 => EventModifier::LocationOnly(Location::read_options(reader, endian, args)?),
+            8 => EventModifier::ExceptionOnly {
+                exception_or_null: VariableLengthId::read_options(
+                    reader,
+                    endian,
+                    args.reference_type_id_size,
+                )?,
+                caught: u8::read_options(reader, endian, ())? != 0,
+                uncaught: u8::read_options(reader, endian, ())? != 0,
+            },
+            9 => EventModifier::FieldOnly {
+                declaring: VariableLengthId::read_options(
+                    reader,
+                    endian,
+                    args.reference_type_id_size,
+                )?,
+                field_id: VariableLengthId::read_options(reader, endian, args.field_id_size)?,
+            },
+            10 => EventModifier::Step {
+                thread: VariableLengthId::read_options(reader, endian, args.object_id_size)?,
+                size: StepSize::read_options(reader, endian, ())?,
+                depth: StepDepth::read_options(reader, endian, ())?,
+            },
+            11 => EventModifier::InstanceOnly(VariableLengthId::read_options(
+                reader,
+                endian,
+                args.object_id_size,
+            )?),
+            12 => EventModifier::SourceNameMatch(string(reader)?),
+            13 => EventModifier::PlatformThreadsOnly,
+            _ => {
+                return Err(binrw::Error::AssertFail {
+                    pos: position,
+                    message: format!("Unknown modifier kind {}", mod_kind),
+                });
+            }
+        })
+    }
+}
+
+/// An event request created with [crate::JdwpClient::event_request_set]
+///
+/// Events generated by the request are delivered to the handle in the order they arrived, in
+/// addition to the client's global event stream. A consumer which only reads handles should
+/// keep the default [crate::OverflowPolicy::DropOldest], so the unread global buffer doesn't
+/// hold back the VM.
+///
+/// The handle buffers as many events as the client's event buffer holds event sets, see
+/// [crate::EventBufferConfig::capacity]. Whatever the overflow policy, a handle whose buffer
+/// is full discards its oldest event, so an unread handle neither grows without limit nor
+/// blocks the connection.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct EventRequestHandle {
+    request_id: i32,
+    event_kind: EventKind,
+    suspend_policy: SuspendPolicy,
+    hits: EventReceiver,
+}
+#[cfg(feature = "tokio")]
+impl EventRequestHandle {
+    pub(crate) fn new(
+        request_id: i32,
+        event_kind: EventKind,
+        suspend_policy: SuspendPolicy,
+        hits: EventReceiver,
+    ) -> Self {
+        EventRequestHandle {
+            request_id,
+            event_kind,
+            suspend_policy,
+            hits,
+        }
+    }
+
+    pub fn request_id(&self) -> i32 {
+        self.request_id
+    }
+
+    pub fn event_kind(&self) -> EventKind {
+        self.event_kind
+    }
+
+    pub fn suspend_policy(&self) -> SuspendPolicy {
+        self.suspend_policy
+    }
+
+    /// Waits for the next event generated by this request. Returns None once the request is
+    /// cleared or the connection is closed and all received events have been consumed.
+    pub async fn next_hit(&mut self) -> Option<Event> {
+        self.hits.recv().await
+    }
+
+    /// Returns the next received event without waiting
+    pub fn try_next_hit(&mut self) -> Option<Event> {
+        self.hits.try_recv()
+    }
+
+    /// Number of events discarded because the handle's buffer was full
+    pub fn dropped_hit_count(&self) -> u64 {
+        self.hits.dropped()
+    }
+}
@@ -0,0 +1,637 @@
+use binrw::{BinRead, BinWrite};
+
+use crate::{
+    ClassStatus, EventKind, JdwpIdSizes, JdwpString, JdwpValue, Location, SuspendPolicy,
+    TaggedObjectId, TypeTag, VariableLengthId,
+};
+
+/// A single event reported by the VM inside an Event.Composite command
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    VmStart {
+        request_id: i32,
+        thread: VariableLengthId,
+    },
+    SingleStep {
+        request_id: i32,
+        thread: VariableLengthId,
+        location: Location,
+    },
+    Breakpoint {
+        request_id: i32,
+        thread: VariableLengthId,
+        location: Location,
+    },
+    MethodEntry {
+        request_id: i32,
+        thread: VariableLengthId,
+        location: Location,
+    },
+    MethodExit {
+        request_id: i32,
+        thread: VariableLengthId,
+        location: Location,
+    },
+    MethodExitWithReturnValue {
+        request_id: i32,
+        thread: VariableLengthId,
+        location: Location,
+        value: JdwpValue,
+    },
+    MonitorContendedEnter {
+        request_id: i32,
+        thread: VariableLengthId,
+        object: TaggedObjectId,
+        location: Location,
+    },
+    MonitorContendedEntered {
+        request_id: i32,
+        thread: VariableLengthId,
+        object: TaggedObjectId,
+        location: Location,
+    },
+    MonitorWait {
+        request_id: i32,
+        thread: VariableLengthId,
+        object: TaggedObjectId,
+        location: Location,
+        timeout: i64,
+    },
+    MonitorWaited {
+        request_id: i32,
+        thread: VariableLengthId,
+        object: TaggedObjectId,
+        location: Location,
+        timed_out: bool,
+    },
+    Exception {
+        request_id: i32,
+        thread: VariableLengthId,
+        location: Location,
+        exception: TaggedObjectId,
+        /// All zeroes if the exception is not caught
+        catch_location: Location,
+    },
+    ThreadStart {
+        request_id: i32,
+        thread: VariableLengthId,
+    },
+    ThreadDeath {
+        request_id: i32,
+        thread: VariableLengthId,
+    },
+    ClassPrepare {
+        request_id: i32,
+        thread: VariableLengthId,
+        ref_type_tag: TypeTag,
+        type_id: VariableLengthId,
+        signature: JdwpString,
+        status: ClassStatus,
+    },
+    ClassUnload {
+        request_id: i32,
+        signature: JdwpString,
+    },
+    FieldAccess {
+        request_id: i32,
+        thread: VariableLengthId,
+        location: Location,
+        ref_type_tag: TypeTag,
+        type_id: VariableLengthId,
+        field_id: VariableLengthId,
+        object: TaggedObjectId,
+    },
+    FieldModification {
+        request_id: i32,
+        thread: VariableLengthId,
+        location: Location,
+        ref_type_tag: TypeTag,
+        type_id: VariableLengthId,
+        field_id: VariableLengthId,
+        object: TaggedObjectId,
+        value_to_be: JdwpValue,
+    },
+    VmDeath {
+        request_id: i32,
+    },
+}
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::VmStart { .. } => EventKind::VmStart,
+            Event::SingleStep { .. } => EventKind::SingleStep,
+            Event::Breakpoint { .. } => EventKind::Breakpoint,
+            Event::MethodEntry { .. } => EventKind::MethodEntry,
+            Event::MethodExit { .. } => EventKind::MethodExit,
+            Event::MethodExitWithReturnValue { .. } => EventKind::MethodExitWithReturnValue,
+            Event::MonitorContendedEnter { .. } => EventKind::MonitorContendedEnter,
+            Event::MonitorContendedEntered { .. } => EventKind::MonitorContendedEntered,
+            Event::MonitorWait { .. } => EventKind::MonitorWait,
+            Event::MonitorWaited { .. } => EventKind::MonitorWaited,
+            Event::Exception { .. } => EventKind::Exception,
+            Event::ThreadStart { .. } => EventKind::ThreadStart,
+            Event::ThreadDeath { .. } => EventKind::ThreadDeath,
+            Event::ClassPrepare { .. } => EventKind::ClassPrepare,
+            Event::ClassUnload { .. } => EventKind::ClassUnload,
+            Event::FieldAccess { .. } => EventKind::FieldAccess,
+            Event::FieldModification { .. } => EventKind::FieldModification,
+            Event::VmDeath { .. } => EventKind::VmDeath,
+        }
+    }
+
+    /// Id of the event request that caused this event, 0 for automatically generated events
+    pub fn request_id(&self) -> i32 {
+        match self {
+            Event::VmStart { request_id, .. }
+            | Event::SingleStep { request_id, .. }
+            | Event::Breakpoint { request_id, .. }
+            | Event::MethodEntry { request_id, .. }
+            | Event::MethodExit { request_id, .. }
+            | Event::MethodExitWithReturnValue { request_id, .. }
+            | Event::MonitorContendedEnter { request_id, .. }
+            | Event::MonitorContendedEntered { request_id, .. }
+            | Event::MonitorWait { request_id, .. }
+            | Event::MonitorWaited { request_id, .. }
+            | Event::Exception { request_id, .. }
+            | Event::ThreadStart { request_id, .. }
+            | Event::ThreadDeath { request_id, .. }
+            | Event::ClassPrepare { request_id, .. }
+            | Event::ClassUnload { request_id, .. }
+            | Event::FieldAccess { request_id, .. }
+            | Event::FieldModification { request_id, .. }
+            | Event::VmDeath { request_id } => *request_id,
+        }
+    }
+
+    /// Thread in which the event occurred, if the event has one
+    pub fn thread(&self) -> Option<VariableLengthId> {
+        match self {
+            Event::VmStart { thread, .. }
+            | Event::SingleStep { thread, .. }
+            | Event::Breakpoint { thread, .. }
+            | Event::MethodEntry { thread, .. }
+            | Event::MethodExit { thread, .. }
+            | Event::MethodExitWithReturnValue { thread, .. }
+            | Event::MonitorContendedEnter { thread, .. }
+            | Event::MonitorContendedEntered { thread, .. }
+            | Event::MonitorWait { thread, .. }
+            | Event::MonitorWaited { thread, .. }
+            | Event::Exception { thread, .. }
+            | Event::ThreadStart { thread, .. }
+            | Event::ThreadDeath { thread, .. }
+            | Event::ClassPrepare { thread, .. }
+            | Event::FieldAccess { thread, .. }
+            | Event::FieldModification { thread, .. } => Some(*thread),
+            Event::ClassUnload { .. } | Event::VmDeath { .. } => None,
+        }
+    }
+
+    /// Location at which the event occurred, if the event has one
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            Event::SingleStep { location, .. }
+            | Event::Breakpoint { location, .. }
+            | Event::MethodEntry { location, .. }
+            | Event::MethodExit { location, .. }
+            | Event::MethodExitWithReturnValue { location, .. }
+            | Event::MonitorContendedEnter { location, .. }
+            | Event::MonitorContendedEntered { location, .. }
+            | Event::MonitorWait { location, .. }
+            | Event::MonitorWaited { location, .. }
+            | Event::Exception { location, .. }
+            | Event::FieldAccess { location, .. }
+            | Event::FieldModification { location, .. } => Some(*location),
+            _ => None,
+        }
+    }
+
+    /// Class in which the event occurred, or the prepared class for CLASS_PREPARE
+    pub fn class(&self) -> Option<VariableLengthId> {
+        match self {
+            Event::ClassPrepare { type_id, .. } => Some(*type_id),
+            _ => self.location().map(|location| location.class_id),
+        }
+    }
+}
+impl BinRead for Event {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        let kind = EventKind::read_options(reader, endian, ())?;
+        let request_id = i32::read_options(reader, endian, ())?;
+
+        let thread =
+            |reader: &mut R| VariableLengthId::read_options(reader, endian, args.object_id_size);
+        let location = |reader: &mut R| Location::read_options(reader, endian, args);
+        let object = |reader: &mut R| TaggedObjectId::read_options(reader, endian, args);
+
+        Ok(match kind {
+            EventKind::VmStart => Event::VmStart {
+                request_id,
+                thread: thread(reader)?,
+            },
+            EventKind::SingleStep => Event::SingleStep {
+                request_id,
+                thread: thread(reader)?,
+                location: location(reader)?,
+            },
+            EventKind::Breakpoint => Event::Breakpoint {
+                request_id,
+                thread: thread(reader)?,
+                location: location(reader)?,
+            },
+            EventKind::MethodEntry => Event::MethodEntry {
+                request_id,
+                thread: thread(reader)?,
+                location: location(reader)?,
+            },
+            EventKind::MethodExit => Event::MethodExit {
+                request_id,
+                thread: thread(reader)?,
+                location: location(reader)?,
+            },
+            EventKind::MethodExitWithReturnValue => Event::MethodExitWithReturnValue {
+                request_id,
+                thread: thread(reader)?,
+                location: location(reader)?,
+                value: JdwpValue::read_options(reader, endian, args)?,
+            },
+            EventKind::MonitorContendedEnter => Event::MonitorContendedEnter {
+                request_id,
+                thread: thread(reader)?,
+                object: object(reader)?,
+                location: location(reader)?,
+            },
+            EventKind::MonitorContendedEntered => Event::MonitorContendedEntered {
+                request_id,
+                thread: thread(reader)?,
+                object: object(reader)?,
+                location: location(reader)?,
+            },
+            EventKind::MonitorWait => Event::MonitorWait {
+                request_id,
+                thread: thread(reader)?,
+                object: object(reader)?,
+                location: location(reader)?,
+                timeout: i64::read_options(reader, endian, ())?,
+            },
+            EventKind::MonitorWaited => Event::MonitorWaited {
+                request_id,
+                thread: thread(reader)?,
+                object: object(reader)?,
+                location: location(reader)?,
+                timed_out: u8::read_options(reader, endian, ())? != 0,
+            },
+            EventKind::Exception => Event::Exception {
+                request_id,
+                thread: thread(reader)?,
+                location: location(reader)?,
+                exception: object(reader)?,
+                catch_location: location(reader)?,
+            },
+            EventKind::ThreadStart => Event::ThreadStart {
+                request_id,
+                thread: thread(reader)?,
+            },
+            EventKind::ThreadDeath => Event::ThreadDeath {
+                request_id,
+                thread: thread(reader)?,
+            },
+            EventKind::ClassPrepare => Event::ClassPrepare {
+                request_id,
+                thread: thread(reader)?,
+                ref_type_tag: TypeTag::read_options(reader, endian, ())?,
+                type_id: VariableLengthId::read_options(
+                    reader,
+                    endian,
+                    args.reference_type_id_size,
+                )?,
+                signature: JdwpString::read_options(reader, endian, ())?,
+                status: ClassStatus::read_options(reader, endian, ())?,
+            },
+            EventKind::ClassUnload => Event::ClassUnload {
+                request_id,
+                signature: JdwpString::read_options(reader, endian, ())?,
+            },
+            EventKind::FieldAccess => Event::FieldAccess {
+                request_id,
+                thread: thread(reader)?,
+                location: location(reader)?,
+                ref_type_tag: TypeTag::read_options(reader, endian, ())?,
+                type_id: VariableLengthId::read_options(
+                    reader,
+                    endian,
+                    args.reference_type_id_size,
+                )?,
+                field_id: VariableLengthId::read_options(reader, endian, args.field_id_size)?,
+                object: object(reader)?,
+            },
+            EventKind::FieldModification => Event::FieldModification {
+                request_id,
+                thread: thread(reader)?,
+                location: location(reader)?,
+                ref_type_tag: TypeTag::read_options(reader, endian, ())?,
+                type_id: VariableLengthId::read_options(
+                    reader,
+                    endian,
+                    args.reference_type_id_size,
+                )?,
+                field_id: VariableLengthId::read_options(reader, endian, args.field_id_size)?,
+                object: object(reader)?,
+                value_to_be: JdwpValue::read_options(reader, endian, args)?,
+            },
+            EventKind::VmDeath => Event::VmDeath { request_id },
+            other => {
+                return Err(binrw::Error::AssertFail {
+                    pos: reader.stream_position().unwrap_or(0),
+                    message: format!("Event kind {:?} is not sent in composite events", other),
+                });
+            }
+        })
+    }
+}
+
+impl BinWrite for Event {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.kind().write_options(writer, endian, ())?;
+        self.request_id().write_options(writer, endian, ())?;
+        if let Some(thread) = self.thread() {
+            thread.write_options(writer, endian, args.object_id_size)?;
+        }
+
+        match self {
+            Event::VmStart { .. }
+            | Event::ThreadStart { .. }
+            | Event::ThreadDeath { .. }
+            | Event::VmDeath { .. } => Ok(()),
+            Event::SingleStep { location, .. }
+            | Event::Breakpoint { location, .. }
+            | Event::MethodEntry { location, .. }
+            | Event::MethodExit { location, .. } => location.write_options(writer, endian, args),
+            Event::MethodExitWithReturnValue {
+                location, value, ..
+            } => {
+                location.write_options(writer, endian, args)?;
+                value.write_options(writer, endian, args)
+            }
+            Event::MonitorContendedEnter {
+                object, location, ..
+            }
+            | Event::MonitorContendedEntered {
+                object, location, ..
+            } => {
+                object.write_options(writer, endian, args)?;
+                location.write_options(writer, endian, args)
+            }
+            Event::MonitorWait {
+                object,
+                location,
+                timeout,
+                ..
+            } => {
+                object.write_options(writer, endian, args)?;
+                location.write_options(writer, endian, args)?;
+                timeout.write_options(writer, endian, ())
+            }
+            Event::MonitorWaited {
+                object,
+                location,
+                timed_out,
+                ..
+            } => {
+                object.write_options(writer, endian, args)?;
+                location.write_options(writer, endian, args)?;
+                (*timed_out as u8).write_options(writer, endian, ())
+            }
+            Event::Exception {
+                location,
+                exception,
+                catch_location,
+                ..
+            } => {
+                location.write_options(writer, endian, args)?;
+                exception.write_options(writer, endian, args)?;
+                catch_location.write_options(writer, endian, args)
+            }
+            Event::ClassPrepare {
+                ref_type_tag,
+                type_id,
+                signature,
+                status,
+                ..
+            } => {
+                ref_type_tag.write_options(writer, endian, ())?;
+                type_id.write_options(writer, endian, args.reference_type_id_size)?;
+                signature.write_options(writer, endian, ())?;
+                status.write_options(writer, endian, ())
+            }
+            Event::ClassUnload { signature, .. } => signature.write_options(writer, endian, ()),
+            Event::FieldAccess {
+                location,
+                ref_type_tag,
+                type_id,
+                field_id,
+                object,
+                ..
+            } => {
+                location.write_options(writer, endian, args)?;
+                ref_type_tag.write_options(writer, endian, ())?;
+                type_id.write_options(writer, endian, args.reference_type_id_size)?;
+                field_id.write_options(writer, endian, args.field_id_size)?;
+                object.write_options(writer, endian, args)
+            }
+            Event::FieldModification {
+                location,
+                ref_type_tag,
+                type_id,
+                field_id,
+                object,
+                value_to_be,
+                ..
+            } => {
+                location.write_options(writer, endian, args)?;
+                ref_type_tag.write_options(writer, endian, ())?;
+                type_id.write_options(writer, endian, args.reference_type_id_size)?;
+                field_id.write_options(writer, endian, args.field_id_size)?;
+                object.write_options(writer, endian, args)?;
+                value_to_be.write_options(writer, endian, args)
+            }
+        }
+    }
+}
+
+/// The body of an Event.Composite command: events which occurred together, with the policy
+/// describing which threads the VM suspended
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventComposite {
+    pub suspend_policy: SuspendPolicy,
+    pub events: Vec<Event>,
+}
+impl BinRead for EventComposite {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        let suspend_policy = SuspendPolicy::read_options(reader, endian, ())?;
+        let length = i32::read_options(reader, endian, ())?;
+        let mut events = Vec::with_capacity(length.max(0) as usize);
+        for _ in 0..length {
+            events.push(Event::read_options(reader, endian, args)?);
+        }
+        Ok(EventComposite {
+            suspend_policy,
+            events,
+        })
+    }
+}
+impl BinWrite for EventComposite {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.suspend_policy.write_options(writer, endian, ())?;
+        (self.events.len() as i32).write_options(writer, endian, ())?;
+        for event in &self.events {
+            event.write_options(writer, endian, args)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const SIZES: JdwpIdSizes = JdwpIdSizes {
+        field_id_size: 8,
+        method_id_size: 8,
+        object_id_size: 8,
+        reference_type_id_size: 8,
+        frame_id_size: 8,
+    };
+
+    #[test]
+    fn test_read_breakpoint_composite() {
+        let data = [
+            0x2, // suspend all
+            0x0, 0x0, 0x0, 0x1, // one event
+            0x2, // breakpoint
+            0x0, 0x0, 0x0, 0x5, // request 5
+            0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1, // thread 1
+            0x1, // class
+            0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x2, // class 2
+            0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x3, // method 3
+            0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x4, // index 4
+        ];
+        let mut cursor = Cursor::new(&data);
+        let composite = EventComposite::read_be_args(&mut cursor, SIZES).unwrap();
+        assert_eq!(composite.suspend_policy, SuspendPolicy::All);
+        assert_eq!(
+            composite.events,
+            vec![Event::Breakpoint {
+                request_id: 5,
+                thread: VariableLengthId { value: 1 },
+                location: Location {
+                    type_tag: TypeTag::Class,
+                    class_id: VariableLengthId { value: 2 },
+                    method_id: VariableLengthId { value: 3 },
+                    index: 4,
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_write_composite_round_trip() {
+        let composite = EventComposite {
+            suspend_policy: SuspendPolicy::EventThread,
+            events: vec![
+                Event::MonitorWaited {
+                    request_id: 3,
+                    thread: VariableLengthId { value: 1 },
+                    object: TaggedObjectId {
+                        tag: crate::Tag::Object,
+                        object_id: VariableLengthId { value: 2 },
+                    },
+                    location: Location {
+                        type_tag: TypeTag::Class,
+                        class_id: VariableLengthId { value: 4 },
+                        method_id: VariableLengthId { value: 5 },
+                        index: 6,
+                    },
+                    timed_out: true,
+                },
+                Event::ClassUnload {
+                    request_id: 7,
+                    signature: JdwpString {
+                        string: String::from("LA;"),
+                    },
+                },
+            ],
+        };
+        let mut cursor = Cursor::new(Vec::new());
+        composite.write_be_args(&mut cursor, SIZES).unwrap();
+        cursor.set_position(0);
+        assert_eq!(
+            EventComposite::read_be_args(&mut cursor, SIZES).unwrap(),
+            composite
+        );
+    }
+
+    #[test]
+    fn test_read_class_unload_composite() {
+        let data = [
+            0x0, // suspend none
+            0x0, 0x0, 0x0, 0x1, // one event
+            0x9, // class unload
+            0x0, 0x0, 0x0, 0x7, // request 7
+            0x0, 0x0, 0x0, 0x3, b'L', b'A', b';',
+        ];
+        let mut cursor = Cursor::new(&data);
+        let composite = EventComposite::read_be_args(&mut cursor, SIZES).unwrap();
+        let event = &composite.events[0];
+        assert_eq!(event.kind(), EventKind::ClassUnload);
+        assert_eq!(event.request_id(), 7);
+        assert_eq!(event.thread(), None);
+    }
+
+    #[test]
+    fn test_read_zeroed_catch_location() {
+        let mut data = vec![
+            0x0, // suspend none
+            0x0, 0x0, 0x0, 0x1, // one event
+            0x4, // exception
+            0x0, 0x0, 0x0, 0x1, // request 1
+            0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1, // thread 1
+        ];
+        data.extend_from_slice(&[0x1; 25]); // location
+        data.extend_from_slice(&[b'L', 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x9]);
+        data.extend_from_slice(&[0x0; 25]); // uncaught, including the type tag
+        let mut cursor = Cursor::new(&data);
+        let composite = EventComposite::read_be_args(&mut cursor, SIZES).unwrap();
+        let Event::Exception { catch_location, .. } = &composite.events[0] else {
+            panic!("expected an exception event");
+        };
+        assert_eq!(catch_location.type_tag, TypeTag::Unknown(0));
+        assert_eq!(catch_location.class_id.value, 0);
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::event_channel::EventReceiver;
+use crate::{
+    Event, EventKind, EventModifier, JdwpClient, JdwpErrorCode, Location, SuspendPolicy,
+    VariableLengthId, result, signature_to_type_name,
+};
+
+/// Exceptions of one type thrown at one location, see [ExceptionStats]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExceptionCount {
+    /// e.g. `java.lang.IllegalStateException`, or `<collected>` if the exception was collected
+    /// before its type could be read
+    pub type_name: String,
+    /// Where the exception was thrown
+    pub location: Location,
+    pub count: u64,
+    /// How many of them weren't caught
+    pub uncaught: u64,
+}
+
+/// Exceptions thrown during a period, most frequent first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExceptionReport {
+    pub period: Duration,
+    pub total: u64,
+    pub counts: Vec<ExceptionCount>,
+}
+impl ExceptionReport {
+    /// Counts summed over all locations per type, most frequent first
+    pub fn by_type(&self) -> Vec<(String, u64)> {
+        let mut by_type: HashMap<&str, u64> = HashMap::new();
+        for count in &self.counts {
+            *by_type.entry(&count.type_name).or_insert(0) += count.count;
+        }
+        let mut by_type: Vec<(String, u64)> = by_type
+            .into_iter()
+            .map(|(type_name, count)| (String::from(type_name), count))
+            .collect();
+        by_type.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        by_type
+    }
+}
+
+/// Aggregates thrown exceptions by type and throw location with a caught and uncaught
+/// EXCEPTION request
+///
+/// The throwing thread is suspended only until the type of the exception has been read, which
+/// takes a single round trip for types seen before. Exceptions also show up in the client's
+/// event queue.
+pub struct ExceptionStats {
+    client: JdwpClient,
+    request_id: Option<i32>,
+    hits: EventReceiver,
+    type_names: HashMap<VariableLengthId, String>,
+    counts: HashMap<(String, Location), (u64, u64)>,
+    period_start: Instant,
+}
+
+impl ExceptionStats {
+    /// Starts counting exceptions, except those thrown in classes matching
+    /// `exclude_patterns`, e.g. `java.*`
+    pub async fn start(client: JdwpClient, exclude_patterns: &[&str]) -> result::Result<Self> {
+        let mut modifiers = vec![EventModifier::ExceptionOnly {
+            exception_or_null: VariableLengthId { value: 0 },
+            caught: true,
+            uncaught: true,
+        }];
+        modifiers.extend(
+            exclude_patterns
+                .iter()
+                .map(|exclude| EventModifier::ClassExclude(String::from(*exclude))),
+        );
+        let (route, hits) = client.event_channel();
+        let request_id = client
+            .event_request_set_routed(
+                EventKind::Exception,
+                SuspendPolicy::EventThread,
+                modifiers,
+                route,
+            )
+            .await?;
+        Ok(ExceptionStats {
+            client,
+            request_id: Some(request_id),
+            hits,
+            type_names: HashMap::new(),
+            counts: HashMap::new(),
+            period_start: Instant::now(),
+        })
+    }
+
+    pub fn request_id(&self) -> Option<i32> {
+        self.request_id
+    }
+
+    /// Counts exceptions for `duration`, or until the connection is closed
+    pub async fn record_for(&mut self, duration: Duration) -> result::Result<()> {
+        let deadline = tokio::time::Instant::now() + duration;
+        while let Ok(Some(event)) = tokio::time::timeout_at(deadline, self.hits.recv()).await {
+            self.record(event).await?;
+        }
+        Ok(())
+    }
+
+    /// Counts of the current period
+    pub fn report(&self) -> ExceptionReport {
+        let mut counts: Vec<ExceptionCount> = self
+            .counts
+            .iter()
+            .map(
+                |((type_name, location), (count, uncaught))| ExceptionCount {
+                    type_name: type_name.clone(),
+                    location: *location,
+                    count: *count,
+                    uncaught: *uncaught,
+                },
+            )
+            .collect();
+        counts.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.type_name.cmp(&b.type_name))
+        });
+        ExceptionReport {
+            period: self.period_start.elapsed(),
+            total: counts.iter().map(|count| count.count).sum(),
+            counts,
+        }
+    }
+
+    /// Counts exceptions for `period`, then returns the report of the period and starts the
+    /// next one
+    pub async fn next_report(&mut self, period: Duration) -> result::Result<ExceptionReport> {
+        self.record_for(period).await?;
+        let report = self.report();
+        self.counts.clear();
+        self.period_start = Instant::now();
+        Ok(report)
+    }
+
+    /// Clears the request. Counts stay available.
+    pub async fn stop(&mut self) -> result::Result<()> {
+        if let Some(request_id) = self.request_id.take() {
+            self.client
+                .event_request_clear(EventKind::Exception, request_id)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn record(&mut self, event: Event) -> result::Result<()> {
+        let Event::Exception {
+            thread,
+            location,
+            exception,
+            catch_location,
+            ..
+        } = event
+        else {
+            return Ok(());
+        };
+
+        let type_name = self.type_name(exception.object_id).await;
+        let resumed = self.client.thread_resume(thread).await;
+        let type_name = type_name?;
+
+        let counts = self.counts.entry((type_name, location)).or_insert((0, 0));
+        counts.0 += 1;
+        if catch_location.class_id.value == 0 {
+            counts.1 += 1;
+        }
+        match resumed {
+            Err(result::Error::JdwpError(JdwpErrorCode::InvalidThread)) => Ok(()),
+            other => other,
+        }
+    }
+
+    async fn type_name(&mut self, exception: VariableLengthId) -> result::Result<String> {
+        let type_id = match self.client.object_get_reference_type(exception).await {
+            Ok(reply) => reply.type_id,
+            Err(result::Error::JdwpError(JdwpErrorCode::InvalidObject)) => {
+                return Ok(String::from("<collected>"));
+            }
+            Err(e) => return Err(e),
+        };
+        if let Some(type_name) = self.type_names.get(&type_id) {
+            return Ok(type_name.clone());
+        }
+        let signature = self.client.ref_type_get_signature(type_id).await?;
+        let type_name = signature_to_type_name(&signature.signature.string);
+        self.type_names.insert(type_id, type_name.clone());
+        Ok(type_name)
+    }
+}
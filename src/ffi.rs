@@ -0,0 +1,551 @@
+//! C ABI for embedding the client in C, C++ or Python tools, declared in
+//! `include/jdwp_client.h`
+//!
+//! Build the library with `cargo rustc --release --features ffi --crate-type cdylib` (or
+//! `staticlib`). Each session runs its connection on a runtime of its own, so events keep
+//! being received between calls. Functions returning a status return 0 on success and -1 on
+//! failure, with the message of the failure available from [jdwp_last_error].
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ffi::{CStr, CString, c_char};
+use std::io;
+use std::ptr;
+use std::time::Duration;
+
+use binrw::BinWrite;
+use tokio::runtime::Runtime;
+
+use crate::breakpoint::LineBreakpointOptions;
+use crate::event_channel::{EventReceiver, EventSender, event_channel};
+use crate::{
+    Event, FrameSlot, JdwpClient, JdwpValue, LineBreakpoint, SuspendPolicy, Tag, Transport,
+    VariableLengthId, result, type_name_to_signature,
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// A connection to a VM, created by [jdwp_connect] and destroyed by [jdwp_free]
+pub struct JdwpSession {
+    runtime: Runtime,
+    client: JdwpClient,
+    breakpoints: Vec<LineBreakpoint>,
+    route: EventSender,
+    hits: EventReceiver,
+    pending: VecDeque<Event>,
+}
+
+/// An event received by [jdwp_poll_event]. Ids the event doesn't have are 0.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JdwpEventData {
+    /// The `EventKind` constant of the JDWP specification
+    pub kind: u8,
+    pub request_id: i32,
+    /// Breakpoint returned by [jdwp_set_breakpoint] which was hit
+    pub breakpoint: i32,
+    pub thread_id: u64,
+    pub class_id: u64,
+    pub method_id: u64,
+    pub index: u64,
+}
+
+/// A value read by [jdwp_read_value]. Integral values and characters are in `int_value`,
+/// floating point values in `float_value` and objects in `object_id`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct JdwpValueData {
+    /// The JDWP tag of the value, e.g. `'I'`
+    pub tag: u8,
+    pub int_value: i64,
+    pub float_value: f64,
+    pub object_id: u64,
+}
+
+/// Connects to a VM listening on `address`, e.g. `localhost:5005`. Returns NULL on failure.
+///
+/// # Safety
+/// `address` must be a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn jdwp_connect(address: *const c_char) -> *mut JdwpSession {
+    let session = unsafe { c_str(address) }.and_then(|address| {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+        let client = runtime.block_on(Transport::tcp(address).connect())?;
+        let (route, hits) = client.event_channel();
+        Ok(JdwpSession {
+            runtime,
+            client,
+            breakpoints: Vec::new(),
+            route,
+            hits,
+            pending: VecDeque::new(),
+        })
+    });
+    match session {
+        Ok(session) => Box::into_raw(Box::new(session)),
+        Err(e) => {
+            set_last_error(e);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Sets a breakpoint on a line of a class given by its name, e.g. `com.example.Main`, which
+/// suspends the thread hitting it. Its number is written to `breakpoint`.
+///
+/// # Safety
+/// `session` must come from [jdwp_connect], `class_name` must be a NUL-terminated string and
+/// `breakpoint` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn jdwp_set_breakpoint(
+    session: *mut JdwpSession,
+    class_name: *const c_char,
+    line: i32,
+    breakpoint: *mut i32,
+) -> i32 {
+    status(|| {
+        let session = unsafe { session_mut(session) }?;
+        let class_name = unsafe { c_str(class_name) }?;
+        let breakpoint = unsafe { out_mut(breakpoint) }?;
+        let (_, unused_hits) = event_channel(1);
+        let line_breakpoint =
+            session
+                .runtime
+                .block_on(session.client.set_line_breakpoint_routed(
+                    class_name,
+                    line,
+                    LineBreakpointOptions::new(SuspendPolicy::EventThread),
+                    session.route.clone(),
+                    unused_hits,
+                ))?;
+        session.breakpoints.push(line_breakpoint);
+        *breakpoint = session.breakpoints.len() as i32;
+        Ok(())
+    })
+}
+
+/// Waits up to `timeout_ms` milliseconds for an event, forever if negative. Returns 1 and
+/// writes the event to `event` if one was received, 0 on timeout and -1 on failure, including
+/// a closed connection.
+///
+/// # Safety
+/// `session` must come from [jdwp_connect] and `event` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn jdwp_poll_event(
+    session: *mut JdwpSession,
+    timeout_ms: i32,
+    event: *mut JdwpEventData,
+) -> i32 {
+    let received = (|| {
+        let session = unsafe { session_mut(session) }?;
+        let event = unsafe { out_mut(event) }?;
+        if session.pending.is_empty() {
+            let JdwpSession {
+                runtime,
+                client,
+                hits,
+                pending,
+                ..
+            } = &mut *session;
+            let wait = async {
+                loop {
+                    let events = tokio::select! {
+                        hit = hits.recv() => hit.map(|hit| vec![hit]),
+                        composite = client.next_event() => composite.map(|c| c.events),
+                    };
+                    match events {
+                        Some(events) if events.is_empty() => continue,
+                        Some(events) => return Ok(events),
+                        None => return Err(closed_error()),
+                    }
+                }
+            };
+            let events = runtime.block_on(async {
+                if timeout_ms < 0 {
+                    wait.await.map(Some)
+                } else {
+                    match tokio::time::timeout(Duration::from_millis(timeout_ms as u64), wait).await
+                    {
+                        Ok(events) => events.map(Some),
+                        Err(_) => Ok(None),
+                    }
+                }
+            })?;
+            match events {
+                Some(events) => pending.extend(events),
+                None => return Ok(false),
+            }
+        }
+        let Some(received) = session.pending.pop_front() else {
+            return Ok(false);
+        };
+        *event = session.event_data(&received)?;
+        result::Result::Ok(true)
+    })();
+    match received {
+        Ok(true) => 1,
+        Ok(false) => 0,
+        Err(e) => {
+            set_last_error(e);
+            -1
+        }
+    }
+}
+
+/// Reads the local variable in `slot` of the frame at `frame_index` of a suspended thread,
+/// the current frame being 0. `tag` is the JDWP tag of the variable's type, e.g. `'I'`.
+///
+/// # Safety
+/// `session` must come from [jdwp_connect] and `value` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn jdwp_read_value(
+    session: *mut JdwpSession,
+    thread_id: u64,
+    frame_index: i32,
+    slot: i32,
+    tag: u8,
+    value: *mut JdwpValueData,
+) -> i32 {
+    status(|| {
+        let session = unsafe { session_mut(session) }?;
+        let value = unsafe { out_mut(value) }?;
+        let tag = Tag::try_from(tag).map_err(|tag| result::Error::InvalidArgument {
+            message: format!("Invalid tag {}", tag),
+        })?;
+        let thread_id = VariableLengthId { value: thread_id };
+        let client = &session.client;
+        let values = session.runtime.block_on(async {
+            let frames = client.thread_get_frames(thread_id, frame_index, 1).await?;
+            let frame = frames
+                .frames
+                .first()
+                .ok_or_else(|| result::Error::InvalidArgument {
+                    message: format!("No frame at index {}", frame_index),
+                })?;
+            client
+                .stack_frame_get_values(thread_id, frame.frame_id, vec![FrameSlot { slot, tag }])
+                .await
+        })?;
+        let read = values
+            .values
+            .first()
+            .ok_or_else(|| result::Error::ParsingError {
+                message: String::from("Reply without a value"),
+            })?;
+        *value = value_data(read);
+        Ok(())
+    })
+}
+
+/// Writes the ids of up to `capacity` threads of the VM to `threads` and their total number
+/// to `count`, so a `capacity` of 0 asks for the number only
+///
+/// # Safety
+/// `session` must come from [jdwp_connect], `threads` must have room for `capacity` ids and
+/// `count` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn jdwp_all_threads(
+    session: *mut JdwpSession,
+    threads: *mut u64,
+    capacity: usize,
+    count: *mut usize,
+) -> i32 {
+    status(|| {
+        let session = unsafe { session_mut(session) }?;
+        let count = unsafe { out_mut(count) }?;
+        if capacity > 0 && threads.is_null() {
+            return Err(null_error("Output argument"));
+        }
+        let all = session
+            .runtime
+            .block_on(session.client.vm_get_all_threads())?
+            .threads;
+        for (i, thread) in all.iter().take(capacity).enumerate() {
+            unsafe { threads.add(i).write(thread.thread_id.value) };
+        }
+        *count = all.len();
+        Ok(())
+    })
+}
+
+/// Writes the name of a thread to `name` as a NUL-terminated string, truncated to fit into
+/// `capacity` bytes, and its length without truncation to `length`
+///
+/// # Safety
+/// `session` must come from [jdwp_connect], `name` must have room for `capacity` bytes and
+/// `length` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn jdwp_thread_name(
+    session: *mut JdwpSession,
+    thread_id: u64,
+    name: *mut c_char,
+    capacity: usize,
+    length: *mut usize,
+) -> i32 {
+    status(|| {
+        let session = unsafe { session_mut(session) }?;
+        let length = unsafe { out_mut(length) }?;
+        if capacity > 0 && name.is_null() {
+            return Err(null_error("Output argument"));
+        }
+        let thread_name = session.runtime.block_on(
+            session
+                .client
+                .thread_name(VariableLengthId { value: thread_id }),
+        )?;
+        if capacity > 0 {
+            let copied = thread_name.len().min(capacity - 1);
+            unsafe {
+                ptr::copy_nonoverlapping(thread_name.as_ptr(), name.cast::<u8>(), copied);
+                name.add(copied).write(0);
+            }
+        }
+        *length = thread_name.len();
+        Ok(())
+    })
+}
+
+/// Writes the id of a loaded class given by its name, e.g. `com.example.Main`, to `class_id`
+///
+/// # Safety
+/// `session` must come from [jdwp_connect], `class_name` must be a NUL-terminated string and
+/// `class_id` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn jdwp_find_class(
+    session: *mut JdwpSession,
+    class_name: *const c_char,
+    class_id: *mut u64,
+) -> i32 {
+    status(|| {
+        let session = unsafe { session_mut(session) }?;
+        let class_name = unsafe { c_str(class_name) }?;
+        let class_id = unsafe { out_mut(class_id) }?;
+        *class_id = session
+            .runtime
+            .block_on(
+                session
+                    .client
+                    .find_loaded_class(&type_name_to_signature(class_name)),
+            )?
+            .value;
+        Ok(())
+    })
+}
+
+/// Calls a static method without arguments in a suspended thread, e.g. one stopped at a
+/// breakpoint, and writes its return value to `value`
+///
+/// # Safety
+/// `session` must come from [jdwp_connect], `class_name` and `method_name` must be
+/// NUL-terminated strings and `value` must be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn jdwp_invoke_static(
+    session: *mut JdwpSession,
+    thread_id: u64,
+    class_name: *const c_char,
+    method_name: *const c_char,
+    value: *mut JdwpValueData,
+) -> i32 {
+    status(|| {
+        let session = unsafe { session_mut(session) }?;
+        let class_name = unsafe { c_str(class_name) }?;
+        let method_name = unsafe { c_str(method_name) }?;
+        let value = unsafe { out_mut(value) }?;
+        let client = &session.client;
+        let returned = session.runtime.block_on(async {
+            let class_id = client
+                .find_loaded_class(&type_name_to_signature(class_name))
+                .await?;
+            client
+                .invoke_static_method(
+                    VariableLengthId { value: thread_id },
+                    class_id,
+                    method_name,
+                    (),
+                )
+                .await
+        })?;
+        *value = value_data(&returned);
+        Ok(())
+    })
+}
+
+/// Resumes a thread, e.g. one suspended by a breakpoint
+///
+/// # Safety
+/// `session` must come from [jdwp_connect].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn jdwp_resume_thread(session: *mut JdwpSession, thread_id: u64) -> i32 {
+    status(|| {
+        let session = unsafe { session_mut(session) }?;
+        session.runtime.block_on(
+            session
+                .client
+                .thread_resume(VariableLengthId { value: thread_id }),
+        )
+    })
+}
+
+/// Closes the connection and frees the session. NULL is ignored.
+///
+/// # Safety
+/// `session` must come from [jdwp_connect] and not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn jdwp_free(session: *mut JdwpSession) {
+    if !session.is_null() {
+        drop(unsafe { Box::from_raw(session) });
+    }
+}
+
+/// Message of the last failure on the calling thread, NULL if nothing failed yet. The string
+/// stays valid until the next failing call on the thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn jdwp_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| {
+        error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |error| error.as_ptr())
+    })
+}
+
+impl JdwpSession {
+    fn event_data(&self, event: &Event) -> result::Result<JdwpEventData> {
+        let mut kind = [0u8];
+        event
+            .kind()
+            .write_be(&mut io::Cursor::new(&mut kind[..]))
+            .map_err(|e| result::Error::ParsingError {
+                message: format!("Serialization error: {:?}", e),
+            })?;
+        let request_id = event.request_id();
+        let breakpoint = self
+            .breakpoints
+            .iter()
+            .position(|breakpoint| breakpoint.request_ids().contains(&request_id))
+            .map_or(0, |index| index as i32 + 1);
+        let location = event.location();
+        Ok(JdwpEventData {
+            kind: kind[0],
+            request_id,
+            breakpoint,
+            thread_id: event.thread().map_or(0, |thread| thread.value),
+            class_id: location.map_or(0, |location| location.class_id.value),
+            method_id: location.map_or(0, |location| location.method_id.value),
+            index: location.map_or(0, |location| location.index),
+        })
+    }
+}
+
+fn value_data(value: &JdwpValue) -> JdwpValueData {
+    let mut data = JdwpValueData {
+        tag: value.tag() as u8,
+        ..Default::default()
+    };
+    match *value {
+        JdwpValue::Void => {}
+        JdwpValue::Boolean(value) => data.int_value = value as i64,
+        JdwpValue::Byte(value) => data.int_value = value as i64,
+        JdwpValue::Char(value) => data.int_value = value as i64,
+        JdwpValue::Short(value) => data.int_value = value as i64,
+        JdwpValue::Int(value) => data.int_value = value as i64,
+        JdwpValue::Long(value) => data.int_value = value,
+        JdwpValue::Float(value) => data.float_value = value as f64,
+        JdwpValue::Double(value) => data.float_value = value,
+        JdwpValue::Object(object) => data.object_id = object.object_id.value,
+    }
+    data
+}
+
+fn status(f: impl FnOnce() -> result::Result<()>) -> i32 {
+    match f() {
+        Ok(()) => 0,
+        Err(e) => {
+            set_last_error(e);
+            -1
+        }
+    }
+}
+
+fn set_last_error(error: result::Error) {
+    let message = format!("{:?}", error).replace('\0', " ");
+    let message = CString::new(message).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn closed_error() -> result::Error {
+    result::Error::IoError(io::Error::new(
+        io::ErrorKind::NotConnected,
+        "Connection closed",
+    ))
+}
+
+fn null_error(name: &str) -> result::Error {
+    result::Error::InvalidArgument {
+        message: format!("{} is NULL", name),
+    }
+}
+
+unsafe fn c_str<'a>(ptr: *const c_char) -> result::Result<&'a str> {
+    if ptr.is_null() {
+        return Err(null_error("String argument"));
+    }
+    unsafe { CStr::from_ptr(ptr) }
+        .to_str()
+        .map_err(|e| result::Error::InvalidArgument {
+            message: format!("Invalid UTF-8: {}", e),
+        })
+}
+
+unsafe fn session_mut<'a>(session: *mut JdwpSession) -> result::Result<&'a mut JdwpSession> {
+    unsafe { session.as_mut() }.ok_or_else(|| null_error("Session"))
+}
+
+unsafe fn out_mut<'a, T>(out: *mut T) -> result::Result<&'a mut T> {
+    unsafe { out.as_mut() }.ok_or_else(|| null_error("Output argument"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_declares_exports() {
+        let header = include_str!("../include/jdwp_client.h");
+        let source = include_str!("ffi.rs");
+        let exports: Vec<&str> = source
+            .split("extern \"C\" fn ")
+            .skip(1)
+            .filter_map(|rest| rest.split('(').next())
+            .filter(|name| name.starts_with("jdwp_"))
+            .collect();
+        assert!(exports.len() >= 6);
+        for name in exports {
+            assert!(
+                header.contains(&format!(" {}(", name)),
+                "{} isn't declared in jdwp_client.h",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_connect_failure_sets_last_error() {
+        let address = CString::new("127.0.0.1:1").unwrap();
+        let session = unsafe { jdwp_connect(address.as_ptr()) };
+        assert!(session.is_null());
+        assert!(!jdwp_last_error().is_null());
+
+        let mut breakpoint = 0;
+        let status =
+            unsafe { jdwp_set_breakpoint(ptr::null_mut(), address.as_ptr(), 1, &mut breakpoint) };
+        assert_eq!(status, -1);
+        let error = unsafe { CStr::from_ptr(jdwp_last_error()) };
+        assert!(error.to_str().unwrap().contains("Session is NULL"));
+    }
+}
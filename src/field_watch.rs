@@ -0,0 +1,118 @@
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+use crate::{JdwpClient, JdwpValue, VariableLengthId, result};
+
+/// Holder of the fields read by [JdwpClient::poll_fields]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FieldOwner {
+    /// Instance fields of an object
+    Object(VariableLengthId),
+    /// Static fields of a class or interface
+    Class(VariableLengthId),
+}
+
+/// Values of the polled fields at one point in time
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldSample {
+    /// One value per field, in the order the fields were given
+    pub values: Vec<JdwpValue>,
+    pub timestamp: Instant,
+}
+
+/// Samples of fields read on a timer, see [JdwpClient::poll_fields]
+///
+/// Polling stops when the watch is dropped or after the first error, such as INVALID_OBJECT
+/// once the object was collected.
+#[derive(Debug)]
+pub struct FieldWatch {
+    samples: mpsc::UnboundedReceiver<result::Result<FieldSample>>,
+    only_changes: bool,
+    last: Option<Vec<JdwpValue>>,
+    poller: JoinHandle<()>,
+}
+impl FieldWatch {
+    /// Skips samples whose values are the same as the ones of the previous sample
+    pub fn only_changes(mut self) -> Self {
+        self.only_changes = true;
+        self
+    }
+
+    /// Waits for the next sample. Returns None once an error was returned, e.g. after the
+    /// connection was closed.
+    pub async fn next_sample(&mut self) -> Option<result::Result<FieldSample>> {
+        loop {
+            let sample = self.samples.recv().await?;
+            if let Ok(sample) = &sample {
+                if self.only_changes && self.last.as_ref() == Some(&sample.values) {
+                    continue;
+                }
+                self.last = Some(sample.values.clone());
+            }
+            return Some(sample);
+        }
+    }
+}
+impl Drop for FieldWatch {
+    fn drop(&mut self) {
+        self.poller.abort();
+    }
+}
+
+impl JdwpClient {
+    /// Reads a field every `interval`, for watch panes and monitoring dashboards
+    pub fn poll_field(
+        &self,
+        owner: FieldOwner,
+        field: VariableLengthId,
+        interval: Duration,
+    ) -> FieldWatch {
+        self.poll_fields(owner, vec![field], interval)
+    }
+
+    /// Reads fields of the same object or class with a single GetValues command every
+    /// `interval`. The first sample is read right away; ticks missed by slow replies are
+    /// skipped rather than bunched up.
+    pub fn poll_fields(
+        &self,
+        owner: FieldOwner,
+        fields: Vec<VariableLengthId>,
+        interval: Duration,
+    ) -> FieldWatch {
+        let (sender, samples) = mpsc::unbounded_channel();
+        let client = self.clone();
+        let poller = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticks.tick().await;
+                let reply = match owner {
+                    FieldOwner::Object(object_id) => {
+                        client.object_get_values(object_id, fields.clone()).await
+                    }
+                    FieldOwner::Class(ref_type_id) => {
+                        client
+                            .ref_type_get_values(ref_type_id, fields.clone())
+                            .await
+                    }
+                };
+                let sample = reply.map(|reply| FieldSample {
+                    values: reply.values,
+                    timestamp: Instant::now(),
+                });
+                let failed = sample.is_err();
+                if sender.send(sample).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        FieldWatch {
+            samples,
+            only_changes: false,
+            last: None,
+            poller,
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use crate::{
+    FieldOwner, FieldsReplyField, JdwpClient, JdwpErrorCode, JdwpValue, VariableLengthId, result,
+};
+
+/// `static` in the modifier bits of a field
+const ACC_STATIC: i32 = 0x0008;
+
+impl JdwpClient {
+    /// Reads fields by name with a single GetValues command, e.g.
+    /// `get_fields(FieldOwner::Object(server), &["name", "port"])`. Instance fields are read
+    /// from objects and static fields from classes, including fields inherited from
+    /// superclasses; a field hidden by one of a subclass resolves to the subclass' field.
+    ///
+    /// The fields of each type are cached after the first lookup, so later calls for the same
+    /// type only send GetValues, plus ReferenceType for objects. Fails with
+    /// [result::Error::FieldNotFound] if a name doesn't resolve, before reading anything.
+    pub async fn get_fields(
+        &self,
+        owner: FieldOwner,
+        names: &[&str],
+    ) -> result::Result<HashMap<String, JdwpValue>> {
+        if names.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let (type_id, is_static) = match owner {
+            FieldOwner::Object(object_id) => (
+                self.object_get_reference_type(object_id).await?.type_id,
+                false,
+            ),
+            FieldOwner::Class(type_id) => (type_id, true),
+        };
+
+        let fields = self.fields_in_hierarchy(type_id).await?;
+        let field_ids = names
+            .iter()
+            .map(|name| {
+                fields
+                    .iter()
+                    .find(|field| {
+                        field.name.string == *name
+                            && (field.mod_bits & ACC_STATIC != 0) == is_static
+                    })
+                    .map(|field| field.field_id)
+                    .ok_or_else(|| result::Error::FieldNotFound {
+                        name: String::from(*name),
+                    })
+            })
+            .collect::<result::Result<Vec<_>>>()?;
+
+        let values = match owner {
+            FieldOwner::Object(object_id) => self.object_get_values(object_id, field_ids).await?,
+            FieldOwner::Class(type_id) => self.ref_type_get_values(type_id, field_ids).await?,
+        };
+        Ok(names
+            .iter()
+            .map(|name| String::from(*name))
+            .zip(values.values)
+            .collect())
+    }
+
+    /// Forgets the fields cached by [JdwpClient::get_fields], e.g. after classes were unloaded,
+    /// as the VM may reuse their ids
+    pub fn clear_field_cache(&self) {
+        self.shared.fields().clear();
+    }
+
+    /// Fields declared by the type followed by those of its superclasses, nearest first
+    async fn fields_in_hierarchy(
+        &self,
+        type_id: VariableLengthId,
+    ) -> result::Result<Vec<FieldsReplyField>> {
+        if let Some(fields) = self.shared.fields().get(&type_id) {
+            return Ok(fields.clone());
+        }
+
+        let mut fields = Vec::new();
+        let mut current = type_id;
+        loop {
+            fields.extend(self.ref_type_get_fields(current).await?.fields);
+            // Interfaces and arrays have no superclass to continue with
+            current = match self.class_type_get_superclass(current).await {
+                Ok(reply) if reply.superclass.value != 0 => reply.superclass,
+                Ok(_) | Err(result::Error::JdwpError(JdwpErrorCode::InvalidClass)) => break,
+                Err(e) => return Err(e),
+            };
+        }
+        self.shared.fields().insert(type_id, fields.clone());
+        Ok(fields)
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+
+use crate::{
+    FrameSlot, JdwpClient, JdwpErrorCode, JdwpValue, Location, Smap, Stratum, VariableLengthId,
+    line_at, result,
+};
+
+/// Stratum in which Kotlin maps the lines of inlined code to the line of their call site
+pub const KOTLIN_DEBUG_STRATUM: &str = "KotlinDebug";
+
+/// A line of the source the class was compiled from, as given by its SMAP
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SourceLine {
+    /// File name, e.g. `Main.kt`
+    pub file: String,
+    /// Path relative to a source root, if the SMAP has one
+    pub path: Option<String>,
+    pub line: i32,
+}
+
+/// A frame of a thread's stack, see [JdwpClient::frames]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub thread_id: VariableLengthId,
+    /// Id of the frame in the VM. Frames synthesized for inlined code share it with the frame
+    /// they were inlined into.
+    pub frame_id: VariableLengthId,
+    /// [JdwpClient::thread_generation] the frame was read in. The frame id is stale once the
+    /// thread's generation has changed.
+    pub generation: u64,
+    pub location: Location,
+    /// Position in the default stratum of the class's SMAP, None for classes without one or if
+    /// frames aren't expanded
+    pub source: Option<SourceLine>,
+    /// Whether the frame was synthesized for code inlined into the next frame
+    pub inlined: bool,
+}
+
+impl JdwpClient {
+    /// Frames of the thread's stack, the current one first. The thread must be suspended.
+    ///
+    /// With `expand_inline`, the frames are mapped to the default stratum of their class's SMAP
+    /// and code inlined from another source file, such as a Kotlin inline function, gets a frame
+    /// of its own before the frame it was inlined into. The call site of inlined code is taken
+    /// from the `KotlinDebug` stratum; SMAPs don't describe inlining nested deeper than that.
+    pub async fn frames(
+        &self,
+        thread_id: VariableLengthId,
+        expand_inline: bool,
+    ) -> result::Result<Vec<Frame>> {
+        let physical_frames = self.physical_frames(thread_id, 0, -1).await?;
+        if !expand_inline {
+            return Ok(physical_frames);
+        }
+
+        let mut smaps: HashMap<VariableLengthId, Option<Smap>> = HashMap::new();
+        let mut frames = Vec::with_capacity(physical_frames.len());
+        for physical in physical_frames {
+            let class_id = physical.location.class_id;
+            if let Entry::Vacant(entry) = smaps.entry(class_id) {
+                entry.insert(self.class_smap(class_id).await?);
+            }
+            let Some(smap) = &smaps[&class_id] else {
+                frames.push(physical);
+                continue;
+            };
+            let Some(line) = self.frame_line(&physical.location).await? else {
+                frames.push(physical);
+                continue;
+            };
+            frames.extend(expand_frame(smap, line, physical));
+        }
+        Ok(frames)
+    }
+
+    /// The `n` innermost frames of the thread's stack, or all of them if it's shallower,
+    /// without transferring the rest of the stack. The thread must be suspended.
+    ///
+    /// Meant for frequent shallow polls, such as samplers; frames aren't expanded like with
+    /// [JdwpClient::frames].
+    pub async fn top_frames(
+        &self,
+        thread_id: VariableLengthId,
+        n: usize,
+    ) -> result::Result<Vec<Frame>> {
+        self.frames_page(thread_id, 0, n).await
+    }
+
+    /// Up to `length` frames of the thread's stack starting at `start`, 0 being the current
+    /// frame. Fewer are returned at the bottom of the stack and none past it, so a stack can
+    /// be paged through until an empty page. The thread must be suspended.
+    pub async fn frames_page(
+        &self,
+        thread_id: VariableLengthId,
+        start: usize,
+        length: usize,
+    ) -> result::Result<Vec<Frame>> {
+        if length == 0 {
+            return Ok(Vec::new());
+        }
+        let start = i32::try_from(start).map_err(|_| result::Error::InvalidArgument {
+            message: format!("Frame index {} out of range", start),
+        })?;
+        let length = i32::try_from(length).unwrap_or(i32::MAX);
+        match self.physical_frames(thread_id, start, length).await {
+            // The VM rejects lengths past the bottom of the stack instead of truncating them
+            Err(result::Error::JdwpError(JdwpErrorCode::InvalidLength)) => {
+                self.physical_frames(thread_id, start, -1).await
+            }
+            Err(result::Error::JdwpError(JdwpErrorCode::InvalidIndex)) => Ok(Vec::new()),
+            frames => frames,
+        }
+    }
+
+    /// Frames as reported by ThreadReference.Frames; `length` -1 reads all remaining ones
+    async fn physical_frames(
+        &self,
+        thread_id: VariableLengthId,
+        start: i32,
+        length: i32,
+    ) -> result::Result<Vec<Frame>> {
+        // Read before the frames, so a resume racing with the command makes them stale
+        let generation = self.thread_generation(thread_id);
+        let reply = self.thread_get_frames(thread_id, start, length).await?;
+        Ok(reply
+            .frames
+            .into_iter()
+            .map(|frame| Frame {
+                thread_id,
+                frame_id: frame.frame_id,
+                generation,
+                location: frame.location,
+                source: None,
+                inlined: false,
+            })
+            .collect())
+    }
+
+    /// Whether the thread of the frame may have run since the frame was read, which makes the
+    /// VM reject its id or, worse, reuse it for another frame
+    pub fn is_frame_stale(&self, frame: &Frame) -> bool {
+        self.thread_generation(frame.thread_id) != frame.generation
+    }
+
+    /// Values of local variables in the frame. Fails with [result::Error::StaleFrame] without
+    /// sending anything if the frame is stale.
+    pub async fn frame_get_values(
+        &self,
+        frame: &Frame,
+        slots: Vec<FrameSlot>,
+    ) -> result::Result<Vec<JdwpValue>> {
+        if self.is_frame_stale(frame) {
+            return Err(result::Error::StaleFrame {
+                thread: frame.thread_id,
+                frame_id: frame.frame_id,
+            });
+        }
+        Ok(self
+            .stack_frame_get_values(frame.thread_id, frame.frame_id, slots)
+            .await?
+            .values)
+    }
+
+    /// Line of the class file a location is at, None for methods without line information
+    async fn frame_line(&self, location: &Location) -> result::Result<Option<i32>> {
+        match self
+            .method_get_line_table(location.class_id, location.method_id)
+            .await
+        {
+            Ok(table) => Ok(line_at(&table.lines, location.index)),
+            Err(result::Error::JdwpError(
+                JdwpErrorCode::AbsentInformation | JdwpErrorCode::NativeMethod,
+            )) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Maps a frame at `line` of the class file through the SMAP of its class, preceded by a frame
+/// for inlined code if the line belongs to another file than the class's own
+fn expand_frame(smap: &Smap, line: i32, mut physical: Frame) -> Vec<Frame> {
+    let Some(stratum) = smap.stratum(&smap.default_stratum) else {
+        return vec![physical];
+    };
+    let source_line = |stratum: &Stratum| {
+        stratum.input_line(line).map(|(file, line)| SourceLine {
+            file: file.name.clone(),
+            path: file.path.clone(),
+            line,
+        })
+    };
+    let Some((file, _)) = stratum.input_line(line) else {
+        return vec![physical];
+    };
+    let own_file = stratum.files.first().map(|file| file.id);
+    if own_file == Some(file.id) {
+        physical.source = source_line(stratum);
+        return vec![physical];
+    }
+
+    let inlined = Frame {
+        source: source_line(stratum),
+        inlined: true,
+        ..physical.clone()
+    };
+    physical.source = smap.stratum(KOTLIN_DEBUG_STRATUM).and_then(source_line);
+    vec![inlined, physical]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TypeTag;
+
+    const SMAP: &str = "SMAP
+Main.kt
+Kotlin
+*S Kotlin
+*F
++ 1 Main.kt
+com/example/MainKt.kt
++ 2 Util.kt
+com/example/Util.kt
+*L
+1#1,10:1
+20#2,3:30
+*E
+*S KotlinDebug
+*F
++ 1 Main.kt
+com/example/MainKt.kt
+*L
+7#1,3:30
+*E
+";
+
+    fn frame() -> Frame {
+        Frame {
+            thread_id: VariableLengthId { value: 0x5 },
+            frame_id: VariableLengthId { value: 0x1 },
+            generation: 0,
+            location: Location {
+                type_tag: TypeTag::Class,
+                class_id: VariableLengthId { value: 0x10 },
+                method_id: VariableLengthId { value: 0x2 },
+                index: 0,
+            },
+            source: None,
+            inlined: false,
+        }
+    }
+
+    #[test]
+    fn test_expand_own_line() {
+        let smap = Smap::parse(SMAP).unwrap();
+        let frames = expand_frame(&smap, 4, frame());
+        assert_eq!(frames.len(), 1);
+        assert!(!frames[0].inlined);
+        assert_eq!(frames[0].source.as_ref().unwrap().line, 4);
+    }
+
+    #[test]
+    fn test_expand_inlined_line() {
+        let smap = Smap::parse(SMAP).unwrap();
+        let frames = expand_frame(&smap, 31, frame());
+        let sources: Vec<_> = frames
+            .iter()
+            .map(|frame| {
+                let source = frame.source.as_ref().unwrap();
+                (frame.inlined, source.file.as_str(), source.line)
+            })
+            .collect();
+        assert_eq!(sources, vec![(true, "Util.kt", 21), (false, "Main.kt", 8)]);
+        assert_eq!(frames[0].frame_id, frames[1].frame_id);
+    }
+}
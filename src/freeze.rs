@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fmt;
+
+use crate::{JdwpClient, JdwpErrorCode, SuspendStatus, ThreadStatus, VariableLengthId, result};
+
+/// `native` in the modifier bits of a method
+const ACC_NATIVE: i32 = 0x0100;
+
+/// State of a thread right after [JdwpClient::freeze]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrozenThread {
+    pub thread_id: VariableLengthId,
+    pub name: String,
+    pub status: ThreadStatus,
+    /// Whether the VM reports the thread as suspended
+    pub suspended: bool,
+    /// Suspend count kept by the VM
+    pub suspend_count: i32,
+    /// Whether the thread was executing a native method, which keeps running until it returns
+    /// to Java code or calls back into the VM
+    pub in_native: bool,
+}
+
+/// Something unexpected found while freezing or thawing the VM
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FreezeWarning {
+    /// The VM doesn't report the thread as suspended
+    NotSuspended { thread_id: VariableLengthId },
+    /// The thread is in a native method and may keep running
+    InNative { thread_id: VariableLengthId },
+    /// The suspend count kept by the VM differs from the one tracked by this client, e.g.
+    /// because another debugger or agent suspended the thread, or a suspension leaked
+    SuspendCountMismatch {
+        thread_id: VariableLengthId,
+        expected: u32,
+        actual: i32,
+    },
+}
+impl fmt::Display for FreezeWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FreezeWarning::NotSuspended { thread_id } => {
+                write!(f, "thread {} isn't suspended", thread_id.value)
+            }
+            FreezeWarning::InNative { thread_id } => write!(
+                f,
+                "thread {} is in a native method and may keep running",
+                thread_id.value
+            ),
+            FreezeWarning::SuspendCountMismatch {
+                thread_id,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "thread {} has a suspend count of {}, expected {}",
+                thread_id.value, actual, expected
+            ),
+        }
+    }
+}
+
+/// The VM stopped by [JdwpClient::freeze], to be undone with [JdwpClient::thaw]
+#[derive(Debug)]
+#[must_use = "a frozen VM stays suspended until it's thawed"]
+pub struct Freeze {
+    pub threads: Vec<FrozenThread>,
+    pub warnings: Vec<FreezeWarning>,
+}
+impl Freeze {
+    /// Whether every thread is suspended and none is in a native method
+    pub fn is_complete(&self) -> bool {
+        !self.warnings.iter().any(|warning| {
+            matches!(
+                warning,
+                FreezeWarning::NotSuspended { .. } | FreezeWarning::InNative { .. }
+            )
+        })
+    }
+}
+
+impl JdwpClient {
+    /// Suspends the whole VM and checks the state of every thread afterwards
+    ///
+    /// Threads executing native code count as suspended but keep running until they return
+    /// to Java code, so they are reported as warnings, as are threads the VM doesn't report as
+    /// suspended and suspend counts which don't match the ones tracked by this client.
+    pub async fn freeze(&self) -> result::Result<Freeze> {
+        self.vm_suspend().await?;
+
+        let mut methods = HashMap::new();
+        let mut threads = Vec::new();
+        let mut warnings = Vec::new();
+        for thread in self.vm_get_all_threads().await?.threads {
+            let thread_id = thread.thread_id;
+            let frozen = match self.frozen_thread(thread_id, &mut methods).await {
+                Ok(frozen) => frozen,
+                // The thread ended after it was listed
+                Err(result::Error::JdwpError(
+                    JdwpErrorCode::InvalidThread | JdwpErrorCode::InvalidObject,
+                )) => continue,
+                Err(e) => return Err(e),
+            };
+            if !frozen.suspended {
+                warnings.push(FreezeWarning::NotSuspended { thread_id });
+            }
+            if frozen.in_native {
+                warnings.push(FreezeWarning::InNative { thread_id });
+            }
+            let expected = self.thread_suspend_count(thread_id);
+            if frozen.suspend_count != expected as i32 {
+                warnings.push(FreezeWarning::SuspendCountMismatch {
+                    thread_id,
+                    expected,
+                    actual: frozen.suspend_count,
+                });
+            }
+            threads.push(frozen);
+        }
+        Ok(Freeze { threads, warnings })
+    }
+
+    /// Resumes the VM suspended by [JdwpClient::freeze]. Returns a warning for every thread
+    /// whose suspend count didn't drop by one, such as threads still suspended by someone
+    /// else.
+    pub async fn thaw(&self, freeze: Freeze) -> result::Result<Vec<FreezeWarning>> {
+        self.vm_resume().await?;
+
+        let mut warnings = Vec::new();
+        for thread in freeze.threads {
+            let actual = match self.thread_get_suspend_count(thread.thread_id).await {
+                Ok(count) => count,
+                Err(result::Error::JdwpError(
+                    JdwpErrorCode::InvalidThread | JdwpErrorCode::InvalidObject,
+                )) => continue,
+                Err(e) => return Err(e),
+            };
+            let expected = (thread.suspend_count - 1).max(0);
+            if actual != expected {
+                warnings.push(FreezeWarning::SuspendCountMismatch {
+                    thread_id: thread.thread_id,
+                    expected: expected as u32,
+                    actual,
+                });
+            }
+        }
+        Ok(warnings)
+    }
+
+    async fn frozen_thread(
+        &self,
+        thread_id: VariableLengthId,
+        methods: &mut HashMap<VariableLengthId, HashMap<VariableLengthId, i32>>,
+    ) -> result::Result<FrozenThread> {
+        let status = self.thread_get_status(thread_id).await?;
+        let suspended = status.suspend_status.contains(SuspendStatus::SUSPENDED);
+        let suspend_count = self.thread_get_suspend_count(thread_id).await?;
+
+        let mut in_native = false;
+        if suspended {
+            // Threads which haven't started or have ended have no frames
+            let frames = match self.thread_get_frames(thread_id, 0, 1).await {
+                Ok(reply) => reply.frames,
+                Err(result::Error::JdwpError(_)) => Vec::new(),
+                Err(e) => return Err(e),
+            };
+            if let Some(frame) = frames.first() {
+                let class_id = frame.location.class_id;
+                let class_methods = match methods.entry(class_id) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(
+                        self.ref_type_get_methods(class_id)
+                            .await?
+                            .methods
+                            .into_iter()
+                            .map(|method| (method.method_id, method.mod_bits))
+                            .collect(),
+                    ),
+                };
+                in_native = class_methods
+                    .get(&frame.location.method_id)
+                    .is_some_and(|mod_bits| mod_bits & ACC_NATIVE != 0);
+            }
+        }
+
+        Ok(FrozenThread {
+            thread_id,
+            name: self.thread_name(thread_id).await?,
+            status: status.thread_status,
+            suspended,
+            suspend_count,
+            in_native,
+        })
+    }
+}
@@ -0,0 +1,323 @@
+use std::collections::HashMap;
+use std::future::Future;
+
+use tokio::sync::mpsc;
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::{
+    JdwpClient, JdwpErrorCode, JdwpValue, Pattern, TaggedObjectId, VariableLengthId, result,
+    signature_to_type_name,
+};
+
+const ACC_STATIC: i32 = 0x0008;
+
+/// Number of types counted per VirtualMachine.InstanceCounts command by
+/// [JdwpClient::class_histogram]
+pub const INSTANCE_COUNTS_CHUNK_SIZE: usize = 1024;
+
+/// Number of objects read concurrently by the heap searches, [JdwpClient::find_strings] and
+/// [JdwpClient::scan_field_values]
+pub const HEAP_SEARCH_CHUNK_SIZE: usize = 64;
+
+const STRING_SIGNATURE: &str = "Ljava/lang/String;";
+
+/// A string instance found by [JdwpClient::find_strings]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoundString {
+    pub string_id: VariableLengthId,
+    pub value: String,
+}
+
+/// An instance whose field matched the predicate of [JdwpClient::scan_field_values]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldMatch {
+    pub object_id: VariableLengthId,
+    pub value: JdwpValue,
+}
+
+/// Matches of [JdwpClient::scan_field_values] as they are found
+///
+/// The scan reads ahead at most [HEAP_SEARCH_CHUNK_SIZE] matches, stops after the first error
+/// and is cancelled when dropped.
+#[derive(Debug)]
+pub struct FieldScan {
+    matches: mpsc::Receiver<result::Result<FieldMatch>>,
+    scanner: JoinHandle<()>,
+}
+impl FieldScan {
+    /// Waits for the next match. Returns None once all instances were scanned or after an
+    /// error was returned.
+    pub async fn next_match(&mut self) -> Option<result::Result<FieldMatch>> {
+        self.matches.recv().await
+    }
+}
+impl Drop for FieldScan {
+    fn drop(&mut self) {
+        self.scanner.abort();
+    }
+}
+
+/// Number of live instances of a class at the time of a [ClassHistogram]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistogramEntry {
+    pub class_id: VariableLengthId,
+    /// e.g. `com.example.Order` or `byte[]`
+    pub class_name: String,
+    pub instances: u64,
+}
+
+/// Instance counts of all loaded classes, like `jmap -histo`, see [JdwpClient::class_histogram]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClassHistogram {
+    /// Classes with at least one instance, most instances first
+    pub entries: Vec<HistogramEntry>,
+}
+impl ClassHistogram {
+    /// How the instance counts changed from this histogram to `later`, most grown classes
+    /// first. Classes whose count didn't change are left out.
+    pub fn diff(&self, later: &ClassHistogram) -> Vec<ClassGrowth> {
+        let mut growth: HashMap<VariableLengthId, ClassGrowth> = HashMap::new();
+        for entry in &self.entries {
+            growth.insert(
+                entry.class_id,
+                ClassGrowth {
+                    class_id: entry.class_id,
+                    class_name: entry.class_name.clone(),
+                    before: entry.instances,
+                    after: 0,
+                },
+            );
+        }
+        for entry in &later.entries {
+            growth
+                .entry(entry.class_id)
+                .or_insert_with(|| ClassGrowth {
+                    class_id: entry.class_id,
+                    class_name: entry.class_name.clone(),
+                    before: 0,
+                    after: 0,
+                })
+                .after = entry.instances;
+        }
+
+        let mut growth: Vec<_> = growth
+            .into_values()
+            .filter(|class| class.before != class.after)
+            .collect();
+        growth.sort_by(|a, b| {
+            b.delta()
+                .cmp(&a.delta())
+                .then_with(|| a.class_name.cmp(&b.class_name))
+        });
+        growth
+    }
+}
+
+/// Change of the instance count of a class between two histograms, see [ClassHistogram::diff]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClassGrowth {
+    pub class_id: VariableLengthId,
+    pub class_name: String,
+    pub before: u64,
+    pub after: u64,
+}
+impl ClassGrowth {
+    /// Instances gained, negative if instances were collected
+    pub fn delta(&self) -> i64 {
+        self.after as i64 - self.before as i64
+    }
+}
+
+impl JdwpClient {
+    /// Counts the live instances of every loaded class, [INSTANCE_COUNTS_CHUNK_SIZE] classes
+    /// per command. Requires the canGetInstanceInfo capability.
+    ///
+    /// Two histograms taken some time apart show which classes keep growing, see
+    /// [ClassHistogram::diff]. The VM only counts reachable objects, so no GC is needed first.
+    pub async fn class_histogram(&self) -> result::Result<ClassHistogram> {
+        let classes = self.vm_get_all_classes().await?.classes;
+        let mut entries = Vec::new();
+        for chunk in classes.chunks(INSTANCE_COUNTS_CHUNK_SIZE) {
+            let counts = self
+                .vm_get_instance_counts(chunk.iter().map(|class| class.type_id).collect())
+                .await?
+                .counts;
+            for (class, count) in chunk.iter().zip(counts) {
+                if count > 0 {
+                    entries.push(HistogramEntry {
+                        class_id: class.type_id,
+                        class_name: signature_to_type_name(&class.signature.string),
+                        instances: count as u64,
+                    });
+                }
+            }
+        }
+        entries.sort_by(|a, b| {
+            b.instances
+                .cmp(&a.instances)
+                .then_with(|| a.class_name.cmp(&b.class_name))
+        });
+        Ok(ClassHistogram { entries })
+    }
+
+    /// Searches all `java.lang.String` instances for values matching the pattern, returning
+    /// at most `limit` of them. Values are read [HEAP_SEARCH_CHUNK_SIZE] strings at a time;
+    /// strings collected during the search are skipped. Requires the canGetInstanceInfo
+    /// capability.
+    pub async fn find_strings(
+        &self,
+        pattern: &Pattern,
+        limit: usize,
+    ) -> result::Result<Vec<FoundString>> {
+        let string_class = self.find_loaded_class(STRING_SIGNATURE).await?;
+        let strings = self
+            .ref_type_get_instances(string_class, 0)
+            .await?
+            .instances;
+
+        let mut found = Vec::new();
+        for chunk in strings.chunks(HEAP_SEARCH_CHUNK_SIZE) {
+            if found.len() >= limit {
+                break;
+            }
+            let values = self
+                .read_concurrently(chunk, |client, string: TaggedObjectId| async move {
+                    let value = client.string_get_value(string.object_id).await?;
+                    Ok(value.string_value.string)
+                })
+                .await?;
+            for (string, value) in chunk.iter().zip(values) {
+                if let Some(value) = value
+                    && found.len() < limit
+                    && pattern.is_match(&value)
+                {
+                    found.push(FoundString {
+                        string_id: string.object_id,
+                        value,
+                    });
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    /// Reads an instance field of every instance of a class and returns those whose value
+    /// satisfies `predicate`, e.g. all sessions of a user. `class_signature` is a JNI
+    /// signature like `Lcom/example/Session;`; instances of subclasses aren't scanned. The
+    /// field may be declared by a superclass.
+    ///
+    /// Values are read with [HEAP_SEARCH_CHUNK_SIZE] GetValues commands in flight; objects
+    /// collected during the scan are skipped. Requires the canGetInstanceInfo capability.
+    pub async fn scan_field_values<P>(
+        &self,
+        class_signature: &str,
+        field_name: &str,
+        predicate: P,
+    ) -> result::Result<FieldScan>
+    where
+        P: Fn(&JdwpValue) -> bool + Send + Sync + 'static,
+    {
+        let class_id = self.find_loaded_class(class_signature).await?;
+        let field_id = self.find_instance_field(class_id, field_name).await?;
+
+        let (sender, matches) = mpsc::channel(HEAP_SEARCH_CHUNK_SIZE);
+        let client = self.clone();
+        let scanner = tokio::spawn(async move {
+            let scanned = async {
+                let instances = client.ref_type_get_instances(class_id, 0).await?.instances;
+                for chunk in instances.chunks(HEAP_SEARCH_CHUNK_SIZE) {
+                    let values = client
+                        .read_concurrently(chunk, |client, object: TaggedObjectId| async move {
+                            client
+                                .object_get_values(object.object_id, vec![field_id])
+                                .await?
+                                .values
+                                .pop()
+                                .ok_or_else(|| result::Error::ParsingError {
+                                    message: String::from("GetValues returned no value"),
+                                })
+                        })
+                        .await?;
+                    for (object, value) in chunk.iter().zip(values) {
+                        let Some(value) = value else {
+                            continue;
+                        };
+                        if !predicate(&value) {
+                            continue;
+                        }
+                        let found = FieldMatch {
+                            object_id: object.object_id,
+                            value,
+                        };
+                        if sender.send(Ok(found)).await.is_err() {
+                            // Nobody is waiting for more matches
+                            return Ok(());
+                        }
+                    }
+                }
+                Ok(())
+            };
+            if let Err(e) = scanned.await {
+                _ = sender.send(Err(e)).await;
+            }
+        });
+        Ok(FieldScan { matches, scanner })
+    }
+
+    /// The instance field named `field_name` of the class or of its nearest superclass
+    /// declaring one
+    async fn find_instance_field(
+        &self,
+        class_id: VariableLengthId,
+        field_name: &str,
+    ) -> result::Result<VariableLengthId> {
+        let mut current = class_id;
+        while current.value != 0 {
+            let field = self
+                .ref_type_get_fields(current)
+                .await?
+                .fields
+                .into_iter()
+                .find(|field| field.name.string == field_name && field.mod_bits & ACC_STATIC == 0);
+            if let Some(field) = field {
+                return Ok(field.field_id);
+            }
+            current = self.class_type_get_superclass(current).await?.superclass;
+        }
+        Err(result::Error::InvalidArgument {
+            message: format!("No instance field {}", field_name),
+        })
+    }
+
+    /// Runs `read` for all items at once, returning the results in the order of the items.
+    /// Items whose object was collected in the meantime, failing with INVALID_OBJECT, are
+    /// None.
+    async fn read_concurrently<T, R, F, Fut>(
+        &self,
+        items: &[T],
+        read: F,
+    ) -> result::Result<Vec<Option<R>>>
+    where
+        T: Copy + Send + 'static,
+        R: Send + 'static,
+        F: Fn(JdwpClient, T) -> Fut,
+        Fut: Future<Output = result::Result<R>> + Send + 'static,
+    {
+        let mut reads = JoinSet::new();
+        for (index, item) in items.iter().enumerate() {
+            let read = read(self.clone(), *item);
+            reads.spawn(async move { (index, read.await) });
+        }
+
+        let mut results: Vec<Option<R>> = (0..items.len()).map(|_| None).collect();
+        while let Some(joined) = reads.join_next().await {
+            let (index, value) = joined.expect("heap read task panicked");
+            match value {
+                Ok(value) => results[index] = Some(value),
+                Err(result::Error::JdwpError(JdwpErrorCode::InvalidObject)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(results)
+    }
+}
@@ -0,0 +1,259 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use crate::{
+    Event, EventComposite, EventKind, EventRequestHandle, FrameSlot, JdwpClient, JdwpValue,
+    LineBreakpoint, Location, SuspendPolicy, Tag, TaggedObjectId, VariableLengthId, result,
+    signature_tag,
+};
+
+/// What happens to the threads an event suspended once its hooks have run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HookAction {
+    /// Resume what the event set suspended, unless another hook of the set keeps it suspended
+    #[default]
+    Resume,
+    /// Leave the threads suspended, e.g. to inspect them from elsewhere
+    KeepSuspended,
+}
+
+pub type HookFuture = Pin<Box<dyn Future<Output = result::Result<HookAction>> + Send>>;
+
+type Hook = Arc<dyn Fn(HookContext) -> HookFuture + Send + Sync>;
+
+enum Trigger {
+    Kind(EventKind),
+    Request(i32),
+    Requests(Box<dyn Fn(i32) -> bool + Send + Sync>),
+}
+impl Trigger {
+    fn matches(&self, event: &Event) -> bool {
+        match self {
+            Trigger::Kind(kind) => event.kind() == *kind,
+            Trigger::Request(request_id) => event.request_id() == *request_id,
+            Trigger::Requests(matches) => matches(event.request_id()),
+        }
+    }
+}
+
+/// An event passed to a hook, with access to the frame it occurred in
+#[derive(Clone)]
+pub struct HookContext {
+    client: JdwpClient,
+    event: Event,
+    suspend_policy: SuspendPolicy,
+}
+impl HookContext {
+    pub fn client(&self) -> &JdwpClient {
+        &self.client
+    }
+
+    pub fn event(&self) -> &Event {
+        &self.event
+    }
+
+    /// Which threads the VM suspended for the event set
+    pub fn suspend_policy(&self) -> SuspendPolicy {
+        self.suspend_policy
+    }
+
+    pub fn thread(&self) -> Option<VariableLengthId> {
+        self.event.thread()
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        self.event.location()
+    }
+
+    /// Local variables and arguments visible at the current location of the event's thread,
+    /// in the order of the method's variable table. The thread must be suspended and its class
+    /// compiled with debug information.
+    pub async fn locals(&self) -> result::Result<Vec<(String, JdwpValue)>> {
+        let (thread, frame_id, location) = self.current_frame().await?;
+        let table = self
+            .client
+            .method_get_variable_table(location.class_id, location.method_id)
+            .await?;
+        let visible: Vec<_> = table
+            .slots
+            .into_iter()
+            .filter(|variable| variable.is_visible_at(location.index))
+            .collect();
+        let slots = visible
+            .iter()
+            .map(|variable| FrameSlot {
+                slot: variable.slot,
+                tag: signature_tag(&variable.signature.string).unwrap_or(Tag::Object),
+            })
+            .collect();
+        let values = self
+            .client
+            .stack_frame_get_values(thread, frame_id, slots)
+            .await?
+            .values;
+        Ok(visible
+            .into_iter()
+            .map(|variable| variable.name.string)
+            .zip(values)
+            .collect())
+    }
+
+    /// The visible local variable or argument with the name
+    pub async fn local(&self, name: &str) -> result::Result<JdwpValue> {
+        self.locals()
+            .await?
+            .into_iter()
+            .find(|(local, _)| local == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| result::Error::InvalidArgument {
+                message: format!("No local variable {} at this location", name),
+            })
+    }
+
+    /// The object the current method of the event's thread was called on, None in static and
+    /// native methods
+    pub async fn this(&self) -> result::Result<Option<TaggedObjectId>> {
+        let (thread, frame_id, _) = self.current_frame().await?;
+        let object = self
+            .client
+            .stack_frame_this_object(thread, frame_id)
+            .await?
+            .object;
+        Ok((object.object_id.value != 0).then_some(object))
+    }
+
+    async fn current_frame(
+        &self,
+    ) -> result::Result<(VariableLengthId, VariableLengthId, Location)> {
+        let thread = self.thread().ok_or(result::Error::NoSuspendedThread)?;
+        let frames = self.client.thread_get_frames(thread, 0, 1).await?.frames;
+        let frame = frames.first().ok_or(result::Error::NoSuspendedThread)?;
+        Ok((thread, frame.frame_id, frame.location))
+    }
+}
+
+/// Async callbacks run for events, instead of reading the event stream by hand
+///
+/// Hooks are registered per event kind or per event request, and [Hooks::run] calls each
+/// matching hook in the order it was registered. Once the hooks of an event set have run, what
+/// the set suspended is resumed unless a hook returned [HookAction::KeepSuspended]. Event sets
+/// no hook matched are left alone.
+///
+/// ```no_run
+/// # async fn example(client: &jdwp_client::JdwpClient) -> jdwp_client::Result<()> {
+/// use jdwp_client::{HookAction, Hooks, SuspendPolicy};
+///
+/// let breakpoint = client
+///     .set_line_breakpoint("com.example.Main", 42, SuspendPolicy::EventThread)
+///     .await?;
+/// let mut hooks = Hooks::new(client);
+/// hooks.on_breakpoint(&breakpoint, |ctx| async move {
+///     println!("count = {:?}", ctx.local("count").await?);
+///     Ok(HookAction::Resume)
+/// });
+/// hooks.run().await
+/// # }
+/// ```
+pub struct Hooks {
+    client: JdwpClient,
+    hooks: Vec<(Trigger, Hook)>,
+    composites: mpsc::UnboundedReceiver<EventComposite>,
+}
+
+impl Hooks {
+    /// Starts collecting events for the hooks. Events received before this call are not
+    /// passed to them.
+    pub fn new(client: &JdwpClient) -> Self {
+        let (sender, composites) = mpsc::unbounded_channel();
+        client.shared.bus().subscribe_composites(sender);
+        Hooks {
+            client: client.clone(),
+            hooks: Vec::new(),
+            composites,
+        }
+    }
+
+    /// Runs the hook for every event of the kind
+    pub fn on<F, R>(&mut self, kind: EventKind, hook: F) -> &mut Self
+    where
+        F: Fn(HookContext) -> R + Send + Sync + 'static,
+        R: Future<Output = result::Result<HookAction>> + Send + 'static,
+    {
+        self.add(Trigger::Kind(kind), hook)
+    }
+
+    /// Runs the hook for every event of the request
+    pub fn on_hit<F, R>(&mut self, handle: &EventRequestHandle, hook: F) -> &mut Self
+    where
+        F: Fn(HookContext) -> R + Send + Sync + 'static,
+        R: Future<Output = result::Result<HookAction>> + Send + 'static,
+    {
+        self.add(Trigger::Request(handle.request_id()), hook)
+    }
+
+    /// Runs the hook for every hit of the breakpoint, including hits in classes loaded later
+    pub fn on_breakpoint<F, R>(&mut self, breakpoint: &LineBreakpoint, hook: F) -> &mut Self
+    where
+        F: Fn(HookContext) -> R + Send + Sync + 'static,
+        R: Future<Output = result::Result<HookAction>> + Send + 'static,
+    {
+        self.add(
+            Trigger::Requests(Box::new(breakpoint.request_matcher())),
+            hook,
+        )
+    }
+
+    fn add<F, R>(&mut self, trigger: Trigger, hook: F) -> &mut Self
+    where
+        F: Fn(HookContext) -> R + Send + Sync + 'static,
+        R: Future<Output = result::Result<HookAction>> + Send + 'static,
+    {
+        self.hooks.push((
+            trigger,
+            Arc::new(move |ctx| Box::pin(hook(ctx)) as HookFuture),
+        ));
+        self
+    }
+
+    /// Dispatches events to the hooks until the connection is closed. A failing hook stops
+    /// the dispatch and its error is returned, leaving the threads of its event set suspended.
+    pub async fn run(mut self) -> result::Result<()> {
+        while let Some(composite) = self.composites.recv().await {
+            let mut matched = false;
+            let mut keep_suspended = false;
+            for event in &composite.events {
+                for (trigger, hook) in &self.hooks {
+                    if !trigger.matches(event) {
+                        continue;
+                    }
+                    matched = true;
+                    let ctx = HookContext {
+                        client: self.client.clone(),
+                        event: event.clone(),
+                        suspend_policy: composite.suspend_policy,
+                    };
+                    if hook(ctx).await? == HookAction::KeepSuspended {
+                        keep_suspended = true;
+                    }
+                }
+            }
+            if matched && !keep_suspended {
+                self.resume(&composite).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn resume(&self, composite: &EventComposite) -> result::Result<()> {
+        match composite.suspend_policy {
+            SuspendPolicy::None | SuspendPolicy::Unknown(_) => Ok(()),
+            SuspendPolicy::EventThread => match composite.events.iter().find_map(Event::thread) {
+                Some(thread) => self.client.thread_resume(thread).await,
+                None => Ok(()),
+            },
+            SuspendPolicy::All => self.client.vm_resume().await,
+        }
+    }
+}
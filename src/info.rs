@@ -0,0 +1,42 @@
+use crate::{CapabilitiesNewReply, ClassPathsReply, VersionReply};
+
+/// Everything needed to identify a target VM, gathered in one call by `JdwpClient::vm_info`
+#[derive(Debug, Clone)]
+pub struct VmInfo {
+    pub description: String,
+    pub jdwp_major: i32,
+    pub jdwp_minor: i32,
+    pub vm_version: String,
+    pub vm_name: String,
+    pub capabilities: CapabilitiesNewReply,
+    pub base_dir: String,
+    pub class_path: Vec<String>,
+    pub boot_class_path: Vec<String>,
+}
+impl VmInfo {
+    pub fn new(
+        version: VersionReply,
+        capabilities: CapabilitiesNewReply,
+        class_paths: ClassPathsReply,
+    ) -> Self {
+        VmInfo {
+            description: version.description.string,
+            jdwp_major: version.jdwp_major,
+            jdwp_minor: version.jdwp_minor,
+            vm_version: version.vm_version.string,
+            vm_name: version.vm_name.string,
+            capabilities,
+            base_dir: class_paths.base_dir.string,
+            class_path: class_paths
+                .classpaths
+                .into_iter()
+                .map(|p| p.string)
+                .collect(),
+            boot_class_path: class_paths
+                .bootclasspaths
+                .into_iter()
+                .map(|p| p.string)
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashSet;
+
+use crate::{JdwpClient, MethodsReplyMethod, VariableLengthId, result};
+
+const ACC_PUBLIC: i32 = 0x0001;
+const ACC_STATIC: i32 = 0x0008;
+
+pub(crate) const OBJECT_SIGNATURE: &str = "Ljava/lang/Object;";
+pub(crate) const RECORD_SIGNATURE: &str = "Ljava/lang/Record;";
+
+/// A component of a Java record, e.g. `x` of `record Point(int x, int y)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordComponent {
+    pub name: String,
+    /// JNI signature of the component type
+    pub signature: String,
+    /// The private field holding the component
+    pub field_id: VariableLengthId,
+    /// The accessor method, `x()`
+    pub accessor_id: VariableLengthId,
+}
+
+/// A JavaBeans property, read by a public `getX()` or `isX()` method
+#[derive(Debug, Clone)]
+pub struct BeanProperty {
+    /// `name` for `getName()`, `URL` for `getURL()`
+    pub name: String,
+    /// Class declaring the getter
+    pub class_id: VariableLengthId,
+    pub getter: MethodsReplyMethod,
+}
+
+impl JdwpClient {
+    /// Components of a record class in declaration order, or None if `type_id` isn't a
+    /// record. Records are recognized by extending java.lang.Record, and their components by
+    /// an instance field with an accessor of the same name and type.
+    pub async fn record_components(
+        &self,
+        type_id: VariableLengthId,
+    ) -> result::Result<Option<Vec<RecordComponent>>> {
+        let superclass = self.class_type_get_superclass(type_id).await?.superclass;
+        if superclass.value == 0
+            || self
+                .ref_type_get_signature(superclass)
+                .await?
+                .signature
+                .string
+                != RECORD_SIGNATURE
+        {
+            return Ok(None);
+        }
+        Ok(Some(self.components_of_record(type_id).await?))
+    }
+
+    /// Components of a class already known to be a record
+    pub(crate) async fn components_of_record(
+        &self,
+        type_id: VariableLengthId,
+    ) -> result::Result<Vec<RecordComponent>> {
+        let methods = self.ref_type_get_methods(type_id).await?.methods;
+        let fields = self.ref_type_get_fields(type_id).await?.fields;
+        Ok(fields
+            .into_iter()
+            .filter(|field| field.mod_bits & ACC_STATIC == 0)
+            .filter_map(|field| {
+                let accessor_signature = format!("(){}", field.signature.string);
+                let accessor = methods.iter().find(|method| {
+                    method.name.string == field.name.string
+                        && method.signature.string == accessor_signature
+                })?;
+                Some(RecordComponent {
+                    name: field.name.string,
+                    signature: field.signature.string,
+                    field_id: field.field_id,
+                    accessor_id: accessor.method_id,
+                })
+            })
+            .collect())
+    }
+
+    /// Bean properties of a class and its superclasses up to, but excluding, Object, sorted by
+    /// name. A getter overridden in a subclass is only listed once, with the subclass as its
+    /// declaring class.
+    pub async fn bean_properties(
+        &self,
+        type_id: VariableLengthId,
+    ) -> result::Result<Vec<BeanProperty>> {
+        let mut properties = Vec::new();
+        let mut seen = HashSet::new();
+        let mut current = type_id;
+        while current.value != 0 {
+            if self.ref_type_get_signature(current).await?.signature.string == OBJECT_SIGNATURE {
+                break;
+            }
+            for method in self.ref_type_get_methods(current).await?.methods {
+                if let Some(name) = property_name(&method)
+                    && seen.insert(name.clone())
+                {
+                    properties.push(BeanProperty {
+                        name,
+                        class_id: current,
+                        getter: method,
+                    });
+                }
+            }
+            current = self.class_type_get_superclass(current).await?.superclass;
+        }
+        properties.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(properties)
+    }
+}
+
+/// Name of the property a method is the getter of, decapitalized like
+/// `java.beans.Introspector`
+fn property_name(method: &MethodsReplyMethod) -> Option<String> {
+    if method.mod_bits & ACC_PUBLIC == 0 || method.mod_bits & ACC_STATIC != 0 {
+        return None;
+    }
+    let return_type = method.signature.string.strip_prefix("()")?;
+    let name = &method.name.string;
+    let rest = match (name.strip_prefix("get"), name.strip_prefix("is")) {
+        (Some(rest), _) if return_type != "V" => rest,
+        (_, Some(rest)) if return_type == "Z" => rest,
+        _ => return None,
+    };
+
+    let mut chars = rest.chars();
+    let first = chars.next().filter(|c| c.is_uppercase())?;
+    // Names starting with two capitals are kept, so getURL() is the URL property
+    if chars.clone().next().is_some_and(char::is_uppercase) {
+        return Some(String::from(rest));
+    }
+    Some(first.to_lowercase().chain(chars).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JdwpString;
+
+    fn method(name: &str, signature: &str, mod_bits: i32) -> MethodsReplyMethod {
+        MethodsReplyMethod {
+            method_id: VariableLengthId { value: 1 },
+            name: JdwpString {
+                string: String::from(name),
+            },
+            signature: JdwpString {
+                string: String::from(signature),
+            },
+            mod_bits,
+        }
+    }
+
+    #[test]
+    fn test_property_name() {
+        let name = |name, signature| property_name(&method(name, signature, ACC_PUBLIC));
+        assert_eq!(
+            name("getName", "()Ljava/lang/String;"),
+            Some(String::from("name"))
+        );
+        assert_eq!(name("isActive", "()Z"), Some(String::from("active")));
+        assert_eq!(
+            name("getURL", "()Ljava/net/URL;"),
+            Some(String::from("URL"))
+        );
+        assert_eq!(name("isActive", "()I"), None);
+        assert_eq!(name("getName", "(I)Ljava/lang/String;"), None);
+        assert_eq!(name("getter", "()I"), None);
+        assert_eq!(name("get", "()I"), None);
+        assert_eq!(name("getNothing", "()V"), None);
+        assert_eq!(property_name(&method("getName", "()I", 0x0002)), None);
+        assert_eq!(property_name(&method("getName", "()I", 0x0009)), None);
+    }
+}
@@ -0,0 +1,234 @@
+use std::fmt;
+
+use crate::{
+    ArrayValues, IntoJdwpArguments, InvokeOptions, JdwpClient, JdwpValue, TaggedObjectId,
+    TryFromJdwpValue, VariableLengthId, result, signature_to_type_name, type_name_to_signature,
+};
+
+/// Class, message and stack trace of a Throwable in the debuggee
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExceptionDetails {
+    pub class_name: String,
+    pub message: Option<String>,
+    /// `toString()` of each stack trace element, innermost frame first
+    pub stack_trace: Vec<String>,
+}
+/// Formats the details like `Throwable.printStackTrace()`, without causes
+impl fmt::Display for ExceptionDetails {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.class_name)?;
+        if let Some(message) = &self.message {
+            write!(f, ": {}", message)?;
+        }
+        for element in &self.stack_trace {
+            write!(f, "\n\tat {}", element)?;
+        }
+        Ok(())
+    }
+}
+
+/// An exception thrown in the debuggee by a method invoked through the client
+///
+/// Details of the exception are only fetched when asked for. Fetching the message and stack
+/// trace invokes methods of the exception in the thread the failed invocation ran in, which
+/// therefore has to still be suspended.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExceptionHandle {
+    exception: TaggedObjectId,
+    thread: VariableLengthId,
+}
+impl ExceptionHandle {
+    pub fn new(exception: TaggedObjectId, thread: VariableLengthId) -> Self {
+        ExceptionHandle { exception, thread }
+    }
+
+    /// The thrown Throwable object
+    pub fn object(&self) -> TaggedObjectId {
+        self.exception
+    }
+
+    /// Thread the failed invocation ran in
+    pub fn thread(&self) -> VariableLengthId {
+        self.thread
+    }
+
+    /// Name of the exception class, e.g. `java.lang.IllegalStateException`
+    pub async fn class_name(&self, client: &JdwpClient) -> result::Result<String> {
+        let reference_type = client
+            .object_get_reference_type(self.exception.object_id)
+            .await?;
+        let signature = client
+            .ref_type_get_signature(reference_type.type_id)
+            .await?
+            .signature;
+        Ok(signature_to_type_name(&signature.string))
+    }
+
+    /// Fetches the class name, message and stack trace at once
+    pub async fn details(&self, client: &JdwpClient) -> result::Result<ExceptionDetails> {
+        client.exception_details(self.thread, self.exception).await
+    }
+
+    /// Result of `getMessage()`
+    pub async fn message(&self, client: &JdwpClient) -> result::Result<Option<String>> {
+        let message = client
+            .invoke_method(self.thread, self.exception, "getMessage", ())
+            .await?;
+        Option::<String>::try_from_jdwp_value(message, client).await
+    }
+
+    /// `toString()` of each element of `getStackTrace()`, innermost frame first
+    pub async fn stack_trace(&self, client: &JdwpClient) -> result::Result<Vec<String>> {
+        let elements = client
+            .invoke_method(self.thread, self.exception, "getStackTrace", ())
+            .await?;
+        let elements = TaggedObjectId::try_from_jdwp_value(elements, client).await?;
+        let ArrayValues::Objects(elements) = client.read_array(elements.object_id).await? else {
+            return Err(result::Error::ParsingError {
+                message: String::from("getStackTrace() didn't return an object array"),
+            });
+        };
+
+        let mut trace = Vec::with_capacity(elements.len());
+        for element in elements {
+            let line = client
+                .invoke_method(self.thread, element, "toString", ())
+                .await?;
+            trace.push(String::try_from_jdwp_value(line, client).await?);
+        }
+        Ok(trace)
+    }
+}
+
+impl JdwpClient {
+    /// Retrieves the class name, `getMessage()` and stack trace of a debuggee Throwable, e.g.
+    /// the exception of an EXCEPTION event. The message and stack trace are fetched by invoking
+    /// methods in `thread_id`, which must be suspended by an event.
+    pub async fn exception_details(
+        &self,
+        thread_id: VariableLengthId,
+        exception: TaggedObjectId,
+    ) -> result::Result<ExceptionDetails> {
+        let handle = ExceptionHandle::new(exception, thread_id);
+        Ok(ExceptionDetails {
+            class_name: handle.class_name(self).await?,
+            message: handle.message(self).await?,
+            stack_trace: handle.stack_trace(self).await?,
+        })
+    }
+
+    /// Invokes an instance method of `object` by name, picking the overload which accepts
+    /// `arguments`. `thread_id` must be suspended by an event.
+    ///
+    /// An exception thrown by the method is returned as [result::Error::InvocationException].
+    pub async fn invoke_method(
+        &self,
+        thread_id: VariableLengthId,
+        object: TaggedObjectId,
+        name: &str,
+        arguments: impl IntoJdwpArguments,
+    ) -> result::Result<JdwpValue> {
+        let arguments = arguments.into_jdwp_arguments(self).await?;
+        let class_id = self
+            .object_get_reference_type(object.object_id)
+            .await?
+            .type_id;
+        let method = self.resolve_method(class_id, name, &arguments).await?;
+
+        let reply = self
+            .object_invoke_method(
+                object.object_id,
+                thread_id,
+                class_id,
+                method.method_id,
+                arguments,
+                InvokeOptions::empty(),
+            )
+            .await?;
+        if reply.exception.object_id.value != 0 {
+            return Err(result::Error::InvocationException {
+                exception: ExceptionHandle::new(reply.exception, thread_id),
+            });
+        }
+        Ok(reply.return_value)
+    }
+
+    /// Invokes a static method of `class_id` by name, picking the overload which accepts
+    /// `arguments`. `thread_id` must be suspended by an event.
+    ///
+    /// An exception thrown by the method is returned as [result::Error::InvocationException].
+    pub async fn invoke_static_method(
+        &self,
+        thread_id: VariableLengthId,
+        class_id: VariableLengthId,
+        name: &str,
+        arguments: impl IntoJdwpArguments,
+    ) -> result::Result<JdwpValue> {
+        let arguments = arguments.into_jdwp_arguments(self).await?;
+        let method = self.resolve_method(class_id, name, &arguments).await?;
+
+        let reply = self
+            .class_type_invoke_method(
+                class_id,
+                thread_id,
+                method.method_id,
+                arguments,
+                InvokeOptions::empty(),
+            )
+            .await?;
+        if reply.exception.object_id.value != 0 {
+            return Err(result::Error::InvocationException {
+                exception: ExceptionHandle::new(reply.exception, thread_id),
+            });
+        }
+        Ok(reply.return_value)
+    }
+
+    /// Calls a static method by class and method name, e.g.
+    /// `call_static::<()>("com.example.Debug", "dump", (1, "all"))`, and converts its return
+    /// value. The class has to be loaded already. The thread to invoke in is chosen by the
+    /// [crate::ThreadPicker] of the client.
+    pub async fn call_static<R: TryFromJdwpValue>(
+        &self,
+        class_name: &str,
+        name: &str,
+        arguments: impl IntoJdwpArguments,
+    ) -> result::Result<R> {
+        let thread_id = self.pick_invocation_thread().await?;
+        let class_id = self
+            .find_loaded_class(&type_name_to_signature(class_name))
+            .await?;
+        let value = self
+            .invoke_static_method(thread_id, class_id, name, arguments)
+            .await?;
+        R::try_from_jdwp_value(value, self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_exception_details() {
+        let details = ExceptionDetails {
+            class_name: String::from("java.lang.IllegalStateException"),
+            message: Some(String::from("bad state")),
+            stack_trace: vec![
+                String::from("Main.run(Main.java:7)"),
+                String::from("Main.main(Main.java:3)"),
+            ],
+        };
+        assert_eq!(
+            details.to_string(),
+            "java.lang.IllegalStateException: bad state\n\tat Main.run(Main.java:7)\n\tat Main.main(Main.java:3)"
+        );
+
+        let details = ExceptionDetails {
+            message: None,
+            stack_trace: Vec::new(),
+            ..details
+        };
+        assert_eq!(details.to_string(), "java.lang.IllegalStateException");
+    }
+}
@@ -0,0 +1,268 @@
+use std::ffi::OsStr;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStderr, ChildStdout, Command, ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::timeout;
+
+use crate::{Debugger, Transport, result};
+
+/// Agent option added to launched VMs. The VM picks a free port and waits for the debugger.
+pub const LAUNCH_AGENT_OPTION: &str =
+    "-agentlib:jdwp=transport=dt_socket,server=y,suspend=y,address=0";
+
+/// How long a launched VM may take to start listening for the debugger
+pub const LAUNCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+const LISTENING_PREFIX: &str = "Listening for transport dt_socket at address: ";
+
+/// A VM started by [Debugger::launch]. The process is killed when the handle is dropped.
+#[derive(Debug)]
+pub struct LaunchedVm {
+    child: Child,
+    address: String,
+    output: Option<mpsc::UnboundedReceiver<OutputLine>>,
+}
+impl LaunchedVm {
+    /// Address the debugger connected to
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    pub fn child(&mut self) -> &mut Child {
+        &mut self.child
+    }
+
+    pub fn kill(&mut self) -> io::Result<()> {
+        self.child.kill()
+    }
+
+    /// Exit status of the process if it has exited, without waiting
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        self.child.try_wait()
+    }
+
+    /// Waits for the next line of output of a VM started with
+    /// [Debugger::launch_capturing_output]. Returns None once both streams are closed, and
+    /// right away if the output isn't captured.
+    pub async fn next_output(&mut self) -> Option<OutputLine> {
+        self.output.as_mut()?.recv().await
+    }
+
+    /// Returns the next captured line of output without waiting
+    pub fn try_next_output(&mut self) -> Option<OutputLine> {
+        self.output.as_mut()?.try_recv().ok()
+    }
+}
+impl Drop for LaunchedVm {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Address in the line a VM prints once its agent listens, e.g. `localhost:34567` for
+/// `Listening for transport dt_socket at address: 34567`
+fn listening_address(line: &str) -> Option<String> {
+    let address = line.trim_end().strip_prefix(LISTENING_PREFIX)?;
+    if address.contains(':') {
+        Some(String::from(address))
+    } else {
+        address
+            .parse::<u16>()
+            .ok()
+            .map(|port| format!("localhost:{}", port))
+    }
+}
+
+/// Stream a line of output of a launched VM was written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// A line written by a VM started with [Debugger::launch_capturing_output]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputLine {
+    pub stream: OutputStream,
+    /// When the line was read, comparable with [crate::Stop::timestamp]
+    pub timestamp: Instant,
+    /// The line without its line break. Invalid UTF-8 is replaced.
+    pub line: String,
+}
+
+/// Where the output of a launched VM goes
+enum OutputSink {
+    Inherit,
+    Capture(mpsc::UnboundedSender<OutputLine>),
+}
+impl OutputSink {
+    fn write(&self, stream: OutputStream, line: &[u8]) {
+        match self {
+            OutputSink::Inherit => {
+                let _ = io::stdout().write_all(line);
+            }
+            OutputSink::Capture(output) => {
+                let line = String::from_utf8_lossy(line);
+                let _ = output.send(OutputLine {
+                    stream,
+                    timestamp: Instant::now(),
+                    line: String::from(line.trim_end_matches(['\n', '\r'])),
+                });
+            }
+        }
+    }
+}
+
+/// Reads the blocking stdout pipe of a launched VM on its own thread, sending the listening
+/// address once it is printed. The rest of the output is passed on, so the VM never blocks on
+/// a full pipe.
+fn read_stdout(stdout: ChildStdout, sink: OutputSink, address_tx: oneshot::Sender<String>) {
+    std::thread::spawn(move || {
+        let mut stdout = BufReader::new(stdout);
+        let mut address_tx = Some(address_tx);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match stdout.read_until(b'\n', &mut line) {
+                Ok(1..) => {
+                    if address_tx.is_some()
+                        && let Some(address) = listening_address(&String::from_utf8_lossy(&line))
+                        && let Some(address_tx) = address_tx.take()
+                    {
+                        let _ = address_tx.send(address);
+                    } else {
+                        sink.write(OutputStream::Stdout, &line);
+                    }
+                }
+                _ => return,
+            }
+        }
+    });
+}
+
+fn read_stderr(stderr: ChildStderr, sink: OutputSink) {
+    std::thread::spawn(move || {
+        let mut stderr = BufReader::new(stderr);
+        let mut line = Vec::new();
+        while let Ok(1..) = stderr.read_until(b'\n', &mut line) {
+            sink.write(OutputStream::Stderr, &line);
+            line.clear();
+        }
+    });
+}
+
+impl Debugger {
+    /// Starts a VM with `command_line`, e.g. `["java", "-cp", "app.jar", "com.example.Main"]`,
+    /// and attaches to it. [LAUNCH_AGENT_OPTION] is inserted right after the program, so the
+    /// VM starts suspended and [Debugger::continue_] starts the program.
+    ///
+    /// The VM's stdout is read to find the port its agent listens on, and then passed on to the
+    /// stdout of this process. Its stderr is inherited.
+    pub async fn launch<I, S>(command_line: I) -> result::Result<(Debugger, LaunchedVm)>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        launch(command_line, false).await
+    }
+
+    /// Like [Debugger::launch], but the stdout and stderr of the VM are captured line by line
+    /// and read with [LaunchedVm::next_output]
+    pub async fn launch_capturing_output<I, S>(
+        command_line: I,
+    ) -> result::Result<(Debugger, LaunchedVm)>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        launch(command_line, true).await
+    }
+}
+
+async fn launch<I, S>(command_line: I, capture: bool) -> result::Result<(Debugger, LaunchedVm)>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let mut command_line = command_line.into_iter();
+    let program = command_line
+        .next()
+        .ok_or_else(|| result::Error::InvalidArgument {
+            message: String::from("Empty command line"),
+        })?;
+    let mut child = Command::new(program)
+        .arg(LAUNCH_AGENT_OPTION)
+        .args(command_line)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(if capture {
+            Stdio::piped()
+        } else {
+            Stdio::inherit()
+        })
+        .spawn()?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| io::Error::other("VM started without stdout"))?;
+    let (address_tx, address_rx) = oneshot::channel();
+    let output = if capture {
+        let (output_tx, output) = mpsc::unbounded_channel();
+        if let Some(stderr) = child.stderr.take() {
+            read_stderr(stderr, OutputSink::Capture(output_tx.clone()));
+        }
+        read_stdout(stdout, OutputSink::Capture(output_tx), address_tx);
+        Some(output)
+    } else {
+        read_stdout(stdout, OutputSink::Inherit, address_tx);
+        None
+    };
+    let mut vm = LaunchedVm {
+        child,
+        address: String::new(),
+        output,
+    };
+
+    vm.address = match timeout(LAUNCH_TIMEOUT, address_rx).await {
+        Ok(Ok(address)) => address,
+        Ok(Err(_)) => {
+            return Err(result::Error::IoError(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "VM exited before listening for a debugger",
+            )));
+        }
+        Err(_) => {
+            return Err(result::Error::IoError(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "VM didn't start listening for a debugger",
+            )));
+        }
+    };
+    let client = Transport::tcp(vm.address.as_str()).connect().await?;
+    Ok((Debugger::new(client), vm))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listening_address() {
+        assert_eq!(
+            listening_address("Listening for transport dt_socket at address: 34567\n").unwrap(),
+            "localhost:34567"
+        );
+        assert_eq!(
+            listening_address("Listening for transport dt_socket at address: 127.0.0.1:5005")
+                .unwrap(),
+            "127.0.0.1:5005"
+        );
+        assert_eq!(listening_address("Hello world"), None);
+    }
+}
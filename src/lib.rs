@@ -1,12 +1,240 @@
+#[cfg(feature = "tokio")]
+mod allocations;
+#[cfg(feature = "tokio")]
+mod art;
+#[cfg(feature = "tokio")]
+mod audit;
+#[cfg(feature = "tokio")]
+mod breakpoint;
+#[cfg(feature = "tokio")]
+mod class_cache;
+#[cfg(feature = "tokio")]
 mod client;
+mod codec;
+#[cfg(feature = "tokio")]
+mod command_stats;
 mod commands;
-mod consts;
+#[cfg(feature = "compression")]
+mod compression;
+mod conformance;
+#[cfg(feature = "tokio")]
+mod connection;
+pub mod consts;
+#[cfg(feature = "tokio")]
+mod convert;
+#[cfg(feature = "tokio")]
+mod debugger;
+#[cfg(feature = "tokio")]
+mod discover;
+#[cfg(feature = "tokio")]
+mod driver;
+#[cfg(feature = "tokio")]
+mod epoch;
+#[cfg(feature = "tokio")]
+mod event_bus;
+#[cfg(feature = "tokio")]
+mod event_channel;
+#[cfg(feature = "tokio")]
+mod event_queue;
+mod event_request;
+mod events;
+#[cfg(feature = "tokio")]
+mod exception_stats;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(feature = "tokio")]
+mod field_watch;
+#[cfg(feature = "tokio")]
+mod fields;
+#[cfg(feature = "tokio")]
+mod frames;
+#[cfg(feature = "tokio")]
+mod freeze;
+#[cfg(feature = "tokio")]
+mod heap;
+#[cfg(feature = "tokio")]
+mod hooks;
+#[cfg(feature = "tokio")]
+mod info;
+#[cfg(feature = "tokio")]
+mod introspect;
+#[cfg(feature = "tokio")]
+mod invoke;
+#[cfg(feature = "rpc")]
+mod json;
+#[cfg(feature = "tokio")]
+mod launch;
+#[cfg(feature = "tokio")]
+mod mock;
+#[cfg(feature = "tokio")]
+mod mux;
+#[cfg(feature = "tokio")]
+mod objects;
+mod pattern;
+#[cfg(feature = "tokio")]
+mod priority;
+#[cfg(feature = "tokio")]
+mod proxy;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "tokio")]
+mod quirks;
+#[cfg(feature = "tokio")]
+mod rate_limit;
+#[cfg(feature = "tokio")]
+mod read_only;
+#[cfg(feature = "tokio")]
+mod reply_body;
 mod result;
+#[cfg(feature = "tokio")]
+mod retention;
+mod retry;
+#[cfg(feature = "rpc")]
+mod rpc;
+#[cfg(feature = "tokio")]
+mod scenario;
+#[cfg(feature = "tokio")]
+mod scope;
+#[cfg(feature = "scripting")]
+mod script;
+#[cfg(feature = "tokio")]
+mod session;
+// Only usable on Windows, but the framing and ring buffers are tested everywhere
+#[cfg(all(feature = "shmem", any(windows, test)))]
+mod shmem;
+mod signature;
+mod smap;
+#[cfg(feature = "tokio")]
+mod source_map;
+#[cfg(feature = "tokio")]
+mod suspension;
+#[cfg(feature = "tokio")]
+mod system;
+#[cfg(test)]
+mod test_vectors;
+#[cfg(feature = "tokio")]
+mod thread_groups;
+#[cfg(feature = "tokio")]
+mod thread_picker;
+#[cfg(feature = "tokio")]
+mod tracer;
+#[cfg(feature = "tokio")]
+mod transport;
 mod types;
+#[cfg(feature = "tokio")]
+mod unsolicited;
 mod utils;
+#[cfg(feature = "tokio")]
+mod value_reader;
+#[cfg(feature = "websocket")]
+mod websocket;
 
+#[cfg(feature = "tokio")]
+pub use allocations::*;
+#[cfg(feature = "tokio")]
+pub use art::*;
+#[cfg(feature = "tokio")]
+pub use audit::*;
+#[cfg(feature = "tokio")]
+pub use breakpoint::*;
+#[cfg(feature = "tokio")]
+pub use class_cache::*;
+#[cfg(feature = "tokio")]
 pub use client::*;
+pub use codec::*;
+#[cfg(feature = "tokio")]
+pub use command_stats::*;
 pub use commands::*;
+#[cfg(feature = "compression")]
+pub use compression::CompressionRelay;
+pub use conformance::Conformance;
 pub use consts::*;
+#[cfg(feature = "tokio")]
+pub use convert::*;
+#[cfg(feature = "tokio")]
+pub use debugger::*;
+#[cfg(feature = "tokio")]
+pub use discover::*;
+#[cfg(feature = "tokio")]
+pub use driver::*;
+#[cfg(feature = "tokio")]
+pub use epoch::*;
+#[cfg(feature = "tokio")]
+pub use event_bus::*;
+#[cfg(feature = "tokio")]
+pub use event_queue::*;
+pub use event_request::*;
+pub use events::*;
+#[cfg(feature = "tokio")]
+pub use exception_stats::*;
+#[cfg(feature = "ffi")]
+pub use ffi::*;
+#[cfg(feature = "tokio")]
+pub use field_watch::*;
+#[cfg(feature = "tokio")]
+pub use frames::*;
+#[cfg(feature = "tokio")]
+pub use freeze::*;
+#[cfg(feature = "tokio")]
+pub use heap::*;
+#[cfg(feature = "tokio")]
+pub use hooks::*;
+#[cfg(feature = "tokio")]
+pub use info::*;
+#[cfg(feature = "tokio")]
+pub use introspect::*;
+#[cfg(feature = "tokio")]
+pub use invoke::*;
+#[cfg(feature = "tokio")]
+pub use launch::*;
+#[cfg(feature = "tokio")]
+pub use mock::*;
+#[cfg(feature = "tokio")]
+pub use mux::*;
+pub use pattern::*;
+#[cfg(feature = "tokio")]
+pub use priority::*;
+#[cfg(feature = "tokio")]
+pub use proxy::*;
+#[cfg(feature = "tokio")]
+pub use quirks::*;
+#[cfg(feature = "tokio")]
+pub use rate_limit::*;
+#[cfg(feature = "tokio")]
+pub use read_only::*;
+#[cfg(feature = "tokio")]
+pub use reply_body::*;
 pub use result::*;
+#[cfg(feature = "tokio")]
+pub use retention::*;
+pub use retry::*;
+#[cfg(feature = "rpc")]
+pub use rpc::RpcServer;
+#[cfg(feature = "tokio")]
+pub use scenario::*;
+#[cfg(feature = "tokio")]
+pub use scope::*;
+#[cfg(feature = "scripting")]
+pub use script::*;
+#[cfg(feature = "tokio")]
+pub use session::*;
+pub use signature::*;
+pub use smap::*;
+#[cfg(feature = "tokio")]
+pub use source_map::*;
+#[cfg(feature = "tokio")]
+pub use thread_groups::*;
+#[cfg(feature = "tokio")]
+pub use thread_picker::*;
+#[cfg(feature = "tokio")]
+pub use tracer::*;
+#[cfg(feature = "tokio")]
+pub use transport::*;
 pub use types::*;
+#[cfg(feature = "tokio")]
+pub use unsolicited::*;
+pub use utils::*;
+#[cfg(feature = "tokio")]
+pub use value_reader::*;
+#[cfg(feature = "websocket")]
+pub use websocket::WebSocketRelay;
@@ -0,0 +1,609 @@
+use binrw::BinWrite;
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, Cursor};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{Instant, Sleep};
+
+use crate::{
+    Command, CommandPacket, EventComposite, IncomingPacket, JdwpErrorCode, JdwpIdSizes,
+    PacketDecoder, encode_command, encode_reply,
+};
+
+const HANDSHAKE: &[u8] = b"JDWP-Handshake";
+
+/// Packet ids of packets injected by the mock, far from those of the client
+const FIRST_INJECTED_PACKET_ID: u32 = 0x4000_0000;
+
+/// A difference between what the client sent to a [MockJdwpTransport] and what it expected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MockMismatch {
+    /// A command arrived while another one was expected, None once the script was done
+    UnexpectedCommand {
+        expected: Option<Command>,
+        actual: Command,
+    },
+    /// The expected command arrived with other data than expected
+    UnexpectedData {
+        command: Command,
+        expected: Vec<u8>,
+        actual: Vec<u8>,
+    },
+    /// The client wrote something that isn't a handshake or a packet
+    InvalidPacket(String),
+}
+impl fmt::Display for MockMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MockMismatch::UnexpectedCommand {
+                expected: Some(expected),
+                actual,
+            } => write!(f, "Expected {:?}, got {:?}", expected, actual),
+            MockMismatch::UnexpectedCommand {
+                expected: None,
+                actual,
+            } => write!(f, "Expected no more commands, got {:?}", actual),
+            MockMismatch::UnexpectedData {
+                command,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Expected {:?} with data {:x?}, got {:x?}",
+                command, expected, actual
+            ),
+            MockMismatch::InvalidPacket(message) => write!(f, "Invalid packet: {}", message),
+        }
+    }
+}
+
+struct ScriptedReply {
+    error_code: u16,
+    data: Vec<u8>,
+    /// Length written to the header instead of the actual one
+    length: Option<u32>,
+}
+
+/// Bytes which the client can read from `ready_at` on
+struct ReadChunk {
+    ready_at: Instant,
+    bytes: Vec<u8>,
+}
+
+/// Answers the commands no expectation of a [MockJdwpTransport] waits for, e.g. from a model
+/// of a VM as [crate::MockScenario] does
+pub(crate) trait MockResponder: Send {
+    /// What the VM sends in response to the command, None if it's unexpected
+    fn respond(&mut self, packet: &CommandPacket) -> Option<MockResponse>;
+}
+
+pub(crate) struct MockResponse {
+    /// Encoded packets, usually the reply followed by any events the command caused
+    pub(crate) packets: Vec<Vec<u8>>,
+    /// Closes the stream after the packets, e.g. after VM_DEATH
+    pub(crate) close: bool,
+}
+
+enum Step {
+    Expect {
+        command: Command,
+        data: Option<Vec<u8>>,
+        reply: Option<ScriptedReply>,
+    },
+    Send(Vec<u8>),
+    Close,
+}
+
+struct MockState {
+    steps: VecDeque<Step>,
+    sizes: JdwpIdSizes,
+    handshake: Vec<u8>,
+    id_sizes_answered: bool,
+    decoder: PacketDecoder,
+    read_data: VecDeque<ReadChunk>,
+    read_waker: Option<Waker>,
+    read_closed: bool,
+    mismatches: Vec<MockMismatch>,
+    next_packet_id: u32,
+    latency: Duration,
+    max_write: Option<usize>,
+    /// Bytes the client can read before the connection drops, None for no limit
+    read_limit: Option<usize>,
+    dropped: bool,
+    responder: Option<Box<dyn MockResponder>>,
+}
+impl MockState {
+    fn queue(&mut self, bytes: &[u8]) {
+        self.read_data.push_back(ReadChunk {
+            ready_at: Instant::now() + self.latency,
+            bytes: bytes.to_vec(),
+        });
+        if let Some(waker) = self.read_waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Sends the packets and closes the stream as scripted up to the next expectation
+    fn advance(&mut self) {
+        if !self.id_sizes_answered {
+            return;
+        }
+        loop {
+            match self.steps.front() {
+                Some(Step::Send(_)) => {
+                    if let Some(Step::Send(bytes)) = self.steps.pop_front() {
+                        self.queue(&bytes);
+                    }
+                }
+                Some(Step::Close) => {
+                    self.steps.pop_front();
+                    self.read_closed = true;
+                    if let Some(waker) = self.read_waker.take() {
+                        waker.wake();
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn reply(&mut self, id: u32, reply: &ScriptedReply) -> Result<(), MockMismatch> {
+        let mut bytes = encode_reply(id, reply.error_code, &reply.data)
+            .map_err(|e| MockMismatch::InvalidPacket(format!("{:?}", e)))?;
+        if let Some(length) = reply.length {
+            bytes[..4].copy_from_slice(&length.to_be_bytes());
+        }
+        self.queue(&bytes);
+        Ok(())
+    }
+
+    fn handle_command(&mut self, packet: CommandPacket) -> Result<(), MockMismatch> {
+        let id = packet.header.id;
+        let actual = packet.header.command;
+        if !self.id_sizes_answered {
+            self.id_sizes_answered = true;
+            if actual == Command::VirtualMachineIDSizes {
+                let sizes: Vec<u8> = [
+                    self.sizes.field_id_size,
+                    self.sizes.method_id_size,
+                    self.sizes.object_id_size,
+                    self.sizes.reference_type_id_size,
+                    self.sizes.frame_id_size,
+                ]
+                .iter()
+                .flat_map(|size| (*size as i32).to_be_bytes())
+                .collect();
+                self.reply(
+                    id,
+                    &ScriptedReply {
+                        error_code: 0,
+                        data: sizes,
+                        length: None,
+                    },
+                )?;
+                self.advance();
+                return Ok(());
+            }
+        }
+
+        let expected = match self.steps.front() {
+            Some(Step::Expect { command, .. }) => Some(*command),
+            _ => None,
+        };
+        if expected != Some(actual) {
+            let response = self
+                .responder
+                .as_mut()
+                .and_then(|responder| responder.respond(&packet));
+            let Some(response) = response else {
+                return Err(MockMismatch::UnexpectedCommand { expected, actual });
+            };
+            for bytes in response.packets {
+                self.queue(&bytes);
+            }
+            if response.close {
+                self.steps.push_front(Step::Close);
+            }
+            self.advance();
+            return Ok(());
+        }
+        if let Some(Step::Expect {
+            data: Some(expected),
+            ..
+        }) = self.steps.front()
+            && *expected != packet.data
+        {
+            return Err(MockMismatch::UnexpectedData {
+                command: actual,
+                expected: expected.clone(),
+                actual: packet.data,
+            });
+        }
+
+        if let Some(Step::Expect {
+            reply: Some(reply), ..
+        }) = self.steps.pop_front()
+        {
+            self.reply(id, &reply)?;
+        }
+        self.advance();
+        Ok(())
+    }
+
+    fn receive(&mut self, mut bytes: &[u8]) -> Result<(), MockMismatch> {
+        if self.handshake.len() < HANDSHAKE.len() {
+            let length = bytes.len().min(HANDSHAKE.len() - self.handshake.len());
+            self.handshake.extend_from_slice(&bytes[..length]);
+            bytes = &bytes[length..];
+            if self.handshake.len() == HANDSHAKE.len() {
+                if self.handshake != HANDSHAKE {
+                    return Err(MockMismatch::InvalidPacket(format!(
+                        "Expected the handshake, got {:x?}",
+                        self.handshake
+                    )));
+                }
+                self.queue(HANDSHAKE);
+            }
+        }
+
+        self.decoder.push(bytes);
+        loop {
+            match self.decoder.next_packet() {
+                Ok(Some(IncomingPacket::Command(packet))) => self.handle_command(packet)?,
+                // The client never replies, and unknown commands can't be expected
+                Ok(Some(packet)) => {
+                    return Err(MockMismatch::InvalidPacket(format!(
+                        "Unexpected packet {:?}",
+                        packet
+                    )));
+                }
+                Ok(None) => return Ok(()),
+                Err(e) => return Err(MockMismatch::InvalidPacket(format!("{:?}", e))),
+            }
+        }
+    }
+}
+
+fn lock(state: &Mutex<MockState>) -> MutexGuard<'_, MockState> {
+    match state.lock() {
+        Ok(state) => state,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// A scripted VM to connect a client to, for tests of the packets exchanged
+///
+/// The script is a sequence of expected commands, each with its reply, and of packets the VM
+/// sends on its own, e.g. events. Commands must arrive in the order they are expected in;
+/// anything else fails the write with [io::ErrorKind::InvalidData] and is recorded as a
+/// [MockMismatch], to be checked through a [MockJdwpHandle]. Replies carry the id of the
+/// command they answer. The handshake and the VirtualMachine.IDSizes command sent on
+/// connecting are answered automatically; packets scripted before the first expectation are
+/// sent after them.
+///
+/// ```no_run
+/// # async fn example() -> jdwp_client::Result<()> {
+/// use jdwp_client::{Command, JdwpClient, MockJdwpTransport};
+///
+/// let transport = MockJdwpTransport::new()
+///     .expect_command(Command::VirtualMachineSuspend)
+///     .reply_with(&[]);
+/// let handle = transport.handle();
+/// let client = JdwpClient::new(transport).await?;
+/// client.vm_suspend().await?;
+/// handle.assert_done();
+/// # Ok(())
+/// # }
+/// ```
+pub struct MockJdwpTransport {
+    state: Arc<Mutex<MockState>>,
+    delay: Option<Pin<Box<Sleep>>>,
+}
+impl MockJdwpTransport {
+    /// A transport whose VM uses 8 byte ids
+    pub fn new() -> Self {
+        Self::with_id_sizes(JdwpIdSizes {
+            field_id_size: 8,
+            method_id_size: 8,
+            object_id_size: 8,
+            reference_type_id_size: 8,
+            frame_id_size: 8,
+        })
+    }
+
+    pub fn with_id_sizes(sizes: JdwpIdSizes) -> Self {
+        MockJdwpTransport {
+            state: Arc::new(Mutex::new(MockState {
+                steps: VecDeque::new(),
+                sizes,
+                handshake: Vec::new(),
+                id_sizes_answered: false,
+                decoder: PacketDecoder::new(),
+                read_data: VecDeque::new(),
+                read_waker: None,
+                read_closed: false,
+                mismatches: Vec::new(),
+                next_packet_id: FIRST_INJECTED_PACKET_ID,
+                latency: Duration::ZERO,
+                max_write: None,
+                read_limit: None,
+                dropped: false,
+                responder: None,
+            })),
+            delay: None,
+        }
+    }
+
+    /// Delays everything the VM sends, the handshake included, by `latency` after it was
+    /// triggered. Works with the paused clock of `#[tokio::test(start_paused = true)]`.
+    pub fn with_latency(self, latency: Duration) -> Self {
+        lock(&self.state).latency = latency;
+        self
+    }
+
+    /// Accepts at most `max` bytes per write, as a congested socket does
+    pub fn short_writes(self, max: usize) -> Self {
+        lock(&self.state).max_write = Some(max.max(1));
+        self
+    }
+
+    /// Drops the connection once the client read `bytes` bytes, the handshake included, e.g.
+    /// in the middle of a packet. Reads end and writes fail with [io::ErrorKind::BrokenPipe]
+    /// from then on.
+    pub fn drop_after_bytes(self, bytes: usize) -> Self {
+        lock(&self.state).read_limit = Some(bytes);
+        self
+    }
+
+    pub(crate) fn with_responder(self, responder: impl MockResponder + 'static) -> Self {
+        lock(&self.state).responder = Some(Box::new(responder));
+        self
+    }
+
+    /// Expects the client to send `command` next, once the previous steps are done
+    pub fn expect_command(self, command: Command) -> MockExpectation {
+        MockExpectation {
+            transport: self,
+            command,
+            data: None,
+        }
+    }
+
+    /// Sends an encoded packet once the previous steps are done
+    pub fn send_packet(self, bytes: &[u8]) -> Self {
+        lock(&self.state)
+            .steps
+            .push_back(Step::Send(bytes.to_vec()));
+        self
+    }
+
+    /// Sends a set of events once the previous steps are done
+    ///
+    /// # Panics
+    /// If the events can't be encoded with the id sizes of the transport
+    pub fn send_events(self, composite: &EventComposite) -> Self {
+        let bytes = {
+            let mut state = lock(&self.state);
+            let mut data = Vec::new();
+            composite
+                .write_be_args(&mut Cursor::new(&mut data), state.sizes)
+                .expect("Failed to encode events");
+            state.next_packet_id = state.next_packet_id.wrapping_add(1);
+            encode_command(state.next_packet_id, Command::EventComposite, &data)
+                .expect("Failed to encode events")
+        };
+        self.send_packet(&bytes)
+    }
+
+    /// Closes the stream once the previous steps are done and their packets were read
+    pub fn close(self) -> Self {
+        lock(&self.state).steps.push_back(Step::Close);
+        self
+    }
+
+    /// Handle to check the script after the transport was moved into a client
+    pub fn handle(&self) -> MockJdwpHandle {
+        MockJdwpHandle {
+            state: self.state.clone(),
+        }
+    }
+}
+impl Default for MockJdwpTransport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl fmt::Debug for MockJdwpTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockJdwpTransport").finish_non_exhaustive()
+    }
+}
+impl AsyncRead for MockJdwpTransport {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            let mut state = lock(&this.state);
+            if state.read_limit == Some(0) {
+                state.dropped = true;
+                return Poll::Ready(Ok(()));
+            }
+            let Some(chunk) = state.read_data.front_mut() else {
+                if state.read_closed {
+                    return Poll::Ready(Ok(()));
+                }
+                state.read_waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            };
+
+            if chunk.ready_at > Instant::now() {
+                let ready_at = chunk.ready_at;
+                drop(state);
+                let delay = this
+                    .delay
+                    .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(ready_at)));
+                delay.as_mut().reset(ready_at);
+                if delay.as_mut().poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                continue;
+            }
+
+            let mut length = buf.remaining().min(chunk.bytes.len());
+            if let Some(limit) = state.read_limit {
+                length = length.min(limit);
+            }
+            let chunk = state.read_data.front_mut().expect("Checked above");
+            buf.put_slice(&chunk.bytes[..length]);
+            chunk.bytes.drain(..length);
+            if chunk.bytes.is_empty() {
+                state.read_data.pop_front();
+            }
+            if let Some(limit) = &mut state.read_limit {
+                *limit -= length;
+            }
+            return Poll::Ready(Ok(()));
+        }
+    }
+}
+impl AsyncWrite for MockJdwpTransport {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut state = lock(&self.state);
+        if state.dropped {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "Connection dropped",
+            )));
+        }
+        let buf = &buf[..buf.len().min(state.max_write.unwrap_or(usize::MAX))];
+        match state.receive(buf) {
+            Ok(()) => Poll::Ready(Ok(buf.len())),
+            Err(mismatch) => {
+                let error = io::Error::new(io::ErrorKind::InvalidData, mismatch.to_string());
+                state.mismatches.push(mismatch);
+                Poll::Ready(Err(error))
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// A command expected by a [MockJdwpTransport], completed by choosing its reply
+#[derive(Debug)]
+pub struct MockExpectation {
+    transport: MockJdwpTransport,
+    command: Command,
+    data: Option<Vec<u8>>,
+}
+impl MockExpectation {
+    /// Expects exactly this data, any data is accepted otherwise
+    pub fn with_data(mut self, data: &[u8]) -> Self {
+        self.data = Some(data.to_vec());
+        self
+    }
+
+    /// Replies with the encoded data of a successful reply
+    pub fn reply_with(self, data: &[u8]) -> MockJdwpTransport {
+        self.push(Some(ScriptedReply {
+            error_code: 0,
+            data: data.to_vec(),
+            length: None,
+        }))
+    }
+
+    pub fn reply_error(self, error: JdwpErrorCode) -> MockJdwpTransport {
+        self.push(Some(ScriptedReply {
+            error_code: error as u16,
+            data: Vec::new(),
+            length: None,
+        }))
+    }
+
+    /// Replies with `data` under a header claiming the packet is `length` bytes long, e.g. less
+    /// than a header to test the handling of corrupted headers
+    pub fn reply_with_length(self, data: &[u8], length: u32) -> MockJdwpTransport {
+        self.push(Some(ScriptedReply {
+            error_code: 0,
+            data: data.to_vec(),
+            length: Some(length),
+        }))
+    }
+
+    /// Leaves the command unanswered, e.g. to test timeouts
+    pub fn no_reply(self) -> MockJdwpTransport {
+        self.push(None)
+    }
+
+    fn push(self, reply: Option<ScriptedReply>) -> MockJdwpTransport {
+        lock(&self.transport.state).steps.push_back(Step::Expect {
+            command: self.command,
+            data: self.data,
+            reply,
+        });
+        self.transport
+    }
+}
+
+/// Checks the script of a [MockJdwpTransport] moved into a client
+#[derive(Debug, Clone)]
+pub struct MockJdwpHandle {
+    state: Arc<Mutex<MockState>>,
+}
+impl MockJdwpHandle {
+    /// Mismatches so far, in the order they happened
+    pub fn mismatches(&self) -> Vec<MockMismatch> {
+        lock(&self.state).mismatches.clone()
+    }
+
+    /// Number of expected commands which haven't arrived yet
+    pub fn remaining_expectations(&self) -> usize {
+        lock(&self.state)
+            .steps
+            .iter()
+            .filter(|step| matches!(step, Step::Expect { .. }))
+            .count()
+    }
+
+    /// Checks that every expected command arrived and nothing else did
+    ///
+    /// # Panics
+    /// With the mismatches and the number of missing commands otherwise
+    pub fn assert_done(&self) {
+        let mismatches = self.mismatches();
+        let remaining = self.remaining_expectations();
+        if !mismatches.is_empty() || remaining > 0 {
+            let mismatches: Vec<String> = mismatches.iter().map(|m| m.to_string()).collect();
+            panic!(
+                "Mock script not followed: {} expected commands missing, mismatches: [{}]",
+                remaining,
+                mismatches.join("; ")
+            );
+        }
+    }
+}
+impl fmt::Debug for MockState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockState")
+            .field("steps", &self.steps.len())
+            .field("mismatches", &self.mismatches)
+            .finish_non_exhaustive()
+    }
+}
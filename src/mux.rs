@@ -0,0 +1,419 @@
+use binrw::{BinRead, BinWrite};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Cursor};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::connection::{ReplyHook, read_packet, write_packet};
+use crate::{
+    Command, CommandPacket, Event, EventComposite, EventKind, EventRequestSetReply, IncomingPacket,
+    JdwpClient, JdwpErrorCode, SuspendPolicy, VariableLengthId, encode_command, encode_reply,
+    result,
+};
+
+const HANDSHAKE: &[u8; 14] = b"JDWP-Handshake";
+
+type DebuggerId = u64;
+
+/// Shares one VM connection between several debuggers
+///
+/// A VM accepts a single debugger. The server owns that connection and accepts any number of
+/// debuggers on its own socket. Their commands are sent with packet ids of the VM connection
+/// and each reply goes back to the debugger which asked, with the id it used. Events go to the
+/// debugger which created the event request that caused them; automatically generated events
+/// such as VM_DEATH go to every debugger.
+///
+/// Commands which would affect the other debuggers are arbitrated:
+/// - VirtualMachine.Dispose only disconnects the debugger which sent it. As when it closes the
+///   connection, its event requests are cleared and VirtualMachine.Suspend commands it didn't
+///   undo are resumed.
+/// - EventRequest.Clear and EventRequest.ClearAllBreakpoints only clear requests of the
+///   debugger which sent them.
+/// - An event set which suspended threads is suspended once more for every additional
+///   debugger it is delivered to, so each of them resumes it once. One delivered to no
+///   debugger is resumed.
+pub struct MuxServer {
+    client: JdwpClient,
+    listener: TcpListener,
+    state: Arc<MuxState>,
+}
+
+impl MuxServer {
+    /// Listens for debuggers on `address`, forwarding to the VM `client` is connected to
+    pub async fn bind(client: JdwpClient, address: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(MuxServer {
+            client,
+            listener: TcpListener::bind(address).await?,
+            state: Arc::new(MuxState::default()),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts debuggers and delivers events until the VM connection is closed, which also
+    /// disconnects every debugger
+    pub async fn run(self) -> result::Result<()> {
+        let mut events = tokio::spawn(dispatch_events(self.client.clone(), self.state.clone()));
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => {
+                    let (stream, _) = accepted?;
+                    tokio::spawn(serve_debugger(stream, self.client.clone(), self.state.clone()));
+                }
+                _ = &mut events => return Ok(()),
+            }
+        }
+    }
+}
+
+struct Debugger {
+    outgoing: mpsc::UnboundedSender<Vec<u8>>,
+    next_packet_id: u32,
+    /// VirtualMachine.Suspend commands not undone by VirtualMachine.Resume
+    vm_suspends: u32,
+}
+
+#[derive(Default)]
+struct MuxTables {
+    next_debugger_id: DebuggerId,
+    debuggers: HashMap<DebuggerId, Debugger>,
+    /// Owner and kind of each event request created by a debugger
+    requests: HashMap<i32, (DebuggerId, EventKind)>,
+}
+
+#[derive(Default)]
+struct MuxState {
+    tables: Mutex<MuxTables>,
+}
+impl MuxState {
+    fn tables(&self) -> std::sync::MutexGuard<'_, MuxTables> {
+        match self.tables.lock() {
+            Ok(tables) => tables,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn add_debugger(&self, outgoing: mpsc::UnboundedSender<Vec<u8>>) -> DebuggerId {
+        let mut tables = self.tables();
+        tables.next_debugger_id += 1;
+        let id = tables.next_debugger_id;
+        tables.debuggers.insert(
+            id,
+            Debugger {
+                outgoing,
+                next_packet_id: 0,
+                vm_suspends: 0,
+            },
+        );
+        id
+    }
+
+    /// Removes the debugger, returning its event requests and unmatched VM suspensions
+    fn remove_debugger(&self, id: DebuggerId) -> (Vec<(i32, EventKind)>, u32) {
+        let mut tables = self.tables();
+        let vm_suspends = tables
+            .debuggers
+            .remove(&id)
+            .map_or(0, |debugger| debugger.vm_suspends);
+        (take_requests(&mut tables, id, |_| true), vm_suspends)
+    }
+
+    /// Splits an event set by the debuggers its events belong to and encodes a packet for each
+    fn route(
+        &self,
+        composite: &EventComposite,
+        client: &JdwpClient,
+    ) -> Vec<(mpsc::UnboundedSender<Vec<u8>>, Vec<u8>)> {
+        let Ok(sizes) = client.id_sizes() else {
+            return Vec::new();
+        };
+        let mut tables = self.tables();
+        let mut split: BTreeMap<DebuggerId, Vec<Event>> = BTreeMap::new();
+        for event in &composite.events {
+            match event.request_id() {
+                0 => {
+                    for id in tables.debuggers.keys() {
+                        split.entry(*id).or_default().push(event.clone());
+                    }
+                }
+                request_id => {
+                    if let Some((owner, _)) = tables.requests.get(&request_id)
+                        && tables.debuggers.contains_key(owner)
+                    {
+                        split.entry(*owner).or_default().push(event.clone());
+                    }
+                }
+            }
+        }
+
+        let mut packets = Vec::new();
+        for (id, events) in split {
+            let Some(debugger) = tables.debuggers.get_mut(&id) else {
+                continue;
+            };
+            let mut data = Vec::new();
+            let encoded = EventComposite {
+                suspend_policy: composite.suspend_policy,
+                events,
+            }
+            .write_be_args(&mut Cursor::new(&mut data), sizes);
+            if let Err(e) = encoded {
+                eprintln!("Failed to encode events for debugger {}: {:?}", id, e);
+                continue;
+            }
+            debugger.next_packet_id = debugger.next_packet_id.wrapping_add(1);
+            match encode_command(debugger.next_packet_id, Command::EventComposite, &data) {
+                Ok(bytes) => packets.push((debugger.outgoing.clone(), bytes)),
+                Err(e) => eprintln!("Failed to encode events for debugger {}: {:?}", id, e),
+            }
+        }
+        packets
+    }
+
+    fn close(&self) {
+        self.tables().debuggers.clear();
+    }
+}
+
+/// Removes the debugger's requests which match `filter` from the table
+fn take_requests(
+    tables: &mut MuxTables,
+    id: DebuggerId,
+    filter: impl Fn(EventKind) -> bool,
+) -> Vec<(i32, EventKind)> {
+    let mut taken: Vec<_> = tables
+        .requests
+        .iter()
+        .filter(|(_, (owner, kind))| *owner == id && filter(*kind))
+        .map(|(request_id, (_, kind))| (*request_id, *kind))
+        .collect();
+    // Oldest first, as the VM hands out increasing request ids
+    taken.sort_unstable_by_key(|(request_id, _)| *request_id);
+    for (request_id, _) in &taken {
+        tables.requests.remove(request_id);
+    }
+    taken
+}
+
+async fn dispatch_events(client: JdwpClient, state: Arc<MuxState>) {
+    while let Some(composite) = client.next_event().await {
+        let packets = state.route(&composite, &client);
+
+        // The VM suspended once for the whole set, so the suspension is balanced before any
+        // debugger can resume it
+        let thread = composite.events.iter().find_map(Event::thread);
+        let adjustment = match packets.len() {
+            0 => suspension_adjustment(&client, composite.suspend_policy, thread, false).await,
+            count => {
+                let mut adjustment = Ok(());
+                for _ in 1..count {
+                    adjustment =
+                        suspension_adjustment(&client, composite.suspend_policy, thread, true)
+                            .await;
+                }
+                adjustment
+            }
+        };
+        if let Err(e) = adjustment {
+            eprintln!("Failed to balance event suspension: {:?}", e);
+        }
+
+        for (outgoing, bytes) in packets {
+            let _ = outgoing.send(bytes);
+        }
+    }
+    state.close();
+}
+
+async fn suspension_adjustment(
+    client: &JdwpClient,
+    policy: SuspendPolicy,
+    thread: Option<VariableLengthId>,
+    suspend: bool,
+) -> result::Result<()> {
+    match (policy, thread) {
+        (SuspendPolicy::All, _) if suspend => client.vm_suspend().await,
+        (SuspendPolicy::All, _) => client.vm_resume().await,
+        (SuspendPolicy::EventThread, Some(thread)) if suspend => {
+            client.thread_suspend(thread).await
+        }
+        (SuspendPolicy::EventThread, Some(thread)) => client.thread_resume(thread).await,
+        _ => Ok(()),
+    }
+}
+
+async fn accept_handshake(stream: &mut TcpStream) -> io::Result<()> {
+    let mut buffer = [0u8; 14];
+    stream.read_exact(&mut buffer).await?;
+    if &buffer != HANDSHAKE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid JDWP handshake",
+        ));
+    }
+    stream.write_all(HANDSHAKE).await?;
+    stream.flush().await
+}
+
+async fn serve_debugger(mut stream: TcpStream, client: JdwpClient, state: Arc<MuxState>) {
+    if let Err(e) = accept_handshake(&mut stream).await {
+        eprintln!("Debugger handshake failed: {:?}", e);
+        return;
+    }
+    let _ = stream.set_nodelay(true);
+    let (mut reader, mut writer) = stream.into_split();
+    let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<Vec<u8>>();
+    let id = state.add_debugger(outgoing.clone());
+
+    tokio::spawn(async move {
+        while let Some(bytes) = outgoing_rx.recv().await {
+            if write_packet(&mut writer, &bytes).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    loop {
+        match read_packet(&mut reader).await {
+            Ok(IncomingPacket::Command(command))
+                if command.header.command == Command::VirtualMachineDispose =>
+            {
+                send_reply(&outgoing, command.header.id, 0, &[]);
+                break;
+            }
+            Ok(IncomingPacket::Command(command)) => {
+                // The next command is only read once this one was written to the VM, so the
+                // debugger's commands reach the VM in order while their replies are awaited
+                // concurrently
+                let (written, written_rx) = oneshot::channel();
+                tokio::spawn(forward(
+                    command,
+                    id,
+                    client.clone(),
+                    state.clone(),
+                    outgoing.clone(),
+                    written,
+                ));
+                let _ = written_rx.await;
+            }
+            // Debuggers don't reply to anything the server sends
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+
+    let (requests, vm_suspends) = state.remove_debugger(id);
+    for (request_id, kind) in requests {
+        let _ = client.event_request_clear(kind, request_id).await;
+    }
+    for _ in 0..vm_suspends {
+        let _ = client.vm_resume().await;
+    }
+}
+
+fn send_reply(outgoing: &mpsc::UnboundedSender<Vec<u8>>, id: u32, error_code: u16, data: &[u8]) {
+    match encode_reply(id, error_code, data) {
+        Ok(bytes) => {
+            let _ = outgoing.send(bytes);
+        }
+        Err(e) => eprintln!("Failed to encode reply {}: {:?}", id, e),
+    }
+}
+
+/// Request id of an EventRequest.Clear command, which follows the event kind
+fn cleared_request_id(data: &[u8]) -> Option<i32> {
+    Some(i32::from_be_bytes(data.get(1..5)?.try_into().ok()?))
+}
+
+/// Forwards one command of a debugger to the VM and sends the reply back. `written` is
+/// signalled, or dropped, once the command was written or handled without the VM.
+async fn forward(
+    command: CommandPacket,
+    id: DebuggerId,
+    client: JdwpClient,
+    state: Arc<MuxState>,
+    outgoing: mpsc::UnboundedSender<Vec<u8>>,
+    written: oneshot::Sender<()>,
+) {
+    let CommandPacket { header, data } = command;
+    match header.command {
+        Command::EventRequestClear => {
+            let owned = cleared_request_id(&data).is_some_and(|request_id| {
+                matches!(state.tables().requests.get(&request_id), Some((owner, _)) if *owner == id)
+            });
+            // Requests of other debuggers are left alone, as if they didn't exist
+            if !owned {
+                send_reply(&outgoing, header.id, 0, &[]);
+                return;
+            }
+        }
+        Command::EventRequestClearAllBreakpoints => {
+            let breakpoints = take_requests(&mut state.tables(), id, |kind| {
+                kind == EventKind::Breakpoint
+            });
+            for (request_id, kind) in breakpoints {
+                let _ = client.event_request_clear(kind, request_id).await;
+            }
+            send_reply(&outgoing, header.id, 0, &[]);
+            return;
+        }
+        _ => {}
+    }
+
+    // Ownership of a new request is recorded by the reader task before any of its events can
+    // be dispatched
+    let on_success: Option<ReplyHook> = match header.command {
+        Command::EventRequestSet => {
+            let kind = EventKind::read_be(&mut Cursor::new(&data)).ok();
+            let state = state.clone();
+            kind.map(|kind| -> ReplyHook {
+                Arc::new(move |_, reply| {
+                    if let Ok(reply) = EventRequestSetReply::read_be(&mut Cursor::new(reply)) {
+                        state.tables().requests.insert(reply.request_id, (id, kind));
+                    }
+                })
+            })
+        }
+        _ => None,
+    };
+
+    let reply = match client
+        .send_raw_ordered(header.command, &data, on_success, written)
+        .await
+    {
+        Ok(reply) => reply,
+        Err(e) => {
+            eprintln!("Failed to forward {:?}: {:?}", header.command, e);
+            send_reply(&outgoing, header.id, JdwpErrorCode::Internal as u16, &[]);
+            return;
+        }
+    };
+
+    if reply.header.is_success() {
+        let mut tables = state.tables();
+        match header.command {
+            Command::EventRequestClear => {
+                if let Some(request_id) = cleared_request_id(&data) {
+                    tables.requests.remove(&request_id);
+                }
+            }
+            Command::VirtualMachineSuspend => {
+                if let Some(debugger) = tables.debuggers.get_mut(&id) {
+                    debugger.vm_suspends += 1;
+                }
+            }
+            Command::VirtualMachineResume => {
+                if let Some(debugger) = tables.debuggers.get_mut(&id) {
+                    debugger.vm_suspends = debugger.vm_suspends.saturating_sub(1);
+                }
+            }
+            _ => {}
+        }
+    }
+    send_reply(&outgoing, header.id, reply.header.error_code, &reply.data);
+}
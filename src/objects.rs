@@ -0,0 +1,160 @@
+use std::collections::HashSet;
+
+use crate::{
+    JdwpClient, ReflectedTypeReply, Tag, TaggedObjectId, TryFromJdwpValue, TypeTag,
+    VariableLengthId, result, type_name_to_signature,
+};
+
+/// Signatures every array is an instance of, besides its own
+const ARRAY_SUPERTYPES: [&str; 3] = [
+    "Ljava/lang/Object;",
+    "Ljava/lang/Cloneable;",
+    "Ljava/io/Serializable;",
+];
+
+impl TaggedObjectId {
+    /// Whether both refer to the same object (`==` in Java). Tags are ignored, so a string
+    /// tagged as an object is still the same string.
+    pub fn is_same_object(&self, other: &TaggedObjectId) -> bool {
+        self.object_id == other.object_id
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.object_id.value == 0
+    }
+}
+
+impl JdwpClient {
+    /// Invokes `a.equals(b)` in `thread_id`, which must be suspended by an event. A null `a`
+    /// only equals a null `b`.
+    pub async fn object_equals(
+        &self,
+        thread_id: VariableLengthId,
+        a: TaggedObjectId,
+        b: TaggedObjectId,
+    ) -> result::Result<bool> {
+        if a.is_null() {
+            return Ok(b.is_null());
+        }
+        let value = self.invoke_method(thread_id, a, "equals", (b,)).await?;
+        bool::try_from_jdwp_value(value, self).await
+    }
+
+    /// Invokes `object.hashCode()` in `thread_id`, which must be suspended by an event
+    pub async fn object_hash_code(
+        &self,
+        thread_id: VariableLengthId,
+        object: TaggedObjectId,
+    ) -> result::Result<i32> {
+        let value = self
+            .invoke_method(thread_id, object, "hashCode", ())
+            .await?;
+        i32::try_from_jdwp_value(value, self).await
+    }
+
+    /// The class object of a type, tagged as such so it can be passed on as a value, e.g. as
+    /// an argument of type `Class<?>`
+    pub async fn class_object(
+        &self,
+        ref_type_id: VariableLengthId,
+    ) -> result::Result<TaggedObjectId> {
+        Ok(TaggedObjectId {
+            tag: Tag::ClassObject,
+            object_id: self
+                .ref_type_get_class_object(ref_type_id)
+                .await?
+                .class_object,
+        })
+    }
+
+    /// The value of the expression `Foo.class` for a type name such as `com.example.Foo` or
+    /// `int[]`. Fails with [result::Error::ClassNotFound] if the type isn't loaded; primitive
+    /// types such as `int` have no loaded class of their own.
+    pub async fn class_literal(&self, type_name: &str) -> result::Result<TaggedObjectId> {
+        let ref_type_id = self
+            .find_loaded_class(&type_name_to_signature(type_name))
+            .await?;
+        self.class_object(ref_type_id).await
+    }
+
+    /// The type a class object represents, the inverse of [JdwpClient::class_object]. Fails
+    /// with [result::Error::TypeMismatch] for objects which aren't tagged as class objects.
+    pub async fn reflected_type(
+        &self,
+        class_object: TaggedObjectId,
+    ) -> result::Result<ReflectedTypeReply> {
+        if class_object.tag != Tag::ClassObject {
+            return Err(result::Error::TypeMismatch {
+                expected: "java.lang.Class",
+                found: class_object.tag,
+            });
+        }
+        self.class_object_get_reflected_type(class_object.object_id)
+            .await
+    }
+
+    /// Whether `object` is an instance of the class or interface with the JNI signature
+    /// `class_signature` (`instanceof` in Java), checked by walking the superclasses and
+    /// interfaces of its type without invoking anything. Null is an instance of nothing.
+    ///
+    /// Arrays are instances of their own type, Object, Cloneable and Serializable; covariance
+    /// of reference arrays (`String[]` as `Object[]`) isn't considered.
+    pub async fn instance_of(
+        &self,
+        object: TaggedObjectId,
+        class_signature: &str,
+    ) -> result::Result<bool> {
+        if object.is_null() {
+            return Ok(false);
+        }
+        let object_type = self.object_get_reference_type(object.object_id).await?;
+        let signature = self
+            .ref_type_get_signature(object_type.type_id)
+            .await?
+            .signature
+            .string;
+        if signature.starts_with('[') {
+            return Ok(signature == class_signature || ARRAY_SUPERTYPES.contains(&class_signature));
+        }
+        if signature == class_signature || class_signature == ARRAY_SUPERTYPES[0] {
+            return Ok(true);
+        }
+
+        let is_interface = object_type.ref_type_tag == TypeTag::Interface;
+        let mut pending = self.supertypes(object_type.type_id, is_interface).await?;
+        let mut seen = HashSet::new();
+        while let Some((type_id, is_interface)) = pending.pop() {
+            if !seen.insert(type_id) {
+                continue;
+            }
+            if self.ref_type_get_signature(type_id).await?.signature.string == class_signature {
+                return Ok(true);
+            }
+            pending.extend(self.supertypes(type_id, is_interface).await?);
+        }
+        Ok(false)
+    }
+
+    /// Direct superclass and interfaces of a type, each with whether it's an interface
+    async fn supertypes(
+        &self,
+        type_id: VariableLengthId,
+        is_interface: bool,
+    ) -> result::Result<Vec<(VariableLengthId, bool)>> {
+        let mut supertypes: Vec<_> = self
+            .ref_type_get_interfaces(type_id)
+            .await?
+            .interfaces
+            .into_iter()
+            .map(|interface| (interface, true))
+            .collect();
+        // Interfaces have no superclass
+        if !is_interface {
+            let superclass = self.class_type_get_superclass(type_id).await?.superclass;
+            if superclass.value != 0 {
+                supertypes.push((superclass, false));
+            }
+        }
+        Ok(supertypes)
+    }
+}
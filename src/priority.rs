@@ -0,0 +1,85 @@
+use std::collections::VecDeque;
+
+use crate::connection::OutgoingPacket;
+
+/// Order in which the writer task sends commands queued at the same time, see
+/// [crate::JdwpClient::set_command_priority]. Commands of the same priority are sent in the
+/// order they were queued.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CommandPriority {
+    /// Bulk fetches, e.g. of a sampler or a heap search
+    Background,
+    #[default]
+    Normal,
+    /// Operations a user is waiting for, e.g. stepping or resuming
+    Interactive,
+}
+impl CommandPriority {
+    const ALL: [CommandPriority; 3] = [
+        CommandPriority::Interactive,
+        CommandPriority::Normal,
+        CommandPriority::Background,
+    ];
+}
+
+/// Packets waiting for the writer task, one queue per priority
+#[derive(Default)]
+pub(crate) struct OutgoingQueue {
+    interactive: VecDeque<OutgoingPacket>,
+    normal: VecDeque<OutgoingPacket>,
+    background: VecDeque<OutgoingPacket>,
+}
+impl OutgoingQueue {
+    pub(crate) fn push(&mut self, packet: OutgoingPacket) {
+        self.queue(packet.priority).push_back(packet);
+    }
+
+    /// The oldest packet of the highest priority
+    pub(crate) fn pop(&mut self) -> Option<OutgoingPacket> {
+        CommandPriority::ALL
+            .into_iter()
+            .find_map(|priority| self.queue(priority).pop_front())
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.interactive.is_empty() && self.normal.is_empty() && self.background.is_empty()
+    }
+
+    fn queue(&mut self, priority: CommandPriority) -> &mut VecDeque<OutgoingPacket> {
+        match priority {
+            CommandPriority::Interactive => &mut self.interactive,
+            CommandPriority::Normal => &mut self.normal,
+            CommandPriority::Background => &mut self.background,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(id: u8, priority: CommandPriority) -> OutgoingPacket {
+        OutgoingPacket {
+            bytes: vec![id],
+            written: None,
+            priority,
+        }
+    }
+
+    #[test]
+    fn test_pop_order() {
+        let mut queue = OutgoingQueue::default();
+        queue.push(packet(1, CommandPriority::Background));
+        queue.push(packet(2, CommandPriority::Normal));
+        queue.push(packet(3, CommandPriority::Background));
+        queue.push(packet(4, CommandPriority::Interactive));
+        queue.push(packet(5, CommandPriority::Normal));
+
+        let mut order = Vec::new();
+        while let Some(packet) = queue.pop() {
+            order.push(packet.bytes[0]);
+        }
+        assert_eq!(order, vec![4, 2, 5, 1, 3]);
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+use std::io;
+use std::net::IpAddr;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Proxy the TCP connection to the VM is made through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProxyConfig {
+    /// SOCKS5 proxy at `address`, optionally with username/password authentication
+    Socks5 {
+        address: String,
+        credentials: Option<ProxyCredentials>,
+    },
+    /// HTTP proxy at `address` which supports the CONNECT method, optionally with basic
+    /// authentication
+    HttpConnect {
+        address: String,
+        credentials: Option<ProxyCredentials>,
+    },
+}
+impl ProxyConfig {
+    pub fn socks5(address: impl Into<String>) -> Self {
+        ProxyConfig::Socks5 {
+            address: address.into(),
+            credentials: None,
+        }
+    }
+
+    pub fn http_connect(address: impl Into<String>) -> Self {
+        ProxyConfig::HttpConnect {
+            address: address.into(),
+            credentials: None,
+        }
+    }
+
+    pub fn with_credentials(mut self, username: &str, password: &str) -> Self {
+        let new_credentials = Some(ProxyCredentials {
+            username: String::from(username),
+            password: String::from(password),
+        });
+        match &mut self {
+            ProxyConfig::Socks5 { credentials, .. }
+            | ProxyConfig::HttpConnect { credentials, .. } => *credentials = new_credentials,
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+/// Opens a tunnel to `target` ("host:port") through the proxy
+pub(crate) async fn connect(proxy: &ProxyConfig, target: &str) -> io::Result<TcpStream> {
+    let (host, port) = split_host_port(target)?;
+    match proxy {
+        ProxyConfig::Socks5 {
+            address,
+            credentials,
+        } => {
+            let mut stream = TcpStream::connect(address.as_str()).await?;
+            socks5_connect(&mut stream, host, port, credentials.as_ref()).await?;
+            Ok(stream)
+        }
+        ProxyConfig::HttpConnect {
+            address,
+            credentials,
+        } => {
+            let mut stream = TcpStream::connect(address.as_str()).await?;
+            http_connect(&mut stream, target, credentials.as_ref()).await?;
+            Ok(stream)
+        }
+    }
+}
+
+fn split_host_port(target: &str) -> io::Result<(&str, u16)> {
+    let invalid = || {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Expected host:port, got {}", target),
+        )
+    };
+    let (host, port) = target.rsplit_once(':').ok_or_else(invalid)?;
+    let host = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host);
+    Ok((host, port.parse().map_err(|_| invalid())?))
+}
+
+fn proxy_error(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionRefused, message)
+}
+
+async fn socks5_connect(
+    stream: &mut TcpStream,
+    host: &str,
+    port: u16,
+    credentials: Option<&ProxyCredentials>,
+) -> io::Result<()> {
+    const VERSION: u8 = 5;
+    const NO_AUTHENTICATION: u8 = 0;
+    const USERNAME_PASSWORD: u8 = 2;
+
+    // Greeting with the offered authentication methods
+    let method = match credentials {
+        Some(_) => USERNAME_PASSWORD,
+        None => NO_AUTHENTICATION,
+    };
+    stream.write_all(&[VERSION, 1, method]).await?;
+    let mut choice = [0u8; 2];
+    stream.read_exact(&mut choice).await?;
+    if choice != [VERSION, method] {
+        return Err(proxy_error(String::from(
+            "SOCKS5 proxy rejected the authentication method",
+        )));
+    }
+
+    if let Some(credentials) = credentials {
+        let mut request = vec![1];
+        for field in [&credentials.username, &credentials.password] {
+            let length = u8::try_from(field.len()).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "SOCKS5 credentials too long")
+            })?;
+            request.push(length);
+            request.extend_from_slice(field.as_bytes());
+        }
+        stream.write_all(&request).await?;
+        let mut status = [0u8; 2];
+        stream.read_exact(&mut status).await?;
+        if status[1] != 0 {
+            return Err(proxy_error(String::from(
+                "SOCKS5 proxy rejected the credentials",
+            )));
+        }
+    }
+
+    // CONNECT with the target as an address or a domain name
+    let mut request = vec![VERSION, 1, 0];
+    match host.parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(1);
+            request.extend_from_slice(&ip.octets());
+        }
+        Ok(IpAddr::V6(ip)) => {
+            request.push(4);
+            request.extend_from_slice(&ip.octets());
+        }
+        Err(_) => {
+            let length = u8::try_from(host.len()).map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidInput, "Host name too long for SOCKS5")
+            })?;
+            request.extend_from_slice(&[3, length]);
+            request.extend_from_slice(host.as_bytes());
+        }
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[1] != 0 {
+        return Err(proxy_error(format!(
+            "SOCKS5 proxy failed to connect with reply code {}",
+            reply[1]
+        )));
+    }
+    // Skip the bound address and port
+    let address_length = match reply[3] {
+        1 => 4,
+        4 => 16,
+        3 => stream.read_u8().await? as usize,
+        other => {
+            return Err(proxy_error(format!(
+                "SOCKS5 proxy replied with address type {}",
+                other
+            )));
+        }
+    };
+    let mut bound = vec![0u8; address_length + 2];
+    stream.read_exact(&mut bound).await?;
+    Ok(())
+}
+
+async fn http_connect(
+    stream: &mut TcpStream,
+    target: &str,
+    credentials: Option<&ProxyCredentials>,
+) -> io::Result<()> {
+    const MAX_RESPONSE_LENGTH: usize = 8 * 1024;
+
+    let mut request = format!("CONNECT {target} HTTP/1.1\r\nHost: {target}\r\n");
+    if let Some(credentials) = credentials {
+        let token = base64(format!("{}:{}", credentials.username, credentials.password).as_bytes());
+        request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", token));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+
+    // Read byte by byte so nothing the VM sends after the response is consumed
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() >= MAX_RESPONSE_LENGTH {
+            return Err(proxy_error(String::from("HTTP proxy response too long")));
+        }
+        response.push(stream.read_u8().await?);
+    }
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(()),
+        _ => Err(proxy_error(format!(
+            "HTTP proxy refused CONNECT: {}",
+            status_line
+        ))),
+    }
+}
+
+pub(crate) fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"user:pass"), "dXNlcjpwYXNz");
+    }
+
+    #[test]
+    fn test_split_host_port() {
+        assert_eq!(
+            split_host_port("localhost:5005").unwrap(),
+            ("localhost", 5005)
+        );
+        assert_eq!(split_host_port("[::1]:5005").unwrap(), ("::1", 5005));
+        assert!(split_host_port("localhost").is_err());
+    }
+}
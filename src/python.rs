@@ -0,0 +1,626 @@
+//! Python module `jdwp_client`, exposing a [Debugger] to asyncio code
+//!
+//! Build and install it into the active environment with `maturin develop --release` from
+//! `python/`. Every method of `Debugger` is a coroutine, run on a Tokio runtime shared by all
+//! sessions, so the event loop keeps running while the VM is waited on.
+//!
+//! ```python
+//! import asyncio
+//! import jdwp_client
+//!
+//! async def main():
+//!     debugger = await jdwp_client.Debugger.attach("localhost:5005")
+//!     await debugger.set_breakpoint("com.example.Main", 42)
+//!     stop = await debugger.run_to_breakpoint()
+//!     print(stop.thread, await debugger.evaluate("com.example.Main", "count"))
+//!     await debugger.detach()
+//!
+//! asyncio.run(main())
+//! ```
+//!
+//! Failures raise `jdwp_client.JdwpError`. After `detach` or `kill` every call raises it.
+
+use std::mem;
+use std::sync::{Arc, OnceLock, PoisonError, RwLock};
+
+use pyo3::exceptions::{PyException, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::PyTuple;
+use pyo3::{IntoPyObjectExt, create_exception};
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::{Mutex, oneshot};
+
+use crate::debugger::BreakpointId;
+use crate::{
+    BreakpointSpec, Debugger as Session, IntoJdwpValue, JdwpValue, Tag, TaggedObjectId,
+    TryFromJdwpValue, VariableLengthId, result,
+};
+
+create_exception!(
+    jdwp_client,
+    JdwpError,
+    PyException,
+    "A request to the VM failed or the session has ended"
+);
+
+fn error(value: result::Error) -> PyErr {
+    JdwpError::new_err(format!("{:?}", value))
+}
+
+fn session(guard: &mut Option<Session>) -> PyResult<&mut Session> {
+    guard
+        .as_mut()
+        .ok_or_else(|| JdwpError::new_err("The session has ended"))
+}
+
+/// Held shared while a Tokio thread hands a result to asyncio, and exclusively from interpreter
+/// exit on. A thread entering the interpreter while it finalizes is terminated, which aborts the
+/// process when the thread runs Rust code.
+static DELIVERIES: RwLock<()> = RwLock::new(());
+
+fn runtime() -> PyResult<&'static Runtime> {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    if let Some(runtime) = RUNTIME.get() {
+        return Ok(runtime);
+    }
+    let runtime = Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| JdwpError::new_err(format!("{:?}", e)))?;
+    Ok(RUNTIME.get_or_init(|| runtime))
+}
+
+/// Runs `future` on the Tokio runtime and returns an asyncio future of its result. Cancelling
+/// the asyncio future drops `future`.
+fn awaitable<F, T>(py: Python<'_>, future: F) -> PyResult<Bound<'_, PyAny>>
+where
+    F: Future<Output = PyResult<T>> + Send + 'static,
+    T: for<'py> IntoPyObject<'py> + Send + 'static,
+{
+    let event_loop = py.import("asyncio")?.call_method0("get_running_loop")?;
+    let py_future = event_loop.call_method0("create_future")?;
+    let (cancel, cancelled) = oneshot::channel();
+    py_future.call_method1("add_done_callback", (CancelOnDone(Some(cancel)),))?;
+
+    let event_loop = event_loop.unbind();
+    let target = py_future.clone().unbind();
+    let resolve = wrap_pyfunction!(resolve, py)?.unbind();
+    runtime()?.spawn(async move {
+        let result = tokio::select! {
+            result = future => result,
+            _ = cancelled => return,
+        };
+        // Waiting for the GIL would hold back the tasks of a Tokio worker thread
+        tokio::task::spawn_blocking(move || {
+            let _delivery = DELIVERIES.read().unwrap_or_else(PoisonError::into_inner);
+            Python::attach(move |py| {
+                let (value, failed) = match result.and_then(|value| value.into_py_any(py)) {
+                    Ok(value) => (value, false),
+                    Err(e) => (e.into_value(py).into_any(), true),
+                };
+                if let Err(e) = event_loop.call_method1(
+                    py,
+                    "call_soon_threadsafe",
+                    (resolve, target, value, failed),
+                ) {
+                    e.print(py);
+                }
+            });
+        });
+    });
+    Ok(py_future)
+}
+
+/// Completes an asyncio future unless it was cancelled, called in the thread of its event loop
+#[pyfunction]
+fn resolve(future: &Bound<'_, PyAny>, value: &Bound<'_, PyAny>, failed: bool) -> PyResult<()> {
+    if !future.call_method0("done")?.is_truthy()? {
+        let method = if failed {
+            "set_exception"
+        } else {
+            "set_result"
+        };
+        future.call_method1(method, (value,))?;
+    }
+    Ok(())
+}
+
+/// Done callback of the asyncio futures of [awaitable], cancelling the Rust future
+#[pyclass]
+struct CancelOnDone(Option<oneshot::Sender<()>>);
+
+#[pymethods]
+impl CancelOnDone {
+    fn __call__(&mut self, future: &Bound<'_, PyAny>) -> PyResult<()> {
+        if future.call_method0("cancelled")?.is_truthy()?
+            && let Some(cancel) = self.0.take()
+        {
+            let _ = cancel.send(());
+        }
+        Ok(())
+    }
+}
+
+/// Registered with `atexit`, so results arriving later wait forever instead of entering the
+/// finalizing interpreter
+#[pyfunction]
+fn close_deliveries(py: Python<'_>) {
+    py.detach(|| mem::forget(DELIVERIES.write().unwrap_or_else(PoisonError::into_inner)));
+}
+
+/// A debugging session, see [Session]
+#[pyclass(name = "Debugger")]
+struct Debugger {
+    session: Arc<Mutex<Option<Session>>>,
+}
+
+#[pymethods]
+impl Debugger {
+    /// Connects to a VM listening on `address`, e.g. `localhost:5005`
+    #[staticmethod]
+    fn attach(py: Python<'_>, address: String) -> PyResult<Bound<'_, PyAny>> {
+        awaitable(py, async move {
+            let session = Session::attach(&address).await.map_err(error)?;
+            Ok(Debugger {
+                session: Arc::new(Mutex::new(Some(session))),
+            })
+        })
+    }
+
+    /// Every loaded class
+    fn classes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let session = self.session.clone();
+        awaitable(py, async move {
+            let mut guard = session.lock().await;
+            let client = self::session(&mut guard)?.client();
+            client.enable_class_tracking().await.map_err(error)?;
+            Ok(client
+                .classes()
+                .into_iter()
+                .map(Class::from)
+                .collect::<Vec<_>>())
+        })
+    }
+
+    /// Loaded classes whose simple name is `simple_name`
+    fn find_classes<'py>(
+        &self,
+        py: Python<'py>,
+        simple_name: String,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let session = self.session.clone();
+        awaitable(py, async move {
+            let mut guard = session.lock().await;
+            let classes = self::session(&mut guard)?
+                .client()
+                .find_classes(&simple_name)
+                .await
+                .map_err(error)?;
+            Ok(classes.into_iter().map(Class::from).collect::<Vec<_>>())
+        })
+    }
+
+    /// Every live thread
+    fn threads<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let session = self.session.clone();
+        awaitable(py, async move {
+            let mut guard = session.lock().await;
+            let client = self::session(&mut guard)?.client();
+            let mut threads = Vec::new();
+            for thread in client.vm_get_all_threads().await.map_err(error)?.threads {
+                threads.push(Thread {
+                    id: thread.thread_id.value,
+                    name: client.thread_name(thread.thread_id).await.map_err(error)?,
+                });
+            }
+            Ok(threads)
+        })
+    }
+
+    /// Sets a breakpoint and returns its id. The breakpoint is also set in classes loaded
+    /// later.
+    #[pyo3(signature = (class_name, line, condition = None, stratum = None))]
+    fn set_breakpoint<'py>(
+        &self,
+        py: Python<'py>,
+        class_name: String,
+        line: i32,
+        condition: Option<String>,
+        stratum: Option<String>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let session = self.session.clone();
+        awaitable(py, async move {
+            let mut spec = BreakpointSpec::new(class_name, line);
+            if let Some(condition) = condition {
+                spec = spec.condition(condition);
+            }
+            if let Some(stratum) = stratum {
+                spec = spec.stratum(stratum);
+            }
+            let mut guard = session.lock().await;
+            let id = self::session(&mut guard)?
+                .set_breakpoint_with(spec)
+                .await
+                .map_err(error)?;
+            Ok(id.0)
+        })
+    }
+
+    fn clear_breakpoint<'py>(&self, py: Python<'py>, id: u32) -> PyResult<Bound<'py, PyAny>> {
+        let session = self.session.clone();
+        awaitable(py, async move {
+            let mut guard = session.lock().await;
+            self::session(&mut guard)?
+                .clear_breakpoint(BreakpointId(id))
+                .await
+                .map_err(error)
+        })
+    }
+
+    /// `(id, class_name, line)` of every breakpoint
+    fn breakpoints<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let session = self.session.clone();
+        awaitable(py, async move {
+            let mut guard = session.lock().await;
+            let session = self::session(&mut guard)?;
+            Ok(session
+                .breakpoint_ids()
+                .into_iter()
+                .filter_map(|id| {
+                    let spec = session.breakpoint_spec(id)?;
+                    Some((id.0, spec.class_name.clone(), spec.line))
+                })
+                .collect::<Vec<_>>())
+        })
+    }
+
+    /// The current stop, None while the debuggee runs
+    fn stop<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let session = self.session.clone();
+        awaitable(py, async move {
+            let mut guard = session.lock().await;
+            Ok(self::session(&mut guard)?.stop().map(Stop::from))
+        })
+    }
+
+    fn continue_<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let session = self.session.clone();
+        awaitable(py, async move {
+            let mut guard = session.lock().await;
+            self::session(&mut guard)?.continue_().await.map_err(error)
+        })
+    }
+
+    fn run_to_breakpoint<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let session = self.session.clone();
+        awaitable(py, async move {
+            let mut guard = session.lock().await;
+            let stop = self::session(&mut guard)?
+                .run_to_breakpoint()
+                .await
+                .map_err(error)?;
+            Ok(Stop::from(&stop))
+        })
+    }
+
+    fn step_over<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let session = self.session.clone();
+        awaitable(py, async move {
+            let mut guard = session.lock().await;
+            let stop = self::session(&mut guard)?
+                .step_over()
+                .await
+                .map_err(error)?;
+            Ok(Stop::from(&stop))
+        })
+    }
+
+    /// Calls a static method in the thread of the current stop and returns its return value
+    ///
+    /// Arguments may be bools, ints (Java ints, or longs when they don't fit), floats (Java
+    /// doubles), strs (created in the VM), Objects or None for null.
+    #[pyo3(signature = (class_name, method_name, *arguments))]
+    fn evaluate<'py>(
+        &self,
+        py: Python<'py>,
+        class_name: String,
+        method_name: String,
+        arguments: &Bound<'py, PyTuple>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let arguments = arguments
+            .iter()
+            .map(|argument| Argument::extract(&argument))
+            .collect::<PyResult<Vec<_>>>()?;
+        let session = self.session.clone();
+        awaitable(py, async move {
+            let mut guard = session.lock().await;
+            let session = self::session(&mut guard)?;
+            let client = session.client();
+            let mut values = Vec::with_capacity(arguments.len());
+            for argument in arguments {
+                values.push(match argument {
+                    Argument::Value(value) => value,
+                    Argument::String(string) => {
+                        string.into_jdwp_value(client).await.map_err(error)?
+                    }
+                });
+            }
+            let value: JdwpValue = session
+                .evaluate(&class_name, &method_name, values)
+                .await
+                .map_err(error)?;
+            Value::read(value, client).await
+        })
+    }
+
+    /// Resumes the debuggee and closes the connection
+    fn detach<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let session = self.session.clone();
+        awaitable(py, async move {
+            if let Some(session) = session.lock().await.take() {
+                session.detach().await.map_err(error)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Terminates the debuggee with `exit_code`
+    #[pyo3(signature = (exit_code = 1))]
+    fn kill<'py>(&self, py: Python<'py>, exit_code: i32) -> PyResult<Bound<'py, PyAny>> {
+        let session = self.session.clone();
+        awaitable(py, async move {
+            if let Some(session) = session.lock().await.take() {
+                session.kill(exit_code).await.map_err(error)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Where the debuggee stopped, `breakpoint` is None at the end of a step
+#[pyclass(frozen, get_all)]
+struct Stop {
+    thread: u64,
+    class_id: u64,
+    method_id: u64,
+    index: u64,
+    breakpoint: Option<u32>,
+}
+impl From<&crate::Stop> for Stop {
+    fn from(value: &crate::Stop) -> Self {
+        Stop {
+            thread: value.thread.value,
+            class_id: value.location.class_id.value,
+            method_id: value.location.method_id.value,
+            index: value.location.index,
+            breakpoint: value.breakpoint.map(|id| id.0),
+        }
+    }
+}
+
+#[pyclass(frozen, get_all)]
+struct Thread {
+    id: u64,
+    name: String,
+}
+
+#[pyclass(frozen, get_all)]
+struct Class {
+    id: u64,
+    /// Source-level name, e.g. `com.example.Main$Inner`
+    name: String,
+    /// JNI signature, e.g. `Lcom/example/Main;`
+    signature: String,
+}
+impl From<crate::LoadedClass> for Class {
+    fn from(value: crate::LoadedClass) -> Self {
+        Class {
+            id: value.type_id.value,
+            name: value.type_name(),
+            signature: value.signature,
+        }
+    }
+}
+
+/// An object in the VM, returned by and passed to [Debugger::evaluate]
+#[pyclass(frozen)]
+struct Object {
+    object: TaggedObjectId,
+}
+#[pymethods]
+impl Object {
+    #[getter]
+    fn id(&self) -> u64 {
+        self.object.object_id.value
+    }
+
+    /// JDWP tag of the object, e.g. `L` for objects and `[` for arrays
+    #[getter]
+    fn tag(&self) -> char {
+        self.object.tag as u8 as char
+    }
+}
+
+/// An argument of [Debugger::evaluate], strings being created in the VM once it's connected
+enum Argument {
+    Value(JdwpValue),
+    String(String),
+}
+impl Argument {
+    fn extract(value: &Bound<'_, PyAny>) -> PyResult<Self> {
+        let value = if value.is_none() {
+            JdwpValue::Object(TaggedObjectId {
+                tag: Tag::Object,
+                object_id: VariableLengthId { value: 0 },
+            })
+        } else if let Ok(value) = value.extract::<bool>() {
+            JdwpValue::Boolean(value)
+        } else if let Ok(value) = value.extract::<i64>() {
+            i32::try_from(value).map_or(JdwpValue::Long(value), JdwpValue::Int)
+        } else if let Ok(value) = value.extract::<f64>() {
+            JdwpValue::Double(value)
+        } else if let Ok(value) = value.extract::<String>() {
+            return Ok(Argument::String(value));
+        } else if let Ok(value) = value.cast::<Object>() {
+            JdwpValue::Object(value.get().object)
+        } else {
+            return Err(PyTypeError::new_err(format!(
+                "Unsupported argument type {}",
+                value.get_type().name()?
+            )));
+        };
+        Ok(Argument::Value(value))
+    }
+}
+
+/// A return value of [Debugger::evaluate], None for void and null
+#[derive(IntoPyObject)]
+enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Object(Object),
+}
+impl Value {
+    async fn read(value: JdwpValue, client: &crate::JdwpClient) -> PyResult<Option<Self>> {
+        Ok(Some(match value {
+            JdwpValue::Void => return Ok(None),
+            JdwpValue::Boolean(value) => Value::Bool(value),
+            JdwpValue::Byte(value) => Value::Int(value.into()),
+            JdwpValue::Char(value) => Value::String(
+                char::from_u32(value.into())
+                    .unwrap_or(char::REPLACEMENT_CHARACTER)
+                    .to_string(),
+            ),
+            JdwpValue::Short(value) => Value::Int(value.into()),
+            JdwpValue::Int(value) => Value::Int(value.into()),
+            JdwpValue::Long(value) => Value::Int(value),
+            JdwpValue::Float(value) => Value::Float(value.into()),
+            JdwpValue::Double(value) => Value::Float(value),
+            JdwpValue::Object(object) if object.object_id.value == 0 => return Ok(None),
+            JdwpValue::Object(object) if object.tag == Tag::String => Value::String(
+                String::try_from_jdwp_value(value, client)
+                    .await
+                    .map_err(error)?,
+            ),
+            JdwpValue::Object(object) => Value::Object(Object { object }),
+        }))
+    }
+}
+
+#[pymodule]
+fn jdwp_client(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Debugger>()?;
+    module.add_class::<Stop>()?;
+    module.add_class::<Thread>()?;
+    module.add_class::<Class>()?;
+    module.add_class::<Object>()?;
+    module.add("JdwpError", module.py().get_type::<JdwpError>())?;
+    module
+        .py()
+        .import("atexit")?
+        .call_method1("register", (wrap_pyfunction!(close_deliveries, module)?,))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Command, JdwpClient, MockJdwpTransport};
+
+    const THREAD: u64 = 0x20;
+
+    /// Runs `code` with a `debugger` attached to `transport` and the module imported as
+    /// `jdwp_client`
+    fn run(transport: MockJdwpTransport, code: &std::ffi::CStr) -> PyResult<()> {
+        let session =
+            runtime()?.block_on(async { Session::new(JdwpClient::new(transport).await.unwrap()) });
+        Python::initialize();
+        Python::attach(|py| {
+            let module = PyModule::new(py, "jdwp_client")?;
+            jdwp_client(&module)?;
+            let globals = pyo3::types::PyDict::new(py);
+            globals.set_item("jdwp_client", module)?;
+            globals.set_item(
+                "debugger",
+                Debugger {
+                    session: Arc::new(Mutex::new(Some(session))),
+                },
+            )?;
+            py.run(code, Some(&globals), None)
+        })
+    }
+
+    #[test]
+    fn test_threads() {
+        let mut name = 4u32.to_be_bytes().to_vec();
+        name.extend_from_slice(b"main");
+        let transport = MockJdwpTransport::new()
+            .expect_command(Command::VirtualMachineAllThreads)
+            .reply_with(&[&1i32.to_be_bytes()[..], &THREAD.to_be_bytes()].concat())
+            .expect_command(Command::ThreadReferenceName)
+            .with_data(&THREAD.to_be_bytes())
+            .reply_with(&name);
+        run(
+            transport,
+            cr#"
+import asyncio
+
+async def main():
+    threads = await debugger.threads()
+    assert [(thread.id, thread.name) for thread in threads] == [(0x20, "main")]
+
+asyncio.run(main())
+"#,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_cancelled_call_releases_session() {
+        let transport = MockJdwpTransport::new()
+            .expect_command(Command::VirtualMachineAllThreads)
+            .no_reply()
+            .expect_command(Command::VirtualMachineAllThreads)
+            .reply_with(&0i32.to_be_bytes());
+        run(
+            transport,
+            cr#"
+import asyncio
+
+async def main():
+    try:
+        await asyncio.wait_for(debugger.threads(), 0.1)
+        raise AssertionError("threads returned without a reply")
+    except asyncio.TimeoutError:
+        pass
+    # Waits for the session lock, which the cancelled call would hold until it timed out
+    assert await asyncio.wait_for(debugger.threads(), 1) == []
+
+asyncio.run(main())
+"#,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_unsupported_argument() {
+        run(
+            MockJdwpTransport::new(),
+            cr#"
+import asyncio
+
+async def main():
+    try:
+        await debugger.evaluate("com.example.Main", "count", object())
+        raise AssertionError("evaluate accepted an object")
+    except TypeError:
+        pass
+
+asyncio.run(main())
+"#,
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,273 @@
+use bitflags::bitflags;
+
+use crate::{Command, JdwpClient, JdwpErrorCode, VersionReply, result};
+
+/// Implementation of a VM, as far as it matters for its JDWP behaviour
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VmFamily {
+    /// HotSpot and other VMs running the JDWP agent of OpenJDK, e.g. GraalVM
+    HotSpot,
+    OpenJ9,
+    /// The Android runtime, which reports itself as Dalvik
+    Art,
+    Other,
+}
+impl VmFamily {
+    /// Guesses the family from the reply to VirtualMachine.Version
+    pub fn detect(version: &VersionReply) -> Self {
+        let name = &version.vm_name.string;
+        let description = &version.description.string;
+        if name.contains("J9") {
+            VmFamily::OpenJ9
+        } else if name == "Dalvik" || description.starts_with("Android") {
+            VmFamily::Art
+        } else if name.contains("HotSpot") || name.contains("OpenJDK") || name.contains("GraalVM") {
+            VmFamily::HotSpot
+        } else {
+            VmFamily::Other
+        }
+    }
+
+    /// Quirks all versions of the family are known to have
+    pub fn known_quirks(&self) -> Quirks {
+        match self {
+            VmFamily::Art => {
+                Quirks::DEX_BYTECODES
+                    | Quirks::FIXED_VM_VERSION
+                    | Quirks::NO_HOLD_EVENTS
+                    | Quirks::MUTABLE_THREAD_NAMES
+                    | Quirks::DDM_HELLO
+            }
+            VmFamily::OpenJ9 => Quirks::INVALID_SLOT_FOR_TYPE_MISMATCH,
+            VmFamily::HotSpot | VmFamily::Other => Quirks::empty(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Quirks(u32);
+bitflags! {
+    /// Known differences between a VM and the reference implementation which capabilities
+    /// don't tell, see [JdwpClient::detect_quirks]
+    impl Quirks : u32 {
+        /// Method.Bytecodes and ReferenceType.ConstantPool describe Dalvik bytecode rather
+        /// than class file bytecode
+        const DEX_BYTECODES = 1;
+        /// The `vm_version` of VirtualMachine.Version is fixed, e.g. `1.6.0`, rather than the
+        /// Java version of the runtime
+        const FIXED_VM_VERSION = 1 << 1;
+        /// StackFrame.GetValues and SetValues reply INVALID_SLOT for a slot accessed with
+        /// another type than it holds, which the client reports as TYPE_MISMATCH like the
+        /// reference implementation
+        const INVALID_SLOT_FOR_TYPE_MISMATCH = 1 << 2;
+        /// VirtualMachine.HoldEvents and ReleaseEvents aren't implemented, so a full event
+        /// buffer blocks the reader instead of holding events back
+        const NO_HOLD_EVENTS = 1 << 3;
+        /// Threads are renamed while they run, e.g. pooled and binder threads, so their names
+        /// aren't cached
+        const MUTABLE_THREAD_NAMES = 1 << 4;
+        /// The VM expects a debugger to introduce itself with a DDM HELO chunk, see
+        /// [JdwpClient::ddm_hello]
+        const DDM_HELLO = 1 << 5;
+    }
+}
+impl Quirks {
+    /// Explanations of the quirks, e.g. to warn the user of a debugger
+    pub fn warnings(&self) -> Vec<&'static str> {
+        self.iter()
+            .filter_map(|quirk| match quirk {
+                Quirks::DEX_BYTECODES => {
+                    Some("Bytecodes and constant pools are in the Dalvik format")
+                }
+                Quirks::FIXED_VM_VERSION => {
+                    Some("The reported VM version isn't the Java version of the runtime")
+                }
+                Quirks::INVALID_SLOT_FOR_TYPE_MISMATCH => {
+                    Some("Type mismatches of local variables are reported as invalid slots")
+                }
+                Quirks::NO_HOLD_EVENTS => {
+                    Some("Events can't be held back while the event buffer is full")
+                }
+                Quirks::MUTABLE_THREAD_NAMES => Some("Threads may be renamed while they run"),
+                Quirks::DDM_HELLO => Some("The VM expects a DDM handshake"),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Maps an error code of a reply to `command` to the one the reference implementation
+    /// replies in the same situation
+    pub fn normalize_error_code(&self, command: Command, code: u16) -> u16 {
+        if self.contains(Quirks::INVALID_SLOT_FOR_TYPE_MISMATCH)
+            && matches!(
+                command,
+                Command::StackFrameGetValues | Command::StackFrameSetValues
+            )
+            && code == JdwpErrorCode::InvalidSlot as u16
+        {
+            return JdwpErrorCode::TypeMismatch as u16;
+        }
+        code
+    }
+}
+
+/// Quirks of a family of VMs, optionally only of the versions starting with a prefix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuirkEntry {
+    pub family: VmFamily,
+    /// Prefix of the `vm_version` the entry applies to, e.g. `17.`; None for all versions
+    pub vm_version_prefix: Option<String>,
+    pub quirks: Quirks,
+}
+
+/// Maps VMs to their [Quirks]. The default database lists the quirks known of the common VMs;
+/// tools can add entries for the VMs they meet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuirkDatabase {
+    entries: Vec<QuirkEntry>,
+}
+impl Default for QuirkDatabase {
+    fn default() -> Self {
+        QuirkDatabase::empty()
+            .with_entry(VmFamily::Art, None, VmFamily::Art.known_quirks())
+            .with_entry(VmFamily::OpenJ9, None, VmFamily::OpenJ9.known_quirks())
+    }
+}
+impl QuirkDatabase {
+    /// A database without entries
+    pub fn empty() -> Self {
+        QuirkDatabase {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn with_entry(
+        mut self,
+        family: VmFamily,
+        vm_version_prefix: Option<&str>,
+        quirks: Quirks,
+    ) -> Self {
+        self.entries.push(QuirkEntry {
+            family,
+            vm_version_prefix: vm_version_prefix.map(String::from),
+            quirks,
+        });
+        self
+    }
+
+    pub fn entries(&self) -> &[QuirkEntry] {
+        &self.entries
+    }
+
+    /// Quirks of all entries matching the VM
+    pub fn lookup(&self, version: &VersionReply) -> Quirks {
+        let family = VmFamily::detect(version);
+        self.entries
+            .iter()
+            .filter(|entry| entry.family == family)
+            .filter(|entry| {
+                entry
+                    .vm_version_prefix
+                    .as_ref()
+                    .is_none_or(|prefix| version.vm_version.string.starts_with(prefix.as_str()))
+            })
+            .fold(Quirks::empty(), |quirks, entry| quirks | entry.quirks)
+    }
+}
+
+impl JdwpClient {
+    /// Asks the VM for its version and looks up its quirks in `database`. The client adapts
+    /// to the quirks from then on, e.g. by normalizing error codes; the quirks are returned for
+    /// the caller to adapt or warn as well. Applies to the whole connection.
+    pub async fn detect_quirks(&self, database: &QuirkDatabase) -> result::Result<Quirks> {
+        let version = self.vm_get_version().await?;
+        let quirks = database.lookup(&version);
+        self.set_quirks(quirks);
+        Ok(quirks)
+    }
+
+    /// Makes the client adapt to `quirks`, e.g. for a VM not in any database
+    pub fn set_quirks(&self, quirks: Quirks) {
+        *self.shared.quirks() = quirks;
+        self.shared
+            .events
+            .set_hold_events_supported(!quirks.contains(Quirks::NO_HOLD_EVENTS));
+    }
+
+    /// Quirks the client adapts to, none until detected or set
+    pub fn quirks(&self) -> Quirks {
+        *self.shared.quirks()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JdwpString;
+
+    fn string(value: &str) -> JdwpString {
+        JdwpString {
+            string: String::from(value),
+        }
+    }
+
+    fn version(description: &str, vm_version: &str, vm_name: &str) -> VersionReply {
+        VersionReply {
+            description: string(description),
+            jdwp_major: 1,
+            jdwp_minor: 6,
+            vm_version: string(vm_version),
+            vm_name: string(vm_name),
+        }
+    }
+
+    #[test]
+    fn test_detect_family() {
+        let art = version("Android Runtime 2.1.0", "1.6.0", "Dalvik");
+        let openj9 = version("Java Debug Wire Protocol", "17.0.9", "Eclipse OpenJ9 VM");
+        let hotspot = version(
+            "Java Debug Wire Protocol",
+            "21.0.8",
+            "OpenJDK 64-Bit Server VM",
+        );
+        assert_eq!(VmFamily::detect(&art), VmFamily::Art);
+        assert_eq!(VmFamily::detect(&openj9), VmFamily::OpenJ9);
+        assert_eq!(VmFamily::detect(&hotspot), VmFamily::HotSpot);
+        assert_eq!(
+            VmFamily::detect(&version("Custom", "1.0", "Custom VM")),
+            VmFamily::Other
+        );
+
+        let database = QuirkDatabase::default().with_entry(
+            VmFamily::HotSpot,
+            Some("21."),
+            Quirks::FIXED_VM_VERSION,
+        );
+        assert_eq!(database.lookup(&art), VmFamily::Art.known_quirks());
+        assert!(database.lookup(&art).contains(Quirks::DEX_BYTECODES));
+        assert_eq!(database.lookup(&hotspot), Quirks::FIXED_VM_VERSION);
+        assert_eq!(
+            database.lookup(&version("Java Debug Wire Protocol", "17.0.2", "OpenJDK")),
+            Quirks::empty()
+        );
+        assert_eq!(database.lookup(&art).warnings().len(), 5);
+    }
+
+    #[test]
+    fn test_normalize_error_code() {
+        let invalid_slot = JdwpErrorCode::InvalidSlot as u16;
+        let quirks = Quirks::INVALID_SLOT_FOR_TYPE_MISMATCH;
+        assert_eq!(
+            quirks.normalize_error_code(Command::StackFrameGetValues, invalid_slot),
+            JdwpErrorCode::TypeMismatch as u16
+        );
+        assert_eq!(
+            quirks.normalize_error_code(Command::ThreadReferenceFrames, invalid_slot),
+            invalid_slot
+        );
+        assert_eq!(
+            Quirks::empty().normalize_error_code(Command::StackFrameGetValues, invalid_slot),
+            invalid_slot
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Token bucket limiting how many commands a client sends
+///
+/// Every command, including each retry, takes one token. Tokens are added at a steady rate up
+/// to the burst size, and a command waits until one is available. Clones share their tokens.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    interval: Duration,
+    burst: u32,
+    bucket: Arc<Mutex<Bucket>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: u32,
+    /// When the next token is added
+    next_token: Instant,
+}
+
+impl RateLimiter {
+    /// Allows `commands_per_second` commands on average and up to `burst` at once. Both are at
+    /// least 1.
+    pub fn new(commands_per_second: u32, burst: u32) -> Self {
+        Self::with_interval(Duration::from_secs(1) / commands_per_second.max(1), burst)
+    }
+
+    /// Adds a token every `interval`, allowing up to `burst` commands at once
+    pub fn with_interval(interval: Duration, burst: u32) -> Self {
+        let burst = burst.max(1);
+        RateLimiter {
+            interval,
+            burst,
+            bucket: Arc::new(Mutex::new(Bucket {
+                tokens: burst,
+                next_token: Instant::now() + interval,
+            })),
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    /// Waits until a token is available and takes it
+    pub(crate) async fn acquire(&self) {
+        loop {
+            let wait_until = {
+                let mut bucket = match self.bucket.lock() {
+                    Ok(bucket) => bucket,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                let now = Instant::now();
+                while bucket.next_token <= now && bucket.tokens < self.burst {
+                    bucket.tokens += 1;
+                    bucket.next_token += self.interval;
+                }
+                if bucket.tokens == self.burst {
+                    // A full bucket doesn't save up tokens while idle
+                    bucket.next_token = bucket.next_token.max(now + self.interval);
+                }
+                if bucket.tokens > 0 {
+                    bucket.tokens -= 1;
+                    return;
+                }
+                bucket.next_token
+            };
+            tokio::time::sleep_until(wait_until).await;
+        }
+    }
+}
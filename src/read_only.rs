@@ -0,0 +1,324 @@
+use bytes::Bytes;
+use std::collections::HashMap;
+
+use crate::{
+    AllClassesReply, AllThreadsReply, ArrayGetValuesReply, ArrayLengthReply, ArrayValues,
+    CapabilitiesNewReply, ClassObjectReply, ClassPathsReply, ClassesBySignatureReply, FieldOwner,
+    FieldsReply, GetValuesReply, InstanceCountsReply, InstancesReply, InterfacesReply, JdwpClient,
+    JdwpValue, LineTableReply, MethodsReply, MethodsReplyMethod, ObjectReferenceTypeReply,
+    ReferringObjectsReply, ReflectedTypeReply, SignatureReply, SourceDebugExtensionReply,
+    SourceFileReply, StringValueReply, SuperclassReply, ThreadGroupChildrenReply,
+    ThreadGroupNameReply, ThreadNameReply, ThreadStatusReply, TopLevelThreadGroupsReply,
+    VariableLengthId, VariableTableReply, VersionReply, VmInfo, result,
+};
+
+/// A client which can only send commands that don't change the state of the VM
+///
+/// Commands which suspend or resume threads, create objects or strings, invoke methods, set
+/// values, create event requests or end the connection aren't available, so monitoring tools
+/// built on it can't affect the target by mistake. The wrapped client can't be taken back out.
+#[derive(Clone)]
+pub struct ReadOnlyClient {
+    client: JdwpClient,
+}
+
+impl From<JdwpClient> for ReadOnlyClient {
+    fn from(client: JdwpClient) -> Self {
+        ReadOnlyClient { client }
+    }
+}
+
+impl ReadOnlyClient {
+    pub fn new(client: JdwpClient) -> Self {
+        Self::from(client)
+    }
+
+    pub fn is_vm_suspended(&self) -> bool {
+        self.client.is_vm_suspended()
+    }
+
+    pub fn is_thread_suspended(&self, thread_id: VariableLengthId) -> bool {
+        self.client.is_thread_suspended(thread_id)
+    }
+
+    pub async fn vm_get_version(&self) -> result::Result<VersionReply> {
+        self.client.vm_get_version().await
+    }
+
+    pub async fn vm_get_classes_by_signature(
+        &self,
+        signature: &str,
+    ) -> result::Result<ClassesBySignatureReply> {
+        self.client.vm_get_classes_by_signature(signature).await
+    }
+
+    pub async fn vm_get_all_classes(&self) -> result::Result<AllClassesReply> {
+        self.client.vm_get_all_classes().await
+    }
+
+    pub async fn vm_get_all_threads(&self) -> result::Result<AllThreadsReply> {
+        self.client.vm_get_all_threads().await
+    }
+
+    pub async fn vm_get_top_level_thread_groups(
+        &self,
+    ) -> result::Result<TopLevelThreadGroupsReply> {
+        self.client.vm_get_top_level_thread_groups().await
+    }
+
+    pub async fn vm_get_class_paths(&self) -> result::Result<ClassPathsReply> {
+        self.client.vm_get_class_paths().await
+    }
+
+    pub async fn vm_get_capabilities_new(&self) -> result::Result<CapabilitiesNewReply> {
+        self.client.vm_get_capabilities_new().await
+    }
+
+    pub async fn vm_get_instance_counts(
+        &self,
+        ref_types: Vec<VariableLengthId>,
+    ) -> result::Result<InstanceCountsReply> {
+        self.client.vm_get_instance_counts(ref_types).await
+    }
+
+    pub async fn vm_info(&self) -> result::Result<VmInfo> {
+        self.client.vm_info().await
+    }
+
+    pub async fn ref_type_get_signature(
+        &self,
+        ref_type_id: VariableLengthId,
+    ) -> result::Result<SignatureReply> {
+        self.client.ref_type_get_signature(ref_type_id).await
+    }
+
+    pub async fn ref_type_get_fields(
+        &self,
+        ref_type_id: VariableLengthId,
+    ) -> result::Result<FieldsReply> {
+        self.client.ref_type_get_fields(ref_type_id).await
+    }
+
+    pub async fn ref_type_get_methods(
+        &self,
+        ref_type_id: VariableLengthId,
+    ) -> result::Result<MethodsReply> {
+        self.client.ref_type_get_methods(ref_type_id).await
+    }
+
+    pub async fn ref_type_get_values(
+        &self,
+        ref_type_id: VariableLengthId,
+        fields: Vec<VariableLengthId>,
+    ) -> result::Result<GetValuesReply> {
+        self.client.ref_type_get_values(ref_type_id, fields).await
+    }
+
+    pub async fn ref_type_get_source_file(
+        &self,
+        ref_type_id: VariableLengthId,
+    ) -> result::Result<SourceFileReply> {
+        self.client.ref_type_get_source_file(ref_type_id).await
+    }
+
+    pub async fn ref_type_get_interfaces(
+        &self,
+        ref_type_id: VariableLengthId,
+    ) -> result::Result<InterfacesReply> {
+        self.client.ref_type_get_interfaces(ref_type_id).await
+    }
+
+    pub async fn ref_type_get_source_debug_extension(
+        &self,
+        ref_type_id: VariableLengthId,
+    ) -> result::Result<SourceDebugExtensionReply> {
+        self.client
+            .ref_type_get_source_debug_extension(ref_type_id)
+            .await
+    }
+
+    pub async fn ref_type_get_instances(
+        &self,
+        ref_type_id: VariableLengthId,
+        max_instances: i32,
+    ) -> result::Result<InstancesReply> {
+        self.client
+            .ref_type_get_instances(ref_type_id, max_instances)
+            .await
+    }
+
+    pub async fn ref_type_get_class_object(
+        &self,
+        ref_type_id: VariableLengthId,
+    ) -> result::Result<ClassObjectReply> {
+        self.client.ref_type_get_class_object(ref_type_id).await
+    }
+
+    pub async fn class_type_get_superclass(
+        &self,
+        class_id: VariableLengthId,
+    ) -> result::Result<SuperclassReply> {
+        self.client.class_type_get_superclass(class_id).await
+    }
+
+    pub async fn find_method(
+        &self,
+        type_id: VariableLengthId,
+        name: &str,
+        signature: &str,
+    ) -> result::Result<MethodsReplyMethod> {
+        self.client.find_method(type_id, name, signature).await
+    }
+
+    pub async fn resolve_method(
+        &self,
+        type_id: VariableLengthId,
+        name: &str,
+        arguments: &[JdwpValue],
+    ) -> result::Result<MethodsReplyMethod> {
+        self.client.resolve_method(type_id, name, arguments).await
+    }
+
+    pub async fn method_get_line_table(
+        &self,
+        ref_type_id: VariableLengthId,
+        method_id: VariableLengthId,
+    ) -> result::Result<LineTableReply> {
+        self.client
+            .method_get_line_table(ref_type_id, method_id)
+            .await
+    }
+
+    pub async fn method_get_variable_table(
+        &self,
+        ref_type_id: VariableLengthId,
+        method_id: VariableLengthId,
+    ) -> result::Result<VariableTableReply> {
+        self.client
+            .method_get_variable_table(ref_type_id, method_id)
+            .await
+    }
+
+    pub async fn object_get_values(
+        &self,
+        object_id: VariableLengthId,
+        fields: Vec<VariableLengthId>,
+    ) -> result::Result<GetValuesReply> {
+        self.client.object_get_values(object_id, fields).await
+    }
+
+    pub async fn get_fields(
+        &self,
+        owner: FieldOwner,
+        names: &[&str],
+    ) -> result::Result<HashMap<String, JdwpValue>> {
+        self.client.get_fields(owner, names).await
+    }
+
+    pub async fn object_get_reference_type(
+        &self,
+        object_id: VariableLengthId,
+    ) -> result::Result<ObjectReferenceTypeReply> {
+        self.client.object_get_reference_type(object_id).await
+    }
+
+    pub async fn object_get_referring_objects(
+        &self,
+        object_id: VariableLengthId,
+        max_referrers: i32,
+    ) -> result::Result<ReferringObjectsReply> {
+        self.client
+            .object_get_referring_objects(object_id, max_referrers)
+            .await
+    }
+
+    pub async fn class_object_get_reflected_type(
+        &self,
+        class_object_id: VariableLengthId,
+    ) -> result::Result<ReflectedTypeReply> {
+        self.client
+            .class_object_get_reflected_type(class_object_id)
+            .await
+    }
+
+    pub async fn string_get_value(
+        &self,
+        string_id: VariableLengthId,
+    ) -> result::Result<StringValueReply> {
+        self.client.string_get_value(string_id).await
+    }
+
+    pub async fn thread_get_name(
+        &self,
+        thread_id: VariableLengthId,
+    ) -> result::Result<ThreadNameReply> {
+        self.client.thread_get_name(thread_id).await
+    }
+
+    pub async fn thread_name(&self, thread_id: VariableLengthId) -> result::Result<String> {
+        self.client.thread_name(thread_id).await
+    }
+
+    pub async fn thread_get_status(
+        &self,
+        thread_id: VariableLengthId,
+    ) -> result::Result<ThreadStatusReply> {
+        self.client.thread_get_status(thread_id).await
+    }
+
+    /// Number of frames on the thread's stack. The thread must already be suspended.
+    pub async fn thread_frame_count(&self, thread_id: VariableLengthId) -> result::Result<i32> {
+        self.client.thread_frame_count(thread_id).await
+    }
+
+    pub async fn thread_get_suspend_count(
+        &self,
+        thread_id: VariableLengthId,
+    ) -> result::Result<i32> {
+        self.client.thread_get_suspend_count(thread_id).await
+    }
+
+    pub async fn thread_group_get_name(
+        &self,
+        group_id: VariableLengthId,
+    ) -> result::Result<ThreadGroupNameReply> {
+        self.client.thread_group_get_name(group_id).await
+    }
+
+    pub async fn thread_group_get_children(
+        &self,
+        group_id: VariableLengthId,
+    ) -> result::Result<ThreadGroupChildrenReply> {
+        self.client.thread_group_get_children(group_id).await
+    }
+
+    pub async fn array_get_length(
+        &self,
+        array_id: VariableLengthId,
+    ) -> result::Result<ArrayLengthReply> {
+        self.client.array_get_length(array_id).await
+    }
+
+    pub async fn array_get_values(
+        &self,
+        array_id: VariableLengthId,
+        first_index: i32,
+        length: i32,
+    ) -> result::Result<ArrayGetValuesReply> {
+        self.client
+            .array_get_values(array_id, first_index, length)
+            .await
+    }
+
+    pub async fn read_array(&self, array_id: VariableLengthId) -> result::Result<ArrayValues> {
+        self.client.read_array(array_id).await
+    }
+
+    pub async fn read_char_array(&self, array_id: VariableLengthId) -> result::Result<String> {
+        self.client.read_char_array(array_id).await
+    }
+
+    pub async fn read_byte_array(&self, array_id: VariableLengthId) -> result::Result<Bytes> {
+        self.client.read_byte_array(array_id).await
+    }
+}
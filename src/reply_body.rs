@@ -0,0 +1,221 @@
+use binrw::BinRead;
+use bytes::Bytes;
+use std::io::{self, Cursor};
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+use tokio::sync::mpsc;
+
+use crate::{
+    ArrayGetValuesOut, ArrayValues, Command, JdwpClient, JdwpIdSizes, PACKET_HEADER_LENGTH, Tag,
+    VariableLengthId, result,
+};
+
+/// Replies with at least this many bytes of data are streamed to requests sent with
+/// [JdwpClient::send_streaming] instead of being buffered
+pub const STREAMING_THRESHOLD: usize = 1024 * 1024;
+
+/// Size of the chunks the reader task reads a streamed reply in
+const BODY_CHUNK_SIZE: usize = 64 * 1024;
+/// Chunks read ahead of the consumer of a [ReplyBody]
+const BODY_CHANNEL_CAPACITY: usize = 16;
+
+/// Data of a reply, read as it arrives for replies of at least [STREAMING_THRESHOLD] bytes
+///
+/// While a streamed body is being read, the connection delivers nothing else: events and
+/// other replies queue up behind the rest of the body on the stream. Read bodies promptly, or
+/// drop them, which makes the reader task skip the rest.
+#[derive(Debug)]
+pub struct ReplyBody {
+    len: usize,
+    chunk: Bytes,
+    chunks: Option<mpsc::Receiver<io::Result<Bytes>>>,
+}
+impl ReplyBody {
+    fn buffered(data: Vec<u8>) -> Self {
+        ReplyBody {
+            len: data.len(),
+            chunk: Bytes::from(data),
+            chunks: None,
+        }
+    }
+
+    fn streamed(len: usize, chunks: mpsc::Receiver<io::Result<Bytes>>) -> Self {
+        ReplyBody {
+            len,
+            chunk: Bytes::new(),
+            chunks: Some(chunks),
+        }
+    }
+
+    /// Length of the whole data, including what has been read already
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the data is read from the connection as it arrives, rather than from memory
+    pub fn is_streamed(&self) -> bool {
+        self.chunks.is_some()
+    }
+}
+impl AsyncRead for ReplyBody {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            if !self.chunk.is_empty() {
+                let length = buf.remaining().min(self.chunk.len());
+                buf.put_slice(&self.chunk.split_to(length));
+                return Poll::Ready(Ok(()));
+            }
+            let Some(chunks) = &mut self.chunks else {
+                return Poll::Ready(Ok(()));
+            };
+            match ready!(chunks.poll_recv(cx)) {
+                Some(Ok(chunk)) => self.chunk = chunk,
+                Some(Err(e)) => return Poll::Ready(Err(e)),
+                None => self.chunks = None,
+            }
+        }
+    }
+}
+
+/// Reads the `length` bytes of a streamed reply's data into its body, chunk by chunk. The data
+/// is read to the end even if the body was dropped, to keep the stream in sync.
+pub(crate) async fn pump_body<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    length: usize,
+    body: mpsc::Sender<io::Result<Bytes>>,
+) -> io::Result<()> {
+    let mut remaining = length;
+    while remaining > 0 {
+        let mut chunk = vec![0u8; remaining.min(BODY_CHUNK_SIZE)];
+        if let Err(e) = reader.read_exact(&mut chunk).await {
+            let _ = body
+                .send(Err(io::Error::new(e.kind(), e.to_string())))
+                .await;
+            return Err(e);
+        }
+        remaining -= chunk.len();
+        // Fails right away once the body is dropped
+        let _ = body.send(Ok(Bytes::from(chunk))).await;
+    }
+    Ok(())
+}
+
+/// Decodes the `arrayregion` of an ArrayReference.GetValues reply a few elements at a time,
+/// see [JdwpClient::array_get_values_streaming]
+#[derive(Debug)]
+pub struct ArrayRegionReader {
+    body: ReplyBody,
+    sizes: JdwpIdSizes,
+    tag: Tag,
+    remaining: usize,
+}
+impl ArrayRegionReader {
+    /// Reads the tag and length of the region from the start of the body
+    pub async fn new(mut body: ReplyBody, sizes: JdwpIdSizes) -> result::Result<Self> {
+        let mut prefix = [0u8; 5];
+        body.read_exact(&mut prefix).await?;
+        let tag = Tag::read_be(&mut Cursor::new(&prefix[..1])).map_err(|e| {
+            result::Error::ParsingError {
+                message: format!("Binary parsing error: {:?}", e),
+            }
+        })?;
+        let length = i32::from_be_bytes([prefix[1], prefix[2], prefix[3], prefix[4]]);
+        Ok(ArrayRegionReader {
+            body,
+            sizes,
+            tag,
+            remaining: length.max(0) as usize,
+        })
+    }
+
+    /// Tag of the elements, an object tag such as [Tag::Object] for arrays of references
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    /// Number of elements not read yet
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+
+    /// Reads the next `max` elements, or the remaining ones if fewer. None once all elements
+    /// were read.
+    pub async fn next_values(&mut self, max: usize) -> result::Result<Option<ArrayValues>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        let count = self.remaining.min(max.max(1));
+        let element_size = match self.tag {
+            Tag::Boolean | Tag::Byte => 1,
+            Tag::Char | Tag::Short => 2,
+            Tag::Int | Tag::Float => 4,
+            Tag::Long | Tag::Double => 8,
+            _ => 1 + self.sizes.object_id_size as usize,
+        };
+
+        // The elements are decoded as a region of their own
+        let mut region = vec![self.tag as u8];
+        region.extend_from_slice(&(count as i32).to_be_bytes());
+        let start = region.len();
+        region.resize(start + count * element_size, 0);
+        self.body.read_exact(&mut region[start..]).await?;
+        self.remaining -= count;
+
+        let values =
+            ArrayValues::read_be_args(&mut Cursor::new(&region), self.sizes).map_err(|e| {
+                result::Error::ParsingError {
+                    message: format!("Binary parsing error: {:?}", e),
+                }
+            })?;
+        Ok(Some(values))
+    }
+}
+
+impl JdwpClient {
+    /// Sends an encoded command whose reply may be too large to buffer, returning the data of
+    /// the reply as a [ReplyBody]. Replies shorter than [STREAMING_THRESHOLD] are buffered as
+    /// usual. The command isn't retried.
+    pub async fn send_streaming(&self, command: Command, data: &[u8]) -> result::Result<ReplyBody> {
+        let (body, chunks) = mpsc::channel(BODY_CHANNEL_CAPACITY);
+        let reply = self
+            .send_request_once(command, data, self.timeout_duration, None, Some(body), None)
+            .await?;
+        if !reply.header.is_success() {
+            return Err(result::Error::from_error_code(reply.header.error_code));
+        }
+        let length = (reply.header.length as usize).saturating_sub(PACKET_HEADER_LENGTH);
+        if reply.data.len() == length {
+            Ok(ReplyBody::buffered(reply.data))
+        } else {
+            Ok(ReplyBody::streamed(length, chunks))
+        }
+    }
+
+    /// Values of a region of an array, decoded as they arrive, so multi-hundred-MB arrays can
+    /// be processed without buffering them entirely
+    pub async fn array_get_values_streaming(
+        &self,
+        array_id: VariableLengthId,
+        first_index: i32,
+        length: i32,
+    ) -> result::Result<ArrayRegionReader> {
+        let data = self.encode_variable_out_data(&ArrayGetValuesOut {
+            array_id,
+            first_index,
+            length,
+        })?;
+        let body = self
+            .send_streaming(Command::ArrayReferenceGetValues, &data)
+            .await?;
+        ArrayRegionReader::new(body, self.id_sizes()?).await
+    }
+}
@@ -1,19 +1,171 @@
-#[derive(Debug)]
-pub enum JdwpErrorCode {}
+#[cfg(feature = "tokio")]
+use crate::ExceptionHandle;
+use crate::{Command, Tag, VariableLengthId, binrw_enum};
+
+binrw_enum! {
+    #[repr(u16)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum JdwpErrorCode {
+        InvalidThread = 10,
+        InvalidThreadGroup = 11,
+        InvalidPriority = 12,
+        ThreadNotSuspended = 13,
+        ThreadSuspended = 14,
+        ThreadNotAlive = 15,
+        InvalidObject = 20,
+        InvalidClass = 21,
+        ClassNotPrepared = 22,
+        InvalidMethodId = 23,
+        InvalidLocation = 24,
+        InvalidFieldId = 25,
+        InvalidFrameId = 30,
+        NoMoreFrames = 31,
+        OpaqueFrame = 32,
+        NotCurrentFrame = 33,
+        TypeMismatch = 34,
+        InvalidSlot = 35,
+        Duplicate = 40,
+        NotFound = 41,
+        InvalidModule = 42,
+        InvalidMonitor = 50,
+        NotMonitorOwner = 51,
+        Interrupt = 52,
+        InvalidClassFormat = 60,
+        CircularClassDefinition = 61,
+        FailsVerification = 62,
+        AddMethodNotImplemented = 63,
+        SchemaChangeNotImplemented = 64,
+        InvalidTypestate = 65,
+        HierarchyChangeNotImplemented = 66,
+        DeleteMethodNotImplemented = 67,
+        UnsupportedVersion = 68,
+        NamesDontMatch = 69,
+        ClassModifiersChangeNotImplemented = 70,
+        MethodModifiersChangeNotImplemented = 71,
+        ClassAttributeChangeNotImplemented = 72,
+        NotImplemented = 99,
+        NullPointer = 100,
+        AbsentInformation = 101,
+        InvalidEventType = 102,
+        IllegalArgument = 103,
+        OutOfMemory = 110,
+        AccessDenied = 111,
+        VmDead = 112,
+        Internal = 113,
+        UnattachedThread = 115,
+        InvalidTag = 500,
+        AlreadyInvoking = 502,
+        InvalidIndex = 503,
+        InvalidLength = 504,
+        InvalidString = 506,
+        InvalidClassLoader = 507,
+        InvalidArray = 508,
+        TransportLoad = 509,
+        TransportInit = 510,
+        NativeMethod = 511,
+        InvalidCount = 512,
+    }
+}
 
 #[derive(Debug)]
 pub enum Error {
     IoError(std::io::Error),
     JdwpError(JdwpErrorCode),
-    ParsingError { message: String },
+    /// The VM replied with an error code not defined by the specification
+    UnknownJdwpError {
+        code: u16,
+    },
+    ParsingError {
+        message: String,
+    },
+    /// A reply decoded without errors doesn't match its packet, see [crate::Conformance]
+    ReplyParseError(ReplyParseError),
     IdSizesUnknown,
-    IdSizesTruncated,
+    /// The VM replied to IDSizes with a size a [VariableLengthId] can't hold
+    InvalidIdSize {
+        name: &'static str,
+        size: i32,
+    },
+    InvalidArgument {
+        message: String,
+    },
+    ClassNotFound {
+        signature: String,
+    },
+    MethodNotFound {
+        name: String,
+        signature: String,
+    },
+    FieldNotFound {
+        name: String,
+    },
+    /// An invoked method or constructor threw an exception in the debuggee
+    #[cfg(feature = "tokio")]
+    InvocationException {
+        exception: ExceptionHandle,
+    },
+    /// A value sent by the VM has a different type than the one it's converted to
+    TypeMismatch {
+        expected: &'static str,
+        found: Tag,
+    },
+    /// A command which requires a suspended thread was about to be sent for a thread the client
+    /// doesn't know to be suspended
+    ThreadNotSuspended {
+        thread: VariableLengthId,
+        required_by: Command,
+    },
+    /// A frame was used after its thread was resumed, which invalidates the frame ids read while
+    /// it was suspended
+    StaleFrame {
+        thread: VariableLengthId,
+        frame_id: VariableLengthId,
+    },
+    /// A helper needs a suspended thread to invoke methods in, but the client knows of none
+    NoSuspendedThread,
+    /// Nothing was listening on the address before the deadline ran out
+    ConnectionRefused {
+        address: String,
+        attempts: u32,
+    },
+    /// Something accepted the connection but didn't complete the JDWP handshake
+    HandshakeFailed {
+        address: String,
+        message: String,
+    },
+}
+
+/// Ways in which a reply can be malformed without failing to decode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplyParseError {
+    /// The data of the reply is longer than its contents, e.g. because the reply has a field
+    /// the client doesn't know of. `expected` is the length of the data according to the
+    /// header, `actual` the number of bytes decoded.
+    TrailingBytes { expected: usize, actual: usize },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+impl Error {
+    /// Maps a non-zero error code from a reply header to an error
+    pub fn from_error_code(code: u16) -> Self {
+        match JdwpErrorCode::try_from(code) {
+            Ok(code) => Error::JdwpError(code),
+            Err(code) => Error::UnknownJdwpError { code },
+        }
+    }
+}
+
 impl From<std::io::Error> for Error {
     fn from(value: std::io::Error) -> Self {
         Error::IoError(value)
     }
 }
+
+impl From<jdwp_codec::Error> for Error {
+    fn from(value: jdwp_codec::Error) -> Self {
+        Error::ParsingError {
+            message: value.to_string(),
+        }
+    }
+}
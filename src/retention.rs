@@ -0,0 +1,229 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::{
+    ArrayValues, JdwpClient, JdwpValue, Tag, TaggedObjectId, VariableLengthId, result,
+    signature_to_type_name,
+};
+
+const ACC_STATIC: i32 = 0x0008;
+
+/// Bounds of the search of [JdwpClient::path_to_roots_with]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionLimits {
+    /// Longest path followed, in references
+    pub max_depth: usize,
+    /// Referrers followed per object
+    pub max_referrers: i32,
+    /// Paths returned
+    pub max_paths: usize,
+}
+impl Default for RetentionLimits {
+    fn default() -> Self {
+        RetentionLimits {
+            max_depth: 12,
+            max_referrers: 16,
+            max_paths: 4,
+        }
+    }
+}
+
+/// A reference on a [RetentionPath]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetainingReference {
+    /// The object holding the reference, a class object for static fields
+    pub referrer: TaggedObjectId,
+    /// Type of the referrer, e.g. `java.util.HashMap$Node`, or for a class object the class
+    /// declaring the static field
+    pub class_name: String,
+    /// The field (`next`) or array element (`[3]`) holding the reference, None if it couldn't
+    /// be found, e.g. for references held by the VM itself
+    pub field: Option<String>,
+}
+
+/// A chain of references keeping an object alive, see [JdwpClient::path_to_roots]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionPath {
+    /// Starts at the referrer of the object and ends at the outermost referrer found
+    pub references: Vec<RetainingReference>,
+    /// True if the outermost referrer is a root: a class holding the reference in a static
+    /// field, or an object no other object references, which only stacks, JNI or the VM can
+    /// keep alive. False if the search stopped at the depth limit.
+    pub reaches_root: bool,
+}
+
+impl JdwpClient {
+    /// Explains why an object is retained, with [RetentionLimits::default]
+    pub async fn path_to_roots(
+        &self,
+        object: TaggedObjectId,
+    ) -> result::Result<Vec<RetentionPath>> {
+        self.path_to_roots_with(object, RetentionLimits::default())
+            .await
+    }
+
+    /// Explains why an object is retained by following its referrers outwards, shortest
+    /// paths first. Every object is visited once, so paths sharing a referrer are reported
+    /// once. Requires the canGetInstanceInfo capability; suspend the VM first so references
+    /// don't change during the search.
+    pub async fn path_to_roots_with(
+        &self,
+        object: TaggedObjectId,
+        limits: RetentionLimits,
+    ) -> result::Result<Vec<RetentionPath>> {
+        let mut paths = Vec::new();
+        let mut visited = HashSet::from([object.object_id]);
+        let mut pending = VecDeque::from([(object, Vec::<RetainingReference>::new())]);
+        while let Some((current, references)) = pending.pop_front() {
+            if paths.len() >= limits.max_paths {
+                break;
+            }
+            if references.len() >= limits.max_depth {
+                paths.push(RetentionPath {
+                    references,
+                    reaches_root: false,
+                });
+                continue;
+            }
+
+            let referrers = self
+                .object_get_referring_objects(current.object_id, limits.max_referrers)
+                .await?
+                .referrers;
+            if referrers.is_empty() {
+                paths.push(RetentionPath {
+                    references,
+                    reaches_root: true,
+                });
+                continue;
+            }
+            for referrer in referrers {
+                if !visited.insert(referrer.object_id) {
+                    continue;
+                }
+                let mut references = references.clone();
+                references.push(self.describe_reference(referrer, current).await?);
+                if referrer.tag == Tag::ClassObject {
+                    paths.push(RetentionPath {
+                        references,
+                        reaches_root: true,
+                    });
+                } else {
+                    pending.push_back((referrer, references));
+                }
+            }
+        }
+        paths.truncate(limits.max_paths);
+        Ok(paths)
+    }
+
+    /// Finds the field or array element of `referrer` holding `target`
+    async fn describe_reference(
+        &self,
+        referrer: TaggedObjectId,
+        target: TaggedObjectId,
+    ) -> result::Result<RetainingReference> {
+        let holds_target = |value: &JdwpValue| match value {
+            JdwpValue::Object(object) => object.object_id == target.object_id,
+            _ => false,
+        };
+
+        if referrer.tag == Tag::ClassObject {
+            let type_id = self
+                .class_object_get_reflected_type(referrer.object_id)
+                .await?
+                .type_id;
+            let fields: Vec<_> = self
+                .ref_type_get_fields(type_id)
+                .await?
+                .fields
+                .into_iter()
+                .filter(|field| {
+                    field.mod_bits & ACC_STATIC != 0 && holds_reference(&field.signature.string)
+                })
+                .collect();
+            let mut field = None;
+            if !fields.is_empty() {
+                let ids = fields.iter().map(|field| field.field_id).collect();
+                let values = self.ref_type_get_values(type_id, ids).await?.values;
+                field = fields
+                    .into_iter()
+                    .zip(values)
+                    .find(|(_, value)| holds_target(value))
+                    .map(|(field, _)| field.name.string);
+            }
+            return Ok(RetainingReference {
+                referrer,
+                class_name: self.type_name(type_id).await?,
+                field,
+            });
+        }
+
+        let type_id = self
+            .object_get_reference_type(referrer.object_id)
+            .await?
+            .type_id;
+        let class_name = self.type_name(type_id).await?;
+        let field = if referrer.tag == Tag::Array {
+            match self.read_array(referrer.object_id).await? {
+                ArrayValues::Objects(elements) => elements
+                    .iter()
+                    .position(|element| element.object_id == target.object_id)
+                    .map(|index| format!("[{}]", index)),
+                ArrayValues::Primitive(_) => None,
+            }
+        } else {
+            self.instance_field_holding(referrer.object_id, type_id, holds_target)
+                .await?
+        };
+        Ok(RetainingReference {
+            referrer,
+            class_name,
+            field,
+        })
+    }
+
+    /// Name of the instance field of the object, declared by its class or a superclass, whose
+    /// value matches
+    async fn instance_field_holding(
+        &self,
+        object_id: VariableLengthId,
+        type_id: VariableLengthId,
+        matches: impl Fn(&JdwpValue) -> bool,
+    ) -> result::Result<Option<String>> {
+        let mut current = type_id;
+        while current.value != 0 {
+            let fields: Vec<_> = self
+                .ref_type_get_fields(current)
+                .await?
+                .fields
+                .into_iter()
+                .filter(|field| {
+                    field.mod_bits & ACC_STATIC == 0 && holds_reference(&field.signature.string)
+                })
+                .collect();
+            if !fields.is_empty() {
+                let ids = fields.iter().map(|field| field.field_id).collect();
+                let values = self.object_get_values(object_id, ids).await?.values;
+                if let Some((field, _)) = fields
+                    .into_iter()
+                    .zip(values)
+                    .find(|(_, value)| matches(value))
+                {
+                    return Ok(Some(field.name.string));
+                }
+            }
+            current = self.class_type_get_superclass(current).await?.superclass;
+        }
+        Ok(None)
+    }
+
+    async fn type_name(&self, type_id: VariableLengthId) -> result::Result<String> {
+        let signature = self.ref_type_get_signature(type_id).await?.signature.string;
+        Ok(signature_to_type_name(&signature))
+    }
+}
+
+/// Whether a field of the JNI signature holds references
+fn holds_reference(signature: &str) -> bool {
+    signature.starts_with('L') || signature.starts_with('[')
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::JdwpErrorCode;
+
+/// How often and how fast to retry a command that failed with a particular error code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryRule {
+    /// Number of retries after the first attempt
+    pub max_retries: u32,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay, which doubles after every retry
+    pub max_backoff: Duration,
+}
+impl RetryRule {
+    pub fn new(max_retries: u32, initial_backoff: Duration) -> Self {
+        RetryRule {
+            max_retries,
+            initial_backoff,
+            max_backoff: initial_backoff * 16,
+        }
+    }
+
+    /// Delay before retry number `attempt` (starting at 0)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(1u32 << attempt.min(31))
+            .min(self.max_backoff)
+    }
+}
+
+/// Per error code retry configuration of a client. The default policy never retries.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RetryPolicy {
+    rules: HashMap<JdwpErrorCode, RetryRule>,
+}
+impl RetryPolicy {
+    /// A policy which never retries
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Retries errors commonly caused by racing a resume: THREAD_NOT_SUSPENDED and
+    /// INVALID_FRAMEID, 3 times starting with a 10 ms backoff
+    pub fn transient() -> Self {
+        let rule = RetryRule::new(3, Duration::from_millis(10));
+        Self::none()
+            .with_rule(JdwpErrorCode::ThreadNotSuspended, rule)
+            .with_rule(JdwpErrorCode::InvalidFrameId, rule)
+    }
+
+    pub fn with_rule(mut self, code: JdwpErrorCode, rule: RetryRule) -> Self {
+        self.rules.insert(code, rule);
+        self
+    }
+
+    pub fn without_rule(mut self, code: JdwpErrorCode) -> Self {
+        self.rules.remove(&code);
+        self
+    }
+
+    pub fn rule(&self, code: JdwpErrorCode) -> Option<&RetryRule> {
+        self.rules.get(&code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_max() {
+        let rule = RetryRule {
+            max_retries: 10,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(50),
+        };
+        assert_eq!(rule.backoff(0), Duration::from_millis(10));
+        assert_eq!(rule.backoff(1), Duration::from_millis(20));
+        assert_eq!(rule.backoff(2), Duration::from_millis(40));
+        assert_eq!(rule.backoff(3), Duration::from_millis(50));
+        assert_eq!(rule.backoff(40), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_transient_policy() {
+        let policy = RetryPolicy::transient();
+        assert!(policy.rule(JdwpErrorCode::ThreadNotSuspended).is_some());
+        assert!(policy.rule(JdwpErrorCode::InvalidFrameId).is_some());
+        assert!(policy.rule(JdwpErrorCode::InvalidObject).is_none());
+        assert!(
+            RetryPolicy::none()
+                .rule(JdwpErrorCode::InvalidFrameId)
+                .is_none()
+        );
+    }
+}
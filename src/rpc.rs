@@ -0,0 +1,294 @@
+//! Remote control of a [Debugger] over JSON-RPC 2.0, so the debugger can run next to the VM
+//! while frontends connect from elsewhere
+//!
+//! Requests and responses are JSON objects, one per line, over a TCP connection. All
+//! connections share the debugger, and requests are handled one at a time, so a
+//! `execution.runToBreakpoint` holds back requests of other connections until it returns.
+//!
+//! | Method | Params | Result |
+//! |---|---|---|
+//! | `vm.version` | | `{description, jdwpMajor, jdwpMinor, vmVersion, vmName}` |
+//! | `threads.list` | | `[{id, name}]` |
+//! | `classes.find` | `{name}` | `{id}` |
+//! | `breakpoints.set` | `{className, line, condition?, stratum?}` | `{id}` |
+//! | `breakpoints.clear` | `{id}` | `null` |
+//! | `breakpoints.list` | | `[{id, className, line}]` |
+//! | `execution.stop` | | stop or `null` |
+//! | `execution.continue` | | `null` |
+//! | `execution.runToBreakpoint` | | stop |
+//! | `execution.stepOver` | | stop |
+//! | `session.detach` | | `null` |
+//! | `session.kill` | `{exitCode?}` | `null` |
+//!
+//! A stop is `{thread, classId, methodId, index, breakpoint}`, with a null breakpoint at the
+//! end of a step. After `session.detach` or `session.kill` every request fails.
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::Mutex;
+
+use crate::debugger::BreakpointId;
+use crate::json::Json;
+use crate::{BreakpointSpec, Debugger, Stop, result, type_name_to_signature};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Failures of the debugger, such as JDWP errors
+const SERVER_ERROR: i64 = -32000;
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+impl From<result::Error> for RpcError {
+    fn from(value: result::Error) -> Self {
+        RpcError {
+            code: SERVER_ERROR,
+            message: format!("{:?}", value),
+        }
+    }
+}
+
+/// Serves a [Debugger] to JSON-RPC clients, see the [module documentation](self)
+pub struct RpcServer {
+    listener: TcpListener,
+    debugger: Arc<Mutex<Option<Debugger>>>,
+}
+
+impl RpcServer {
+    pub async fn bind(address: impl ToSocketAddrs, debugger: Debugger) -> io::Result<Self> {
+        Ok(RpcServer {
+            listener: TcpListener::bind(address).await?,
+            debugger: Arc::new(Mutex::new(Some(debugger))),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts connections until accepting fails
+    pub async fn run(self) -> io::Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            let debugger = self.debugger.clone();
+            tokio::spawn(async move {
+                if let Err(e) = serve(stream, debugger).await {
+                    eprintln!("RPC connection error: {:?}", e);
+                }
+            });
+        }
+    }
+}
+
+async fn serve(stream: TcpStream, debugger: Arc<Mutex<Option<Debugger>>>) -> io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = handle(&line, &debugger).await {
+            writer
+                .write_all(format!("{}\n", response).as_bytes())
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// The response to a request, None for notifications
+async fn handle(line: &str, debugger: &Mutex<Option<Debugger>>) -> Option<Json> {
+    let request = match Json::parse(line) {
+        Ok(request) => request,
+        Err(message) => return Some(error_response(Json::Null, PARSE_ERROR, message)),
+    };
+    let id = request.get("id").cloned();
+    let method = match (
+        request.get("jsonrpc").and_then(Json::as_str),
+        request.get("method").and_then(Json::as_str),
+    ) {
+        (Some("2.0"), Some(method)) => method,
+        _ => {
+            return Some(error_response(
+                id.unwrap_or(Json::Null),
+                INVALID_REQUEST,
+                String::from("Not a JSON-RPC 2.0 request"),
+            ));
+        }
+    };
+    let params = request.get("params").cloned().unwrap_or(Json::Null);
+
+    let result = call(debugger, method, &params).await;
+    let id = id?;
+    Some(match result {
+        Ok(result) => Json::object([
+            ("jsonrpc", Json::string("2.0")),
+            ("id", id),
+            ("result", result),
+        ]),
+        Err(error) => error_response(id, error.code, error.message),
+    })
+}
+
+async fn call(
+    debugger: &Mutex<Option<Debugger>>,
+    method: &str,
+    params: &Json,
+) -> Result<Json, RpcError> {
+    let mut guard = debugger.lock().await;
+    let Some(session) = guard.as_mut() else {
+        return Err(RpcError {
+            code: SERVER_ERROR,
+            message: String::from("The session has ended"),
+        });
+    };
+    let client = session.client();
+
+    match method {
+        "vm.version" => {
+            let version = client.vm_get_version().await?;
+            Ok(Json::object([
+                ("description", Json::string(version.description.string)),
+                ("jdwpMajor", Json::number(version.jdwp_major)),
+                ("jdwpMinor", Json::number(version.jdwp_minor)),
+                ("vmVersion", Json::string(version.vm_version.string)),
+                ("vmName", Json::string(version.vm_name.string)),
+            ]))
+        }
+        "threads.list" => {
+            let mut threads = Vec::new();
+            for thread in client.vm_get_all_threads().await?.threads {
+                threads.push(Json::object([
+                    ("id", Json::number(thread.thread_id.value)),
+                    (
+                        "name",
+                        Json::string(client.thread_name(thread.thread_id).await?),
+                    ),
+                ]));
+            }
+            Ok(Json::Array(threads))
+        }
+        "classes.find" => {
+            let name = str_param(params, "name")?;
+            let class_id = client
+                .find_loaded_class(&type_name_to_signature(name))
+                .await?;
+            Ok(Json::object([("id", Json::number(class_id.value))]))
+        }
+        "breakpoints.set" => {
+            let mut spec = BreakpointSpec::new(
+                str_param(params, "className")?,
+                int_param(params, "line")? as i32,
+            );
+            if let Some(condition) = params.get("condition").and_then(Json::as_str) {
+                spec = spec.condition(condition);
+            }
+            if let Some(stratum) = params.get("stratum").and_then(Json::as_str) {
+                spec = spec.stratum(stratum);
+            }
+            let id = session.set_breakpoint_with(spec).await?;
+            Ok(Json::object([("id", Json::number(id.0))]))
+        }
+        "breakpoints.clear" => {
+            let id = BreakpointId(int_param(params, "id")? as u32);
+            session.clear_breakpoint(id).await?;
+            Ok(Json::Null)
+        }
+        "breakpoints.list" => Ok(Json::Array(
+            session
+                .breakpoint_ids()
+                .into_iter()
+                .filter_map(|id| {
+                    let spec = session.breakpoint_spec(id)?;
+                    Some(Json::object([
+                        ("id", Json::number(id.0)),
+                        ("className", Json::string(spec.class_name.clone())),
+                        ("line", Json::number(spec.line)),
+                    ]))
+                })
+                .collect(),
+        )),
+        "execution.stop" => Ok(session.stop().map_or(Json::Null, stop_json)),
+        "execution.continue" => {
+            session.continue_().await?;
+            Ok(Json::Null)
+        }
+        "execution.runToBreakpoint" => Ok(stop_json(&session.run_to_breakpoint().await?)),
+        "execution.stepOver" => Ok(stop_json(&session.step_over().await?)),
+        "session.detach" => {
+            if let Some(session) = guard.take() {
+                session.detach().await?;
+            }
+            Ok(Json::Null)
+        }
+        "session.kill" => {
+            let exit_code = match params.get("exitCode") {
+                Some(_) => int_param(params, "exitCode")? as i32,
+                None => 1,
+            };
+            if let Some(session) = guard.take() {
+                session.kill(exit_code).await?;
+            }
+            Ok(Json::Null)
+        }
+        _ => Err(RpcError {
+            code: METHOD_NOT_FOUND,
+            message: format!("Unknown method {}", method),
+        }),
+    }
+}
+
+fn stop_json(stop: &Stop) -> Json {
+    Json::object([
+        ("thread", Json::number(stop.thread.value)),
+        ("classId", Json::number(stop.location.class_id.value)),
+        ("methodId", Json::number(stop.location.method_id.value)),
+        ("index", Json::number(stop.location.index)),
+        (
+            "breakpoint",
+            stop.breakpoint.map_or(Json::Null, |id| Json::number(id.0)),
+        ),
+    ])
+}
+
+fn str_param<'a>(params: &'a Json, name: &str) -> Result<&'a str, RpcError> {
+    params
+        .get(name)
+        .and_then(Json::as_str)
+        .ok_or_else(|| invalid_param(name, "a string"))
+}
+
+fn int_param(params: &Json, name: &str) -> Result<i64, RpcError> {
+    params
+        .get(name)
+        .and_then(Json::as_i64)
+        .ok_or_else(|| invalid_param(name, "an integer"))
+}
+
+fn invalid_param(name: &str, expected: &str) -> RpcError {
+    RpcError {
+        code: INVALID_PARAMS,
+        message: format!("Parameter {} must be {}", name, expected),
+    }
+}
+
+fn error_response(id: Json, code: i64, message: String) -> Json {
+    Json::object([
+        ("jsonrpc", Json::string("2.0")),
+        ("id", id),
+        (
+            "error",
+            Json::object([
+                ("code", Json::number(code)),
+                ("message", Json::String(message)),
+            ]),
+        ),
+    ])
+}
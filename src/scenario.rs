@@ -0,0 +1,669 @@
+use binrw::{BinRead, BinWrite, Endian};
+use std::collections::VecDeque;
+use std::io::Cursor;
+
+use crate::mock::{MockResponder, MockResponse};
+use crate::{
+    ClassStatus, Command, CommandPacket, Event, EventComposite, EventKind, EventModifier,
+    EventRequestClearOut, EventRequestSetOut, JdwpErrorCode, JdwpIdSizes, JdwpString, Location,
+    MockJdwpTransport, SuspendPolicy, TypeTag, VariableLengthId, encode_command, encode_reply,
+    type_name_to_signature,
+};
+
+const SIZES: JdwpIdSizes = JdwpIdSizes {
+    field_id_size: 8,
+    method_id_size: 8,
+    object_id_size: 8,
+    reference_type_id_size: 8,
+    frame_id_size: 8,
+};
+
+const FIRST_THREAD_ID: u64 = 0x100;
+const FIRST_CLASS_ID: u64 = 0x1000;
+const FIRST_METHOD_ID: u64 = 0x2000;
+/// Packet ids of the events sent by the scenario
+const FIRST_EVENT_PACKET_ID: u32 = 0x6000_0000;
+
+/// A method of a [MockClass] with its line table
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockMethod {
+    name: String,
+    signature: String,
+    /// Lines and the code index each starts at
+    lines: Vec<(i32, u64)>,
+}
+impl MockMethod {
+    /// A method by its name and JNI signature, e.g. `main` and `([Ljava/lang/String;)V`
+    pub fn new(name: &str, signature: &str) -> Self {
+        MockMethod {
+            name: String::from(name),
+            signature: String::from(signature),
+            lines: Vec::new(),
+        }
+    }
+
+    /// Adds a line whose code starts at `code_index`
+    pub fn line(mut self, line: i32, code_index: u64) -> Self {
+        self.lines.push((line, code_index));
+        self
+    }
+}
+
+/// A class of a [MockScenario]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockClass {
+    name: String,
+    source_file: Option<String>,
+    methods: Vec<MockMethod>,
+    loaded: bool,
+}
+impl MockClass {
+    /// A class by its name, e.g. `com.example.Main`, loaded from the start
+    pub fn new(name: &str) -> Self {
+        MockClass {
+            name: String::from(name),
+            source_file: None,
+            methods: Vec::new(),
+            loaded: true,
+        }
+    }
+
+    pub fn source_file(mut self, source_file: &str) -> Self {
+        self.source_file = Some(String::from(source_file));
+        self
+    }
+
+    pub fn method(mut self, method: MockMethod) -> Self {
+        self.methods.push(method);
+        self
+    }
+
+    /// Leaves the class unloaded until [MockScenario::load_class] loads it
+    pub fn loaded_later(mut self) -> Self {
+        self.loaded = false;
+        self
+    }
+}
+
+struct ScenarioClass {
+    class: MockClass,
+    id: VariableLengthId,
+    method_ids: Vec<VariableLengthId>,
+}
+impl ScenarioClass {
+    fn signature(&self) -> String {
+        type_name_to_signature(&self.class.name)
+    }
+
+    fn method(&self, method_id: VariableLengthId) -> Option<&MockMethod> {
+        let index = self.method_ids.iter().position(|id| *id == method_id)?;
+        self.class.methods.get(index)
+    }
+}
+
+/// What the program of a scenario does once resumed
+#[derive(Debug, Clone, Copy)]
+enum Action {
+    Reach {
+        thread: VariableLengthId,
+        class: usize,
+        location: Location,
+    },
+    LoadClass {
+        thread: VariableLengthId,
+        class: usize,
+    },
+    Exit,
+}
+
+struct ScenarioRequest {
+    id: i32,
+    out: EventRequestSetOut,
+    /// Occurrences left to skip for a Count modifier
+    skip: Option<i32>,
+}
+
+/// A fake VM with classes, threads and a program, for integration tests of debugging features
+/// without a JVM
+///
+/// The VM starts suspended and reports VM_START. Whenever a thread or the VM is resumed, the
+/// program runs until it does something an event request asks for: a thread reaching a line
+/// is reported to the breakpoint requests at its location and to the step requests of the
+/// thread, a class being loaded to the class prepare requests matching it. Whatever no request
+/// asks for passes silently, and at the end of the program the VM idles, or dies if the
+/// program exits. Steps of any depth complete at the next line the thread reaches.
+///
+/// The VM answers the commands describing its classes and threads, and sets, clears and counts
+/// event requests. Other commands are answered with NOT_IMPLEMENTED. Ids are 8 bytes long.
+pub struct MockScenario {
+    classes: Vec<ScenarioClass>,
+    threads: Vec<(String, VariableLengthId)>,
+    actions: VecDeque<Action>,
+    next_method_id: u64,
+}
+impl MockScenario {
+    pub fn new() -> Self {
+        MockScenario {
+            classes: Vec::new(),
+            threads: Vec::new(),
+            actions: VecDeque::new(),
+            next_method_id: FIRST_METHOD_ID,
+        }
+    }
+
+    pub fn class(mut self, class: MockClass) -> Self {
+        let method_ids = class
+            .methods
+            .iter()
+            .map(|_| {
+                self.next_method_id += 1;
+                VariableLengthId {
+                    value: self.next_method_id,
+                }
+            })
+            .collect();
+        self.classes.push(ScenarioClass {
+            class,
+            id: VariableLengthId {
+                value: FIRST_CLASS_ID + self.classes.len() as u64,
+            },
+            method_ids,
+        });
+        self
+    }
+
+    pub fn thread(mut self, name: &str) -> Self {
+        let id = VariableLengthId {
+            value: FIRST_THREAD_ID + self.threads.len() as u64,
+        };
+        self.threads.push((String::from(name), id));
+        self
+    }
+
+    pub fn thread_id(&self, name: &str) -> Option<VariableLengthId> {
+        self.threads
+            .iter()
+            .find(|(thread, _)| thread == name)
+            .map(|(_, id)| *id)
+    }
+
+    pub fn class_id(&self, name: &str) -> Option<VariableLengthId> {
+        self.class_index(name).map(|index| self.classes[index].id)
+    }
+
+    /// Location of the first code index of a line of a method
+    pub fn location(&self, class: &str, method: &str, line: i32) -> Option<Location> {
+        let class = &self.classes[self.class_index(class)?];
+        let index = class
+            .class
+            .methods
+            .iter()
+            .position(|candidate| candidate.name == method)?;
+        let code_index = class.class.methods[index]
+            .lines
+            .iter()
+            .filter(|(candidate, _)| *candidate == line)
+            .map(|(_, code_index)| *code_index)
+            .min()?;
+        Some(Location {
+            type_tag: TypeTag::Class,
+            class_id: class.id,
+            method_id: class.method_ids[index],
+            index: code_index,
+        })
+    }
+
+    /// Makes a thread reach a line of a method
+    ///
+    /// # Panics
+    /// If the thread, the class, the method or the line wasn't declared
+    pub fn reach(mut self, thread: &str, class: &str, method: &str, line: i32) -> Self {
+        let action = Action::Reach {
+            thread: self.expect_thread(thread),
+            class: self.expect_class(class),
+            location: self
+                .location(class, method, line)
+                .unwrap_or_else(|| panic!("No line {} in {}.{}", line, class, method)),
+        };
+        self.actions.push_back(action);
+        self
+    }
+
+    /// Makes a thread load a class declared with [MockClass::loaded_later]
+    ///
+    /// # Panics
+    /// If the thread or the class wasn't declared
+    pub fn load_class(mut self, thread: &str, class: &str) -> Self {
+        let action = Action::LoadClass {
+            thread: self.expect_thread(thread),
+            class: self.expect_class(class),
+        };
+        self.actions.push_back(action);
+        self
+    }
+
+    /// Ends the program, which makes the VM report VM_DEATH and close the connection
+    pub fn exit(mut self) -> Self {
+        self.actions.push_back(Action::Exit);
+        self
+    }
+
+    /// The transport to connect a client to. A thread named `main` is added if none was
+    /// declared, as VM_START is reported in a thread.
+    pub fn build(mut self) -> MockJdwpTransport {
+        if self.threads.is_empty() {
+            self = self.thread("main");
+        }
+        let mut vm = ScenarioVm {
+            classes: self.classes,
+            threads: self.threads,
+            actions: self.actions,
+            requests: Vec::new(),
+            next_request_id: 0,
+            next_packet_id: FIRST_EVENT_PACKET_ID,
+        };
+        let vm_start = vm.event_packet(&EventComposite {
+            suspend_policy: SuspendPolicy::All,
+            events: vec![Event::VmStart {
+                request_id: 0,
+                thread: vm.threads[0].1,
+            }],
+        });
+        MockJdwpTransport::with_id_sizes(SIZES)
+            .send_packet(&vm_start)
+            .with_responder(vm)
+    }
+
+    fn class_index(&self, name: &str) -> Option<usize> {
+        self.classes
+            .iter()
+            .position(|class| class.class.name == name)
+    }
+
+    fn expect_thread(&self, name: &str) -> VariableLengthId {
+        self.thread_id(name)
+            .unwrap_or_else(|| panic!("Unknown thread {}", name))
+    }
+
+    fn expect_class(&self, name: &str) -> usize {
+        self.class_index(name)
+            .unwrap_or_else(|| panic!("Unknown class {}", name))
+    }
+}
+impl Default for MockScenario {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where an action happens, to match against the modifiers of event requests
+struct Occurrence<'a> {
+    thread: VariableLengthId,
+    class: &'a ScenarioClass,
+    location: Option<Location>,
+}
+
+/// Whether a class name matches a ClassMatch pattern, which may begin or end with '*'
+fn class_matches(pattern: &str, name: &str) -> bool {
+    if let Some(suffix) = pattern.strip_prefix('*') {
+        name.ends_with(suffix)
+    } else if let Some(prefix) = pattern.strip_suffix('*') {
+        name.starts_with(prefix)
+    } else {
+        pattern == name
+    }
+}
+
+/// Ids of the requests of `kind` reporting the occurrence. Count modifiers are counted
+/// down, and requests whose count ran out are removed once they report.
+fn report(
+    requests: &mut Vec<ScenarioRequest>,
+    kind: EventKind,
+    occurrence: &Occurrence,
+) -> Vec<(i32, SuspendPolicy)> {
+    let mut reported = Vec::new();
+    for request in requests.iter_mut() {
+        if request.out.event_kind != kind {
+            continue;
+        }
+        let matches = request.out.modifiers.iter().all(|modifier| match modifier {
+            EventModifier::ThreadOnly(thread) | EventModifier::Step { thread, .. } => {
+                *thread == occurrence.thread
+            }
+            EventModifier::ClassOnly(class_id) => *class_id == occurrence.class.id,
+            EventModifier::ClassMatch(pattern) => {
+                class_matches(pattern, &occurrence.class.class.name)
+            }
+            EventModifier::ClassExclude(pattern) => {
+                !class_matches(pattern, &occurrence.class.class.name)
+            }
+            EventModifier::LocationOnly(location) => Some(*location) == occurrence.location,
+            _ => true,
+        });
+        if !matches {
+            continue;
+        }
+        if let Some(skip) = &mut request.skip {
+            *skip -= 1;
+            if *skip > 0 {
+                continue;
+            }
+        }
+        reported.push((request.id, request.out.suspend_policy));
+    }
+    requests.retain(|request| {
+        request.skip != Some(0) || !reported.iter().any(|(id, _)| *id == request.id)
+    });
+    reported
+}
+
+/// Adds an event to a composite, which suspends as much as the most suspending request
+fn push_event(composite: &mut EventComposite, policy: SuspendPolicy, event: Event) {
+    let rank = |policy| match policy {
+        SuspendPolicy::All => 2,
+        SuspendPolicy::EventThread => 1,
+        _ => 0,
+    };
+    if rank(policy) > rank(composite.suspend_policy) {
+        composite.suspend_policy = policy;
+    }
+    composite.events.push(event);
+}
+
+struct ScenarioVm {
+    classes: Vec<ScenarioClass>,
+    threads: Vec<(String, VariableLengthId)>,
+    actions: VecDeque<Action>,
+    requests: Vec<ScenarioRequest>,
+    next_request_id: i32,
+    next_packet_id: u32,
+}
+impl ScenarioVm {
+    fn event_packet(&mut self, composite: &EventComposite) -> Vec<u8> {
+        let mut data = Cursor::new(Vec::new());
+        composite
+            .write_be_args(&mut data, SIZES)
+            .expect("Events of a scenario can be encoded");
+        self.next_packet_id = self.next_packet_id.wrapping_add(1);
+        encode_command(self.next_packet_id, Command::EventComposite, data.get_ref())
+            .expect("Events of a scenario can be encoded")
+    }
+
+    /// Runs the program until an event is reported. Returns the events, and whether the VM died.
+    fn run(&mut self) -> (Vec<Vec<u8>>, bool) {
+        while let Some(action) = self.actions.pop_front() {
+            let mut composite = EventComposite {
+                suspend_policy: SuspendPolicy::None,
+                events: Vec::new(),
+            };
+            match action {
+                Action::Reach {
+                    thread,
+                    class,
+                    location,
+                } => {
+                    let occurrence = Occurrence {
+                        thread,
+                        class: &self.classes[class],
+                        location: Some(location),
+                    };
+                    let breakpoints =
+                        report(&mut self.requests, EventKind::Breakpoint, &occurrence);
+                    let steps = report(&mut self.requests, EventKind::SingleStep, &occurrence);
+                    for (request_id, policy) in steps {
+                        push_event(
+                            &mut composite,
+                            policy,
+                            Event::SingleStep {
+                                request_id,
+                                thread,
+                                location,
+                            },
+                        );
+                    }
+                    for (request_id, policy) in breakpoints {
+                        push_event(
+                            &mut composite,
+                            policy,
+                            Event::Breakpoint {
+                                request_id,
+                                thread,
+                                location,
+                            },
+                        );
+                    }
+                }
+                Action::LoadClass { thread, class } => {
+                    self.classes[class].class.loaded = true;
+                    let occurrence = Occurrence {
+                        thread,
+                        class: &self.classes[class],
+                        location: None,
+                    };
+                    let prepares = report(&mut self.requests, EventKind::ClassPrepare, &occurrence);
+                    let signature = self.classes[class].signature();
+                    let type_id = self.classes[class].id;
+                    for (request_id, policy) in prepares {
+                        push_event(
+                            &mut composite,
+                            policy,
+                            Event::ClassPrepare {
+                                request_id,
+                                thread,
+                                ref_type_tag: TypeTag::Class,
+                                type_id,
+                                signature: JdwpString {
+                                    string: signature.clone(),
+                                },
+                                status: ClassStatus::VERIFIED | ClassStatus::PREPARED,
+                            },
+                        );
+                    }
+                }
+                Action::Exit => {
+                    composite.events.push(Event::VmDeath { request_id: 0 });
+                    return (vec![self.event_packet(&composite)], true);
+                }
+            }
+
+            if !composite.events.is_empty() {
+                return (vec![self.event_packet(&composite)], false);
+            }
+        }
+        (Vec::new(), false)
+    }
+}
+
+fn read_id(data: &mut Cursor<&[u8]>) -> Result<VariableLengthId, JdwpErrorCode> {
+    VariableLengthId::read_options(data, Endian::Big, 8).map_err(|_| JdwpErrorCode::IllegalArgument)
+}
+
+/// Encodes the data of a reply
+struct ReplyWriter(Cursor<Vec<u8>>);
+impl ReplyWriter {
+    fn new() -> Self {
+        ReplyWriter(Cursor::new(Vec::new()))
+    }
+
+    fn write<T: for<'a> BinWrite<Args<'a> = ()>>(&mut self, value: T) -> &mut Self {
+        value
+            .write_be(&mut self.0)
+            .expect("Replies of a scenario can be encoded");
+        self
+    }
+
+    fn id(&mut self, id: VariableLengthId) -> &mut Self {
+        id.write_options(&mut self.0, Endian::Big, 8)
+            .expect("Replies of a scenario can be encoded");
+        self
+    }
+
+    fn string(&mut self, string: &str) -> &mut Self {
+        self.write(JdwpString {
+            string: String::from(string),
+        })
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>, JdwpErrorCode> {
+        Ok(std::mem::take(self.0.get_mut()))
+    }
+}
+
+impl ScenarioVm {
+    fn class(&self, id: VariableLengthId) -> Result<&ScenarioClass, JdwpErrorCode> {
+        self.classes
+            .iter()
+            .find(|class| class.id == id && class.class.loaded)
+            .ok_or(JdwpErrorCode::InvalidClass)
+    }
+
+    fn thread(&self, id: VariableLengthId) -> Result<&str, JdwpErrorCode> {
+        self.threads
+            .iter()
+            .find(|(_, thread)| *thread == id)
+            .map(|(name, _)| name.as_str())
+            .ok_or(JdwpErrorCode::InvalidThread)
+    }
+
+    /// The data of the reply to a command, or the error the VM replies with
+    fn reply_data(&mut self, packet: &CommandPacket) -> Result<Vec<u8>, JdwpErrorCode> {
+        let mut data = Cursor::new(packet.data.as_slice());
+        let mut reply = ReplyWriter::new();
+        match packet.header.command {
+            Command::VirtualMachineVersion => reply
+                .string("Mock scenario VM")
+                .write(1i32)
+                .write(8i32)
+                .string("1.8.0")
+                .string("Mock VM")
+                .finish(),
+            Command::VirtualMachineClassesBySignature => {
+                let signature = JdwpString::read_be(&mut data)
+                    .map_err(|_| JdwpErrorCode::IllegalArgument)?
+                    .string;
+                let classes: Vec<_> = self
+                    .classes
+                    .iter()
+                    .filter(|class| class.class.loaded && class.signature() == signature)
+                    .collect();
+                reply.write(classes.len() as i32);
+                for class in classes {
+                    reply
+                        .write(TypeTag::Class)
+                        .id(class.id)
+                        .write(ClassStatus::VERIFIED | ClassStatus::PREPARED);
+                }
+                reply.finish()
+            }
+            Command::VirtualMachineAllThreads => {
+                reply.write(self.threads.len() as i32);
+                for (_, thread) in &self.threads {
+                    reply.id(*thread);
+                }
+                reply.finish()
+            }
+            Command::VirtualMachineSuspend
+            | Command::VirtualMachineResume
+            | Command::VirtualMachineDispose
+            | Command::VirtualMachineExit
+            | Command::VirtualMachineHoldEvents
+            | Command::VirtualMachineReleaseEvents
+            | Command::ThreadReferenceSuspend
+            | Command::ThreadReferenceResume => reply.finish(),
+            Command::ReferenceTypeSignature => {
+                let signature = self.class(read_id(&mut data)?)?.signature();
+                reply.string(&signature).finish()
+            }
+            Command::ReferenceTypeSourceFile => {
+                let source_file = self.class(read_id(&mut data)?)?.class.source_file.clone();
+                let source_file = source_file.ok_or(JdwpErrorCode::AbsentInformation)?;
+                reply.string(&source_file).finish()
+            }
+            Command::ReferenceTypeSourceDebugExtension => {
+                self.class(read_id(&mut data)?)?;
+                Err(JdwpErrorCode::AbsentInformation)
+            }
+            Command::ReferenceTypeMethods => {
+                let class = self.class(read_id(&mut data)?)?;
+                reply.write(class.class.methods.len() as i32);
+                for (method, id) in class.class.methods.iter().zip(&class.method_ids) {
+                    reply
+                        .id(*id)
+                        .string(&method.name)
+                        .string(&method.signature)
+                        .write(0i32);
+                }
+                reply.finish()
+            }
+            Command::MethodLineTable => {
+                let class = self.class(read_id(&mut data)?)?;
+                let method = class
+                    .method(read_id(&mut data)?)
+                    .ok_or(JdwpErrorCode::InvalidMethodId)?;
+                let indices = method.lines.iter().map(|(_, code_index)| *code_index);
+                reply
+                    .write(indices.clone().min().unwrap_or(0))
+                    .write(indices.max().unwrap_or(0))
+                    .write(method.lines.len() as i32);
+                for (line, code_index) in &method.lines {
+                    reply.write(*code_index).write(*line);
+                }
+                reply.finish()
+            }
+            Command::ThreadReferenceName => {
+                let name = String::from(self.thread(read_id(&mut data)?)?);
+                reply.string(&name).finish()
+            }
+            Command::EventRequestSet => {
+                let out = EventRequestSetOut::read_be_args(&mut data, SIZES)
+                    .map_err(|_| JdwpErrorCode::IllegalArgument)?;
+                let skip = out.modifiers.iter().find_map(|modifier| match modifier {
+                    EventModifier::Count(count) => Some(*count),
+                    _ => None,
+                });
+                self.next_request_id += 1;
+                let id = self.next_request_id;
+                self.requests.push(ScenarioRequest { id, out, skip });
+                reply.write(id).finish()
+            }
+            Command::EventRequestClear => {
+                let out = EventRequestClearOut::read_be(&mut data)
+                    .map_err(|_| JdwpErrorCode::IllegalArgument)?;
+                self.requests.retain(|request| {
+                    request.id != out.request_id || request.out.event_kind != out.event_kind
+                });
+                reply.finish()
+            }
+            Command::EventRequestClearAllBreakpoints => {
+                self.requests
+                    .retain(|request| request.out.event_kind != EventKind::Breakpoint);
+                reply.finish()
+            }
+            _ => Err(JdwpErrorCode::NotImplemented),
+        }
+    }
+}
+
+impl MockResponder for ScenarioVm {
+    fn respond(&mut self, packet: &CommandPacket) -> Option<MockResponse> {
+        let command = packet.header.command;
+        let (error_code, data) = match self.reply_data(packet) {
+            Ok(data) => (0, data),
+            Err(error) => (error as u16, Vec::new()),
+        };
+        let reply = encode_reply(packet.header.id, error_code, &data)
+            .expect("Replies of a scenario can be encoded");
+
+        let mut packets = vec![reply];
+        let mut close = command == Command::VirtualMachineExit;
+        if matches!(
+            command,
+            Command::VirtualMachineResume | Command::ThreadReferenceResume
+        ) {
+            let (events, died) = self.run();
+            packets.extend(events);
+            close = died;
+        }
+        Some(MockResponse { packets, close })
+    }
+}
@@ -0,0 +1,107 @@
+use std::future::Future;
+
+use tokio::runtime::Handle;
+
+use crate::{JdwpClient, VariableLengthId, result};
+
+/// The VM suspended by [JdwpClient::suspended_scope] until the scope ends
+///
+/// [SuspendedScope::finish] resumes the VM and reports whether that worked. A scope which is
+/// dropped without being finished, e.g. because of an early return or a panic, sends the resume
+/// from a background task instead, so the target isn't left frozen by a tool that gave up.
+#[must_use = "the VM is resumed as soon as the scope is dropped"]
+pub struct SuspendedScope {
+    client: JdwpClient,
+    finished: bool,
+}
+impl SuspendedScope {
+    pub fn client(&self) -> &JdwpClient {
+        &self.client
+    }
+
+    /// Resumes the VM, ending the scope
+    pub async fn finish(mut self) -> result::Result<()> {
+        self.finished = true;
+        self.client.vm_resume().await
+    }
+}
+impl Drop for SuspendedScope {
+    fn drop(&mut self) {
+        if !self.finished {
+            let client = self.client.clone();
+            resume_in_background(async move { client.vm_resume().await });
+        }
+    }
+}
+
+/// A thread suspended by [JdwpClient::suspend_thread_scope] until the scope ends
+///
+/// Every scope suspends and resumes the thread once, and the VM keeps a suspend count per
+/// thread, so scopes of different tools can overlap: the thread runs again when the last of
+/// them ends. Like [SuspendedScope], a dropped scope resumes the thread from a background task.
+#[must_use = "the thread is resumed as soon as the scope is dropped"]
+pub struct ThreadSuspendScope {
+    client: JdwpClient,
+    thread_id: VariableLengthId,
+    finished: bool,
+}
+impl ThreadSuspendScope {
+    pub fn client(&self) -> &JdwpClient {
+        &self.client
+    }
+
+    pub fn thread_id(&self) -> VariableLengthId {
+        self.thread_id
+    }
+
+    /// Resumes the thread once, ending the scope
+    pub async fn finish(mut self) -> result::Result<()> {
+        self.finished = true;
+        self.client.thread_resume(self.thread_id).await
+    }
+}
+impl Drop for ThreadSuspendScope {
+    fn drop(&mut self) {
+        if !self.finished {
+            let client = self.client.clone();
+            let thread_id = self.thread_id;
+            resume_in_background(async move { client.thread_resume(thread_id).await });
+        }
+    }
+}
+
+/// Sends the resume of a dropped scope. Without a runtime there is no reader left to route the
+/// reply either, so nothing is sent.
+fn resume_in_background(resume: impl Future<Output = result::Result<()>> + Send + 'static) {
+    if let Ok(runtime) = Handle::try_current() {
+        runtime.spawn(async move {
+            if let Err(e) = resume.await {
+                eprintln!("Failed to resume at the end of a scope: {:?}", e);
+            }
+        });
+    }
+}
+
+impl JdwpClient {
+    /// Suspends the VM until the returned scope is finished or dropped
+    pub async fn suspended_scope(&self) -> result::Result<SuspendedScope> {
+        self.vm_suspend().await?;
+        Ok(SuspendedScope {
+            client: self.clone(),
+            finished: false,
+        })
+    }
+
+    /// Suspends a single thread until the returned scope is finished or dropped
+    pub async fn suspend_thread_scope(
+        &self,
+        thread_id: VariableLengthId,
+    ) -> result::Result<ThreadSuspendScope> {
+        self.thread_suspend(thread_id).await?;
+        Ok(ThreadSuspendScope {
+            client: self.clone(),
+            thread_id,
+            finished: false,
+        })
+    }
+}
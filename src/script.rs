@@ -0,0 +1,812 @@
+//! Breakpoint conditions and actions written as small scripts, evaluated against the locals of
+//! the frame that hit the breakpoint
+
+use std::fmt;
+
+use tokio::sync::mpsc;
+
+use crate::{
+    HookAction, HookContext, Hooks, JdwpValue, LineBreakpoint, Tag, TaggedObjectId, result,
+};
+
+/// Deeper expressions are rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 64;
+
+/// A value seen or produced by a [Script]
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Object(TaggedObjectId),
+}
+impl From<JdwpValue> for ScriptValue {
+    fn from(value: JdwpValue) -> Self {
+        match value {
+            JdwpValue::Void => ScriptValue::Null,
+            JdwpValue::Boolean(value) => ScriptValue::Bool(value),
+            JdwpValue::Byte(value) => ScriptValue::Int(value.into()),
+            JdwpValue::Char(value) => match char::from_u32(value.into()) {
+                Some(value) => ScriptValue::String(value.to_string()),
+                None => ScriptValue::Int(value.into()),
+            },
+            JdwpValue::Short(value) => ScriptValue::Int(value.into()),
+            JdwpValue::Int(value) => ScriptValue::Int(value.into()),
+            JdwpValue::Long(value) => ScriptValue::Int(value),
+            JdwpValue::Float(value) => ScriptValue::Float(value.into()),
+            JdwpValue::Double(value) => ScriptValue::Float(value),
+            JdwpValue::Object(object) if object.object_id.value == 0 => ScriptValue::Null,
+            JdwpValue::Object(object) => ScriptValue::Object(object),
+        }
+    }
+}
+impl fmt::Display for ScriptValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptValue::Null => f.write_str("null"),
+            ScriptValue::Bool(value) => write!(f, "{}", value),
+            ScriptValue::Int(value) => write!(f, "{}", value),
+            ScriptValue::Float(value) => write!(f, "{}", value),
+            ScriptValue::String(value) => f.write_str(value),
+            ScriptValue::Object(object) => {
+                write!(f, "{:?}@{:x}", object.tag, object.object_id.value)
+            }
+        }
+    }
+}
+
+/// The variables a script can read
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScriptScope {
+    variables: Vec<(String, ScriptValue)>,
+}
+impl ScriptScope {
+    /// The locals visible at the location of a hook's event, with strings read from the VM
+    pub async fn from_context(ctx: &HookContext) -> result::Result<Self> {
+        let mut scope = ScriptScope::default();
+        for (name, value) in ctx.locals().await? {
+            let value = match value {
+                JdwpValue::Object(object)
+                    if object.tag == Tag::String && object.object_id.value != 0 =>
+                {
+                    let string = ctx.client().string_get_value(object.object_id).await?;
+                    ScriptValue::String(string.string_value.string)
+                }
+                value => ScriptValue::from(value),
+            };
+            scope.set(name, value);
+        }
+        Ok(scope)
+    }
+
+    pub fn set(&mut self, name: impl Into<String>, value: ScriptValue) -> &mut Self {
+        let name = name.into();
+        match self
+            .variables
+            .iter_mut()
+            .find(|(variable, _)| *variable == name)
+        {
+            Some((_, variable)) => *variable = value,
+            None => self.variables.push((name, value)),
+        }
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ScriptValue> {
+        self.variables
+            .iter()
+            .find(|(variable, _)| variable == name)
+            .map(|(_, value)| value)
+    }
+}
+
+/// What running a [Script] produced
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScriptOutcome {
+    /// Value of the last statement
+    pub value: ScriptValue,
+    /// Lines written by `print`
+    pub output: Vec<String>,
+    /// True if the script called `suspend()`
+    pub suspend: bool,
+}
+
+/// A compiled breakpoint condition or action
+///
+/// Scripts only see a copy of the frame's locals: they can't call into the VM, change variables
+/// or loop, so a script can't hang or alter the debuggee. A script is a list of statements
+/// separated by `;`, and its value is that of the last statement.
+///
+/// ```text
+/// let total = count * 2;
+/// if total > 10 && name != null { print("big", name, total); suspend() }
+/// ```
+///
+/// - Values are `null`, booleans, integers, floats, strings and objects. String locals are read
+///   from the VM before the script runs; other objects can only be compared and printed.
+/// - Operators: `+ - * / %`, `== != < <= > >=`, `&& || !`. `+` concatenates when either side
+///   is a string.
+/// - `let name = expr` binds a script variable, `if cond { .. } else { .. }` branches.
+/// - Functions: `print(..)` writes its arguments as a line of output, `suspend()` keeps the
+///   thread suspended after the action, `len(string)` and `str(value)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Script {
+    statements: Vec<Statement>,
+}
+impl Script {
+    pub fn compile(source: &str) -> result::Result<Script> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+        };
+        let statements = parser.statements(0)?;
+        if parser.position != parser.tokens.len() {
+            return Err(parser.error("Unexpected token"));
+        }
+        Ok(Script { statements })
+    }
+
+    /// Runs the script, failing on unknown variables and mismatched types
+    pub fn run(&self, scope: &ScriptScope) -> result::Result<ScriptOutcome> {
+        let mut interpreter = Interpreter {
+            scope: scope.clone(),
+            output: Vec::new(),
+            suspend: false,
+        };
+        let value = interpreter.statements(&self.statements)?;
+        Ok(ScriptOutcome {
+            value,
+            output: interpreter.output,
+            suspend: interpreter.suspend,
+        })
+    }
+
+    /// Runs the script and requires it to evaluate to a boolean
+    pub fn test(&self, scope: &ScriptScope) -> result::Result<bool> {
+        match self.run(scope)?.value {
+            ScriptValue::Bool(value) => Ok(value),
+            value => Err(script_error(format!(
+                "Condition evaluated to {}, not a boolean",
+                value
+            ))),
+        }
+    }
+}
+
+/// An action run when a breakpoint is hit, optionally only when a condition holds
+#[derive(Debug, Clone, PartialEq)]
+pub struct BreakpointScript {
+    condition: Option<Script>,
+    action: Script,
+}
+impl BreakpointScript {
+    pub fn new(action: &str) -> result::Result<Self> {
+        Ok(BreakpointScript {
+            condition: None,
+            action: Script::compile(action)?,
+        })
+    }
+
+    /// Runs the action only for hits where the condition evaluates to true. The thread of other
+    /// hits is resumed.
+    pub fn when(mut self, condition: &str) -> result::Result<Self> {
+        self.condition = Some(Script::compile(condition)?);
+        Ok(self)
+    }
+
+    /// The outcome of the action, None if the condition didn't hold
+    pub fn run(&self, scope: &ScriptScope) -> result::Result<Option<ScriptOutcome>> {
+        if let Some(condition) = &self.condition
+            && !condition.test(scope)?
+        {
+            return Ok(None);
+        }
+        self.action.run(scope).map(Some)
+    }
+}
+
+impl Hooks {
+    /// Runs the script for every hit of the breakpoint, sending the lines it prints to `output`.
+    /// The thread is resumed afterwards unless the action called `suspend()`.
+    pub fn on_breakpoint_script(
+        &mut self,
+        breakpoint: &LineBreakpoint,
+        script: BreakpointScript,
+        output: mpsc::UnboundedSender<String>,
+    ) -> &mut Self {
+        self.on_breakpoint(breakpoint, move |ctx| {
+            let script = script.clone();
+            let output = output.clone();
+            async move {
+                let scope = ScriptScope::from_context(&ctx).await?;
+                let Some(outcome) = script.run(&scope)? else {
+                    return Ok(HookAction::Resume);
+                };
+                for line in outcome.output {
+                    // Nobody listening to the output isn't a reason to stop
+                    _ = output.send(line);
+                }
+                if outcome.suspend {
+                    Ok(HookAction::KeepSuspended)
+                } else {
+                    Ok(HookAction::Resume)
+                }
+            }
+        })
+    }
+}
+
+fn script_error(message: String) -> result::Error {
+    result::Error::InvalidArgument { message }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(ScriptValue),
+    String(String),
+    Identifier(String),
+    Symbol(&'static str),
+}
+
+const SYMBOLS: [&str; 21] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "/", "%", "!", "=", "(", ")", "{",
+    "}", ",", ";",
+];
+
+fn tokenize(source: &str) -> result::Result<Vec<(Token, usize)>> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+    while let Some(&(offset, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() {
+            let mut text = String::new();
+            while let Some(&(_, c)) = chars.peek()
+                && (c.is_ascii_digit() || c == '.')
+            {
+                text.push(c);
+                chars.next();
+            }
+            let number = if text.contains('.') {
+                text.parse().ok().map(ScriptValue::Float)
+            } else {
+                text.parse().ok().map(ScriptValue::Int)
+            };
+            let number = number
+                .ok_or_else(|| script_error(format!("Invalid number at offset {}", offset)))?;
+            tokens.push((Token::Number(number), offset));
+        } else if c.is_alphabetic() || c == '_' || c == '$' {
+            let mut name = String::new();
+            while let Some(&(_, c)) = chars.peek()
+                && (c.is_alphanumeric() || c == '_' || c == '$')
+            {
+                name.push(c);
+                chars.next();
+            }
+            tokens.push((Token::Identifier(name), offset));
+        } else if c == '"' {
+            chars.next();
+            let mut string = String::new();
+            loop {
+                match chars.next() {
+                    None => {
+                        return Err(script_error(format!(
+                            "Unterminated string at offset {}",
+                            offset
+                        )));
+                    }
+                    Some((_, '"')) => break,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, 'n')) => string.push('\n'),
+                        Some((_, 't')) => string.push('\t'),
+                        Some((_, c @ ('"' | '\\'))) => string.push(c),
+                        _ => {
+                            return Err(script_error(format!(
+                                "Invalid escape in string at offset {}",
+                                offset
+                            )));
+                        }
+                    },
+                    Some((_, c)) => string.push(c),
+                }
+            }
+            tokens.push((Token::String(string), offset));
+        } else {
+            let rest = &source[offset..];
+            let symbol = SYMBOLS
+                .into_iter()
+                .find(|symbol| rest.starts_with(symbol))
+                .ok_or_else(|| {
+                    script_error(format!("Unexpected character {} at offset {}", c, offset))
+                })?;
+            for _ in 0..symbol.len() {
+                chars.next();
+            }
+            tokens.push((Token::Symbol(symbol), offset));
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Statement {
+    Let(String, Expression),
+    If(Expression, Vec<Statement>, Vec<Statement>),
+    Expression(Expression),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expression {
+    Literal(ScriptValue),
+    Variable(String),
+    Call(String, Vec<Expression>),
+    Unary(&'static str, Box<Expression>),
+    Binary(&'static str, Box<Expression>, Box<Expression>),
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+impl Parser {
+    /// Statements up to the end of the script or of the block
+    fn statements(&mut self, depth: usize) -> result::Result<Vec<Statement>> {
+        let mut statements = Vec::new();
+        loop {
+            while self.eat(";") {}
+            match self.peek() {
+                None | Some(Token::Symbol("}")) => return Ok(statements),
+                _ => statements.push(self.statement(depth)?),
+            }
+            // Blocks end an if statement without a separator
+            let ended_by_block = matches!(statements.last(), Some(Statement::If(..)));
+            if !ended_by_block
+                && !self.eat(";")
+                && !matches!(self.peek(), None | Some(Token::Symbol("}")))
+            {
+                return Err(self.error("Expected ';'"));
+            }
+        }
+    }
+
+    fn statement(&mut self, depth: usize) -> result::Result<Statement> {
+        if self.eat_keyword("let") {
+            let name = match self.next() {
+                Some(Token::Identifier(name)) => name,
+                _ => return Err(self.error("Expected a variable name")),
+            };
+            self.expect("=")?;
+            return Ok(Statement::Let(name, self.expression(depth)?));
+        }
+        if self.eat_keyword("if") {
+            let condition = self.expression(depth)?;
+            let then = self.block(depth)?;
+            let otherwise = if !self.eat_keyword("else") {
+                Vec::new()
+            } else if matches!(self.peek(), Some(Token::Identifier(keyword)) if keyword == "if") {
+                vec![self.statement(depth + 1)?]
+            } else {
+                self.block(depth)?
+            };
+            return Ok(Statement::If(condition, then, otherwise));
+        }
+        Ok(Statement::Expression(self.expression(depth)?))
+    }
+
+    fn block(&mut self, depth: usize) -> result::Result<Vec<Statement>> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("Nested too deeply"));
+        }
+        self.expect("{")?;
+        let statements = self.statements(depth + 1)?;
+        self.expect("}")?;
+        Ok(statements)
+    }
+
+    fn expression(&mut self, depth: usize) -> result::Result<Expression> {
+        self.binary(0, depth)
+    }
+
+    /// Operators by increasing precedence
+    const PRECEDENCE: [&'static [&'static str]; 5] = [
+        &["||"],
+        &["&&"],
+        &["==", "!=", "<", "<=", ">", ">="],
+        &["+", "-"],
+        &["*", "/", "%"],
+    ];
+
+    fn binary(&mut self, level: usize, depth: usize) -> result::Result<Expression> {
+        if level == Self::PRECEDENCE.len() {
+            return self.unary(depth);
+        }
+        let mut left = self.binary(level + 1, depth)?;
+        loop {
+            let operator = match self.peek() {
+                Some(Token::Symbol(symbol)) if Self::PRECEDENCE[level].contains(symbol) => *symbol,
+                _ => return Ok(left),
+            };
+            self.position += 1;
+            let right = self.binary(level + 1, depth)?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right));
+        }
+    }
+
+    fn unary(&mut self, depth: usize) -> result::Result<Expression> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("Nested too deeply"));
+        }
+        for symbol in ["!", "-"] {
+            if self.eat(symbol) {
+                return Ok(Expression::Unary(symbol, Box::new(self.unary(depth + 1)?)));
+            }
+        }
+        self.primary(depth)
+    }
+
+    fn primary(&mut self, depth: usize) -> result::Result<Expression> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(Expression::Literal(number)),
+            Some(Token::String(string)) => Ok(Expression::Literal(ScriptValue::String(string))),
+            Some(Token::Identifier(name)) => match name.as_str() {
+                "null" => Ok(Expression::Literal(ScriptValue::Null)),
+                "true" => Ok(Expression::Literal(ScriptValue::Bool(true))),
+                "false" => Ok(Expression::Literal(ScriptValue::Bool(false))),
+                _ if self.eat("(") => {
+                    let mut arguments = Vec::new();
+                    if !self.eat(")") {
+                        loop {
+                            arguments.push(self.expression(depth + 1)?);
+                            if self.eat(")") {
+                                break;
+                            }
+                            self.expect(",")?;
+                        }
+                    }
+                    Ok(Expression::Call(name, arguments))
+                }
+                _ => Ok(Expression::Variable(name)),
+            },
+            Some(Token::Symbol("(")) => {
+                let expression = self.expression(depth + 1)?;
+                self.expect(")")?;
+                Ok(expression)
+            }
+            _ => {
+                self.position = self.position.saturating_sub(1);
+                Err(self.error("Expected a value"))
+            }
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        let matches = matches!(self.peek(), Some(Token::Symbol(next)) if *next == symbol);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let matches = matches!(self.peek(), Some(Token::Identifier(next)) if next == keyword);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn expect(&mut self, symbol: &str) -> result::Result<()> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected '{}'", symbol)))
+        }
+    }
+
+    fn error(&self, message: &str) -> result::Error {
+        match self.tokens.get(self.position) {
+            Some((_, offset)) => script_error(format!("{} at offset {}", message, offset)),
+            None => script_error(format!("{} at the end of the script", message)),
+        }
+    }
+}
+
+struct Interpreter {
+    scope: ScriptScope,
+    output: Vec<String>,
+    suspend: bool,
+}
+impl Interpreter {
+    fn statements(&mut self, statements: &[Statement]) -> result::Result<ScriptValue> {
+        let mut value = ScriptValue::Null;
+        for statement in statements {
+            value = match statement {
+                Statement::Let(name, expression) => {
+                    let value = self.evaluate(expression)?;
+                    self.scope.set(name.clone(), value);
+                    ScriptValue::Null
+                }
+                Statement::If(condition, then, otherwise) => match self.evaluate(condition)? {
+                    ScriptValue::Bool(true) => self.statements(then)?,
+                    ScriptValue::Bool(false) => self.statements(otherwise)?,
+                    value => {
+                        return Err(script_error(format!(
+                            "if condition evaluated to {}, not a boolean",
+                            value
+                        )));
+                    }
+                },
+                Statement::Expression(expression) => self.evaluate(expression)?,
+            };
+        }
+        Ok(value)
+    }
+
+    fn evaluate(&mut self, expression: &Expression) -> result::Result<ScriptValue> {
+        match expression {
+            Expression::Literal(value) => Ok(value.clone()),
+            Expression::Variable(name) => self
+                .scope
+                .get(name)
+                .cloned()
+                .ok_or_else(|| script_error(format!("Unknown variable {}", name))),
+            Expression::Call(name, arguments) => {
+                let mut values = Vec::with_capacity(arguments.len());
+                for argument in arguments {
+                    values.push(self.evaluate(argument)?);
+                }
+                self.call(name, values)
+            }
+            Expression::Unary(operator, operand) => match (*operator, self.evaluate(operand)?) {
+                ("!", ScriptValue::Bool(value)) => Ok(ScriptValue::Bool(!value)),
+                ("-", ScriptValue::Int(value)) => value
+                    .checked_neg()
+                    .map(ScriptValue::Int)
+                    .ok_or_else(|| script_error(String::from("Integer overflow"))),
+                ("-", ScriptValue::Float(value)) => Ok(ScriptValue::Float(-value)),
+                (operator, value) => Err(script_error(format!(
+                    "Can't apply {} to {}",
+                    operator, value
+                ))),
+            },
+            Expression::Binary("&&", left, right) => match self.evaluate(left)? {
+                ScriptValue::Bool(false) => Ok(ScriptValue::Bool(false)),
+                ScriptValue::Bool(true) => self.boolean(right),
+                value => Err(script_error(format!("Can't apply && to {}", value))),
+            },
+            Expression::Binary("||", left, right) => match self.evaluate(left)? {
+                ScriptValue::Bool(true) => Ok(ScriptValue::Bool(true)),
+                ScriptValue::Bool(false) => self.boolean(right),
+                value => Err(script_error(format!("Can't apply || to {}", value))),
+            },
+            Expression::Binary(operator, left, right) => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                binary(operator, left, right)
+            }
+        }
+    }
+
+    fn boolean(&mut self, expression: &Expression) -> result::Result<ScriptValue> {
+        match self.evaluate(expression)? {
+            value @ ScriptValue::Bool(_) => Ok(value),
+            value => Err(script_error(format!("Expected a boolean, got {}", value))),
+        }
+    }
+
+    fn call(&mut self, name: &str, arguments: Vec<ScriptValue>) -> result::Result<ScriptValue> {
+        match (name, arguments.as_slice()) {
+            ("print", _) => {
+                let line: Vec<String> = arguments.iter().map(ScriptValue::to_string).collect();
+                self.output.push(line.join(" "));
+                Ok(ScriptValue::Null)
+            }
+            ("suspend", []) => {
+                self.suspend = true;
+                Ok(ScriptValue::Null)
+            }
+            ("len", [ScriptValue::String(string)]) => {
+                Ok(ScriptValue::Int(string.chars().count() as i64))
+            }
+            ("str", [value]) => Ok(ScriptValue::String(value.to_string())),
+            _ => Err(script_error(format!(
+                "Unknown function {} with {} arguments",
+                name,
+                arguments.len()
+            ))),
+        }
+    }
+}
+
+fn binary(operator: &str, left: ScriptValue, right: ScriptValue) -> result::Result<ScriptValue> {
+    use ScriptValue::{Bool, Float, Int, String as Str};
+
+    let overflow = || script_error(String::from("Integer overflow"));
+    Ok(match (operator, left, right) {
+        ("==", left, right) => Bool(equal(&left, &right)),
+        ("!=", left, right) => Bool(!equal(&left, &right)),
+        ("+", Str(left), right) => Str(format!("{}{}", left, right)),
+        ("+", left, Str(right)) => Str(format!("{}{}", left, right)),
+        ("/" | "%", Int(_), Int(0)) => return Err(script_error(String::from("Division by zero"))),
+        (operator, Int(left), Int(right)) => match operator {
+            "+" => Int(left.checked_add(right).ok_or_else(overflow)?),
+            "-" => Int(left.checked_sub(right).ok_or_else(overflow)?),
+            "*" => Int(left.checked_mul(right).ok_or_else(overflow)?),
+            "/" => Int(left.checked_div(right).ok_or_else(overflow)?),
+            "%" => Int(left.checked_rem(right).ok_or_else(overflow)?),
+            _ => compare(operator, left.cmp(&right)),
+        },
+        (operator, left @ (Int(_) | Float(_)), right @ (Int(_) | Float(_))) => {
+            let (left, right) = (as_float(&left), as_float(&right));
+            match operator {
+                "+" => Float(left + right),
+                "-" => Float(left - right),
+                "*" => Float(left * right),
+                "/" => Float(left / right),
+                "%" => Float(left % right),
+                _ => match left.partial_cmp(&right) {
+                    Some(ordering) => compare(operator, ordering),
+                    None => Bool(false),
+                },
+            }
+        }
+        (operator @ ("<" | "<=" | ">" | ">="), Str(left), Str(right)) => {
+            compare(operator, left.cmp(&right))
+        }
+        (operator, left, right) => {
+            return Err(script_error(format!(
+                "Can't apply {} to {} and {}",
+                operator, left, right
+            )));
+        }
+    })
+}
+
+fn compare(operator: &str, ordering: std::cmp::Ordering) -> ScriptValue {
+    ScriptValue::Bool(match operator {
+        "<" => ordering.is_lt(),
+        "<=" => ordering.is_le(),
+        ">" => ordering.is_gt(),
+        _ => ordering.is_ge(),
+    })
+}
+
+fn equal(left: &ScriptValue, right: &ScriptValue) -> bool {
+    match (left, right) {
+        (ScriptValue::Object(left), ScriptValue::Object(right)) => {
+            left.object_id == right.object_id
+        }
+        (
+            ScriptValue::Int(_) | ScriptValue::Float(_),
+            ScriptValue::Int(_) | ScriptValue::Float(_),
+        ) if std::mem::discriminant(left) != std::mem::discriminant(right) => {
+            as_float(left) == as_float(right)
+        }
+        (left, right) => left == right,
+    }
+}
+
+fn as_float(value: &ScriptValue) -> f64 {
+    match value {
+        ScriptValue::Int(value) => *value as f64,
+        ScriptValue::Float(value) => *value,
+        _ => f64::NAN,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VariableLengthId;
+
+    fn scope() -> ScriptScope {
+        let mut scope = ScriptScope::default();
+        scope
+            .set("count", ScriptValue::Int(7))
+            .set("name", ScriptValue::String(String::from("main")))
+            .set("ratio", ScriptValue::Float(0.5))
+            .set(
+                "this",
+                ScriptValue::from(JdwpValue::Object(TaggedObjectId {
+                    tag: Tag::Object,
+                    object_id: VariableLengthId { value: 0x42 },
+                })),
+            );
+        scope
+    }
+
+    fn run(source: &str) -> result::Result<ScriptOutcome> {
+        Script::compile(source)?.run(&scope())
+    }
+
+    #[test]
+    fn test_expressions() {
+        let cases = [
+            ("1 + 2 * 3", ScriptValue::Int(7)),
+            ("(1 + 2) * 3 % 4", ScriptValue::Int(1)),
+            ("count > 5 && name == \"main\"", ScriptValue::Bool(true)),
+            ("!(count >= 8) || missing", ScriptValue::Bool(true)),
+            ("ratio * 4 == 2", ScriptValue::Bool(true)),
+            ("-count + len(name)", ScriptValue::Int(-3)),
+            (
+                "name + \"#\" + count",
+                ScriptValue::String(String::from("main#7")),
+            ),
+            ("this != null", ScriptValue::Bool(true)),
+            ("let double = count * 2; double - 4", ScriptValue::Int(10)),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(run(source).unwrap().value, expected, "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_actions() {
+        let outcome =
+            run("if count > 5 { print(\"big\", name, this); suspend() } else { print(\"small\") }")
+                .unwrap();
+        assert_eq!(outcome.output, vec![String::from("big main Object@42")]);
+        assert!(outcome.suspend);
+
+        let outcome =
+            run("if count > 50 { suspend() } else if count > 5 { print(str(count)) }").unwrap();
+        assert_eq!(outcome.output, vec![String::from("7")]);
+        assert!(!outcome.suspend);
+    }
+
+    #[test]
+    fn test_breakpoint_script() {
+        let script = BreakpointScript::new("print(count)")
+            .unwrap()
+            .when("count % 2 == 1")
+            .unwrap();
+        let outcome = script.run(&scope()).unwrap().unwrap();
+        assert_eq!(outcome.output, vec![String::from("7")]);
+
+        let mut even = scope();
+        even.set("count", ScriptValue::Int(8));
+        assert_eq!(script.run(&even).unwrap(), None);
+    }
+
+    #[test]
+    fn test_errors() {
+        for source in [
+            "1 +",
+            "let = 2",
+            "if count { 1 }",
+            "\"open",
+            "count # 2",
+            "1 2",
+        ] {
+            assert!(run(source).is_err(), "{}", source);
+        }
+        for source in [
+            "missing",
+            "count / 0",
+            "name - 1",
+            "exit()",
+            "9223372036854775807 + 1",
+        ] {
+            assert!(run(source).is_err(), "{}", source);
+        }
+        assert!(Script::compile("count").unwrap().test(&scope()).is_err());
+        let deep = format!(
+            "{}1{}",
+            "(".repeat(MAX_DEPTH + 2),
+            ")".repeat(MAX_DEPTH + 2)
+        );
+        assert!(Script::compile(&deep).is_err());
+    }
+}
@@ -0,0 +1,221 @@
+use crate::result;
+
+/// Version written in the first line of exported breakpoints, bumped whenever the format
+/// changes incompatibly
+pub const BREAKPOINT_FORMAT_VERSION: u32 = 1;
+
+const HEADER: &str = "jdwp-client-breakpoints";
+
+/// When a breakpoint stops the debuggee
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum HitPolicy {
+    /// On every hit
+    #[default]
+    Always,
+    /// Only on the n-th hit, after which the breakpoint is spent
+    Nth(i32),
+}
+
+/// Everything needed to set a [crate::Debugger] breakpoint again, e.g. in a restarted VM
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BreakpointSpec {
+    /// Class name, e.g. `com.example.Main`
+    pub class_name: String,
+    pub line: i32,
+    /// Stratum the line is given in, e.g. `Kotlin`, see
+    /// [crate::JdwpClient::set_stratum_line_breakpoint]
+    pub stratum: Option<String>,
+    /// Name of a static method without parameters returning a boolean, looked up in the class
+    /// the breakpoint is hit in. Hits where it returns false are skipped.
+    pub condition: Option<String>,
+    pub hit_policy: HitPolicy,
+}
+impl BreakpointSpec {
+    pub fn new(class_name: impl Into<String>, line: i32) -> Self {
+        BreakpointSpec {
+            class_name: class_name.into(),
+            line,
+            stratum: None,
+            condition: None,
+            hit_policy: HitPolicy::Always,
+        }
+    }
+
+    pub fn stratum(mut self, stratum: impl Into<String>) -> Self {
+        self.stratum = Some(stratum.into());
+        self
+    }
+
+    pub fn condition(mut self, method_name: impl Into<String>) -> Self {
+        self.condition = Some(method_name.into());
+        self
+    }
+
+    pub fn hit_policy(mut self, hit_policy: HitPolicy) -> Self {
+        self.hit_policy = hit_policy;
+        self
+    }
+}
+
+/// Writes breakpoints in a line based text format: a version header followed by one
+/// `class:line` line per breakpoint, with optional `stratum=`, `hit=` and `condition=` fields.
+/// Whitespace, `%`, `=` and `#` in names are percent-encoded, so any name reads back the same.
+pub fn format_breakpoints<'a>(specs: impl IntoIterator<Item = &'a BreakpointSpec>) -> String {
+    let mut data = format!("{} {}\n", HEADER, BREAKPOINT_FORMAT_VERSION);
+    for spec in specs {
+        data.push_str(&format!("{}:{}", escape(&spec.class_name), spec.line));
+        if let Some(stratum) = &spec.stratum {
+            data.push_str(&format!(" stratum={}", escape(stratum)));
+        }
+        if let HitPolicy::Nth(count) = spec.hit_policy {
+            data.push_str(&format!(" hit={}", count));
+        }
+        if let Some(condition) = &spec.condition {
+            data.push_str(&format!(" condition={}", escape(condition)));
+        }
+        data.push('\n');
+    }
+    data
+}
+
+/// Reads breakpoints written by [format_breakpoints]. Empty lines and lines starting with `#`
+/// are ignored.
+pub fn parse_breakpoints(data: &str) -> result::Result<Vec<BreakpointSpec>> {
+    let mut lines = data
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+    let version = lines
+        .next()
+        .and_then(|(_, line)| line.strip_prefix(HEADER))
+        .and_then(|version| version.trim().parse::<u32>().ok())
+        .ok_or_else(|| invalid(String::from("Missing breakpoint format header")))?;
+    if version != BREAKPOINT_FORMAT_VERSION {
+        return Err(invalid(format!(
+            "Unsupported breakpoint format version {}",
+            version
+        )));
+    }
+
+    lines
+        .map(|(number, line)| {
+            parse_breakpoint(line)
+                .ok_or_else(|| invalid(format!("Invalid breakpoint on line {}: {}", number, line)))
+        })
+        .collect()
+}
+
+fn parse_breakpoint(line: &str) -> Option<BreakpointSpec> {
+    let mut fields = line.split_whitespace();
+    let (class_name, line_number) = fields.next()?.rsplit_once(':')?;
+    if class_name.is_empty() {
+        return None;
+    }
+    let mut spec = BreakpointSpec::new(unescape(class_name)?, line_number.parse().ok()?);
+    for field in fields {
+        match field.split_once('=')? {
+            ("stratum", stratum) => spec.stratum = Some(unescape(stratum)?),
+            ("hit", count) => spec.hit_policy = HitPolicy::Nth(count.parse().ok()?),
+            ("condition", method_name) => spec.condition = Some(unescape(method_name)?),
+            _ => return None,
+        }
+    }
+    Some(spec)
+}
+
+/// Percent-encodes the characters which would end a field, split it or start a comment
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_whitespace() || matches!(c, '%' | '=' | '#') {
+            for byte in c.encode_utf8(&mut [0u8; 4]).bytes() {
+                escaped.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            escaped.push(c);
+        }
+    }
+    escaped
+}
+
+fn unescape(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail
+                .get(..2)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))?;
+            bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn invalid(message: String) -> result::Error {
+    result::Error::InvalidArgument { message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_and_parse_breakpoints() {
+        let specs = vec![
+            BreakpointSpec::new("com.example.Main", 6),
+            BreakpointSpec::new("com.example.Main$Inner", 12)
+                .hit_policy(HitPolicy::Nth(3))
+                .condition("isReady"),
+            BreakpointSpec::new("com.example.MainKt", 3).stratum("Kotlin"),
+        ];
+        let data = format_breakpoints(&specs);
+        assert_eq!(
+            data,
+            "jdwp-client-breakpoints 1\n\
+             com.example.Main:6\n\
+             com.example.Main$Inner:12 hit=3 condition=isReady\n\
+             com.example.MainKt:3 stratum=Kotlin\n"
+        );
+        assert_eq!(parse_breakpoints(&data).unwrap(), specs);
+    }
+
+    #[test]
+    fn test_format_and_parse_escaped_names() {
+        let specs = vec![
+            BreakpointSpec::new("#Weird Class", 1)
+                .stratum("My Lang")
+                .condition("a=b%c\td"),
+            BreakpointSpec::new("com.example.Main", 2).stratum(""),
+        ];
+        let data = format_breakpoints(&specs);
+        assert_eq!(
+            data,
+            "jdwp-client-breakpoints 1\n\
+             %23Weird%20Class:1 stratum=My%20Lang condition=a%3Db%25c%09d\n\
+             com.example.Main:2 stratum=\n"
+        );
+        assert_eq!(parse_breakpoints(&data).unwrap(), specs);
+    }
+
+    #[test]
+    fn test_parse_invalid_breakpoints() {
+        assert!(parse_breakpoints("").is_err());
+        assert!(parse_breakpoints("com.example.Main:6").is_err());
+        assert!(parse_breakpoints("jdwp-client-breakpoints 2\n").is_err());
+        assert!(parse_breakpoints("jdwp-client-breakpoints 1\ncom.example.Main\n").is_err());
+        assert!(parse_breakpoints("jdwp-client-breakpoints 1\ncom.example.Main:6 x=1\n").is_err());
+        assert!(parse_breakpoints("jdwp-client-breakpoints 1\ncom.example.Main%2:6\n").is_err());
+        assert!(parse_breakpoints("jdwp-client-breakpoints 1\ncom.example.Main%FF:6\n").is_err());
+        assert_eq!(
+            parse_breakpoints("# saved\njdwp-client-breakpoints 1\n\n# none\n").unwrap(),
+            vec![]
+        );
+    }
+}
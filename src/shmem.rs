@@ -0,0 +1,632 @@
+//! The shared memory transport of the JDWP agent (`dt_shmem`), which only exists on Windows
+//!
+//! A VM started with `-agentlib:jdwp=transport=dt_shmem,server=y,address=<name>` listens on a
+//! file mapping called `<name>`. Attaching follows the agent's shmemBase.c: the debugger puts
+//! its process id into that mapping and signals the VM, which then creates a mapping called
+//! `<name>.<pid>` holding a ring buffer for each direction. Each ring buffer is guarded by a
+//! named mutex, with one auto-reset event signalling new data and one signalling free space.
+//!
+//! The handshake is exchanged as it is, but packets are framed differently than on sockets:
+//! there is no length, and the id, error code and data length are in the byte order of the
+//! machine. The framing is translated in both directions, so the client sees the same stream
+//! of packets as over TCP.
+
+use std::ffi::{CStr, CString};
+use std::io;
+use std::ptr;
+
+use jdwp_codec::{FLAG_REPLY, HEADER_LENGTH, Header, HeaderKind};
+
+/// Length of the names of synchronization objects in the mappings, MAX_IPC_NAME in
+/// shmemBase.c
+const NAME_LENGTH: usize = 75;
+/// Capacity of each ring buffer, SHARED_BUFFER_SIZE in shmemBase.c
+const BUFFER_SIZE: usize = 5000;
+/// Length of a dt_shmem packet header: id, flags, command set and command or error code, and
+/// the length of the data
+const FRAME_HEADER_LENGTH: usize = 4 + 1 + 2 + 4;
+
+// Offsets into SharedStream, see shmemBase.c
+const STREAM_READ_OFFSET: usize = 228;
+const STREAM_WRITE_OFFSET: usize = 232;
+const STREAM_IS_FULL: usize = 236;
+const STREAM_BUFFER: usize = 237;
+
+/// Reads the NUL terminated name at `offset` of a mapping
+///
+/// # Safety
+/// `base + offset` must point to [NAME_LENGTH] readable bytes.
+unsafe fn read_name(base: *const u8, offset: usize) -> io::Result<CString> {
+    let bytes = unsafe { std::slice::from_raw_parts(base.add(offset), NAME_LENGTH) };
+    CStr::from_bytes_until_nul(bytes)
+        .map(CStr::to_owned)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Unterminated object name"))
+}
+
+/// A ring buffer laid out like SharedStream. Only accessed while holding its mutex, as the VM
+/// changes it too.
+#[derive(Clone, Copy)]
+struct SharedStream {
+    base: *mut u8,
+}
+
+impl SharedStream {
+    /// # Safety
+    /// `base` must point to a SharedStream which stays mapped while this is used.
+    unsafe fn new(base: *mut u8) -> Self {
+        SharedStream { base }
+    }
+
+    fn offset(&self, field: usize) -> io::Result<usize> {
+        let offset = unsafe { ptr::read_volatile(self.base.add(field) as *const i32) };
+        usize::try_from(offset)
+            .ok()
+            .filter(|offset| *offset < BUFFER_SIZE)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Shared memory offset out of range: {}", offset),
+                )
+            })
+    }
+
+    fn set_offset(&self, field: usize, offset: usize) {
+        unsafe { ptr::write_volatile(self.base.add(field) as *mut i32, offset as i32) };
+    }
+
+    fn is_full(&self) -> bool {
+        unsafe { ptr::read_volatile(self.base.add(STREAM_IS_FULL)) != 0 }
+    }
+
+    fn set_full(&self, full: bool) {
+        unsafe { ptr::write_volatile(self.base.add(STREAM_IS_FULL), u8::from(full)) };
+    }
+
+    /// Moves buffered bytes into `buf` up to the end of the buffer, returns how many. 0 means
+    /// the buffer is empty.
+    fn read(&self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = self.offset(STREAM_READ_OFFSET)?;
+        let end = self.offset(STREAM_WRITE_OFFSET)?;
+        if (start == end && !self.is_full()) || buf.is_empty() {
+            return Ok(0);
+        }
+        let end = if start < end { end } else { BUFFER_SIZE };
+        let length = (end - start).min(buf.len());
+        unsafe {
+            ptr::copy_nonoverlapping(
+                self.base.add(STREAM_BUFFER + start),
+                buf.as_mut_ptr(),
+                length,
+            );
+        }
+        self.set_offset(STREAM_READ_OFFSET, (start + length) % BUFFER_SIZE);
+        self.set_full(false);
+        Ok(length)
+    }
+
+    /// Moves bytes of `data` into free space up to the end of the buffer, returns how many. 0
+    /// means the buffer is full.
+    fn write(&self, data: &[u8]) -> io::Result<usize> {
+        let start = self.offset(STREAM_WRITE_OFFSET)?;
+        let end = self.offset(STREAM_READ_OFFSET)?;
+        if self.is_full() || data.is_empty() {
+            return Ok(0);
+        }
+        let end = if start < end { end } else { BUFFER_SIZE };
+        let length = (end - start).min(data.len());
+        unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), self.base.add(STREAM_BUFFER + start), length);
+        }
+        let written_to = (start + length) % BUFFER_SIZE;
+        self.set_offset(STREAM_WRITE_OFFSET, written_to);
+        self.set_full(written_to == self.offset(STREAM_READ_OFFSET)?);
+        Ok(length)
+    }
+}
+
+/// Translates a JDWP packet to the dt_shmem framing
+fn encode_frame(header: &Header, data: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(FRAME_HEADER_LENGTH + data.len());
+    frame.extend_from_slice(&header.id.to_ne_bytes());
+    frame.push(header.flags);
+    match header.kind {
+        HeaderKind::Command {
+            command_set,
+            command,
+        } => frame.extend_from_slice(&[command_set, command]),
+        HeaderKind::Reply { error_code } => frame.extend_from_slice(&error_code.to_ne_bytes()),
+    }
+    frame.extend_from_slice(&(data.len() as i32).to_ne_bytes());
+    frame.extend_from_slice(data);
+    frame
+}
+
+/// Reads a packet in the dt_shmem framing with `read_exact` and translates it to a JDWP packet
+fn decode_frame(mut read_exact: impl FnMut(&mut [u8]) -> io::Result<()>) -> io::Result<Vec<u8>> {
+    let mut frame_header = [0u8; FRAME_HEADER_LENGTH];
+    read_exact(&mut frame_header)?;
+    let [i0, i1, i2, i3, flags, k0, k1, l0, l1, l2, l3] = frame_header;
+    let data_length = usize::try_from(i32::from_ne_bytes([l0, l1, l2, l3]))
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Negative data length"))?;
+    let kind = if flags & FLAG_REPLY != 0 {
+        HeaderKind::Reply {
+            error_code: u16::from_ne_bytes([k0, k1]),
+        }
+    } else {
+        HeaderKind::Command {
+            command_set: k0,
+            command: k1,
+        }
+    };
+    let header = Header {
+        length: (HEADER_LENGTH + data_length) as u32,
+        id: u32::from_ne_bytes([i0, i1, i2, i3]),
+        flags,
+        kind,
+    };
+
+    let mut packet = vec![0u8; HEADER_LENGTH + data_length];
+    packet[..HEADER_LENGTH].copy_from_slice(&header.encode());
+    read_exact(&mut packet[HEADER_LENGTH..])?;
+    Ok(packet)
+}
+
+#[cfg(windows)]
+pub(crate) use win32::connect;
+
+#[cfg(windows)]
+mod win32 {
+    use std::ffi::{CStr, CString};
+    use std::io;
+    use std::pin::Pin;
+    use std::ptr;
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use jdwp_codec::{HEADER_LENGTH, Header};
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream, ReadBuf};
+    use tokio::runtime;
+    use windows_sys::Win32::Foundation::{
+        CloseHandle, HANDLE, WAIT_ABANDONED_0, WAIT_FAILED, WAIT_OBJECT_0, WAIT_TIMEOUT,
+    };
+    use windows_sys::Win32::System::Memory::{
+        FILE_MAP_WRITE, MEMORY_MAPPED_VIEW_ADDRESS, MapViewOfFile, OpenFileMappingA,
+        UnmapViewOfFile,
+    };
+    use windows_sys::Win32::System::Threading::{
+        CreateEventA, EVENT_ALL_ACCESS, GetCurrentProcessId, INFINITE, MUTEX_ALL_ACCESS,
+        OpenEventA, OpenProcess, PROCESS_SYNCHRONIZE, ReleaseMutex, SetEvent,
+        WaitForMultipleObjects,
+    };
+    use windows_sys::Win32::System::WindowsProgramming::OpenMutexA;
+
+    use super::{NAME_LENGTH, SharedStream, decode_frame, encode_frame, read_name};
+
+    /// Longest name a VM listens on, MAX_IPC_PREFIX in shmemBase.c
+    const MAX_ADDRESS_LENGTH: usize = 50;
+    /// How long the VM has to accept, it doesn't while another debugger is attached
+    const ATTACH_TIMEOUT: Duration = Duration::from_secs(10);
+    const HANDSHAKE_LENGTH: usize = 14;
+    const PIPE_BUFFER_SIZE: usize = 64 * 1024;
+
+    // Offsets into SharedListener, the mapping the VM listens on
+    const LISTENER_MUTEX: usize = 0;
+    const LISTENER_ACCEPT_EVENT: usize = NAME_LENGTH;
+    const LISTENER_ATTACH_EVENT: usize = 2 * NAME_LENGTH;
+    const LISTENER_IS_LISTENING: usize = 3 * NAME_LENGTH;
+    const LISTENER_ACCEPTING_PID: usize = 232;
+    const LISTENER_ATTACHING_PID: usize = 240;
+
+    // Offsets into SharedStream. A connection's mapping holds the stream towards the debugger
+    // followed by the one towards the VM.
+    const STREAM_MUTEX: usize = 0;
+    const STREAM_HAS_DATA_EVENT: usize = NAME_LENGTH;
+    const STREAM_HAS_SPACE_EVENT: usize = 2 * NAME_LENGTH;
+    const STREAM_SIZE: usize = 5240;
+
+    struct Handle(HANDLE);
+
+    impl Handle {
+        fn new(handle: HANDLE) -> io::Result<Self> {
+            if handle.is_null() {
+                Err(io::Error::last_os_error())
+            } else {
+                Ok(Handle(handle))
+            }
+        }
+
+        fn signal(&self) -> io::Result<()> {
+            if unsafe { SetEvent(self.0) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for Handle {
+        fn drop(&mut self) {
+            unsafe { CloseHandle(self.0) };
+        }
+    }
+
+    /// Holds a mutex until dropped
+    struct Locked<'a>(&'a Handle);
+
+    impl Drop for Locked<'_> {
+        fn drop(&mut self) {
+            unsafe { ReleaseMutex(self.0.0) };
+        }
+    }
+
+    /// Waits until one of `handles` is signalled and returns its index
+    fn wait(handles: &[HANDLE], timeout: u32) -> io::Result<usize> {
+        let result =
+            unsafe { WaitForMultipleObjects(handles.len() as u32, handles.as_ptr(), 0, timeout) };
+        match result {
+            WAIT_TIMEOUT => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Timed out waiting for the VM",
+            )),
+            WAIT_FAILED => Err(io::Error::last_os_error()),
+            // A mutex whose owner exited while holding it
+            _ if result >= WAIT_ABANDONED_0 => Err(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "The VM exited while holding a lock",
+            )),
+            _ => Ok((result - WAIT_OBJECT_0) as usize),
+        }
+    }
+
+    fn lock(mutex: &Handle, timeout: u32) -> io::Result<Locked<'_>> {
+        wait(&[mutex.0], timeout)?;
+        Ok(Locked(mutex))
+    }
+
+    struct Mapping {
+        _handle: Handle,
+        view: *mut u8,
+    }
+
+    impl Mapping {
+        fn open(name: &CStr) -> io::Result<Self> {
+            let handle = Handle::new(unsafe {
+                OpenFileMappingA(FILE_MAP_WRITE, 0, name.as_ptr() as *const u8)
+            })?;
+            let view = unsafe { MapViewOfFile(handle.0, FILE_MAP_WRITE, 0, 0, 0) }.Value;
+            if view.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Mapping {
+                _handle: handle,
+                view: view as *mut u8,
+            })
+        }
+    }
+
+    impl Drop for Mapping {
+        fn drop(&mut self) {
+            unsafe {
+                UnmapViewOfFile(MEMORY_MAPPED_VIEW_ADDRESS {
+                    Value: self.view as *mut _,
+                })
+            };
+        }
+    }
+
+    /// Opens the object named at `offset` of a mapping
+    fn open_named(
+        base: *const u8,
+        offset: usize,
+        open: unsafe extern "system" fn(u32, i32, *const u8) -> HANDLE,
+        access: u32,
+    ) -> io::Result<Handle> {
+        let name = unsafe { read_name(base, offset)? };
+        Handle::new(unsafe { open(access, 0, name.as_ptr() as *const u8) })
+    }
+
+    /// One direction of a connection
+    struct Stream {
+        shared: SharedStream,
+        mutex: Handle,
+        has_data: Handle,
+        has_space: Handle,
+    }
+
+    impl Stream {
+        fn open(base: *mut u8) -> io::Result<Self> {
+            Ok(Stream {
+                shared: unsafe { SharedStream::new(base) },
+                mutex: open_named(base, STREAM_MUTEX, OpenMutexA, MUTEX_ALL_ACCESS)?,
+                has_data: open_named(base, STREAM_HAS_DATA_EVENT, OpenEventA, EVENT_ALL_ACCESS)?,
+                has_space: open_named(base, STREAM_HAS_SPACE_EVENT, OpenEventA, EVENT_ALL_ACCESS)?,
+            })
+        }
+    }
+
+    struct Connection {
+        incoming: Stream,
+        outgoing: Stream,
+        vm: Handle,
+        /// Manual-reset event set once the stream was dropped, to wake up waiting threads
+        closed: Handle,
+        _mapping: Mapping,
+    }
+
+    // The mapping is only touched while holding the mutex of its stream, and the handles may
+    // be used from any thread
+    unsafe impl Send for Connection {}
+    unsafe impl Sync for Connection {}
+
+    impl Connection {
+        /// Attaches to the VM listening on the mapping called `address`, like shmemBase_attach
+        fn attach(address: &str) -> io::Result<Self> {
+            if address.len() >= MAX_ADDRESS_LENGTH {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Shared memory name too long: {}", address),
+                ));
+            }
+            let name = CString::new(address)?;
+            let listener = Mapping::open(&name)?;
+            let base = listener.view;
+            let mutex = open_named(base, LISTENER_MUTEX, OpenMutexA, MUTEX_ALL_ACCESS)?;
+            let accept = open_named(base, LISTENER_ACCEPT_EVENT, OpenEventA, EVENT_ALL_ACCESS)?;
+            let attach = open_named(base, LISTENER_ATTACH_EVENT, OpenEventA, EVENT_ALL_ACCESS)?;
+
+            let timeout = ATTACH_TIMEOUT.as_millis() as u32;
+            let pid = unsafe { GetCurrentProcessId() };
+            let vm_pid = {
+                let _locked = lock(&mutex, timeout)?;
+                if unsafe { ptr::read_volatile(base.add(LISTENER_IS_LISTENING)) } == 0 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::ConnectionRefused,
+                        format!("No VM listens on {}", address),
+                    ));
+                }
+                unsafe {
+                    ptr::write_volatile(base.add(LISTENER_ATTACHING_PID) as *mut i64, pid.into())
+                };
+                attach.signal()?;
+                wait(&[accept.0], timeout)?;
+                unsafe { ptr::read_volatile(base.add(LISTENER_ACCEPTING_PID) as *const i64) }
+            };
+
+            let mapping = Mapping::open(&CString::new(format!("{}.{}", address, pid))?)?;
+            Ok(Connection {
+                incoming: Stream::open(mapping.view)?,
+                outgoing: Stream::open(unsafe { mapping.view.add(STREAM_SIZE) })?,
+                vm: Handle::new(unsafe { OpenProcess(PROCESS_SYNCHRONIZE, 0, vm_pid as u32) })?,
+                closed: Handle::new(unsafe { CreateEventA(ptr::null(), 1, 0, ptr::null()) })?,
+                _mapping: mapping,
+            })
+        }
+
+        fn lock<'a>(&self, stream: &'a Stream) -> io::Result<Locked<'a>> {
+            match wait(&[stream.mutex.0, self.closed.0], INFINITE)? {
+                0 => Ok(Locked(&stream.mutex)),
+                _ => Err(self.closed_error()),
+            }
+        }
+
+        /// Waits until `event` is signalled, failing once the VM exited or the stream was
+        /// dropped
+        fn wait_for(&self, event: &Handle) -> io::Result<()> {
+            match wait(&[event.0, self.vm.0, self.closed.0], INFINITE)? {
+                0 => Ok(()),
+                1 => Err(io::Error::new(
+                    io::ErrorKind::ConnectionReset,
+                    "The VM exited",
+                )),
+                _ => Err(self.closed_error()),
+            }
+        }
+
+        fn closed_error(&self) -> io::Error {
+            io::Error::new(io::ErrorKind::NotConnected, "Connection closed")
+        }
+
+        fn read_exact(&self, buf: &mut [u8]) -> io::Result<()> {
+            let stream = &self.incoming;
+            let mut filled = 0;
+            while filled < buf.len() {
+                let read = {
+                    let _locked = self.lock(stream)?;
+                    stream.shared.read(&mut buf[filled..])?
+                };
+                if read == 0 {
+                    self.wait_for(&stream.has_data)?;
+                } else {
+                    filled += read;
+                    stream.has_space.signal()?;
+                }
+            }
+            Ok(())
+        }
+
+        fn write_all(&self, data: &[u8]) -> io::Result<()> {
+            let stream = &self.outgoing;
+            let mut written = 0;
+            while written < data.len() {
+                let length = {
+                    let _locked = self.lock(stream)?;
+                    stream.shared.write(&data[written..])?
+                };
+                if length == 0 {
+                    self.wait_for(&stream.has_space)?;
+                } else {
+                    written += length;
+                    stream.has_data.signal()?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// A dt_shmem connection as one stream of JDWP packets. The ring buffers are waited on
+    /// with blocking calls, so two threads pump them into an in-memory duplex stream while
+    /// translating the framing.
+    pub(crate) struct ShmemStream {
+        stream: DuplexStream,
+        connection: Arc<Connection>,
+    }
+
+    pub(crate) async fn connect(address: &str) -> io::Result<ShmemStream> {
+        let address = String::from(address);
+        let connection = tokio::task::spawn_blocking(move || Connection::attach(&address))
+            .await
+            .map_err(io::Error::other)??;
+        let connection = Arc::new(connection);
+
+        let (stream, pipe_end) = tokio::io::duplex(PIPE_BUFFER_SIZE);
+        let (mut from_client, mut to_client) = tokio::io::split(pipe_end);
+        let runtime = runtime::Handle::current();
+        let handle = runtime.clone();
+        let incoming = connection.clone();
+        std::thread::spawn(move || {
+            let mut handshake = [0u8; HANDSHAKE_LENGTH];
+            if incoming.read_exact(&mut handshake).is_ok()
+                && handle.block_on(to_client.write_all(&handshake)).is_ok()
+            {
+                while let Ok(packet) = decode_frame(|buf| incoming.read_exact(buf)) {
+                    if handle.block_on(to_client.write_all(&packet)).is_err() {
+                        break;
+                    }
+                }
+            }
+            let _ = handle.block_on(to_client.shutdown());
+        });
+        let outgoing = connection.clone();
+        std::thread::spawn(move || -> io::Result<()> {
+            let mut handshake = [0u8; HANDSHAKE_LENGTH];
+            runtime.block_on(from_client.read_exact(&mut handshake))?;
+            outgoing.write_all(&handshake)?;
+            loop {
+                let mut header = [0u8; HEADER_LENGTH];
+                runtime.block_on(from_client.read_exact(&mut header))?;
+                let header = Header::decode(&header)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                let mut data = vec![0u8; header.data_length()];
+                runtime.block_on(from_client.read_exact(&mut data))?;
+                outgoing.write_all(&encode_frame(&header, &data))?;
+            }
+        });
+        Ok(ShmemStream { stream, connection })
+    }
+
+    impl Drop for ShmemStream {
+        fn drop(&mut self) {
+            let _ = self.connection.closed.signal();
+        }
+    }
+
+    impl AsyncRead for ShmemStream {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.stream).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for ShmemStream {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.stream).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.stream).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.stream).poll_shutdown(cx)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Zeroed memory the size of a SharedStream, aligned like the mapping
+    fn shared_stream_memory() -> Vec<u64> {
+        vec![0u64; 5240 / 8]
+    }
+
+    #[test]
+    fn test_ring_buffer_wraps_around() {
+        let mut memory = shared_stream_memory();
+        let stream = unsafe { SharedStream::new(memory.as_mut_ptr() as *mut u8) };
+        let data: Vec<u8> = (0..BUFFER_SIZE).map(|i| i as u8).collect();
+
+        assert_eq!(stream.write(&data[..4000]).unwrap(), 4000);
+        let mut read = vec![0u8; 3000];
+        assert_eq!(stream.read(&mut read).unwrap(), 3000);
+        assert_eq!(read, data[..3000]);
+
+        // The write stops at the end of the buffer and continues at its start
+        assert_eq!(stream.write(&data[..3000]).unwrap(), 1000);
+        assert_eq!(stream.write(&data[1000..3000]).unwrap(), 2000);
+        assert_eq!(stream.write(&data[3000..]).unwrap(), 1000);
+        assert!(stream.is_full());
+        assert_eq!(stream.write(&data).unwrap(), 0);
+
+        // Reads stop at the end of the buffer too
+        let mut read = vec![0u8; BUFFER_SIZE];
+        assert_eq!(stream.read(&mut read).unwrap(), 2000);
+        assert_eq!(read[..1000], data[3000..4000]);
+        assert_eq!(read[1000..2000], data[..1000]);
+        assert_eq!(stream.read(&mut read).unwrap(), 3000);
+        assert_eq!(read[..3000], data[1000..4000]);
+        assert_eq!(stream.read(&mut read).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_ring_buffer_rejects_corrupt_offsets() {
+        let mut memory = shared_stream_memory();
+        let stream = unsafe { SharedStream::new(memory.as_mut_ptr() as *mut u8) };
+        stream.set_offset(STREAM_WRITE_OFFSET, BUFFER_SIZE);
+        assert_eq!(
+            stream.read(&mut [0u8; 4]).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_read_name() {
+        let mut memory = [0u8; 2 * NAME_LENGTH];
+        memory[NAME_LENGTH..NAME_LENGTH + 15].copy_from_slice(b"javadebug.mutex");
+        let name = unsafe { read_name(memory.as_ptr(), NAME_LENGTH) }.unwrap();
+        assert_eq!(name.as_bytes(), b"javadebug.mutex");
+
+        let memory = [b'x'; NAME_LENGTH];
+        assert!(unsafe { read_name(memory.as_ptr(), 0) }.is_err());
+    }
+
+    #[test]
+    fn test_frames() {
+        let command = jdwp_codec::encode_command(7, 1, 1, &[1, 2, 3]).unwrap();
+        let reply = jdwp_codec::encode_reply(7, 100, &[]).unwrap();
+        for packet in [command, reply] {
+            let header = Header::decode(packet[..HEADER_LENGTH].try_into().unwrap()).unwrap();
+            let frame = encode_frame(&header, &packet[HEADER_LENGTH..]);
+            assert_eq!(frame.len(), packet.len());
+            assert_eq!(frame[..4], 7u32.to_ne_bytes());
+
+            let mut remaining = frame.as_slice();
+            let decoded = decode_frame(|buf| {
+                let (read, rest) = remaining.split_at(buf.len());
+                buf.copy_from_slice(read);
+                remaining = rest;
+                Ok(())
+            })
+            .unwrap();
+            assert_eq!(decoded, packet);
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use crate::{Tag, result};
+
+/// Converts a Java source-level type name (`int`, `java.lang.String`, `byte[][]`) into a JNI
+/// signature (`I`, `Ljava/lang/String;`, `[[B`)
+pub fn type_name_to_signature(type_name: &str) -> String {
+    let mut name = type_name.trim();
+    let mut signature = String::new();
+    while let Some(element) = name.strip_suffix("[]") {
+        signature.push('[');
+        name = element.trim_end();
+    }
+
+    match name {
+        "boolean" => signature.push('Z'),
+        "byte" => signature.push('B'),
+        "char" => signature.push('C'),
+        "short" => signature.push('S'),
+        "int" => signature.push('I'),
+        "long" => signature.push('J'),
+        "float" => signature.push('F'),
+        "double" => signature.push('D'),
+        "void" => signature.push('V'),
+        class_name => {
+            signature.push('L');
+            signature.push_str(&class_name.replace('.', "/"));
+            signature.push(';');
+        }
+    }
+    signature
+}
+
+/// Converts a JNI signature (`I`, `Ljava/lang/String;`, `[[B`) into a Java source-level type
+/// name (`int`, `java.lang.String`, `byte[][]`). Unknown signatures are returned as they are.
+pub fn signature_to_type_name(signature: &str) -> String {
+    let element = signature.trim_start_matches('[');
+    let dimensions = signature.len() - element.len();
+
+    let mut name = match element {
+        "Z" => String::from("boolean"),
+        "B" => String::from("byte"),
+        "C" => String::from("char"),
+        "S" => String::from("short"),
+        "I" => String::from("int"),
+        "J" => String::from("long"),
+        "F" => String::from("float"),
+        "D" => String::from("double"),
+        "V" => String::from("void"),
+        class => match class.strip_prefix('L').and_then(|c| c.strip_suffix(';')) {
+            Some(class_name) => class_name.replace('/', "."),
+            None => return String::from(signature),
+        },
+    };
+    for _ in 0..dimensions {
+        name.push_str("[]");
+    }
+    name
+}
+
+/// Splits a method descriptor like `(ILjava/lang/String;)V` into its parameter signatures and
+/// return type signature
+pub fn parse_method_descriptor(descriptor: &str) -> result::Result<(Vec<&str>, &str)> {
+    let invalid = || result::Error::ParsingError {
+        message: format!("Invalid method descriptor: {}", descriptor),
+    };
+
+    let params = descriptor.strip_prefix('(').ok_or_else(invalid)?;
+    let (mut params, return_type) = params.split_once(')').ok_or_else(invalid)?;
+
+    let mut types = Vec::new();
+    while !params.is_empty() {
+        let length = field_signature_length(params).ok_or_else(invalid)?;
+        types.push(&params[..length]);
+        params = &params[length..];
+    }
+
+    if return_type.is_empty() || field_signature_length(return_type) != Some(return_type.len()) {
+        return Err(invalid());
+    }
+    Ok((types, return_type))
+}
+
+/// Returns the value tag used for a field signature
+pub fn signature_tag(signature: &str) -> Option<Tag> {
+    Some(match signature.as_bytes().first()? {
+        b'Z' => Tag::Boolean,
+        b'B' => Tag::Byte,
+        b'C' => Tag::Char,
+        b'S' => Tag::Short,
+        b'I' => Tag::Int,
+        b'J' => Tag::Long,
+        b'F' => Tag::Float,
+        b'D' => Tag::Double,
+        b'V' => Tag::Void,
+        b'[' => Tag::Array,
+        b'L' => match signature {
+            "Ljava/lang/String;" => Tag::String,
+            "Ljava/lang/Thread;" => Tag::Thread,
+            "Ljava/lang/ThreadGroup;" => Tag::ThreadGroup,
+            "Ljava/lang/ClassLoader;" => Tag::ClassLoader,
+            "Ljava/lang/Class;" => Tag::ClassObject,
+            _ => Tag::Object,
+        },
+        _ => return None,
+    })
+}
+
+/// Length of the first field signature in `signature`
+fn field_signature_length(signature: &str) -> Option<usize> {
+    let dimensions = signature.bytes().take_while(|b| *b == b'[').count();
+    let length = match signature.as_bytes().get(dimensions)? {
+        b'Z' | b'B' | b'C' | b'S' | b'I' | b'J' | b'F' | b'D' | b'V' => 1,
+        b'L' => signature[dimensions..].find(';')? + 1,
+        _ => return None,
+    };
+    Some(dimensions + length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_type_name_to_signature() {
+        assert_eq!(type_name_to_signature("int"), "I");
+        assert_eq!(
+            type_name_to_signature("java.lang.String"),
+            "Ljava/lang/String;"
+        );
+        assert_eq!(type_name_to_signature("byte[][]"), "[[B");
+        assert_eq!(
+            type_name_to_signature("java.util.List[]"),
+            "[Ljava/util/List;"
+        );
+    }
+
+    #[test]
+    fn test_signature_to_type_name() {
+        assert_eq!(signature_to_type_name("I"), "int");
+        assert_eq!(
+            signature_to_type_name("Ljava/lang/String;"),
+            "java.lang.String"
+        );
+        assert_eq!(signature_to_type_name("[[B"), "byte[][]");
+        assert_eq!(
+            signature_to_type_name("[Ljava/util/List;"),
+            "java.util.List[]"
+        );
+        assert_eq!(signature_to_type_name("Q"), "Q");
+    }
+
+    #[test]
+    fn test_parse_method_descriptor() {
+        let (params, return_type) =
+            parse_method_descriptor("(I[JLjava/lang/String;[[Ljava/lang/Object;)V").unwrap();
+        assert_eq!(
+            params,
+            vec!["I", "[J", "Ljava/lang/String;", "[[Ljava/lang/Object;"]
+        );
+        assert_eq!(return_type, "V");
+
+        let (params, return_type) = parse_method_descriptor("()Ljava/lang/String;").unwrap();
+        assert!(params.is_empty());
+        assert_eq!(return_type, "Ljava/lang/String;");
+    }
+
+    #[test]
+    fn test_parse_method_descriptor_invalid() {
+        assert!(parse_method_descriptor("I)V").is_err());
+        assert!(parse_method_descriptor("(Ljava/lang/String)V").is_err());
+        assert!(parse_method_descriptor("(I)").is_err());
+        assert!(parse_method_descriptor("(Q)V").is_err());
+    }
+}
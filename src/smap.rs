@@ -0,0 +1,282 @@
+use crate::result;
+#[cfg(feature = "tokio")]
+use crate::{JdwpClient, JdwpErrorCode, VariableLengthId};
+
+/// Name of the stratum of the Java source, which classes have even without an SMAP
+pub const JAVA_STRATUM: &str = "Java";
+
+/// Source map of a class compiled from another language (JSR-045), read from its
+/// SourceDebugExtension attribute
+///
+/// Each stratum maps the lines of the class file ("output" lines) to the lines of the sources
+/// in one language ("input" lines), e.g. the `Kotlin` stratum of a class using inline functions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Smap {
+    /// Name of the generated source, e.g. `Main.kt`
+    pub output_file: String,
+    pub default_stratum: String,
+    pub strata: Vec<Stratum>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stratum {
+    pub name: String,
+    pub files: Vec<SmapFile>,
+    pub lines: Vec<SmapLine>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SmapFile {
+    pub id: u32,
+    /// File name, e.g. `Main.kt`
+    pub name: String,
+    /// Path relative to a source root, e.g. `com/example/Main.kt`, if the SMAP has one
+    pub path: Option<String>,
+}
+
+/// `repeat` consecutive input lines starting at `input_start`, each of which maps to
+/// `output_increment` output lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SmapLine {
+    pub input_start: i32,
+    pub file_id: u32,
+    pub repeat: i32,
+    pub output_start: i32,
+    pub output_increment: i32,
+}
+
+impl Smap {
+    pub fn parse(data: &str) -> result::Result<Self> {
+        let invalid = |message: &str| result::Error::ParsingError {
+            message: format!("Invalid SMAP: {}", message),
+        };
+        let mut lines = data.lines().map(str::trim_end);
+        if lines.next() != Some("SMAP") {
+            return Err(invalid("missing header"));
+        }
+        let output_file = lines.next().ok_or_else(|| invalid("missing output file"))?;
+        let default_stratum = lines
+            .next()
+            .ok_or_else(|| invalid("missing default stratum"))?;
+
+        let mut strata: Vec<Stratum> = Vec::new();
+        let mut section = "";
+        let mut file_id = 0;
+        while let Some(line) = lines.next() {
+            if let Some(header) = line.strip_prefix('*') {
+                section = header;
+                if let Some(name) = header.strip_prefix("S ") {
+                    strata.push(Stratum {
+                        name: String::from(name.trim()),
+                        files: Vec::new(),
+                        lines: Vec::new(),
+                    });
+                }
+                // Kotlin ends each stratum with *E rather than only the last one
+                continue;
+            }
+            // Vendor sections and lines of unknown sections are skipped
+            let Some(stratum) = strata.last_mut() else {
+                continue;
+            };
+            match section {
+                "F" => {
+                    let (with_path, entry) = match line.strip_prefix('+') {
+                        Some(entry) => (true, entry),
+                        None => (false, line),
+                    };
+                    let (id, name) = entry.trim().split_once(' ').ok_or_else(|| invalid(line))?;
+                    let path = match with_path {
+                        true => Some(String::from(
+                            lines.next().ok_or_else(|| invalid("missing file path"))?,
+                        )),
+                        false => None,
+                    };
+                    stratum.files.push(SmapFile {
+                        id: id.parse().map_err(|_| invalid(line))?,
+                        name: String::from(name.trim()),
+                        path,
+                    });
+                }
+                "L" => {
+                    let entry = parse_line_info(line, file_id).ok_or_else(|| invalid(line))?;
+                    file_id = entry.file_id;
+                    stratum.lines.push(entry);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Smap {
+            output_file: String::from(output_file),
+            default_stratum: String::from(default_stratum),
+            strata,
+        })
+    }
+
+    pub fn stratum(&self, name: &str) -> Option<&Stratum> {
+        self.strata.iter().find(|stratum| stratum.name == name)
+    }
+}
+
+/// Parses `InputStartLine[#LineFileID][,RepeatCount]:OutputStartLine[,OutputLineIncrement]`.
+/// The file id defaults to the one of the previous line.
+fn parse_line_info(line: &str, file_id: u32) -> Option<SmapLine> {
+    let (input, output) = line.split_once(':')?;
+    let (input, repeat) = match input.split_once(',') {
+        Some((input, repeat)) => (input, repeat.parse().ok()?),
+        None => (input, 1),
+    };
+    let (input_start, file_id) = match input.split_once('#') {
+        Some((start, file_id)) => (start.parse().ok()?, file_id.parse().ok()?),
+        None => (input.parse().ok()?, file_id),
+    };
+    let (output_start, output_increment) = match output.split_once(',') {
+        Some((start, increment)) => (start.parse().ok()?, increment.parse().ok()?),
+        None => (output.parse().ok()?, 1),
+    };
+    Some(SmapLine {
+        input_start,
+        file_id,
+        repeat,
+        output_start,
+        output_increment,
+    })
+}
+
+impl Stratum {
+    pub fn file(&self, id: u32) -> Option<&SmapFile> {
+        self.files.iter().find(|file| file.id == id)
+    }
+
+    /// Output lines `line` of the file `file_id` maps to
+    pub fn output_lines(&self, file_id: u32, line: i32) -> Vec<i32> {
+        let mut output = Vec::new();
+        for entry in &self.lines {
+            let offset = line - entry.input_start;
+            if entry.file_id != file_id || offset < 0 || offset >= entry.repeat {
+                continue;
+            }
+            let start = entry.output_start + offset * entry.output_increment;
+            output.extend(start..start + entry.output_increment.max(1));
+        }
+        output
+    }
+
+    /// File and input line an output line maps to
+    pub fn input_line(&self, output_line: i32) -> Option<(&SmapFile, i32)> {
+        self.lines.iter().find_map(|entry| {
+            let offset = output_line - entry.output_start;
+            let index = match entry.output_increment {
+                0 if offset == 0 => 0,
+                0 => return None,
+                increment => offset.checked_div(increment)?,
+            };
+            if offset < 0 || index >= entry.repeat {
+                return None;
+            }
+            Some((self.file(entry.file_id)?, entry.input_start + index))
+        })
+    }
+}
+
+/// JVM lines of `line` in the source of a class in `stratum`. The class's own source is the
+/// first file of the stratum. Classes without an SMAP or without the stratum, as well as the
+/// Java stratum, use their JVM lines.
+#[cfg_attr(not(feature = "tokio"), allow(dead_code))]
+pub(crate) fn jvm_lines(smap: Option<&Smap>, stratum: &str, line: i32) -> Vec<i32> {
+    match smap.and_then(|smap| smap.stratum(stratum)) {
+        Some(stratum) if stratum.name != JAVA_STRATUM => match stratum.files.first() {
+            Some(file) => stratum.output_lines(file.id, line),
+            None => Vec::new(),
+        },
+        _ => vec![line],
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl JdwpClient {
+    /// Reads and parses the SMAP of a class. Returns None if the class has none.
+    pub async fn class_smap(&self, ref_type_id: VariableLengthId) -> result::Result<Option<Smap>> {
+        match self.ref_type_get_source_debug_extension(ref_type_id).await {
+            Ok(reply) => Smap::parse(&reply.extension.string).map(Some),
+            Err(result::Error::JdwpError(JdwpErrorCode::AbsentInformation)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KOTLIN_SMAP: &str = "SMAP
+Main.kt
+Kotlin
+*S Kotlin
+*F
++ 1 Main.kt
+com/example/MainKt.kt
++ 2 Util.kt
+com/example/Util.kt
+*L
+1#1,10:1
+20#2,3:30,2
+*E
+*S KotlinDebug
+*F
++ 1 Main.kt
+com/example/MainKt.kt
+*L
+5#1:30
+*E
+";
+
+    #[test]
+    fn test_parse_smap() {
+        let smap = Smap::parse(KOTLIN_SMAP).unwrap();
+        assert_eq!(smap.output_file, "Main.kt");
+        assert_eq!(smap.default_stratum, "Kotlin");
+        assert_eq!(smap.strata.len(), 2);
+
+        let kotlin = smap.stratum("Kotlin").unwrap();
+        assert_eq!(
+            kotlin.file(2),
+            Some(&SmapFile {
+                id: 2,
+                name: String::from("Util.kt"),
+                path: Some(String::from("com/example/Util.kt")),
+            })
+        );
+        assert_eq!(
+            kotlin.lines[1],
+            SmapLine {
+                input_start: 20,
+                file_id: 2,
+                repeat: 3,
+                output_start: 30,
+                output_increment: 2,
+            }
+        );
+        assert_eq!(smap.stratum("KotlinDebug").unwrap().lines[0].file_id, 1);
+        assert!(Smap::parse("Main.kt\nKotlin\n").is_err());
+    }
+
+    #[test]
+    fn test_line_mapping() {
+        let smap = Smap::parse(KOTLIN_SMAP).unwrap();
+        let kotlin = smap.stratum("Kotlin").unwrap();
+        assert_eq!(kotlin.output_lines(1, 4), vec![4]);
+        assert_eq!(kotlin.output_lines(2, 21), vec![32, 33]);
+        assert_eq!(kotlin.output_lines(2, 23), Vec::<i32>::new());
+
+        let (file, line) = kotlin.input_line(33).unwrap();
+        assert_eq!((file.name.as_str(), line), ("Util.kt", 21));
+        assert_eq!(kotlin.input_line(7).unwrap().1, 7);
+        assert!(kotlin.input_line(36).is_none());
+
+        assert_eq!(jvm_lines(Some(&smap), "Kotlin", 4), vec![4]);
+        assert_eq!(jvm_lines(Some(&smap), "Java", 4), vec![4]);
+        assert_eq!(jvm_lines(None, "Kotlin", 4), vec![4]);
+    }
+}
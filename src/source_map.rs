@@ -0,0 +1,301 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::{JdwpClient, JdwpErrorCode, LineTableEntry, Location, Smap, VariableLengthId, result};
+
+/// Where the source of a class was found
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SourceFile {
+    /// Absolute path of a file under one of the source roots
+    Path(PathBuf),
+    /// Entry of a JAR or ZIP with sources, such as `guava-33.0-sources.jar`
+    ArchiveEntry { archive: PathBuf, entry: String },
+}
+
+/// A source line a [Location] maps to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SourcePosition {
+    pub file: SourceFile,
+    pub line: i32,
+}
+
+/// Maps locations in the debuggee to source files and lines
+///
+/// The file is looked up by the package of the class and its `SourceFile` attribute, first in
+/// the source roots and then in the source archives, each in the order they were added. Source
+/// files and line tables are cached by class and method id, so a map should only be used with
+/// a single connection.
+///
+/// With a stratum selected, locations in classes whose SMAP has the stratum map to the files
+/// and lines of that stratum instead, e.g. to the Kotlin source of an inlined function.
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    roots: Vec<PathBuf>,
+    archives: Vec<PathBuf>,
+    stratum: Option<String>,
+    files: Mutex<HashMap<VariableLengthId, Option<SourceFile>>>,
+    packages: Mutex<HashMap<VariableLengthId, String>>,
+    smaps: Mutex<HashMap<VariableLengthId, Option<Smap>>>,
+    found: Mutex<HashMap<String, Option<SourceFile>>>,
+    line_tables: Mutex<HashMap<(VariableLengthId, VariableLengthId), Vec<LineTableEntry>>>,
+    archive_entries: Mutex<HashMap<PathBuf, HashSet<String>>>,
+}
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a directory laid out by package, e.g. `src/main/java`
+    pub fn source_root(mut self, path: impl Into<PathBuf>) -> Self {
+        self.roots.push(path.into());
+        self
+    }
+
+    /// Adds a JAR or ZIP laid out by package
+    pub fn source_archive(mut self, path: impl Into<PathBuf>) -> Self {
+        self.archives.push(path.into());
+        self
+    }
+
+    /// Maps locations to the lines of `stratum`, e.g. `Kotlin`, where the class has an SMAP
+    /// with it
+    pub fn stratum(mut self, stratum: impl Into<String>) -> Self {
+        self.stratum = Some(stratum.into());
+        self
+    }
+
+    /// Maps `location` to its source file and line. Returns None if the class has no source
+    /// file attribute, the file isn't found or the method has no line information.
+    pub async fn resolve(
+        &self,
+        client: &JdwpClient,
+        location: &Location,
+    ) -> result::Result<Option<SourcePosition>> {
+        if let Some(stratum) = &self.stratum
+            && let Some(smap) = self.smap(client, location.class_id).await?
+            && let Some(stratum) = smap.stratum(stratum)
+        {
+            let Some(line) = self.line(client, location).await? else {
+                return Ok(None);
+            };
+            let Some((file, line)) = stratum.input_line(line) else {
+                return Ok(None);
+            };
+            let relative = match &file.path {
+                Some(path) => path.clone(),
+                None => {
+                    self.relative_path(client, location.class_id, &file.name)
+                        .await?
+                }
+            };
+            return Ok(self
+                .find(&relative)?
+                .map(|file| SourcePosition { file, line }));
+        }
+
+        let Some(file) = self.source_file(client, location.class_id).await? else {
+            return Ok(None);
+        };
+        let Some(line) = self.line(client, location).await? else {
+            return Ok(None);
+        };
+        Ok(Some(SourcePosition { file, line }))
+    }
+
+    /// Forgets all cached files, line tables and archive listings, e.g. after sources changed
+    pub fn clear_cache(&self) {
+        lock(&self.files).clear();
+        lock(&self.packages).clear();
+        lock(&self.smaps).clear();
+        lock(&self.found).clear();
+        lock(&self.line_tables).clear();
+        lock(&self.archive_entries).clear();
+    }
+
+    async fn source_file(
+        &self,
+        client: &JdwpClient,
+        class_id: VariableLengthId,
+    ) -> result::Result<Option<SourceFile>> {
+        if let Some(file) = lock(&self.files).get(&class_id) {
+            return Ok(file.clone());
+        }
+
+        let file = match client.ref_type_get_source_file(class_id).await {
+            Ok(reply) => {
+                let relative = self
+                    .relative_path(client, class_id, &reply.source_file.string)
+                    .await?;
+                self.find(&relative)?
+            }
+            Err(result::Error::JdwpError(JdwpErrorCode::AbsentInformation)) => None,
+            Err(e) => return Err(e),
+        };
+        lock(&self.files).insert(class_id, file.clone());
+        Ok(file)
+    }
+
+    async fn smap(
+        &self,
+        client: &JdwpClient,
+        class_id: VariableLengthId,
+    ) -> result::Result<Option<Smap>> {
+        if let Some(smap) = lock(&self.smaps).get(&class_id) {
+            return Ok(smap.clone());
+        }
+        let smap = client.class_smap(class_id).await?;
+        lock(&self.smaps).insert(class_id, smap.clone());
+        Ok(smap)
+    }
+
+    /// Path of `file_name` in the package of the class, relative to a source root
+    async fn relative_path(
+        &self,
+        client: &JdwpClient,
+        class_id: VariableLengthId,
+        file_name: &str,
+    ) -> result::Result<String> {
+        let cached = lock(&self.packages).get(&class_id).cloned();
+        let package = match cached {
+            Some(package) => package,
+            None => {
+                let signature = client.ref_type_get_signature(class_id).await?.signature;
+                let package = package_path(&signature.string);
+                lock(&self.packages).insert(class_id, package.clone());
+                package
+            }
+        };
+        Ok(match package.is_empty() {
+            true => String::from(file_name),
+            false => format!("{}/{}", package, file_name),
+        })
+    }
+
+    fn find(&self, relative: &str) -> result::Result<Option<SourceFile>> {
+        if let Some(file) = lock(&self.found).get(relative) {
+            return Ok(file.clone());
+        }
+        let file = self.search(relative)?;
+        lock(&self.found).insert(String::from(relative), file.clone());
+        Ok(file)
+    }
+
+    fn search(&self, relative: &str) -> result::Result<Option<SourceFile>> {
+        for root in &self.roots {
+            let path = root.join(relative);
+            if path.is_file() {
+                return Ok(Some(SourceFile::Path(std::path::absolute(path)?)));
+            }
+        }
+        for archive in &self.archives {
+            if self.archive_contains(archive, relative)? {
+                return Ok(Some(SourceFile::ArchiveEntry {
+                    archive: std::path::absolute(archive)?,
+                    entry: String::from(relative),
+                }));
+            }
+        }
+        Ok(None)
+    }
+
+    fn archive_contains(&self, archive: &Path, entry: &str) -> result::Result<bool> {
+        let mut entries = lock(&self.archive_entries);
+        if !entries.contains_key(archive) {
+            let zip = zip::ZipArchive::new(File::open(archive)?).map_err(|e| {
+                result::Error::ParsingError {
+                    message: format!("Invalid source archive {}: {}", archive.display(), e),
+                }
+            })?;
+            let names = zip.file_names().map(String::from).collect();
+            entries.insert(archive.to_path_buf(), names);
+        }
+        Ok(entries[archive].contains(entry))
+    }
+
+    async fn line(&self, client: &JdwpClient, location: &Location) -> result::Result<Option<i32>> {
+        let key = (location.class_id, location.method_id);
+        let cached = lock(&self.line_tables).get(&key).cloned();
+        let lines = match cached {
+            Some(lines) => lines,
+            None => {
+                let lines = match client
+                    .method_get_line_table(location.class_id, location.method_id)
+                    .await
+                {
+                    Ok(table) => table.lines,
+                    Err(result::Error::JdwpError(
+                        JdwpErrorCode::AbsentInformation | JdwpErrorCode::NativeMethod,
+                    )) => Vec::new(),
+                    Err(e) => return Err(e),
+                };
+                lock(&self.line_tables).insert(key, lines.clone());
+                lines
+            }
+        };
+        Ok(line_at(&lines, location.index))
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    match mutex.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Directory of the package of a class relative to a source root, e.g. `com/example` for
+/// `Lcom/example/Main$Inner;`
+fn package_path(signature: &str) -> String {
+    let class = signature
+        .strip_prefix('L')
+        .and_then(|class| class.strip_suffix(';'))
+        .unwrap_or(signature);
+    match class.rsplit_once('/') {
+        Some((package, _)) => String::from(package),
+        None => String::new(),
+    }
+}
+
+/// Line of the last line table entry starting at or before `index`
+pub(crate) fn line_at(lines: &[LineTableEntry], index: u64) -> Option<i32> {
+    lines
+        .iter()
+        .filter(|entry| entry.line_code_index <= index)
+        .max_by_key(|entry| entry.line_code_index)
+        .map(|entry| entry.line_number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_path() {
+        assert_eq!(package_path("Lcom/example/Main$Inner;"), "com/example");
+        assert_eq!(package_path("LMain;"), "");
+    }
+
+    #[test]
+    fn test_line_at() {
+        let lines = [
+            LineTableEntry {
+                line_code_index: 0,
+                line_number: 5,
+            },
+            LineTableEntry {
+                line_code_index: 8,
+                line_number: 7,
+            },
+            LineTableEntry {
+                line_code_index: 4,
+                line_number: 6,
+            },
+        ];
+        assert_eq!(line_at(&lines, 0), Some(5));
+        assert_eq!(line_at(&lines, 5), Some(6));
+        assert_eq!(line_at(&lines, 20), Some(7));
+        assert_eq!(line_at(&[], 0), None);
+    }
+}
@@ -0,0 +1,325 @@
+use binrw::BinRead;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+use crate::{Command, EventComposite, JdwpIdSizes, SuspendPolicy, VariableLengthId};
+
+/// Change to the suspension state made by a successful command. It is applied by the reader task
+/// when the reply arrives, so it stays ordered with events the VM sends around it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SuspensionChange {
+    VmSuspended,
+    VmResumed,
+    ThreadSuspended(VariableLengthId),
+    ThreadResumed(VariableLengthId),
+    Cleared,
+}
+impl SuspensionChange {
+    pub(crate) fn for_request(command: Command, data: &[u8], sizes: JdwpIdSizes) -> Option<Self> {
+        let thread =
+            || VariableLengthId::read_be_args(&mut Cursor::new(data), sizes.object_id_size).ok();
+        match command {
+            Command::VirtualMachineSuspend => Some(SuspensionChange::VmSuspended),
+            Command::VirtualMachineResume => Some(SuspensionChange::VmResumed),
+            // Disposing resumes every thread the debugger suspended
+            Command::VirtualMachineDispose => Some(SuspensionChange::Cleared),
+            Command::ThreadReferenceSuspend => thread().map(SuspensionChange::ThreadSuspended),
+            Command::ThreadReferenceResume => thread().map(SuspensionChange::ThreadResumed),
+            _ => None,
+        }
+    }
+}
+
+/// Suspend counts of the VM and its threads as far as the client has seen them
+///
+/// The VM keeps a suspend count per thread. VirtualMachine.Suspend and Resume change the count of
+/// every thread at once, which is tracked in `vm`, while `threads` holds how far the count of a
+/// single thread differs from it. `event_threads` are the threads stopped at an event which
+/// haven't run since, most recent last.
+///
+/// The generation of a thread changes whenever it may have run, which invalidates the frame ids
+/// read while it was suspended. Like the counts, it's kept as a VM-wide value and per-thread
+/// offsets: a VM resume bumps `vm_generation` and lowers the offsets of the threads staying
+/// suspended, so their generation stays the same. Generations never go back, so offsets are
+/// kept for as long as the connection. `epoch` changes whenever the generation of any thread
+/// does.
+#[derive(Debug, Default)]
+pub(crate) struct SuspensionState {
+    vm: u32,
+    threads: HashMap<VariableLengthId, i64>,
+    event_threads: Vec<VariableLengthId>,
+    vm_generation: u64,
+    generation_offsets: HashMap<VariableLengthId, u64>,
+    epoch: u64,
+}
+impl SuspensionState {
+    pub(crate) fn count(&self, thread: VariableLengthId) -> i64 {
+        self.vm as i64 + self.threads.get(&thread).copied().unwrap_or(0)
+    }
+
+    pub(crate) fn generation(&self, thread: VariableLengthId) -> u64 {
+        let offset = self.generation_offsets.get(&thread).copied().unwrap_or(0);
+        self.vm_generation.wrapping_add(offset)
+    }
+
+    pub(crate) fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    /// Starts a new generation of every thread, whether it ran or not
+    pub(crate) fn barrier(&mut self) {
+        self.vm_generation = self.vm_generation.wrapping_add(1);
+        self.epoch = self.epoch.wrapping_add(1);
+    }
+
+    pub(crate) fn is_vm_suspended(&self) -> bool {
+        self.vm > 0
+    }
+
+    pub(crate) fn is_thread_suspended(&self, thread: VariableLengthId) -> bool {
+        self.count(thread) > 0
+    }
+
+    /// Resumes needed to undo every suspension seen by the client: VirtualMachine.Resume
+    /// `vm` times, then ThreadReference.Resume for each thread suspended on its own
+    pub(crate) fn leaked(&self) -> (u32, Vec<(VariableLengthId, u32)>) {
+        let threads = self
+            .threads
+            .iter()
+            .filter(|(_, offset)| **offset > 0)
+            .map(|(thread, offset)| (*thread, *offset as u32))
+            .collect();
+        (self.vm, threads)
+    }
+
+    /// Threads stopped at an event which are still suspended, most recent first
+    pub(crate) fn event_threads(&self) -> impl Iterator<Item = VariableLengthId> + '_ {
+        self.event_threads.iter().rev().copied()
+    }
+
+    fn forget_running_event_threads(&mut self) {
+        let mut event_threads = std::mem::take(&mut self.event_threads);
+        event_threads.retain(|thread| self.is_thread_suspended(*thread));
+        self.event_threads = event_threads;
+    }
+
+    fn vm_suspended(&mut self) {
+        self.vm += 1;
+    }
+
+    fn vm_resumed(&mut self) {
+        if self.vm > 0 {
+            // Threads which are already running stay at zero
+            let vm = self.vm as i64;
+            for (thread, offset) in self.threads.iter_mut() {
+                if vm + *offset == 0 {
+                    *offset += 1;
+                } else if vm + *offset == 1 && vm > 1 {
+                    // Resumed on its own before, so it runs although the VM stays suspended
+                    Self::next_generation(&mut self.generation_offsets, &mut self.epoch, *thread);
+                }
+            }
+            self.vm -= 1;
+            if self.vm == 0 {
+                self.barrier();
+                for (thread, offset) in &self.threads {
+                    if *offset > 0 {
+                        let generation = self.generation_offsets.entry(*thread).or_insert(0);
+                        *generation = generation.wrapping_sub(1);
+                    }
+                }
+            }
+        } else {
+            for (thread, offset) in self.threads.iter_mut() {
+                if *offset > 0 {
+                    *offset -= 1;
+                    if *offset == 0 {
+                        Self::next_generation(
+                            &mut self.generation_offsets,
+                            &mut self.epoch,
+                            *thread,
+                        );
+                    }
+                }
+            }
+        }
+        self.threads.retain(|_, offset| *offset != 0);
+        self.forget_running_event_threads();
+    }
+
+    fn next_generation(
+        offsets: &mut HashMap<VariableLengthId, u64>,
+        epoch: &mut u64,
+        thread: VariableLengthId,
+    ) {
+        let generation = offsets.entry(thread).or_insert(0);
+        *generation = generation.wrapping_add(1);
+        *epoch = epoch.wrapping_add(1);
+    }
+
+    fn thread_suspended(&mut self, thread: VariableLengthId) {
+        *self.threads.entry(thread).or_insert(0) += 1;
+    }
+
+    fn thread_resumed(&mut self, thread: VariableLengthId) {
+        if self.count(thread) > 0 {
+            let offset = self.threads.entry(thread).or_insert(0);
+            *offset -= 1;
+            if *offset == 0 {
+                self.threads.remove(&thread);
+            }
+            if self.count(thread) == 0 {
+                Self::next_generation(&mut self.generation_offsets, &mut self.epoch, thread);
+            }
+        }
+        self.forget_running_event_threads();
+    }
+
+    pub(crate) fn apply(&mut self, change: SuspensionChange) {
+        match change {
+            SuspensionChange::VmSuspended => self.vm_suspended(),
+            SuspensionChange::VmResumed => self.vm_resumed(),
+            SuspensionChange::ThreadSuspended(thread) => self.thread_suspended(thread),
+            SuspensionChange::ThreadResumed(thread) => self.thread_resumed(thread),
+            SuspensionChange::Cleared => self.clear(),
+        }
+    }
+
+    /// Applies the suspend policy of an event set sent by the VM
+    pub(crate) fn event_received(&mut self, composite: &EventComposite) {
+        let mut threads: Vec<_> = composite.events.iter().filter_map(|e| e.thread()).collect();
+        threads.dedup();
+        match composite.suspend_policy {
+            // Nothing is known about the effect of an undefined policy
+            SuspendPolicy::None | SuspendPolicy::Unknown(_) => return,
+            SuspendPolicy::EventThread => {
+                for thread in &threads {
+                    self.thread_suspended(*thread);
+                }
+            }
+            SuspendPolicy::All => self.vm_suspended(),
+        }
+        for thread in threads {
+            self.event_threads.retain(|known| *known != thread);
+            self.event_threads.push(thread);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.barrier();
+        self.vm = 0;
+        self.threads.clear();
+        self.event_threads.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THREAD: VariableLengthId = VariableLengthId { value: 1 };
+    const OTHER: VariableLengthId = VariableLengthId { value: 2 };
+
+    #[test]
+    fn test_vm_suspend_is_counted() {
+        let mut state = SuspensionState::default();
+        state.vm_suspended();
+        state.vm_suspended();
+        state.vm_resumed();
+        assert!(state.is_thread_suspended(THREAD));
+        state.vm_resumed();
+        assert!(!state.is_thread_suspended(THREAD));
+        assert!(!state.is_vm_suspended());
+    }
+
+    #[test]
+    fn test_thread_and_vm_counts_combine() {
+        let mut state = SuspensionState::default();
+        state.thread_suspended(THREAD);
+        state.vm_suspended();
+        state.thread_resumed(OTHER);
+        state.vm_resumed();
+        assert!(state.is_thread_suspended(THREAD));
+        assert!(!state.is_thread_suspended(OTHER));
+
+        // Resuming the VM again also resumes threads suspended on their own
+        state.vm_resumed();
+        assert!(!state.is_thread_suspended(THREAD));
+    }
+
+    #[test]
+    fn test_resumed_thread_stays_running() {
+        let mut state = SuspensionState::default();
+        state.vm_suspended();
+        state.thread_resumed(THREAD);
+        assert!(!state.is_thread_suspended(THREAD));
+        assert!(state.is_thread_suspended(OTHER));
+        state.vm_resumed();
+        assert!(!state.is_thread_suspended(THREAD));
+        assert!(!state.is_thread_suspended(OTHER));
+    }
+
+    #[test]
+    fn test_generation_changes_when_thread_runs() {
+        let mut state = SuspensionState::default();
+        state.vm_suspended();
+        state.vm_suspended();
+        state.thread_suspended(THREAD);
+        let (thread, other) = (state.generation(THREAD), state.generation(OTHER));
+
+        // Still suspended once
+        state.vm_resumed();
+        assert_eq!(state.generation(OTHER), other);
+
+        // THREAD keeps its own suspension
+        state.vm_resumed();
+        assert_eq!(state.generation(THREAD), thread);
+        assert_ne!(state.generation(OTHER), other);
+
+        state.thread_resumed(THREAD);
+        assert_ne!(state.generation(THREAD), thread);
+
+        // OTHER is resumed on its own and runs once the VM count drops to one
+        state.vm_suspended();
+        state.vm_suspended();
+        state.thread_resumed(OTHER);
+        let other = state.generation(OTHER);
+        state.vm_resumed();
+        assert_ne!(state.generation(OTHER), other);
+    }
+
+    #[test]
+    fn test_leaked_suspensions() {
+        let mut state = SuspensionState::default();
+        state.vm_suspended();
+        state.vm_suspended();
+        state.thread_suspended(THREAD);
+        state.thread_resumed(OTHER);
+        assert_eq!(state.leaked(), (2, vec![(THREAD, 1)]));
+    }
+
+    #[test]
+    fn test_event_threads_until_resumed() {
+        let event = |thread: VariableLengthId, suspend_policy| EventComposite {
+            suspend_policy,
+            events: vec![crate::Event::ThreadStart {
+                request_id: 1,
+                thread,
+            }],
+        };
+        let mut state = SuspensionState::default();
+        state.event_received(&event(THREAD, SuspendPolicy::EventThread));
+        state.event_received(&event(OTHER, SuspendPolicy::All));
+        state.event_received(&event(VariableLengthId { value: 3 }, SuspendPolicy::None));
+        assert_eq!(
+            state.event_threads().collect::<Vec<_>>(),
+            vec![OTHER, THREAD]
+        );
+
+        // THREAD keeps the suspension of its own event
+        state.vm_resumed();
+        assert_eq!(state.event_threads().collect::<Vec<_>>(), vec![THREAD]);
+        state.thread_resumed(THREAD);
+        assert_eq!(state.event_threads().count(), 0);
+    }
+}
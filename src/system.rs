@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    ArrayValues, JdwpClient, JdwpValue, TaggedObjectId, TryFromJdwpValue, result,
+    type_name_to_signature,
+};
+
+const SYSTEM_CLASS: &str = "java.lang.System";
+
+impl JdwpClient {
+    /// Value of a system property of the debuggee, e.g. `java.class.path`, from
+    /// `System.getProperty`. The method is invoked like [JdwpClient::call_static].
+    pub async fn system_property(&self, name: &str) -> result::Result<Option<String>> {
+        self.call_static(SYSTEM_CLASS, "getProperty", (name,)).await
+    }
+
+    /// All system properties with string values, from `System.getProperties`. The methods are
+    /// invoked like [JdwpClient::call_static].
+    pub async fn system_properties(&self) -> result::Result<BTreeMap<String, String>> {
+        let thread_id = self.pick_invocation_thread().await?;
+        let system = self
+            .find_loaded_class(&type_name_to_signature(SYSTEM_CLASS))
+            .await?;
+        let properties = self
+            .invoke_static_method(thread_id, system, "getProperties", ())
+            .await?;
+        let properties = TaggedObjectId::try_from_jdwp_value(properties, self).await?;
+
+        let names = self
+            .invoke_method(thread_id, properties, "stringPropertyNames", ())
+            .await?;
+        let names = TaggedObjectId::try_from_jdwp_value(names, self).await?;
+        let names = self.invoke_method(thread_id, names, "toArray", ()).await?;
+        let names = TaggedObjectId::try_from_jdwp_value(names, self).await?;
+        let ArrayValues::Objects(names) = self.read_array(names.object_id).await? else {
+            return Err(result::Error::ParsingError {
+                message: String::from("toArray() didn't return an object array"),
+            });
+        };
+
+        let mut values = BTreeMap::new();
+        for name in names {
+            let value = self
+                .invoke_method(thread_id, properties, "getProperty", (name,))
+                .await?;
+            if let Some(value) = Option::<String>::try_from_jdwp_value(value, self).await? {
+                values.insert(
+                    String::try_from_jdwp_value(JdwpValue::Object(name), self).await?,
+                    value,
+                );
+            }
+        }
+        Ok(values)
+    }
+
+    /// Value of an environment variable of the debuggee, from `System.getenv`. The method is
+    /// invoked like [JdwpClient::call_static].
+    pub async fn environment_variable(&self, name: &str) -> result::Result<Option<String>> {
+        self.call_static(SYSTEM_CLASS, "getenv", (name,)).await
+    }
+}
@@ -0,0 +1,454 @@
+//! Wire encodings of commands, replies and events laid out as in the JDWP specification. The
+//! golden tests fail when a change to the binrw layer changes the bytes sent or accepted.
+
+use binrw::{BinRead, BinWrite};
+use std::io::Cursor;
+
+use crate::{
+    AllThreadsReply, ArrayGetValuesOut, ArrayGetValuesReply, ArrayValues, ClassInvokeMethodOut,
+    ClassStatus, ClassesBySignatureOut, ClassesBySignatureReply, Command, CreateStringOut, Event,
+    EventComposite, EventKind, EventModifier, EventRequestClearOut, EventRequestSetOut,
+    IdSizesReply, InvokeMethodReply, InvokeOptions, JdwpIdSizes, JdwpStringSlice, JdwpValue,
+    LineTableReply, Location, MethodsReply, ObjectInvokeMethodOut, PrimitiveArray, RefTypeOut,
+    ReplyPacketHeader, StepDepth, StepSize, SuspendPolicy, Tag, TaggedObjectId, TypeTag,
+    VariableLengthId, VersionReply, encode_command,
+};
+
+/// Id sizes of HotSpot on 64-bit platforms
+const SIZES: JdwpIdSizes = JdwpIdSizes {
+    field_id_size: 8,
+    method_id_size: 8,
+    object_id_size: 8,
+    reference_type_id_size: 8,
+    frame_id_size: 8,
+};
+
+/// Id sizes of VMs using 32-bit ids
+const SIZES_4: JdwpIdSizes = JdwpIdSizes {
+    field_id_size: 4,
+    method_id_size: 4,
+    object_id_size: 4,
+    reference_type_id_size: 4,
+    frame_id_size: 4,
+};
+
+fn id(value: u64) -> VariableLengthId {
+    VariableLengthId { value }
+}
+
+fn encode<T>(value: &T, sizes: JdwpIdSizes) -> Vec<u8>
+where
+    T: for<'a> BinWrite<Args<'a> = JdwpIdSizes>,
+{
+    let mut cursor = Cursor::new(Vec::new());
+    value.write_be_args(&mut cursor, sizes).unwrap();
+    cursor.into_inner()
+}
+
+/// Decodes a whole vector, failing if bytes are left over
+fn decode<T>(bytes: &[u8], sizes: JdwpIdSizes) -> T
+where
+    T: for<'a> BinRead<Args<'a> = JdwpIdSizes>,
+{
+    let mut cursor = Cursor::new(bytes);
+    let value = T::read_be_args(&mut cursor, sizes).unwrap();
+    assert_eq!(cursor.position() as usize, bytes.len(), "trailing bytes");
+    value
+}
+
+fn decode_plain<T>(bytes: &[u8]) -> T
+where
+    T: for<'a> BinRead<Args<'a> = ()>,
+{
+    let mut cursor = Cursor::new(bytes);
+    let value = T::read_be(&mut cursor).unwrap();
+    assert_eq!(cursor.position() as usize, bytes.len(), "trailing bytes");
+    value
+}
+
+fn encode_plain<T>(value: &T) -> Vec<u8>
+where
+    T: for<'a> BinWrite<Args<'a> = ()>,
+{
+    let mut cursor = Cursor::new(Vec::new());
+    value.write_be(&mut cursor).unwrap();
+    cursor.into_inner()
+}
+
+#[test]
+fn test_command_packet() {
+    // VirtualMachine.Version with id 1: length 11, id, flags, command set 1, command 1
+    const VECTOR: &[u8] = &[0, 0, 0, 11, 0, 0, 0, 1, 0x00, 1, 1];
+    let bytes = encode_command(1, Command::VirtualMachineVersion, &[]).unwrap();
+    assert_eq!(bytes, VECTOR);
+
+    // ThreadReference.Name with a body
+    const WITH_BODY: &[u8] = &[0, 0, 0, 15, 0, 0, 1, 0, 0x00, 11, 1, 0, 0, 0, 5];
+    let bytes = encode_command(0x100, Command::ThreadReferenceName, &[0, 0, 0, 5]).unwrap();
+    assert_eq!(bytes, WITH_BODY);
+}
+
+#[test]
+fn test_reply_packet_header() {
+    // Reply to packet 7 failing with INVALID_OBJECT (20)
+    const VECTOR: &[u8] = &[0, 0, 0, 11, 0, 0, 0, 7, 0x80, 0, 20];
+    let header: ReplyPacketHeader = decode_plain(VECTOR);
+    assert_eq!(header.length, 11);
+    assert_eq!(header.id, 7);
+    assert!(header.flags.is_reply());
+    assert_eq!(header.error_code, 20);
+    assert_eq!(encode_plain(&header), VECTOR);
+}
+
+#[test]
+fn test_version_reply() {
+    const VECTOR: &[u8] = &[
+        0, 0, 0, 3, b'J', b'D', b'K', // description
+        0, 0, 0, 1, // jdwpMajor
+        0, 0, 0, 21, // jdwpMinor
+        0, 0, 0, 2, b'2', b'1', // vmVersion
+        0, 0, 0, 2, b'H', b'S', // vmName
+    ];
+    let reply: VersionReply = decode_plain(VECTOR);
+    assert_eq!(reply.description, "JDK");
+    assert_eq!((reply.jdwp_major, reply.jdwp_minor), (1, 21));
+    assert_eq!(reply.vm_version, "21");
+    assert_eq!(reply.vm_name, "HS");
+    assert_eq!(encode_plain(&reply), VECTOR);
+}
+
+#[test]
+fn test_id_sizes_reply() {
+    const VECTOR: &[u8] = &[0, 0, 0, 8, 0, 0, 0, 8, 0, 0, 0, 8, 0, 0, 0, 8, 0, 0, 0, 8];
+    let reply: IdSizesReply = decode_plain(VECTOR);
+    assert_eq!(reply.field_id_size, 8);
+    assert_eq!(reply.method_id_size, 8);
+    assert_eq!(reply.object_id_size, 8);
+    assert_eq!(reply.reference_type_id_size, 8);
+    assert_eq!(reply.frame_id_size, 8);
+    assert_eq!(encode_plain(&reply), VECTOR);
+}
+
+#[test]
+fn test_classes_by_signature() {
+    const OUT: &[u8] = &[0, 0, 0, 3, b'L', b'A', b';'];
+    let out = ClassesBySignatureOut {
+        signature: JdwpStringSlice { value: "LA;" },
+    };
+    assert_eq!(encode_plain(&out), OUT);
+
+    const REPLY: &[u8] = &[
+        0, 0, 0, 1, // classes
+        1, // refTypeTag CLASS
+        0, 0, 0, 0, 0, 0, 0, 0x10, // typeID
+        0, 0, 0, 7, // VERIFIED | PREPARED | INITIALIZED
+    ];
+    let reply: ClassesBySignatureReply = decode(REPLY, SIZES);
+    assert_eq!(reply.classes.len(), 1);
+    assert_eq!(reply.classes[0].ref_type_tag, TypeTag::Class);
+    assert_eq!(reply.classes[0].type_id, id(0x10));
+    assert_eq!(
+        reply.classes[0].status,
+        ClassStatus::VERIFIED | ClassStatus::PREPARED | ClassStatus::INITIALIZED
+    );
+}
+
+#[test]
+fn test_all_threads_reply_with_4_byte_ids() {
+    const VECTOR: &[u8] = &[0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2];
+    let reply: AllThreadsReply = decode(VECTOR, SIZES_4);
+    let threads: Vec<_> = reply.threads.iter().map(|t| t.thread_id).collect();
+    assert_eq!(threads, vec![id(1), id(2)]);
+}
+
+#[test]
+fn test_create_string_out() {
+    const VECTOR: &[u8] = &[0, 0, 0, 2, 0xC3, 0xA9];
+    let out = CreateStringOut {
+        utf: JdwpStringSlice { value: "é" },
+    };
+    assert_eq!(encode_plain(&out), VECTOR);
+}
+
+#[test]
+fn test_ref_type_out() {
+    assert_eq!(
+        encode(
+            &RefTypeOut {
+                ref_type_id: id(0x10)
+            },
+            SIZES
+        ),
+        [0, 0, 0, 0, 0, 0, 0, 0x10]
+    );
+    assert_eq!(
+        encode(
+            &RefTypeOut {
+                ref_type_id: id(0x10)
+            },
+            SIZES_4
+        ),
+        [0, 0, 0, 0x10]
+    );
+}
+
+#[test]
+fn test_methods_reply() {
+    const VECTOR: &[u8] = &[
+        0, 0, 0, 1, // methods
+        0, 0, 0, 0, 0, 0, 0, 0x2, // methodID
+        0, 0, 0, 3, b'r', b'u', b'n', // name
+        0, 0, 0, 3, b'(', b')', b'V', // signature
+        0, 0, 0, 1, // modBits: public
+    ];
+    let reply: MethodsReply = decode(VECTOR, SIZES);
+    assert_eq!(reply.methods.len(), 1);
+    assert_eq!(reply.methods[0].method_id, id(2));
+    assert_eq!(reply.methods[0].name, "run");
+    assert_eq!(reply.methods[0].signature, "()V");
+    assert_eq!(reply.methods[0].mod_bits, 1);
+}
+
+#[test]
+fn test_class_invoke_method() {
+    const OUT: &[u8] = &[
+        0, 0, 0, 0, 0, 0, 0, 0x10, // clazz
+        0, 0, 0, 0, 0, 0, 0, 0x1, // thread
+        0, 0, 0, 0, 0, 0, 0, 0x2, // methodID
+        0, 0, 0, 2, // arguments
+        b'I', 0, 0, 0, 7, // int 7
+        b'Z', 1, // true
+        0, 0, 0, 1, // INVOKE_SINGLE_THREADED
+    ];
+    let out = ClassInvokeMethodOut {
+        class_id: id(0x10),
+        thread_id: id(1),
+        method_id: id(2),
+        arguments: vec![JdwpValue::Int(7), JdwpValue::Boolean(true)],
+        options: InvokeOptions::INVOKE_SINGLE_THREADED,
+    };
+    assert_eq!(encode(&out, SIZES), OUT);
+
+    const REPLY: &[u8] = &[
+        b'J', 0, 0, 0, 0, 0, 0, 0, 42, // returnValue
+        b'L', 0, 0, 0, 0, 0, 0, 0, 0, // exception: null
+    ];
+    let reply: InvokeMethodReply = decode(REPLY, SIZES);
+    assert_eq!(reply.return_value, JdwpValue::Long(42));
+    assert_eq!(reply.exception.tag, Tag::Object);
+    assert_eq!(reply.exception.object_id, id(0));
+}
+
+#[test]
+fn test_object_invoke_method_out() {
+    const VECTOR: &[u8] = &[
+        0, 0, 0, 0x50, // object
+        0, 0, 0, 0x1, // thread
+        0, 0, 0, 0x10, // clazz
+        0, 0, 0, 0x2, // methodID
+        0, 0, 0, 1, // arguments
+        b's', 0, 0, 0, 0x40, // string
+        0, 0, 0, 2, // INVOKE_NONVIRTUAL
+    ];
+    let out = ObjectInvokeMethodOut {
+        object_id: id(0x50),
+        thread_id: id(1),
+        class_id: id(0x10),
+        method_id: id(2),
+        arguments: vec![JdwpValue::Object(TaggedObjectId {
+            tag: Tag::String,
+            object_id: id(0x40),
+        })],
+        options: InvokeOptions::INVOKE_NONVIRTUAL,
+    };
+    assert_eq!(encode(&out, SIZES_4), VECTOR);
+}
+
+#[test]
+fn test_line_table_reply() {
+    const VECTOR: &[u8] = &[
+        0, 0, 0, 0, 0, 0, 0, 0, // start
+        0, 0, 0, 0, 0, 0, 0, 9, // end
+        0, 0, 0, 2, // lines
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, // index 0, line 3
+        0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 4, // index 4, line 4
+    ];
+    let reply: LineTableReply = decode_plain(VECTOR);
+    assert_eq!((reply.start, reply.end), (0, 9));
+    let lines: Vec<_> = reply
+        .lines
+        .iter()
+        .map(|l| (l.line_code_index, l.line_number))
+        .collect();
+    assert_eq!(lines, vec![(0, 3), (4, 4)]);
+    assert_eq!(encode_plain(&reply), VECTOR);
+}
+
+#[test]
+fn test_array_get_values() {
+    const OUT: &[u8] = &[0, 0, 0, 0x80, 0, 0, 0, 1, 0, 0, 0, 2];
+    let out = ArrayGetValuesOut {
+        array_id: id(0x80),
+        first_index: 1,
+        length: 2,
+    };
+    assert_eq!(encode(&out, SIZES_4), OUT);
+
+    // Primitive values are untagged
+    const INTS: &[u8] = &[b'I', 0, 0, 0, 2, 0, 0, 0, 1, 0xFF, 0xFF, 0xFF, 0xFF];
+    let reply: ArrayGetValuesReply = decode(INTS, SIZES_4);
+    assert!(matches!(
+        reply.values,
+        ArrayValues::Primitive(PrimitiveArray::Int(ref v)) if v == &[1, -1]
+    ));
+
+    // Object values carry their own tag
+    const OBJECTS: &[u8] = &[b'L', 0, 0, 0, 2, b's', 0, 0, 0, 9, b'L', 0, 0, 0, 0];
+    let reply: ArrayGetValuesReply = decode(OBJECTS, SIZES_4);
+    let ArrayValues::Objects(objects) = reply.values else {
+        panic!("expected objects");
+    };
+    assert_eq!(objects[0].tag, Tag::String);
+    assert_eq!(objects[0].object_id, id(9));
+    assert_eq!(objects[1].tag, Tag::Object);
+    assert_eq!(objects[1].object_id, id(0));
+}
+
+#[test]
+fn test_event_request_set_out() {
+    const VECTOR: &[u8] = &[
+        2, // BREAKPOINT
+        1, // EVENT_THREAD
+        0, 0, 0, 3, // modifiers
+        1, 0, 0, 0, 1, // Count 1
+        5, 0, 0, 0, 5, b'j', b'a', b'v', b'a', b'*', // ClassMatch
+        7, 1, 0, 0, 0, 0x10, 0, 0, 0, 0x2, 0, 0, 0, 0, 0, 0, 0, 4, // LocationOnly
+    ];
+    let out = EventRequestSetOut {
+        event_kind: EventKind::Breakpoint,
+        suspend_policy: SuspendPolicy::EventThread,
+        modifiers: vec![
+            EventModifier::Count(1),
+            EventModifier::ClassMatch(String::from("java*")),
+            EventModifier::LocationOnly(Location {
+                type_tag: TypeTag::Class,
+                class_id: id(0x10),
+                method_id: id(2),
+                index: 4,
+            }),
+        ],
+    };
+    assert_eq!(encode(&out, SIZES_4), VECTOR);
+
+    const STEP: &[u8] = &[
+        1, // SINGLE_STEP
+        2, // ALL
+        0, 0, 0, 1, // modifiers
+        10, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, // Step: thread 1, LINE, OVER
+    ];
+    let out = EventRequestSetOut {
+        event_kind: EventKind::SingleStep,
+        suspend_policy: SuspendPolicy::All,
+        modifiers: vec![EventModifier::Step {
+            thread: id(1),
+            size: StepSize::Line,
+            depth: StepDepth::Over,
+        }],
+    };
+    assert_eq!(encode(&out, SIZES_4), STEP);
+
+    // Decoded as a VM would
+    let decoded: EventRequestSetOut = decode(VECTOR, SIZES_4);
+    assert_eq!(decoded.event_kind, EventKind::Breakpoint);
+    assert_eq!(decoded.suspend_policy, SuspendPolicy::EventThread);
+    assert_eq!(decoded.modifiers.len(), 3);
+    assert_eq!(
+        decoded.modifiers[2],
+        EventModifier::LocationOnly(Location {
+            type_tag: TypeTag::Class,
+            class_id: id(0x10),
+            method_id: id(2),
+            index: 4,
+        })
+    );
+    let decoded: EventRequestSetOut = decode(STEP, SIZES_4);
+    assert_eq!(decoded.modifiers, out.modifiers);
+}
+
+#[test]
+fn test_event_request_clear_out() {
+    const VECTOR: &[u8] = &[8, 0, 0, 0, 3];
+    let out = EventRequestClearOut {
+        event_kind: EventKind::ClassPrepare,
+        request_id: 3,
+    };
+    assert_eq!(encode_plain(&out), VECTOR);
+}
+
+#[test]
+fn test_class_prepare_event() {
+    const VECTOR: &[u8] = &[
+        1, // EVENT_THREAD
+        0, 0, 0, 1, // events
+        8, // CLASS_PREPARE
+        0, 0, 0, 2, // requestID
+        0, 0, 0, 1, // thread
+        1, // refTypeTag
+        0, 0, 0, 0x10, // typeID
+        0, 0, 0, 3, b'L', b'A', b';', // signature
+        0, 0, 0, 3, // VERIFIED | PREPARED
+    ];
+    let composite: EventComposite = decode(VECTOR, SIZES_4);
+    assert_eq!(composite.suspend_policy, SuspendPolicy::EventThread);
+    assert_eq!(
+        composite.events,
+        vec![Event::ClassPrepare {
+            request_id: 2,
+            thread: id(1),
+            ref_type_tag: TypeTag::Class,
+            type_id: id(0x10),
+            signature: crate::JdwpString {
+                string: String::from("LA;"),
+            },
+            status: ClassStatus::VERIFIED | ClassStatus::PREPARED,
+        }]
+    );
+}
+
+#[test]
+fn test_exception_event() {
+    const VECTOR: &[u8] = &[
+        2, // ALL
+        0, 0, 0, 1, // events
+        4, // EXCEPTION
+        0, 0, 0, 6, // requestID
+        0, 0, 0, 1, // thread
+        1, 0, 0, 0, 0x10, 0, 0, 0, 0x2, 0, 0, 0, 0, 0, 0, 0, 7, // location
+        b'L', 0, 0, 0, 0x50, // exception
+        // catchLocation of an uncaught exception, which HotSpot sends with a CLASS tag
+        1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ];
+    let composite: EventComposite = decode(VECTOR, SIZES_4);
+    let [
+        Event::Exception {
+            exception,
+            location,
+            catch_location,
+            ..
+        },
+    ] = composite.events.as_slice()
+    else {
+        panic!("expected one exception event");
+    };
+    assert_eq!(exception.object_id, id(0x50));
+    assert_eq!(location.index, 7);
+    assert_eq!(catch_location.class_id, id(0));
+}
+
+#[test]
+fn test_vm_death_event() {
+    const VECTOR: &[u8] = &[0, 0, 0, 0, 1, 99, 0, 0, 0, 0];
+    let composite: EventComposite = decode(VECTOR, SIZES);
+    assert_eq!(composite.suspend_policy, SuspendPolicy::None);
+    assert_eq!(composite.events, vec![Event::VmDeath { request_id: 0 }]);
+}
@@ -0,0 +1,180 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use crate::{JdwpClient, JdwpValue, SuspendStatus, ThreadStatus, VariableLengthId, result};
+
+/// A thread in a [ThreadGroupNode]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadInfo {
+    pub thread_id: VariableLengthId,
+    pub name: String,
+    pub status: ThreadStatus,
+    pub suspended: bool,
+    /// None if the `daemon` field of java.lang.Thread couldn't be found in this VM version
+    pub daemon: Option<bool>,
+}
+
+/// A thread group with its threads and subgroups, see [JdwpClient::thread_groups_tree]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadGroupNode {
+    pub group_id: VariableLengthId,
+    pub name: String,
+    pub threads: Vec<ThreadInfo>,
+    pub groups: Vec<ThreadGroupNode>,
+}
+impl ThreadGroupNode {
+    /// The first group with the name in this subtree, searched depth-first
+    pub fn find_group(&self, name: &str) -> Option<&ThreadGroupNode> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.groups.iter().find_map(|group| group.find_group(name))
+    }
+
+    /// Threads of this group and all its subgroups
+    pub fn all_threads(&self) -> Vec<&ThreadInfo> {
+        let mut threads = Vec::new();
+        let mut pending = vec![self];
+        while let Some(group) = pending.pop() {
+            threads.extend(&group.threads);
+            pending.extend(group.groups.iter().rev());
+        }
+        threads
+    }
+}
+
+/// Where the daemon flag of a thread is stored, which changed in JDK 19
+#[derive(Debug, Clone, Copy)]
+enum DaemonField {
+    /// `Thread.daemon`
+    Thread(VariableLengthId),
+    /// `Thread.holder.daemon`
+    Holder {
+        holder: VariableLengthId,
+        daemon: VariableLengthId,
+    },
+}
+
+type GroupFuture<'a> = Pin<Box<dyn Future<Output = result::Result<ThreadGroupNode>> + Send + 'a>>;
+
+impl JdwpClient {
+    /// The thread groups of the VM as a tree, with name, status and daemon flag of each
+    /// thread. The tree is a snapshot; threads may have started or ended by the time it's
+    /// returned.
+    pub async fn thread_groups_tree(&self) -> result::Result<Vec<ThreadGroupNode>> {
+        let top_level = self.vm_get_top_level_thread_groups().await?.threads_groups;
+        let daemon_field = self.daemon_field().await?;
+        let mut groups = Vec::with_capacity(top_level.len());
+        for group in top_level {
+            groups.push(
+                self.thread_group_node(group.thread_group_id, daemon_field)
+                    .await?,
+            );
+        }
+        Ok(groups)
+    }
+
+    fn thread_group_node(
+        &self,
+        group_id: VariableLengthId,
+        daemon_field: Option<DaemonField>,
+    ) -> GroupFuture<'_> {
+        Box::pin(async move {
+            let name = self
+                .thread_group_get_name(group_id)
+                .await?
+                .group_name
+                .string;
+            let children = self.thread_group_get_children(group_id).await?;
+
+            let mut threads = Vec::with_capacity(children.child_threads.len());
+            for thread_id in children.child_threads {
+                let status = self.thread_get_status(thread_id).await?;
+                let daemon = match daemon_field {
+                    Some(field) => self.thread_daemon(thread_id, field).await?,
+                    None => None,
+                };
+                threads.push(ThreadInfo {
+                    thread_id,
+                    name: self.thread_name(thread_id).await?,
+                    status: status.thread_status,
+                    suspended: status.suspend_status.contains(SuspendStatus::SUSPENDED),
+                    daemon,
+                });
+            }
+
+            let mut groups = Vec::with_capacity(children.child_groups.len());
+            for child in children.child_groups {
+                groups.push(self.thread_group_node(child, daemon_field).await?);
+            }
+            Ok(ThreadGroupNode {
+                group_id,
+                name,
+                threads,
+                groups,
+            })
+        })
+    }
+
+    async fn daemon_field(&self) -> result::Result<Option<DaemonField>> {
+        let field = |class_id: VariableLengthId, name: &'static str| async move {
+            let fields = self.ref_type_get_fields(class_id).await?.fields;
+            result::Result::Ok(
+                fields
+                    .into_iter()
+                    .find(|field| field.name.string == name)
+                    .map(|field| field.field_id),
+            )
+        };
+
+        let Ok(thread_class) = self.find_loaded_class("Ljava/lang/Thread;").await else {
+            return Ok(None);
+        };
+        if let Some(daemon) = field(thread_class, "daemon").await? {
+            return Ok(Some(DaemonField::Thread(daemon)));
+        }
+        let Some(holder) = field(thread_class, "holder").await? else {
+            return Ok(None);
+        };
+        let Ok(holder_class) = self
+            .find_loaded_class("Ljava/lang/Thread$FieldHolder;")
+            .await
+        else {
+            return Ok(None);
+        };
+        Ok(field(holder_class, "daemon")
+            .await?
+            .map(|daemon| DaemonField::Holder { holder, daemon }))
+    }
+
+    async fn thread_daemon(
+        &self,
+        thread_id: VariableLengthId,
+        field: DaemonField,
+    ) -> result::Result<Option<bool>> {
+        let (object_id, daemon) = match field {
+            DaemonField::Thread(daemon) => (thread_id, daemon),
+            DaemonField::Holder { holder, daemon } => {
+                match self
+                    .object_get_values(thread_id, vec![holder])
+                    .await?
+                    .values[..]
+                {
+                    // Virtual threads have no holder
+                    [JdwpValue::Object(holder)] if holder.object_id.value != 0 => {
+                        (holder.object_id, daemon)
+                    }
+                    _ => return Ok(None),
+                }
+            }
+        };
+        match self
+            .object_get_values(object_id, vec![daemon])
+            .await?
+            .values[..]
+        {
+            [JdwpValue::Boolean(daemon)] => Ok(Some(daemon)),
+            _ => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::{JdwpClient, VariableLengthId, result};
+
+/// How helpers which invoke methods without being given a thread, like
+/// [JdwpClient::call_static], choose the thread to invoke in
+///
+/// The VM only runs invocations in threads stopped at an event, e.g. a breakpoint or step. A
+/// thread which is only suspended by VirtualMachine.Suspend or ThreadReference.Suspend is
+/// rejected with INVALID_THREAD. No thread is created for invocations, so when no thread has
+/// stopped at an event yet, one has to be stopped first, e.g. with
+/// [JdwpClient::set_line_breakpoint].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThreadPicker {
+    /// The thread which most recently stopped at an event and is still suspended, otherwise
+    /// any thread known to be suspended
+    #[default]
+    PreferEventThread,
+    /// The thread which most recently stopped at an event and is still suspended
+    EventThreadOnly,
+    /// The first thread of VirtualMachine.AllThreads known to be suspended
+    AnySuspended,
+    /// Always the given thread
+    Thread(VariableLengthId),
+}
+
+impl JdwpClient {
+    /// Sets how helpers which invoke methods choose their thread
+    pub fn set_thread_picker(&mut self, thread_picker: ThreadPicker) {
+        self.thread_picker = thread_picker;
+    }
+
+    pub fn thread_picker(&self) -> ThreadPicker {
+        self.thread_picker
+    }
+
+    /// Whether the thread stopped at an event and is still suspended, as seen by this client
+    pub fn is_thread_at_event(&self, thread_id: VariableLengthId) -> bool {
+        self.shared
+            .suspension()
+            .event_threads()
+            .any(|thread| thread == thread_id)
+    }
+
+    /// The thread helpers would invoke methods in, chosen by the [ThreadPicker] of this client
+    pub async fn pick_invocation_thread(&self) -> result::Result<VariableLengthId> {
+        let event_thread = self.shared.suspension().event_threads().next();
+        match (self.thread_picker, event_thread) {
+            (ThreadPicker::Thread(thread_id), _) => Ok(thread_id),
+            (ThreadPicker::PreferEventThread | ThreadPicker::EventThreadOnly, Some(thread_id)) => {
+                Ok(thread_id)
+            }
+            (ThreadPicker::EventThreadOnly, None) => Err(result::Error::NoSuspendedThread),
+            (ThreadPicker::PreferEventThread | ThreadPicker::AnySuspended, _) => self
+                .vm_get_all_threads()
+                .await?
+                .threads
+                .iter()
+                .map(|thread| thread.thread_id)
+                .find(|thread_id| self.is_thread_suspended(*thread_id))
+                .ok_or(result::Error::NoSuspendedThread),
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::{
+    Event, EventKind, EventModifier, JdwpClient, Location, SuspendPolicy, VariableLengthId, result,
+    signature_to_type_name,
+};
+
+/// A method call recorded by a [Tracer]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    /// Where the method was entered
+    pub location: Location,
+    /// `com.example.Main.run`, or the class and method ids if they couldn't be resolved
+    pub name: String,
+    /// Time since the tracer started until the method was entered
+    pub start: Duration,
+    pub duration: Duration,
+    /// False if the method was still running when the tracer stopped
+    pub complete: bool,
+    pub children: Vec<Call>,
+}
+
+/// Call trees recorded by [Tracer::stop], one per thread
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    pub threads: HashMap<VariableLengthId, Vec<Call>>,
+}
+impl Trace {
+    /// Writes the calls as Chrome trace events (complete `X` events with microsecond
+    /// timestamps), which `chrome://tracing` and Perfetto open as a flame chart per thread
+    pub fn to_chrome_trace(&self) -> String {
+        let mut threads: Vec<_> = self.threads.iter().collect();
+        threads.sort_by_key(|(thread, _)| thread.value);
+
+        let mut events = Vec::new();
+        for (thread, calls) in threads {
+            let mut pending: Vec<&Call> = calls.iter().rev().collect();
+            while let Some(call) = pending.pop() {
+                events.push(format!(
+                    "{{\"name\":{},\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":1,\"tid\":{}}}",
+                    json_string(&call.name),
+                    call.start.as_micros(),
+                    call.duration.as_micros(),
+                    thread.value
+                ));
+                pending.extend(call.children.iter().rev());
+            }
+        }
+        format!("{{\"traceEvents\":[{}]}}", events.join(","))
+    }
+}
+
+#[derive(Default)]
+struct ThreadCalls {
+    roots: Vec<Call>,
+    stack: Vec<Call>,
+}
+impl ThreadCalls {
+    fn exit(&mut self, mut call: Call, end: Duration) {
+        call.duration = end.saturating_sub(call.start);
+        match self.stack.last_mut() {
+            Some(parent) => parent.children.push(call),
+            None => self.roots.push(call),
+        }
+    }
+}
+
+/// Records method calls with METHOD_ENTRY and METHOD_EXIT requests, turning the client into a
+/// lightweight tracing profiler
+///
+/// The requests don't suspend the debuggee, so timings are taken when the events are received
+/// and include the jitter of the connection. Events of the requests also show up in the
+/// client's event queue.
+pub struct Tracer {
+    client: JdwpClient,
+    requests: Vec<(EventKind, i32)>,
+    hits: mpsc::UnboundedReceiver<(Instant, Event)>,
+    started: Instant,
+    threads: HashMap<VariableLengthId, ThreadCalls>,
+}
+
+impl Tracer {
+    /// Starts tracing methods of classes matching any of `class_patterns` (e.g. `com.acme.*`),
+    /// or of all classes if there are none, except classes matching `exclude_patterns`
+    pub async fn start(
+        client: JdwpClient,
+        class_patterns: &[&str],
+        exclude_patterns: &[&str],
+    ) -> result::Result<Self> {
+        let (route, mut events) = client.event_channel();
+        let (timed, hits) = mpsc::unbounded_channel();
+        // Events are timestamped as they arrive rather than when they're recorded
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                if timed.send((Instant::now(), event)).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut tracer = Tracer {
+            client,
+            requests: Vec::new(),
+            hits,
+            started: Instant::now(),
+            threads: HashMap::new(),
+        };
+
+        // Modifiers of a request must all match, so each pattern needs its own requests
+        let patterns: Vec<Option<&str>> = match class_patterns.is_empty() {
+            true => vec![None],
+            false => class_patterns.iter().copied().map(Some).collect(),
+        };
+        for pattern in patterns {
+            let mut modifiers: Vec<EventModifier> = exclude_patterns
+                .iter()
+                .map(|exclude| EventModifier::ClassExclude(String::from(*exclude)))
+                .collect();
+            if let Some(pattern) = pattern {
+                modifiers.insert(0, EventModifier::ClassMatch(String::from(pattern)));
+            }
+            for event_kind in [EventKind::MethodEntry, EventKind::MethodExit] {
+                let request = tracer
+                    .client
+                    .event_request_set_routed(
+                        event_kind,
+                        SuspendPolicy::None,
+                        modifiers.clone(),
+                        route.clone(),
+                    )
+                    .await;
+                match request {
+                    Ok(request_id) => tracer.requests.push((event_kind, request_id)),
+                    Err(e) => {
+                        tracer.clear().await;
+                        return Err(e);
+                    }
+                }
+            }
+        }
+        tracer.started = Instant::now();
+        Ok(tracer)
+    }
+
+    /// Records the calls of events received so far without waiting
+    pub fn record_pending(&mut self) {
+        while let Ok((received, event)) = self.hits.try_recv() {
+            self.record(received, event);
+        }
+    }
+
+    /// Records calls for `duration`, or until the connection is closed
+    pub async fn record_for(&mut self, duration: Duration) {
+        let deadline = tokio::time::Instant::now() + duration;
+        while let Ok(Some((received, event))) =
+            tokio::time::timeout_at(deadline, self.hits.recv()).await
+        {
+            self.record(received, event);
+        }
+    }
+
+    /// Clears the requests and returns the recorded call trees. Calls still running are ended
+    /// at the time the tracer stopped.
+    pub async fn stop(mut self) -> result::Result<Trace> {
+        self.record_pending();
+        let end = self.started.elapsed();
+        self.clear().await;
+
+        let mut threads = HashMap::new();
+        for (thread, mut calls) in self.threads.drain() {
+            while let Some(call) = calls.stack.pop() {
+                calls.exit(call, end);
+            }
+            threads.insert(thread, calls.roots);
+        }
+
+        let mut names = HashMap::new();
+        for calls in threads.values_mut() {
+            for call in calls {
+                resolve_names(&self.client, call, &mut names).await?;
+            }
+        }
+        Ok(Trace { threads })
+    }
+
+    fn record(&mut self, received: Instant, event: Event) {
+        let now = received.saturating_duration_since(self.started);
+        match event {
+            Event::MethodEntry {
+                thread, location, ..
+            } => self.threads.entry(thread).or_default().stack.push(Call {
+                location,
+                name: String::new(),
+                start: now,
+                duration: Duration::ZERO,
+                complete: false,
+                children: Vec::new(),
+            }),
+            Event::MethodExit {
+                thread, location, ..
+            }
+            | Event::MethodExitWithReturnValue {
+                thread, location, ..
+            } => {
+                let Some(calls) = self.threads.get_mut(&thread) else {
+                    return;
+                };
+                // Exits of calls entered before the tracer started have no matching entry
+                let matches = calls.stack.last().is_some_and(|call| {
+                    call.location.class_id == location.class_id
+                        && call.location.method_id == location.method_id
+                });
+                if matches && let Some(mut call) = calls.stack.pop() {
+                    call.complete = true;
+                    calls.exit(call, now);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    async fn clear(&mut self) {
+        for (event_kind, request_id) in self.requests.drain(..) {
+            // The connection may already be closed
+            let _ = self
+                .client
+                .event_request_clear(event_kind, request_id)
+                .await;
+        }
+    }
+}
+
+/// Fills in the names of `call` and its children, caching the methods of each class
+async fn resolve_names(
+    client: &JdwpClient,
+    call: &mut Call,
+    names: &mut HashMap<(VariableLengthId, VariableLengthId), String>,
+) -> result::Result<()> {
+    let mut pending = vec![call];
+    while let Some(call) = pending.pop() {
+        let key = (call.location.class_id, call.location.method_id);
+        if !names.contains_key(&key) {
+            let class_id = call.location.class_id;
+            let class_name = match client.ref_type_get_signature(class_id).await {
+                Ok(reply) => signature_to_type_name(&reply.signature.string),
+                Err(result::Error::JdwpError(_)) => format!("class@{:x}", class_id.value),
+                Err(e) => return Err(e),
+            };
+            let methods = match client.ref_type_get_methods(class_id).await {
+                Ok(reply) => reply.methods,
+                Err(result::Error::JdwpError(_)) => Vec::new(),
+                Err(e) => return Err(e),
+            };
+            for method in methods {
+                names.insert(
+                    (class_id, method.method_id),
+                    format!("{}.{}", class_name, method.name.string),
+                );
+            }
+            names
+                .entry(key)
+                .or_insert_with(|| format!("{}.method@{:x}", class_name, key.1.value));
+        }
+        call.name = names[&key].clone();
+        pending.extend(call.children.iter_mut());
+    }
+    Ok(())
+}
+
+fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TypeTag;
+
+    fn call(name: &str, start: u64, duration: u64, children: Vec<Call>) -> Call {
+        Call {
+            location: Location {
+                type_tag: TypeTag::Class,
+                class_id: VariableLengthId { value: 1 },
+                method_id: VariableLengthId { value: 1 },
+                index: 0,
+            },
+            name: String::from(name),
+            start: Duration::from_micros(start),
+            duration: Duration::from_micros(duration),
+            complete: true,
+            children,
+        }
+    }
+
+    #[test]
+    fn test_chrome_trace() {
+        let mut trace = Trace::default();
+        trace.threads.insert(
+            VariableLengthId { value: 7 },
+            vec![call(
+                "Main.run",
+                10,
+                50,
+                vec![call("Main.\"step\"", 20, 5, vec![])],
+            )],
+        );
+        assert_eq!(
+            trace.to_chrome_trace(),
+            "{\"traceEvents\":[\
+             {\"name\":\"Main.run\",\"ph\":\"X\",\"ts\":10,\"dur\":50,\"pid\":1,\"tid\":7},\
+             {\"name\":\"Main.\\\"step\\\"\",\"ph\":\"X\",\"ts\":20,\"dur\":5,\"pid\":1,\"tid\":7}\
+             ]}"
+        );
+    }
+}
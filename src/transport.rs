@@ -0,0 +1,600 @@
+#[cfg(feature = "ssh")]
+use std::path::PathBuf;
+
+use socket2::{SockRef, TcpKeepalive};
+use std::io;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::time::{Instant, sleep, timeout_at};
+
+use crate::connection::do_handshake;
+use crate::proxy::{self, ProxyConfig};
+use crate::{JdwpClient, RetryRule, TargetKind, result};
+
+/// Socket options of TCP connections to the VM, see [ClientConfig::tcp]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcpOptions {
+    /// Disables Nagle's algorithm, so small commands such as a step aren't held back waiting
+    /// for the reply to the previous one
+    pub nodelay: bool,
+    /// Idle time after which keepalive probes are sent, None leaves keepalive off. Keeps
+    /// connections through NATs and firewalls open while the VM is suspended.
+    pub keepalive: Option<Duration>,
+    /// SO_SNDBUF in bytes, None keeps the system default
+    pub send_buffer_size: Option<usize>,
+    /// SO_RCVBUF in bytes, None keeps the system default. Larger buffers help with bulk
+    /// transfers such as array dumps over links with a high latency.
+    pub recv_buffer_size: Option<usize>,
+}
+impl Default for TcpOptions {
+    fn default() -> Self {
+        TcpOptions {
+            nodelay: true,
+            keepalive: None,
+            send_buffer_size: None,
+            recv_buffer_size: None,
+        }
+    }
+}
+impl TcpOptions {
+    pub(crate) fn apply(&self, stream: &TcpStream) -> io::Result<()> {
+        let socket = SockRef::from(stream);
+        socket.set_tcp_nodelay(self.nodelay)?;
+        if let Some(idle) = self.keepalive {
+            socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(idle))?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        Ok(())
+    }
+}
+
+/// Options for establishing a connection with [Transport::connect_with]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientConfig {
+    proxy: Option<ProxyConfig>,
+    tcp: TcpOptions,
+    #[cfg(feature = "compression")]
+    compression: bool,
+    target: TargetKind,
+}
+impl ClientConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes TCP connections through a SOCKS5 or HTTP CONNECT proxy
+    pub fn proxy(mut self, proxy: ProxyConfig) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    pub fn get_proxy(&self) -> Option<&ProxyConfig> {
+        self.proxy.as_ref()
+    }
+
+    /// Socket options of TCP connections, including those to a proxy
+    pub fn tcp(mut self, tcp: TcpOptions) -> Self {
+        self.tcp = tcp;
+        self
+    }
+
+    pub fn get_tcp(&self) -> &TcpOptions {
+        &self.tcp
+    }
+
+    /// Compresses TCP connections with zstd. The address connected to must be a
+    /// [crate::CompressionRelay], which decompresses for the VM.
+    #[cfg(feature = "compression")]
+    pub fn compression(mut self, enabled: bool) -> Self {
+        self.compression = enabled;
+        self
+    }
+
+    #[cfg(feature = "compression")]
+    pub fn get_compression(&self) -> bool {
+        self.compression
+    }
+
+    /// Kind of VM connected to, which the client adapts to right after the handshake, see
+    /// [JdwpClient::adapt_to_target]
+    pub fn target(mut self, target: TargetKind) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn get_target(&self) -> TargetKind {
+        self.target
+    }
+}
+
+/// Way of reaching the JDWP agent of a VM
+#[derive(Debug, Clone)]
+pub struct Transport {
+    kind: TransportKind,
+}
+
+#[derive(Debug, Clone)]
+enum TransportKind {
+    Tcp {
+        address: String,
+    },
+    #[cfg(feature = "ssh")]
+    Ssh {
+        host: String,
+        credentials: SshCredentials,
+        target_address: String,
+    },
+    #[cfg(all(windows, feature = "shmem"))]
+    Shmem {
+        address: String,
+    },
+    #[cfg(feature = "websocket")]
+    WebSocket {
+        url: String,
+    },
+}
+
+impl Transport {
+    /// Plain TCP connection to `address`, e.g. `localhost:5005`
+    pub fn tcp(address: impl Into<String>) -> Self {
+        Transport {
+            kind: TransportKind::Tcp {
+                address: address.into(),
+            },
+        }
+    }
+
+    /// Connection to `target_address` as seen from the SSH server `host`, for VMs which only
+    /// listen on an address the SSH server can reach, e.g. `localhost:5005`
+    #[cfg(feature = "ssh")]
+    pub fn ssh(
+        host: impl Into<String>,
+        credentials: SshCredentials,
+        target_address: impl Into<String>,
+    ) -> Self {
+        Transport {
+            kind: TransportKind::Ssh {
+                host: host.into(),
+                credentials,
+                target_address: target_address.into(),
+            },
+        }
+    }
+
+    /// Connection to a VM on this machine started with `transport=dt_shmem`, listening on the
+    /// shared memory called `address`, e.g. `javadebug`
+    #[cfg(all(windows, feature = "shmem"))]
+    pub fn shmem(address: impl Into<String>) -> Self {
+        Transport {
+            kind: TransportKind::Shmem {
+                address: address.into(),
+            },
+        }
+    }
+
+    /// Connection through a WebSocket relay such as [crate::WebSocketRelay], at a
+    /// `ws://host:port/path` URL
+    #[cfg(feature = "websocket")]
+    pub fn websocket(url: impl Into<String>) -> Self {
+        Transport {
+            kind: TransportKind::WebSocket { url: url.into() },
+        }
+    }
+
+    /// Connects and performs the JDWP handshake
+    pub async fn connect(&self) -> result::Result<JdwpClient> {
+        self.connect_with(&ClientConfig::default()).await
+    }
+
+    /// Connects with the given options and performs the JDWP handshake. The proxy and socket
+    /// options only apply to TCP connections.
+    pub async fn connect_with(&self, config: &ClientConfig) -> result::Result<JdwpClient> {
+        let client = self.connect_stream(config).await?;
+        client.adapt_to_target(config.target).await?;
+        Ok(client)
+    }
+
+    async fn connect_stream(&self, config: &ClientConfig) -> result::Result<JdwpClient> {
+        match &self.kind {
+            TransportKind::Tcp { address } => {
+                let stream = match &config.proxy {
+                    Some(proxy) => proxy::connect(proxy, address).await?,
+                    None => TcpStream::connect(address.as_str()).await?,
+                };
+                config.tcp.apply(&stream)?;
+                #[cfg(feature = "compression")]
+                if config.compression {
+                    return JdwpClient::new(crate::compression::compress(stream)?).await;
+                }
+                JdwpClient::new(stream).await
+            }
+            #[cfg(feature = "ssh")]
+            TransportKind::Ssh {
+                host,
+                credentials,
+                target_address,
+            } => JdwpClient::new(ssh::connect(host, credentials, target_address).await?).await,
+            #[cfg(all(windows, feature = "shmem"))]
+            TransportKind::Shmem { address } => {
+                JdwpClient::new(crate::shmem::connect(address).await?).await
+            }
+            #[cfg(feature = "websocket")]
+            TransportKind::WebSocket { url } => {
+                JdwpClient::new(crate::websocket::connect(url).await?).await
+            }
+        }
+    }
+}
+
+impl JdwpClient {
+    /// Connects to `address` over TCP, retrying with exponential backoff until the JDWP agent
+    /// listens or `deadline` runs out. Meant for attaching to a VM or container which was just
+    /// started.
+    ///
+    /// Refused connections are retried, as are connections closed before the handshake
+    /// completes, which is what port forwarders do while nothing listens behind them. Once the
+    /// deadline runs out the error is [result::Error::ConnectionRefused] if nothing ever
+    /// accepted the connection and [result::Error::HandshakeFailed] otherwise. A peer answering
+    /// the handshake with something else fails immediately. Connections use the default
+    /// [TcpOptions].
+    pub async fn connect_with_retry(address: &str, deadline: Duration) -> result::Result<Self> {
+        let backoff = RetryRule {
+            max_retries: u32::MAX,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+        };
+        let give_up = Instant::now() + deadline;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let failure = match timeout_at(give_up, TcpStream::connect(address)).await {
+                Ok(Ok(mut stream)) => {
+                    TcpOptions::default().apply(&stream)?;
+                    match timeout_at(give_up, do_handshake(&mut stream)).await {
+                        Ok(Ok(())) => return JdwpClient::after_handshake(stream).await,
+                        Ok(Err(result::Error::IoError(e))) if is_closed_early(&e) => {
+                            handshake_failed(address, format!("{:?}", e))
+                        }
+                        Ok(Err(e)) => return Err(handshake_failed(address, format!("{:?}", e))),
+                        Err(_) => {
+                            return Err(handshake_failed(address, String::from("Timed out")));
+                        }
+                    }
+                }
+                Ok(Err(e)) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                    result::Error::ConnectionRefused {
+                        address: String::from(address),
+                        attempts,
+                    }
+                }
+                Ok(Err(e)) => return Err(e.into()),
+                Err(_) => {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "Connect timed out").into());
+                }
+            };
+
+            let delay = backoff.backoff(attempts - 1);
+            if Instant::now() + delay >= give_up {
+                return Err(failure);
+            }
+            sleep(delay).await;
+        }
+    }
+}
+
+fn is_closed_early(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+    )
+}
+
+fn handshake_failed(address: &str, message: String) -> result::Error {
+    result::Error::HandshakeFailed {
+        address: String::from(address),
+        message,
+    }
+}
+
+/// How to log in to an SSH server. The server's host key has to be listed in `known_hosts`,
+/// connections to unknown servers or servers whose key changed fail.
+#[cfg(feature = "ssh")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshCredentials {
+    pub user: String,
+    pub auth: SshAuth,
+    pub port: u16,
+    /// known_hosts file in the OpenSSH format, `~/.ssh/known_hosts` if None
+    pub known_hosts: Option<PathBuf>,
+}
+
+#[cfg(feature = "ssh")]
+impl SshCredentials {
+    /// Logs in as `user` on port 22, checking the host key against `~/.ssh/known_hosts`
+    pub fn new(user: impl Into<String>, auth: SshAuth) -> Self {
+        SshCredentials {
+            user: user.into(),
+            auth,
+            port: 22,
+            known_hosts: None,
+        }
+    }
+}
+
+/// Way of proving the identity of an SSH user
+#[cfg(feature = "ssh")]
+#[derive(Clone, PartialEq, Eq)]
+pub enum SshAuth {
+    Password(String),
+    /// Private key in the OpenSSH or PEM format, decrypted with `passphrase` if it's encrypted
+    KeyFile {
+        path: PathBuf,
+        passphrase: Option<String>,
+    },
+    /// Keys held by the agent listening on `SSH_AUTH_SOCK`, tried in turn. Only available on
+    /// Unix.
+    Agent,
+}
+
+#[cfg(feature = "ssh")]
+impl std::fmt::Debug for SshAuth {
+    // Leaves out passwords and passphrases, so credentials can be logged
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SshAuth::Password(_) => f.write_str("Password"),
+            SshAuth::KeyFile { path, .. } => f.debug_struct("KeyFile").field("path", path).finish(),
+            SshAuth::Agent => f.write_str("Agent"),
+        }
+    }
+}
+
+#[cfg(feature = "ssh")]
+mod ssh {
+    use russh::client::{self, Handle, Msg};
+    use russh::keys::{
+        PrivateKeyWithHashAlg, PublicKeyOrCertificate, check_known_hosts_path, load_secret_key,
+    };
+    use russh::{ChannelStream, Error};
+    use std::io;
+    use std::path::PathBuf;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+    use super::{SshAuth, SshCredentials};
+
+    /// Accepts the server if its host key is listed in the known_hosts file
+    pub(super) struct KnownHosts {
+        host: String,
+        port: u16,
+        path: Option<PathBuf>,
+    }
+
+    impl client::Handler for KnownHosts {
+        type Error = Error;
+
+        async fn check_server_key(&mut self, key: &PublicKeyOrCertificate) -> Result<bool, Error> {
+            // Host certificates would need the CA to be trusted instead
+            let PublicKeyOrCertificate::PublicKey { key, .. } = key else {
+                return Ok(false);
+            };
+            let path = match &self.path {
+                Some(path) => path.clone(),
+                None => std::env::home_dir()
+                    .ok_or(russh::keys::Error::NoHomeDir)?
+                    .join(".ssh")
+                    .join("known_hosts"),
+            };
+            Ok(check_known_hosts_path(&self.host, self.port, key, path)?)
+        }
+    }
+
+    /// Splits `host:port` into its parts, `[::1]:5005` included
+    pub(super) fn split_address(address: &str) -> io::Result<(&str, u16)> {
+        address
+            .rsplit_once(':')
+            .and_then(|(host, port)| {
+                let host = match host.strip_prefix('[') {
+                    Some(host) => host.strip_suffix(']')?,
+                    None => host,
+                };
+                Some((host, port.parse().ok()?))
+            })
+            .filter(|(host, _)| !host.is_empty())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Expected host:port, got {}", address),
+                )
+            })
+    }
+
+    /// A direct-tcpip channel to the VM. The session is kept with it, as the connection to
+    /// the SSH server only lives as long as one of them.
+    pub(super) struct SshStream {
+        stream: ChannelStream<Msg>,
+        _session: Handle<KnownHosts>,
+    }
+
+    pub(super) async fn connect(
+        host: &str,
+        credentials: &SshCredentials,
+        target_address: &str,
+    ) -> io::Result<SshStream> {
+        let (target_host, target_port) = split_address(target_address)?;
+        let known_hosts = KnownHosts {
+            host: String::from(host),
+            port: credentials.port,
+            path: credentials.known_hosts.clone(),
+        };
+        let config = Arc::new(client::Config::default());
+        let mut session = client::connect(config, (host, credentials.port), known_hosts)
+            .await
+            .map_err(io::Error::other)?;
+
+        if !authenticate(&mut session, credentials).await? {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("SSH login as {} on {} was refused", credentials.user, host),
+            ));
+        }
+        // The originator is only informational, there is no local socket behind the channel
+        let channel = session
+            .channel_open_direct_tcpip(target_host, u32::from(target_port), "127.0.0.1", 0)
+            .await
+            .map_err(io::Error::other)?;
+        Ok(SshStream {
+            stream: channel.into_stream(),
+            _session: session,
+        })
+    }
+
+    /// Logs in, returning whether the server accepted the credentials
+    async fn authenticate(
+        session: &mut Handle<KnownHosts>,
+        credentials: &SshCredentials,
+    ) -> io::Result<bool> {
+        let user = credentials.user.as_str();
+        let result = match &credentials.auth {
+            SshAuth::Password(password) => session.authenticate_password(user, password).await,
+            SshAuth::KeyFile { path, passphrase } => {
+                let key = load_secret_key(path, passphrase.as_deref()).map_err(io::Error::other)?;
+                let hash_alg = session
+                    .best_supported_rsa_hash()
+                    .await
+                    .map_err(io::Error::other)?
+                    .flatten();
+                let key = PrivateKeyWithHashAlg::new(Arc::new(key), hash_alg);
+                session.authenticate_publickey(user, key).await
+            }
+            SshAuth::Agent => return authenticate_with_agent(session, user).await,
+        };
+        Ok(result.map_err(io::Error::other)?.success())
+    }
+
+    #[cfg(unix)]
+    async fn authenticate_with_agent(
+        session: &mut Handle<KnownHosts>,
+        user: &str,
+    ) -> io::Result<bool> {
+        use russh::keys::agent::AgentIdentity;
+        use russh::keys::agent::client::AgentClient;
+
+        let mut agent = AgentClient::connect_env().await.map_err(io::Error::other)?;
+        let hash_alg = session
+            .best_supported_rsa_hash()
+            .await
+            .map_err(io::Error::other)?
+            .flatten();
+        let identities = agent.request_identities().await.map_err(io::Error::other)?;
+        for identity in identities {
+            let AgentIdentity::PublicKey { key, .. } = identity else {
+                continue;
+            };
+            let result = session
+                .authenticate_publickey_with(user, key, hash_alg, &mut agent)
+                .await
+                .map_err(io::Error::other)?;
+            if result.success() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    #[cfg(not(unix))]
+    async fn authenticate_with_agent(_: &mut Handle<KnownHosts>, _: &str) -> io::Result<bool> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "SSH agents are only supported on Unix",
+        ))
+    }
+
+    impl AsyncRead for SshStream {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.stream).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for SshStream {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.stream).poll_write(cx, buf)
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.stream).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.stream).poll_shutdown(cx)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_split_address() {
+            assert_eq!(
+                split_address("localhost:5005").unwrap(),
+                ("localhost", 5005)
+            );
+            assert_eq!(split_address("[::1]:5005").unwrap(), ("::1", 5005));
+            assert!(split_address("localhost").is_err());
+            assert!(split_address(":5005").is_err());
+            assert!(split_address("localhost:jdwp").is_err());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_apply_tcp_options() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let options = TcpOptions {
+            keepalive: Some(Duration::from_secs(30)),
+            recv_buffer_size: Some(64 * 1024),
+            ..TcpOptions::default()
+        };
+        options.apply(&stream).unwrap();
+
+        let socket = SockRef::from(&stream);
+        assert!(socket.tcp_nodelay().unwrap());
+        assert!(socket.keepalive().unwrap());
+        // The kernel may round the size up, e.g. Linux doubles it for bookkeeping
+        assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+
+        TcpOptions {
+            nodelay: false,
+            ..options
+        }
+        .apply(&stream)
+        .unwrap();
+        assert!(!socket.tcp_nodelay().unwrap());
+    }
+}
@@ -1,4 +1,7 @@
 use binrw::{BinRead, BinWrite};
+use std::io::SeekFrom;
+
+use crate::{Tag, TypeTag, VariableLengthId, conformance, result};
 
 pub type JdwpIdSize = u8;
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -9,11 +12,67 @@ pub struct JdwpIdSizes {
     pub reference_type_id_size: JdwpIdSize,
     pub frame_id_size: JdwpIdSize,
 }
+impl JdwpIdSizes {
+    /// Checks that every size is between 1 and 8 bytes, the sizes a [VariableLengthId] can hold.
+    /// Broken proxies have been seen replying to IDSizes with zeroes or garbage, which would
+    /// otherwise only show up as confusing parsing errors later.
+    pub fn validate(&self) -> result::Result<()> {
+        for (name, size) in [
+            ("fieldID", self.field_id_size),
+            ("methodID", self.method_id_size),
+            ("objectID", self.object_id_size),
+            ("referenceTypeID", self.reference_type_id_size),
+            ("frameID", self.frame_id_size),
+        ] {
+            if !(1..=8).contains(&size) {
+                return Err(result::Error::InvalidIdSize {
+                    name,
+                    size: size as i32,
+                });
+            }
+        }
+        Ok(())
+    }
+}
 
-#[derive(Debug)]
+/// Longest string, in bytes, decoded by [JdwpString] unless the client receiving it sets
+/// another limit with `JdwpClient::set_max_string_length`. Longer strings fail to decode
+/// instead of making the client allocate whatever length a hostile or buggy VM sends.
+pub const DEFAULT_MAX_STRING_LENGTH: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct JdwpString {
     pub string: String,
 }
+impl JdwpString {
+    /// Reads a string but only keeps its first `max_bytes` bytes, skipping the rest. A UTF-8
+    /// sequence cut in half at the end is dropped.
+    pub fn read_prefix<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        max_bytes: usize,
+    ) -> binrw::BinResult<JdwpStringPrefix> {
+        let length = u32::read_options(reader, endian, ())?;
+        let kept = (length as usize).min(max_bytes);
+        let mut bytes = vec![0u8; kept];
+        reader.read_exact(&mut bytes)?;
+        reader.seek(SeekFrom::Current(length as i64 - kept as i64))?;
+
+        if let Err(e) = std::str::from_utf8(&bytes) {
+            if e.error_len().is_some() || kept == length as usize {
+                return Err(binrw::Error::Custom {
+                    pos: reader.stream_position().unwrap_or(0),
+                    err: Box::new(e),
+                });
+            }
+            bytes.truncate(e.valid_up_to());
+        }
+        Ok(JdwpStringPrefix {
+            prefix: String::from_utf8(bytes).expect("checked above"),
+            length,
+        })
+    }
+}
 impl BinRead for JdwpString {
     type Args<'a> = ();
 
@@ -22,12 +81,23 @@ impl BinRead for JdwpString {
         endian: binrw::Endian,
         args: Self::Args<'_>,
     ) -> binrw::BinResult<Self> {
+        let pos = reader.stream_position().unwrap_or(0);
         let length = u32::read_options(reader, endian, args)?;
         if length == 0 {
             return Ok(JdwpString {
                 string: String::from(""),
             });
         }
+        let max_length = conformance::max_string_length();
+        if length as usize > max_length {
+            return Err(binrw::Error::AssertFail {
+                pos,
+                message: format!(
+                    "String length {} exceeds the maximum of {}",
+                    length, max_length
+                ),
+            });
+        }
 
         let mut bytes = vec![0u8; length as usize];
         reader.read_exact(&mut bytes)?;
@@ -69,6 +139,19 @@ impl PartialEq<JdwpString> for &str {
     }
 }
 
+/// Start of a string read with [JdwpString::read_prefix]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JdwpStringPrefix {
+    pub prefix: String,
+    /// Length of the whole string in bytes
+    pub length: u32,
+}
+impl JdwpStringPrefix {
+    pub fn is_truncated(&self) -> bool {
+        self.prefix.len() < self.length as usize
+    }
+}
+
 /// Write-only version of JdwpString which uses &'a str instead of String
 #[derive(Debug)]
 pub struct JdwpStringSlice<'a> {
@@ -94,11 +177,446 @@ impl<'a> BinWrite for JdwpStringSlice<'a> {
     }
 }
 
+/// An executable location: a code index within a method of a class or interface
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Location {
+    pub type_tag: TypeTag,
+    pub class_id: VariableLengthId,
+    pub method_id: VariableLengthId,
+    pub index: u64,
+}
+impl BinRead for Location {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        // Locations which don't exist, e.g. the catch location of an uncaught exception, are
+        // all zeroes, which is valid even where unknown tags aren't
+        let type_tag = match u8::read_options(reader, endian, ())? {
+            0 => TypeTag::Unknown(0),
+            _ => {
+                reader.seek(SeekFrom::Current(-1))?;
+                TypeTag::read_options(reader, endian, ())?
+            }
+        };
+        Ok(Location {
+            type_tag,
+            class_id: VariableLengthId::read_options(reader, endian, args.reference_type_id_size)?,
+            method_id: VariableLengthId::read_options(reader, endian, args.method_id_size)?,
+            index: u64::read_options(reader, endian, ())?,
+        })
+    }
+}
+impl BinWrite for Location {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.type_tag.write_options(writer, endian, ())?;
+        self.class_id
+            .write_options(writer, endian, args.reference_type_id_size)?;
+        self.method_id
+            .write_options(writer, endian, args.method_id_size)?;
+        self.index.write_options(writer, endian, ())
+    }
+}
+
+/// Object id prefixed with a tag describing the kind of object
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TaggedObjectId {
+    pub tag: Tag,
+    pub object_id: VariableLengthId,
+}
+impl BinRead for TaggedObjectId {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        Ok(TaggedObjectId {
+            tag: Tag::read_options(reader, endian, ())?,
+            object_id: VariableLengthId::read_options(reader, endian, args.object_id_size)?,
+        })
+    }
+}
+impl BinWrite for TaggedObjectId {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        self.tag.write_options(writer, endian, ())?;
+        self.object_id
+            .write_options(writer, endian, args.object_id_size)
+    }
+}
+
+/// A single JDWP value. Object values keep their tag so the kind of object is not lost.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum JdwpValue {
+    Void,
+    Boolean(bool),
+    Byte(i8),
+    Char(u16),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Object(TaggedObjectId),
+}
+impl JdwpValue {
+    pub fn tag(&self) -> Tag {
+        match self {
+            JdwpValue::Void => Tag::Void,
+            JdwpValue::Boolean(_) => Tag::Boolean,
+            JdwpValue::Byte(_) => Tag::Byte,
+            JdwpValue::Char(_) => Tag::Char,
+            JdwpValue::Short(_) => Tag::Short,
+            JdwpValue::Int(_) => Tag::Int,
+            JdwpValue::Long(_) => Tag::Long,
+            JdwpValue::Float(_) => Tag::Float,
+            JdwpValue::Double(_) => Tag::Double,
+            JdwpValue::Object(object) => object.tag,
+        }
+    }
+
+    /// Reads a value preceded by its tag, as values are in most replies and events. For
+    /// decoding commands this crate doesn't know, e.g. vendor extensions.
+    pub fn read_tagged<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        id_sizes: JdwpIdSizes,
+    ) -> binrw::BinResult<Self> {
+        Self::read_options(reader, binrw::Endian::Big, id_sizes)
+    }
+
+    /// Reads a value whose tag is known from context, e.g. the elements of a primitive array
+    /// region or a field whose type is known
+    pub fn read_untagged<R: std::io::Read + std::io::Seek>(
+        tag: Tag,
+        reader: &mut R,
+        id_sizes: JdwpIdSizes,
+    ) -> binrw::BinResult<Self> {
+        Self::read_untagged_options(reader, binrw::Endian::Big, tag, id_sizes)
+    }
+
+    fn read_untagged_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        tag: Tag,
+        sizes: JdwpIdSizes,
+    ) -> binrw::BinResult<Self> {
+        Ok(match tag {
+            Tag::Void => JdwpValue::Void,
+            Tag::Boolean => JdwpValue::Boolean(u8::read_options(reader, endian, ())? != 0),
+            Tag::Byte => JdwpValue::Byte(i8::read_options(reader, endian, ())?),
+            Tag::Char => JdwpValue::Char(u16::read_options(reader, endian, ())?),
+            Tag::Short => JdwpValue::Short(i16::read_options(reader, endian, ())?),
+            Tag::Int => JdwpValue::Int(i32::read_options(reader, endian, ())?),
+            Tag::Long => JdwpValue::Long(i64::read_options(reader, endian, ())?),
+            Tag::Float => JdwpValue::Float(f32::read_options(reader, endian, ())?),
+            Tag::Double => JdwpValue::Double(f64::read_options(reader, endian, ())?),
+            _ => JdwpValue::Object(TaggedObjectId {
+                tag,
+                object_id: VariableLengthId::read_options(reader, endian, sizes.object_id_size)?,
+            }),
+        })
+    }
+}
+impl BinRead for JdwpValue {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        let tag = Tag::read_options(reader, endian, ())?;
+        JdwpValue::read_untagged_options(reader, endian, tag, args)
+    }
+}
+impl BinWrite for JdwpValue {
+    type Args<'a> = JdwpIdSizes;
+
+    fn write_options<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<()> {
+        if let JdwpValue::Object(object) = self {
+            return object.write_options(writer, endian, args);
+        }
+
+        self.tag().write_options(writer, endian, ())?;
+        match self {
+            JdwpValue::Void | JdwpValue::Object(_) => Ok(()),
+            JdwpValue::Boolean(v) => (*v as u8).write_options(writer, endian, ()),
+            JdwpValue::Byte(v) => v.write_options(writer, endian, ()),
+            JdwpValue::Char(v) => v.write_options(writer, endian, ()),
+            JdwpValue::Short(v) => v.write_options(writer, endian, ()),
+            JdwpValue::Int(v) => v.write_options(writer, endian, ()),
+            JdwpValue::Long(v) => v.write_options(writer, endian, ()),
+            JdwpValue::Float(v) => v.write_options(writer, endian, ()),
+            JdwpValue::Double(v) => v.write_options(writer, endian, ()),
+        }
+    }
+}
+
+/// Elements of a primitive array, stored without per-element tags
+#[derive(Clone, Debug, PartialEq)]
+pub enum PrimitiveArray {
+    Boolean(Vec<bool>),
+    Byte(Vec<u8>),
+    Char(Vec<u16>),
+    Short(Vec<i16>),
+    Int(Vec<i32>),
+    Long(Vec<i64>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+}
+
+/// Contents of (a region of) an array
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArrayValues {
+    Primitive(PrimitiveArray),
+    Objects(Vec<TaggedObjectId>),
+}
+impl ArrayValues {
+    /// Tag of the elements, [Tag::Object] for arrays of objects whatever their element type
+    pub fn element_tag(&self) -> Tag {
+        match self {
+            ArrayValues::Primitive(PrimitiveArray::Boolean(_)) => Tag::Boolean,
+            ArrayValues::Primitive(PrimitiveArray::Byte(_)) => Tag::Byte,
+            ArrayValues::Primitive(PrimitiveArray::Char(_)) => Tag::Char,
+            ArrayValues::Primitive(PrimitiveArray::Short(_)) => Tag::Short,
+            ArrayValues::Primitive(PrimitiveArray::Int(_)) => Tag::Int,
+            ArrayValues::Primitive(PrimitiveArray::Long(_)) => Tag::Long,
+            ArrayValues::Primitive(PrimitiveArray::Float(_)) => Tag::Float,
+            ArrayValues::Primitive(PrimitiveArray::Double(_)) => Tag::Double,
+            ArrayValues::Objects(_) => Tag::Object,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            ArrayValues::Primitive(PrimitiveArray::Boolean(v)) => v.len(),
+            ArrayValues::Primitive(PrimitiveArray::Byte(v)) => v.len(),
+            ArrayValues::Primitive(PrimitiveArray::Char(v)) => v.len(),
+            ArrayValues::Primitive(PrimitiveArray::Short(v)) => v.len(),
+            ArrayValues::Primitive(PrimitiveArray::Int(v)) => v.len(),
+            ArrayValues::Primitive(PrimitiveArray::Long(v)) => v.len(),
+            ArrayValues::Primitive(PrimitiveArray::Float(v)) => v.len(),
+            ArrayValues::Primitive(PrimitiveArray::Double(v)) => v.len(),
+            ArrayValues::Objects(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Decodes a `char[]` as UTF-16, combining surrogate pairs and replacing unpaired
+    /// surrogates with U+FFFD. None for arrays of other types.
+    pub fn chars_to_string_lossy(&self) -> Option<String> {
+        match self {
+            ArrayValues::Primitive(PrimitiveArray::Char(units)) => {
+                Some(String::from_utf16_lossy(units))
+            }
+            _ => None,
+        }
+    }
+
+    /// Appends another region of the same array. Returns false if the element types differ.
+    pub fn append(&mut self, other: ArrayValues) -> bool {
+        match (self, other) {
+            (ArrayValues::Primitive(a), ArrayValues::Primitive(b)) => match (a, b) {
+                (PrimitiveArray::Boolean(a), PrimitiveArray::Boolean(b)) => a.extend(b),
+                (PrimitiveArray::Byte(a), PrimitiveArray::Byte(b)) => a.extend(b),
+                (PrimitiveArray::Char(a), PrimitiveArray::Char(b)) => a.extend(b),
+                (PrimitiveArray::Short(a), PrimitiveArray::Short(b)) => a.extend(b),
+                (PrimitiveArray::Int(a), PrimitiveArray::Int(b)) => a.extend(b),
+                (PrimitiveArray::Long(a), PrimitiveArray::Long(b)) => a.extend(b),
+                (PrimitiveArray::Float(a), PrimitiveArray::Float(b)) => a.extend(b),
+                (PrimitiveArray::Double(a), PrimitiveArray::Double(b)) => a.extend(b),
+                _ => return false,
+            },
+            (ArrayValues::Objects(a), ArrayValues::Objects(b)) => a.extend(b),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// The `arrayregion` type: a tag followed by a count and that many values
+impl BinRead for ArrayValues {
+    type Args<'a> = JdwpIdSizes;
+
+    fn read_options<R: std::io::Read + std::io::Seek>(
+        reader: &mut R,
+        endian: binrw::Endian,
+        args: Self::Args<'_>,
+    ) -> binrw::BinResult<Self> {
+        fn read_vec<R: std::io::Read + std::io::Seek, V: for<'a> BinRead<Args<'a> = ()>>(
+            reader: &mut R,
+            endian: binrw::Endian,
+            count: usize,
+        ) -> binrw::BinResult<Vec<V>> {
+            let mut values = Vec::with_capacity(count);
+            for _ in 0..count {
+                values.push(V::read_options(reader, endian, ())?);
+            }
+            Ok(values)
+        }
+
+        let tag = Tag::read_options(reader, endian, ())?;
+        let count = i32::read_options(reader, endian, ())? as usize;
+        let values = match tag {
+            Tag::Boolean => PrimitiveArray::Boolean(
+                read_vec::<R, u8>(reader, endian, count)?
+                    .into_iter()
+                    .map(|b| b != 0)
+                    .collect(),
+            ),
+            Tag::Byte => {
+                let mut bytes = vec![0u8; count];
+                reader.read_exact(&mut bytes)?;
+                PrimitiveArray::Byte(bytes)
+            }
+            Tag::Char => PrimitiveArray::Char(read_vec(reader, endian, count)?),
+            Tag::Short => PrimitiveArray::Short(read_vec(reader, endian, count)?),
+            Tag::Int => PrimitiveArray::Int(read_vec(reader, endian, count)?),
+            Tag::Long => PrimitiveArray::Long(read_vec(reader, endian, count)?),
+            Tag::Float => PrimitiveArray::Float(read_vec(reader, endian, count)?),
+            Tag::Double => PrimitiveArray::Double(read_vec(reader, endian, count)?),
+            _ => {
+                // Object arrays carry a tag for every element
+                let mut objects = Vec::with_capacity(count);
+                for _ in 0..count {
+                    objects.push(TaggedObjectId::read_options(reader, endian, args)?);
+                }
+                return Ok(ArrayValues::Objects(objects));
+            }
+        };
+
+        Ok(ArrayValues::Primitive(values))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::io::Cursor;
 
+    #[test]
+    fn test_chars_to_string_lossy() {
+        // "a😀" with the emoji as a surrogate pair, then an unpaired high surrogate
+        let chars =
+            ArrayValues::Primitive(PrimitiveArray::Char(vec![0x61, 0xD83D, 0xDE00, 0xD83D]));
+        assert_eq!(chars.chars_to_string_lossy().unwrap(), "a😀\u{FFFD}");
+        let ints = ArrayValues::Primitive(PrimitiveArray::Int(vec![0x61]));
+        assert_eq!(ints.chars_to_string_lossy(), None);
+    }
+
+    #[test]
+    fn test_read_values() {
+        let sizes = JdwpIdSizes {
+            field_id_size: 8,
+            method_id_size: 8,
+            object_id_size: 4,
+            reference_type_id_size: 8,
+            frame_id_size: 8,
+        };
+        let data = [b'J', 0, 0, 0, 0, 0, 0, 0x1, 0x2, b's', 0, 0, 0, 0x9];
+        let mut reader = Cursor::new(&data);
+        assert_eq!(
+            JdwpValue::read_tagged(&mut reader, sizes).unwrap(),
+            JdwpValue::Long(0x102)
+        );
+        assert_eq!(
+            JdwpValue::read_tagged(&mut reader, sizes).unwrap(),
+            JdwpValue::Object(TaggedObjectId {
+                tag: Tag::String,
+                object_id: VariableLengthId { value: 9 },
+            })
+        );
+
+        let data = [0, 0x61, 0, 0, 0, 0x7];
+        let mut reader = Cursor::new(&data);
+        assert_eq!(
+            JdwpValue::read_untagged(Tag::Char, &mut reader, sizes).unwrap(),
+            JdwpValue::Char(0x61)
+        );
+        assert_eq!(
+            JdwpValue::read_untagged(Tag::Thread, &mut reader, sizes).unwrap(),
+            JdwpValue::Object(TaggedObjectId {
+                tag: Tag::Thread,
+                object_id: VariableLengthId { value: 7 },
+            })
+        );
+        assert!(JdwpValue::read_untagged(Tag::Int, &mut reader, sizes).is_err());
+    }
+
+    #[test]
+    fn test_validate_id_sizes() {
+        let sizes = JdwpIdSizes {
+            field_id_size: 8,
+            method_id_size: 8,
+            object_id_size: 8,
+            reference_type_id_size: 8,
+            frame_id_size: 8,
+        };
+        assert!(sizes.validate().is_ok());
+        assert!(matches!(
+            JdwpIdSizes {
+                frame_id_size: 9,
+                ..sizes
+            }
+            .validate(),
+            Err(result::Error::InvalidIdSize {
+                name: "frameID",
+                size: 9
+            })
+        ));
+    }
+
+    #[test]
+    fn test_read_jdwp_string_prefix() {
+        let data = [0u8, 0, 0, 4, b'a', 0xC3, 0xA9, b'b', 0xFF];
+        let mut cursor = Cursor::new(&data);
+        let value = JdwpString::read_prefix(&mut cursor, binrw::Endian::Big, 2).unwrap();
+        // The cut 'é' is dropped
+        assert_eq!(value.prefix, "a");
+        assert_eq!(value.length, 4);
+        assert!(value.is_truncated());
+        assert_eq!(cursor.position(), 8);
+
+        let mut cursor = Cursor::new(&data);
+        let value = JdwpString::read_prefix(&mut cursor, binrw::Endian::Big, 16).unwrap();
+        assert_eq!(value.prefix, "aéb");
+        assert!(!value.is_truncated());
+    }
+
+    #[test]
+    fn test_read_jdwp_string_over_maximum() {
+        // The length alone is rejected, without reading (or allocating) the data
+        let data = [0x7Fu8, 0xFF, 0xFF, 0xFF];
+        let mut cursor = Cursor::new(&data);
+        assert!(JdwpString::read_be(&mut cursor).is_err());
+    }
+
     #[test]
     fn test_read_jdwp_string_empty() {
         let data = [0u8, 0u8, 0u8, 0u8]; // 0 length
@@ -0,0 +1,46 @@
+use tokio::sync::mpsc;
+
+use crate::{JdwpClient, ReplyPacket};
+
+/// What the reader task does with an unsolicited reply, one whose id belongs to no command sent
+/// by the client or to a command which already got its reply
+///
+/// Late replies to timed out or cancelled requests and replies to commands sent without waiting
+/// for them are expected and always dropped silently.
+#[derive(Debug, Clone, Default)]
+pub enum UnsolicitedReplyPolicy {
+    Drop,
+    /// Log and drop
+    #[default]
+    Log,
+    /// Pass the reply on, e.g. to a diagnostics view. Replies are dropped once the receiver is.
+    Report(mpsc::UnboundedSender<ReplyPacket>),
+}
+impl UnsolicitedReplyPolicy {
+    pub(crate) fn handle(&self, reply: ReplyPacket) {
+        match self {
+            UnsolicitedReplyPolicy::Drop => {}
+            UnsolicitedReplyPolicy::Log => eprintln!(
+                "Dropped an unsolicited reply with id {}, error code {} and {} bytes of data",
+                reply.header.id,
+                reply.header.error_code,
+                reply.data.len()
+            ),
+            UnsolicitedReplyPolicy::Report(replies) => {
+                let _ = replies.send(reply);
+            }
+        }
+    }
+}
+
+impl JdwpClient {
+    /// Sets how unsolicited replies are handled, which hint at a misbehaving agent or proxy.
+    /// Like [JdwpClient::set_resume_on_shutdown], this applies to the whole connection.
+    pub fn set_unsolicited_reply_policy(&self, policy: UnsolicitedReplyPolicy) {
+        *self.shared.unsolicited_reply_policy() = policy;
+    }
+
+    pub fn unsolicited_reply_policy(&self) -> UnsolicitedReplyPolicy {
+        self.shared.unsolicited_reply_policy().clone()
+    }
+}
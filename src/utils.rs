@@ -1,17 +1,42 @@
+use std::fmt::Display;
+
+use crate::conformance;
+
+/// Whether enums reject unknown values like enums without an `Unknown` variant, because the
+/// packet being decoded is from a client in [crate::Conformance::Strict] mode
+#[doc(hidden)]
+pub fn strict_enum_decoding() -> bool {
+    conformance::decoding_strictly()
+}
+
+/// Logs an unknown value decoded as `Unknown` once the packet being decoded is done
+#[doc(hidden)]
+pub fn tolerate_unknown_enum_value(name: &str, value: impl Display) {
+    conformance::tolerate(format!("unknown {} {}", name, value));
+}
+
 /// Macro to implement BinRead + BinWrite for repr(xx) enums
+///
+/// A last variant marked `#[unknown]` holds values which aren't listed, so decoding a new or
+/// vendor-specific value doesn't fail the whole packet. `TryFrom` still only accepts listed
+/// values.
 #[macro_export]
 macro_rules! binrw_enum {
     (
         #[repr($ty:ty)]
         $(#[$meta:meta])*
         $vis:vis enum $name:ident {
-            $($variant:ident = $value:expr),* $(,)?
+            $($variant:ident = $value:expr,)*
+            #[unknown]
+            $unknown:ident $(,)?
         }
     ) => {
         $(#[$meta])*
         #[repr($ty)]
         $vis enum $name {
-            $($variant = $value),*
+            $($variant = $value,)*
+            /// A value not defined by the specification
+            $unknown($ty),
         }
 
         impl binrw::BinRead for $name {
@@ -23,10 +48,14 @@ macro_rules! binrw_enum {
                 _: Self::Args<'_>
             ) -> binrw::BinResult<Self> {
                 let val = <$ty>::read_options(reader, endian, ())?;
-                match val {
-                    $(x if x == $value => Ok(Self::$variant),)*
-                    other => Err(binrw::Error::AssertFail {
-                        pos: reader.stream_position()?,
+                match Self::try_from(val) {
+                    Ok(value) => Ok(value),
+                    Err(other) if !$crate::strict_enum_decoding() => {
+                        $crate::tolerate_unknown_enum_value(stringify!($name), other);
+                        Ok(Self::$unknown(other))
+                    }
+                    Err(other) => Err(binrw::Error::AssertFail {
+                        pos: reader.stream_position().unwrap_or(0),
                         message: format!(
                             "Invalid value {} for enum {}",
                             other,
@@ -37,6 +66,65 @@ macro_rules! binrw_enum {
             }
         }
 
+        $crate::binrw_enum!(@try_from $ty, $name, $($variant = $value),*);
+
+        impl binrw::BinWrite for $name {
+            type Args<'a> = ();
+
+            fn write_options<W: binrw::io::Write + binrw::io::Seek>(
+                &self,
+                writer: &mut W,
+                endian: binrw::Endian,
+                _: Self::Args<'_>
+            ) -> binrw::BinResult<()> {
+                <$ty>::from(*self).write_options(writer, endian, ())
+            }
+        }
+
+        impl From<$name> for $ty {
+            fn from(value: $name) -> $ty {
+                match value {
+                    $($name::$variant => $value,)*
+                    $name::$unknown(value) => value,
+                }
+            }
+        }
+    };
+    (
+        #[repr($ty:ty)]
+        $(#[$meta:meta])*
+        $vis:vis enum $name:ident {
+            $($variant:ident = $value:expr),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr($ty)]
+        $vis enum $name {
+            $($variant = $value),*
+        }
+
+        impl binrw::BinRead for $name {
+            type Args<'a> = ();
+
+            fn read_options<R: binrw::io::Read + binrw::io::Seek>(
+                reader: &mut R,
+                endian: binrw::Endian,
+                _: Self::Args<'_>
+            ) -> binrw::BinResult<Self> {
+                let val = <$ty>::read_options(reader, endian, ())?;
+                Self::try_from(val).map_err(|other| binrw::Error::AssertFail {
+                    pos: reader.stream_position().unwrap_or(0),
+                    message: format!(
+                        "Invalid value {} for enum {}",
+                        other,
+                        stringify!($name)
+                    ),
+                })
+            }
+        }
+
+        $crate::binrw_enum!(@try_from $ty, $name, $($variant = $value),*);
+
         impl binrw::BinWrite for $name {
             type Args<'a> = ();
 
@@ -46,10 +134,27 @@ macro_rules! binrw_enum {
                 endian: binrw::Endian,
                 _: Self::Args<'_>
             ) -> binrw::BinResult<()> {
-                let val: $ty = match self {
-                    $(Self::$variant => $value),*
-                };
-                val.write_options(writer, endian, ())
+                <$ty>::from(*self).write_options(writer, endian, ())
+            }
+        }
+
+        impl From<$name> for $ty {
+            fn from(value: $name) -> $ty {
+                match value {
+                    $($name::$variant => $value),*
+                }
+            }
+        }
+    };
+    (@try_from $ty:ty, $name:ident, $($variant:ident = $value:expr),*) => {
+        impl TryFrom<$ty> for $name {
+            type Error = $ty;
+
+            fn try_from(value: $ty) -> ::core::result::Result<Self, $ty> {
+                match value {
+                    $(x if x == $value => Ok(Self::$variant),)*
+                    other => Err(other),
+                }
             }
         }
     };
@@ -57,6 +162,7 @@ macro_rules! binrw_enum {
 
 #[cfg(test)]
 mod tests {
+    use crate::conformance::{Conformance, decode};
     use binrw::{BinRead, BinWrite};
     use std::io::Cursor;
 
@@ -69,6 +175,16 @@ mod tests {
             Value3 = 3,
         }
     }
+    binrw_enum! {
+        #[repr(u8)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum TestOpenSet {
+            Value1 = 1,
+            Value2 = 2,
+            #[unknown]
+            Unknown,
+        }
+    }
     binrw_enum! {
         #[repr(u16)]
         #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -95,6 +211,12 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_enum_try_from() {
+        assert_eq!(TestSet::try_from(1u8), Ok(TestSet::Value1));
+        assert_eq!(TestSet::try_from(42u8), Err(42u8));
+    }
+
     #[test]
     fn test_enum_write() {
         let mut buffer = Cursor::new(Vec::new());
@@ -124,4 +246,26 @@ mod tests {
         TestSet16::Value3.write_be(&mut buffer).unwrap();
         assert_eq!(buffer.into_inner(), vec![0u8, 3u8]);
     }
+
+    #[test]
+    fn test_enum_unknown_fallback() {
+        let value = TestOpenSet::read_be(&mut Cursor::new(&[99u8])).unwrap();
+        assert_eq!(value, TestOpenSet::Unknown(99));
+        assert_eq!(TestOpenSet::try_from(99u8), Err(99u8));
+
+        let mut buffer = Cursor::new(Vec::new());
+        value.write_be(&mut buffer).unwrap();
+        assert_eq!(buffer.into_inner(), vec![99u8]);
+
+        assert!(
+            decode::<TestOpenSet>(
+                &[99u8],
+                (),
+                Conformance::Strict,
+                crate::DEFAULT_MAX_STRING_LENGTH,
+                "test",
+            )
+            .is_err()
+        );
+    }
 }
@@ -0,0 +1,966 @@
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use crate::introspect::{OBJECT_SIGNATURE, RECORD_SIGNATURE};
+use crate::{
+    ArrayValues, BeanProperty, InvokeOptions, JdwpClient, JdwpErrorCode, JdwpValue, PrimitiveArray,
+    Tag, TaggedObjectId, TryFromJdwpValue, VariableLengthId, result, signature_to_type_name,
+};
+
+/// `static` in the modifier bits of a field
+const ACC_STATIC: i32 = 0x0008;
+
+const ENUM_SIGNATURE: &str = "Ljava/lang/Enum;";
+
+/// Classes whose instances box a primitive in their `value` field
+const WRAPPER_CLASSES: [&str; 8] = [
+    "java.lang.Boolean",
+    "java.lang.Byte",
+    "java.lang.Character",
+    "java.lang.Short",
+    "java.lang.Integer",
+    "java.lang.Long",
+    "java.lang.Float",
+    "java.lang.Double",
+];
+
+/// A debuggee value read by a [ValueReader], displayed the way an IDE's variables view would
+#[derive(Debug, Clone, PartialEq)]
+pub enum DisplayValue {
+    Null,
+    Primitive(JdwpValue),
+    String {
+        object: TaggedObjectId,
+        value: String,
+    },
+    /// An object shown by its instance fields, including inherited ones
+    Object {
+        object: TaggedObjectId,
+        type_name: String,
+        fields: Vec<(String, DisplayValue)>,
+    },
+    Array {
+        object: TaggedObjectId,
+        type_name: String,
+        length: usize,
+        /// The first elements, at most [ValueReader::max_elements] of them
+        elements: Vec<DisplayValue>,
+    },
+    /// A `java.util.Collection`, read with `size()` and `toArray()`
+    Collection {
+        object: TaggedObjectId,
+        type_name: String,
+        size: i32,
+        elements: Vec<DisplayValue>,
+    },
+    /// A `java.util.Map`, read with `size()` and its entry set
+    Map {
+        object: TaggedObjectId,
+        type_name: String,
+        size: i32,
+        entries: Vec<(DisplayValue, DisplayValue)>,
+    },
+    /// A constant of a Java enum, shown like `Status.ACTIVE`
+    EnumConstant {
+        object: TaggedObjectId,
+        /// The enum type, also for constants with a body, which are instances of a subclass
+        type_name: String,
+        name: String,
+    },
+    /// A Java record shown by its components, like its generated `toString()`
+    Record {
+        object: TaggedObjectId,
+        type_name: String,
+        components: Vec<(String, DisplayValue)>,
+    },
+    /// An object shown by the result of its `toString()`, see [ValueReader::to_string_for]
+    Text {
+        object: TaggedObjectId,
+        type_name: String,
+        text: String,
+    },
+    /// An object below the depth limit, shown by type and id only
+    Unexpanded {
+        object: TaggedObjectId,
+        type_name: String,
+    },
+}
+
+impl fmt::Display for DisplayValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn list<T>(
+            f: &mut fmt::Formatter<'_>,
+            items: &[T],
+            total: usize,
+            item: impl Fn(&mut fmt::Formatter<'_>, &T) -> fmt::Result,
+        ) -> fmt::Result {
+            for (index, value) in items.iter().enumerate() {
+                if index > 0 {
+                    write!(f, ", ")?;
+                }
+                item(f, value)?;
+            }
+            if items.len() < total {
+                write!(f, ", ...")?;
+            }
+            Ok(())
+        }
+
+        match self {
+            DisplayValue::Null => write!(f, "null"),
+            DisplayValue::Primitive(value) => write_primitive(f, value),
+            DisplayValue::String { value, .. } => write!(f, "{:?}", value),
+            DisplayValue::Object {
+                type_name, fields, ..
+            } => {
+                write!(f, "{} {{", type_name)?;
+                list(f, fields, fields.len(), |f, (name, value)| {
+                    write!(f, "{}={}", name, value)
+                })?;
+                write!(f, "}}")
+            }
+            DisplayValue::Array {
+                type_name,
+                length,
+                elements,
+                ..
+            } => {
+                let element_type = type_name.strip_suffix("[]").unwrap_or(type_name);
+                // Chars are UTF-16 code units, so surrogate pairs only make sense together
+                if element_type == "char" {
+                    let units: Vec<u16> = elements
+                        .iter()
+                        .filter_map(|element| match element {
+                            DisplayValue::Primitive(JdwpValue::Char(unit)) => Some(*unit),
+                            _ => None,
+                        })
+                        .collect();
+                    write!(f, "char[{}] {:?}", length, String::from_utf16_lossy(&units))?;
+                    if elements.len() < *length {
+                        write!(f, "...")?;
+                    }
+                    return Ok(());
+                }
+                write!(f, "{}[{}] [", element_type, length)?;
+                list(f, elements, *length, |f, value| write!(f, "{}", value))?;
+                write!(f, "]")
+            }
+            DisplayValue::Collection {
+                type_name,
+                size,
+                elements,
+                ..
+            } => {
+                write!(f, "{}(size={}) [", type_name, size)?;
+                list(f, elements, *size as usize, |f, value| {
+                    write!(f, "{}", value)
+                })?;
+                write!(f, "]")
+            }
+            DisplayValue::Map {
+                type_name,
+                size,
+                entries,
+                ..
+            } => {
+                write!(f, "{}(size={}) {{", type_name, size)?;
+                list(f, entries, *size as usize, |f, (key, value)| {
+                    write!(f, "{}={}", key, value)
+                })?;
+                write!(f, "}}")
+            }
+            DisplayValue::Record {
+                type_name,
+                components,
+                ..
+            } => {
+                write!(f, "{}[", type_name)?;
+                list(f, components, components.len(), |f, (name, value)| {
+                    write!(f, "{}={}", name, value)
+                })?;
+                write!(f, "]")
+            }
+            DisplayValue::EnumConstant {
+                type_name, name, ..
+            } => {
+                let simple_name = type_name.rsplit(['.', '$']).next().unwrap_or(type_name);
+                write!(f, "{}.{}", simple_name, name)
+            }
+            DisplayValue::Text { text, .. } => write!(f, "{}", text),
+            DisplayValue::Unexpanded { object, type_name } => {
+                write!(f, "{}@{:x}", type_name, object.object_id.value)
+            }
+        }
+    }
+}
+
+fn write_primitive(f: &mut fmt::Formatter<'_>, value: &JdwpValue) -> fmt::Result {
+    match value {
+        JdwpValue::Void => write!(f, "void"),
+        JdwpValue::Boolean(v) => write!(f, "{}", v),
+        JdwpValue::Byte(v) => write!(f, "{}", v),
+        JdwpValue::Char(v) => match char::from_u32(*v as u32) {
+            Some(c) => write!(f, "{:?}", c),
+            None => write!(f, "'\\u{:04x}'", v),
+        },
+        JdwpValue::Short(v) => write!(f, "{}", v),
+        JdwpValue::Int(v) => write!(f, "{}", v),
+        JdwpValue::Long(v) => write!(f, "{}", v),
+        JdwpValue::Float(v) => write!(f, "{}", v),
+        JdwpValue::Double(v) => write!(f, "{}", v),
+        JdwpValue::Object(object) => write!(f, "@{:x}", object.object_id.value),
+    }
+}
+
+type ReadFuture<'a> = Pin<Box<dyn Future<Output = result::Result<DisplayValue>> + Send + 'a>>;
+
+/// Something worth telling the user about a read, see [ValueReader::read_with_warnings]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueWarning {
+    /// `toString()` was invoked, which resumes the VM while it runs and may have changed
+    /// program state, e.g. by initializing lazy fields
+    ToStringInvoked { type_name: String },
+    /// `toString()` threw, so the object is shown by its fields
+    ToStringThrew { type_name: String },
+    /// `toString()` didn't return in time, so the object is shown by its fields. The method
+    /// keeps running in the debuggee, so no more methods are invoked for the rest of the read.
+    ToStringTimedOut { type_name: String },
+    /// Bean getters were invoked, see [ValueWarning::ToStringInvoked]
+    GettersInvoked { type_name: String },
+}
+impl fmt::Display for ValueWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueWarning::ToStringInvoked { type_name } => write!(
+                f,
+                "invoked {}.toString(), which may have side effects",
+                type_name
+            ),
+            ValueWarning::ToStringThrew { type_name } => {
+                write!(f, "{}.toString() threw an exception", type_name)
+            }
+            ValueWarning::ToStringTimedOut { type_name } => {
+                write!(f, "{}.toString() timed out and is still running", type_name)
+            }
+            ValueWarning::GettersInvoked { type_name } => write!(
+                f,
+                "invoked getters of {}, which may have side effects",
+                type_name
+            ),
+        }
+    }
+}
+
+/// State of a single read
+#[derive(Default)]
+struct ReadState {
+    warnings: Vec<ValueWarning>,
+    /// Set once an invocation timed out, as the thread is still busy running it
+    thread_busy: bool,
+}
+
+/// Reads debuggee values deeply for display, e.g. the variables at a breakpoint
+///
+/// Objects are expanded into their fields up to a depth limit, and arrays are read up to an
+/// element limit. With a thread to invoke methods in, collections and maps are read through
+/// their `size()`, `toArray()` and entry methods, so they show their elements rather than
+/// their internal fields.
+#[derive(Debug, Clone)]
+pub struct ValueReader {
+    max_depth: usize,
+    max_elements: usize,
+    thread: Option<VariableLengthId>,
+    to_string_patterns: Vec<String>,
+    to_string_timeout: Duration,
+    bean_patterns: Vec<String>,
+    unbox: bool,
+}
+impl Default for ValueReader {
+    fn default() -> Self {
+        ValueReader {
+            max_depth: 2,
+            max_elements: 100,
+            thread: None,
+            to_string_patterns: Vec::new(),
+            to_string_timeout: Duration::from_secs(1),
+            bean_patterns: Vec::new(),
+            unbox: false,
+        }
+    }
+}
+impl ValueReader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of object levels expanded below the value itself. Defaults to 2.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Number of elements read from arrays, collections and maps. Defaults to 100.
+    pub fn max_elements(mut self, max_elements: usize) -> Self {
+        self.max_elements = max_elements;
+        self
+    }
+
+    /// Thread methods of collections and maps are invoked in, which must be suspended by an
+    /// event. Without one, collections are shown by their fields.
+    pub fn thread(mut self, thread: VariableLengthId) -> Self {
+        self.thread = Some(thread);
+        self
+    }
+
+    /// Shows objects of classes matching any of `patterns` (e.g. `com.acme.*` or
+    /// `*.Money`) by invoking their `toString()` in the [ValueReader::thread]. Off by default,
+    /// as invoking resumes the VM and runs arbitrary code; each invocation is reported as a
+    /// [ValueWarning]. Objects whose `toString()` throws or times out are shown by their fields.
+    pub fn to_string_for(mut self, patterns: &[&str]) -> Self {
+        self.to_string_patterns = patterns.iter().map(|p| String::from(*p)).collect();
+        self
+    }
+
+    /// How long to wait for a `toString()` to return. Defaults to 1 second.
+    pub fn to_string_timeout(mut self, timeout: Duration) -> Self {
+        self.to_string_timeout = timeout;
+        self
+    }
+
+    /// Shows objects of classes matching any of `patterns` by their bean properties instead of
+    /// their fields, invoking every public `getX()` and `isX()` in the [ValueReader::thread].
+    /// Off by default for the same reasons as [ValueReader::to_string_for]. Properties whose
+    /// getter throws are left out.
+    pub fn bean_properties_for(mut self, patterns: &[&str]) -> Self {
+        self.bean_patterns = patterns.iter().map(|p| String::from(*p)).collect();
+        self
+    }
+
+    /// Shows boxed primitives such as `java.lang.Integer` as the primitive they hold, read from
+    /// their `value` field
+    pub fn unbox_primitives(mut self) -> Self {
+        self.unbox = true;
+        self
+    }
+
+    pub async fn read(
+        &self,
+        client: &JdwpClient,
+        value: JdwpValue,
+    ) -> result::Result<DisplayValue> {
+        Ok(self.read_with_warnings(client, value).await?.0)
+    }
+
+    /// Like [ValueReader::read], also returning what was done that the user should know about,
+    /// such as methods invoked with possible side effects
+    pub async fn read_with_warnings(
+        &self,
+        client: &JdwpClient,
+        value: JdwpValue,
+    ) -> result::Result<(DisplayValue, Vec<ValueWarning>)> {
+        let mut state = ReadState::default();
+        let value = self.read_at(client, value, 0, &mut state).await?;
+        Ok((value, state.warnings))
+    }
+
+    fn read_at<'a>(
+        &'a self,
+        client: &'a JdwpClient,
+        value: JdwpValue,
+        depth: usize,
+        state: &'a mut ReadState,
+    ) -> ReadFuture<'a> {
+        Box::pin(async move {
+            let object = match value {
+                JdwpValue::Object(object) if object.object_id.value == 0 => {
+                    return Ok(DisplayValue::Null);
+                }
+                JdwpValue::Object(object) => object,
+                primitive => return Ok(DisplayValue::Primitive(primitive)),
+            };
+            if object.tag == Tag::String {
+                let value = client
+                    .string_get_value(object.object_id)
+                    .await?
+                    .string_value
+                    .string;
+                return Ok(DisplayValue::String { object, value });
+            }
+
+            let type_id = client
+                .object_get_reference_type(object.object_id)
+                .await?
+                .type_id;
+            let signature = client
+                .ref_type_get_signature(type_id)
+                .await?
+                .signature
+                .string;
+            let type_name = signature_to_type_name(&signature);
+            if self.unbox && WRAPPER_CLASSES.contains(&type_name.as_str()) {
+                let value = boxed_value(client, type_id, object.object_id).await?;
+                return Ok(DisplayValue::Primitive(value));
+            }
+            let superclasses = match object.tag {
+                Tag::Array => Vec::new(),
+                _ => superclasses(client, type_id).await?,
+            };
+            if let Some((type_name, name)) =
+                enum_constant(client, &signature, &superclasses, object).await?
+            {
+                return Ok(DisplayValue::EnumConstant {
+                    object,
+                    type_name,
+                    name,
+                });
+            }
+            if depth > self.max_depth {
+                return Ok(DisplayValue::Unexpanded { object, type_name });
+            }
+
+            if object.tag == Tag::Array {
+                let length = client
+                    .array_get_length(object.object_id)
+                    .await?
+                    .array_length;
+                let count = (length.max(0) as usize).min(self.max_elements);
+                let values = client
+                    .array_get_values(object.object_id, 0, count as i32)
+                    .await?
+                    .values;
+                let mut elements = Vec::with_capacity(count);
+                for value in array_elements(values) {
+                    elements.push(self.read_at(client, value, depth + 1, state).await?);
+                }
+                return Ok(DisplayValue::Array {
+                    object,
+                    type_name,
+                    length: length.max(0) as usize,
+                    elements,
+                });
+            }
+
+            if let Some(thread) = self.thread.filter(|_| !state.thread_busy) {
+                if self
+                    .to_string_patterns
+                    .iter()
+                    .any(|pattern| matches_class_pattern(pattern, &type_name))
+                {
+                    if let Some(text) = self
+                        .invoke_to_string(client, thread, object, &type_name, state)
+                        .await?
+                    {
+                        return Ok(DisplayValue::Text {
+                            object,
+                            type_name,
+                            text,
+                        });
+                    }
+                } else if self
+                    .bean_patterns
+                    .iter()
+                    .any(|pattern| matches_class_pattern(pattern, &type_name))
+                {
+                    let properties = client.bean_properties(type_id).await?;
+                    state.warnings.push(ValueWarning::GettersInvoked {
+                        type_name: type_name.clone(),
+                    });
+                    let fields = self
+                        .read_properties(client, thread, object, properties, depth, state)
+                        .await?;
+                    return Ok(DisplayValue::Object {
+                        object,
+                        type_name,
+                        fields,
+                    });
+                } else if client.instance_of(object, "Ljava/util/Map;").await? {
+                    return self
+                        .read_map(client, thread, object, type_name, depth, state)
+                        .await;
+                } else if client.instance_of(object, "Ljava/util/Collection;").await? {
+                    return self
+                        .read_collection(client, thread, object, type_name, depth, state)
+                        .await;
+                }
+            }
+
+            if superclasses
+                .first()
+                .is_some_and(|(_, signature)| signature == RECORD_SIGNATURE)
+            {
+                let components = client.components_of_record(type_id).await?;
+                let ids: Vec<_> = components.iter().map(|c| c.field_id).collect();
+                let values = match ids.is_empty() {
+                    true => Vec::new(),
+                    false => {
+                        client
+                            .object_get_values(object.object_id, ids)
+                            .await?
+                            .values
+                    }
+                };
+                let mut read = Vec::with_capacity(values.len());
+                for (component, value) in components.into_iter().zip(values) {
+                    let value = self.read_at(client, value, depth + 1, state).await?;
+                    read.push((component.name, value));
+                }
+                return Ok(DisplayValue::Record {
+                    object,
+                    type_name,
+                    components: read,
+                });
+            }
+
+            let values = instance_fields(client, type_id, &superclasses, object.object_id).await?;
+            let mut fields = Vec::with_capacity(values.len());
+            for (name, value) in values {
+                fields.push((name, self.read_at(client, value, depth + 1, state).await?));
+            }
+            Ok(DisplayValue::Object {
+                object,
+                type_name,
+                fields,
+            })
+        })
+    }
+
+    /// Result of `toString()`, or None if it threw or timed out
+    async fn invoke_to_string(
+        &self,
+        client: &JdwpClient,
+        thread: VariableLengthId,
+        object: TaggedObjectId,
+        type_name: &str,
+        state: &mut ReadState,
+    ) -> result::Result<Option<String>> {
+        let type_name = String::from(type_name);
+        state.warnings.push(ValueWarning::ToStringInvoked {
+            type_name: type_name.clone(),
+        });
+        let invocation = invoke::<Option<String>>(client, thread, object, "toString");
+        match tokio::time::timeout(self.to_string_timeout, invocation).await {
+            Ok(Ok(text)) => Ok(Some(text.unwrap_or_else(|| String::from("null")))),
+            Ok(Err(result::Error::InvocationException { .. })) => {
+                state
+                    .warnings
+                    .push(ValueWarning::ToStringThrew { type_name });
+                Ok(None)
+            }
+            Ok(Err(e)) => Err(e),
+            Err(_) => {
+                state.thread_busy = true;
+                state
+                    .warnings
+                    .push(ValueWarning::ToStringTimedOut { type_name });
+                Ok(None)
+            }
+        }
+    }
+
+    /// Names and values of bean properties, leaving out those whose getter threw
+    async fn read_properties(
+        &self,
+        client: &JdwpClient,
+        thread: VariableLengthId,
+        object: TaggedObjectId,
+        properties: Vec<BeanProperty>,
+        depth: usize,
+        state: &mut ReadState,
+    ) -> result::Result<Vec<(String, DisplayValue)>> {
+        let mut fields = Vec::with_capacity(properties.len());
+        for property in properties {
+            let reply = client
+                .object_invoke_method(
+                    object.object_id,
+                    thread,
+                    property.class_id,
+                    property.getter.method_id,
+                    Vec::new(),
+                    InvokeOptions::empty(),
+                )
+                .await?;
+            if reply.exception.object_id.value != 0 {
+                continue;
+            }
+            let value = self
+                .read_at(client, reply.return_value, depth + 1, state)
+                .await?;
+            fields.push((property.name, value));
+        }
+        Ok(fields)
+    }
+
+    async fn read_collection(
+        &self,
+        client: &JdwpClient,
+        thread: VariableLengthId,
+        object: TaggedObjectId,
+        type_name: String,
+        depth: usize,
+        state: &mut ReadState,
+    ) -> result::Result<DisplayValue> {
+        let size = invoke::<i32>(client, thread, object, "size").await?;
+        let array = invoke::<TaggedObjectId>(client, thread, object, "toArray").await?;
+        let mut elements = Vec::new();
+        for value in self.first_elements(client, array).await? {
+            elements.push(self.read_at(client, value, depth + 1, state).await?);
+        }
+        Ok(DisplayValue::Collection {
+            object,
+            type_name,
+            size,
+            elements,
+        })
+    }
+
+    async fn read_map(
+        &self,
+        client: &JdwpClient,
+        thread: VariableLengthId,
+        object: TaggedObjectId,
+        type_name: String,
+        depth: usize,
+        state: &mut ReadState,
+    ) -> result::Result<DisplayValue> {
+        let size = invoke::<i32>(client, thread, object, "size").await?;
+        let entry_set = invoke::<TaggedObjectId>(client, thread, object, "entrySet").await?;
+        let array = invoke::<TaggedObjectId>(client, thread, entry_set, "toArray").await?;
+        let mut entries = Vec::new();
+        for entry in self.first_elements(client, array).await? {
+            let JdwpValue::Object(entry) = entry else {
+                continue;
+            };
+            let key = client.invoke_method(thread, entry, "getKey", ()).await?;
+            let value = client.invoke_method(thread, entry, "getValue", ()).await?;
+            entries.push((
+                self.read_at(client, key, depth + 1, state).await?,
+                self.read_at(client, value, depth + 1, state).await?,
+            ));
+        }
+        Ok(DisplayValue::Map {
+            object,
+            type_name,
+            size,
+            entries,
+        })
+    }
+
+    async fn first_elements(
+        &self,
+        client: &JdwpClient,
+        array: TaggedObjectId,
+    ) -> result::Result<Vec<JdwpValue>> {
+        let length = client.array_get_length(array.object_id).await?.array_length;
+        let count = (length.max(0) as usize).min(self.max_elements) as i32;
+        let values = client.array_get_values(array.object_id, 0, count).await?;
+        Ok(array_elements(values.values))
+    }
+}
+
+impl JdwpClient {
+    /// The primitive held by a `java.lang.Integer`, `java.lang.Boolean` or other wrapper, or
+    /// None if the object isn't one. Reads the `value` field, so nothing is invoked.
+    pub async fn unbox(&self, object: TaggedObjectId) -> result::Result<Option<JdwpValue>> {
+        if object.object_id.value == 0 {
+            return Ok(None);
+        }
+        let type_id = self
+            .object_get_reference_type(object.object_id)
+            .await?
+            .type_id;
+        let signature = self.ref_type_get_signature(type_id).await?.signature.string;
+        if !WRAPPER_CLASSES.contains(&signature_to_type_name(&signature).as_str()) {
+            return Ok(None);
+        }
+        Ok(Some(boxed_value(self, type_id, object.object_id).await?))
+    }
+
+    /// `name()` of an enum constant, or None if the object isn't one. Reads the `name` field,
+    /// so nothing is invoked.
+    pub async fn enum_constant_name(
+        &self,
+        object: TaggedObjectId,
+    ) -> result::Result<Option<String>> {
+        if object.object_id.value == 0 {
+            return Ok(None);
+        }
+        let type_id = self
+            .object_get_reference_type(object.object_id)
+            .await?
+            .type_id;
+        let signature = self.ref_type_get_signature(type_id).await?.signature.string;
+        let superclasses = superclasses(self, type_id).await?;
+        Ok(enum_constant(self, &signature, &superclasses, object)
+            .await?
+            .map(|(_, name)| name))
+    }
+}
+
+/// Superclasses of a class with their signatures, nearest first and ending with Object
+async fn superclasses(
+    client: &JdwpClient,
+    type_id: VariableLengthId,
+) -> result::Result<Vec<(VariableLengthId, String)>> {
+    let mut superclasses = Vec::new();
+    let mut current = type_id;
+    loop {
+        let superclass = match client.class_type_get_superclass(current).await {
+            Ok(reply) if reply.superclass.value != 0 => reply.superclass,
+            // Interfaces and arrays have no superclass
+            Ok(_) | Err(result::Error::JdwpError(JdwpErrorCode::InvalidClass)) => break,
+            Err(e) => return Err(e),
+        };
+        let signature = client
+            .ref_type_get_signature(superclass)
+            .await?
+            .signature
+            .string;
+        let is_object = signature == OBJECT_SIGNATURE;
+        superclasses.push((superclass, signature));
+        if is_object {
+            break;
+        }
+        current = superclass;
+    }
+    Ok(superclasses)
+}
+
+/// Enum type name and constant name of an object, if it's an enum constant. Constants with a
+/// body are instances of an anonymous subclass of the enum.
+async fn enum_constant(
+    client: &JdwpClient,
+    signature: &str,
+    superclasses: &[(VariableLengthId, String)],
+    object: TaggedObjectId,
+) -> result::Result<Option<(String, String)>> {
+    let Some(position) = superclasses
+        .iter()
+        .take(2)
+        .position(|(_, signature)| signature == ENUM_SIGNATURE)
+    else {
+        return Ok(None);
+    };
+    let enum_signature = match position {
+        0 => signature,
+        _ => &superclasses[0].1,
+    };
+
+    let name_field = client
+        .ref_type_get_fields(superclasses[position].0)
+        .await?
+        .fields
+        .into_iter()
+        .find(|field| field.name.string == "name" && field.mod_bits & ACC_STATIC == 0);
+    let Some(name_field) = name_field else {
+        return Ok(None);
+    };
+    let value = client
+        .object_get_values(object.object_id, vec![name_field.field_id])
+        .await?
+        .values
+        .pop();
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    Ok(Some((
+        signature_to_type_name(enum_signature),
+        String::try_from_jdwp_value(value, client).await?,
+    )))
+}
+
+/// Value of the `value` field of a wrapper class instance
+async fn boxed_value(
+    client: &JdwpClient,
+    type_id: VariableLengthId,
+    object_id: VariableLengthId,
+) -> result::Result<JdwpValue> {
+    let field = client
+        .ref_type_get_fields(type_id)
+        .await?
+        .fields
+        .into_iter()
+        .find(|field| field.name.string == "value" && field.mod_bits & ACC_STATIC == 0)
+        .ok_or_else(|| result::Error::ParsingError {
+            message: String::from("Wrapper class has no value field"),
+        })?;
+    client
+        .object_get_values(object_id, vec![field.field_id])
+        .await?
+        .values
+        .pop()
+        .ok_or_else(|| result::Error::ParsingError {
+            message: String::from("GetValues returned no value"),
+        })
+}
+
+async fn invoke<R: TryFromJdwpValue>(
+    client: &JdwpClient,
+    thread: VariableLengthId,
+    object: TaggedObjectId,
+    name: &str,
+) -> result::Result<R> {
+    let value = client.invoke_method(thread, object, name, ()).await?;
+    R::try_from_jdwp_value(value, client).await
+}
+
+/// Names and values of the instance fields of an object, those of superclasses first
+async fn instance_fields(
+    client: &JdwpClient,
+    type_id: VariableLengthId,
+    superclasses: &[(VariableLengthId, String)],
+    object_id: VariableLengthId,
+) -> result::Result<Vec<(String, JdwpValue)>> {
+    // Object has no instance fields
+    let hierarchy = superclasses
+        .iter()
+        .filter(|(_, signature)| signature != OBJECT_SIGNATURE)
+        .map(|(class_id, _)| *class_id)
+        .rev()
+        .chain([type_id]);
+
+    let mut fields = Vec::new();
+    for class_id in hierarchy {
+        fields.extend(
+            client
+                .ref_type_get_fields(class_id)
+                .await?
+                .fields
+                .into_iter()
+                .filter(|field| field.mod_bits & ACC_STATIC == 0),
+        );
+    }
+    if fields.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let ids = fields.iter().map(|field| field.field_id).collect();
+    let values = client.object_get_values(object_id, ids).await?.values;
+    Ok(fields
+        .into_iter()
+        .map(|field| field.name.string)
+        .zip(values)
+        .collect())
+}
+
+/// Whether a class name matches a pattern, which like a JDWP class pattern may begin or end
+/// with `*`
+fn matches_class_pattern(pattern: &str, type_name: &str) -> bool {
+    if let Some(suffix) = pattern.strip_prefix('*') {
+        type_name.ends_with(suffix)
+    } else if let Some(prefix) = pattern.strip_suffix('*') {
+        type_name.starts_with(prefix)
+    } else {
+        pattern == type_name
+    }
+}
+
+fn array_elements(values: ArrayValues) -> Vec<JdwpValue> {
+    fn wrap<T: Copy>(values: Vec<T>, variant: impl Fn(T) -> JdwpValue) -> Vec<JdwpValue> {
+        values.into_iter().map(variant).collect()
+    }
+
+    match values {
+        ArrayValues::Objects(objects) => wrap(objects, JdwpValue::Object),
+        ArrayValues::Primitive(PrimitiveArray::Boolean(v)) => wrap(v, JdwpValue::Boolean),
+        ArrayValues::Primitive(PrimitiveArray::Byte(v)) => wrap(v, |b| JdwpValue::Byte(b as i8)),
+        ArrayValues::Primitive(PrimitiveArray::Char(v)) => wrap(v, JdwpValue::Char),
+        ArrayValues::Primitive(PrimitiveArray::Short(v)) => wrap(v, JdwpValue::Short),
+        ArrayValues::Primitive(PrimitiveArray::Int(v)) => wrap(v, JdwpValue::Int),
+        ArrayValues::Primitive(PrimitiveArray::Long(v)) => wrap(v, JdwpValue::Long),
+        ArrayValues::Primitive(PrimitiveArray::Float(v)) => wrap(v, JdwpValue::Float),
+        ArrayValues::Primitive(PrimitiveArray::Double(v)) => wrap(v, JdwpValue::Double),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(value: u64) -> TaggedObjectId {
+        TaggedObjectId {
+            tag: Tag::Object,
+            object_id: VariableLengthId { value },
+        }
+    }
+
+    #[test]
+    fn test_matches_class_pattern() {
+        assert!(matches_class_pattern("com.acme.*", "com.acme.Money"));
+        assert!(matches_class_pattern("*.Money", "com.acme.Money"));
+        assert!(matches_class_pattern("com.acme.Money", "com.acme.Money"));
+        assert!(!matches_class_pattern(
+            "com.acme.Money",
+            "com.acme.MoneyBag"
+        ));
+        assert!(!matches_class_pattern("org.*", "com.acme.Money"));
+    }
+
+    #[test]
+    fn test_display() {
+        let value = DisplayValue::Object {
+            object: object(0x30),
+            type_name: String::from("com.acme.Order"),
+            fields: vec![
+                (
+                    String::from("id"),
+                    DisplayValue::Primitive(JdwpValue::Int(7)),
+                ),
+                (
+                    String::from("grade"),
+                    DisplayValue::Primitive(JdwpValue::Char(65)),
+                ),
+                (
+                    String::from("items"),
+                    DisplayValue::Collection {
+                        object: object(0x31),
+                        type_name: String::from("java.util.ArrayList"),
+                        size: 3,
+                        elements: vec![
+                            DisplayValue::String {
+                                object: object(0x32),
+                                value: String::from("a"),
+                            },
+                            DisplayValue::Null,
+                        ],
+                    },
+                ),
+                (
+                    String::from("owner"),
+                    DisplayValue::Unexpanded {
+                        object: object(0x3f),
+                        type_name: String::from("com.acme.User"),
+                    },
+                ),
+            ],
+        };
+        assert_eq!(
+            value.to_string(),
+            "com.acme.Order {id=7, grade='A', items=java.util.ArrayList(size=3) [\"a\", null, ...], \
+             owner=com.acme.User@3f}"
+        );
+
+        let array = DisplayValue::Array {
+            object: object(0x40),
+            type_name: String::from("int[]"),
+            length: 2,
+            elements: vec![
+                DisplayValue::Primitive(JdwpValue::Int(1)),
+                DisplayValue::Primitive(JdwpValue::Int(2)),
+            ],
+        };
+        assert_eq!(array.to_string(), "int[2] [1, 2]");
+
+        let chars = DisplayValue::Array {
+            object: object(0x41),
+            type_name: String::from("char[]"),
+            length: 4,
+            elements: [0x68, 0xD83D, 0xDE00]
+                .into_iter()
+                .map(|unit| DisplayValue::Primitive(JdwpValue::Char(unit)))
+                .collect(),
+        };
+        assert_eq!(chars.to_string(), "char[4] \"h😀\"...");
+    }
+}
@@ -0,0 +1,411 @@
+//! JDWP tunnelled through WebSocket binary frames (RFC 6455), for debugger frontends running
+//! in a browser, which can't open TCP connections
+//!
+//! [WebSocketRelay] accepts WebSocket connections and forwards the payload of their frames to
+//! the JDWP agent of a VM. [Transport::websocket](crate::Transport::websocket) connects through
+//! such a relay. Only unencrypted `ws://` URLs are supported; `wss://` needs a TLS terminating
+//! proxy in front of the relay.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use sha1::{Digest, Sha1};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::mpsc;
+
+use crate::proxy::base64;
+
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const MAX_HEADER_LENGTH: usize = 8 * 1024;
+/// Frames larger than this are rejected, JDWP packets are far smaller
+const MAX_FRAME_LENGTH: u64 = 16 * 1024 * 1024;
+const BUFFER_SIZE: usize = 64 * 1024;
+
+const OPCODE_CONTINUATION: u8 = 0x0;
+const OPCODE_BINARY: u8 = 0x2;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+/// Which end of the connection frames are written by. Clients mask their frames, servers
+/// don't.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    Client,
+    Server,
+}
+
+struct Frame {
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/// Forwards WebSocket connections to the JDWP agent of a VM, one TCP connection per WebSocket
+///
+/// The relay doesn't look at the packets, so the JDWP handshake and everything after it is up
+/// to the frontend. Each frame may carry any part of the byte stream.
+pub struct WebSocketRelay {
+    listener: TcpListener,
+    target: String,
+}
+
+impl WebSocketRelay {
+    /// Listens for WebSocket connections on `address`, forwarding them to `target`, e.g.
+    /// `localhost:5005`
+    pub async fn bind(address: impl ToSocketAddrs, target: impl Into<String>) -> io::Result<Self> {
+        Ok(WebSocketRelay {
+            listener: TcpListener::bind(address).await?,
+            target: target.into(),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts connections until accepting fails. A connection whose upgrade or target fails
+    /// is closed without affecting the others.
+    pub async fn run(self) -> io::Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            let target = self.target.clone();
+            tokio::spawn(async move {
+                if let Err(e) = relay(stream, &target).await {
+                    eprintln!("WebSocket relay error: {:?}", e);
+                }
+            });
+        }
+    }
+}
+
+async fn relay(stream: TcpStream, target: &str) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut websocket = accept(stream).await?;
+    let mut vm = TcpStream::connect(target).await?;
+    vm.set_nodelay(true)?;
+    tokio::io::copy_bidirectional(&mut websocket, &mut vm).await?;
+    Ok(())
+}
+
+/// Opens a WebSocket connection to a `ws://host:port/path` URL. The payload of the binary
+/// frames is read and written through the returned stream.
+pub(crate) async fn connect(url: &str) -> io::Result<DuplexStream> {
+    let (address, path) = parse_url(url)?;
+    let mut stream = TcpStream::connect(address.as_str()).await?;
+    stream.set_nodelay(true)?;
+
+    let key = base64(&random_bytes::<16>());
+    let request = format!(
+        "GET {path} HTTP/1.1\r\nHost: {address}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: {key}\r\nSec-WebSocket-Version: 13\r\n\r\n"
+    );
+    stream.write_all(request.as_bytes()).await?;
+
+    let response = read_header(&mut stream).await?;
+    let status_line = response.lines().next().unwrap_or_default();
+    if status_line.split_whitespace().nth(1) != Some("101") {
+        return Err(handshake_error(format!(
+            "WebSocket upgrade refused: {}",
+            status_line
+        )));
+    }
+    if header_value(&response, "Sec-WebSocket-Accept") != Some(accept_key(&key).as_str()) {
+        return Err(handshake_error(String::from(
+            "Invalid Sec-WebSocket-Accept in the upgrade response",
+        )));
+    }
+    Ok(tunnel(stream, Role::Client))
+}
+
+/// Completes the server side of the upgrade on a connection accepted by a listener
+pub(crate) async fn accept<S>(mut stream: S) -> io::Result<DuplexStream>
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let request = read_header(&mut stream).await?;
+    let Some(key) = header_value(&request, "Sec-WebSocket-Key") else {
+        stream
+            .write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")
+            .await?;
+        return Err(handshake_error(String::from(
+            "Request without Sec-WebSocket-Key",
+        )));
+    };
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    );
+    stream.write_all(response.as_bytes()).await?;
+    Ok(tunnel(stream, Role::Server))
+}
+
+/// Pumps the frames of an upgraded connection to and from an in-memory stream, which is
+/// returned. Control frames are answered by the pumps.
+fn tunnel<S>(stream: S, role: Role) -> DuplexStream
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let (app_end, pipe_end) = tokio::io::duplex(BUFFER_SIZE);
+    let (mut from_app, mut to_app) = tokio::io::split(pipe_end);
+    let (mut socket_reader, mut socket_writer) = tokio::io::split(stream);
+    let (frames, mut outgoing) = mpsc::unbounded_channel::<Vec<u8>>();
+
+    // Only this task writes to the socket, so frames of the other two aren't interleaved
+    tokio::spawn(async move {
+        while let Some(frame) = outgoing.recv().await {
+            if socket_writer.write_all(&frame).await.is_err() {
+                break;
+            }
+        }
+        let _ = socket_writer.shutdown().await;
+    });
+
+    let control = frames.clone();
+    tokio::spawn(async move {
+        loop {
+            let frame = match read_frame(&mut socket_reader).await {
+                Ok(frame) => frame,
+                Err(_) => break,
+            };
+            match frame.opcode {
+                OPCODE_BINARY | OPCODE_CONTINUATION => {
+                    if to_app.write_all(&frame.payload).await.is_err() {
+                        break;
+                    }
+                }
+                OPCODE_PING => {
+                    let _ = control.send(encode_frame(OPCODE_PONG, &frame.payload, role));
+                }
+                OPCODE_PONG => {}
+                // Closing, or text which has no place in a JDWP stream
+                _ => {
+                    let _ = control.send(encode_frame(OPCODE_CLOSE, &[], role));
+                    break;
+                }
+            }
+        }
+        let _ = to_app.shutdown().await;
+    });
+
+    tokio::spawn(async move {
+        let mut buffer = vec![0u8; BUFFER_SIZE];
+        while let Ok(read @ 1..) = from_app.read(&mut buffer).await {
+            if frames
+                .send(encode_frame(OPCODE_BINARY, &buffer[..read], role))
+                .is_err()
+            {
+                return;
+            }
+        }
+        let _ = frames.send(encode_frame(OPCODE_CLOSE, &[], role));
+    });
+
+    app_end
+}
+
+fn encode_frame(opcode: u8, payload: &[u8], role: Role) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    frame.push(0x80 | opcode);
+    let mask_bit = if role == Role::Client { 0x80 } else { 0x00 };
+    match payload.len() {
+        length @ 0..=125 => frame.push(mask_bit | length as u8),
+        length @ 126..=0xFFFF => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    if role == Role::Client {
+        let mask = random_bytes::<4>();
+        frame.extend_from_slice(&mask);
+        frame.extend(
+            payload
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ mask[i % 4]),
+        );
+    } else {
+        frame.extend_from_slice(payload);
+    }
+    frame
+}
+
+async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Frame> {
+    let first = reader.read_u8().await?;
+    let second = reader.read_u8().await?;
+    let length = match second & 0x7F {
+        126 => reader.read_u16().await? as u64,
+        127 => reader.read_u64().await?,
+        length => length as u64,
+    };
+    if length > MAX_FRAME_LENGTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("WebSocket frame too long: {}", length),
+        ));
+    }
+    let mask = if second & 0x80 != 0 {
+        let mut mask = [0u8; 4];
+        reader.read_exact(&mut mask).await?;
+        Some(mask)
+    } else {
+        None
+    };
+
+    let mut payload = vec![0u8; length as usize];
+    reader.read_exact(&mut payload).await?;
+    if let Some(mask) = mask {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+    }
+    Ok(Frame {
+        opcode: first & 0x0F,
+        payload,
+    })
+}
+
+/// Reads an HTTP header byte by byte, so nothing sent after it is consumed
+async fn read_header<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<String> {
+    let mut header = Vec::new();
+    while !header.ends_with(b"\r\n\r\n") {
+        if header.len() >= MAX_HEADER_LENGTH {
+            return Err(handshake_error(String::from("HTTP header too long")));
+        }
+        header.push(stream.read_u8().await?);
+    }
+    Ok(String::from_utf8_lossy(&header).into_owned())
+}
+
+fn header_value<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+    header.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim()
+            .eq_ignore_ascii_case(name)
+            .then_some(value.trim())
+    })
+}
+
+fn accept_key(key: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(ACCEPT_GUID.as_bytes());
+    base64(&sha1.finalize())
+}
+
+/// Splits `ws://host:port/path` into the address to connect to and the path to request
+fn parse_url(url: &str) -> io::Result<(String, String)> {
+    let invalid = |message: &str| {
+        io::Error::new(io::ErrorKind::InvalidInput, format!("{}: {}", message, url))
+    };
+    let rest = url
+        .strip_prefix("ws://")
+        .ok_or_else(|| invalid("Only ws:// URLs are supported"))?;
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    if authority.is_empty() {
+        return Err(invalid("URL without a host"));
+    }
+    // An IPv6 address ends with ']' unless a port follows it
+    let has_port = authority
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| !port.ends_with(']'));
+    let address = if has_port {
+        String::from(authority)
+    } else {
+        format!("{}:80", authority)
+    };
+    Ok((address, String::from(path)))
+}
+
+fn handshake_error(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Unpredictable enough for keys and masks, which only guard against confused proxies
+fn random_bytes<const N: usize>() -> [u8; N] {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut bytes = [0u8; N];
+    for chunk in bytes.chunks_mut(8) {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        chunk.copy_from_slice(&hasher.finish().to_le_bytes()[..chunk.len()]);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accept_key() {
+        // Example of RFC 6455, section 1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn test_parse_url() {
+        let parse = |url| parse_url(url).unwrap();
+        assert_eq!(
+            parse("ws://localhost:8080/jdwp"),
+            (String::from("localhost:8080"), String::from("/jdwp"))
+        );
+        assert_eq!(
+            parse("ws://example.com"),
+            (String::from("example.com:80"), String::from("/"))
+        );
+        assert_eq!(
+            parse("ws://[::1]/"),
+            (String::from("[::1]:80"), String::from("/"))
+        );
+        assert!(parse_url("wss://example.com").is_err());
+        assert!(parse_url("ws:///jdwp").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_masked_frame_round_trip() {
+        let payload = vec![0x42; 300];
+        let frame = encode_frame(OPCODE_BINARY, &payload, Role::Client);
+        assert_eq!(frame[1], 0x80 | 126);
+        let decoded = read_frame(&mut frame.as_slice()).await.unwrap();
+        assert_eq!(decoded.opcode, OPCODE_BINARY);
+        assert_eq!(decoded.payload, payload);
+    }
+
+    #[tokio::test]
+    async fn test_relay() {
+        let vm = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let vm_address = vm.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = vm.accept().await.unwrap();
+            let mut handshake = [0u8; 14];
+            stream.read_exact(&mut handshake).await.unwrap();
+            stream.write_all(&handshake).await.unwrap();
+        });
+        let relay = WebSocketRelay::bind("127.0.0.1:0", vm_address.to_string())
+            .await
+            .unwrap();
+        let url = format!("ws://{}/jdwp", relay.local_addr().unwrap());
+        tokio::spawn(relay.run());
+
+        let mut stream = connect(&url).await.unwrap();
+        stream.write_all(b"JDWP-Handshake").await.unwrap();
+        let mut reply = [0u8; 14];
+        stream.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"JDWP-Handshake");
+    }
+}
@@ -0,0 +1,122 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod allocations_tests {
+    use crate::common::{
+        MockStreamBuilder, command_packet, event_packet, id, jdwp_string, reply_packet,
+    };
+    use jdwp_client::{AllocationTracker, JdwpClient};
+    use std::time::Duration;
+
+    fn location(class_id: u64, method_id: u64, index: u64) -> Vec<u8> {
+        let mut bytes = vec![0x1];
+        bytes.extend_from_slice(&id(class_id));
+        bytes.extend_from_slice(&id(method_id));
+        bytes.extend_from_slice(&id(index));
+        bytes
+    }
+
+    fn methods_reply() -> Vec<u8> {
+        let mut reply = vec![0x0, 0x0, 0x0, 0x3];
+        for (method_id, name) in [(0x1, "<init>"), (0x2, "<init>"), (0x3, "total")] {
+            reply.extend_from_slice(&id(method_id));
+            reply.extend_from_slice(&jdwp_string(name));
+            reply.extend_from_slice(&jdwp_string("()V"));
+            reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x1]);
+        }
+        reply
+    }
+
+    fn breakpoint_out(method_id: u64) -> Vec<u8> {
+        let mut out = vec![0x2, 0x1, 0x0, 0x0, 0x0, 0x1, 0x7];
+        out.extend_from_slice(&location(0x10, method_id, 0));
+        out
+    }
+
+    fn hit(packet_id: u32, request_id: u8, thread: u64, method_id: u64) -> Vec<u8> {
+        let mut event = vec![0x2, 0x0, 0x0, 0x0, request_id];
+        event.extend_from_slice(&id(thread));
+        event.extend_from_slice(&location(0x10, method_id, 0));
+        event_packet(packet_id, 0x1, &[event])
+    }
+
+    fn frames_out(thread: u64) -> Vec<u8> {
+        let mut out = id(thread).to_vec();
+        out.extend_from_slice(&[0x0, 0x0, 0x0, 0x1, 0x0, 0x0, 0x0, 0x1]);
+        out
+    }
+
+    fn frames_reply(caller_index: u64) -> Vec<u8> {
+        let mut reply = vec![0x0, 0x0, 0x0, 0x1];
+        reply.extend_from_slice(&id(0x99));
+        reply.extend_from_slice(&location(0x20, 0x4, caller_index));
+        reply
+    }
+
+    /// Answers `command` and sends `event` right after the reply
+    fn reply_then(
+        id: u32,
+        command: [u8; 2],
+        out: &[u8],
+        reply: &[u8],
+        event: Vec<u8>,
+    ) -> (Vec<u8>, Vec<u8>) {
+        let mut response = reply_packet(id, 0, reply);
+        response.extend_from_slice(&event);
+        (command_packet(id, command, out), response)
+    }
+
+    #[tokio::test]
+    async fn test_top_allocators() {
+        let steps = [
+            reply_then(
+                5,
+                [0xF, 0x1],
+                &breakpoint_out(0x2),
+                &[0x0, 0x0, 0x0, 0x8],
+                hit(100, 7, 0x5, 0x1),
+            ),
+            reply_then(7, [0xB, 0x3], &id(0x5), &[], hit(101, 8, 0x6, 0x2)),
+            reply_then(9, [0xB, 0x3], &id(0x6), &[], hit(102, 7, 0x5, 0x1)),
+        ];
+        let mut builder = MockStreamBuilder::default()
+            .command_reply(
+                2,
+                [0x1, 0x2],
+                &jdwp_string("Lcom/acme/Order;"),
+                &[
+                    0x0, 0x0, 0x0, 0x1, 0x1, 0, 0, 0, 0, 0, 0, 0, 0x10, 0x0, 0x0, 0x0, 0x7,
+                ],
+            )
+            .command_reply(3, [0x2, 0x5], &id(0x10), &methods_reply())
+            .command_reply(4, [0xF, 0x1], &breakpoint_out(0x1), &[0x0, 0x0, 0x0, 0x7])
+            .command_reply(6, [0xB, 0x6], &frames_out(0x5), &frames_reply(12))
+            .command_reply(8, [0xB, 0x6], &frames_out(0x6), &frames_reply(30))
+            .command_reply(10, [0xB, 0x6], &frames_out(0x5), &frames_reply(12))
+            .command_reply(11, [0xB, 0x3], &id(0x5), &[])
+            .command_reply(12, [0xF, 0x2], &[0x2, 0x0, 0x0, 0x0, 0x7], &[])
+            .command_reply(13, [0xF, 0x2], &[0x2, 0x0, 0x0, 0x0, 0x8], &[]);
+        for (out, response) in steps {
+            builder = builder.response_bytes(&out, &response);
+        }
+        let client = JdwpClient::new(builder.build()).await.unwrap();
+
+        let mut tracker = AllocationTracker::start(client, &["com.acme.Order"])
+            .await
+            .unwrap();
+        assert_eq!(tracker.request_ids(), &[7, 8]);
+        tracker.record_for(Duration::from_millis(50)).await.unwrap();
+        tracker.stop().await;
+
+        let sites = tracker.top_allocators(10);
+        let counts: Vec<_> = sites
+            .iter()
+            .map(|site| (site.caller.unwrap().index, site.count))
+            .collect();
+        assert_eq!(counts, vec![(12, 2), (30, 1)]);
+        assert_eq!(sites[0].class_name, "com.acme.Order");
+        assert_eq!(tracker.top_allocators(1).len(), 1);
+    }
+}
@@ -0,0 +1,267 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod array_tests {
+    use crate::common::MockStreamBuilder;
+    use jdwp_client::{
+        ArrayValues, BLOCKING_DECODE_THRESHOLD, Error, JdwpClient, PrimitiveArray,
+        STREAMING_THRESHOLD, Tag, VariableLengthId,
+    };
+
+    const ARRAY_ID: [u8; 8] = [0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x9];
+
+    fn get_values_out(first_index: i32, length: i32) -> Vec<u8> {
+        let mut out = ARRAY_ID.to_vec();
+        out.extend_from_slice(&first_index.to_be_bytes());
+        out.extend_from_slice(&length.to_be_bytes());
+        out
+    }
+
+    #[tokio::test]
+    async fn test_length_cmd() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xd, 0x1], &ARRAY_ID, &[0x0, 0x0, 0x1, 0x0])
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let reply = client
+            .array_get_length(VariableLengthId { value: 9 })
+            .await
+            .unwrap();
+        assert_eq!(reply.array_length, 256);
+    }
+
+    #[tokio::test]
+    async fn test_read_array_chunked_ints() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xd, 0x1], &ARRAY_ID, &[0x0, 0x0, 0x0, 0x3])
+            .command_reply(
+                3,
+                [0xd, 0x2],
+                &get_values_out(0, 2),
+                &[
+                    b'I', 0x0, 0x0, 0x0, 0x2, // int region of two elements
+                    0x0, 0x0, 0x0, 0x1, 0xff, 0xff, 0xff, 0xff,
+                ],
+            )
+            .command_reply(
+                4,
+                [0xd, 0x2],
+                &get_values_out(2, 1),
+                &[b'I', 0x0, 0x0, 0x0, 0x1, 0x0, 0x0, 0x1, 0x0],
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let values = client
+            .read_array_chunked(VariableLengthId { value: 9 }, 2)
+            .await
+            .unwrap();
+        assert_eq!(
+            values,
+            ArrayValues::Primitive(PrimitiveArray::Int(vec![1, -1, 256]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_array_objects() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xd, 0x1], &ARRAY_ID, &[0x0, 0x0, 0x0, 0x2])
+            .command_reply(
+                3,
+                [0xd, 0x2],
+                &get_values_out(0, 2),
+                &[
+                    b's', 0x0, 0x0, 0x0, 0x2, // string region of two elements
+                    b's', 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x5, // string object, id=5
+                    b'L', 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, // null element
+                ],
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let values = client
+            .read_array(VariableLengthId { value: 9 })
+            .await
+            .unwrap();
+        let ArrayValues::Objects(objects) = values else {
+            panic!("Expected an object array");
+        };
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[0].tag, Tag::String);
+        assert_eq!(objects[0].object_id.value, 5);
+        assert_eq!(objects[1].tag, Tag::Object);
+        assert_eq!(objects[1].object_id.value, 0);
+    }
+
+    #[tokio::test]
+    async fn test_read_byte_array() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xd, 0x1], &ARRAY_ID, &[0x0, 0x0, 0x0, 0x4])
+            .command_reply(
+                3,
+                [0xd, 0x2],
+                &get_values_out(0, 4),
+                &[b'B', 0x0, 0x0, 0x0, 0x4, 0xca, 0xfe, 0xba, 0xbe],
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let bytes = client
+            .read_byte_array(VariableLengthId { value: 9 })
+            .await
+            .unwrap();
+        assert_eq!(&bytes[..], &[0xca, 0xfe, 0xba, 0xbe]);
+    }
+
+    #[tokio::test]
+    async fn test_read_byte_array_chunked() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xd, 0x1], &ARRAY_ID, &[0x0, 0x0, 0x0, 0x3])
+            .command_reply(
+                3,
+                [0xd, 0x2],
+                &get_values_out(0, 2),
+                &[b'B', 0x0, 0x0, 0x0, 0x2, 0x1, 0x2],
+            )
+            .command_reply(
+                4,
+                [0xd, 0x2],
+                &get_values_out(2, 1),
+                &[b'B', 0x0, 0x0, 0x0, 0x1, 0x3],
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let bytes = client
+            .read_byte_array_chunked(VariableLengthId { value: 9 }, 2)
+            .await
+            .unwrap();
+        assert_eq!(&bytes[..], &[0x1, 0x2, 0x3]);
+    }
+
+    #[tokio::test]
+    async fn test_read_char_array() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xd, 0x1], &ARRAY_ID, &[0x0, 0x0, 0x0, 0x3])
+            .command_reply(
+                3,
+                [0xd, 0x2],
+                &get_values_out(0, 3),
+                &[
+                    b'C', 0x0, 0x0, 0x0, 0x3, // char region of three elements
+                    0x0, 0x68, 0xD8, 0x3D, 0xDE, 0x00, // 'h' and a surrogate pair
+                ],
+            )
+            .command_reply(4, [0xd, 0x1], &ARRAY_ID, &[0x0, 0x0, 0x0, 0x1])
+            .command_reply(
+                5,
+                [0xd, 0x2],
+                &get_values_out(0, 1),
+                &[b'C', 0x0, 0x0, 0x0, 0x1, 0xD8, 0x3D],
+            )
+            .command_reply(6, [0xd, 0x1], &ARRAY_ID, &[0x0, 0x0, 0x0, 0x1])
+            .command_reply(
+                7,
+                [0xd, 0x2],
+                &get_values_out(0, 1),
+                &[b'I', 0x0, 0x0, 0x0, 0x1, 0x0, 0x0, 0x0, 0x68],
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let text = client
+            .read_char_array(VariableLengthId { value: 9 })
+            .await
+            .unwrap();
+        assert_eq!(text, "h😀");
+        assert!(matches!(
+            client.read_char_array(VariableLengthId { value: 9 }).await,
+            Err(Error::InvalidArgument { .. })
+        ));
+        // The element type is reported rather than the array's own tag
+        assert!(matches!(
+            client.read_char_array(VariableLengthId { value: 9 }).await,
+            Err(Error::TypeMismatch {
+                expected: "char[]",
+                found: Tag::Int,
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_large_reply_is_decoded() {
+        let length = (BLOCKING_DECODE_THRESHOLD / 4 + 1) as i32;
+        let mut reply = vec![b'I'];
+        reply.extend_from_slice(&length.to_be_bytes());
+        for value in 0..length {
+            reply.extend_from_slice(&value.to_be_bytes());
+        }
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xd, 0x2], &get_values_out(0, length), &reply)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let values = client
+            .array_get_values(VariableLengthId { value: 9 }, 0, length)
+            .await
+            .unwrap()
+            .values;
+        assert_eq!(
+            values,
+            ArrayValues::Primitive(PrimitiveArray::Int((0..length).collect()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_large_reply_is_decoded_inline() {
+        let length = (BLOCKING_DECODE_THRESHOLD / 4 + 1) as i32;
+        let mut reply = vec![b'I'];
+        reply.extend_from_slice(&length.to_be_bytes());
+        for value in 0..length {
+            reply.extend_from_slice(&value.to_be_bytes());
+        }
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xd, 0x2], &get_values_out(0, length), &reply)
+            .build();
+        let client = JdwpClient::new_inline(mock_stream).await.unwrap();
+        let values = client
+            .array_get_values(VariableLengthId { value: 9 }, 0, length)
+            .await
+            .unwrap()
+            .values;
+        assert_eq!(
+            values,
+            ArrayValues::Primitive(PrimitiveArray::Int((0..length).collect()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_streamed_array_values() {
+        let length = (STREAMING_THRESHOLD / 4 + 10) as i32;
+        let mut reply = vec![b'I'];
+        reply.extend_from_slice(&length.to_be_bytes());
+        for value in 0..length {
+            reply.extend_from_slice(&value.to_be_bytes());
+        }
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xd, 0x2], &get_values_out(0, length), &reply)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let mut region = client
+            .array_get_values_streaming(VariableLengthId { value: 9 }, 0, length)
+            .await
+            .unwrap();
+        assert_eq!(region.tag(), Tag::Int);
+        assert_eq!(region.remaining(), length as usize);
+
+        let mut next = 0;
+        while let Some(values) = region.next_values(100_000).await.unwrap() {
+            let ArrayValues::Primitive(PrimitiveArray::Int(values)) = values else {
+                panic!("Expected int values, got {:?}", values);
+            };
+            assert!(values.len() <= 100_000);
+            for value in values {
+                assert_eq!(value, next);
+                next += 1;
+            }
+        }
+        assert_eq!(next, length);
+        assert_eq!(region.remaining(), 0);
+    }
+}
@@ -0,0 +1,48 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod array_type_tests {
+    use crate::common::{MockStreamBuilder, id, jdwp_string};
+    use jdwp_client::{JdwpClient, Tag};
+
+    #[tokio::test]
+    async fn test_new_array() {
+        let mut class_reply = vec![0x0, 0x0, 0x0, 0x1, 0x3]; // one class, array type tag
+        class_reply.extend_from_slice(&id(0x10));
+        class_reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x7]);
+
+        let mut new_instance_out = id(0x10).to_vec();
+        new_instance_out.extend_from_slice(&[0x0, 0x0, 0x0, 0x3]);
+        let mut new_instance_reply = vec![b'['];
+        new_instance_reply.extend_from_slice(&id(0x20));
+
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x2], &jdwp_string("[I"), &class_reply)
+            .command_reply(3, [0x4, 0x1], &new_instance_out, &new_instance_reply)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let array = client.new_array("int", 3).await.unwrap();
+        assert_eq!(array.tag, Tag::Array);
+        assert_eq!(array.object_id.value, 0x20);
+    }
+
+    #[tokio::test]
+    async fn test_new_array_not_loaded() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(
+                2,
+                [0x1, 0x2],
+                &jdwp_string("[Lcom/example/Missing;"),
+                &[0x0, 0x0, 0x0, 0x0],
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let result = client.new_array("com.example.Missing", 3).await;
+        assert!(matches!(
+            result,
+            Err(jdwp_client::Error::ClassNotFound { .. })
+        ));
+    }
+}
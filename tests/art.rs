@@ -0,0 +1,80 @@
+#![cfg(feature = "tokio")]
+
+#[cfg(test)]
+mod common;
+
+#[cfg(test)]
+mod art_tests {
+    use crate::common::{MockStreamBuilder, id, jdwp_string};
+    use jdwp_client::{DdmHello, JdwpClient, Quirks, TargetKind, VariableLengthId};
+
+    fn utf16(value: &str) -> Vec<u8> {
+        value.encode_utf16().flat_map(|c| c.to_be_bytes()).collect()
+    }
+
+    fn hello_reply() -> Vec<u8> {
+        let vm_identifier = "Android Runtime 2.1.0";
+        let app_name = "com.example.app";
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&1u32.to_be_bytes());
+        payload.extend_from_slice(&4321u32.to_be_bytes());
+        payload.extend_from_slice(&(vm_identifier.len() as u32).to_be_bytes());
+        payload.extend_from_slice(&(app_name.len() as u32).to_be_bytes());
+        payload.extend(utf16(vm_identifier));
+        payload.extend(utf16(app_name));
+        // The user id which newer versions append
+        payload.extend_from_slice(&0u32.to_be_bytes());
+
+        let mut chunk = b"HELO".to_vec();
+        chunk.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        chunk.extend(payload);
+        chunk
+    }
+
+    #[tokio::test]
+    async fn test_detect_art() {
+        let mut version = jdwp_string("Android Runtime 2.1.0");
+        version.extend_from_slice(&[0x0, 0x0, 0x0, 0x1, 0x0, 0x0, 0x0, 0x6]);
+        version.extend(jdwp_string("1.6.0"));
+        version.extend(jdwp_string("Dalvik"));
+
+        let hello = [b"HELO".as_slice(), &[0, 0, 0, 4], &[0, 0, 0, 1]].concat();
+        let thread = id(0x20);
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x1], &[], &version)
+            .command_reply(3, [0xc7, 0x1], &hello, &hello_reply())
+            .command_reply(4, [0xb, 0x1], &thread, &jdwp_string("main"))
+            .command_reply(5, [0xb, 0x1], &thread, &jdwp_string("binder:4321_1"))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let hello = client.adapt_to_target(TargetKind::Detect).await.unwrap();
+        assert_eq!(
+            hello,
+            Some(DdmHello {
+                protocol_version: 1,
+                pid: 4321,
+                vm_identifier: String::from("Android Runtime 2.1.0"),
+                app_name: String::from("com.example.app"),
+            })
+        );
+        assert!(client.quirks().contains(Quirks::MUTABLE_THREAD_NAMES));
+
+        // Renamed threads are looked up again
+        let thread_id = VariableLengthId { value: 0x20 };
+        assert_eq!(client.thread_name(thread_id).await.unwrap(), "main");
+        assert_eq!(
+            client.thread_name(thread_id).await.unwrap(),
+            "binder:4321_1"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_jvm_target_sends_nothing() {
+        let client = JdwpClient::new(MockStreamBuilder::default().build())
+            .await
+            .unwrap();
+        assert_eq!(client.adapt_to_target(TargetKind::Jvm).await.unwrap(), None);
+        assert_eq!(client.quirks(), Quirks::empty());
+    }
+}
@@ -0,0 +1,59 @@
+#![cfg(feature = "tokio")]
+
+#[cfg(test)]
+mod common;
+
+#[cfg(test)]
+mod audit_tests {
+    use crate::common::{MockStreamBuilder, id, jdwp_string};
+    use jdwp_client::{AuditLog, AuditRecord, Command, JdwpClient, VariableLengthId};
+    use std::sync::{Arc, Mutex};
+
+    const THREAD: VariableLengthId = VariableLengthId { value: 0x5 };
+
+    #[tokio::test]
+    async fn test_audit_log_records_mutating_commands() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xB, 0x1], &id(0x5), &jdwp_string("main"))
+            .command_reply(3, [0xB, 0x2], &id(0x5), &[])
+            .build();
+        let mut client = JdwpClient::new(mock_stream).await.unwrap();
+        let records = Arc::new(Mutex::new(Vec::<AuditRecord>::new()));
+        let sink = records.clone();
+        client.set_audit_log(Some(AuditLog::new(move |record| {
+            sink.lock().unwrap().push(record)
+        })));
+
+        client.thread_name(THREAD).await.unwrap();
+        client.thread_suspend(THREAD).await.unwrap();
+
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].command, Command::ThreadReferenceSuspend);
+        assert_eq!(records[0].packet_id, 3);
+        assert_eq!(records[0].arguments, id(0x5));
+    }
+
+    #[tokio::test]
+    async fn test_audit_log_redacts_arguments() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0xB], &jdwp_string("secret"), &id(0x9))
+            .build();
+        let mut client = JdwpClient::new(mock_stream).await.unwrap();
+        let records = Arc::new(Mutex::new(Vec::<AuditRecord>::new()));
+        let sink = records.clone();
+        let audit_log = AuditLog::new(move |record| sink.lock().unwrap().push(record))
+            .with_redactor(|record| {
+                if record.command == Command::VirtualMachineCreateString {
+                    record.arguments.clear();
+                }
+            });
+        client.set_audit_log(Some(audit_log));
+
+        client.vm_create_string("secret").await.unwrap();
+
+        let records = records.lock().unwrap();
+        assert_eq!(records[0].command, Command::VirtualMachineCreateString);
+        assert!(records[0].arguments.is_empty());
+    }
+}
@@ -0,0 +1,354 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod breakpoint_tests {
+    use crate::common::{
+        MockStreamBuilder, command_packet, event_packet, id, jdwp_string, reply_packet,
+        thread_death_event,
+    };
+    use jdwp_client::{Error, JdwpClient, SuspendPolicy};
+
+    fn class_prepare_out() -> Vec<u8> {
+        let mut out = vec![0x8, 0x1, 0x0, 0x0, 0x0, 0x1, 0x5];
+        out.extend_from_slice(&jdwp_string("com.example.Main"));
+        out
+    }
+
+    fn class_unload_out() -> Vec<u8> {
+        let mut out = vec![0x9, 0x0, 0x0, 0x0, 0x0, 0x1, 0x5];
+        out.extend_from_slice(&jdwp_string("com.example.Main"));
+        out
+    }
+
+    fn classes_reply(class_ids: &[u64]) -> Vec<u8> {
+        let mut reply = (class_ids.len() as u32).to_be_bytes().to_vec();
+        for class_id in class_ids {
+            reply.push(0x1);
+            reply.extend_from_slice(&id(*class_id));
+            reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x7]);
+        }
+        reply
+    }
+
+    fn methods_reply() -> Vec<u8> {
+        let mut reply = vec![0x0, 0x0, 0x0, 0x1];
+        reply.extend_from_slice(&id(0x1));
+        reply.extend_from_slice(&jdwp_string("main"));
+        reply.extend_from_slice(&jdwp_string("([Ljava/lang/String;)V"));
+        reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x9]);
+        reply
+    }
+
+    fn line_table_out(class_id: u64) -> Vec<u8> {
+        let mut out = id(class_id).to_vec();
+        out.extend_from_slice(&id(0x1));
+        out
+    }
+
+    fn line_table_reply() -> Vec<u8> {
+        let mut reply = 0u64.to_be_bytes().to_vec();
+        reply.extend_from_slice(&10u64.to_be_bytes());
+        reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x3]);
+        for (index, line) in [(0u64, 5u32), (4, 6), (7, 6)] {
+            reply.extend_from_slice(&index.to_be_bytes());
+            reply.extend_from_slice(&line.to_be_bytes());
+        }
+        reply
+    }
+
+    fn location(class_id: u64) -> Vec<u8> {
+        let mut bytes = vec![0x1];
+        bytes.extend_from_slice(&id(class_id));
+        bytes.extend_from_slice(&id(0x1));
+        bytes.extend_from_slice(&id(4));
+        bytes
+    }
+
+    fn breakpoint_out(class_id: u64) -> Vec<u8> {
+        let mut out = vec![0x2, 0x1, 0x0, 0x0, 0x0, 0x1, 0x7];
+        out.extend_from_slice(&location(class_id));
+        out
+    }
+
+    fn breakpoint_event(request_id: u8, class_id: u64) -> Vec<u8> {
+        let mut event = vec![0x2, 0x0, 0x0, 0x0, request_id];
+        event.extend_from_slice(&id(0x5));
+        event.extend_from_slice(&location(class_id));
+        event
+    }
+
+    fn class_prepare_event(class_id: u64) -> Vec<u8> {
+        let mut event = vec![0x8, 0x0, 0x0, 0x0, 0x1];
+        event.extend_from_slice(&id(0x5));
+        event.push(0x1);
+        event.extend_from_slice(&id(class_id));
+        event.extend_from_slice(&jdwp_string("Lcom/example/Main;"));
+        event.extend_from_slice(&[0x0, 0x0, 0x0, 0x3]);
+        event
+    }
+
+    fn class_unload_event() -> Vec<u8> {
+        let mut event = vec![0x9, 0x0, 0x0, 0x0, 0x8];
+        event.extend_from_slice(&jdwp_string("Lcom/example/Main;"));
+        event
+    }
+
+    /// Sets a breakpoint in the loaded class 0x10, whose reply is followed by `events`
+    fn loaded_breakpoint_session(events: &[u8]) -> MockStreamBuilder {
+        let mut breakpoint_reply = reply_packet(7, 0, &[0x0, 0x0, 0x0, 0x2]);
+        breakpoint_reply.extend_from_slice(events);
+        MockStreamBuilder::default()
+            .command_reply(2, [0xF, 0x1], &class_prepare_out(), &[0x0, 0x0, 0x0, 0x1])
+            .command_reply(3, [0xF, 0x1], &class_unload_out(), &[0x0, 0x0, 0x0, 0x8])
+            .command_reply(
+                4,
+                [0x1, 0x2],
+                &jdwp_string("Lcom/example/Main;"),
+                &classes_reply(&[0x10]),
+            )
+            .command_reply(5, [0x2, 0x5], &id(0x10), &methods_reply())
+            .command_reply(6, [0x6, 0x1], &line_table_out(0x10), &line_table_reply())
+            .response_bytes(
+                &command_packet(7, [0xF, 0x1], &breakpoint_out(0x10)),
+                &breakpoint_reply,
+            )
+    }
+
+    #[tokio::test]
+    async fn test_line_breakpoint_in_all_loaded_classes() {
+        let mut last_reply = reply_packet(10, 0, &[0x0, 0x0, 0x0, 0x3]);
+        last_reply.extend_from_slice(&event_packet(100, 0x1, &[breakpoint_event(3, 0x11)]));
+        last_reply.extend_from_slice(&event_packet(101, 0x1, &[breakpoint_event(2, 0x10)]));
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xF, 0x1], &class_prepare_out(), &[0x0, 0x0, 0x0, 0x1])
+            .command_reply(3, [0xF, 0x1], &class_unload_out(), &[0x0, 0x0, 0x0, 0x8])
+            .command_reply(
+                4,
+                [0x1, 0x2],
+                &jdwp_string("Lcom/example/Main;"),
+                &classes_reply(&[0x10, 0x11]),
+            )
+            .command_reply(5, [0x2, 0x5], &id(0x10), &methods_reply())
+            .command_reply(6, [0x6, 0x1], &line_table_out(0x10), &line_table_reply())
+            .command_reply(7, [0xF, 0x1], &breakpoint_out(0x10), &[0x0, 0x0, 0x0, 0x2])
+            .command_reply(8, [0x2, 0x5], &id(0x11), &methods_reply())
+            .command_reply(9, [0x6, 0x1], &line_table_out(0x11), &line_table_reply())
+            .response_bytes(
+                &command_packet(10, [0xF, 0x1], &breakpoint_out(0x11)),
+                &last_reply,
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let mut breakpoint = client
+            .set_line_breakpoint("com.example.Main", 6, SuspendPolicy::EventThread)
+            .await
+            .unwrap();
+        assert_eq!(breakpoint.request_ids(), vec![2, 3]);
+
+        let classes: Vec<_> = [breakpoint.next_hit().await, breakpoint.next_hit().await]
+            .into_iter()
+            .map(|hit| hit.unwrap().class().unwrap().value)
+            .collect();
+        assert_eq!(classes, vec![0x11, 0x10]);
+    }
+
+    #[tokio::test]
+    async fn test_line_without_code() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xF, 0x1], &class_prepare_out(), &[0x0, 0x0, 0x0, 0x1])
+            .command_reply(3, [0xF, 0x1], &class_unload_out(), &[0x0, 0x0, 0x0, 0x8])
+            .command_reply(
+                4,
+                [0x1, 0x2],
+                &jdwp_string("Lcom/example/Main;"),
+                &classes_reply(&[0x10]),
+            )
+            .command_reply(5, [0x2, 0x5], &id(0x10), &methods_reply())
+            .command_reply(6, [0x6, 0x1], &line_table_out(0x10), &line_table_reply())
+            .command_reply(7, [0xF, 0x2], &[0x8, 0x0, 0x0, 0x0, 0x1], &[])
+            .command_reply(8, [0xF, 0x2], &[0x9, 0x0, 0x0, 0x0, 0x8], &[])
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let result = client
+            .set_line_breakpoint("com.example.Main", 42, SuspendPolicy::EventThread)
+            .await;
+        assert!(matches!(result, Err(Error::InvalidArgument { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_failed_line_breakpoint_clears_its_requests() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xF, 0x1], &class_prepare_out(), &[0x0, 0x0, 0x0, 0x1])
+            .command_reply(3, [0xF, 0x1], &class_unload_out(), &[0x0, 0x0, 0x0, 0x8])
+            .command_reply(
+                4,
+                [0x1, 0x2],
+                &jdwp_string("Lcom/example/Main;"),
+                &classes_reply(&[0x10, 0x11]),
+            )
+            .command_reply(5, [0x2, 0x5], &id(0x10), &methods_reply())
+            .command_reply(6, [0x6, 0x1], &line_table_out(0x10), &line_table_reply())
+            .command_reply(7, [0xF, 0x1], &breakpoint_out(0x10), &[0x0, 0x0, 0x0, 0x2])
+            .response_bytes(
+                &command_packet(8, [0x2, 0x5], &id(0x11)),
+                &reply_packet(8, 21, &[]),
+            )
+            .command_reply(9, [0xF, 0x2], &[0x8, 0x0, 0x0, 0x0, 0x1], &[])
+            .command_reply(10, [0xF, 0x2], &[0x9, 0x0, 0x0, 0x0, 0x8], &[])
+            .command_reply(11, [0xF, 0x2], &[0x2, 0x0, 0x0, 0x0, 0x2], &[])
+            .command_reply(12, [0x1, 0x9], &[], &[])
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let result = client
+            .set_line_breakpoint("com.example.Main", 6, SuspendPolicy::EventThread)
+            .await;
+        assert!(matches!(result, Err(Error::JdwpError(_))));
+        // Answered only once the three requests were cleared before it
+        client.vm_resume().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dropped_line_breakpoint_clears_its_requests() {
+        let mut last_clear_reply = reply_packet(10, 0, &[]);
+        last_clear_reply.extend_from_slice(&event_packet(100, 0x0, &[thread_death_event(50, 0x5)]));
+        let mock_stream = loaded_breakpoint_session(&[])
+            .command_reply(8, [0xF, 0x2], &[0x8, 0x0, 0x0, 0x0, 0x1], &[])
+            .command_reply(9, [0xF, 0x2], &[0x9, 0x0, 0x0, 0x0, 0x8], &[])
+            .response_bytes(
+                &command_packet(10, [0xF, 0x2], &[0x2, 0x0, 0x0, 0x0, 0x2]),
+                &last_clear_reply,
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let breakpoint = client
+            .set_line_breakpoint("com.example.Main", 6, SuspendPolicy::EventThread)
+            .await
+            .unwrap();
+        drop(breakpoint);
+        assert!(client.next_event().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_line_breakpoint_in_class_loaded_later() {
+        let mut classes = reply_packet(4, 0, &classes_reply(&[]));
+        classes.extend_from_slice(&event_packet(100, 0x1, &[class_prepare_event(0x10)]));
+
+        let mut resume_reply = reply_packet(8, 0, &[]);
+        resume_reply.extend_from_slice(&event_packet(101, 0x1, &[breakpoint_event(2, 0x10)]));
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xF, 0x1], &class_prepare_out(), &[0x0, 0x0, 0x0, 0x1])
+            .command_reply(3, [0xF, 0x1], &class_unload_out(), &[0x0, 0x0, 0x0, 0x8])
+            .response_bytes(
+                &command_packet(4, [0x1, 0x2], &jdwp_string("Lcom/example/Main;")),
+                &classes,
+            )
+            .command_reply(5, [0x2, 0x5], &id(0x10), &methods_reply())
+            .command_reply(6, [0x6, 0x1], &line_table_out(0x10), &line_table_reply())
+            .command_reply(7, [0xF, 0x1], &breakpoint_out(0x10), &[0x0, 0x0, 0x0, 0x2])
+            .response_bytes(&command_packet(8, [0xB, 0x3], &id(0x5)), &resume_reply)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let mut breakpoint = client
+            .set_line_breakpoint("com.example.Main", 6, SuspendPolicy::EventThread)
+            .await
+            .unwrap();
+        let hit = breakpoint.next_hit().await.unwrap();
+        assert_eq!(hit.class().unwrap().value, 0x10);
+        assert_eq!(breakpoint.request_ids(), vec![2]);
+    }
+
+    #[tokio::test]
+    async fn test_class_unload_clears_requests() {
+        let mock_stream =
+            loaded_breakpoint_session(&event_packet(100, 0x0, &[class_unload_event()]))
+                .command_reply(
+                    8,
+                    [0x1, 0x2],
+                    &jdwp_string("Lcom/example/Main;"),
+                    &classes_reply(&[]),
+                )
+                .command_reply(9, [0xF, 0x2], &[0x2, 0x0, 0x0, 0x0, 0x2], &[])
+                .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let breakpoint = client
+            .set_line_breakpoint("com.example.Main", 6, SuspendPolicy::EventThread)
+            .await
+            .unwrap();
+        while !breakpoint.is_pending_reload() {
+            tokio::task::yield_now().await;
+        }
+        assert!(breakpoint.request_ids().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_line_breakpoint_rearmed_after_reload() {
+        let mut clear_reply = reply_packet(9, 0, &[]);
+        clear_reply.extend_from_slice(&event_packet(101, 0x1, &[class_prepare_event(0x12)]));
+        let mock_stream =
+            loaded_breakpoint_session(&event_packet(100, 0x0, &[class_unload_event()]))
+                .command_reply(
+                    8,
+                    [0x1, 0x2],
+                    &jdwp_string("Lcom/example/Main;"),
+                    &classes_reply(&[]),
+                )
+                .response_bytes(
+                    &command_packet(9, [0xF, 0x2], &[0x2, 0x0, 0x0, 0x0, 0x2]),
+                    &clear_reply,
+                )
+                .command_reply(10, [0x2, 0x5], &id(0x12), &methods_reply())
+                .command_reply(11, [0x6, 0x1], &line_table_out(0x12), &line_table_reply())
+                .command_reply(12, [0xF, 0x1], &breakpoint_out(0x12), &[0x0, 0x0, 0x0, 0x4])
+                .command_reply(13, [0xB, 0x3], &id(0x5), &[])
+                .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let breakpoint = client
+            .set_line_breakpoint("com.example.Main", 6, SuspendPolicy::EventThread)
+            .await
+            .unwrap();
+        while breakpoint.request_ids() != vec![4] {
+            tokio::task::yield_now().await;
+        }
+        assert!(!breakpoint.is_pending_reload());
+    }
+
+    #[tokio::test]
+    async fn test_stratum_line_breakpoint() {
+        let smap = "SMAP\nMain.kt\nKotlin\n*S Kotlin\n*F\n+ 1 Main.kt\ncom/example/Main.kt\n*L\n1#1,2:1\n3#1:6\n*E\n";
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xF, 0x1], &class_prepare_out(), &[0x0, 0x0, 0x0, 0x1])
+            .command_reply(3, [0xF, 0x1], &class_unload_out(), &[0x0, 0x0, 0x0, 0x8])
+            .command_reply(
+                4,
+                [0x1, 0x2],
+                &jdwp_string("Lcom/example/Main;"),
+                &classes_reply(&[0x10]),
+            )
+            .command_reply(5, [0x2, 0xC], &id(0x10), &jdwp_string(smap))
+            .command_reply(6, [0x2, 0x5], &id(0x10), &methods_reply())
+            .command_reply(7, [0x6, 0x1], &line_table_out(0x10), &line_table_reply())
+            .command_reply(8, [0xF, 0x1], &breakpoint_out(0x10), &[0x0, 0x0, 0x0, 0x2])
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let breakpoint = client
+            .set_stratum_line_breakpoint(
+                "com.example.Main",
+                "Kotlin",
+                3,
+                SuspendPolicy::EventThread,
+            )
+            .await
+            .unwrap();
+        assert_eq!(breakpoint.request_ids(), vec![2]);
+    }
+}
@@ -0,0 +1,171 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod class_cache_tests {
+    use crate::common::{
+        MockStreamBuilder, command_packet, event_packet, id, jdwp_string, reply_packet,
+    };
+    use jdwp_client::{ClassStatus, JdwpClient, TypeTag};
+
+    const CLASS_PREPARE_OUT: [u8; 6] = [0x8, 0x0, 0x0, 0x0, 0x0, 0x0];
+    const CLASS_UNLOAD_OUT: [u8; 6] = [0x9, 0x0, 0x0, 0x0, 0x0, 0x0];
+
+    fn all_classes_reply(classes: &[(u64, &str)]) -> Vec<u8> {
+        let mut reply = (classes.len() as u32).to_be_bytes().to_vec();
+        for (class_id, signature) in classes {
+            reply.push(0x1);
+            reply.extend_from_slice(&id(*class_id));
+            reply.extend_from_slice(&jdwp_string(signature));
+            reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x7]);
+        }
+        reply
+    }
+
+    fn class_prepare_event(class_id: u64, signature: &str) -> Vec<u8> {
+        let mut event = vec![0x8, 0x0, 0x0, 0x0, 0x4];
+        event.extend_from_slice(&id(0x5));
+        event.push(0x1);
+        event.extend_from_slice(&id(class_id));
+        event.extend_from_slice(&jdwp_string(signature));
+        event.extend_from_slice(&[0x0, 0x0, 0x0, 0x3]);
+        event
+    }
+
+    fn class_unload_event(signature: &str) -> Vec<u8> {
+        let mut event = vec![0x9, 0x0, 0x0, 0x0, 0x5];
+        event.extend_from_slice(&jdwp_string(signature));
+        event
+    }
+
+    /// Tracks classes with request ids 4 and 5, preparing `com.acme.MyService` (0x12) before the
+    /// AllClasses reply arrives
+    fn tracking_session() -> MockStreamBuilder {
+        let mut request_reply = reply_packet(2, 0, &[0x0, 0x0, 0x0, 0x4]);
+        request_reply.extend_from_slice(&event_packet(
+            100,
+            0x0,
+            &[class_prepare_event(0x12, "Lcom/acme/MyService;")],
+        ));
+        MockStreamBuilder::default()
+            .response_bytes(
+                &command_packet(2, [0xF, 0x1], &CLASS_PREPARE_OUT),
+                &request_reply,
+            )
+            .command_reply(3, [0xF, 0x1], &CLASS_UNLOAD_OUT, &[0x0, 0x0, 0x0, 0x5])
+            .command_reply(
+                4,
+                [0x1, 0x3],
+                &[],
+                &all_classes_reply(&[
+                    (0x10, "Lcom/acme/Main;"),
+                    (0x11, "Lcom/acme/Registry$MyService;"),
+                    (0x12, "Lcom/acme/MyService;"),
+                    (0x13, "Lorg/other/MyService;"),
+                ]),
+            )
+    }
+
+    #[tokio::test]
+    async fn test_find_classes_by_simple_name() {
+        let client = JdwpClient::new(tracking_session().build()).await.unwrap();
+
+        let found = client.find_classes("MyService").await.unwrap();
+        let names: Vec<_> = found.iter().map(|class| class.type_name()).collect();
+        assert_eq!(
+            names,
+            vec![
+                "com.acme.MyService",
+                "com.acme.Registry$MyService",
+                "org.other.MyService"
+            ]
+        );
+        assert_eq!(found[0].ref_type_tag, TypeTag::Class);
+        assert_eq!(
+            found[0].status,
+            ClassStatus::VERIFIED | ClassStatus::PREPARED | ClassStatus::INITIALIZED
+        );
+
+        // Answered from the cache without sending anything
+        assert!(client.find_classes("Missing").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_find_classes_updated_by_class_prepare() {
+        let mut last_reply = reply_packet(4, 0, &all_classes_reply(&[(0x10, "Lcom/acme/Main;")]));
+        last_reply.extend_from_slice(&event_packet(
+            101,
+            0x0,
+            &[class_prepare_event(0x14, "Lcom/acme/Late;")],
+        ));
+        let mut request_reply = reply_packet(2, 0, &[0x0, 0x0, 0x0, 0x4]);
+        request_reply.extend_from_slice(&event_packet(
+            100,
+            0x0,
+            &[class_prepare_event(0x12, "Lcom/acme/MyService;")],
+        ));
+        let mock_stream = MockStreamBuilder::default()
+            .response_bytes(
+                &command_packet(2, [0xF, 0x1], &CLASS_PREPARE_OUT),
+                &request_reply,
+            )
+            .command_reply(3, [0xF, 0x1], &CLASS_UNLOAD_OUT, &[0x0, 0x0, 0x0, 0x5])
+            .response_bytes(&command_packet(4, [0x1, 0x3], &[]), &last_reply)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let found = client.find_classes_in_package("com.acme.").await.unwrap();
+        assert!(found.iter().any(|class| class.type_id.value == 0x12));
+
+        // Both events also reach the event queue, the late one after the class list
+        client.next_event().await.unwrap();
+        client.next_event().await.unwrap();
+        let found = client.find_classes_in_package("com.acme.").await.unwrap();
+        let ids: Vec<_> = found.iter().map(|class| class.type_id.value).collect();
+        assert_eq!(ids, vec![0x14, 0x10, 0x12]);
+        assert!(
+            client
+                .find_classes_in_package("org.")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_classes_snapshot() {
+        let mut last_reply = reply_packet(
+            4,
+            0,
+            &all_classes_reply(&[(0x10, "Lcom/acme/Main;"), (0x11, "Lcom/acme/Old;")]),
+        );
+        last_reply.extend_from_slice(&event_packet(
+            100,
+            0x0,
+            &[
+                class_unload_event("Lcom/acme/Old;"),
+                class_prepare_event(0x12, "Lcom/acme/New;"),
+            ],
+        ));
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xF, 0x1], &CLASS_PREPARE_OUT, &[0x0, 0x0, 0x0, 0x4])
+            .command_reply(3, [0xF, 0x1], &CLASS_UNLOAD_OUT, &[0x0, 0x0, 0x0, 0x5])
+            .response_bytes(&command_packet(4, [0x1, 0x3], &[]), &last_reply)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        assert!(client.classes().is_empty());
+
+        client.enable_class_tracking().await.unwrap();
+        client.next_event().await.unwrap();
+        let signatures: Vec<_> = client
+            .classes()
+            .into_iter()
+            .map(|class| class.signature)
+            .collect();
+        assert_eq!(signatures, vec!["Lcom/acme/Main;", "Lcom/acme/New;"]);
+
+        // Enabled only once
+        client.enable_class_tracking().await.unwrap();
+    }
+}
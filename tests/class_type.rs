@@ -0,0 +1,120 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod class_type_tests {
+    use crate::common::{MockStreamBuilder, id, jdwp_string};
+    use jdwp_client::{Command, Error, JdwpClient, JdwpValue, Tag, VariableLengthId};
+
+    fn method(method_id: u64, name: &str, signature: &str) -> Vec<u8> {
+        let mut bytes = id(method_id).to_vec();
+        bytes.extend_from_slice(&jdwp_string(name));
+        bytes.extend_from_slice(&jdwp_string(signature));
+        bytes.extend_from_slice(&[0x0, 0x0, 0x0, 0x1]); // public
+        bytes
+    }
+
+    #[tokio::test]
+    async fn test_new_object() {
+        let mut class_reply = vec![0x0, 0x0, 0x0, 0x1, 0x1]; // one class, class type tag
+        class_reply.extend_from_slice(&id(0x10));
+        class_reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x7]);
+
+        let mut methods_reply = vec![0x0, 0x0, 0x0, 0x2];
+        methods_reply.extend_from_slice(&method(0x1, "<init>", "()V"));
+        methods_reply.extend_from_slice(&method(0x2, "<init>", "(I)V"));
+
+        let mut new_instance_out = id(0x10).to_vec(); // class
+        new_instance_out.extend_from_slice(&id(0x1)); // thread
+        new_instance_out.extend_from_slice(&id(0x2)); // (I)V constructor
+        new_instance_out.extend_from_slice(&[0x0, 0x0, 0x0, 0x1, b'I', 0x0, 0x0, 0x0, 0x10]);
+        new_instance_out.extend_from_slice(&[0x0, 0x0, 0x0, 0x0]); // options
+        let mut new_instance_reply = vec![b'L'];
+        new_instance_reply.extend_from_slice(&id(0x30));
+        new_instance_reply.push(b'L');
+        new_instance_reply.extend_from_slice(&id(0x0));
+
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x8], &[], &[])
+            .command_reply(
+                3,
+                [0x1, 0x2],
+                &jdwp_string("Ljava/lang/StringBuilder;"),
+                &class_reply,
+            )
+            .command_reply(4, [0x2, 0x5], &id(0x10), &methods_reply)
+            .command_reply(5, [0x3, 0x4], &new_instance_out, &new_instance_reply)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.vm_suspend().await.unwrap();
+        let object = client
+            .new_object(
+                VariableLengthId { value: 1 },
+                "java.lang.StringBuilder",
+                &[JdwpValue::Int(16)],
+            )
+            .await
+            .unwrap();
+        assert_eq!(object.tag, Tag::Object);
+        assert_eq!(object.object_id.value, 0x30);
+    }
+
+    #[tokio::test]
+    async fn test_new_object_requires_suspended_thread() {
+        let client = JdwpClient::new(MockStreamBuilder::default().build())
+            .await
+            .unwrap();
+        let result = client
+            .new_object(VariableLengthId { value: 1 }, "java.lang.Object", &[])
+            .await;
+        assert!(matches!(
+            result,
+            Err(Error::ThreadNotSuspended {
+                thread: VariableLengthId { value: 1 },
+                required_by: Command::ClassTypeNewInstance,
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_call_static() {
+        let mut threads_reply = vec![0x0, 0x0, 0x0, 0x1];
+        threads_reply.extend_from_slice(&id(0x1));
+        let mut class_reply = vec![0x0, 0x0, 0x0, 0x1, 0x1];
+        class_reply.extend_from_slice(&id(0x10));
+        class_reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x7]);
+
+        let mut methods_reply = vec![0x0, 0x0, 0x0, 0x1];
+        methods_reply.extend_from_slice(&method(0x2, "square", "(I)I"));
+
+        let mut invoke_out = id(0x10).to_vec(); // class
+        invoke_out.extend_from_slice(&id(0x1)); // thread
+        invoke_out.extend_from_slice(&id(0x2)); // square
+        invoke_out.extend_from_slice(&[0x0, 0x0, 0x0, 0x1, b'I', 0x0, 0x0, 0x0, 0x7]);
+        invoke_out.extend_from_slice(&[0x0, 0x0, 0x0, 0x0]); // options
+        let mut invoke_reply = vec![b'I', 0x0, 0x0, 0x0, 0x31, b'L'];
+        invoke_reply.extend_from_slice(&id(0x0));
+
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x8], &[], &[])
+            .command_reply(3, [0x1, 0x4], &[], &threads_reply)
+            .command_reply(
+                4,
+                [0x1, 0x2],
+                &jdwp_string("Lcom/example/Debug;"),
+                &class_reply,
+            )
+            .command_reply(5, [0x2, 0x5], &id(0x10), &methods_reply)
+            .command_reply(6, [0x3, 0x3], &invoke_out, &invoke_reply)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.vm_suspend().await.unwrap();
+
+        let square: i32 = client
+            .call_static("com.example.Debug", "square", (7,))
+            .await
+            .unwrap();
+        assert_eq!(square, 49);
+    }
+}
@@ -0,0 +1,370 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod client_tests {
+    use crate::common::{
+        MockStreamBuilder, command_packet, event_packet, id, jdwp_string, reply_packet,
+        thread_start_event,
+    };
+    use jdwp_client::{
+        Command, Conformance, DEFAULT_MAX_STRING_LENGTH, Error, JdwpClient, JdwpErrorCode,
+        ReplyParseError, RetryPolicy, TypeTag, UnsolicitedReplyPolicy, VariableLengthId,
+    };
+    use std::time::Duration;
+
+    const THREAD_NOT_SUSPENDED: u16 = 13;
+
+    #[tokio::test]
+    async fn test_error_code_is_reported() {
+        let mock_stream = MockStreamBuilder::default()
+            .response_bytes(
+                &command_packet(2, [0x1, 0x8], &[]),
+                &reply_packet(2, THREAD_NOT_SUSPENDED, &[]),
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let result = client.vm_suspend().await;
+        assert!(matches!(
+            result,
+            Err(Error::JdwpError(JdwpErrorCode::ThreadNotSuspended))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_error_code_is_reported() {
+        let mock_stream = MockStreamBuilder::default()
+            .response_bytes(
+                &command_packet(2, [0x1, 0x8], &[]),
+                &reply_packet(2, 9999, &[]),
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let result = client.vm_suspend().await;
+        assert!(matches!(
+            result,
+            Err(Error::UnknownJdwpError { code: 9999 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_transient_error_is_retried() {
+        let mock_stream = MockStreamBuilder::default()
+            .response_bytes(
+                &command_packet(2, [0x1, 0x8], &[]),
+                &reply_packet(2, THREAD_NOT_SUSPENDED, &[]),
+            )
+            .response_bytes(
+                &command_packet(3, [0x1, 0x8], &[]),
+                &reply_packet(3, THREAD_NOT_SUSPENDED, &[]),
+            )
+            .command_reply(4, [0x1, 0x8], &[], &[])
+            .build();
+        let mut client = JdwpClient::new(mock_stream).await.unwrap();
+        client.set_retry_policy(RetryPolicy::transient());
+        client.vm_suspend().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_retries_are_bounded() {
+        let mut builder = MockStreamBuilder::default();
+        for id in 2..6 {
+            builder = builder.response_bytes(
+                &command_packet(id, [0x1, 0x8], &[]),
+                &reply_packet(id, THREAD_NOT_SUSPENDED, &[]),
+            );
+        }
+        let mut client = JdwpClient::new(builder.build()).await.unwrap();
+        client.set_retry_policy(RetryPolicy::transient());
+        let result = client.vm_suspend().await;
+        assert!(matches!(
+            result,
+            Err(Error::JdwpError(JdwpErrorCode::ThreadNotSuspended))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_request_is_cleaned_up() {
+        // No reply is ever sent for the suspend command
+        let mock_stream = MockStreamBuilder::default().build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let result = tokio::time::timeout(Duration::from_millis(20), client.vm_suspend()).await;
+        assert!(result.is_err(), "Request should still be in flight");
+        assert_eq!(client.pending_request_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_late_reply_is_discarded() {
+        // The reply to the dropped suspend (id 2) only arrives together with the resume reply
+        let mut late_replies = reply_packet(2, 0, &[]);
+        late_replies.extend_from_slice(&reply_packet(3, 0, &[]));
+        let mock_stream = MockStreamBuilder::default()
+            .response_bytes(&command_packet(3, [0x1, 0x9], &[]), &late_replies)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let result = tokio::time::timeout(Duration::from_millis(20), client.vm_suspend()).await;
+        assert!(result.is_err(), "Request should still be in flight");
+
+        client.vm_resume().await.unwrap();
+        assert_eq!(client.pending_request_count(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_late_suspend_reply_is_applied() {
+        // The reply to the timed out suspend (id 2) only arrives together with the next reply
+        let mut late_replies = reply_packet(2, 0, &[]);
+        late_replies.extend_from_slice(&reply_packet(3, 0, &[]));
+        let mock_stream = MockStreamBuilder::default()
+            .response_bytes(&command_packet(3, [0x1, 0xF], &[]), &late_replies)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let result = client.vm_suspend().await;
+        assert!(
+            matches!(result, Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::TimedOut)
+        );
+        assert!(!client.is_vm_suspended());
+
+        client.vm_hold_events().await.unwrap();
+        assert!(client.is_vm_suspended());
+    }
+
+    #[tokio::test]
+    async fn test_cloned_clients_share_connection() {
+        // Packet ids are allocated in whichever order the tasks run
+        let mut builder = MockStreamBuilder::default();
+        for id in 2..4 {
+            builder = builder
+                .command_reply(id, [0x1, 0x8], &[], &[])
+                .command_reply(id, [0x1, 0x9], &[], &[]);
+        }
+        let client = JdwpClient::new(builder.build()).await.unwrap();
+
+        let suspender = client.clone();
+        let suspend = tokio::spawn(async move { suspender.vm_suspend().await });
+        let resumer = client.clone();
+        let resume = tokio::spawn(async move { resumer.vm_resume().await });
+
+        suspend.await.unwrap().unwrap();
+        resume.await.unwrap().unwrap();
+        assert_eq!(client.pending_request_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_id_sizes_are_rejected() {
+        let mut sizes = Vec::new();
+        for size in [8, 8, 0, 8, 8] {
+            sizes.extend_from_slice(&i32::to_be_bytes(size));
+        }
+        let mock_stream = MockStreamBuilder::new()
+            .with_jdwp_handshake()
+            .response_bytes(
+                &command_packet(1, [0x1, 0x7], &[]),
+                &reply_packet(1, 0, &sizes),
+            )
+            .build();
+        let result = JdwpClient::new(mock_stream).await;
+        assert!(matches!(
+            result,
+            Err(Error::InvalidIdSize {
+                name: "objectID",
+                size: 0
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_command_stats() {
+        let mock_stream = MockStreamBuilder::default()
+            .response_bytes(
+                &command_packet(2, [0x1, 0x8], &[]),
+                &reply_packet(2, 0, &[]),
+            )
+            .response_bytes(
+                &command_packet(3, [0x1, 0x8], &[]),
+                &reply_packet(3, THREAD_NOT_SUSPENDED, &[]),
+            )
+            .response_bytes(
+                &command_packet(4, [0x1, 0x1], &[]),
+                &reply_packet(4, 0, &[0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1]),
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.vm_suspend().await.unwrap();
+        client.vm_suspend().await.unwrap_err();
+        let _ = client.vm_get_version().await;
+
+        let stats = client.command_stats();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].command, Command::VirtualMachineSuspend);
+        assert_eq!(stats[0].count, 2);
+        assert_eq!(stats[0].errors, 1);
+        assert_eq!(stats[0].bytes_sent, 22);
+        assert_eq!(stats[0].bytes_received, 22);
+        assert_eq!(stats[1].command, Command::VirtualMachineVersion);
+        assert_eq!(stats[1].bytes_received, 19);
+
+        let report = client.slow_command_report(1);
+        assert_eq!(report.commands.len(), 1);
+        assert_eq!(report.to_string().lines().count(), 2);
+
+        client.reset_command_stats();
+        assert!(client.command_stats().is_empty());
+    }
+
+    /// ClassesBySignature reply with an unknown type tag and a byte left over
+    fn deviating_classes_reply() -> Vec<u8> {
+        let mut reply = vec![0x0, 0x0, 0x0, 0x1, 0x7];
+        reply.extend_from_slice(&id(0x1));
+        reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x7, 0xff]);
+        reply
+    }
+
+    #[tokio::test]
+    async fn test_conformance() {
+        let signature = jdwp_string("Lhello/HelloWorld;");
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x2], &signature, &deviating_classes_reply())
+            .command_reply(3, [0x1, 0x2], &signature, &deviating_classes_reply())
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        assert_eq!(client.conformance(), Conformance::Lenient);
+        let classes = client
+            .vm_get_classes_by_signature("Lhello/HelloWorld;")
+            .await
+            .unwrap()
+            .classes;
+        assert_eq!(classes[0].ref_type_tag, TypeTag::Unknown(0x7));
+
+        client.set_conformance(Conformance::Strict);
+        assert_eq!(client.conformance(), Conformance::Strict);
+        let result = client
+            .vm_get_classes_by_signature("Lhello/HelloWorld;")
+            .await;
+        assert!(matches!(result, Err(Error::ParsingError { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_trailing_bytes() {
+        // A version reply with one more field than the specification defines
+        let mut reply = jdwp_string("JDWP");
+        reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x15, 0x0, 0x0, 0x0, 0x0]);
+        reply.extend_from_slice(&jdwp_string("21.0.8"));
+        reply.extend_from_slice(&jdwp_string("OpenJDK 64-Bit Server VM"));
+        reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x1]);
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x1], &[], &reply)
+            .command_reply(3, [0x1, 0x1], &[], &reply)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let version = client.vm_get_version().await.unwrap();
+        assert_eq!(version.vm_name.string, "OpenJDK 64-Bit Server VM");
+
+        client.set_conformance(Conformance::Strict);
+        let result = client.vm_get_version().await;
+        assert!(matches!(
+            result,
+            Err(Error::ReplyParseError(ReplyParseError::TrailingBytes {
+                expected,
+                actual
+            })) if expected == reply.len() && actual == reply.len() - 4
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_max_string_length() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xB, 0x1], &id(0x5), &jdwp_string("main"))
+            .command_reply(3, [0xB, 0x1], &id(0x5), &jdwp_string("main"))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        assert_eq!(client.max_string_length(), DEFAULT_MAX_STRING_LENGTH);
+        let thread = VariableLengthId { value: 0x5 };
+        assert_eq!(
+            client.thread_get_name(thread).await.unwrap().thread_name,
+            "main"
+        );
+
+        client.set_max_string_length(3);
+        let result = client.thread_get_name(thread).await;
+        assert!(matches!(result, Err(Error::ParsingError { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_unsolicited_replies_are_reported() {
+        let mut first_replies = reply_packet(77, 0, &[]);
+        first_replies.extend_from_slice(&reply_packet(2, 0, &[]));
+        // The late reply to the dropped suspend (id 3) is expected, the second reply to the
+        // resume (id 4) isn't
+        let mut second_replies = reply_packet(3, 0, &[]);
+        second_replies.extend_from_slice(&reply_packet(4, 0, &[]));
+        second_replies.extend_from_slice(&reply_packet(4, 0, &[0x1]));
+        let mock_stream = MockStreamBuilder::default()
+            .response_bytes(&command_packet(2, [0x1, 0x9], &[]), &first_replies)
+            .response_bytes(&command_packet(4, [0x1, 0x9], &[]), &second_replies)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let (sender, mut unsolicited) = tokio::sync::mpsc::unbounded_channel();
+        client.set_unsolicited_reply_policy(UnsolicitedReplyPolicy::Report(sender));
+
+        client.vm_resume().await.unwrap();
+        let result = tokio::time::timeout(Duration::from_millis(20), client.vm_suspend()).await;
+        assert!(result.is_err(), "Request should still be in flight");
+        client.vm_resume().await.unwrap();
+
+        let reply = unsolicited.recv().await.unwrap();
+        assert_eq!(reply.header.id, 77);
+        let reply = unsolicited.recv().await.unwrap();
+        assert_eq!((reply.header.id, reply.data), (4, vec![0x1]));
+        assert!(unsolicited.try_recv().is_err());
+        assert_eq!(client.pending_request_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_client_driver() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x8], &[], &[])
+            .build();
+        let (client, driver) = JdwpClient::new_with_driver(mock_stream).await.unwrap();
+
+        // Nothing is spawned, so the request only completes while the driver is polled
+        let request = async move {
+            let result = client.vm_suspend().await;
+            drop(client);
+            result
+        };
+        let (result, ()) = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(request, driver)
+        })
+        .await
+        .expect("The driver should complete once the client is dropped");
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_inline_client() {
+        let mut reply = reply_packet(2, 0, &[]);
+        reply.extend(event_packet(100, 0, &[thread_start_event(1, 0x20)]));
+        let mock_stream = MockStreamBuilder::default()
+            .response_bytes(&command_packet(2, [0x1, 0x8], &[]), &reply)
+            .build();
+        let client = JdwpClient::new_inline(mock_stream).await.unwrap();
+
+        client.vm_suspend().await.unwrap();
+        // The event was read along with the reply
+        assert_eq!(client.buffered_event_count(), 1);
+        let composite = client.next_event().await.unwrap();
+        assert_eq!(composite.events.len(), 1);
+
+        // Unanswered commands time out after exactly the timeout of the paused clock
+        let start = tokio::time::Instant::now();
+        let result = client.vm_resume().await;
+        assert!(
+            matches!(result, Err(Error::IoError(e)) if e.kind() == std::io::ErrorKind::TimedOut)
+        );
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+    }
+}
@@ -1,3 +1,6 @@
+// Shared between integration test crates, each of which uses a different subset
+#![allow(dead_code)]
+
 use std::collections::HashMap;
 use std::io;
 use std::pin::Pin;
@@ -8,6 +11,8 @@ use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 pub struct MockStream {
     read_data: std::collections::VecDeque<u8>,
     write_data: Vec<u8>,
+    /// Length of `write_data` at the last flush
+    flushed: usize,
     waker: Option<Waker>,
     responses: HashMap<Vec<u8>, Vec<u8>>,
     default_response: Option<Vec<u8>>,
@@ -20,6 +25,7 @@ impl MockStream {
         Self {
             read_data: std::collections::VecDeque::new(),
             write_data: Vec::new(),
+            flushed: 0,
             waker: None,
             responses: HashMap::new(),
             default_response: None,
@@ -50,28 +56,44 @@ impl MockStream {
     /// Clear all written data (useful between test operations)
     pub fn clear_written(&mut self) {
         self.write_data.clear();
+        self.flushed = 0;
     }
 
-    /// Check for automatic responses based on what was written
+    /// Check for automatic responses based on what was written since the last flush, which
+    /// may hold several packets written together
     fn check_responses(&mut self) {
-        // Check if what was written matches any response patterns
-        for (input, output) in &self.responses.clone() {
-            if self.write_data.ends_with(input) {
-                self.read_data.extend(output);
-                if let Some(waker) = self.waker.take() {
-                    waker.wake();
-                }
-                return;
-            }
-        }
+        let flushed = std::mem::replace(&mut self.flushed, self.write_data.len());
+        let written = &self.write_data[flushed..];
+
+        // Answer the matching inputs in the order they were written
+        let mut outputs: Vec<(usize, &Vec<u8>)> = self
+            .responses
+            .iter()
+            .filter_map(|(input, output)| {
+                written
+                    .windows(input.len())
+                    .position(|window| window == input.as_slice())
+                    .map(|position| (position, output))
+            })
+            .collect();
+        outputs.sort_by_key(|(position, _)| *position);
+        let mut output: Vec<u8> = outputs
+            .into_iter()
+            .flat_map(|(_, output)| output.iter().copied())
+            .collect();
 
         // Check default response
-        if let Some(default) = &self.default_response {
-            if !self.write_data.is_empty() {
-                self.read_data.extend(default);
-                if let Some(waker) = self.waker.take() {
-                    waker.wake();
-                }
+        if output.is_empty()
+            && let Some(default) = &self.default_response
+            && !self.write_data.is_empty()
+        {
+            output = default.clone();
+        }
+
+        if !output.is_empty() {
+            self.read_data.extend(output);
+            if let Some(waker) = self.waker.take() {
+                waker.wake();
             }
         }
     }
@@ -138,6 +160,67 @@ impl AsyncWrite for MockStream {
     }
 }
 
+/// Encode a JDWP string (length-prefixed UTF-8)
+pub fn jdwp_string(value: &str) -> Vec<u8> {
+    let mut bytes = (value.len() as u32).to_be_bytes().to_vec();
+    bytes.extend_from_slice(value.as_bytes());
+    bytes
+}
+
+/// Encode an 8 byte id, matching the sizes set up by `with_initial_id_sizes`
+pub fn id(value: u64) -> [u8; 8] {
+    value.to_be_bytes()
+}
+
+/// Build a command packet as the client would send it
+pub fn command_packet(id: u32, command: [u8; 2], data: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(11 + data.len());
+    packet.extend_from_slice(&(11 + data.len() as u32).to_be_bytes());
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.push(0x0);
+    packet.extend_from_slice(&command);
+    packet.extend_from_slice(data);
+    packet
+}
+
+/// Build an Event.Composite command packet as the VM would send it
+pub fn event_packet(id: u32, suspend_policy: u8, events: &[Vec<u8>]) -> Vec<u8> {
+    let mut data = vec![suspend_policy];
+    data.extend_from_slice(&(events.len() as u32).to_be_bytes());
+    for event in events {
+        data.extend_from_slice(event);
+    }
+
+    command_packet(id, [0x40, 0x64], &data)
+}
+
+/// Encode a THREAD_START event for the given request and thread
+pub fn thread_start_event(request_id: i32, thread_id: u64) -> Vec<u8> {
+    let mut event = vec![0x6];
+    event.extend_from_slice(&request_id.to_be_bytes());
+    event.extend_from_slice(&id(thread_id));
+    event
+}
+
+/// Encode a THREAD_DEATH event for the given request and thread
+pub fn thread_death_event(request_id: i32, thread_id: u64) -> Vec<u8> {
+    let mut event = vec![0x7];
+    event.extend_from_slice(&request_id.to_be_bytes());
+    event.extend_from_slice(&id(thread_id));
+    event
+}
+
+/// Build a reply packet as the VM would send it
+pub fn reply_packet(id: u32, error_code: u16, data: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(11 + data.len());
+    packet.extend_from_slice(&(11 + data.len() as u32).to_be_bytes());
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.push(0x80);
+    packet.extend_from_slice(&error_code.to_be_bytes());
+    packet.extend_from_slice(data);
+    packet
+}
+
 /// Builder for creating mock streams with predefined behavior
 pub struct MockStreamBuilder {
     responses: HashMap<Vec<u8>, Vec<u8>>,
@@ -160,6 +243,14 @@ impl MockStreamBuilder {
         self
     }
 
+    /// Add a response to a full command packet with the given id
+    pub fn command_reply(self, id: u32, command: [u8; 2], out: &[u8], reply: &[u8]) -> Self {
+        self.response_bytes(
+            &command_packet(id, command, out),
+            &reply_packet(id, 0, reply),
+        )
+    }
+
     /// Add JDWP-Handshake as input&output
     pub fn with_jdwp_handshake(self) -> Self {
         let handshake_bytes = "JDWP-Handshake".as_bytes();
@@ -289,6 +380,18 @@ mod tests {
         assert_eq!(&buffer, b"world");
     }
 
+    #[test]
+    fn test_packet_builders() {
+        assert_eq!(
+            command_packet(2, [0x1, 0x4], &[]),
+            [0x0, 0x0, 0x0, 0xb, 0x0, 0x0, 0x0, 0x2, 0x0, 0x1, 0x4]
+        );
+        assert_eq!(
+            reply_packet(2, 0, &[0x1]),
+            [0x0, 0x0, 0x0, 0xc, 0x0, 0x0, 0x0, 0x2, 0x80, 0x0, 0x0, 0x1]
+        );
+    }
+
     #[tokio::test]
     async fn test_default_response() {
         let mut stream = MockStreamBuilder::new()
@@ -0,0 +1,80 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod convert_tests {
+    use crate::common::{MockStreamBuilder, id, jdwp_string};
+    use jdwp_client::{
+        Error, IntoJdwpArguments, IntoJdwpValue, JdwpClient, JdwpValue, Tag, TaggedObjectId,
+        TryFromJdwpValue, VariableLengthId,
+    };
+
+    fn string_object(object_id: u64) -> JdwpValue {
+        JdwpValue::Object(TaggedObjectId {
+            tag: Tag::String,
+            object_id: VariableLengthId { value: object_id },
+        })
+    }
+
+    #[tokio::test]
+    async fn test_tuple_arguments() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0xB], &jdwp_string("hello"), &id(0x40))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let arguments = (42, "hello", true, None::<i32>)
+            .into_jdwp_arguments(&client)
+            .await
+            .unwrap();
+        assert_eq!(
+            arguments,
+            vec![
+                JdwpValue::Int(42),
+                string_object(0x40),
+                JdwpValue::Boolean(true),
+                JdwpValue::Object(TaggedObjectId {
+                    tag: Tag::Object,
+                    object_id: VariableLengthId { value: 0 },
+                }),
+            ]
+        );
+        assert_eq!(
+            'x'.into_jdwp_value(&client).await.unwrap(),
+            JdwpValue::Char(0x78)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_values_from_vm() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xA, 0x1], &id(0x40), &jdwp_string("hello"))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let string = String::try_from_jdwp_value(string_object(0x40), &client)
+            .await
+            .unwrap();
+        assert_eq!(string, "hello");
+        let null = Option::<String>::try_from_jdwp_value(string_object(0), &client)
+            .await
+            .unwrap();
+        assert_eq!(null, None);
+        assert_eq!(
+            i64::try_from_jdwp_value(JdwpValue::Long(7), &client)
+                .await
+                .unwrap(),
+            7
+        );
+
+        let result = i32::try_from_jdwp_value(JdwpValue::Boolean(true), &client).await;
+        assert!(matches!(
+            result,
+            Err(Error::TypeMismatch {
+                expected: "i32",
+                found: Tag::Boolean,
+            })
+        ));
+    }
+}
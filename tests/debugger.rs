@@ -0,0 +1,235 @@
+#![cfg(feature = "tokio")]
+
+#[cfg(test)]
+mod common;
+
+#[cfg(test)]
+mod debugger_tests {
+    use crate::common::{
+        MockStreamBuilder, command_packet, event_packet, id, jdwp_string, reply_packet,
+    };
+    use jdwp_client::{BreakpointSpec, Debugger, Error, Event, HitPolicy, JdwpClient};
+
+    fn class_prepare_out() -> Vec<u8> {
+        let mut out = vec![0x8, 0x1, 0x0, 0x0, 0x0, 0x1, 0x5];
+        out.extend_from_slice(&jdwp_string("com.example.Main"));
+        out
+    }
+
+    fn class_unload_out() -> Vec<u8> {
+        let mut out = vec![0x9, 0x0, 0x0, 0x0, 0x0, 0x1, 0x5];
+        out.extend_from_slice(&jdwp_string("com.example.Main"));
+        out
+    }
+
+    fn classes_reply() -> Vec<u8> {
+        let mut reply = vec![0x0, 0x0, 0x0, 0x1, 0x1];
+        reply.extend_from_slice(&id(0x10));
+        reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x7]);
+        reply
+    }
+
+    fn methods_reply() -> Vec<u8> {
+        let mut reply = vec![0x0, 0x0, 0x0, 0x1];
+        reply.extend_from_slice(&id(0x1));
+        reply.extend_from_slice(&jdwp_string("main"));
+        reply.extend_from_slice(&jdwp_string("([Ljava/lang/String;)V"));
+        reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x9]);
+        reply
+    }
+
+    fn line_table_out() -> Vec<u8> {
+        let mut out = id(0x10).to_vec();
+        out.extend_from_slice(&id(0x1));
+        out
+    }
+
+    fn line_table_reply() -> Vec<u8> {
+        let mut reply = 0u64.to_be_bytes().to_vec();
+        reply.extend_from_slice(&10u64.to_be_bytes());
+        reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x2]);
+        for (index, line) in [(0u64, 5u32), (4, 6)] {
+            reply.extend_from_slice(&index.to_be_bytes());
+            reply.extend_from_slice(&line.to_be_bytes());
+        }
+        reply
+    }
+
+    fn location(index: u64) -> Vec<u8> {
+        let mut bytes = vec![0x1];
+        bytes.extend_from_slice(&id(0x10));
+        bytes.extend_from_slice(&id(0x1));
+        bytes.extend_from_slice(&id(index));
+        bytes
+    }
+
+    /// Event of `kind` for `request_id` in thread 5
+    fn stop_event(kind: u8, request_id: u8, index: u64) -> Vec<u8> {
+        let mut event = vec![kind, 0x0, 0x0, 0x0, request_id];
+        event.extend_from_slice(&id(0x5));
+        event.extend_from_slice(&location(index));
+        event
+    }
+
+    fn step_out() -> Vec<u8> {
+        let mut out = vec![0x1, 0x1, 0x0, 0x0, 0x0, 0x1, 0xA];
+        out.extend_from_slice(&id(0x5));
+        out.extend_from_slice(&[0x0, 0x0, 0x0, 0x1, 0x0, 0x0, 0x0, 0x1]);
+        out
+    }
+
+    fn breakpoint_session() -> MockStreamBuilder {
+        let mut breakpoint_out = vec![0x2, 0x1, 0x0, 0x0, 0x0, 0x1, 0x7];
+        breakpoint_out.extend_from_slice(&location(4));
+        breakpoint_session_with(&breakpoint_out)
+    }
+
+    /// Sets a breakpoint with the request `breakpoint_out`, which is hit right away
+    fn breakpoint_session_with(breakpoint_out: &[u8]) -> MockStreamBuilder {
+        let mut breakpoint_reply = reply_packet(7, 0, &[0x0, 0x0, 0x0, 0x2]);
+        breakpoint_reply.extend_from_slice(&event_packet(100, 0x1, &[stop_event(0x2, 2, 4)]));
+        MockStreamBuilder::default()
+            .command_reply(2, [0xF, 0x1], &class_prepare_out(), &[0x0, 0x0, 0x0, 0x1])
+            .command_reply(3, [0xF, 0x1], &class_unload_out(), &[0x0, 0x0, 0x0, 0x8])
+            .command_reply(
+                4,
+                [0x1, 0x2],
+                &jdwp_string("Lcom/example/Main;"),
+                &classes_reply(),
+            )
+            .command_reply(5, [0x2, 0x5], &id(0x10), &methods_reply())
+            .command_reply(6, [0x6, 0x1], &line_table_out(), &line_table_reply())
+            .response_bytes(
+                &command_packet(7, [0xF, 0x1], breakpoint_out),
+                &breakpoint_reply,
+            )
+    }
+
+    #[tokio::test]
+    async fn test_run_to_breakpoint_and_step_over() {
+        let mut resume_reply = reply_packet(9, 0, &[]);
+        resume_reply.extend_from_slice(&event_packet(101, 0x1, &[stop_event(0x1, 3, 9)]));
+        let mock_stream = breakpoint_session()
+            .command_reply(8, [0xF, 0x1], &step_out(), &[0x0, 0x0, 0x0, 0x3])
+            .response_bytes(&command_packet(9, [0xB, 0x3], &id(0x5)), &resume_reply)
+            .command_reply(10, [0xF, 0x2], &[0x1, 0x0, 0x0, 0x0, 0x3], &[])
+            .build();
+        let mut debugger = Debugger::new(JdwpClient::new(mock_stream).await.unwrap());
+
+        let breakpoint = debugger
+            .set_breakpoint("com.example.Main", 6)
+            .await
+            .unwrap();
+        let stop = debugger.run_to_breakpoint().await.unwrap();
+        assert_eq!(stop.breakpoint, Some(breakpoint));
+        assert_eq!(stop.thread.value, 0x5);
+        assert_eq!(stop.location.index, 4);
+        assert!(debugger.client().is_thread_suspended(stop.thread));
+
+        let stop = debugger.step_over().await.unwrap();
+        assert_eq!(stop.breakpoint, None);
+        assert_eq!(stop.location.index, 9);
+        assert!(matches!(stop.event, Event::SingleStep { .. }));
+        assert_eq!(debugger.stop(), Some(&stop));
+    }
+
+    #[tokio::test]
+    async fn test_step_over_requires_stop() {
+        let mock_stream = MockStreamBuilder::default().build();
+        let mut debugger = Debugger::new(JdwpClient::new(mock_stream).await.unwrap());
+        assert!(matches!(
+            debugger.step_over().await,
+            Err(Error::NoSuspendedThread)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_export_breakpoints() {
+        let mut debugger =
+            Debugger::new(JdwpClient::new(breakpoint_session().build()).await.unwrap());
+        debugger
+            .set_breakpoint("com.example.Main", 6)
+            .await
+            .unwrap();
+        assert_eq!(
+            debugger.export_breakpoints(),
+            "jdwp-client-breakpoints 1\ncom.example.Main:6\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_breakpoints() {
+        let mut breakpoint_out = vec![0x2, 0x1, 0x0, 0x0, 0x0, 0x2, 0x7];
+        breakpoint_out.extend_from_slice(&location(4));
+        breakpoint_out.extend_from_slice(&[0x1, 0x0, 0x0, 0x0, 0x3]);
+        let mock_stream = breakpoint_session_with(&breakpoint_out).build();
+        let mut debugger = Debugger::new(JdwpClient::new(mock_stream).await.unwrap());
+
+        let ids = debugger
+            .import_breakpoints("jdwp-client-breakpoints 1\ncom.example.Main:6 hit=3\n")
+            .await
+            .unwrap();
+        assert_eq!(ids.len(), 1);
+        assert_eq!(
+            debugger.breakpoint_spec(ids[0]),
+            Some(&BreakpointSpec::new("com.example.Main", 6).hit_policy(HitPolicy::Nth(3)))
+        );
+        assert_eq!(debugger.breakpoint(ids[0]).unwrap().request_ids(), vec![2]);
+
+        let result = debugger
+            .import_breakpoints("jdwp-client-breakpoints 2\ncom.example.Main:6\n")
+            .await;
+        assert!(matches!(result, Err(Error::InvalidArgument { .. })));
+        assert_eq!(debugger.breakpoint_ids(), ids);
+    }
+
+    #[tokio::test]
+    async fn test_import_breakpoints_rolls_back_on_failure() {
+        let mut other_class_prepare_out = vec![0x8, 0x1, 0x0, 0x0, 0x0, 0x1, 0x5];
+        other_class_prepare_out.extend_from_slice(&jdwp_string("com.example.Other"));
+        let mock_stream = breakpoint_session()
+            .response_bytes(
+                &command_packet(8, [0xF, 0x1], &other_class_prepare_out),
+                &reply_packet(8, 113, &[]),
+            )
+            .command_reply(9, [0xF, 0x2], &[0x8, 0x0, 0x0, 0x0, 0x1], &[])
+            .command_reply(10, [0xF, 0x2], &[0x9, 0x0, 0x0, 0x0, 0x8], &[])
+            .command_reply(11, [0xF, 0x2], &[0x2, 0x0, 0x0, 0x0, 0x2], &[])
+            .build();
+        let mut debugger = Debugger::new(JdwpClient::new(mock_stream).await.unwrap());
+
+        let result = debugger
+            .import_breakpoints(
+                "jdwp-client-breakpoints 1\ncom.example.Main:6\ncom.example.Other:3\n",
+            )
+            .await;
+        assert!(result.is_err());
+        assert!(debugger.breakpoint_ids().is_empty());
+        assert_eq!(debugger.export_breakpoints(), "jdwp-client-breakpoints 1\n");
+    }
+
+    #[tokio::test]
+    async fn test_detach() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x8], &[], &[])
+            .command_reply(3, [0xF, 0x3], &[], &[])
+            .command_reply(4, [0x1, 0x9], &[], &[])
+            .command_reply(5, [0x1, 0x6], &[], &[])
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.vm_suspend().await.unwrap();
+
+        let debugger = Debugger::new(client.clone());
+        debugger.detach().await.unwrap();
+        assert!(!client.is_vm_suspended());
+    }
+
+    #[tokio::test]
+    async fn test_kill() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0xA], &[0x0, 0x0, 0x0, 0x3], &[])
+            .build();
+        let debugger = Debugger::new(JdwpClient::new(mock_stream).await.unwrap());
+        debugger.kill(3).await.unwrap();
+    }
+}
@@ -0,0 +1,110 @@
+#![cfg(feature = "tokio")]
+
+#[cfg(test)]
+mod common;
+
+#[cfg(test)]
+mod discover_tests {
+    use crate::common::{MockStreamBuilder, command_packet, jdwp_string, reply_packet};
+    use jdwp_client::{DiscoveredVersion, DiscoveryOptions, JdwpClient, discover};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn options(ports: Vec<u16>) -> DiscoveryOptions {
+        DiscoveryOptions {
+            ports,
+            concurrency: 2,
+            timeout: Duration::from_secs(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_discover_reports_version() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut handshake = [0u8; 14];
+            stream.read_exact(&mut handshake).await.unwrap();
+            stream.write_all(&handshake).await.unwrap();
+
+            let mut id_sizes = vec![0u8; 11];
+            stream.read_exact(&mut id_sizes).await.unwrap();
+            assert_eq!(id_sizes, command_packet(1, [0x1, 0x7], &[]));
+            let sizes: Vec<u8> = [8i32; 5].iter().flat_map(|s| s.to_be_bytes()).collect();
+            stream.write_all(&reply_packet(1, 0, &sizes)).await.unwrap();
+
+            let mut version = vec![0u8; 11];
+            stream.read_exact(&mut version).await.unwrap();
+            assert_eq!(version, command_packet(2, [0x1, 0x1], &[]));
+            let mut data = jdwp_string("Java Debug Wire Protocol");
+            data.extend_from_slice(&17i32.to_be_bytes());
+            data.extend_from_slice(&0i32.to_be_bytes());
+            data.extend(jdwp_string("17.0.2"));
+            data.extend(jdwp_string("OpenJDK 64-Bit Server VM"));
+            stream.write_all(&reply_packet(2, 0, &data)).await.unwrap();
+        });
+
+        let endpoints = discover("127.0.0.1", &options(vec![address.port()]))
+            .await
+            .unwrap();
+        assert_eq!(endpoints.len(), 1);
+        assert_eq!(endpoints[0].address, address);
+        assert_eq!(
+            endpoints[0].version,
+            Some(DiscoveredVersion {
+                description: String::from("Java Debug Wire Protocol"),
+                jdwp_major: 17,
+                jdwp_minor: 0,
+                vm_version: String::from("17.0.2"),
+                vm_name: String::from("OpenJDK 64-Bit Server VM"),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_discover_skips_other_services() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n")
+                .await
+                .unwrap();
+        });
+        // Nothing listens on a port just released
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed_port = closed.local_addr().unwrap().port();
+        drop(closed);
+
+        let endpoints = discover("127.0.0.1", &options(vec![port, closed_port]))
+            .await
+            .unwrap();
+        assert!(endpoints.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_probe_stream() {
+        let mut version = jdwp_string("Java Debug Wire Protocol");
+        version.extend_from_slice(&21i32.to_be_bytes());
+        version.extend_from_slice(&0i32.to_be_bytes());
+        version.extend(jdwp_string("21.0.8"));
+        version.extend(jdwp_string("OpenJDK 64-Bit Server VM"));
+        let mut capabilities = vec![0x0; 32];
+        capabilities[15] = 0x1;
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x1], &[], &version)
+            .command_reply(3, [0x1, 0x11], &[], &capabilities)
+            .command_reply(4, [0x1, 0x6], &[], &[])
+            .build();
+
+        let fingerprint = JdwpClient::probe_stream(mock_stream).await.unwrap();
+        assert_eq!(fingerprint.version.jdwp_major, 21);
+        assert_eq!(fingerprint.version.vm_version, "21.0.8");
+        assert_eq!(fingerprint.id_sizes.object_id_size, 8);
+        assert!(fingerprint.capabilities.can_get_instance_info);
+        assert!(!fingerprint.capabilities.can_redefine_classes);
+    }
+}
@@ -0,0 +1,196 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod event_request_tests {
+    use crate::common::{MockStreamBuilder, command_packet, event_packet, id, reply_packet};
+    use jdwp_client::{
+        Event, EventBufferConfig, EventKind, EventModifier, JdwpClient, Location, OverflowPolicy,
+        SuspendPolicy, TypeTag, VariableLengthId,
+    };
+
+    fn location(method_id: u64) -> Location {
+        Location {
+            type_tag: TypeTag::Class,
+            class_id: VariableLengthId { value: 0x10 },
+            method_id: VariableLengthId { value: method_id },
+            index: 0,
+        }
+    }
+
+    fn location_bytes(method_id: u64) -> Vec<u8> {
+        let mut bytes = vec![0x1];
+        bytes.extend_from_slice(&id(0x10));
+        bytes.extend_from_slice(&id(method_id));
+        bytes.extend_from_slice(&id(0));
+        bytes
+    }
+
+    fn set_breakpoint_out(method_id: u64) -> Vec<u8> {
+        let mut out = vec![0x2, 0x0, 0x0, 0x0, 0x0, 0x1, 0x7];
+        out.extend_from_slice(&location_bytes(method_id));
+        out
+    }
+
+    fn breakpoint_event(request_id: u8, thread_id: u64, method_id: u64) -> Vec<u8> {
+        let mut event = vec![0x2, 0x0, 0x0, 0x0, request_id];
+        event.extend_from_slice(&id(thread_id));
+        event.extend_from_slice(&location_bytes(method_id));
+        event
+    }
+
+    #[tokio::test]
+    async fn test_events_are_routed_to_handles() {
+        // The events for the first request arrive right after the replies, before the set
+        // commands return
+        let mut second_reply = reply_packet(3, 0, &[0x0, 0x0, 0x0, 0x8]);
+        second_reply.extend_from_slice(&event_packet(100, 0x0, &[breakpoint_event(7, 0x1, 0x1)]));
+        second_reply.extend_from_slice(&event_packet(101, 0x0, &[breakpoint_event(8, 0x2, 0x2)]));
+        second_reply.extend_from_slice(&event_packet(102, 0x0, &[breakpoint_event(7, 0x3, 0x1)]));
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(
+                2,
+                [0xF, 0x1],
+                &set_breakpoint_out(0x1),
+                &[0x0, 0x0, 0x0, 0x7],
+            )
+            .response_bytes(
+                &command_packet(3, [0xF, 0x1], &set_breakpoint_out(0x2)),
+                &second_reply,
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let mut first = client
+            .event_request_set(
+                EventKind::Breakpoint,
+                SuspendPolicy::None,
+                vec![EventModifier::LocationOnly(location(0x1))],
+            )
+            .await
+            .unwrap();
+        let mut second = client
+            .event_request_set(
+                EventKind::Breakpoint,
+                SuspendPolicy::None,
+                vec![EventModifier::LocationOnly(location(0x2))],
+            )
+            .await
+            .unwrap();
+        assert_eq!(first.request_id(), 7);
+        assert_eq!(second.request_id(), 8);
+
+        let threads: Vec<_> = [
+            first.next_hit().await,
+            first.next_hit().await,
+            second.next_hit().await,
+        ]
+        .into_iter()
+        .map(|event| event.unwrap().thread().unwrap().value)
+        .collect();
+        assert_eq!(threads, vec![0x1, 0x3, 0x2]);
+        assert!(first.try_next_hit().is_none());
+
+        // The global stream still sees every event
+        for _ in 0..3 {
+            let composite = client.next_event().await.unwrap();
+            assert!(matches!(composite.events[0], Event::Breakpoint { .. }));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_full_handle_drops_oldest_hit() {
+        let mut reply = reply_packet(2, 0, &[0x0, 0x0, 0x0, 0x7]);
+        for (packet_id, thread_id) in [(100, 0x1), (101, 0x2), (102, 0x3)] {
+            reply.extend_from_slice(&event_packet(
+                packet_id,
+                0x0,
+                &[breakpoint_event(7, thread_id, 0x1)],
+            ));
+        }
+        let mock_stream = MockStreamBuilder::default()
+            .response_bytes(
+                &command_packet(2, [0xF, 0x1], &set_breakpoint_out(0x1)),
+                &reply,
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.set_event_buffer_config(EventBufferConfig {
+            capacity: 2,
+            overflow_policy: OverflowPolicy::DropOldest,
+        });
+
+        let mut handle = client
+            .event_request_set(
+                EventKind::Breakpoint,
+                SuspendPolicy::None,
+                vec![EventModifier::LocationOnly(location(0x1))],
+            )
+            .await
+            .unwrap();
+        while handle.dropped_hit_count() == 0 {
+            tokio::task::yield_now().await;
+        }
+        let threads: Vec<_> = [handle.try_next_hit(), handle.try_next_hit()]
+            .into_iter()
+            .map(|event| event.unwrap().thread().unwrap().value)
+            .collect();
+        assert_eq!(threads, vec![0x2, 0x3]);
+        assert!(handle.try_next_hit().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_clear_closes_handle() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(
+                2,
+                [0xF, 0x1],
+                &set_breakpoint_out(0x1),
+                &[0x0, 0x0, 0x0, 0x7],
+            )
+            .command_reply(3, [0xF, 0x2], &[0x2, 0x0, 0x0, 0x0, 0x7], &[])
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let mut handle = client
+            .event_request_set(
+                EventKind::Breakpoint,
+                SuspendPolicy::None,
+                vec![EventModifier::LocationOnly(location(0x1))],
+            )
+            .await
+            .unwrap();
+        client
+            .event_request_clear(handle.event_kind(), handle.request_id())
+            .await
+            .unwrap();
+        assert!(handle.next_hit().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_clear_all_event_requests() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(
+                2,
+                [0xF, 0x1],
+                &[0x6, 0x0, 0x0, 0x0, 0x0, 0x0],
+                &[0x0, 0x0, 0x0, 0x9],
+            )
+            .command_reply(3, [0xF, 0x2], &[0x6, 0x0, 0x0, 0x0, 0x9], &[])
+            .command_reply(4, [0xF, 0x3], &[], &[])
+            .command_reply(5, [0xF, 0x3], &[], &[])
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let mut handle = client
+            .event_request_set(EventKind::ThreadStart, SuspendPolicy::None, vec![])
+            .await
+            .unwrap();
+        client.clear_all_event_requests().await.unwrap();
+        assert!(handle.next_hit().await.is_none());
+
+        // Nothing is left but the breakpoints, which may have been set by other means
+        client.clear_all_event_requests().await.unwrap();
+    }
+}
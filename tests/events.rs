@@ -0,0 +1,180 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod event_tests {
+    use crate::common::{
+        MockStreamBuilder, command_packet, event_packet, reply_packet, thread_start_event,
+    };
+    use jdwp_client::{
+        Event, EventBufferConfig, EventKind, JdwpClient, OverflowPolicy, SuspendPolicy,
+        VariableLengthId,
+    };
+
+    /// Reply to the resume command (id 2) followed by the given events
+    fn resume_with_events(events: &[Vec<u8>]) -> MockStreamBuilder {
+        let mut output = reply_packet(2, 0, &[]);
+        for (i, event) in events.iter().enumerate() {
+            output.extend_from_slice(&event_packet(
+                100 + i as u32,
+                0x0,
+                std::slice::from_ref(event),
+            ));
+        }
+        MockStreamBuilder::default().response_bytes(&command_packet(2, [0x1, 0x9], &[]), &output)
+    }
+
+    #[tokio::test]
+    async fn test_receive_event() {
+        let mock_stream = resume_with_events(&[thread_start_event(3, 0x11)]).build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.vm_resume().await.unwrap();
+
+        let composite = client.next_event().await.unwrap();
+        assert_eq!(composite.suspend_policy, SuspendPolicy::None);
+        assert_eq!(
+            composite.events,
+            vec![Event::ThreadStart {
+                request_id: 3,
+                thread: VariableLengthId { value: 0x11 },
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_event_before_id_sizes() {
+        // VM_START is sent by a VM started with suspend=y right after the handshake, before the
+        // reply to IDSizes
+        let mut vm_start = vec![0x5a, 0x0, 0x0, 0x0, 0x0];
+        vm_start.extend_from_slice(&[0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1]);
+        let mut output = event_packet(100, 0x2, &[vm_start]);
+        output.extend_from_slice(&reply_packet(
+            1,
+            0,
+            &[
+                0x0, 0x0, 0x0, 0x8, 0x0, 0x0, 0x0, 0x8, 0x0, 0x0, 0x0, 0x8, 0x0, 0x0, 0x0, 0x8,
+                0x0, 0x0, 0x0, 0x8,
+            ],
+        ));
+        let mock_stream = MockStreamBuilder::new()
+            .with_jdwp_handshake()
+            .response_bytes(&command_packet(1, [0x1, 0x7], &[]), &output)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let composite = client.next_event().await.unwrap();
+        assert_eq!(composite.suspend_policy, SuspendPolicy::All);
+        assert!(matches!(composite.events[0], Event::VmStart { .. }));
+        assert!(client.is_vm_suspended());
+    }
+
+    #[tokio::test]
+    async fn test_drop_oldest_overflow() {
+        let mock_stream = resume_with_events(&[
+            thread_start_event(1, 0x11),
+            thread_start_event(1, 0x12),
+            thread_start_event(1, 0x13),
+        ])
+        .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.set_event_buffer_config(EventBufferConfig {
+            capacity: 1,
+            overflow_policy: OverflowPolicy::DropOldest,
+        });
+        client.vm_resume().await.unwrap();
+
+        // Wait until the reader has processed all three events
+        while client.dropped_event_count() < 2 {
+            tokio::task::yield_now().await;
+        }
+        let composite = client.next_event().await.unwrap();
+        assert_eq!(
+            composite.events[0].thread(),
+            Some(VariableLengthId { value: 0x13 })
+        );
+        assert!(client.try_next_event().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_hold_events_overflow() {
+        // The VM answers HoldEvents (id 3) and ReleaseEvents (id 4) with another event each, which
+        // only shows up if the client sent those commands
+        let mut hold_reply = reply_packet(3, 0, &[]);
+        hold_reply.extend_from_slice(&event_packet(200, 0x0, &[thread_start_event(1, 0x21)]));
+        let mut release_reply = reply_packet(4, 0, &[]);
+        release_reply.extend_from_slice(&event_packet(201, 0x0, &[thread_start_event(1, 0x22)]));
+
+        let mock_stream =
+            resume_with_events(&[thread_start_event(1, 0x11), thread_start_event(1, 0x12)])
+                .response_bytes(&command_packet(3, [0x1, 0xf], &[]), &hold_reply)
+                .response_bytes(&command_packet(4, [0x1, 0x10], &[]), &release_reply)
+                .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.set_event_buffer_config(EventBufferConfig {
+            capacity: 1,
+            overflow_policy: OverflowPolicy::HoldEvents,
+        });
+        client.vm_resume().await.unwrap();
+
+        let mut threads = Vec::new();
+        for _ in 0..4 {
+            let composite = client.next_event().await.unwrap();
+            threads.push(composite.events[0].thread().unwrap().value);
+        }
+        assert_eq!(threads, vec![0x11, 0x12, 0x21, 0x22]);
+        assert_eq!(client.dropped_event_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_filtered_subscription() {
+        let mock_stream = resume_with_events(&[
+            thread_start_event(3, 0x11),
+            thread_start_event(3, 0x12),
+            thread_start_event(3, 0x12),
+        ])
+        .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let mut subscription = client
+            .events()
+            .filter(EventKind::ThreadStart)
+            .in_thread(VariableLengthId { value: 0x12 })
+            .stream();
+        let mut deaths = client.events().filter(EventKind::ThreadDeath).stream();
+        client.vm_resume().await.unwrap();
+
+        for _ in 0..2 {
+            let event = subscription.next().await.unwrap();
+            assert_eq!(event.thread(), Some(VariableLengthId { value: 0x12 }));
+        }
+        assert!(deaths.try_next().is_none());
+        assert_eq!(client.buffered_event_count(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_full_subscription_drops_oldest() {
+        let mock_stream = resume_with_events(&[
+            thread_start_event(3, 0x11),
+            thread_start_event(3, 0x12),
+            thread_start_event(3, 0x13),
+        ])
+        .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.set_event_buffer_config(EventBufferConfig {
+            capacity: 2,
+            overflow_policy: OverflowPolicy::DropOldest,
+        });
+        let mut subscription = client.events().filter(EventKind::ThreadStart).stream();
+        client.vm_resume().await.unwrap();
+
+        while subscription.dropped_event_count() == 0 {
+            tokio::task::yield_now().await;
+        }
+        let threads: Vec<_> = [subscription.try_next(), subscription.try_next()]
+            .into_iter()
+            .map(|event| event.unwrap().thread().unwrap().value)
+            .collect();
+        assert_eq!(threads, vec![0x12, 0x13]);
+        assert!(subscription.try_next().is_none());
+    }
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod exception_stats_tests {
+    use crate::common::{
+        MockStreamBuilder, command_packet, event_packet, id, jdwp_string, reply_packet,
+    };
+    use jdwp_client::{ExceptionStats, JdwpClient};
+    use std::time::Duration;
+
+    fn location(class_id: u64, index: u64) -> Vec<u8> {
+        let mut bytes = vec![if class_id == 0 { 0x0 } else { 0x1 }];
+        bytes.extend_from_slice(&id(class_id));
+        bytes.extend_from_slice(&id(if class_id == 0 { 0x0 } else { 0x1 }));
+        bytes.extend_from_slice(&id(index));
+        bytes
+    }
+
+    fn exception_request_out() -> Vec<u8> {
+        let mut out = vec![0x4, 0x1, 0x0, 0x0, 0x0, 0x2, 0x8];
+        out.extend_from_slice(&id(0x0));
+        out.extend_from_slice(&[0x1, 0x1, 0x6]);
+        out.extend_from_slice(&jdwp_string("java.*"));
+        out
+    }
+
+    fn exception_event(packet_id: u32, exception: u64, caught: bool) -> Vec<u8> {
+        let mut event = vec![0x4, 0x0, 0x0, 0x0, 0x3];
+        event.extend_from_slice(&id(0x5));
+        event.extend_from_slice(&location(0x10, 4));
+        event.push(b'L');
+        event.extend_from_slice(&id(exception));
+        match caught {
+            true => event.extend_from_slice(&location(0x10, 9)),
+            false => event.extend_from_slice(&location(0x0, 0)),
+        }
+        event_packet(packet_id, 0x1, &[event])
+    }
+
+    fn reference_type_reply() -> Vec<u8> {
+        let mut reply = vec![0x1];
+        reply.extend_from_slice(&id(0x30));
+        reply
+    }
+
+    #[tokio::test]
+    async fn test_exception_report() {
+        let mut request_reply = reply_packet(2, 0, &[0x0, 0x0, 0x0, 0x3]);
+        request_reply.extend_from_slice(&exception_event(100, 0x40, true));
+        let mut resume_reply = reply_packet(5, 0, &[]);
+        resume_reply.extend_from_slice(&exception_event(101, 0x41, false));
+        let mock_stream = MockStreamBuilder::default()
+            .response_bytes(
+                &command_packet(2, [0xF, 0x1], &exception_request_out()),
+                &request_reply,
+            )
+            .command_reply(3, [0x9, 0x1], &id(0x40), &reference_type_reply())
+            .command_reply(
+                4,
+                [0x2, 0x1],
+                &id(0x30),
+                &jdwp_string("Ljava/lang/IllegalStateException;"),
+            )
+            .response_bytes(&command_packet(5, [0xB, 0x3], &id(0x5)), &resume_reply)
+            .command_reply(6, [0x9, 0x1], &id(0x41), &reference_type_reply())
+            .command_reply(7, [0xB, 0x3], &id(0x5), &[])
+            .command_reply(8, [0xF, 0x2], &[0x4, 0x0, 0x0, 0x0, 0x3], &[])
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let mut stats = ExceptionStats::start(client, &["java.*"]).await.unwrap();
+        assert_eq!(stats.request_id(), Some(3));
+        let report = stats.next_report(Duration::from_millis(50)).await.unwrap();
+        assert_eq!(report.total, 2);
+        assert_eq!(report.counts.len(), 1);
+        assert_eq!(
+            report.counts[0].type_name,
+            "java.lang.IllegalStateException"
+        );
+        assert_eq!(report.counts[0].location.index, 4);
+        assert_eq!(report.counts[0].uncaught, 1);
+        assert_eq!(
+            report.by_type(),
+            vec![(String::from("java.lang.IllegalStateException"), 2)]
+        );
+
+        // The next period starts empty
+        assert_eq!(stats.report().total, 0);
+        stats.stop().await.unwrap();
+        assert_eq!(stats.request_id(), None);
+    }
+}
@@ -0,0 +1,183 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod field_watch_tests {
+    use crate::common::{MockStreamBuilder, command_packet, id, jdwp_string, reply_packet};
+    use jdwp_client::{
+        Error, FieldOwner, JdwpClient, JdwpErrorCode, JdwpValue, Tag, TaggedObjectId,
+        VariableLengthId,
+    };
+    use std::time::Duration;
+
+    fn get_values_out(owner: u64, fields: &[u64]) -> Vec<u8> {
+        let mut out = id(owner).to_vec();
+        out.extend_from_slice(&(fields.len() as u32).to_be_bytes());
+        for field in fields {
+            out.extend_from_slice(&id(*field));
+        }
+        out
+    }
+
+    fn int_values_reply(values: &[i32]) -> Vec<u8> {
+        let mut reply = (values.len() as u32).to_be_bytes().to_vec();
+        for value in values {
+            reply.push(b'I');
+            reply.extend_from_slice(&value.to_be_bytes());
+        }
+        reply
+    }
+
+    #[tokio::test]
+    async fn test_poll_fields_of_object() {
+        let out = get_values_out(0x30, &[0x1, 0x2]);
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x9, 0x2], &out, &int_values_reply(&[1, 2]))
+            .command_reply(3, [0x9, 0x2], &out, &int_values_reply(&[3, 4]))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let mut watch = client.poll_fields(
+            FieldOwner::Object(VariableLengthId { value: 0x30 }),
+            vec![
+                VariableLengthId { value: 0x1 },
+                VariableLengthId { value: 0x2 },
+            ],
+            Duration::from_millis(5),
+        );
+        let first = watch.next_sample().await.unwrap().unwrap();
+        let second = watch.next_sample().await.unwrap().unwrap();
+        assert_eq!(first.values, vec![JdwpValue::Int(1), JdwpValue::Int(2)]);
+        assert_eq!(second.values, vec![JdwpValue::Int(3), JdwpValue::Int(4)]);
+        assert!(second.timestamp >= first.timestamp);
+    }
+
+    #[tokio::test]
+    async fn test_poll_static_field_changes() {
+        let out = get_values_out(0x10, &[0x1]);
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x2, 0x6], &out, &int_values_reply(&[1]))
+            .command_reply(3, [0x2, 0x6], &out, &int_values_reply(&[1]))
+            .command_reply(4, [0x2, 0x6], &out, &int_values_reply(&[2]))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let mut watch = client
+            .poll_field(
+                FieldOwner::Class(VariableLengthId { value: 0x10 }),
+                VariableLengthId { value: 0x1 },
+                Duration::from_millis(5),
+            )
+            .only_changes();
+        let values: Vec<_> = [
+            watch.next_sample().await.unwrap().unwrap(),
+            watch.next_sample().await.unwrap().unwrap(),
+        ]
+        .into_iter()
+        .map(|sample| sample.values)
+        .collect();
+        assert_eq!(
+            values,
+            vec![vec![JdwpValue::Int(1)], vec![JdwpValue::Int(2)]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_poll_stops_after_error() {
+        let mock_stream = MockStreamBuilder::default()
+            .response_bytes(
+                &command_packet(2, [0x9, 0x2], &get_values_out(0x30, &[0x1])),
+                &reply_packet(2, 20, &[]),
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let mut watch = client.poll_field(
+            FieldOwner::Object(VariableLengthId { value: 0x30 }),
+            VariableLengthId { value: 0x1 },
+            Duration::from_millis(5),
+        );
+        assert!(matches!(
+            watch.next_sample().await,
+            Some(Err(Error::JdwpError(JdwpErrorCode::InvalidObject)))
+        ));
+        assert!(watch.next_sample().await.is_none());
+    }
+
+    fn fields_reply(fields: &[(u64, &str, &str, i32)]) -> Vec<u8> {
+        let mut reply = (fields.len() as u32).to_be_bytes().to_vec();
+        for (field_id, name, signature, mod_bits) in fields {
+            reply.extend_from_slice(&id(*field_id));
+            reply.extend(jdwp_string(name));
+            reply.extend(jdwp_string(signature));
+            reply.extend_from_slice(&mod_bits.to_be_bytes());
+        }
+        reply
+    }
+
+    #[tokio::test]
+    async fn test_get_fields_by_name() {
+        let object = id(0x30);
+        let reference_type = [&[1u8][..], &id(0x10)].concat();
+        let mut values = 2u32.to_be_bytes().to_vec();
+        values.push(b's');
+        values.extend_from_slice(&id(0x40));
+        values.push(b'I');
+        values.extend_from_slice(&8080i32.to_be_bytes());
+        let out = get_values_out(0x30, &[0x2, 0x1]);
+
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x9, 0x1], &object, &reference_type)
+            .command_reply(
+                3,
+                [0x2, 0x4],
+                &id(0x10),
+                &fields_reply(&[(0x1, "port", "I", 0x2), (0x3, "COUNT", "I", 0x8)]),
+            )
+            .command_reply(4, [0x3, 0x1], &id(0x10), &id(0x11))
+            .command_reply(
+                5,
+                [0x2, 0x4],
+                &id(0x11),
+                &fields_reply(&[(0x2, "name", "Ljava/lang/String;", 0x4)]),
+            )
+            .command_reply(6, [0x3, 0x1], &id(0x11), &id(0x0))
+            .command_reply(7, [0x9, 0x2], &out, &values)
+            // The fields are cached
+            .command_reply(8, [0x9, 0x1], &object, &reference_type)
+            .command_reply(9, [0x9, 0x2], &out, &values)
+            .command_reply(10, [0x9, 0x1], &object, &reference_type)
+            .command_reply(
+                11,
+                [0x2, 0x6],
+                &get_values_out(0x10, &[0x3]),
+                &int_values_reply(&[3]),
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let owner = FieldOwner::Object(VariableLengthId { value: 0x30 });
+        for _ in 0..2 {
+            let fields = client.get_fields(owner, &["name", "port"]).await.unwrap();
+            assert_eq!(fields.len(), 2);
+            assert_eq!(
+                fields["name"],
+                JdwpValue::Object(TaggedObjectId {
+                    tag: Tag::String,
+                    object_id: VariableLengthId { value: 0x40 },
+                })
+            );
+            assert_eq!(fields["port"], JdwpValue::Int(8080));
+        }
+
+        // Static fields are only read from classes
+        assert!(matches!(
+            client.get_fields(owner, &["COUNT"]).await,
+            Err(Error::FieldNotFound { name }) if name == "COUNT"
+        ));
+        let class = FieldOwner::Class(VariableLengthId { value: 0x10 });
+        let fields = client.get_fields(class, &["COUNT"]).await.unwrap();
+        assert_eq!(fields["COUNT"], JdwpValue::Int(3));
+    }
+}
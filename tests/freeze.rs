@@ -0,0 +1,93 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod freeze_tests {
+    use crate::common::{MockStreamBuilder, id, jdwp_string};
+    use jdwp_client::{FreezeWarning, JdwpClient, ThreadStatus, VariableLengthId};
+
+    fn status_reply(status: i32, suspended: bool) -> Vec<u8> {
+        let mut reply = status.to_be_bytes().to_vec();
+        reply.extend_from_slice(&(suspended as i32).to_be_bytes());
+        reply
+    }
+
+    fn frames_out(thread_id: u64) -> Vec<u8> {
+        let mut out = id(thread_id).to_vec();
+        out.extend_from_slice(&[0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1]);
+        out
+    }
+
+    fn frames_reply(method_id: u64) -> Vec<u8> {
+        let mut reply = vec![0x0, 0x0, 0x0, 0x1];
+        reply.extend_from_slice(&id(0x70));
+        reply.push(0x1);
+        reply.extend_from_slice(&id(0x10));
+        reply.extend_from_slice(&id(method_id));
+        reply.extend_from_slice(&id(0x0));
+        reply
+    }
+
+    fn methods_reply() -> Vec<u8> {
+        let mut reply = vec![0x0, 0x0, 0x0, 0x2];
+        for (method_id, name, mod_bits) in [(0x5, "read0", 0x101i32), (0x6, "run", 0x1)] {
+            reply.extend_from_slice(&id(method_id));
+            reply.extend_from_slice(&jdwp_string(name));
+            reply.extend_from_slice(&jdwp_string("()V"));
+            reply.extend_from_slice(&mod_bits.to_be_bytes());
+        }
+        reply
+    }
+
+    #[tokio::test]
+    async fn test_freeze_and_thaw() {
+        let mut all_threads = vec![0x0, 0x0, 0x0, 0x2];
+        all_threads.extend_from_slice(&id(0x1));
+        all_threads.extend_from_slice(&id(0x2));
+
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x8], &[], &[])
+            .command_reply(3, [0x1, 0x4], &[], &all_threads)
+            .command_reply(4, [0xB, 0x4], &id(0x1), &status_reply(1, true))
+            .command_reply(5, [0xB, 0xC], &id(0x1), &[0x0, 0x0, 0x0, 0x1])
+            .command_reply(6, [0xB, 0x6], &frames_out(0x1), &frames_reply(0x5))
+            .command_reply(7, [0x2, 0x5], &id(0x10), &methods_reply())
+            .command_reply(8, [0xB, 0x1], &id(0x1), &jdwp_string("reader"))
+            .command_reply(9, [0xB, 0x4], &id(0x2), &status_reply(4, true))
+            // Also suspended by someone else
+            .command_reply(10, [0xB, 0xC], &id(0x2), &[0x0, 0x0, 0x0, 0x2])
+            .command_reply(11, [0xB, 0x6], &frames_out(0x2), &frames_reply(0x6))
+            .command_reply(12, [0xB, 0x1], &id(0x2), &jdwp_string("main"))
+            .command_reply(13, [0x1, 0x9], &[], &[])
+            .command_reply(14, [0xB, 0xC], &id(0x1), &[0x0, 0x0, 0x0, 0x0])
+            .command_reply(15, [0xB, 0xC], &id(0x2), &[0x0, 0x0, 0x0, 0x1])
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let freeze = client.freeze().await.unwrap();
+        assert!(client.is_vm_suspended());
+        assert_eq!(freeze.threads.len(), 2);
+        assert_eq!(freeze.threads[0].name, "reader");
+        assert!(freeze.threads[0].in_native);
+        assert_eq!(freeze.threads[1].status, ThreadStatus::Wait);
+        assert!(!freeze.threads[1].in_native);
+        assert_eq!(
+            freeze.warnings,
+            vec![
+                FreezeWarning::InNative {
+                    thread_id: VariableLengthId { value: 0x1 }
+                },
+                FreezeWarning::SuspendCountMismatch {
+                    thread_id: VariableLengthId { value: 0x2 },
+                    expected: 1,
+                    actual: 2
+                },
+            ]
+        );
+        assert!(!freeze.is_complete());
+
+        assert_eq!(client.thaw(freeze).await.unwrap(), vec![]);
+        assert!(!client.is_vm_suspended());
+    }
+}
@@ -0,0 +1,191 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod heap_tests {
+    use crate::common::{MockStreamBuilder, command_packet, id, jdwp_string, reply_packet};
+    use jdwp_client::{
+        ClassGrowth, FieldMatch, FoundString, JdwpClient, JdwpValue, Pattern, VariableLengthId,
+    };
+
+    fn all_classes_reply(classes: &[(u64, &str)]) -> Vec<u8> {
+        let mut reply = (classes.len() as i32).to_be_bytes().to_vec();
+        for (class_id, signature) in classes {
+            reply.push(0x1);
+            reply.extend_from_slice(&id(*class_id));
+            reply.extend_from_slice(&jdwp_string(signature));
+            reply.extend_from_slice(&7i32.to_be_bytes());
+        }
+        reply
+    }
+
+    fn instance_counts(values: &[i64]) -> Vec<u8> {
+        let mut data = (values.len() as i32).to_be_bytes().to_vec();
+        for value in values {
+            data.extend_from_slice(&value.to_be_bytes());
+        }
+        data
+    }
+
+    #[tokio::test]
+    async fn test_class_histogram_diff() {
+        let classes = all_classes_reply(&[
+            (0x10, "Lcom/example/Order;"),
+            (0x11, "[B"),
+            (0x12, "Lcom/example/Unused;"),
+        ]);
+        let mut counts_out = 3i32.to_be_bytes().to_vec();
+        for class_id in [0x10, 0x11, 0x12] {
+            counts_out.extend_from_slice(&id(class_id));
+        }
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x3], &[], &classes)
+            .command_reply(3, [0x1, 0x15], &counts_out, &instance_counts(&[5, 40, 0]))
+            .command_reply(4, [0x1, 0x3], &[], &classes)
+            .command_reply(5, [0x1, 0x15], &counts_out, &instance_counts(&[120, 40, 2]))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let before = client.class_histogram().await.unwrap();
+        let names: Vec<_> = before
+            .entries
+            .iter()
+            .map(|entry| (entry.class_name.as_str(), entry.instances))
+            .collect();
+        assert_eq!(names, vec![("byte[]", 40), ("com.example.Order", 5)]);
+
+        let after = client.class_histogram().await.unwrap();
+        let growth = before.diff(&after);
+        assert_eq!(
+            growth,
+            vec![
+                ClassGrowth {
+                    class_id: VariableLengthId { value: 0x10 },
+                    class_name: String::from("com.example.Order"),
+                    before: 5,
+                    after: 120,
+                },
+                ClassGrowth {
+                    class_id: VariableLengthId { value: 0x12 },
+                    class_name: String::from("com.example.Unused"),
+                    before: 0,
+                    after: 2,
+                },
+            ]
+        );
+        assert_eq!(growth[0].delta(), 115);
+        assert_eq!(after.diff(&before)[0].delta(), -2);
+    }
+
+    #[tokio::test]
+    async fn test_find_strings() {
+        let mut class_reply = vec![0x0, 0x0, 0x0, 0x1, 0x1];
+        class_reply.extend_from_slice(&id(0x10));
+        class_reply.extend_from_slice(&7i32.to_be_bytes());
+        let mut instances_out = id(0x10).to_vec();
+        instances_out.extend_from_slice(&0i32.to_be_bytes());
+        let mut instances = 4i32.to_be_bytes().to_vec();
+        for string_id in [0x60, 0x61, 0x62, 0x63] {
+            instances.push(b's');
+            instances.extend_from_slice(&id(string_id));
+        }
+
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(
+                2,
+                [0x1, 0x2],
+                &jdwp_string("Ljava/lang/String;"),
+                &class_reply,
+            )
+            .command_reply(3, [0x2, 0x10], &instances_out, &instances)
+            .command_reply(4, [0xA, 0x1], &id(0x60), &jdwp_string("hello"))
+            // Collected between the Instances and Value commands
+            .response_bytes(
+                &command_packet(5, [0xA, 0x1], &id(0x61)),
+                &reply_packet(5, 20, &[]),
+            )
+            .command_reply(6, [0xA, 0x1], &id(0x62), &jdwp_string("password=hunter2"))
+            .command_reply(7, [0xA, 0x1], &id(0x63), &jdwp_string("PASSWORD=x"))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let pattern = Pattern::new("(?i)^password=").unwrap();
+        assert_eq!(
+            client.find_strings(&pattern, 10).await.unwrap(),
+            vec![
+                FoundString {
+                    string_id: VariableLengthId { value: 0x62 },
+                    value: String::from("password=hunter2"),
+                },
+                FoundString {
+                    string_id: VariableLengthId { value: 0x63 },
+                    value: String::from("PASSWORD=x"),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_scan_field_values() {
+        let mut class_reply = vec![0x0, 0x0, 0x0, 0x1, 0x1];
+        class_reply.extend_from_slice(&id(0x10));
+        class_reply.extend_from_slice(&7i32.to_be_bytes());
+        let mut fields = 2i32.to_be_bytes().to_vec();
+        for (field_id, name, mod_bits) in [(0x50, "ACTIVE", 0x9i32), (0x51, "userId", 0x2)] {
+            fields.extend_from_slice(&id(field_id));
+            fields.extend_from_slice(&jdwp_string(name));
+            fields.extend_from_slice(&jdwp_string("I"));
+            fields.extend_from_slice(&mod_bits.to_be_bytes());
+        }
+        let mut instances_out = id(0x10).to_vec();
+        instances_out.extend_from_slice(&0i32.to_be_bytes());
+        let mut instances = 3i32.to_be_bytes().to_vec();
+        for object_id in [0x30, 0x31, 0x32] {
+            instances.push(b'L');
+            instances.extend_from_slice(&id(object_id));
+        }
+        let get_values_out =
+            |object_id: u64| [&id(object_id)[..], &1i32.to_be_bytes(), &id(0x51)].concat();
+        let int_value = |value: i32| [&1i32.to_be_bytes()[..], b"I", &value.to_be_bytes()].concat();
+
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(
+                2,
+                [0x1, 0x2],
+                &jdwp_string("Lcom/example/Session;"),
+                &class_reply,
+            )
+            .command_reply(3, [0x2, 0x4], &id(0x10), &fields)
+            .command_reply(4, [0x2, 0x10], &instances_out, &instances)
+            .command_reply(5, [0x9, 0x2], &get_values_out(0x30), &int_value(7))
+            .command_reply(6, [0x9, 0x2], &get_values_out(0x31), &int_value(8))
+            .command_reply(7, [0x9, 0x2], &get_values_out(0x32), &int_value(7))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let mut scan = client
+            .scan_field_values("Lcom/example/Session;", "userId", |value| {
+                *value == JdwpValue::Int(7)
+            })
+            .await
+            .unwrap();
+        let mut found = Vec::new();
+        while let Some(found_match) = scan.next_match().await {
+            found.push(found_match.unwrap());
+        }
+        assert_eq!(
+            found,
+            vec![
+                FieldMatch {
+                    object_id: VariableLengthId { value: 0x30 },
+                    value: JdwpValue::Int(7),
+                },
+                FieldMatch {
+                    object_id: VariableLengthId { value: 0x32 },
+                    value: JdwpValue::Int(7),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,208 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod hooks_tests {
+    use crate::common::{
+        command_packet, event_packet, id, jdwp_string, reply_packet, thread_start_event,
+    };
+    use jdwp_client::{
+        EventKind, HookAction, Hooks, JdwpClient, JdwpValue, Tag, TaggedObjectId, VariableLengthId,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+    use tokio::sync::{mpsc, oneshot};
+
+    async fn handshake(vm: &mut DuplexStream) {
+        let mut handshake = [0u8; 14];
+        vm.read_exact(&mut handshake).await.unwrap();
+        vm.write_all(&handshake).await.unwrap();
+
+        let mut id_sizes = [0u8; 11];
+        vm.read_exact(&mut id_sizes).await.unwrap();
+        let sizes: Vec<u8> = [8i32; 5].iter().flat_map(|s| s.to_be_bytes()).collect();
+        vm.write_all(&reply_packet(1, 0, &sizes)).await.unwrap();
+    }
+
+    async fn read_packet(vm: &mut DuplexStream) -> Vec<u8> {
+        let mut length = [0u8; 4];
+        vm.read_exact(&mut length).await.unwrap();
+        let mut packet = length.to_vec();
+        packet.resize(u32::from_be_bytes(length) as usize, 0);
+        vm.read_exact(&mut packet[4..]).await.unwrap();
+        packet
+    }
+
+    fn location() -> Vec<u8> {
+        let mut location = vec![0x1];
+        location.extend_from_slice(&id(0x10));
+        location.extend_from_slice(&id(0x1));
+        location.extend_from_slice(&4u64.to_be_bytes());
+        location
+    }
+
+    fn breakpoint_event(request_id: i32, thread_id: u64) -> Vec<u8> {
+        let mut event = vec![0x2];
+        event.extend_from_slice(&request_id.to_be_bytes());
+        event.extend_from_slice(&id(thread_id));
+        event.extend_from_slice(&location());
+        event
+    }
+
+    fn frames_reply() -> Vec<u8> {
+        let mut reply = 1i32.to_be_bytes().to_vec();
+        reply.extend_from_slice(&id(0x20));
+        reply.extend_from_slice(&location());
+        reply
+    }
+
+    fn variable(code_index: u64, name: &str, signature: &str, length: u32, slot: i32) -> Vec<u8> {
+        let mut variable = code_index.to_be_bytes().to_vec();
+        variable.extend_from_slice(&jdwp_string(name));
+        variable.extend_from_slice(&jdwp_string(signature));
+        variable.extend_from_slice(&length.to_be_bytes());
+        variable.extend_from_slice(&slot.to_be_bytes());
+        variable
+    }
+
+    /// Answers one command, checking it is the expected one
+    async fn expect(vm: &mut DuplexStream, id: u32, command: [u8; 2], out: &[u8], reply: &[u8]) {
+        assert_eq!(read_packet(vm).await, command_packet(id, command, out));
+        vm.write_all(&reply_packet(id, 0, reply)).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_breakpoint_hook_reads_frame_and_resumes() {
+        let (stream, mut vm) = tokio::io::duplex(1024);
+        let (ready_sender, ready) = oneshot::channel();
+        let fake_vm = tokio::spawn(async move {
+            handshake(&mut vm).await;
+            ready.await.unwrap();
+            vm.write_all(&event_packet(100, 0x1, &[breakpoint_event(7, 5)]))
+                .await
+                .unwrap();
+
+            let frames_out = [&id(5)[..], &0i32.to_be_bytes(), &1i32.to_be_bytes()].concat();
+            let frame_out = [id(5), id(0x20)].concat();
+            expect(&mut vm, 2, [0xb, 0x6], &frames_out, &frames_reply()).await;
+            let this = [&[b'L'][..], &id(0x42)].concat();
+            expect(&mut vm, 3, [0x10, 0x3], &frame_out, &this).await;
+
+            expect(&mut vm, 4, [0xb, 0x6], &frames_out, &frames_reply()).await;
+            let table = [
+                &1i32.to_be_bytes()[..],
+                &3i32.to_be_bytes(),
+                &variable(0, "this", "Lcom/example/Main;", 10, 0),
+                &variable(2, "count", "I", 8, 1),
+                &variable(6, "late", "J", 4, 2),
+            ]
+            .concat();
+            expect(
+                &mut vm,
+                5,
+                [0x6, 0x2],
+                &[id(0x10), id(0x1)].concat(),
+                &table,
+            )
+            .await;
+            let values_out = [
+                &frame_out[..],
+                &2i32.to_be_bytes(),
+                &0i32.to_be_bytes(),
+                b"L",
+                &1i32.to_be_bytes(),
+                b"I",
+            ]
+            .concat();
+            let values = [
+                &2i32.to_be_bytes()[..],
+                b"L",
+                &id(0x42),
+                b"I",
+                &7i32.to_be_bytes(),
+            ]
+            .concat();
+            expect(&mut vm, 6, [0x10, 0x1], &values_out, &values).await;
+
+            expect(&mut vm, 7, [0xb, 0x3], &id(5), &[]).await;
+        });
+
+        let client = JdwpClient::new(stream).await.unwrap();
+        let (sender, mut results) = mpsc::unbounded_channel();
+        let mut hooks = Hooks::new(&client);
+        hooks.on(EventKind::Breakpoint, move |ctx| {
+            let sender = sender.clone();
+            async move {
+                let this = ctx.this().await?;
+                let locals = ctx.locals().await?;
+                sender.send((ctx.thread(), this, locals)).unwrap();
+                Ok(HookAction::Resume)
+            }
+        });
+        let run = tokio::spawn(hooks.run());
+        ready_sender.send(()).unwrap();
+
+        let (thread, this, locals) = results.recv().await.unwrap();
+        let object = TaggedObjectId {
+            tag: Tag::Object,
+            object_id: VariableLengthId { value: 0x42 },
+        };
+        assert_eq!(thread, Some(VariableLengthId { value: 5 }));
+        assert_eq!(this, Some(object));
+        assert_eq!(
+            locals,
+            vec![
+                (String::from("this"), JdwpValue::Object(object)),
+                (String::from("count"), JdwpValue::Int(7)),
+            ]
+        );
+
+        fake_vm.await.unwrap();
+        run.abort();
+    }
+
+    #[tokio::test]
+    async fn test_keep_suspended() {
+        let (stream, mut vm) = tokio::io::duplex(1024);
+        let (ready_sender, ready) = oneshot::channel();
+        let fake_vm = tokio::spawn(async move {
+            handshake(&mut vm).await;
+            ready.await.unwrap();
+            vm.write_all(&event_packet(100, 0x1, &[breakpoint_event(7, 5)]))
+                .await
+                .unwrap();
+            vm.write_all(&event_packet(101, 0x0, &[thread_start_event(8, 6)]))
+                .await
+                .unwrap();
+
+            let mut rest = Vec::new();
+            vm.read_to_end(&mut rest).await.unwrap();
+            rest
+        });
+
+        let client = JdwpClient::new(stream).await.unwrap();
+        let (sender, mut results) = mpsc::unbounded_channel();
+        let mut hooks = Hooks::new(&client);
+        let breakpoints = sender.clone();
+        hooks
+            .on(EventKind::Breakpoint, move |ctx| {
+                breakpoints.send(ctx.event().kind()).unwrap();
+                async { Ok(HookAction::KeepSuspended) }
+            })
+            .on(EventKind::ThreadStart, move |ctx| {
+                sender.send(ctx.event().kind()).unwrap();
+                async { Ok(HookAction::Resume) }
+            });
+        let run = tokio::spawn(hooks.run());
+        ready_sender.send(()).unwrap();
+
+        assert_eq!(results.recv().await, Some(EventKind::Breakpoint));
+        assert_eq!(results.recv().await, Some(EventKind::ThreadStart));
+
+        // Neither the kept breakpoint nor the unsuspended thread start resumes anything
+        run.abort();
+        _ = run.await;
+        drop(client);
+        assert!(fake_vm.await.unwrap().is_empty());
+    }
+}
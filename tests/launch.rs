@@ -0,0 +1,117 @@
+#![cfg(feature = "tokio")]
+
+#[cfg(test)]
+mod common;
+
+#[cfg(all(test, unix))]
+mod launch_tests {
+    use crate::common::{command_packet, reply_packet};
+    use jdwp_client::{Debugger, LAUNCH_AGENT_OPTION, OutputStream};
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// Writes a script standing in for `java`, which records its arguments, prints the line a
+    /// VM prints when its agent listens on `address` and then stays alive
+    fn fake_java(name: &str, address: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("jdwp-launch-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let script = dir.join("java");
+        std::fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\necho \"$@\" > {}\necho Starting\necho \"Listening for transport dt_socket at address: {}\"\necho Started\necho Warning >&2\nexec sleep 30\n",
+                dir.join("args").display(),
+                address
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        script
+    }
+
+    /// Accepts the debugger like an agent would, answering the handshake and IDSizes
+    fn accept_debugger(listener: TcpListener) -> tokio::task::JoinHandle<TcpStream> {
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut handshake = [0u8; 14];
+            stream.read_exact(&mut handshake).await.unwrap();
+            stream.write_all(&handshake).await.unwrap();
+            let mut id_sizes = vec![0u8; 11];
+            stream.read_exact(&mut id_sizes).await.unwrap();
+            assert_eq!(id_sizes, command_packet(1, [0x1, 0x7], &[]));
+            let sizes: Vec<u8> = [8i32; 5].iter().flat_map(|s| s.to_be_bytes()).collect();
+            stream.write_all(&reply_packet(1, 0, &sizes)).await.unwrap();
+            stream
+        })
+    }
+
+    #[tokio::test]
+    async fn test_launch_attaches_to_listening_vm() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let vm = accept_debugger(listener);
+
+        let script = fake_java("attach", &address);
+        let (debugger, mut launched) = Debugger::launch([
+            script.to_str().unwrap(),
+            "-cp",
+            "app.jar",
+            "com.example.Main",
+        ])
+        .await
+        .unwrap();
+        let _stream = vm.await.unwrap();
+
+        assert_eq!(launched.address(), address);
+        assert!(launched.try_wait().unwrap().is_none());
+        assert_eq!(debugger.client().pending_request_count(), 0);
+        let args = std::fs::read_to_string(script.with_file_name("args")).unwrap();
+        assert_eq!(
+            args.trim_end(),
+            format!("{} -cp app.jar com.example.Main", LAUNCH_AGENT_OPTION)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_launch_capturing_output() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let vm = accept_debugger(listener);
+
+        let script = fake_java("capture", &address);
+        let (_debugger, mut launched) =
+            Debugger::launch_capturing_output([script.to_str().unwrap(), "com.example.Main"])
+                .await
+                .unwrap();
+        let _stream = vm.await.unwrap();
+
+        let mut output = Vec::new();
+        for _ in 0..3 {
+            output.push(launched.next_output().await.unwrap());
+        }
+        output.sort_by_key(|line| line.timestamp);
+        let stdout: Vec<&str> = output
+            .iter()
+            .filter(|line| line.stream == OutputStream::Stdout)
+            .map(|line| line.line.as_str())
+            .collect();
+        assert_eq!(stdout, ["Starting", "Started"]);
+        assert!(
+            output
+                .iter()
+                .any(|line| line.stream == OutputStream::Stderr && line.line == "Warning")
+        );
+        assert!(launched.try_next_output().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_launch_fails_when_vm_exits() {
+        let result = Debugger::launch(["/bin/sh", "-c", "exit 1"]).await;
+        assert!(matches!(
+            result,
+            Err(jdwp_client::Error::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof
+        ));
+    }
+}
@@ -0,0 +1,122 @@
+#![cfg(feature = "tokio")]
+
+#[cfg(test)]
+mod mock_tests {
+    use jdwp_client::{
+        Command, Error, Event, EventComposite, JdwpClient, JdwpErrorCode, MockJdwpTransport,
+        MockMismatch, SuspendPolicy, VariableLengthId,
+    };
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    const THREAD: VariableLengthId = VariableLengthId { value: 0x20 };
+
+    fn thread_start() -> EventComposite {
+        EventComposite {
+            suspend_policy: SuspendPolicy::None,
+            events: vec![Event::ThreadStart {
+                request_id: 1,
+                thread: THREAD,
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ordered_expectations() {
+        let transport = MockJdwpTransport::new()
+            .expect_command(Command::VirtualMachineSuspend)
+            .reply_with(&[])
+            .send_events(&thread_start())
+            .expect_command(Command::ThreadReferenceSuspendCount)
+            .with_data(&THREAD.value.to_be_bytes())
+            .reply_with(&[0x0, 0x0, 0x0, 0x1])
+            .expect_command(Command::VirtualMachineResume)
+            .reply_error(JdwpErrorCode::VmDead)
+            .close();
+        let handle = transport.handle();
+        let client = JdwpClient::new(transport).await.unwrap();
+
+        client.vm_suspend().await.unwrap();
+        assert_eq!(client.next_event().await, Some(thread_start()));
+        assert_eq!(client.thread_get_suspend_count(THREAD).await.unwrap(), 1);
+        assert!(matches!(
+            client.vm_resume().await,
+            Err(Error::JdwpError(JdwpErrorCode::VmDead))
+        ));
+        // The stream was closed after the last reply
+        assert_eq!(client.next_event().await, None);
+        handle.assert_done();
+    }
+
+    #[tokio::test]
+    async fn test_unexpected_command() {
+        let transport = MockJdwpTransport::new()
+            .expect_command(Command::VirtualMachineSuspend)
+            .reply_with(&[]);
+        let handle = transport.handle();
+        let client = JdwpClient::new(transport).await.unwrap();
+
+        assert!(client.vm_resume().await.is_err());
+        assert_eq!(
+            handle.mismatches(),
+            vec![MockMismatch::UnexpectedCommand {
+                expected: Some(Command::VirtualMachineSuspend),
+                actual: Command::VirtualMachineResume,
+            }]
+        );
+        assert_eq!(handle.remaining_expectations(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_latency() {
+        let transport = MockJdwpTransport::new()
+            .with_latency(Duration::from_secs(2))
+            .expect_command(Command::VirtualMachineSuspend)
+            .reply_with(&[]);
+        let client = JdwpClient::new(transport).await.unwrap();
+
+        let start = Instant::now();
+        client.vm_suspend().await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+    }
+
+    #[tokio::test]
+    async fn test_short_writes() {
+        let transport = MockJdwpTransport::new()
+            .short_writes(3)
+            .expect_command(Command::ThreadReferenceSuspendCount)
+            .with_data(&THREAD.value.to_be_bytes())
+            .reply_with(&[0x0, 0x0, 0x0, 0x2]);
+        let handle = transport.handle();
+        let client = JdwpClient::new(transport).await.unwrap();
+
+        assert_eq!(client.thread_get_suspend_count(THREAD).await.unwrap(), 2);
+        handle.assert_done();
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_mid_packet() {
+        // The handshake and the IDSizes reply, then half of the next reply
+        let transport = MockJdwpTransport::new()
+            .drop_after_bytes(14 + 31 + 6)
+            .expect_command(Command::ThreadReferenceSuspendCount)
+            .reply_with(&[0x0, 0x0, 0x0, 0x2]);
+        let client = JdwpClient::new(transport).await.unwrap();
+
+        assert!(client.thread_get_suspend_count(THREAD).await.is_err());
+        assert_eq!(client.next_event().await, None);
+        assert!(client.vm_suspend().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_header() {
+        let transport = MockJdwpTransport::new()
+            .expect_command(Command::VirtualMachineSuspend)
+            .reply_with_length(&[], 4);
+        let client = JdwpClient::new(transport).await.unwrap();
+
+        assert!(client.vm_suspend().await.is_err());
+        // The reader gave up on the stream, which is out of sync
+        assert_eq!(client.next_event().await, None);
+    }
+}
@@ -0,0 +1,163 @@
+#![cfg(feature = "tokio")]
+
+#[cfg(test)]
+mod common;
+
+#[cfg(test)]
+mod mux_tests {
+    use crate::common::{
+        MockStreamBuilder, command_packet, event_packet, reply_packet, thread_death_event,
+        thread_start_event,
+    };
+    use jdwp_client::{JdwpClient, MuxServer};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    const THREAD_START_REQUEST: [u8; 6] = [6, 0, 0, 0, 0, 0];
+
+    async fn start(vm: MockStreamBuilder) -> String {
+        let client = JdwpClient::new(vm.build()).await.unwrap();
+        let server = MuxServer::bind(client, "127.0.0.1:0").await.unwrap();
+        let address = server.local_addr().unwrap().to_string();
+        tokio::spawn(server.run());
+        address
+    }
+
+    async fn debugger(address: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream.write_all(b"JDWP-Handshake").await.unwrap();
+        let mut handshake = [0u8; 14];
+        stream.read_exact(&mut handshake).await.unwrap();
+        assert_eq!(&handshake, b"JDWP-Handshake");
+        stream
+    }
+
+    async fn read_packet(stream: &mut TcpStream) -> Vec<u8> {
+        let mut length = [0u8; 4];
+        stream.read_exact(&mut length).await.unwrap();
+        let mut packet = length.to_vec();
+        packet.resize(u32::from_be_bytes(length) as usize, 0);
+        stream.read_exact(&mut packet[4..]).await.unwrap();
+        packet
+    }
+
+    #[tokio::test]
+    async fn test_forward_restores_packet_ids() {
+        let vm = MockStreamBuilder::default().command_reply(2, [1, 1], &[], &[1, 2, 3]);
+        let address = start(vm).await;
+
+        let mut debugger = debugger(&address).await;
+        debugger
+            .write_all(&command_packet(77, [1, 1], &[]))
+            .await
+            .unwrap();
+        assert_eq!(
+            read_packet(&mut debugger).await,
+            reply_packet(77, 0, &[1, 2, 3])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_command_after_clear_all_breakpoints_waits_for_it() {
+        const BREAKPOINT_REQUEST: [u8; 6] = [2, 0, 0, 0, 0, 0];
+        let vm = MockStreamBuilder::default()
+            .command_reply(2, [15, 1], &BREAKPOINT_REQUEST, &5i32.to_be_bytes())
+            .command_reply(3, [15, 1], &BREAKPOINT_REQUEST, &6i32.to_be_bytes())
+            .command_reply(4, [15, 2], &[2, 0, 0, 0, 5], &[])
+            .command_reply(5, [15, 2], &[2, 0, 0, 0, 6], &[])
+            .command_reply(6, [1, 1], &[], &[1]);
+        let address = start(vm).await;
+
+        let mut debugger = debugger(&address).await;
+        for id in [50, 51] {
+            debugger
+                .write_all(&command_packet(id, [15, 1], &BREAKPOINT_REQUEST))
+                .await
+                .unwrap();
+            read_packet(&mut debugger).await;
+        }
+        // Version is only sent once both breakpoints were cleared
+        let mut commands = command_packet(52, [15, 3], &[]);
+        commands.extend(command_packet(53, [1, 1], &[]));
+        debugger.write_all(&commands).await.unwrap();
+        assert_eq!(read_packet(&mut debugger).await, reply_packet(52, 0, &[]));
+        assert_eq!(read_packet(&mut debugger).await, reply_packet(53, 0, &[1]));
+    }
+
+    #[tokio::test]
+    async fn test_events_go_to_request_owner() {
+        let vm = MockStreamBuilder::default()
+            .command_reply(2, [15, 1], &THREAD_START_REQUEST, &5i32.to_be_bytes())
+            .response_bytes(&command_packet(3, [15, 1], &THREAD_START_REQUEST), &{
+                let mut output = reply_packet(3, 0, &6i32.to_be_bytes());
+                output.extend(event_packet(
+                    100,
+                    0,
+                    &[thread_start_event(6, 2), thread_death_event(5, 3)],
+                ));
+                output
+            });
+        let address = start(vm).await;
+        let mut first = debugger(&address).await;
+        let mut second = debugger(&address).await;
+
+        first
+            .write_all(&command_packet(10, [15, 1], &THREAD_START_REQUEST))
+            .await
+            .unwrap();
+        assert_eq!(
+            read_packet(&mut first).await,
+            reply_packet(10, 0, &5i32.to_be_bytes())
+        );
+        second
+            .write_all(&command_packet(20, [15, 1], &THREAD_START_REQUEST))
+            .await
+            .unwrap();
+        assert_eq!(
+            read_packet(&mut second).await,
+            reply_packet(20, 0, &6i32.to_be_bytes())
+        );
+
+        assert_eq!(
+            read_packet(&mut first).await,
+            event_packet(1, 0, &[thread_death_event(5, 3)])
+        );
+        assert_eq!(
+            read_packet(&mut second).await,
+            event_packet(1, 0, &[thread_start_event(6, 2)])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dispose_only_disconnects_sender() {
+        let vm = MockStreamBuilder::default()
+            .command_reply(2, [15, 1], &THREAD_START_REQUEST, &5i32.to_be_bytes())
+            .command_reply(3, [15, 2], &[6, 0, 0, 0, 5], &[])
+            .command_reply(4, [1, 1], &[], &[1, 2, 3]);
+        let address = start(vm).await;
+
+        let mut first = debugger(&address).await;
+        first
+            .write_all(&command_packet(10, [15, 1], &THREAD_START_REQUEST))
+            .await
+            .unwrap();
+        read_packet(&mut first).await;
+        first
+            .write_all(&command_packet(11, [1, 6], &[]))
+            .await
+            .unwrap();
+        assert_eq!(read_packet(&mut first).await, reply_packet(11, 0, &[]));
+        // The connection is closed once the request was cleared with packet id 3
+        assert_eq!(first.read(&mut [0u8; 1]).await.unwrap(), 0);
+
+        let mut second = debugger(&address).await;
+        second
+            .write_all(&command_packet(30, [1, 1], &[]))
+            .await
+            .unwrap();
+        assert_eq!(
+            read_packet(&mut second).await,
+            reply_packet(30, 0, &[1, 2, 3])
+        );
+    }
+}
@@ -0,0 +1,328 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod object_reference_tests {
+    use crate::common::{MockStreamBuilder, id, jdwp_string};
+    use jdwp_client::{Error, JdwpClient, JdwpValue, Tag, TaggedObjectId, VariableLengthId};
+
+    const THREAD: VariableLengthId = VariableLengthId { value: 0x1 };
+
+    fn reference_type_reply(type_id: u64) -> Vec<u8> {
+        let mut reply = vec![0x1];
+        reply.extend_from_slice(&id(type_id));
+        reply
+    }
+
+    fn methods_reply(method_id: u64, name: &str, signature: &str) -> Vec<u8> {
+        let mut reply = vec![0x0, 0x0, 0x0, 0x1];
+        reply.extend_from_slice(&id(method_id));
+        reply.extend_from_slice(&jdwp_string(name));
+        reply.extend_from_slice(&jdwp_string(signature));
+        reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x1]);
+        reply
+    }
+
+    fn invoke_out(object_id: u64, class_id: u64, method_id: u64, arguments: &[u8]) -> Vec<u8> {
+        let mut out = id(object_id).to_vec();
+        out.extend_from_slice(&id(THREAD.value));
+        out.extend_from_slice(&id(class_id));
+        out.extend_from_slice(&id(method_id));
+        out.extend_from_slice(arguments);
+        out.extend_from_slice(&[0x0, 0x0, 0x0, 0x0]);
+        out
+    }
+
+    fn invoke_reply(return_value: &[u8], exception_id: u64) -> Vec<u8> {
+        let mut reply = return_value.to_vec();
+        reply.push(b'L');
+        reply.extend_from_slice(&id(exception_id));
+        reply
+    }
+
+    #[tokio::test]
+    async fn test_invoke_method_exception() {
+        let mut string_return = vec![b's'];
+        string_return.extend_from_slice(&id(0x70));
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x8], &[], &[])
+            .command_reply(3, [0x9, 0x1], &id(0x30), &reference_type_reply(0x10))
+            .command_reply(4, [0x2, 0x5], &id(0x10), &methods_reply(0x1, "get", "(I)I"))
+            .command_reply(
+                5,
+                [0x9, 0x6],
+                &invoke_out(
+                    0x30,
+                    0x10,
+                    0x1,
+                    &[0x0, 0x0, 0x0, 0x1, b'I', 0x0, 0x0, 0x0, 0x5],
+                ),
+                &invoke_reply(&[b'I', 0x0, 0x0, 0x0, 0x0], 0x50),
+            )
+            .command_reply(6, [0x9, 0x1], &id(0x50), &reference_type_reply(0x60))
+            .command_reply(
+                7,
+                [0x2, 0x1],
+                &id(0x60),
+                &jdwp_string("Ljava/lang/IllegalStateException;"),
+            )
+            .command_reply(8, [0x9, 0x1], &id(0x50), &reference_type_reply(0x60))
+            .command_reply(
+                9,
+                [0x2, 0x5],
+                &id(0x60),
+                &methods_reply(0x2, "getMessage", "()Ljava/lang/String;"),
+            )
+            .command_reply(
+                10,
+                [0x9, 0x6],
+                &invoke_out(0x50, 0x60, 0x2, &[0x0, 0x0, 0x0, 0x0]),
+                &invoke_reply(&string_return, 0x0),
+            )
+            .command_reply(11, [0xA, 0x1], &id(0x70), &jdwp_string("bad state"))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.vm_suspend().await.unwrap();
+
+        let object = TaggedObjectId {
+            tag: Tag::Object,
+            object_id: VariableLengthId { value: 0x30 },
+        };
+        let result = client.invoke_method(THREAD, object, "get", (5,)).await;
+        let Err(Error::InvocationException { exception }) = result else {
+            panic!("Expected an exception, got {:?}", result);
+        };
+        assert_eq!(exception.object().object_id.value, 0x50);
+        assert_eq!(
+            exception.class_name(&client).await.unwrap(),
+            "java.lang.IllegalStateException"
+        );
+        assert_eq!(
+            exception.message(&client).await.unwrap(),
+            Some(String::from("bad state"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invoke_method_return_value() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x8], &[], &[])
+            .command_reply(3, [0x9, 0x1], &id(0x30), &reference_type_reply(0x10))
+            .command_reply(4, [0x2, 0x5], &id(0x10), &methods_reply(0x1, "size", "()I"))
+            .command_reply(
+                5,
+                [0x9, 0x6],
+                &invoke_out(0x30, 0x10, 0x1, &[0x0, 0x0, 0x0, 0x0]),
+                &invoke_reply(&[b'I', 0x0, 0x0, 0x0, 0x3], 0x0),
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.vm_suspend().await.unwrap();
+
+        let object = TaggedObjectId {
+            tag: Tag::Object,
+            object_id: VariableLengthId { value: 0x30 },
+        };
+        let value = client
+            .invoke_method(THREAD, object, "size", ())
+            .await
+            .unwrap();
+        assert_eq!(value, JdwpValue::Int(3));
+    }
+
+    #[tokio::test]
+    async fn test_exception_details() {
+        let exception_methods = {
+            let mut reply = vec![0x0, 0x0, 0x0, 0x2];
+            for (method_id, name, signature) in [
+                (0x2, "getMessage", "()Ljava/lang/String;"),
+                (0x3, "getStackTrace", "()[Ljava/lang/StackTraceElement;"),
+            ] {
+                reply.extend_from_slice(&id(method_id));
+                reply.extend_from_slice(&jdwp_string(name));
+                reply.extend_from_slice(&jdwp_string(signature));
+                reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x1]);
+            }
+            reply
+        };
+        let tagged = |tag: u8, object_id: u64| {
+            let mut value = vec![tag];
+            value.extend_from_slice(&id(object_id));
+            value
+        };
+        let mut get_values_out = id(0x80).to_vec();
+        get_values_out.extend_from_slice(&[0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x1]);
+        let mut stack_trace_values = vec![b'L', 0x0, 0x0, 0x0, 0x1];
+        stack_trace_values.extend_from_slice(&tagged(b'L', 0x90));
+
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x8], &[], &[])
+            .command_reply(3, [0x9, 0x1], &id(0x50), &reference_type_reply(0x60))
+            .command_reply(
+                4,
+                [0x2, 0x1],
+                &id(0x60),
+                &jdwp_string("Ljava/lang/IllegalStateException;"),
+            )
+            .command_reply(5, [0x9, 0x1], &id(0x50), &reference_type_reply(0x60))
+            .command_reply(6, [0x2, 0x5], &id(0x60), &exception_methods)
+            .command_reply(
+                7,
+                [0x9, 0x6],
+                &invoke_out(0x50, 0x60, 0x2, &[0x0, 0x0, 0x0, 0x0]),
+                &invoke_reply(&tagged(b's', 0x70), 0x0),
+            )
+            .command_reply(8, [0xA, 0x1], &id(0x70), &jdwp_string("bad state"))
+            .command_reply(9, [0x9, 0x1], &id(0x50), &reference_type_reply(0x60))
+            .command_reply(10, [0x2, 0x5], &id(0x60), &exception_methods)
+            .command_reply(
+                11,
+                [0x9, 0x6],
+                &invoke_out(0x50, 0x60, 0x3, &[0x0, 0x0, 0x0, 0x0]),
+                &invoke_reply(&tagged(b'[', 0x80), 0x0),
+            )
+            .command_reply(12, [0xD, 0x1], &id(0x80), &[0x0, 0x0, 0x0, 0x1])
+            .command_reply(13, [0xD, 0x2], &get_values_out, &stack_trace_values)
+            .command_reply(14, [0x9, 0x1], &id(0x90), &reference_type_reply(0xA0))
+            .command_reply(
+                15,
+                [0x2, 0x5],
+                &id(0xA0),
+                &methods_reply(0x4, "toString", "()Ljava/lang/String;"),
+            )
+            .command_reply(
+                16,
+                [0x9, 0x6],
+                &invoke_out(0x90, 0xA0, 0x4, &[0x0, 0x0, 0x0, 0x0]),
+                &invoke_reply(&tagged(b's', 0xB0), 0x0),
+            )
+            .command_reply(
+                17,
+                [0xA, 0x1],
+                &id(0xB0),
+                &jdwp_string("Main.main(Main.java:3)"),
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.vm_suspend().await.unwrap();
+
+        let exception = TaggedObjectId {
+            tag: Tag::Object,
+            object_id: VariableLengthId { value: 0x50 },
+        };
+        let details = client.exception_details(THREAD, exception).await.unwrap();
+        assert_eq!(
+            details.to_string(),
+            "java.lang.IllegalStateException: bad state\n\tat Main.main(Main.java:3)"
+        );
+    }
+
+    fn object(object_id: u64) -> TaggedObjectId {
+        TaggedObjectId {
+            tag: Tag::Object,
+            object_id: VariableLengthId { value: object_id },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_object_equals_and_hash_code() {
+        let mut equals_argument = vec![0x0, 0x0, 0x0, 0x1, b'L'];
+        equals_argument.extend_from_slice(&id(0x31));
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x8], &[], &[])
+            .command_reply(3, [0x9, 0x1], &id(0x30), &reference_type_reply(0x10))
+            .command_reply(
+                4,
+                [0x2, 0x5],
+                &id(0x10),
+                &methods_reply(0x1, "equals", "(Ljava/lang/Object;)Z"),
+            )
+            .command_reply(
+                5,
+                [0x9, 0x6],
+                &invoke_out(0x30, 0x10, 0x1, &equals_argument),
+                &invoke_reply(&[b'Z', 0x1], 0x0),
+            )
+            .command_reply(6, [0x9, 0x1], &id(0x30), &reference_type_reply(0x10))
+            .command_reply(
+                7,
+                [0x2, 0x5],
+                &id(0x10),
+                &methods_reply(0x2, "hashCode", "()I"),
+            )
+            .command_reply(
+                8,
+                [0x9, 0x6],
+                &invoke_out(0x30, 0x10, 0x2, &[0x0, 0x0, 0x0, 0x0]),
+                &invoke_reply(&[b'I', 0x0, 0x0, 0x0, 0x2A], 0x0),
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.vm_suspend().await.unwrap();
+
+        assert!(
+            client
+                .object_equals(THREAD, object(0x30), object(0x31))
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            client.object_hash_code(THREAD, object(0x30)).await.unwrap(),
+            42
+        );
+
+        // Null and identity need no round trip
+        assert!(
+            client
+                .object_equals(THREAD, object(0x0), object(0x0))
+                .await
+                .unwrap()
+        );
+        assert!(object(0x30).is_same_object(&TaggedObjectId {
+            tag: Tag::String,
+            object_id: VariableLengthId { value: 0x30 },
+        }));
+        assert!(!object(0x30).is_same_object(&object(0x31)));
+    }
+
+    #[tokio::test]
+    async fn test_instance_of() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x9, 0x1], &id(0x30), &reference_type_reply(0x10))
+            .command_reply(3, [0x2, 0x1], &id(0x10), &jdwp_string("Lcom/acme/Impl;"))
+            .command_reply(4, [0x2, 0xA], &id(0x10), &{
+                let mut reply = vec![0x0, 0x0, 0x0, 0x1];
+                reply.extend_from_slice(&id(0x11));
+                reply
+            })
+            .command_reply(5, [0x3, 0x1], &id(0x10), &id(0x12))
+            .command_reply(6, [0x2, 0x1], &id(0x12), &jdwp_string("Lcom/acme/Base;"))
+            .command_reply(7, [0x2, 0xA], &id(0x12), &[0x0, 0x0, 0x0, 0x0])
+            .command_reply(8, [0x3, 0x1], &id(0x12), &id(0x0))
+            .command_reply(9, [0x2, 0x1], &id(0x11), &jdwp_string("Ljava/util/List;"))
+            .command_reply(10, [0x9, 0x1], &id(0x30), &reference_type_reply(0x10))
+            .command_reply(11, [0x2, 0x1], &id(0x10), &jdwp_string("Lcom/acme/Impl;"))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        assert!(
+            client
+                .instance_of(object(0x30), "Ljava/util/List;")
+                .await
+                .unwrap()
+        );
+        assert!(
+            client
+                .instance_of(object(0x30), "Ljava/lang/Object;")
+                .await
+                .unwrap()
+        );
+        assert!(
+            !client
+                .instance_of(object(0x0), "Ljava/lang/Object;")
+                .await
+                .unwrap()
+        );
+    }
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "tokio")]
+
+#[cfg(test)]
+mod common;
+
+#[cfg(test)]
+mod rate_limit_tests {
+    use crate::common::{MockStreamBuilder, id};
+    use jdwp_client::{JdwpClient, RateLimiter, VariableLengthId};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    const THREAD: VariableLengthId = VariableLengthId { value: 0x5 };
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limiter_spaces_commands() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xB, 0x2], &id(0x5), &[])
+            .command_reply(3, [0xB, 0x2], &id(0x5), &[])
+            .command_reply(4, [0xB, 0x2], &id(0x5), &[])
+            .build();
+        let mut client = JdwpClient::new(mock_stream).await.unwrap();
+        client.set_rate_limiter(Some(RateLimiter::with_interval(
+            Duration::from_millis(100),
+            2,
+        )));
+
+        let start = Instant::now();
+        client.thread_suspend(THREAD).await.unwrap();
+        client.thread_suspend(THREAD).await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(100));
+        client.thread_suspend(THREAD).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert_eq!(client.thread_suspend_count(THREAD), 3);
+    }
+
+    #[test]
+    fn test_rate_limiter_settings() {
+        let limiter = RateLimiter::new(4, 0);
+        assert_eq!(limiter.interval(), Duration::from_millis(250));
+        assert_eq!(limiter.burst(), 1);
+    }
+}
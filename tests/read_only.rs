@@ -0,0 +1,36 @@
+#![cfg(feature = "tokio")]
+
+#[cfg(test)]
+mod common;
+
+#[cfg(test)]
+mod read_only_tests {
+    use crate::common::{MockStreamBuilder, id, jdwp_string};
+    use jdwp_client::{JdwpClient, ReadOnlyClient, VariableLengthId};
+
+    #[tokio::test]
+    async fn test_read_only_client_forwards_queries() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xB, 0x1], &id(0x5), &jdwp_string("main"))
+            .command_reply(3, [0xA, 0x1], &id(0x6), &jdwp_string("value"))
+            .build();
+        let client = ReadOnlyClient::new(JdwpClient::new(mock_stream).await.unwrap());
+
+        assert_eq!(
+            client
+                .thread_name(VariableLengthId { value: 0x5 })
+                .await
+                .unwrap(),
+            "main"
+        );
+        assert_eq!(
+            client
+                .string_get_value(VariableLengthId { value: 0x6 })
+                .await
+                .unwrap()
+                .string_value,
+            "value"
+        );
+        assert!(!client.is_vm_suspended());
+    }
+}
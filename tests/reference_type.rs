@@ -0,0 +1,137 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod reference_type_tests {
+    use crate::common::{MockStreamBuilder, id, jdwp_string};
+    use jdwp_client::{Error, JdwpClient, JdwpValue, Tag, TaggedObjectId, VariableLengthId};
+
+    fn methods_reply(methods: &[(u64, &str, &str)]) -> Vec<u8> {
+        let mut reply = (methods.len() as u32).to_be_bytes().to_vec();
+        for (method_id, name, signature) in methods {
+            reply.extend_from_slice(&id(*method_id));
+            reply.extend_from_slice(&jdwp_string(name));
+            reply.extend_from_slice(&jdwp_string(signature));
+            reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x1]);
+        }
+        reply
+    }
+
+    #[tokio::test]
+    async fn test_find_inherited_method() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(
+                2,
+                [0x2, 0x5],
+                &id(0x10),
+                &methods_reply(&[(0x1, "run", "()V")]),
+            )
+            .command_reply(3, [0x3, 0x1], &id(0x10), &id(0x20))
+            .command_reply(
+                4,
+                [0x2, 0x5],
+                &id(0x20),
+                &methods_reply(&[
+                    (0x2, "toString", "()Ljava/lang/String;"),
+                    (0x3, "hashCode", "()I"),
+                ]),
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let method = client
+            .find_method(
+                VariableLengthId { value: 0x10 },
+                "toString",
+                "()Ljava/lang/String;",
+            )
+            .await
+            .unwrap();
+        assert_eq!(method.method_id.value, 0x2);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_overload() {
+        let methods = methods_reply(&[
+            (0x1, "append", "(I)Ljava/lang/StringBuilder;"),
+            (0x2, "append", "(J)Ljava/lang/StringBuilder;"),
+            (
+                0x3,
+                "append",
+                "(Ljava/lang/String;)Ljava/lang/StringBuilder;",
+            ),
+            (
+                0x4,
+                "append",
+                "(Ljava/lang/Object;)Ljava/lang/StringBuilder;",
+            ),
+        ]);
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x2, 0x5], &id(0x10), &methods)
+            .command_reply(3, [0x2, 0x5], &id(0x10), &methods)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let class = VariableLengthId { value: 0x10 };
+
+        let method = client
+            .resolve_method(class, "append", &[JdwpValue::Long(1)])
+            .await
+            .unwrap();
+        assert_eq!(method.method_id.value, 0x2);
+
+        let object = JdwpValue::Object(TaggedObjectId {
+            tag: Tag::String,
+            object_id: VariableLengthId { value: 0x30 },
+        });
+        let result = client.resolve_method(class, "append", &[object]).await;
+        assert!(matches!(result, Err(Error::InvalidArgument { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_class_literal_round_trip() {
+        let mut classes = 1u32.to_be_bytes().to_vec();
+        classes.push(1);
+        classes.extend_from_slice(&id(0x10));
+        classes.extend_from_slice(&7i32.to_be_bytes());
+        let reflected_type = [&[1u8][..], &id(0x10)].concat();
+
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x2], &jdwp_string("Lcom/example/Foo;"), &classes)
+            .command_reply(3, [0x2, 0xb], &id(0x10), &id(0x50))
+            .command_reply(4, [0x11, 0x1], &id(0x50), &reflected_type)
+            .command_reply(
+                5,
+                [0x1, 0x2],
+                &jdwp_string("Lcom/example/Bar;"),
+                &[0, 0, 0, 0],
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let class_object = client.class_literal("com.example.Foo").await.unwrap();
+        assert_eq!(
+            class_object,
+            TaggedObjectId {
+                tag: Tag::ClassObject,
+                object_id: VariableLengthId { value: 0x50 },
+            }
+        );
+        let reflected = client.reflected_type(class_object).await.unwrap();
+        assert_eq!(reflected.type_id, VariableLengthId { value: 0x10 });
+
+        assert!(matches!(
+            client.class_literal("com.example.Bar").await,
+            Err(Error::ClassNotFound { .. })
+        ));
+        // Other objects are rejected without asking the VM
+        let string = TaggedObjectId {
+            tag: Tag::String,
+            object_id: VariableLengthId { value: 0x60 },
+        };
+        assert!(matches!(
+            client.reflected_type(string).await,
+            Err(Error::TypeMismatch { .. })
+        ));
+    }
+}
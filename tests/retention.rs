@@ -0,0 +1,174 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod retention_tests {
+    use crate::common::{MockStreamBuilder, id, jdwp_string};
+    use jdwp_client::{
+        JdwpClient, RetainingReference, RetentionLimits, Tag, TaggedObjectId, VariableLengthId,
+    };
+
+    fn object(tag: Tag, value: u64) -> TaggedObjectId {
+        TaggedObjectId {
+            tag,
+            object_id: VariableLengthId { value },
+        }
+    }
+
+    fn referrers_out(object_id: u64) -> Vec<u8> {
+        [&id(object_id)[..], &16i32.to_be_bytes()].concat()
+    }
+
+    fn referrers_reply(referrers: &[(u8, u64)]) -> Vec<u8> {
+        let mut reply = (referrers.len() as i32).to_be_bytes().to_vec();
+        for (tag, object_id) in referrers {
+            reply.push(*tag);
+            reply.extend_from_slice(&id(*object_id));
+        }
+        reply
+    }
+
+    fn fields_reply(fields: &[(u64, &str, &str, i32)]) -> Vec<u8> {
+        let mut reply = (fields.len() as i32).to_be_bytes().to_vec();
+        for (field_id, name, signature, mod_bits) in fields {
+            reply.extend_from_slice(&id(*field_id));
+            reply.extend_from_slice(&jdwp_string(name));
+            reply.extend_from_slice(&jdwp_string(signature));
+            reply.extend_from_slice(&mod_bits.to_be_bytes());
+        }
+        reply
+    }
+
+    fn get_values_out(object_id: u64, field_id: u64) -> Vec<u8> {
+        [&id(object_id)[..], &1i32.to_be_bytes(), &id(field_id)].concat()
+    }
+
+    fn object_value(object_id: u64) -> Vec<u8> {
+        [&1i32.to_be_bytes()[..], b"L", &id(object_id)].concat()
+    }
+
+    fn type_reply(type_id: u64) -> Vec<u8> {
+        [&[0x1][..], &id(type_id)].concat()
+    }
+
+    #[tokio::test]
+    async fn test_path_through_field_to_static() {
+        let node_fields = fields_reply(&[
+            (0x50, "value", "I", 0x2),
+            (0x51, "next", "Lcom/example/Node;", 0x2),
+        ]);
+        let cache_fields = fields_reply(&[(0x52, "HEAD", "Lcom/example/Node;", 0xA)]);
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(
+                2,
+                [0x9, 0xA],
+                &referrers_out(0x30),
+                &referrers_reply(&[(b'L', 0x31)]),
+            )
+            .command_reply(3, [0x9, 0x1], &id(0x31), &type_reply(0x10))
+            .command_reply(4, [0x2, 0x1], &id(0x10), &jdwp_string("Lcom/example/Node;"))
+            .command_reply(5, [0x2, 0x4], &id(0x10), &node_fields)
+            .command_reply(
+                6,
+                [0x9, 0x2],
+                &get_values_out(0x31, 0x51),
+                &object_value(0x30),
+            )
+            .command_reply(
+                7,
+                [0x9, 0xA],
+                &referrers_out(0x31),
+                &referrers_reply(&[(b'c', 0x40)]),
+            )
+            .command_reply(8, [0x11, 0x1], &id(0x40), &type_reply(0x11))
+            .command_reply(9, [0x2, 0x4], &id(0x11), &cache_fields)
+            .command_reply(
+                10,
+                [0x2, 0x6],
+                &get_values_out(0x11, 0x52),
+                &object_value(0x31),
+            )
+            .command_reply(
+                11,
+                [0x2, 0x1],
+                &id(0x11),
+                &jdwp_string("Lcom/example/Cache;"),
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let paths = client
+            .path_to_roots(object(Tag::Object, 0x30))
+            .await
+            .unwrap();
+        assert_eq!(paths.len(), 1);
+        assert!(paths[0].reaches_root);
+        assert_eq!(
+            paths[0].references,
+            vec![
+                RetainingReference {
+                    referrer: object(Tag::Object, 0x31),
+                    class_name: String::from("com.example.Node"),
+                    field: Some(String::from("next")),
+                },
+                RetainingReference {
+                    referrer: object(Tag::ClassObject, 0x40),
+                    class_name: String::from("com.example.Cache"),
+                    field: Some(String::from("HEAD")),
+                },
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_depth_limit() {
+        let array_values = [
+            &[b'L'][..],
+            &2i32.to_be_bytes(),
+            b"L",
+            &id(0x0),
+            b"L",
+            &id(0x30),
+        ]
+        .concat();
+        let array_out = [&id(0x32)[..], &0i32.to_be_bytes(), &2i32.to_be_bytes()].concat();
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(
+                2,
+                [0x9, 0xA],
+                &referrers_out(0x30),
+                &referrers_reply(&[(b'[', 0x32)]),
+            )
+            .command_reply(3, [0x9, 0x1], &id(0x32), &[&[0x3][..], &id(0x12)].concat())
+            .command_reply(
+                4,
+                [0x2, 0x1],
+                &id(0x12),
+                &jdwp_string("[Ljava/lang/Object;"),
+            )
+            .command_reply(5, [0xD, 0x1], &id(0x32), &2i32.to_be_bytes())
+            .command_reply(6, [0xD, 0x2], &array_out, &array_values)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let limits = RetentionLimits {
+            max_depth: 1,
+            ..RetentionLimits::default()
+        };
+        let paths = client
+            .path_to_roots_with(object(Tag::Object, 0x30), limits)
+            .await
+            .unwrap();
+        assert_eq!(paths.len(), 1);
+        assert!(!paths[0].reaches_root);
+        assert_eq!(
+            paths[0].references,
+            vec![RetainingReference {
+                referrer: object(Tag::Array, 0x32),
+                class_name: String::from("java.lang.Object[]"),
+                field: Some(String::from("[1]")),
+            }]
+        );
+    }
+}
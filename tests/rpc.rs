@@ -0,0 +1,83 @@
+#![cfg(feature = "rpc")]
+
+#[cfg(test)]
+mod common;
+
+#[cfg(test)]
+mod rpc_tests {
+    use crate::common::{MockStreamBuilder, jdwp_string};
+    use jdwp_client::{Debugger, JdwpClient, RpcServer};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpStream;
+
+    fn version_reply() -> Vec<u8> {
+        let mut reply = jdwp_string("Java Debug Wire Protocol");
+        reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x15, 0x0, 0x0, 0x0, 0x0]);
+        reply.extend_from_slice(&jdwp_string("21.0.8"));
+        reply.extend_from_slice(&jdwp_string("OpenJDK 64-Bit Server VM"));
+        reply
+    }
+
+    #[tokio::test]
+    async fn test_requests() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x1], &[], &version_reply())
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let server = RpcServer::bind("127.0.0.1:0", Debugger::new(client))
+            .await
+            .unwrap();
+        let address = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let (reader, mut writer) = TcpStream::connect(address).await.unwrap().into_split();
+        let mut lines = BufReader::new(reader).lines();
+        let requests = [
+            (
+                r#"{"jsonrpc":"2.0","id":1,"method":"vm.version"}"#,
+                r#"{"jsonrpc":"2.0","id":1,"result":{"description":"Java Debug Wire Protocol","jdwpMajor":21,"jdwpMinor":0,"vmVersion":"21.0.8","vmName":"OpenJDK 64-Bit Server VM"}}"#,
+            ),
+            (
+                r#"{"jsonrpc":"2.0","id":"a","method":"vm.reboot"}"#,
+                r#"{"jsonrpc":"2.0","id":"a","error":{"code":-32601,"message":"Unknown method vm.reboot"}}"#,
+            ),
+            (
+                r#"{"jsonrpc":"2.0","id":3,"method":"breakpoints.clear"}"#,
+                r#"{"jsonrpc":"2.0","id":3,"error":{"code":-32602,"message":"Parameter id must be an integer"}}"#,
+            ),
+            (
+                r#"{"jsonrpc":"2.0","id":4,"method":"breakpoints.clear","params":{"id":9}}"#,
+                r#"{"jsonrpc":"2.0","id":4,"error":{"code":-32000,"message":"InvalidArgument { message: \"Unknown breakpoint BreakpointId(9)\" }"}}"#,
+            ),
+            (
+                r#"{"id":5,"method":"vm.version"}"#,
+                r#"{"jsonrpc":"2.0","id":5,"error":{"code":-32600,"message":"Not a JSON-RPC 2.0 request"}}"#,
+            ),
+            (
+                "{\"jsonrpc\"",
+                r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32700,"message":"Expected ':' at offset 10"}}"#,
+            ),
+        ];
+        for (request, response) in requests {
+            writer
+                .write_all(format!("{}\n", request).as_bytes())
+                .await
+                .unwrap();
+            assert_eq!(lines.next_line().await.unwrap().unwrap(), response);
+        }
+
+        // Notifications aren't answered
+        writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"method\":\"breakpoints.list\"}\n")
+            .await
+            .unwrap();
+        writer
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":6,\"method\":\"breakpoints.list\"}\n")
+            .await
+            .unwrap();
+        assert_eq!(
+            lines.next_line().await.unwrap().unwrap(),
+            r#"{"jsonrpc":"2.0","id":6,"result":[]}"#
+        );
+    }
+}
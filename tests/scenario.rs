@@ -0,0 +1,82 @@
+#![cfg(feature = "tokio")]
+
+#[cfg(test)]
+mod scenario_tests {
+    use jdwp_client::{Debugger, JdwpClient, MockClass, MockMethod, MockScenario};
+
+    fn main_class() -> MockClass {
+        MockClass::new("com.example.Main")
+            .source_file("Main.java")
+            .method(
+                MockMethod::new("main", "([Ljava/lang/String;)V")
+                    .line(3, 0)
+                    .line(4, 4)
+                    .line(5, 9),
+            )
+    }
+
+    #[tokio::test]
+    async fn test_breakpoint_and_step() {
+        let scenario = MockScenario::new()
+            .class(main_class())
+            .thread("main")
+            .reach("main", "com.example.Main", "main", 3)
+            .reach("main", "com.example.Main", "main", 4)
+            .reach("main", "com.example.Main", "main", 5)
+            .exit();
+        let line_4 = scenario.location("com.example.Main", "main", 4).unwrap();
+        let line_5 = scenario.location("com.example.Main", "main", 5).unwrap();
+        let main = scenario.thread_id("main").unwrap();
+        let transport = scenario.build();
+        let handle = transport.handle();
+        let mut debugger = Debugger::new(JdwpClient::new(transport).await.unwrap());
+
+        let breakpoint = debugger
+            .set_breakpoint("com.example.Main", 4)
+            .await
+            .unwrap();
+        let stop = debugger.run_to_breakpoint().await.unwrap();
+        assert_eq!(stop.breakpoint, Some(breakpoint));
+        assert_eq!(stop.thread, main);
+        assert_eq!(stop.location, line_4);
+
+        let stop = debugger.step_over().await.unwrap();
+        assert_eq!(stop.breakpoint, None);
+        assert_eq!(stop.location, line_5);
+
+        // The program exits instead of hitting the breakpoint again
+        assert!(debugger.run_to_breakpoint().await.is_err());
+        assert!(handle.mismatches().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_breakpoint_in_class_loaded_later() {
+        let scenario = MockScenario::new()
+            .class(main_class())
+            .class(
+                MockClass::new("com.example.Worker")
+                    .method(MockMethod::new("run", "()V").line(10, 0).line(11, 2))
+                    .loaded_later(),
+            )
+            .thread("main")
+            .reach("main", "com.example.Main", "main", 3)
+            .load_class("main", "com.example.Worker")
+            .reach("main", "com.example.Worker", "run", 10)
+            .reach("main", "com.example.Worker", "run", 11)
+            .exit();
+        let line_11 = scenario.location("com.example.Worker", "run", 11).unwrap();
+        let transport = scenario.build();
+        let handle = transport.handle();
+        let mut debugger = Debugger::new(JdwpClient::new(transport).await.unwrap());
+
+        // The breakpoint is pending until the class is prepared
+        let breakpoint = debugger
+            .set_breakpoint("com.example.Worker", 11)
+            .await
+            .unwrap();
+        let stop = debugger.run_to_breakpoint().await.unwrap();
+        assert_eq!(stop.breakpoint, Some(breakpoint));
+        assert_eq!(stop.location, line_11);
+        assert!(handle.mismatches().is_empty());
+    }
+}
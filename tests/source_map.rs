@@ -0,0 +1,142 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod source_map_tests {
+    use crate::common::{MockStreamBuilder, id, jdwp_string};
+    use jdwp_client::{
+        JdwpClient, Location, SourceFile, SourceMap, SourcePosition, TypeTag, VariableLengthId,
+    };
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("jdwp-sources-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn line_table_out() -> Vec<u8> {
+        let mut out = id(0x10).to_vec();
+        out.extend_from_slice(&id(0x1));
+        out
+    }
+
+    fn line_table_reply() -> Vec<u8> {
+        let mut reply = 0u64.to_be_bytes().to_vec();
+        reply.extend_from_slice(&10u64.to_be_bytes());
+        reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x2]);
+        for (index, line) in [(0u64, 5u32), (4, 6)] {
+            reply.extend_from_slice(&index.to_be_bytes());
+            reply.extend_from_slice(&line.to_be_bytes());
+        }
+        reply
+    }
+
+    /// A VM in which class 0x10 is `com.example.Main$Inner` declared in `Main.java`
+    async fn client() -> JdwpClient {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x2, 0x7], &id(0x10), &jdwp_string("Main.java"))
+            .command_reply(
+                3,
+                [0x2, 0x1],
+                &id(0x10),
+                &jdwp_string("Lcom/example/Main$Inner;"),
+            )
+            .command_reply(4, [0x6, 0x1], &line_table_out(), &line_table_reply())
+            .build();
+        JdwpClient::new(mock_stream).await.unwrap()
+    }
+
+    fn location(index: u64) -> Location {
+        Location {
+            type_tag: TypeTag::Class,
+            class_id: VariableLengthId { value: 0x10 },
+            method_id: VariableLengthId { value: 0x1 },
+            index,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resolve_in_source_root() {
+        let root = temp_dir("root");
+        std::fs::create_dir_all(root.join("com/example")).unwrap();
+        std::fs::write(root.join("com/example/Main.java"), "class Main {}").unwrap();
+        let client = client().await;
+        let sources = SourceMap::new()
+            .source_root(temp_dir("empty"))
+            .source_root(&root);
+
+        let position = sources.resolve(&client, &location(5)).await.unwrap();
+        assert_eq!(
+            position,
+            Some(SourcePosition {
+                file: SourceFile::Path(root.join("com/example/Main.java")),
+                line: 6,
+            })
+        );
+        // Served from the cache, the mock VM doesn't reply to the commands again
+        let position = sources.resolve(&client, &location(0)).await.unwrap();
+        assert_eq!(position.unwrap().line, 5);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_in_source_archive() {
+        let archive = temp_dir("archive").join("example-sources.jar");
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&archive).unwrap());
+        zip.start_file(
+            "com/example/Main.java",
+            zip::write::SimpleFileOptions::default(),
+        )
+        .unwrap();
+        zip.write_all(b"class Main {}").unwrap();
+        zip.finish().unwrap();
+        let client = client().await;
+        let sources = SourceMap::new().source_archive(&archive);
+
+        let position = sources.resolve(&client, &location(4)).await.unwrap();
+        assert_eq!(
+            position,
+            Some(SourcePosition {
+                file: SourceFile::ArchiveEntry {
+                    archive,
+                    entry: String::from("com/example/Main.java"),
+                },
+                line: 6,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resolve_missing_source() {
+        let client = client().await;
+        let sources = SourceMap::new().source_root(temp_dir("missing"));
+        assert_eq!(sources.resolve(&client, &location(4)).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_in_stratum() {
+        let root = temp_dir("stratum");
+        std::fs::create_dir_all(root.join("com/example")).unwrap();
+        std::fs::write(root.join("com/example/Util.kt"), "inline fun log() {}").unwrap();
+        let smap = "SMAP\nMain.kt\nKotlin\n*S Kotlin\n*F\n+ 1 Main.kt\ncom/example/Main.kt\n\
+                    + 2 Util.kt\ncom/example/Util.kt\n*L\n1#1,5:1\n21#2:6\n*E\n";
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x2, 0xC], &id(0x10), &jdwp_string(smap))
+            .command_reply(3, [0x6, 0x1], &line_table_out(), &line_table_reply())
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let sources = SourceMap::new().source_root(&root).stratum("Kotlin");
+
+        let position = sources.resolve(&client, &location(4)).await.unwrap();
+        assert_eq!(
+            position,
+            Some(SourcePosition {
+                file: SourceFile::Path(root.join("com/example/Util.kt")),
+                line: 21,
+            })
+        );
+    }
+}
@@ -0,0 +1,78 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod system_tests {
+    use crate::common::{MockStreamBuilder, id, jdwp_string};
+    use jdwp_client::{Error, JdwpClient};
+
+    fn all_threads_reply(thread_id: u64) -> Vec<u8> {
+        let mut reply = vec![0x0, 0x0, 0x0, 0x1];
+        reply.extend_from_slice(&id(thread_id));
+        reply
+    }
+
+    #[tokio::test]
+    async fn test_system_property() {
+        let mut class_reply = vec![0x0, 0x0, 0x0, 0x1, 0x1]; // one class, class type tag
+        class_reply.extend_from_slice(&id(0x10));
+        class_reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x7]);
+
+        let mut methods_reply = vec![0x0, 0x0, 0x0, 0x2];
+        for (method_id, signature) in [
+            (0x2, "(Ljava/lang/String;)Ljava/lang/String;"),
+            (
+                0x3,
+                "(Ljava/lang/String;Ljava/lang/String;)Ljava/lang/String;",
+            ),
+        ] {
+            methods_reply.extend_from_slice(&id(method_id));
+            methods_reply.extend_from_slice(&jdwp_string("getProperty"));
+            methods_reply.extend_from_slice(&jdwp_string(signature));
+            methods_reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x9]); // public static
+        }
+
+        let mut invoke_out = id(0x10).to_vec(); // class
+        invoke_out.extend_from_slice(&id(0x1)); // thread
+        invoke_out.extend_from_slice(&id(0x2)); // getProperty(String)
+        invoke_out.extend_from_slice(&[0x0, 0x0, 0x0, 0x1, b's']);
+        invoke_out.extend_from_slice(&id(0x40));
+        invoke_out.extend_from_slice(&[0x0, 0x0, 0x0, 0x0]); // options
+        let mut invoke_reply = vec![b's'];
+        invoke_reply.extend_from_slice(&id(0x70));
+        invoke_reply.push(b'L');
+        invoke_reply.extend_from_slice(&id(0x0));
+
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x8], &[], &[])
+            .command_reply(3, [0x1, 0x4], &[], &all_threads_reply(0x1))
+            .command_reply(
+                4,
+                [0x1, 0x2],
+                &jdwp_string("Ljava/lang/System;"),
+                &class_reply,
+            )
+            .command_reply(5, [0x1, 0xB], &jdwp_string("java.class.path"), &id(0x40))
+            .command_reply(6, [0x2, 0x5], &id(0x10), &methods_reply)
+            .command_reply(7, [0x3, 0x3], &invoke_out, &invoke_reply)
+            .command_reply(8, [0xA, 0x1], &id(0x70), &jdwp_string("/app/main.jar"))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.vm_suspend().await.unwrap();
+
+        let class_path = client.system_property("java.class.path").await.unwrap();
+        assert_eq!(class_path.as_deref(), Some("/app/main.jar"));
+    }
+
+    #[tokio::test]
+    async fn test_system_property_requires_suspended_thread() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x4], &[], &all_threads_reply(0x1))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let result = client.system_property("java.class.path").await;
+        assert!(matches!(result, Err(Error::NoSuspendedThread)));
+    }
+}
@@ -0,0 +1,100 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod thread_groups_tests {
+    use crate::common::{MockStreamBuilder, id, jdwp_string};
+    use jdwp_client::{JdwpClient, ThreadStatus, VariableLengthId};
+
+    fn ids(values: &[u64]) -> Vec<u8> {
+        let mut bytes = (values.len() as u32).to_be_bytes().to_vec();
+        for value in values {
+            bytes.extend_from_slice(&id(*value));
+        }
+        bytes
+    }
+
+    fn children_reply(threads: &[u64], groups: &[u64]) -> Vec<u8> {
+        let mut reply = ids(threads);
+        reply.extend_from_slice(&ids(groups));
+        reply
+    }
+
+    fn status_reply(status: i32, suspended: bool) -> Vec<u8> {
+        let mut reply = status.to_be_bytes().to_vec();
+        reply.extend_from_slice(&(suspended as i32).to_be_bytes());
+        reply
+    }
+
+    fn daemon_out(thread_id: u64) -> Vec<u8> {
+        let mut out = id(thread_id).to_vec();
+        out.extend_from_slice(&ids(&[0x1]));
+        out
+    }
+
+    #[tokio::test]
+    async fn test_thread_groups_tree() {
+        let mut thread_class = vec![0x0, 0x0, 0x0, 0x1, 0x1];
+        thread_class.extend_from_slice(&id(0x20));
+        thread_class.extend_from_slice(&[0x0, 0x0, 0x0, 0x7]);
+        let mut fields = vec![0x0, 0x0, 0x0, 0x1];
+        fields.extend_from_slice(&id(0x1));
+        fields.extend_from_slice(&jdwp_string("daemon"));
+        fields.extend_from_slice(&jdwp_string("Z"));
+        fields.extend_from_slice(&[0x0, 0x0, 0x0, 0x2]);
+
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x5], &[], &ids(&[0x100]))
+            .command_reply(
+                3,
+                [0x1, 0x2],
+                &jdwp_string("Ljava/lang/Thread;"),
+                &thread_class,
+            )
+            .command_reply(4, [0x2, 0x4], &id(0x20), &fields)
+            .command_reply(5, [0xC, 0x1], &id(0x100), &jdwp_string("system"))
+            .command_reply(6, [0xC, 0x3], &id(0x100), &children_reply(&[0x1], &[0x101]))
+            .command_reply(7, [0xB, 0x4], &id(0x1), &status_reply(1, true))
+            .command_reply(
+                8,
+                [0x9, 0x2],
+                &daemon_out(0x1),
+                &[0x0, 0x0, 0x0, 0x1, b'Z', 0x1],
+            )
+            .command_reply(9, [0xB, 0x1], &id(0x1), &jdwp_string("Reference Handler"))
+            .command_reply(10, [0xC, 0x1], &id(0x101), &jdwp_string("main"))
+            .command_reply(11, [0xC, 0x3], &id(0x101), &children_reply(&[0x2], &[]))
+            .command_reply(12, [0xB, 0x4], &id(0x2), &status_reply(4, false))
+            .command_reply(
+                13,
+                [0x9, 0x2],
+                &daemon_out(0x2),
+                &[0x0, 0x0, 0x0, 0x1, b'Z', 0x0],
+            )
+            .command_reply(14, [0xB, 0x1], &id(0x2), &jdwp_string("main"))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let tree = client.thread_groups_tree().await.unwrap();
+        assert_eq!(tree.len(), 1);
+        let system = &tree[0];
+        assert_eq!(system.name, "system");
+        assert_eq!(system.threads[0].name, "Reference Handler");
+        assert_eq!(system.threads[0].status, ThreadStatus::Running);
+        assert!(system.threads[0].suspended);
+        assert_eq!(system.threads[0].daemon, Some(true));
+
+        let main = system.find_group("main").unwrap();
+        assert_eq!(main.group_id, VariableLengthId { value: 0x101 });
+        assert_eq!(main.threads[0].status, ThreadStatus::Wait);
+        assert_eq!(main.threads[0].daemon, Some(false));
+
+        let names: Vec<_> = system
+            .all_threads()
+            .into_iter()
+            .map(|thread| thread.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Reference Handler", "main"]);
+    }
+}
@@ -0,0 +1,295 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod thread_reference_tests {
+    use crate::common::{
+        MockStreamBuilder, command_packet, event_packet, id, jdwp_string, reply_packet,
+        thread_death_event, thread_start_event,
+    };
+    use jdwp_client::{
+        Command, Error, FrameSlot, JdwpClient, JdwpValue, Tag, ThreadPicker, VariableLengthId,
+    };
+
+    const THREAD: VariableLengthId = VariableLengthId { value: 0x5 };
+
+    #[tokio::test]
+    async fn test_frame_count_requires_suspended_thread() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xB, 0x2], &id(0x5), &[])
+            .command_reply(3, [0xB, 0x7], &id(0x5), &[0x0, 0x0, 0x0, 0x3])
+            .command_reply(4, [0xB, 0x3], &id(0x5), &[])
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        assert!(matches!(
+            client.thread_frame_count(THREAD).await,
+            Err(Error::ThreadNotSuspended {
+                thread: THREAD,
+                required_by: Command::ThreadReferenceFrameCount,
+            })
+        ));
+
+        client.thread_suspend(THREAD).await.unwrap();
+        assert!(client.is_thread_suspended(THREAD));
+        assert_eq!(client.thread_frame_count(THREAD).await.unwrap(), 3);
+
+        client.thread_resume(THREAD).await.unwrap();
+        assert!(!client.is_thread_suspended(THREAD));
+    }
+
+    #[tokio::test]
+    async fn test_event_thread_is_suspended() {
+        let mut output = reply_packet(2, 0, &[]);
+        output.extend_from_slice(&event_packet(
+            0x10,
+            0x1, // EVENT_THREAD
+            &[thread_start_event(1, 0x5)],
+        ));
+        let mock_stream = MockStreamBuilder::default()
+            .response_bytes(&command_packet(2, [0x1, 0x9], &[]), &output)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.vm_resume().await.unwrap();
+        client.next_event().await.unwrap();
+        assert!(client.is_thread_suspended(THREAD));
+        assert!(!client.is_vm_suspended());
+    }
+
+    #[tokio::test]
+    async fn test_suspension_checks_can_be_disabled() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xB, 0x7], &id(0x5), &[0x0, 0x0, 0x0, 0x1])
+            .build();
+        let mut client = JdwpClient::new(mock_stream).await.unwrap();
+        client.set_suspension_checks(false);
+        assert_eq!(client.thread_frame_count(THREAD).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_thread_name_is_cached_until_death() {
+        let mut resume_reply = reply_packet(3, 0, &[]);
+        resume_reply.extend_from_slice(&event_packet(0x10, 0x0, &[thread_death_event(1, 0x5)]));
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xB, 0x1], &id(0x5), &jdwp_string("main"))
+            .response_bytes(&command_packet(3, [0x1, 0x9], &[]), &resume_reply)
+            .command_reply(4, [0xB, 0x1], &id(0x5), &jdwp_string("worker"))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        assert_eq!(client.thread_name(THREAD).await.unwrap(), "main");
+        assert_eq!(client.thread_name(THREAD).await.unwrap(), "main");
+
+        client.vm_resume().await.unwrap();
+        client.next_event().await.unwrap();
+        assert_eq!(client.thread_name(THREAD).await.unwrap(), "worker");
+    }
+
+    #[tokio::test]
+    async fn test_thread_name_is_not_cached_after_death_in_flight() {
+        // The thread dies while its name is being looked up
+        let mut name_reply = event_packet(0x10, 0x0, &[thread_death_event(1, 0x5)]);
+        name_reply.extend_from_slice(&reply_packet(2, 0, &jdwp_string("main")));
+        let mock_stream = MockStreamBuilder::default()
+            .response_bytes(&command_packet(2, [0xB, 0x1], &id(0x5)), &name_reply)
+            .command_reply(3, [0xB, 0x1], &id(0x5), &jdwp_string("worker"))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        assert_eq!(client.thread_name(THREAD).await.unwrap(), "main");
+        // The id was reused by a new thread
+        assert_eq!(client.thread_name(THREAD).await.unwrap(), "worker");
+    }
+
+    #[tokio::test]
+    async fn test_event_thread_is_picked_for_invocations() {
+        let mut output = reply_packet(3, 0, &[]);
+        output.extend_from_slice(&event_packet(
+            0x10,
+            0x1, // EVENT_THREAD
+            &[thread_start_event(1, 0x5)],
+        ));
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x8], &[], &[])
+            .response_bytes(&command_packet(3, [0x1, 0x9], &[]), &output)
+            .build();
+        let mut client = JdwpClient::new(mock_stream).await.unwrap();
+        client.set_thread_picker(ThreadPicker::EventThreadOnly);
+
+        // Suspending the VM doesn't stop any thread at an event
+        client.vm_suspend().await.unwrap();
+        assert!(matches!(
+            client.pick_invocation_thread().await,
+            Err(Error::NoSuspendedThread)
+        ));
+
+        client.vm_resume().await.unwrap();
+        client.next_event().await.unwrap();
+        assert!(client.is_thread_at_event(THREAD));
+        assert_eq!(client.pick_invocation_thread().await.unwrap(), THREAD);
+    }
+
+    #[tokio::test]
+    async fn test_thread_suspend_scopes_nest() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xB, 0x2], &id(0x5), &[])
+            .command_reply(3, [0xB, 0x2], &id(0x5), &[])
+            .command_reply(4, [0xB, 0x3], &id(0x5), &[])
+            .command_reply(5, [0xB, 0x3], &id(0x5), &[])
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let outer = client.suspend_thread_scope(THREAD).await.unwrap();
+        let inner = client.suspend_thread_scope(THREAD).await.unwrap();
+        assert_eq!(client.thread_suspend_count(THREAD), 2);
+
+        inner.finish().await.unwrap();
+        assert!(client.is_thread_suspended(THREAD));
+        drop(outer);
+        while client.is_thread_suspended(THREAD) {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(client.thread_suspend_count(THREAD), 0);
+    }
+
+    fn frame(frame_id: u64, class_id: u64, index: u64) -> Vec<u8> {
+        let mut frame = id(frame_id).to_vec();
+        frame.push(0x1);
+        frame.extend_from_slice(&id(class_id));
+        frame.extend_from_slice(&id(0x2));
+        frame.extend_from_slice(&index.to_be_bytes());
+        frame
+    }
+
+    #[tokio::test]
+    async fn test_frames_with_inlined_code() {
+        let smap = "SMAP\nMain.kt\nKotlin\n*S Kotlin\n*F\n+ 1 Main.kt\nMainKt.kt\n+ 2 Util.kt\n\
+                    Util.kt\n*L\n1#1,10:1\n20#2,3:30\n*E\n*S KotlinDebug\n*F\n+ 1 Main.kt\n\
+                    MainKt.kt\n*L\n7#1,3:30\n*E\n";
+        let mut frames = vec![0x0, 0x0, 0x0, 0x2];
+        frames.extend_from_slice(&frame(0x1, 0x10, 4));
+        frames.extend_from_slice(&frame(0x2, 0x20, 0));
+        let mut frames_out = id(0x5).to_vec();
+        frames_out.extend_from_slice(&[0x0, 0x0, 0x0, 0x0, 0xFF, 0xFF, 0xFF, 0xFF]);
+        let mut line_table_out = id(0x10).to_vec();
+        line_table_out.extend_from_slice(&id(0x2));
+        let mut line_table = 0u64.to_be_bytes().to_vec();
+        line_table.extend_from_slice(&10u64.to_be_bytes());
+        line_table.extend_from_slice(&[0x0, 0x0, 0x0, 0x1]);
+        line_table.extend_from_slice(&4u64.to_be_bytes());
+        line_table.extend_from_slice(&31u32.to_be_bytes());
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xB, 0x2], &id(0x5), &[])
+            .command_reply(3, [0xB, 0x6], &frames_out, &frames)
+            .command_reply(4, [0x2, 0xC], &id(0x10), &jdwp_string(smap))
+            .command_reply(5, [0x6, 0x1], &line_table_out, &line_table)
+            .response_bytes(
+                &command_packet(6, [0x2, 0xC], &id(0x20)),
+                &reply_packet(6, 101, &[]), // ABSENT_INFORMATION
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.thread_suspend(THREAD).await.unwrap();
+
+        let frames = client.frames(THREAD, true).await.unwrap();
+        let summary: Vec<_> = frames
+            .iter()
+            .map(|frame| {
+                (
+                    frame.frame_id.value,
+                    frame.inlined,
+                    frame.source.as_ref().map(|source| source.line),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0x1, true, Some(21)),
+                (0x1, false, Some(8)),
+                (0x2, false, None)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stale_frame() {
+        let mut frames = vec![0x0, 0x0, 0x0, 0x1];
+        frames.extend_from_slice(&frame(0x1, 0x10, 4));
+        let mut frames_out = id(0x5).to_vec();
+        frames_out.extend_from_slice(&[0x0, 0x0, 0x0, 0x0, 0xFF, 0xFF, 0xFF, 0xFF]);
+        let mut get_values_out = id(0x5).to_vec();
+        get_values_out.extend_from_slice(&id(0x1));
+        get_values_out.extend_from_slice(&[0x0, 0x0, 0x0, 0x1, 0x0, 0x0, 0x0, 0x2, b'I']);
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xB, 0x2], &id(0x5), &[])
+            .command_reply(3, [0xB, 0x6], &frames_out, &frames)
+            .command_reply(
+                4,
+                [0x10, 0x1],
+                &get_values_out,
+                &[0x0, 0x0, 0x0, 0x1, b'I', 0x0, 0x0, 0x0, 0x2A],
+            )
+            .command_reply(5, [0xB, 0x3], &id(0x5), &[])
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.thread_suspend(THREAD).await.unwrap();
+
+        let frame = client.frames(THREAD, false).await.unwrap().remove(0);
+        let slots = vec![FrameSlot {
+            slot: 2,
+            tag: Tag::Int,
+        }];
+        let values = client
+            .frame_get_values(&frame, slots.clone())
+            .await
+            .unwrap();
+        assert_eq!(values, vec![JdwpValue::Int(42)]);
+
+        client.thread_resume(THREAD).await.unwrap();
+        assert!(client.is_frame_stale(&frame));
+        let result = client.frame_get_values(&frame, slots).await;
+        assert!(matches!(
+            result,
+            Err(Error::StaleFrame { thread, frame_id }) if thread == THREAD && frame_id.value == 0x1
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_top_frames() {
+        let frames_out = |start: i32, length: i32| {
+            [&id(0x5)[..], &start.to_be_bytes(), &length.to_be_bytes()].concat()
+        };
+        let mut two_frames = vec![0x0, 0x0, 0x0, 0x2];
+        two_frames.extend_from_slice(&frame(0x1, 0x10, 4));
+        two_frames.extend_from_slice(&frame(0x2, 0x20, 0));
+        let mut one_frame = vec![0x0, 0x0, 0x0, 0x1];
+        one_frame.extend_from_slice(&frame(0x1, 0x10, 4));
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0xB, 0x2], &id(0x5), &[])
+            .command_reply(3, [0xB, 0x6], &frames_out(0, 1), &one_frame)
+            .response_bytes(
+                &command_packet(4, [0xB, 0x6], &frames_out(0, 5)),
+                &reply_packet(4, 504, &[]), // INVALID_LENGTH
+            )
+            .command_reply(5, [0xB, 0x6], &frames_out(0, -1), &two_frames)
+            .response_bytes(
+                &command_packet(6, [0xB, 0x6], &frames_out(2, 2)),
+                &reply_packet(6, 503, &[]), // INVALID_INDEX
+            )
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.thread_suspend(THREAD).await.unwrap();
+
+        let top = client.top_frames(THREAD, 1).await.unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].frame_id.value, 0x1);
+
+        let frames = client.top_frames(THREAD, 5).await.unwrap();
+        let ids: Vec<_> = frames.iter().map(|frame| frame.frame_id.value).collect();
+        assert_eq!(ids, vec![0x1, 0x2]);
+
+        assert!(client.frames_page(THREAD, 2, 2).await.unwrap().is_empty());
+        assert!(client.top_frames(THREAD, 0).await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,97 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod tracer_tests {
+    use crate::common::{
+        MockStreamBuilder, command_packet, event_packet, id, jdwp_string, reply_packet,
+    };
+    use jdwp_client::{JdwpClient, Tracer, VariableLengthId};
+    use std::time::Duration;
+
+    fn method_request_out(event_kind: u8) -> Vec<u8> {
+        let mut out = vec![event_kind, 0x0, 0x0, 0x0, 0x0, 0x1, 0x5];
+        out.extend_from_slice(&jdwp_string("com.acme.*"));
+        out
+    }
+
+    fn method_event(event_kind: u8, request_id: u8, method_id: u64) -> Vec<u8> {
+        let mut event = vec![event_kind, 0x0, 0x0, 0x0, request_id];
+        event.extend_from_slice(&id(0x5));
+        event.push(0x1);
+        event.extend_from_slice(&id(0x10));
+        event.extend_from_slice(&id(method_id));
+        event.extend_from_slice(&id(0x0));
+        event
+    }
+
+    fn methods_reply() -> Vec<u8> {
+        let mut reply = vec![0x0, 0x0, 0x0, 0x2];
+        for (method_id, name) in [(0x1, "run"), (0x2, "step")] {
+            reply.extend_from_slice(&id(method_id));
+            reply.extend_from_slice(&jdwp_string(name));
+            reply.extend_from_slice(&jdwp_string("()V"));
+            reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x1]);
+        }
+        reply
+    }
+
+    #[tokio::test]
+    async fn test_call_tree() {
+        let mut exit_reply = reply_packet(3, 0, &[0x0, 0x0, 0x0, 0x5]);
+        exit_reply.extend_from_slice(&event_packet(
+            100,
+            0x0,
+            &[
+                method_event(0x28, 4, 0x1),
+                method_event(0x28, 4, 0x2),
+                method_event(0x29, 5, 0x2),
+                method_event(0x28, 4, 0x2),
+                method_event(0x29, 5, 0x2),
+                method_event(0x29, 5, 0x1),
+                method_event(0x28, 4, 0x1),
+            ],
+        ));
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(
+                2,
+                [0xF, 0x1],
+                &method_request_out(0x28),
+                &[0x0, 0x0, 0x0, 0x4],
+            )
+            .response_bytes(
+                &command_packet(3, [0xF, 0x1], &method_request_out(0x29)),
+                &exit_reply,
+            )
+            .command_reply(4, [0xF, 0x2], &[0x28, 0x0, 0x0, 0x0, 0x4], &[])
+            .command_reply(5, [0xF, 0x2], &[0x29, 0x0, 0x0, 0x0, 0x5], &[])
+            .command_reply(6, [0x2, 0x1], &id(0x10), &jdwp_string("Lcom/acme/Main;"))
+            .command_reply(7, [0x2, 0x5], &id(0x10), &methods_reply())
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let mut tracer = Tracer::start(client, &["com.acme.*"], &[]).await.unwrap();
+        tracer.record_for(Duration::from_millis(50)).await;
+        let trace = tracer.stop().await.unwrap();
+
+        let calls = &trace.threads[&VariableLengthId { value: 0x5 }];
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].name, "com.acme.Main.run");
+        assert!(calls[0].complete);
+        let children: Vec<_> = calls[0]
+            .children
+            .iter()
+            .map(|call| (call.name.as_str(), call.complete))
+            .collect();
+        assert_eq!(
+            children,
+            vec![("com.acme.Main.step", true), ("com.acme.Main.step", true)]
+        );
+        assert!(!calls[1].complete);
+
+        let json = trace.to_chrome_trace();
+        assert!(json.starts_with("{\"traceEvents\":[{\"name\":\"com.acme.Main.run\",\"ph\":\"X\""));
+        assert_eq!(json.matches("\"tid\":5").count(), 4);
+    }
+}
@@ -0,0 +1,287 @@
+#![cfg(feature = "tokio")]
+
+mod common;
+
+#[cfg(test)]
+mod value_reader_tests {
+    use crate::common::{MockStreamBuilder, id, jdwp_string};
+    use jdwp_client::{
+        DisplayValue, JdwpClient, JdwpValue, Tag, TaggedObjectId, ValueReader, ValueWarning,
+        VariableLengthId,
+    };
+
+    const THREAD: VariableLengthId = VariableLengthId { value: 0x1 };
+
+    fn object(tag: Tag, value: u64) -> JdwpValue {
+        JdwpValue::Object(TaggedObjectId {
+            tag,
+            object_id: VariableLengthId { value },
+        })
+    }
+
+    fn field(field_id: u64, name: &str, signature: &str, mod_bits: i32) -> Vec<u8> {
+        let mut bytes = id(field_id).to_vec();
+        bytes.extend_from_slice(&jdwp_string(name));
+        bytes.extend_from_slice(&jdwp_string(signature));
+        bytes.extend_from_slice(&mod_bits.to_be_bytes());
+        bytes
+    }
+
+    #[tokio::test]
+    async fn test_read_object_fields() {
+        let mut reference_type = vec![0x1];
+        reference_type.extend_from_slice(&id(0x10));
+        let mut fields = 3u32.to_be_bytes().to_vec();
+        fields.extend_from_slice(&field(0x1, "id", "I", 0x2));
+        fields.extend_from_slice(&field(0x2, "name", "Ljava/lang/String;", 0x2));
+        fields.extend_from_slice(&field(0x3, "COUNT", "I", 0x8));
+        let mut values_out = id(0x30).to_vec();
+        values_out.extend_from_slice(&2u32.to_be_bytes());
+        values_out.extend_from_slice(&id(0x1));
+        values_out.extend_from_slice(&id(0x2));
+        let mut values = 2u32.to_be_bytes().to_vec();
+        values.push(b'I');
+        values.extend_from_slice(&7i32.to_be_bytes());
+        values.push(b's');
+        values.extend_from_slice(&id(0x40));
+
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x9, 0x1], &id(0x30), &reference_type)
+            .command_reply(3, [0x2, 0x1], &id(0x10), &jdwp_string("Lcom/acme/Order;"))
+            .command_reply(4, [0x3, 0x1], &id(0x10), &id(0x11))
+            .command_reply(5, [0x2, 0x1], &id(0x11), &jdwp_string("Ljava/lang/Object;"))
+            .command_reply(6, [0x2, 0x4], &id(0x10), &fields)
+            .command_reply(7, [0x9, 0x2], &values_out, &values)
+            .command_reply(8, [0xA, 0x1], &id(0x40), &jdwp_string("widget"))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let value = ValueReader::new()
+            .read(&client, object(Tag::Object, 0x30))
+            .await
+            .unwrap();
+        assert!(matches!(value, DisplayValue::Object { ref fields, .. } if fields.len() == 2));
+        assert_eq!(value.to_string(), "com.acme.Order {id=7, name=\"widget\"}");
+    }
+
+    #[tokio::test]
+    async fn test_read_null_and_primitives() {
+        let client = JdwpClient::new(MockStreamBuilder::default().build())
+            .await
+            .unwrap();
+
+        let reader = ValueReader::new();
+        assert_eq!(
+            reader
+                .read(&client, object(Tag::Object, 0x0))
+                .await
+                .unwrap(),
+            DisplayValue::Null
+        );
+        assert_eq!(
+            reader
+                .read(&client, JdwpValue::Char(0x263A))
+                .await
+                .unwrap()
+                .to_string(),
+            "'☺'"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_with_to_string() {
+        let mut reference_type = vec![0x1];
+        reference_type.extend_from_slice(&id(0x10));
+        let mut methods = 1u32.to_be_bytes().to_vec();
+        methods.extend_from_slice(&id(0x5));
+        methods.extend_from_slice(&jdwp_string("toString"));
+        methods.extend_from_slice(&jdwp_string("()Ljava/lang/String;"));
+        methods.extend_from_slice(&1i32.to_be_bytes());
+        let mut invoke_out = id(0x30).to_vec();
+        invoke_out.extend_from_slice(&id(THREAD.value));
+        invoke_out.extend_from_slice(&id(0x10));
+        invoke_out.extend_from_slice(&id(0x5));
+        invoke_out.extend_from_slice(&[0x0; 8]);
+        let mut invoke_reply = vec![b's'];
+        invoke_reply.extend_from_slice(&id(0x70));
+        invoke_reply.push(b'L');
+        invoke_reply.extend_from_slice(&id(0x0));
+
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x8], &[], &[])
+            .command_reply(3, [0x9, 0x1], &id(0x30), &reference_type)
+            .command_reply(4, [0x2, 0x1], &id(0x10), &jdwp_string("Lcom/acme/Money;"))
+            .command_reply(5, [0x3, 0x1], &id(0x10), &id(0x11))
+            .command_reply(6, [0x2, 0x1], &id(0x11), &jdwp_string("Ljava/lang/Object;"))
+            .command_reply(7, [0x9, 0x1], &id(0x30), &reference_type)
+            .command_reply(8, [0x2, 0x5], &id(0x10), &methods)
+            .command_reply(9, [0x9, 0x6], &invoke_out, &invoke_reply)
+            .command_reply(10, [0xA, 0x1], &id(0x70), &jdwp_string("12.50 EUR"))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.vm_suspend().await.unwrap();
+
+        let (value, warnings) = ValueReader::new()
+            .thread(THREAD)
+            .to_string_for(&["*.Money"])
+            .read_with_warnings(&client, object(Tag::Object, 0x30))
+            .await
+            .unwrap();
+        assert!(
+            matches!(value, DisplayValue::Text { ref type_name, .. } if type_name == "com.acme.Money")
+        );
+        assert_eq!(value.to_string(), "12.50 EUR");
+        assert_eq!(
+            warnings,
+            vec![ValueWarning::ToStringInvoked {
+                type_name: String::from("com.acme.Money")
+            }]
+        );
+    }
+
+    fn integer_replies(builder: MockStreamBuilder, first_id: u32) -> MockStreamBuilder {
+        let mut reference_type = vec![0x1];
+        reference_type.extend_from_slice(&id(0x20));
+        let mut fields = 2u32.to_be_bytes().to_vec();
+        fields.extend_from_slice(&field(0x1, "MIN_VALUE", "I", 0x19));
+        fields.extend_from_slice(&field(0x2, "value", "I", 0x12));
+        let mut values_out = id(0x30).to_vec();
+        values_out.extend_from_slice(&1u32.to_be_bytes());
+        values_out.extend_from_slice(&id(0x2));
+        let mut values = 1u32.to_be_bytes().to_vec();
+        values.push(b'I');
+        values.extend_from_slice(&42i32.to_be_bytes());
+
+        builder
+            .command_reply(first_id, [0x9, 0x1], &id(0x30), &reference_type)
+            .command_reply(
+                first_id + 1,
+                [0x2, 0x1],
+                &id(0x20),
+                &jdwp_string("Ljava/lang/Integer;"),
+            )
+            .command_reply(first_id + 2, [0x2, 0x4], &id(0x20), &fields)
+            .command_reply(first_id + 3, [0x9, 0x2], &values_out, &values)
+    }
+
+    #[tokio::test]
+    async fn test_unbox() {
+        let mock_stream =
+            integer_replies(integer_replies(MockStreamBuilder::default(), 2), 6).build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let boxed = TaggedObjectId {
+            tag: Tag::Object,
+            object_id: VariableLengthId { value: 0x30 },
+        };
+        assert_eq!(client.unbox(boxed).await.unwrap(), Some(JdwpValue::Int(42)));
+        assert_eq!(
+            ValueReader::new()
+                .unbox_primitives()
+                .read(&client, object(Tag::Object, 0x30))
+                .await
+                .unwrap(),
+            DisplayValue::Primitive(JdwpValue::Int(42))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_enum_constant() {
+        // ACTIVE has a body, so it's an instance of Order$Status$1
+        let mut reference_type = vec![0x1];
+        reference_type.extend_from_slice(&id(0x12));
+        let mut fields = 2u32.to_be_bytes().to_vec();
+        fields.extend_from_slice(&field(0x1, "name", "Ljava/lang/String;", 0x12));
+        fields.extend_from_slice(&field(0x2, "ordinal", "I", 0x12));
+        let mut values_out = id(0x30).to_vec();
+        values_out.extend_from_slice(&1u32.to_be_bytes());
+        values_out.extend_from_slice(&id(0x1));
+        let mut values = 1u32.to_be_bytes().to_vec();
+        values.push(b's');
+        values.extend_from_slice(&id(0x40));
+
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x9, 0x1], &id(0x30), &reference_type)
+            .command_reply(
+                3,
+                [0x2, 0x1],
+                &id(0x12),
+                &jdwp_string("Lcom/acme/Order$Status$1;"),
+            )
+            .command_reply(4, [0x3, 0x1], &id(0x12), &id(0x11))
+            .command_reply(
+                5,
+                [0x2, 0x1],
+                &id(0x11),
+                &jdwp_string("Lcom/acme/Order$Status;"),
+            )
+            .command_reply(6, [0x3, 0x1], &id(0x11), &id(0x10))
+            .command_reply(7, [0x2, 0x1], &id(0x10), &jdwp_string("Ljava/lang/Enum;"))
+            .command_reply(8, [0x3, 0x1], &id(0x10), &id(0x9))
+            .command_reply(9, [0x2, 0x1], &id(0x9), &jdwp_string("Ljava/lang/Object;"))
+            .command_reply(10, [0x2, 0x4], &id(0x10), &fields)
+            .command_reply(11, [0x9, 0x2], &values_out, &values)
+            .command_reply(12, [0xA, 0x1], &id(0x40), &jdwp_string("ACTIVE"))
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let value = ValueReader::new()
+            .read(&client, object(Tag::Object, 0x30))
+            .await
+            .unwrap();
+        assert!(matches!(
+            value,
+            DisplayValue::EnumConstant { ref type_name, ref name, .. }
+                if type_name == "com.acme.Order$Status" && name == "ACTIVE"
+        ));
+        assert_eq!(value.to_string(), "Status.ACTIVE");
+    }
+
+    #[tokio::test]
+    async fn test_read_record() {
+        let mut reference_type = vec![0x1];
+        reference_type.extend_from_slice(&id(0x10));
+        let mut methods = 3u32.to_be_bytes().to_vec();
+        for (method_id, name, signature) in [
+            (0x1, "x", "()I"),
+            (0x2, "y", "()I"),
+            (0x3, "toString", "()Ljava/lang/String;"),
+        ] {
+            methods.extend_from_slice(&id(method_id));
+            methods.extend_from_slice(&jdwp_string(name));
+            methods.extend_from_slice(&jdwp_string(signature));
+            methods.extend_from_slice(&1i32.to_be_bytes());
+        }
+        let mut fields = 2u32.to_be_bytes().to_vec();
+        fields.extend_from_slice(&field(0x4, "x", "I", 0x12));
+        fields.extend_from_slice(&field(0x5, "y", "I", 0x12));
+        let mut values_out = id(0x30).to_vec();
+        values_out.extend_from_slice(&2u32.to_be_bytes());
+        values_out.extend_from_slice(&id(0x4));
+        values_out.extend_from_slice(&id(0x5));
+        let mut values = 2u32.to_be_bytes().to_vec();
+        for value in [1i32, 2] {
+            values.push(b'I');
+            values.extend_from_slice(&value.to_be_bytes());
+        }
+
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x9, 0x1], &id(0x30), &reference_type)
+            .command_reply(3, [0x2, 0x1], &id(0x10), &jdwp_string("Lcom/acme/Point;"))
+            .command_reply(4, [0x3, 0x1], &id(0x10), &id(0x11))
+            .command_reply(5, [0x2, 0x1], &id(0x11), &jdwp_string("Ljava/lang/Record;"))
+            .command_reply(6, [0x3, 0x1], &id(0x11), &id(0x9))
+            .command_reply(7, [0x2, 0x1], &id(0x9), &jdwp_string("Ljava/lang/Object;"))
+            .command_reply(8, [0x2, 0x5], &id(0x10), &methods)
+            .command_reply(9, [0x2, 0x4], &id(0x10), &fields)
+            .command_reply(10, [0x9, 0x2], &values_out, &values)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let value = ValueReader::new()
+            .read(&client, object(Tag::Object, 0x30))
+            .await
+            .unwrap();
+        assert_eq!(value.to_string(), "com.acme.Point[x=1, y=2]");
+    }
+}
@@ -1,10 +1,12 @@
+#![cfg(feature = "tokio")]
+
 mod common;
 
 #[cfg(test)]
 mod vm_tests {
-    use crate::common::MockStreamBuilder;
-    use jdwp_client::{ClassStatus, JdwpClient, TypeTag};
-    use tokio::net::TcpStream;
+    use crate::common::{MockStreamBuilder, command_packet, id, jdwp_string, reply_packet};
+    use jdwp_client::{ClassStatus, JdwpClient, TypeTag, VariableLengthId};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
     #[tokio::test]
     async fn test_mock_connect() {
@@ -166,7 +168,7 @@ mod vm_tests {
             )
             .build();
         let client = JdwpClient::new(mock_stream).await.unwrap();
-        _ = client.vm_dispose().await.unwrap();
+        client.vm_dispose().await.unwrap();
     }
 
     #[tokio::test]
@@ -184,7 +186,7 @@ mod vm_tests {
             )
             .build();
         let client = JdwpClient::new(mock_stream).await.unwrap();
-        _ = client.vm_suspend().await.unwrap();
+        client.vm_suspend().await.unwrap();
     }
 
     #[tokio::test]
@@ -202,6 +204,149 @@ mod vm_tests {
             )
             .build();
         let client = JdwpClient::new(mock_stream).await.unwrap();
-        _ = client.vm_resume().await.unwrap();
+        client.vm_resume().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_vm_info() {
+        let mut version_reply = jdwp_string("JDWP");
+        version_reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x15, 0x0, 0x0, 0x0, 0x0]); // 21.0
+        version_reply.extend_from_slice(&jdwp_string("21.0.8"));
+        version_reply.extend_from_slice(&jdwp_string("OpenJDK 64-Bit Server VM"));
+
+        // canWatchFieldModification, canRedefineClasses and canForceEarlyReturn
+        let mut capabilities_reply = [0x0u8; 32];
+        capabilities_reply[0] = 0x1;
+        capabilities_reply[7] = 0x1;
+        capabilities_reply[20] = 0x1;
+
+        let mut class_paths_reply = jdwp_string("/app");
+        class_paths_reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x2]);
+        class_paths_reply.extend_from_slice(&jdwp_string("app.jar"));
+        class_paths_reply.extend_from_slice(&jdwp_string("lib.jar"));
+        class_paths_reply.extend_from_slice(&[0x0, 0x0, 0x0, 0x0]);
+
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x1], &[], &version_reply)
+            .command_reply(3, [0x1, 0x11], &[], &capabilities_reply)
+            .command_reply(4, [0x1, 0xd], &[], &class_paths_reply)
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        let info = client.vm_info().await.unwrap();
+        assert_eq!(info.jdwp_major, 21);
+        assert_eq!(info.vm_name, "OpenJDK 64-Bit Server VM");
+        assert_eq!(info.vm_version, "21.0.8");
+        assert!(info.capabilities.can_watch_field_modification);
+        assert!(info.capabilities.can_redefine_classes);
+        assert!(info.capabilities.can_force_early_return);
+        assert!(!info.capabilities.can_pop_frames);
+        assert_eq!(info.base_dir, "/app");
+        assert_eq!(info.class_path, vec!["app.jar", "lib.jar"]);
+        assert!(info.boot_class_path.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_suspended_scope() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x8], &[], &[])
+            .command_reply(3, [0x1, 0x9], &[], &[])
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let scope = client.suspended_scope().await.unwrap();
+        assert!(client.is_vm_suspended());
+        scope.finish().await.unwrap();
+        assert!(!client.is_vm_suspended());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_suspended_scope_resumes() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x8], &[], &[])
+            .command_reply(3, [0x1, 0x9], &[], &[])
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+
+        let result: Result<(), &str> = async {
+            let _scope = client.suspended_scope().await.unwrap();
+            Err("tool failed")
+        }
+        .await;
+        assert!(result.is_err());
+        while client.is_vm_suspended() {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dispose_resumes_leaked_suspensions() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x8], &[], &[])
+            .command_reply(3, [0x1, 0x9], &[], &[])
+            .command_reply(4, [0x1, 0x6], &[], &[])
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.set_resume_on_shutdown(true);
+
+        client.vm_suspend().await.unwrap();
+        client.vm_dispose().await.unwrap();
+        assert!(!client.is_vm_suspended());
+    }
+
+    #[tokio::test]
+    async fn test_drop_resumes_leaked_suspensions() {
+        let (stream, mut vm) = tokio::io::duplex(1024);
+        let fake_vm = tokio::spawn(async move {
+            let mut handshake = [0u8; 14];
+            vm.read_exact(&mut handshake).await.unwrap();
+            vm.write_all(&handshake).await.unwrap();
+
+            let mut id_sizes = [0u8; 11];
+            vm.read_exact(&mut id_sizes).await.unwrap();
+            let sizes: Vec<u8> = [8i32; 5].iter().flat_map(|s| s.to_be_bytes()).collect();
+            vm.write_all(&reply_packet(1, 0, &sizes)).await.unwrap();
+
+            let mut suspend = [0u8; 11];
+            vm.read_exact(&mut suspend).await.unwrap();
+            vm.write_all(&reply_packet(2, 0, &[])).await.unwrap();
+            let mut thread_suspend = [0u8; 19];
+            vm.read_exact(&mut thread_suspend).await.unwrap();
+            vm.write_all(&reply_packet(3, 0, &[])).await.unwrap();
+
+            let mut rest = Vec::new();
+            vm.read_to_end(&mut rest).await.unwrap();
+            rest
+        });
+
+        let client = JdwpClient::new(stream).await.unwrap();
+        client.set_resume_on_shutdown(true);
+        client.vm_suspend().await.unwrap();
+        client
+            .thread_suspend(VariableLengthId { value: 5 })
+            .await
+            .unwrap();
+        drop(client);
+
+        let mut expected = command_packet(4, [0x1, 0x9], &[]);
+        expected.extend_from_slice(&command_packet(5, [0xb, 0x3], &id(5)));
+        assert_eq!(fake_vm.await.unwrap(), expected);
+    }
+
+    #[tokio::test]
+    async fn test_resume_barrier() {
+        let mock_stream = MockStreamBuilder::default()
+            .command_reply(2, [0x1, 0x8], &[], &[])
+            .command_reply(3, [0x1, 0x8], &[], &[])
+            .command_reply(4, [0x1, 0x9], &[], &[])
+            .build();
+        let client = JdwpClient::new(mock_stream).await.unwrap();
+        client.vm_suspend().await.unwrap();
+        client.vm_suspend().await.unwrap();
+
+        let before = client.suspend_epoch();
+        let after = client.resume_barrier().await.unwrap();
+        assert!(client.is_vm_suspended());
+        assert!(!before.is_current(&client));
+        assert!(after.is_current(&client));
     }
 }